@@ -94,6 +94,16 @@ pub struct CasStoreConfig {
     /// This store name referenced here may be reused multiple times.
     #[serde(deserialize_with = "convert_string_with_shellexpand")]
     pub cas_store: StoreRefName,
+
+    /// Number of seconds blobs reported as present by `FindMissingBlobs`
+    /// are protected from eviction. Clients like Bazel assume blobs they
+    /// were told exist will still exist for the rest of the build and fail
+    /// otherwise. Requires `max_pinned_bytes` to be set in the eviction
+    /// policy of the stores behind `cas_store`.
+    ///
+    /// Default: 0 (blobs are not pinned)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub pin_found_blobs_seconds: u64,
 }
 
 #[derive(Deserialize, Debug, Default)]
@@ -127,6 +137,16 @@ pub struct ExecutionConfig {
     pub scheduler: SchedulerRefName,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct OperationsConfig {
+    /// The scheduler name referenced in the `schedulers` map in the main config.
+    /// This should usually be the same scheduler used by the `execution`
+    /// service for this instance.
+    #[serde(deserialize_with = "convert_string_with_shellexpand")]
+    pub scheduler: SchedulerRefName,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct ByteStreamConfig {
@@ -149,6 +169,17 @@ pub struct ByteStreamConfig {
     /// Defaults: 10 (seconds)
     #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
     pub persist_stream_on_disconnect_timeout: usize,
+
+    /// Schedulers whose running actions can have their stdout and stderr
+    /// read while they execute. The key is the instance name and the value
+    /// is a scheduler in the "schedulers" configuration. Clients find the
+    /// resource names in `stdout_stream_name` and `stderr_stream_name` of the
+    /// operation metadata, which the execution and operations services of the
+    /// same server only set for these instances.
+    ///
+    /// Default: {} (Live output of actions is not served)
+    #[serde(default)]
+    pub action_output_schedulers: HashMap<InstanceName, SchedulerRefName>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -171,6 +202,34 @@ pub struct PrometheusConfig {
     pub path: String,
 }
 
+/// The admin API serves these endpoints, where `{scheduler}` is the name of a
+/// scheduler in the `schedulers` config and `{action_name}` is an action name
+/// as listed by `GET .../actions`:
+///  * `GET /scheduler/{scheduler}/workers` - Workers with their platform
+///    properties, running actions and last keep alive timestamp (JSON).
+///  * `POST /scheduler/{scheduler}/evict_worker/{worker_id}` - Removes a worker
+///    from the pool, its running actions are requeued.
+///  * `POST /scheduler/{scheduler}/set_drain_worker/{worker_id}/{0|1}` - Stops
+///    or resumes scheduling actions on a worker.
+///  * `GET /scheduler/{scheduler}/actions` - Queued and executing actions with
+///    their priority, age and assigned worker (JSON).
+///  * `POST /scheduler/{scheduler}/cancel_action/{action_name}` - Withdraws
+///    one client from a queued or executing action, which is cancelled once no
+///    client that submitted it is waiting on it.
+///  * `POST /scheduler/{scheduler}/set_action_priority/{priority}/{action_name}` -
+///    Changes the priority of a queued action.
+///  * `GET /scheduler/{scheduler}/completed_actions` - Recently completed
+///    actions (JSON).
+///  * `POST /stores/{store}/pin/{ttl_seconds}` - Pins the digests in the body,
+///    one `{hash}/{size}` per line, and reports how many were pinned.
+///  * `GET /stores/{store}/quota` - Used and reserved bytes and the quotas of
+///    a `quota` store (JSON).
+///  * `POST /reload_config` - Re-reads the config file and applies the
+///    changes, like on `SIGHUP`. See `reload::ConfigChanges` for the changes
+///    that can be applied without a restart. Responds with `409 Conflict`
+///    and applies nothing if a change needs a restart.
+///
+/// On servers with `auth`, only `AuthConfig::admin_principals` may use it.
 #[derive(Deserialize, Debug, Default)]
 #[serde(deny_unknown_fields)]
 pub struct AdminConfig {
@@ -183,6 +242,57 @@ pub struct AdminConfig {
     pub path: String,
 }
 
+/// A web page showing the queue depth per platform property set, the
+/// workers, recently completed actions, action cache hit rates and the health
+/// of the stores and schedulers. The page polls `{path}/state.json`, which
+/// returns the same data as JSON. On servers with `auth`, only
+/// `AuthConfig::admin_principals` may view it.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct DashboardConfig {
+    /// Path to serve the dashboard on. If path is "/dashboard", and your
+    /// domain is "example.com", you can reach the dashboard with:
+    /// <http://example.com/dashboard>.
+    ///
+    /// Default: "/dashboard"
+    #[serde(default)]
+    pub path: String,
+}
+
+/// Serves web pages for the blobs in the CAS and action cache with the same
+/// URLs as `bb_browser`, so links made from
+/// `UploadActionResultConfig::success_message_template` and
+/// `failure_message_template` can be opened. Pages are served at
+/// `{path}/{instance_name}/blobs/{digest_function}/{type}/{hash}-{size}/` where
+/// type is one of `action`, `command`, `directory`, `tree`,
+/// `historical_execute_response` or `file` (followed by the file name).
+/// On servers with `auth`, principals need `read_only` permission on the
+/// instance.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct BrowserConfig {
+    /// Path to serve the browser on. If path is "/browser", and your domain
+    /// is "example.com", a message template compatible with `bb_browser`
+    /// would be:
+    /// <https://example.com/browser/my-instance-name-here/blobs/{digest_function}/action/{action_digest_hash}-{action_digest_size}/>
+    ///
+    /// Default: "/browser"
+    #[serde(default)]
+    pub path: String,
+
+    /// The CAS store of each instance name. The key is the instance_name and
+    /// the value is a store in the "stores" configuration.
+    pub cas_stores: HashMap<InstanceName, StoreRefName>,
+
+    /// The action cache store of each instance name, used to show the cached
+    /// result of an action. The key is the instance_name and the value is a
+    /// store in the "stores" configuration.
+    ///
+    /// Default: {} (Cached results are not shown)
+    #[serde(default)]
+    pub ac_stores: HashMap<InstanceName, StoreRefName>,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct ServicesConfig {
@@ -206,6 +316,11 @@ pub struct ServicesConfig {
     /// place holder.
     pub execution: Option<HashMap<InstanceName, ExecutionConfig>>,
 
+    /// The google.longrunning Operations service used to list, inspect and
+    /// cancel the operations handed out by the execution service.
+    /// The key is the instance_name the operations belong to.
+    pub operations: Option<HashMap<InstanceName, OperationsConfig>>,
+
     /// This is the service used to stream data to and from the CAS.
     /// Bazel's protocol strongly encourages users to use this streaming
     /// interface to interact with the CAS when the data is large.
@@ -227,6 +342,23 @@ pub struct ServicesConfig {
     /// This is the service for any administrative tasks.
     /// It provides a REST API endpoint for administrative purposes.
     pub admin: Option<AdminConfig>,
+
+    /// Serves a web dashboard of the cluster state.
+    pub dashboard: Option<DashboardConfig>,
+
+    /// Serves web pages for actions and blobs that `bb_browser` links in
+    /// execute response messages point to.
+    pub browser: Option<BrowserConfig>,
+
+    /// Serve the gRPC server reflection service so tools like grpcurl can
+    /// discover the services on this listener without the proto files.
+    /// Note: The `grpc.health.v1.Health` service is always served and
+    /// reports the health of the stores and schedulers each configured
+    /// service uses.
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub reflection: bool,
 }
 
 #[derive(Deserialize, Debug)]
@@ -355,6 +487,100 @@ pub struct HttpListener {
     pub tls: Option<TlsConfig>,
 }
 
+/// Rights granted to a principal on an instance. Each permission includes
+/// the rights of the permissions before it.
+#[allow(non_camel_case_types)]
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AuthPermission {
+    /// May read from the CAS and AC and query the capabilities.
+    read_only,
+    /// May also upload to the CAS and AC.
+    read_write,
+    /// May also execute actions.
+    execute,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct JwtAuthConfig {
+    /// Path to a JSON Web Key Set file with the keys tokens may be signed
+    /// with. RSA (RS256) and P-256 (ES256) keys are supported. Tokens are
+    /// sent in the `authorization: Bearer <token>` header.
+    #[serde(deserialize_with = "convert_string_with_shellexpand")]
+    pub jwks_file: String,
+
+    /// If set, the `iss` claim of tokens must be equal to this value.
+    ///
+    /// Default: None
+    #[serde(default, deserialize_with = "convert_optional_string_with_shellexpand")]
+    pub issuer: Option<String>,
+
+    /// If set, the `aud` claim of tokens must contain this value.
+    ///
+    /// Default: None
+    #[serde(default, deserialize_with = "convert_optional_string_with_shellexpand")]
+    pub audience: Option<String>,
+
+    /// Claim holding the name of the principal.
+    ///
+    /// Default: "sub"
+    #[serde(default)]
+    pub principal_claim: String,
+}
+
+/// Authentication and authorization of the gRPC services, admin API,
+/// dashboard and browser of a server.
+/// Requests are authenticated with the first credential found, in order: a
+/// static token, a JWT, then the client certificate. Requests without valid
+/// credentials are rejected with `Unauthenticated` and requests for
+/// instances the principal has no rights on with `PermissionDenied`.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Path to a JSON file mapping static bearer tokens or API keys to the
+    /// name of their principal, ie: `{"secret-token": "ci"}`. Tokens are
+    /// sent in the `authorization: Bearer <token>` or `x-api-key` header.
+    ///
+    /// Default: None
+    #[serde(default, deserialize_with = "convert_optional_string_with_shellexpand")]
+    pub tokens_file: Option<String>,
+
+    /// Validation of JSON Web Tokens.
+    ///
+    /// Default: None
+    #[serde(default)]
+    pub jwt: Option<JwtAuthConfig>,
+
+    /// Maps the common name of client certificates to the name of their
+    /// principal. Requires `tls.client_ca_file` to be set on the listener,
+    /// so clients present a certificate.
+    ///
+    /// Default: {}
+    #[serde(default)]
+    pub mtls_subjects: HashMap<String, String>,
+
+    /// Rights of principals on each instance. The key is the instance_name
+    /// used in the protocol and the value maps principal names to their
+    /// permission. The principal "*" matches every authenticated principal.
+    /// Principals have no rights on instances that are not listed.
+    ///
+    /// Default: {}
+    #[serde(default)]
+    pub instances: HashMap<InstanceName, HashMap<String, AuthPermission>>,
+
+    /// Principals allowed to use the worker API.
+    ///
+    /// Default: []
+    #[serde(default)]
+    pub worker_principals: Vec<String>,
+
+    /// Principals allowed to use the admin API and view the dashboard.
+    ///
+    /// Default: []
+    #[serde(default)]
+    pub admin_principals: Vec<String>,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct ServerConfig {
@@ -370,6 +596,13 @@ pub struct ServerConfig {
 
     /// Services to attach to server.
     pub services: Option<ServicesConfig>,
+
+    /// Authentication and authorization of the gRPC services, admin API,
+    /// dashboard and browser. If not set, every request is allowed.
+    ///
+    /// Default: None
+    #[serde(default)]
+    pub auth: Option<AuthConfig>,
 }
 
 #[allow(non_camel_case_types)]
@@ -548,6 +781,15 @@ pub struct LocalWorkerConfig {
     #[serde(default)]
     pub timeout_handled_externally: bool,
 
+    /// When an action is killed, because it timed out or the scheduler asked
+    /// for it to be stopped, its processes are sent SIGTERM and then SIGKILL
+    /// once this many seconds have passed. Actions are run in their own
+    /// process group so every process they started is killed.
+    ///
+    /// Default: 10 (seconds)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub kill_grace_period_s: u32,
+
     /// The command to execute on every execution request. This will be parsed as
     /// a command + arguments (not shell).
     /// Example: "run.sh" and a job with command: "sleep 5" will result in a
@@ -607,7 +849,7 @@ pub enum WorkerConfig {
     local(LocalWorkerConfig),
 }
 
-#[derive(Deserialize, Debug, Clone, Copy)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct GlobalConfig {
     /// Maximum number of open files that can be opened at one time.
@@ -665,6 +907,15 @@ pub struct GlobalConfig {
     /// Default: 1024*1024 (1MiB)
     #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
     pub default_digest_size_health_check: usize,
+
+    /// Filter of the log messages to print, in the format of the `RUST_LOG`
+    /// environment variable, for example "warn,nativelink_scheduler=info".
+    /// Takes precedence over `RUST_LOG`. Changes are applied when the config
+    /// is reloaded.
+    ///
+    /// Default: The value of `RUST_LOG`, or "warn" if it is not set.
+    #[serde(default)]
+    pub log_level: String,
 }
 
 #[derive(Deserialize, Debug)]
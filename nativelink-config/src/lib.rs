@@ -13,6 +13,8 @@
 // limitations under the License.
 
 pub mod cas_server;
+pub mod reload;
 pub mod schedulers;
 mod serde_utils;
 pub mod stores;
+pub mod validate;
@@ -0,0 +1,239 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeSet;
+
+use serde_json::{Map, Value};
+
+/// Changes between the config a process is running with and a reloaded
+/// config. The configs are compared as the JSON values of the config files,
+/// so a change of a value that is expanded from an environment variable is
+/// not seen.
+///
+/// The following changes can be applied without a restart:
+///  * Adding stores, schedulers and servers.
+///  * Changing the `eviction_policy` of `memory` and `filesystem` stores.
+///  * Changing the `supported_platform_properties` of `simple` schedulers.
+///  * Changing `GlobalConfig::log_level`.
+///
+/// Any other change, including removing a store, scheduler or server, needs
+/// a restart. Running servers are never changed, so adding an instance to a
+/// service of a running server needs a restart too. The instance can be
+/// served without a restart by adding a new server, with a different
+/// `listen_address`, that has the instance.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ConfigChanges {
+    /// Names of the stores that were added.
+    pub new_stores: Vec<String>,
+    /// Names of the stores of which only the `eviction_policy` changed.
+    pub eviction_policy_changes: Vec<String>,
+    /// Names of the schedulers that were added.
+    pub new_schedulers: Vec<String>,
+    /// Names of the schedulers of which only the
+    /// `supported_platform_properties` changed.
+    pub platform_property_changes: Vec<String>,
+    /// Indexes of the servers that were added, in the reloaded config.
+    pub new_servers: Vec<usize>,
+    /// Whether `GlobalConfig::log_level` changed.
+    pub log_level_changed: bool,
+    /// Descriptions of the changes that need a restart.
+    pub restart_required: Vec<String>,
+}
+
+/// Returns the entries of an object, a missing or null value is treated as
+/// an empty object.
+fn entries(value: Option<&Value>) -> Map<String, Value> {
+    match value {
+        Some(Value::Object(entries)) => entries.clone(),
+        _ => Map::new(),
+    }
+}
+
+/// Returns the config of an enum value like `{"memory": {...}}` without
+/// `field`, if the variant is one of `variants`.
+fn without_field(value: &Value, variants: &[&str], field: &str) -> Option<(String, Value)> {
+    let Value::Object(object) = value else {
+        return None;
+    };
+    let (variant, config) = object.iter().next()?;
+    if object.len() != 1 || !variants.contains(&variant.as_str()) {
+        return None;
+    }
+    let mut config = config.as_object()?.clone();
+    config.remove(field);
+    Some((variant.clone(), Value::Object(config)))
+}
+
+/// Whether `running` and `reloaded` only differ in `field`.
+fn only_field_changed(running: &Value, reloaded: &Value, variants: &[&str], field: &str) -> bool {
+    let running = without_field(running, variants, field);
+    running.is_some() && running == without_field(reloaded, variants, field)
+}
+
+impl ConfigChanges {
+    /// Finds the changes between the JSON values of the config files.
+    pub fn new(running: &Value, reloaded: &Value) -> Self {
+        let mut changes = Self::default();
+
+        let running_stores = entries(running.get("stores"));
+        let reloaded_stores = entries(reloaded.get("stores"));
+        for (name, store) in &reloaded_stores {
+            match running_stores.get(name) {
+                None => changes.new_stores.push(name.clone()),
+                Some(running_store) if running_store == store => {}
+                Some(running_store)
+                    if only_field_changed(
+                        running_store,
+                        store,
+                        &["memory", "filesystem"],
+                        "eviction_policy",
+                    ) =>
+                {
+                    changes.eviction_policy_changes.push(name.clone());
+                }
+                Some(_) => changes
+                    .restart_required
+                    .push(format!("store '{name}' changed")),
+            }
+        }
+
+        let running_schedulers = entries(running.get("schedulers"));
+        let reloaded_schedulers = entries(reloaded.get("schedulers"));
+        for (name, scheduler) in &reloaded_schedulers {
+            match running_schedulers.get(name) {
+                None => changes.new_schedulers.push(name.clone()),
+                Some(running_scheduler) if running_scheduler == scheduler => {}
+                Some(running_scheduler)
+                    if only_field_changed(
+                        running_scheduler,
+                        scheduler,
+                        &["simple"],
+                        "supported_platform_properties",
+                    ) =>
+                {
+                    changes.platform_property_changes.push(name.clone());
+                }
+                Some(_) => changes
+                    .restart_required
+                    .push(format!("scheduler '{name}' changed")),
+            }
+        }
+
+        for name in running_stores.keys() {
+            if !reloaded_stores.contains_key(name) {
+                changes
+                    .restart_required
+                    .push(format!("store '{name}' was removed"));
+            }
+        }
+        for name in running_schedulers.keys() {
+            if !reloaded_schedulers.contains_key(name) {
+                changes
+                    .restart_required
+                    .push(format!("scheduler '{name}' was removed"));
+            }
+        }
+
+        let no_servers = Vec::new();
+        let running_servers = running
+            .get("servers")
+            .and_then(Value::as_array)
+            .unwrap_or(&no_servers);
+        let reloaded_servers = reloaded
+            .get("servers")
+            .and_then(Value::as_array)
+            .unwrap_or(&no_servers);
+        for (i, server) in reloaded_servers.iter().enumerate() {
+            if !running_servers.contains(server) {
+                changes.new_servers.push(i);
+            }
+        }
+        for (i, server) in running_servers.iter().enumerate() {
+            if !reloaded_servers.contains(server) {
+                let name = server
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .filter(|name| !name.is_empty())
+                    .map_or_else(|| format!("{i}"), str::to_string);
+                changes.restart_required.push(format!(
+                    "server '{name}' was changed or removed (running servers can't get new \
+                     instances, add them to a new server instead)"
+                ));
+            }
+        }
+
+        let mut running_global = entries(running.get("global"));
+        let mut reloaded_global = entries(reloaded.get("global"));
+        changes.log_level_changed =
+            running_global.remove("log_level") != reloaded_global.remove("log_level");
+        if running_global != reloaded_global {
+            changes.restart_required.push("global changed".to_string());
+        }
+
+        // Any other top level field, like the workers.
+        let fields: BTreeSet<&String> = [running, reloaded]
+            .into_iter()
+            .filter_map(Value::as_object)
+            .flat_map(Map::keys)
+            .filter(|field| {
+                !matches!(
+                    field.as_str(),
+                    "stores" | "schedulers" | "servers" | "global"
+                )
+            })
+            .collect();
+        for field in fields {
+            if running.get(field) != reloaded.get(field) {
+                changes.restart_required.push(format!("{field} changed"));
+            }
+        }
+
+        changes
+    }
+
+    /// Whether there are no changes at all.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Copies `reloaded[section][name]` to `running` once the change of that
+/// field was applied, so it is not applied again by the next reload if one
+/// of the other changes fails. The field is removed from `running` if it is
+/// not in `reloaded`.
+pub fn record_field_change(running: &mut Value, reloaded: &Value, section: &str, name: &str) {
+    match reloaded.get(section).and_then(|entries| entries.get(name)) {
+        Some(value) => running[section][name] = value.clone(),
+        None => {
+            if let Some(entries) = running.get_mut(section).and_then(Value::as_object_mut) {
+                entries.remove(name);
+            }
+        }
+    }
+}
+
+/// Adds the server at `index` of the servers in `reloaded` to `running` once
+/// it was started, see `record_field_change`.
+pub fn record_new_server(running: &mut Value, reloaded: &Value, index: usize) {
+    let Some(server) = reloaded
+        .get("servers")
+        .and_then(|servers| servers.get(index))
+    else {
+        return;
+    };
+    match running.get_mut("servers").and_then(Value::as_array_mut) {
+        Some(servers) => servers.push(server.clone()),
+        None => running["servers"] = Value::Array(vec![server.clone()]),
+    }
+}
@@ -119,6 +119,31 @@ pub struct SimpleScheduler {
     /// The strategy used to assign workers jobs.
     #[serde(default)]
     pub allocation_strategy: WorkerAllocationStrategy,
+
+    /// Extra time, beyond an action's own timeout, that the scheduler waits
+    /// for the worker to report back before it kills the action on the worker
+    /// and fails it with a deadline exceeded error. Actions without a timeout
+    /// are never killed by the scheduler. This should be larger than any
+    /// delay workers add before they start the action's timer.
+    /// Default: 60 (seconds)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub action_timeout_grace_s: u64,
+
+    /// If set, draining a worker kills the actions it is running and queues
+    /// them again so they run on other workers. Otherwise running actions are
+    /// allowed to finish on the draining worker.
+    /// Default: false
+    #[serde(default)]
+    pub kill_actions_on_drain: bool,
+
+    /// Maximum number of bytes of stdout and of stderr kept for each running
+    /// action so clients can read them while the action runs. Once either
+    /// stream reaches this, the worker stops sending the action's output.
+    /// Output beyond this is not streamed, but is still part of the final
+    /// `ActionResult`.
+    /// Default: 16777216 (16MiB)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_action_output_bytes: u64,
 }
 
 /// A scheduler that simply forwards requests to an upstream scheduler.  This
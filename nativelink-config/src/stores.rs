@@ -106,6 +106,48 @@ pub enum StoreConfig {
     /// Note: This store should only be used on CAS stores.
     existence_cache(Box<ExistenceCacheStore>),
 
+    /// Hedge store wraps around a store with high tail latency (ie: S3 or
+    /// GRPC) and sends a second identical read request when the first
+    /// one has not returned any data after a delay. The delay is computed
+    /// from the time-to-first-byte of recent reads, so only the slowest
+    /// reads get hedged. Whichever request returns data first is used and
+    /// the other one is cancelled.
+    ///
+    /// Note: Only reads are hedged, `has()` and uploads are sent to the
+    /// `backend` store only.
+    hedge(Box<HedgeStore>),
+
+    /// Circuit breaker store wraps around a remote store (ie: S3 or GRPC)
+    /// and stops sending requests to it when too many of them fail or
+    /// are slow. While the circuit is open, requests fail right away with
+    /// an `Unavailable` error instead of waiting for the backend's retries
+    /// to give up. When this store is used as the `slow` store of a
+    /// `fast_slow` store, objects in the `fast` store are still served
+    /// while the circuit is open. Other errors of the `slow` store are
+    /// still returned.
+    ///
+    /// After `open_duration_ms`, a few trial requests are let through. If
+    /// they all succeed the circuit closes again, otherwise it re-opens.
+    /// The state of the circuit is reported in the `/status` health
+    /// output and in the metrics.
+    circuit_breaker(Box<CircuitBreakerStore>),
+
+    /// Quota store limits how much data can be written through it into
+    /// its `backend`. To give every instance its own quota on a shared
+    /// store, configure one quota store per instance with a `ref_store`
+    /// pointing at the shared store as `backend`.
+    ///
+    /// Once the objects written through this store use more than
+    /// `soft_quota_bytes`, the least recently used of them are removed
+    /// from the `backend`, so a single instance can't evict everyone
+    /// else's objects. Uploads that would exceed `hard_quota_bytes` are
+    /// rejected with a `ResourceExhausted` error.
+    ///
+    /// Note: Usage is only tracked in memory. After a restart every quota
+    /// store starts from zero and only counts objects written through it
+    /// from then on.
+    quota(Box<QuotaStore>),
+
     /// FastSlow store will first try to fetch the data from the `fast`
     /// store and then if it does not exist try the `slow` store.
     /// When the object does exist in the `slow` store, it will copy
@@ -122,6 +164,23 @@ pub enum StoreConfig {
     /// store).
     fast_slow(Box<FastSlowStore>),
 
+    /// Tiered store is a generalization of `fast_slow` to any number of
+    /// tiers, ordered from fastest to slowest (ie: memory -> local disk ->
+    /// shared filesystem -> S3). Reads walk down the tiers until the object
+    /// is found. Unlike `fast_slow`, an object found in a slower tier is
+    /// only copied into the faster tiers once it has been read often
+    /// enough, and objects that are too large for a tier are never copied
+    /// into it. This prevents large one-off objects from evicting the
+    /// working set of the faster tiers.
+    ///
+    /// On uploads the data is mirrored to every tier that does not have
+    /// `skip_on_update` set.
+    ///
+    /// Note: Since tiers may skip uploads, `has()` checks the tiers in
+    /// order until the object is found, meaning objects that do not exist
+    /// will be looked up in every tier.
+    tiered(Box<TieredStore>),
+
     /// Shards the data to multiple stores. This is useful for cases
     /// when you want to distribute the load across multiple stores.
     /// The digest hash is used to determine which store to send the
@@ -152,6 +211,20 @@ pub enum StoreConfig {
     /// to use (ie: CAS stores).
     size_partitioning(Box<SizePartitioningStore>),
 
+    /// Sends each digest to the store of the first rule it matches. Rules
+    /// can match on the digest size, digest function, instance name, whether
+    /// the request is an AC or CAS access and the digest's hash prefix. This
+    /// allows sending small AC entries to one store, small blobs to memory
+    /// and everything else to S3 without nesting many stores.
+    ///
+    /// The instance name, digest function and access kind come from the
+    /// client request being served. When the store is used outside of a
+    /// client request (ie: as a worker's local store) rules that match on
+    /// instance names or access kind never match and the default digest
+    /// function is assumed, so make sure such stores route digests the same
+    /// way or reads may not find what was written.
+    routing(Box<RoutingStore>),
+
     /// This store will pass-through calls to another GRPC store. This store
     /// is not designed to be used as a sub-store of another store, but it
     /// does satisfy the interface and will likely work.
@@ -206,6 +279,74 @@ pub struct SizePartitioningStore {
     pub upper_store: StoreConfig,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RoutingStore {
+    /// Rules to check in order. The first rule that matches a digest picks
+    /// the store it is sent to.
+    pub rules: Vec<RoutingRule>,
+
+    /// Store to send data when no rule matches.
+    pub default_store: StoreConfig,
+}
+
+/// Kind of client request a routing rule matches.
+#[allow(non_camel_case_types)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoutingAccess {
+    /// Requests to the action cache.
+    ac,
+
+    /// Requests to the content addressable storage.
+    cas,
+}
+
+/// A rule of a routing store. A digest matches the rule if it matches every
+/// condition that is set.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RoutingRule {
+    /// Store to send data when this rule matches.
+    pub store: StoreConfig,
+
+    /// Match digests whose size is >= (greater than eq) this value.
+    ///
+    /// Default: 0
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub min_size: u64,
+
+    /// Match digests whose size is < (less than) this value.
+    ///
+    /// Default: 0 (no limit)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_size: u64,
+
+    /// Match requests using one of these digest functions.
+    ///
+    /// Default: [] (any digest function)
+    #[serde(default)]
+    pub digest_functions: Vec<ConfigDigestHashFunction>,
+
+    /// Match requests for one of these instance names.
+    ///
+    /// Default: [] (any instance name)
+    #[serde(default)]
+    pub instance_names: Vec<String>,
+
+    /// Match only AC or only CAS requests.
+    ///
+    /// Default: None (both)
+    #[serde(default)]
+    pub access: Option<RoutingAccess>,
+
+    /// Match digests whose lowercase hex hash starts with one of these
+    /// prefixes.
+    ///
+    /// Default: [] (any hash)
+    #[serde(default)]
+    pub digest_prefixes: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct RefStore {
@@ -264,6 +405,59 @@ pub struct FastSlowStore {
     pub slow: StoreConfig,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct StoreTier {
+    /// The store backing this tier.
+    pub store: StoreConfig,
+
+    /// Number of times an object must be read from a slower tier before
+    /// it is copied into this tier. Values of 0 and 1 both mean the object
+    /// is copied on the first read, which is how `fast_slow` behaves.
+    ///
+    /// Default: 0
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub promotion_hit_count: u32,
+
+    /// Objects larger than this many bytes are never copied into this tier
+    /// from a slower tier. Zero means there is no limit.
+    ///
+    /// Default: 0
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_promotion_size: u64,
+
+    /// If set, uploads will not be written to this tier. Objects only end
+    /// up in this tier when they are promoted or demoted into it.
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub skip_on_update: bool,
+
+    /// If set, objects evicted from this tier will be written to the next
+    /// slower tier (if they are not already there) instead of being
+    /// dropped. This requires the store of this tier to support eviction
+    /// callbacks, currently only `memory` stores do.
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub demote_on_eviction: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TieredStore {
+    /// The tiers of the store ordered from the fastest to the slowest.
+    /// At least two tiers must be configured.
+    pub tiers: Vec<StoreTier>,
+
+    /// Policy used to evict the read counters used to decide when an
+    /// object should be promoted. Each tracked object uses a small fixed
+    /// amount of memory, so `max_count` is the most useful setting here.
+    /// Failure to set this value will cause counters to never be removed
+    /// causing infinite memory usage.
+    pub hit_counter_eviction_policy: Option<EvictionPolicy>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct MemoryStore {
@@ -271,6 +465,37 @@ pub struct MemoryStore {
     /// value will cause items to never be removed from the store causing
     /// infinite memory usage.
     pub eviction_policy: Option<EvictionPolicy>,
+
+    /// If set, the content of the store is saved to a file on graceful
+    /// shutdown and periodically in the background, and loaded back when
+    /// the store is created. This avoids starting with a cold cache after
+    /// every restart. The store reports itself as initializing until the
+    /// snapshot is loaded.
+    #[serde(default)]
+    pub snapshot: Option<MemoryStoreSnapshot>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MemoryStoreSnapshot {
+    /// Path of the snapshot file. The file is written to a temporary file
+    /// next to it and then renamed, so the directory must be writable.
+    #[serde(deserialize_with = "convert_string_with_shellexpand")]
+    pub path: String,
+
+    /// How often the snapshot is written in the background, in seconds.
+    /// The snapshot is always written on graceful shutdown.
+    ///
+    /// Default: 300 (5 minutes)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub interval_seconds: u32,
+
+    /// Snapshots older than this many seconds are ignored when the store is
+    /// created. Zero means snapshots are loaded no matter how old they are.
+    ///
+    /// Default: 0
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_age_seconds: u32,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -326,6 +551,23 @@ pub struct DedupStore {
     /// Default: 10
     #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
     pub max_concurrent_fetch_per_get: u32,
+
+    /// Keep track of which indexes reference which chunks. When the last
+    /// index referencing a chunk is evicted from the `index_store`, the
+    /// chunk is removed from the `content_store`. When a chunk is evicted
+    /// from the `content_store`, every index referencing it is removed
+    /// as well, so broken indexes are never served.
+    ///
+    /// This requires the `index_store` and `content_store` to report
+    /// evictions (eg: memory stores), otherwise the store fails to start,
+    /// and uses memory proportional to the number of indexes and chunks
+    /// written while the process is running. Chunks that already existed
+    /// when they were first referenced are never removed, since unknown
+    /// indexes might still reference them.
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub track_chunk_references: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -342,6 +584,188 @@ pub struct ExistenceCacheStore {
     /// value will cause items to never be removed from the store causing
     /// infinite memory usage.
     pub eviction_policy: Option<EvictionPolicy>,
+
+    /// Number of seconds a digest the backend reported as missing is
+    /// remembered, so repeated lookups of missing digests don't reach the
+    /// backend. Writes through this store forget the digest right away,
+    /// but writes to the backend from elsewhere are only seen once the
+    /// entry expires.
+    ///
+    /// Default: 0 (missing digests are not cached)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub negative_cache_seconds: u64,
+
+    /// Maximum number of missing digests remembered, see
+    /// `negative_cache_seconds`.
+    ///
+    /// Default: 1000000
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub negative_cache_max_items: u64,
+
+    /// If set, a bloom filter of the digests in the backend is used to
+    /// answer that a digest is missing without asking the backend.
+    #[serde(default)]
+    pub bloom_filter: Option<BloomFilterConfig>,
+}
+
+/// A bloom filter never forgets a digest, so once it is filled from the
+/// backend, the backend must only receive new digests through this store
+/// for lookups to be correct. Digests written to the backend from elsewhere are
+/// reported as missing, which makes clients upload them again.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct BloomFilterConfig {
+    /// Number of digests the bloom filter is sized for. The false positive
+    /// rate grows once more digests than this are added.
+    ///
+    /// Default: 10000000
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub expected_items: u64,
+
+    /// Fraction of missing digests that are wrongly reported as maybe
+    /// existing, and are looked up in the backend. The bloom filter uses
+    /// about 1.2 bytes per expected item at 0.01.
+    ///
+    /// Default: 0.01
+    #[serde(default)]
+    pub false_positive_rate: f64,
+
+    /// The bloom filter is filled with every digest listed by the backend in
+    /// the background, and is not used until this finishes. If set, this
+    /// starts when the store is created, otherwise on the first lookup.
+    /// Requires a backend that can list its items (ie: memory, filesystem or
+    /// S3 stores); with any other backend the filter is never used.
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub preload: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HedgeStore {
+    /// The store that all requests are sent to first.
+    pub backend: StoreConfig,
+
+    /// The store hedged reads are sent to. This store must contain the
+    /// same data as `backend` (ie: a replica in another region). If not
+    /// set, hedged reads are sent to `backend`.
+    #[serde(default)]
+    pub hedge_backend: Option<StoreConfig>,
+
+    /// The percentile of recent time-to-first-byte samples used as the
+    /// delay before a hedged read is sent. For example, 95 means roughly
+    /// 5% of reads will be hedged.
+    ///
+    /// Default: 95
+    #[serde(default)]
+    pub delay_percentile: f32,
+
+    /// Number of recent time-to-first-byte samples to compute the
+    /// percentile from. The percentile is computed again after every 100
+    /// reads.
+    ///
+    /// Default: 1000
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub sample_window: usize,
+
+    /// Lower bound of the delay in milliseconds before a hedged read is
+    /// sent.
+    ///
+    /// Default: 0
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub min_delay_ms: u64,
+
+    /// Upper bound of the delay in milliseconds before a hedged read is
+    /// sent. This delay is also used until enough samples have been
+    /// collected to compute the percentile.
+    ///
+    /// Default: 1000
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_delay_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CircuitBreakerStore {
+    /// The store requests are forwarded to while the circuit is closed.
+    pub backend: StoreConfig,
+
+    /// Percentage of failed requests among the last `window_size`
+    /// requests at which the circuit opens. Only errors caused by the
+    /// backend (`Unavailable`, `DeadlineExceeded`, `Internal`, `Unknown`
+    /// and `ResourceExhausted`) are counted as failures, so clients sending
+    /// invalid requests can't open the circuit.
+    ///
+    /// Default: 50
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub failure_rate_threshold: u32,
+
+    /// Requests that take longer than this many milliseconds are counted
+    /// as failures even if they succeed. The time includes transferring
+    /// the data, so this should be set well above the expected time for
+    /// large objects. Zero disables this check.
+    ///
+    /// Default: 0
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub slow_call_threshold_ms: u64,
+
+    /// Number of most recent requests the failure rate is computed from.
+    ///
+    /// Default: 100
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub window_size: usize,
+
+    /// Minimum number of requests that must be recorded before the
+    /// circuit can open.
+    ///
+    /// Default: 20
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub minimum_calls: usize,
+
+    /// Number of milliseconds the circuit stays open before trial
+    /// requests are let through.
+    ///
+    /// Default: 30000
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub open_duration_ms: u64,
+
+    /// Number of trial requests let through while the circuit is
+    /// half-open. All of them must succeed for the circuit to close.
+    ///
+    /// Default: 5
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub half_open_calls: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct QuotaStore {
+    /// The store objects are written to. This is usually a `ref_store`
+    /// pointing at a store shared with other quota stores. Quota stores
+    /// only know about each other's objects if their `backend` is a
+    /// `ref_store` with the same `name`.
+    pub backend: StoreConfig,
+
+    /// Number of bytes the objects written through this store may use
+    /// before the least recently used of them are removed from the
+    /// `backend`. Requires the `backend` to support removing objects
+    /// (ie: memory or filesystem stores). Objects other quota stores with
+    /// the same `backend` also use are not removed, they only stop counting
+    /// against this store. Zero disables the soft quota.
+    ///
+    /// Default: 0
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub soft_quota_bytes: u64,
+
+    /// Uploads that would make the objects written through this store
+    /// (including uploads in progress) use more than this many bytes are
+    /// rejected with a `ResourceExhausted` error. Zero disables the hard
+    /// quota.
+    ///
+    /// Default: 0
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub hard_quota_bytes: u64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -364,10 +788,32 @@ pub struct VerifyStore {
     /// The digest hash function to hash the contents and to verify if the digest hash is
     /// matching before writing the entry to underlying store.
     ///
-    /// If None, the hash verification will be disabled.
+    /// If None, the hash verification will be disabled. Data of a client
+    /// request is hashed with the digest function of the request instead,
+    /// so this is only the function used outside of requests.
     ///
     /// This should be set to None for AC, but hashing function like `sha256` for CAS stores.
     pub hash_verification_function: Option<ConfigDigestHashFunction>,
+
+    /// If set, reads of whole entries are also verified with `verify_size`
+    /// and `hash_verification_function` while the data is streamed back to
+    /// the reader. The end of the stream is held back until the data is
+    /// verified, and a mismatch terminates the read with a `DataLoss`
+    /// error. Reads of part of an entry are not verified.
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub verify_on_read: bool,
+
+    /// If set, entries that fail verification on read are removed from the
+    /// backend, so the next upload can replace them. A hash mismatch outside
+    /// of a client request never removes the entry, as the digest may have
+    /// been made with another function. Requires a backend that can remove
+    /// items (ie: memory or filesystem stores).
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub remove_corrupt_on_read: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -379,6 +825,26 @@ pub struct CompletenessCheckingStore {
     /// When a request is made, the results are decoded and all output digests/files are verified
     /// to exist in this CAS store before returning success.
     pub cas_store: StoreConfig,
+
+    /// Number of seconds the outputs found in `cas_store` are protected
+    /// from eviction, so clients that got the action result can still
+    /// download them (eg: Bazel's `--remote_download_minimal`). Requires
+    /// `max_pinned_bytes` to be set in the eviction policy of the stores
+    /// behind `cas_store`.
+    ///
+    /// Default: 0 (outputs are not pinned)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub pin_outputs_seconds: u64,
+
+    /// If set, the outputs found in `cas_store` are marked as just used in
+    /// every store behind `cas_store` (eg: the eviction timestamps of
+    /// memory and filesystem stores, or the last modified time of S3
+    /// objects used by bucket lifecycle rules). Without this, an action
+    /// result that keeps being requested may outlive its outputs.
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub refresh_outputs: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone, Copy)]
@@ -459,6 +925,16 @@ pub struct EvictionPolicy {
     /// Default: 0. Zero means never evict based on count.
     #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
     pub max_count: u64,
+
+    /// Maximum number of bytes of items that may be pinned at the same
+    /// time. Pinned items are skipped by the eviction policy until their
+    /// pin expires, so the store may grow up to this many bytes beyond the
+    /// other limits. Items are pinned when clients are told they exist
+    /// (see `pin_found_blobs_seconds` in the CAS config) or through the
+    /// admin API. Pins that would exceed this budget are ignored.
+    /// Default: 0. Zero means items are never pinned.
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub max_pinned_bytes: usize,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
@@ -500,6 +976,53 @@ pub struct S3Store {
     /// Default: false
     #[serde(default)]
     pub disable_http2: bool,
+
+    /// Number of hex characters of the digest hash that object keys are
+    /// grouped on when listing the bucket with ListObjectsV2. Listing every
+    /// object (ie: to preload an existence cache) lists each group
+    /// separately, and batch existence checks only list the groups that
+    /// hold a requested digest. At most 4.
+    ///
+    /// Default: 2 (256 groups)
+    #[serde(default)]
+    pub list_prefix_length: usize,
+
+    /// If existence is checked for at least this many digests at once, the
+    /// key groups of the digests are listed instead of sending one HEAD
+    /// request per digest. A list request returns up to 1000 keys, so this
+    /// is faster when the bucket holds few objects per key group compared
+    /// to the number of digests checked (ie: FindMissingBlobs on large
+    /// trees). A key group is listed with at most as many requests as
+    /// digests are checked in it, if it holds more objects than that the
+    /// digests are checked with HEAD requests instead. So this sends at
+    /// most twice as many requests as HEAD requests would, and the number
+    /// of requests grows with the number of objects in the bucket until
+    /// that limit is reached.
+    ///
+    /// Default: 0 (always use HEAD requests)
+    #[serde(default)]
+    pub list_batch_threshold: usize,
+
+    /// If set, objects that are read and were last modified more than this
+    /// many seconds ago are copied onto themselves in the background,
+    /// keeping their metadata. This resets their last modified time, so
+    /// bucket lifecycle rules that expire old objects approximate LRU
+    /// eviction. Objects larger than 5GB are not refreshed. Each object is
+    /// refreshed at most once per `touch_interval_seconds`, and only a few
+    /// objects are refreshed at a time; reads beyond that skip the refresh.
+    ///
+    /// Default: 0 (objects are not refreshed on read)
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub touch_on_read_seconds: u64,
+
+    /// Objects touched to keep them around (ie: the outputs of Action Cache
+    /// hits) are copied onto themselves at most once per this many seconds.
+    /// Objects modified more recently are not copied, and objects this store
+    /// touched more recently are not checked again.
+    ///
+    /// Default: 3600
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub touch_interval_seconds: u64,
 }
 
 #[allow(non_camel_case_types)]
@@ -560,6 +1083,80 @@ pub struct GrpcStore {
     /// request is queued.
     #[serde(default)]
     pub max_concurrent_requests: usize,
+
+    /// How requests are spread across `endpoints`. If not set, the GRPC
+    /// client balances requests over all endpoints on a single channel and
+    /// sick endpoints are not detected. When set, ByteStream uploads are
+    /// always sent to the same endpoint (including retries and
+    /// `QueryWriteStatus` calls), so they can be resumed.
+    #[serde(default)]
+    pub load_balancing: Option<GrpcLoadBalancing>,
+}
+
+/// Strategy used to pick the endpoint a request is sent to.
+#[allow(non_camel_case_types)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LoadBalancingStrategy {
+    /// Send requests to each endpoint in turn.
+    #[default]
+    round_robin,
+
+    /// Send requests to the endpoint with the fewest requests in flight.
+    least_outstanding_requests,
+
+    /// Pick two random endpoints and send the request to the one with the
+    /// fewest requests in flight. This performs close to
+    /// `least_outstanding_requests` while avoiding every request rushing to
+    /// the same endpoint.
+    power_of_two_choices,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct GrpcLoadBalancing {
+    /// Strategy used to pick the endpoint of each request.
+    ///
+    /// Default: round_robin
+    #[serde(default)]
+    pub strategy: LoadBalancingStrategy,
+
+    /// Number of connections opened to each endpoint. Requests sent to an
+    /// endpoint are spread across its connections.
+    ///
+    /// Default: 1
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub connections_per_endpoint: usize,
+
+    /// If set, endpoints are periodically checked and no requests are sent
+    /// to endpoints that fail the check. If every endpoint is unhealthy,
+    /// requests are sent to all of them.
+    #[serde(default)]
+    pub health_check: Option<GrpcHealthCheck>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct GrpcHealthCheck {
+    /// Number of milliseconds between two checks of an endpoint. A check
+    /// opens a new connection to the endpoint.
+    ///
+    /// Default: 5000
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub interval_ms: u64,
+
+    /// Number of milliseconds after which a check is considered failed.
+    ///
+    /// Default: 1000
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub timeout_ms: u64,
+
+    /// Number of consecutive failed checks or `Unavailable` request errors
+    /// after which an endpoint is considered unhealthy. A single successful
+    /// check makes the endpoint healthy again.
+    ///
+    /// Default: 3
+    #[serde(default, deserialize_with = "convert_numeric_with_shellexpand")]
+    pub unhealthy_threshold: u32,
 }
 
 /// The possible error codes that might occur on an upstream request.
@@ -0,0 +1,730 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use crate::cas_server::{CasConfig, ListenerConfig, ServicesConfig, WorkerConfig};
+use crate::schedulers::SchedulerConfig;
+use crate::stores::{EvictionPolicy, StoreConfig, StoreRefName};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The config fails at startup or when the setting is first used.
+    Error,
+    /// The config works, but the setting is likely a mistake.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => f.write_str("error"),
+            Severity::Warning => f.write_str("warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Location of a value in the config, ie: `stores.CAS.fast_slow.fast`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigPath(pub Vec<PathSegment>);
+
+impl ConfigPath {
+    fn key(&self, key: &str) -> Self {
+        let mut path = self.clone();
+        path.0.push(PathSegment::Key(key.to_string()));
+        path
+    }
+
+    fn index(&self, index: usize) -> Self {
+        let mut path = self.clone();
+        path.0.push(PathSegment::Index(index));
+        path
+    }
+
+    /// Returns the line number, starting at 1, of the value in the JSON5
+    /// `contents` of the config file.
+    pub fn find_line(&self, contents: &str) -> Option<usize> {
+        let mut scanner = Scanner {
+            bytes: contents.as_bytes(),
+            pos: 0,
+        };
+        let pos = scanner.find(&self.0)?;
+        Some(
+            contents.as_bytes()[..pos]
+                .iter()
+                .filter(|b| **b == b'\n')
+                .count()
+                + 1,
+        )
+    }
+}
+
+impl fmt::Display for ConfigPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key)
+                    if !key.is_empty()
+                        && key.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_') =>
+                {
+                    if i > 0 {
+                        f.write_str(".")?;
+                    }
+                    f.write_str(key)?;
+                }
+                PathSegment::Key(key) => write!(f, "[{key:?}]")?,
+                PathSegment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A semantic error or risky setting in the config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub path: ConfigPath,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.severity, self.path, self.message)
+    }
+}
+
+/// Checks the references between the stores, schedulers, servers and
+/// workers of the config and looks for settings that are likely mistakes.
+/// Nothing is opened or connected to. Findings are in the order of the
+/// config fields, maps are visited in the order of their keys.
+pub fn validate(config: &CasConfig) -> Vec<Finding> {
+    let mut validator = Validator {
+        config,
+        findings: Vec::new(),
+    };
+    validator.check_stores();
+    validator.check_schedulers();
+    validator.check_servers();
+    validator.check_workers();
+    validator.findings
+}
+
+/// Returns the entries of `map` ordered by key.
+fn sorted<V>(map: &HashMap<String, V>) -> BTreeMap<&String, &V> {
+    map.iter().collect()
+}
+
+fn scheduler_kind(scheduler: &SchedulerConfig) -> &'static str {
+    match scheduler {
+        SchedulerConfig::simple(_) => "simple",
+        SchedulerConfig::grpc(_) => "grpc",
+        SchedulerConfig::cache_lookup(_) => "cache_lookup",
+        SchedulerConfig::property_modifier(_) => "property_modifier",
+    }
+}
+
+/// Whether workers can connect to the scheduler, which needs a `simple`
+/// scheduler at the bottom of the nested schedulers.
+fn accepts_workers(scheduler: &SchedulerConfig) -> bool {
+    match scheduler {
+        SchedulerConfig::simple(_) => true,
+        SchedulerConfig::grpc(_) => false,
+        SchedulerConfig::cache_lookup(config) => accepts_workers(&config.scheduler),
+        SchedulerConfig::property_modifier(config) => accepts_workers(&config.scheduler),
+    }
+}
+
+fn is_unbounded(eviction_policy: Option<&EvictionPolicy>) -> bool {
+    eviction_policy.map_or(true, |policy| {
+        policy.max_bytes == 0 && policy.max_count == 0 && policy.max_seconds == 0
+    })
+}
+
+/// Follows `ref_store`s to the store they point to. Returns `None` for
+/// missing stores and cycles, which are reported with the stores.
+fn resolve<'a>(
+    stores: &'a HashMap<StoreRefName, StoreConfig>,
+    mut store: &'a StoreConfig,
+) -> Option<&'a StoreConfig> {
+    let mut seen = HashSet::new();
+    while let StoreConfig::ref_store(config) = store {
+        if !seen.insert(&config.name) {
+            return None;
+        }
+        store = stores.get(&config.name)?;
+    }
+    Some(store)
+}
+
+/// Whether the store delivers eviction callbacks, like `dedup` stores with
+/// `track_chunk_references` need. Routing stores deliver them when all of
+/// their stores are memory stores. Missing stores and cycles are reported
+/// with the stores, so they are not reported again.
+fn reports_evictions(stores: &HashMap<StoreRefName, StoreConfig>, store: &StoreConfig) -> bool {
+    match resolve(stores, store) {
+        None | Some(StoreConfig::memory(_)) => true,
+        Some(StoreConfig::routing(config)) => config
+            .rules
+            .iter()
+            .map(|rule| &rule.store)
+            .chain([&config.default_store])
+            .all(|store| matches!(resolve(stores, store), None | Some(StoreConfig::memory(_)))),
+        Some(_) => false,
+    }
+}
+
+/// Appends the cycles of `ref_store`s reachable from `name` to `cycles`.
+fn find_cycles<'a>(
+    name: &'a str,
+    edges: &'a BTreeMap<&str, Vec<String>>,
+    done: &mut HashSet<&'a str>,
+    stack: &mut Vec<&'a str>,
+    cycles: &mut Vec<Vec<&'a str>>,
+) {
+    if done.contains(name) {
+        return;
+    }
+    if let Some(start) = stack.iter().position(|visiting| *visiting == name) {
+        let mut cycle = stack[start..].to_vec();
+        cycle.push(name);
+        cycles.push(cycle);
+        return;
+    }
+    stack.push(name);
+    for next in edges.get(name).into_iter().flatten() {
+        find_cycles(next, edges, done, stack, cycles);
+    }
+    stack.pop();
+    done.insert(name);
+}
+
+struct Validator<'a> {
+    config: &'a CasConfig,
+    findings: Vec<Finding>,
+}
+
+impl<'a> Validator<'a> {
+    fn error(&mut self, path: ConfigPath, message: String) {
+        self.findings.push(Finding {
+            severity: Severity::Error,
+            path,
+            message,
+        });
+    }
+
+    fn warning(&mut self, path: ConfigPath, message: String) {
+        self.findings.push(Finding {
+            severity: Severity::Warning,
+            path,
+            message,
+        });
+    }
+
+    fn check_store_ref(&mut self, path: ConfigPath, name: &str) {
+        if !self.config.stores.contains_key(name) {
+            self.error(path, format!("Store '{name}' does not exist in `stores`"));
+        }
+    }
+
+    fn check_scheduler_ref(&mut self, path: ConfigPath, name: &str) -> Option<&'a SchedulerConfig> {
+        let scheduler = self
+            .config
+            .schedulers
+            .as_ref()
+            .and_then(|schedulers| schedulers.get(name));
+        if scheduler.is_none() {
+            self.error(
+                path,
+                format!("Scheduler '{name}' does not exist in `schedulers`"),
+            );
+        }
+        scheduler
+    }
+
+    fn check_stores(&mut self) {
+        let root = ConfigPath::default().key("stores");
+        let mut edges = BTreeMap::new();
+        for (name, store) in sorted(&self.config.stores) {
+            let mut refs = Vec::new();
+            self.check_store(&root.key(name), store, &mut refs);
+            edges.insert(name.as_str(), refs);
+        }
+
+        let mut done = HashSet::new();
+        let mut cycles = Vec::new();
+        for name in edges.keys() {
+            find_cycles(name, &edges, &mut done, &mut Vec::new(), &mut cycles);
+        }
+        for cycle in cycles {
+            self.error(
+                root.key(cycle[0]),
+                format!("ref_store cycle: {}", cycle.join(" -> ")),
+            );
+        }
+    }
+
+    /// Checks the store and the stores nested in it. The names of the
+    /// existing stores it references with `ref_store` are added to `refs`.
+    fn check_store(&mut self, path: &ConfigPath, store: &StoreConfig, refs: &mut Vec<String>) {
+        match store {
+            StoreConfig::memory(config) => {
+                if is_unbounded(config.eviction_policy.as_ref()) {
+                    self.warning(
+                        path.key("memory"),
+                        "Memory store has no eviction limit and grows until the process runs out of memory".to_string(),
+                    );
+                }
+            }
+            StoreConfig::experimental_s3_store(_)
+            | StoreConfig::filesystem(_)
+            | StoreConfig::grpc(_)
+            | StoreConfig::noop => {}
+            StoreConfig::verify(config) => {
+                self.check_store(&path.key("verify").key("backend"), &config.backend, refs);
+            }
+            StoreConfig::completeness_checking(config) => {
+                let path = path.key("completeness_checking");
+                self.check_store(&path.key("backend"), &config.backend, refs);
+                self.check_store(&path.key("cas_store"), &config.cas_store, refs);
+            }
+            StoreConfig::compression(config) => {
+                self.check_store(
+                    &path.key("compression").key("backend"),
+                    &config.backend,
+                    refs,
+                );
+            }
+            StoreConfig::dedup(config) => {
+                let path = path.key("dedup");
+                self.check_store(&path.key("index_store"), &config.index_store, refs);
+                self.check_store(&path.key("content_store"), &config.content_store, refs);
+                if config.track_chunk_references {
+                    for (field, store) in [
+                        ("index_store", &config.index_store),
+                        ("content_store", &config.content_store),
+                    ] {
+                        if !reports_evictions(&self.config.stores, store) {
+                            self.error(
+                                path.key(field),
+                                format!("`track_chunk_references` needs the {field} to report evictions, which only memory stores do"),
+                            );
+                        }
+                    }
+                }
+            }
+            StoreConfig::existence_cache(config) => {
+                self.check_store(
+                    &path.key("existence_cache").key("backend"),
+                    &config.backend,
+                    refs,
+                );
+            }
+            StoreConfig::hedge(config) => {
+                let path = path.key("hedge");
+                self.check_store(&path.key("backend"), &config.backend, refs);
+                if let Some(hedge_backend) = &config.hedge_backend {
+                    self.check_store(&path.key("hedge_backend"), hedge_backend, refs);
+                }
+            }
+            StoreConfig::circuit_breaker(config) => {
+                self.check_store(
+                    &path.key("circuit_breaker").key("backend"),
+                    &config.backend,
+                    refs,
+                );
+            }
+            StoreConfig::quota(config) => {
+                self.check_store(&path.key("quota").key("backend"), &config.backend, refs);
+            }
+            StoreConfig::fast_slow(config) => {
+                let path = path.key("fast_slow");
+                self.check_store(&path.key("fast"), &config.fast, refs);
+                self.check_store(&path.key("slow"), &config.slow, refs);
+            }
+            StoreConfig::tiered(config) => {
+                let path = path.key("tiered").key("tiers");
+                for (i, tier) in config.tiers.iter().enumerate() {
+                    self.check_store(&path.index(i).key("store"), &tier.store, refs);
+                }
+            }
+            StoreConfig::shard(config) => {
+                let path = path.key("shard").key("stores");
+                for (i, shard) in config.stores.iter().enumerate() {
+                    self.check_store(&path.index(i).key("store"), &shard.store, refs);
+                }
+            }
+            StoreConfig::ref_store(config) => {
+                if self.config.stores.contains_key(&config.name) {
+                    refs.push(config.name.clone());
+                } else {
+                    self.check_store_ref(path.key("ref_store").key("name"), &config.name);
+                }
+            }
+            StoreConfig::size_partitioning(config) => {
+                let path = path.key("size_partitioning");
+                self.check_store(&path.key("lower_store"), &config.lower_store, refs);
+                self.check_store(&path.key("upper_store"), &config.upper_store, refs);
+            }
+            StoreConfig::routing(config) => {
+                let path = path.key("routing");
+                for (i, rule) in config.rules.iter().enumerate() {
+                    self.check_store(&path.key("rules").index(i).key("store"), &rule.store, refs);
+                }
+                self.check_store(&path.key("default_store"), &config.default_store, refs);
+            }
+        }
+    }
+
+    fn check_schedulers(&mut self) {
+        let Some(schedulers) = &self.config.schedulers else {
+            return;
+        };
+        let root = ConfigPath::default().key("schedulers");
+        for (name, scheduler) in sorted(schedulers) {
+            self.check_scheduler(&root.key(name), scheduler);
+        }
+    }
+
+    fn check_scheduler(&mut self, path: &ConfigPath, scheduler: &SchedulerConfig) {
+        match scheduler {
+            SchedulerConfig::simple(_) | SchedulerConfig::grpc(_) => {}
+            SchedulerConfig::cache_lookup(config) => {
+                let path = path.key("cache_lookup");
+                self.check_store_ref(path.key("ac_store"), &config.ac_store);
+                self.check_store_ref(path.key("cas_store"), &config.cas_store);
+                self.check_scheduler(&path.key("scheduler"), &config.scheduler);
+            }
+            SchedulerConfig::property_modifier(config) => {
+                self.check_scheduler(
+                    &path.key("property_modifier").key("scheduler"),
+                    &config.scheduler,
+                );
+            }
+        }
+    }
+
+    fn check_servers(&mut self) {
+        let mut socket_addresses = HashMap::new();
+        for (i, server) in self.config.servers.iter().enumerate() {
+            let path = ConfigPath::default().key("servers").index(i);
+            let ListenerConfig::http(listener) = &server.listener;
+            let address = listener.socket_address.as_str();
+            if let Some(other) = socket_addresses.insert(address, i) {
+                self.error(
+                    path.key("listener").key("http").key("socket_address"),
+                    format!("Socket address '{address}' is also used by servers[{other}]"),
+                );
+            }
+            if let Some(services) = &server.services {
+                self.check_services(&path.key("services"), services);
+            }
+        }
+    }
+
+    fn check_services(&mut self, path: &ConfigPath, services: &ServicesConfig) {
+        for (instance_name, config) in services.cas.iter().flat_map(sorted) {
+            self.check_store_ref(
+                path.key("cas").key(instance_name).key("cas_store"),
+                &config.cas_store,
+            );
+        }
+        for (instance_name, config) in services.ac.iter().flat_map(sorted) {
+            self.check_store_ref(
+                path.key("ac").key(instance_name).key("ac_store"),
+                &config.ac_store,
+            );
+        }
+        for (instance_name, config) in services.capabilities.iter().flat_map(sorted) {
+            if let Some(remote_execution) = &config.remote_execution {
+                self.check_scheduler_ref(
+                    path.key("capabilities")
+                        .key(instance_name)
+                        .key("remote_execution")
+                        .key("scheduler"),
+                    &remote_execution.scheduler,
+                );
+            }
+        }
+        for (instance_name, config) in services.execution.iter().flat_map(sorted) {
+            let path = path.key("execution").key(instance_name);
+            self.check_store_ref(path.key("cas_store"), &config.cas_store);
+            self.check_scheduler_ref(path.key("scheduler"), &config.scheduler);
+        }
+        for (instance_name, config) in services.operations.iter().flat_map(sorted) {
+            self.check_scheduler_ref(
+                path.key("operations").key(instance_name).key("scheduler"),
+                &config.scheduler,
+            );
+        }
+        if let Some(config) = &services.bytestream {
+            let path = path.key("bytestream");
+            for (instance_name, store) in sorted(&config.cas_stores) {
+                self.check_store_ref(path.key("cas_stores").key(instance_name), store);
+            }
+            for (instance_name, scheduler) in sorted(&config.action_output_schedulers) {
+                self.check_scheduler_ref(
+                    path.key("action_output_schedulers").key(instance_name),
+                    scheduler,
+                );
+            }
+        }
+        if let Some(config) = &services.browser {
+            let path = path.key("browser");
+            for (instance_name, store) in sorted(&config.cas_stores) {
+                self.check_store_ref(path.key("cas_stores").key(instance_name), store);
+            }
+            for (instance_name, store) in sorted(&config.ac_stores) {
+                self.check_store_ref(path.key("ac_stores").key(instance_name), store);
+            }
+        }
+        if let Some(config) = &services.worker_api {
+            let path = path.key("worker_api");
+            if let Some(scheduler) = self
+                .check_scheduler_ref(path.key("scheduler"), &config.scheduler)
+                .filter(|scheduler| !accepts_workers(scheduler))
+            {
+                self.error(
+                    path.key("scheduler"),
+                    format!(
+                        "Workers can't connect to the {} scheduler '{}', it needs a `simple` scheduler",
+                        scheduler_kind(scheduler),
+                        config.scheduler
+                    ),
+                );
+            }
+
+            let public_services: Vec<&str> = [
+                ("cas", services.cas.is_some()),
+                ("ac", services.ac.is_some()),
+                ("capabilities", services.capabilities.is_some()),
+                ("execution", services.execution.is_some()),
+                ("operations", services.operations.is_some()),
+                ("bytestream", services.bytestream.is_some()),
+                ("admin", services.admin.is_some()),
+                ("dashboard", services.dashboard.is_some()),
+                ("browser", services.browser.is_some()),
+            ]
+            .into_iter()
+            .filter_map(|(service, is_set)| is_set.then_some(service))
+            .collect();
+            if !public_services.is_empty() {
+                self.warning(
+                    path,
+                    format!(
+                        "worker_api shares the listener with {}, workers and clients should use different listeners",
+                        public_services.join(", ")
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_workers(&mut self) {
+        let stores = &self.config.stores;
+        for (i, worker) in self.config.workers.iter().flatten().enumerate() {
+            let WorkerConfig::local(config) = worker;
+            let path = ConfigPath::default().key("workers").index(i).key("local");
+
+            let cas_path = path.key("cas_fast_slow_store");
+            match stores
+                .get(&config.cas_fast_slow_store)
+                .and_then(|store| resolve(stores, store))
+            {
+                None => self.check_store_ref(cas_path, &config.cas_fast_slow_store),
+                Some(StoreConfig::fast_slow(fast_slow)) => {
+                    if !matches!(
+                        resolve(stores, &fast_slow.fast),
+                        Some(StoreConfig::filesystem(_))
+                    ) {
+                        self.error(
+                            cas_path,
+                            format!(
+                                "The `fast` store of '{}' must be a filesystem store",
+                                config.cas_fast_slow_store
+                            ),
+                        );
+                    }
+                }
+                Some(_) => self.error(
+                    cas_path,
+                    format!(
+                        "Store '{}' must be a fast_slow store",
+                        config.cas_fast_slow_store
+                    ),
+                ),
+            }
+
+            let path = path.key("upload_action_result");
+            let upload_action_result = &config.upload_action_result;
+            if let Some(ac_store) = &upload_action_result.ac_store {
+                self.check_store_ref(path.key("ac_store"), ac_store);
+            }
+            if let Some(historical_results_store) = &upload_action_result.historical_results_store {
+                self.check_store_ref(
+                    path.key("historical_results_store"),
+                    historical_results_store,
+                );
+            }
+        }
+    }
+}
+
+/// Finds values in the JSON5 text of a config file, to report the line of a
+/// `ConfigPath`. The text is expected to be valid JSON5.
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    /// Skips whitespace and comments.
+    fn skip_blank(&mut self) {
+        loop {
+            match (self.peek(), self.bytes.get(self.pos + 1)) {
+                (Some(b), _) if b.is_ascii_whitespace() => self.pos += 1,
+                (Some(b'/'), Some(b'/')) => {
+                    while !matches!(self.peek(), None | Some(b'\n')) {
+                        self.pos += 1;
+                    }
+                }
+                (Some(b'/'), Some(b'*')) => {
+                    self.pos += 2;
+                    while self.pos < self.bytes.len() && !self.bytes[self.pos..].starts_with(b"*/")
+                    {
+                        self.pos += 1;
+                    }
+                    self.pos = self.bytes.len().min(self.pos + 2);
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// Skips a quoted string and returns its contents, escapes are kept.
+    fn string(&mut self) -> &'a [u8] {
+        let quote = self.bytes[self.pos];
+        self.pos += 1;
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+            if b == quote {
+                self.pos += 1;
+                return &self.bytes[start..self.pos - 1];
+            }
+            self.pos += if b == b'\\' { 2 } else { 1 };
+        }
+        self.pos = self.bytes.len();
+        &self.bytes[start..]
+    }
+
+    /// Skips an unquoted key or a literal like a number, `true` or `null`.
+    fn word(&mut self) -> &'a [u8] {
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+            if b.is_ascii_whitespace() || b",:[]{}/\"'".contains(&b) {
+                break;
+            }
+            self.pos += 1;
+        }
+        if self.pos == start && self.pos < self.bytes.len() {
+            // Stray character, skip it so scanning makes progress.
+            self.pos += 1;
+        }
+        &self.bytes[start..self.pos]
+    }
+
+    fn key(&mut self) -> &'a [u8] {
+        match self.peek() {
+            Some(b'"' | b'\'') => self.string(),
+            _ => self.word(),
+        }
+    }
+
+    fn skip_value(&mut self) {
+        match self.peek() {
+            Some(b'{' | b'[') => {
+                self.pos += 1;
+                loop {
+                    self.skip_blank();
+                    match self.peek() {
+                        None => return,
+                        Some(b'}' | b']') => {
+                            self.pos += 1;
+                            return;
+                        }
+                        Some(b',' | b':') => self.pos += 1,
+                        Some(_) => self.skip_value(),
+                    }
+                }
+            }
+            Some(b'"' | b'\'') => {
+                self.string();
+            }
+            _ => {
+                self.word();
+            }
+        }
+    }
+
+    /// Returns the offset of the value at `path`, starting from the value at
+    /// the current position.
+    fn find(&mut self, path: &[PathSegment]) -> Option<usize> {
+        self.skip_blank();
+        let Some((segment, rest)) = path.split_first() else {
+            return Some(self.pos);
+        };
+        let open = self.peek()?;
+        self.pos += 1;
+        let mut index = 0;
+        loop {
+            self.skip_blank();
+            match (self.peek()?, segment) {
+                (b'}' | b']', _) => return None,
+                (b',', _) => self.pos += 1,
+                (_, PathSegment::Key(key)) if open == b'{' => {
+                    let found = self.key() == key.as_bytes();
+                    self.skip_blank();
+                    if self.peek()? != b':' {
+                        return None;
+                    }
+                    self.pos += 1;
+                    if found {
+                        return self.find(rest);
+                    }
+                    self.skip_blank();
+                    self.skip_value();
+                }
+                (_, PathSegment::Index(i)) if open == b'[' => {
+                    if index == *i {
+                        return self.find(rest);
+                    }
+                    index += 1;
+                    self.skip_value();
+                }
+                _ => return None,
+            }
+        }
+    }
+}
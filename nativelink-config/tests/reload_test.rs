@@ -0,0 +1,164 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use nativelink_config::reload::{record_field_change, record_new_server, ConfigChanges};
+use serde_json::{json, Value};
+
+fn running_config() -> Value {
+    json!({
+        "stores": {
+            "CAS_MAIN_STORE": {
+                "memory": {
+                    "eviction_policy": { "max_bytes": 1000 }
+                }
+            },
+            "AC_MAIN_STORE": {
+                "filesystem": {
+                    "content_path": "/tmp/nativelink/data/content_path-ac",
+                    "temp_path": "/tmp/nativelink/data/tmp_path-ac",
+                    "eviction_policy": { "max_bytes": 1000 }
+                }
+            }
+        },
+        "schedulers": {
+            "MAIN_SCHEDULER": {
+                "simple": {
+                    "supported_platform_properties": { "cpu_count": "minimum" }
+                }
+            }
+        },
+        "servers": [{
+            "listener": { "http": { "socket_address": "0.0.0.0:50051" } },
+            "services": { "cas": { "main": { "cas_store": "CAS_MAIN_STORE" } } }
+        }],
+        "global": { "max_open_files": 512 }
+    })
+}
+
+#[cfg(test)]
+mod reload_tests {
+    use pretty_assertions::assert_eq; // Must be declared in every module.
+
+    use super::*;
+
+    #[test]
+    fn unchanged_config_has_no_changes() {
+        assert!(ConfigChanges::new(&running_config(), &running_config()).is_empty());
+    }
+
+    #[test]
+    fn changes_that_can_be_applied_live() {
+        let mut reloaded = running_config();
+        reloaded["stores"]["NEW_STORE"] = json!({ "memory": {} });
+        reloaded["stores"]["CAS_MAIN_STORE"]["memory"]["eviction_policy"]["max_bytes"] =
+            json!(2000);
+        reloaded["stores"]["AC_MAIN_STORE"]["filesystem"]
+            .as_object_mut()
+            .unwrap()
+            .remove("eviction_policy");
+        reloaded["schedulers"]["NEW_SCHEDULER"] = json!({ "simple": {} });
+        reloaded["schedulers"]["MAIN_SCHEDULER"]["simple"]["supported_platform_properties"]
+            ["OSFamily"] = json!("exact");
+        reloaded["servers"].as_array_mut().unwrap().insert(
+            0,
+            json!({
+                "listener": { "http": { "socket_address": "0.0.0.0:50052" } },
+                "services": { "admin": {} }
+            }),
+        );
+        reloaded["global"]["log_level"] = json!("info");
+
+        assert_eq!(
+            ConfigChanges::new(&running_config(), &reloaded),
+            ConfigChanges {
+                new_stores: vec!["NEW_STORE".to_string()],
+                eviction_policy_changes: vec![
+                    "AC_MAIN_STORE".to_string(),
+                    "CAS_MAIN_STORE".to_string()
+                ],
+                new_schedulers: vec!["NEW_SCHEDULER".to_string()],
+                platform_property_changes: vec!["MAIN_SCHEDULER".to_string()],
+                new_servers: vec![0],
+                log_level_changed: true,
+                restart_required: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn changes_that_need_a_restart() {
+        let mut reloaded = running_config();
+        reloaded["stores"]["AC_MAIN_STORE"]["filesystem"]["temp_path"] = json!("/tmp/other");
+        reloaded["stores"]
+            .as_object_mut()
+            .unwrap()
+            .remove("CAS_MAIN_STORE");
+        reloaded["schedulers"]["MAIN_SCHEDULER"]["simple"]["worker_timeout_s"] = json!(10);
+        reloaded["servers"][0]["services"]["ac"] = json!({});
+        reloaded["global"]["max_open_files"] = json!(1024);
+        reloaded["workers"] = json!([]);
+
+        let changes = ConfigChanges::new(&running_config(), &reloaded);
+        assert_eq!(
+            changes.restart_required,
+            vec![
+                "store 'AC_MAIN_STORE' changed",
+                "scheduler 'MAIN_SCHEDULER' changed",
+                "store 'CAS_MAIN_STORE' was removed",
+                "server '0' was changed or removed (running servers can't get new instances, \
+                 add them to a new server instead)",
+                "global changed",
+                "workers changed",
+            ]
+        );
+        // The changed server is seen as a new server.
+        assert_eq!(changes.new_servers, vec![0]);
+        assert_eq!(changes.eviction_policy_changes, Vec::<String>::new());
+        assert_eq!(changes.platform_property_changes, Vec::<String>::new());
+    }
+
+    #[test]
+    fn recorded_changes_are_not_seen_again() {
+        let mut reloaded = running_config();
+        reloaded["stores"]["NEW_STORE"] = json!({ "memory": {} });
+        reloaded["stores"]["AC_MAIN_STORE"]["filesystem"]
+            .as_object_mut()
+            .unwrap()
+            .remove("eviction_policy");
+        reloaded["schedulers"]["NEW_SCHEDULER"] = json!({ "simple": {} });
+        reloaded["servers"].as_array_mut().unwrap().insert(
+            0,
+            json!({
+                "listener": { "http": { "socket_address": "0.0.0.0:50052" } },
+                "services": { "admin": {} }
+            }),
+        );
+        reloaded["global"]["log_level"] = json!("info");
+
+        // Applying the new scheduler failed, everything else was applied.
+        let mut running = running_config();
+        record_field_change(&mut running, &reloaded, "stores", "NEW_STORE");
+        record_field_change(&mut running, &reloaded, "stores", "AC_MAIN_STORE");
+        record_new_server(&mut running, &reloaded, 0);
+        record_field_change(&mut running, &reloaded, "global", "log_level");
+
+        assert_eq!(
+            ConfigChanges::new(&running, &reloaded),
+            ConfigChanges {
+                new_schedulers: vec!["NEW_SCHEDULER".to_string()],
+                ..Default::default()
+            }
+        );
+    }
+}
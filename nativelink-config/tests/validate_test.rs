@@ -0,0 +1,231 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use nativelink_config::cas_server::CasConfig;
+use nativelink_config::validate::{validate, Finding, Severity};
+
+const VALID_CONFIG: &str = r#"{
+  stores: {
+    CAS: {
+      fast_slow: {
+        fast: { filesystem: {
+          content_path: "/tmp/nativelink/content_path",
+          temp_path: "/tmp/nativelink/tmp_path",
+          eviction_policy: { max_bytes: 1000000 },
+        } },
+        slow: { ref_store: { name: "REMOTE_CAS" } },
+      },
+    },
+    REMOTE_CAS: { memory: { eviction_policy: { max_bytes: 1000000 } } },
+  },
+  schedulers: {
+    MAIN: { simple: {} },
+  },
+  workers: [{ local: {
+    worker_api_endpoint: { uri: "grpc://127.0.0.1:50061" },
+    cas_fast_slow_store: "CAS",
+    work_directory: "/tmp/nativelink/work",
+    platform_properties: {},
+  } }],
+  servers: [{
+    listener: { http: { socket_address: "0.0.0.0:50051" } },
+    services: {
+      cas: { main: { cas_store: "REMOTE_CAS" } },
+      execution: { main: { cas_store: "REMOTE_CAS", scheduler: "MAIN" } },
+    },
+  }, {
+    listener: { http: { socket_address: "0.0.0.0:50061" } },
+    services: { worker_api: { scheduler: "MAIN" } },
+  }],
+}"#;
+
+const INVALID_CONFIG: &str = r#"{
+  stores: {
+    // Cycle through a nested store.
+    A: { verify: { backend: { ref_store: { name: "B" } } } },
+    B: { ref_store: { name: "A" } },
+    MEMORY: { memory: {} },
+    "WORKER CAS": {
+      fast_slow: {
+        fast: { memory: { eviction_policy: { max_bytes: 10 } } },
+        slow: { ref_store: { name: "MISSING_STORE" } },
+      },
+    },
+  },
+  schedulers: {
+    REMOTE: { grpc: { endpoint: { address: "grpc://127.0.0.1:50052" } } },
+  },
+  workers: [
+    { local: {
+      worker_api_endpoint: { uri: "grpc://127.0.0.1:50061" },
+      cas_fast_slow_store: "WORKER CAS",
+      work_directory: "/tmp/nativelink/work",
+      platform_properties: {},
+    } },
+    { local: {
+      worker_api_endpoint: { uri: "grpc://127.0.0.1:50061" },
+      cas_fast_slow_store: "MEMORY",
+      upload_action_result: { ac_store: "MISSING_AC" },
+      work_directory: "/tmp/nativelink/work",
+      platform_properties: {},
+    } },
+  ],
+  servers: [{
+    listener: { http: { socket_address: "0.0.0.0:50051" } },
+    services: {
+      cas: { main: { cas_store: "MISSING_CAS" } },
+      execution: { main: { cas_store: "A", scheduler: "MISSING_SCHEDULER" } },
+      worker_api: { scheduler: "REMOTE" },
+    },
+  }, {
+    listener: { http: { socket_address: "0.0.0.0:50051" } },
+  }],
+}"#;
+
+const CHUNK_TRACKING_CONFIG: &str = r#"{
+  stores: {
+    DEDUP: { dedup: {
+      index_store: { ref_store: { name: "INDEX" } },
+      content_store: { filesystem: {
+        content_path: "/tmp/nativelink/content_path",
+        temp_path: "/tmp/nativelink/tmp_path",
+        eviction_policy: { max_bytes: 1000000 },
+      } },
+      track_chunk_references: true,
+    } },
+    INDEX: { memory: { eviction_policy: { max_bytes: 1000000 } } },
+    ROUTED_DEDUP: { dedup: {
+      index_store: { ref_store: { name: "INDEX" } },
+      content_store: { routing: {
+        rules: [{
+          store: { memory: { eviction_policy: { max_bytes: 1000000 } } },
+          instance_names: ["main"],
+        }],
+        default_store: { ref_store: { name: "INDEX" } },
+      } },
+      track_chunk_references: true,
+    } },
+  },
+  servers: [],
+}"#;
+
+fn parse(contents: &str) -> CasConfig {
+    serde_json5::from_str(contents).unwrap()
+}
+
+#[cfg(test)]
+mod validate_tests {
+    use pretty_assertions::assert_eq; // Must be declared in every module.
+
+    use super::*;
+
+    #[test]
+    fn valid_config_has_no_findings() {
+        assert_eq!(validate(&parse(VALID_CONFIG)), Vec::<Finding>::new());
+    }
+
+    #[test]
+    fn invalid_config_reports_every_finding() {
+        let findings: Vec<(Severity, String, Option<usize>)> = validate(&parse(INVALID_CONFIG))
+            .into_iter()
+            .map(|finding| {
+                (
+                    finding.severity,
+                    format!("{}: {}", finding.path, finding.message),
+                    finding.path.find_line(INVALID_CONFIG),
+                )
+            })
+            .collect();
+        assert_eq!(
+            findings,
+            vec![
+                (
+                    Severity::Warning,
+                    "stores.MEMORY.memory: Memory store has no eviction limit and grows until the process runs out of memory".to_string(),
+                    Some(6),
+                ),
+                (
+                    Severity::Error,
+                    "stores[\"WORKER CAS\"].fast_slow.slow.ref_store.name: Store 'MISSING_STORE' does not exist in `stores`".to_string(),
+                    Some(10),
+                ),
+                (
+                    Severity::Error,
+                    "stores.A: ref_store cycle: A -> B -> A".to_string(),
+                    Some(4),
+                ),
+                (
+                    Severity::Error,
+                    "servers[0].services.cas.main.cas_store: Store 'MISSING_CAS' does not exist in `stores`".to_string(),
+                    Some(35),
+                ),
+                (
+                    Severity::Error,
+                    "servers[0].services.execution.main.scheduler: Scheduler 'MISSING_SCHEDULER' does not exist in `schedulers`".to_string(),
+                    Some(36),
+                ),
+                (
+                    Severity::Error,
+                    "servers[0].services.worker_api.scheduler: Workers can't connect to the grpc scheduler 'REMOTE', it needs a `simple` scheduler".to_string(),
+                    Some(37),
+                ),
+                (
+                    Severity::Warning,
+                    "servers[0].services.worker_api: worker_api shares the listener with cas, execution, workers and clients should use different listeners".to_string(),
+                    Some(37),
+                ),
+                (
+                    Severity::Error,
+                    "servers[1].listener.http.socket_address: Socket address '0.0.0.0:50051' is also used by servers[0]".to_string(),
+                    Some(40),
+                ),
+                (
+                    Severity::Error,
+                    "workers[0].local.cas_fast_slow_store: The `fast` store of 'WORKER CAS' must be a filesystem store".to_string(),
+                    Some(20),
+                ),
+                (
+                    Severity::Error,
+                    "workers[1].local.cas_fast_slow_store: Store 'MEMORY' must be a fast_slow store".to_string(),
+                    Some(26),
+                ),
+                (
+                    Severity::Error,
+                    "workers[1].local.upload_action_result.ac_store: Store 'MISSING_AC' does not exist in `stores`".to_string(),
+                    Some(27),
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn chunk_reference_tracking_needs_stores_that_report_evictions() {
+        let findings: Vec<(Severity, String)> = validate(&parse(CHUNK_TRACKING_CONFIG))
+            .into_iter()
+            .map(|finding| {
+                (
+                    finding.severity,
+                    format!("{}: {}", finding.path, finding.message),
+                )
+            })
+            .collect();
+        assert_eq!(
+            findings,
+            vec![(
+                Severity::Error,
+                "stores.DEDUP.dedup.content_store: `track_chunk_references` needs the content_store to report evictions, which only memory stores do".to_string(),
+            )]
+        );
+    }
+}
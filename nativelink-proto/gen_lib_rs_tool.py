@@ -76,6 +76,10 @@ def main():
         cur_node["filename"] = '.'.join(package_parts) + '.pb.rs'
 
     print_package_part_to_mod(tree_root)
+    print()
+    print("/// Encoded `FileDescriptorSet` of all the protos above, used by gRPC")
+    print("/// server reflection.")
+    print('pub const FILE_DESCRIPTOR_SET: &[u8] = include_bytes!("descriptor_set.bin");')
 
 
 if __name__ == "__main__":
@@ -29,7 +29,8 @@ fn main() -> std::io::Result<()> {
     let mut config = Config::new();
     config.bytes(["."]);
     tonic_build::configure()
-        .out_dir(output_dir)
+        .out_dir(&output_dir)
+        .file_descriptor_set_path(output_dir.join("descriptor_set.bin"))
         .compile_with_config(config, &paths, &["nativelink-proto"])?;
     Ok(())
 }
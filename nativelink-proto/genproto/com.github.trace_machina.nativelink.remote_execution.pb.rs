@@ -94,6 +94,31 @@ pub mod execute_result {
         InternalError(super::super::super::super::super::super::google::rpc::Status),
     }
 }
+/// / Output written by a running action since the previous request.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ActionOutputRequest {
+    /// / ID of the worker making the request.
+    #[prost(string, tag = "1")]
+    pub worker_id: ::prost::alloc::string::String,
+    /// / See documentation in ExecuteResult::instance_name.
+    #[prost(string, tag = "2")]
+    pub instance_name: ::prost::alloc::string::String,
+    /// / See documentation in ExecuteResult::action_digest.
+    #[prost(message, optional, tag = "3")]
+    pub action_digest: ::core::option::Option<
+        super::super::super::super::super::build::bazel::remote::execution::v2::Digest,
+    >,
+    /// / See documentation in ExecuteResult::salt.
+    #[prost(uint64, tag = "4")]
+    pub salt: u64,
+    /// / Bytes the action wrote to stdout.
+    #[prost(bytes = "bytes", tag = "5")]
+    pub stdout: ::prost::bytes::Bytes,
+    /// / Bytes the action wrote to stderr.
+    #[prost(bytes = "bytes", tag = "6")]
+    pub stderr: ::prost::bytes::Bytes,
+}
 /// / Result sent back from the server when a node connects.
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -106,7 +131,7 @@ pub struct ConnectionResult {
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct UpdateForWorker {
-    #[prost(oneof = "update_for_worker::Update", tags = "1, 2, 3, 4")]
+    #[prost(oneof = "update_for_worker::Update", tags = "1, 2, 3, 4, 5")]
     pub update: ::core::option::Option<update_for_worker::Update>,
 }
 /// Nested message and enum types in `UpdateForWorker`.
@@ -133,10 +158,23 @@ pub mod update_for_worker {
         /// / The worker may discard any outstanding work that is being executed.
         #[prost(message, tag = "4")]
         Disconnect(()),
+        /// / Informs the worker that it should stop executing an action and kill
+        /// / all of its processes. The worker reports the action back as
+        /// / cancelled.
+        #[prost(message, tag = "5")]
+        KillAction(super::KillAction),
     }
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KillAction {
+    /// / The operation name of the action to kill, this is the same name the
+    /// / execution service hands out to clients.
+    #[prost(string, tag = "1")]
+    pub action_name: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct StartExecute {
     /// / The action information used to execute job.
     #[prost(message, optional, tag = "1")]
@@ -391,6 +429,35 @@ pub mod worker_api_client {
                 );
             self.inner.unary(req, path, codec).await
         }
+        /// / Sends output written by an action that is still running, so the
+        /// / scheduler can stream it to clients before the action completes.
+        pub async fn action_output(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ActionOutputRequest>,
+        ) -> std::result::Result<tonic::Response<()>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/com.github.trace_machina.nativelink.remote_execution.WorkerApi/ActionOutput",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "com.github.trace_machina.nativelink.remote_execution.WorkerApi",
+                        "ActionOutput",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -446,6 +513,12 @@ pub mod worker_api_server {
             &self,
             request: tonic::Request<super::ExecuteResult>,
         ) -> std::result::Result<tonic::Response<()>, tonic::Status>;
+        /// / Sends output written by an action that is still running, so the
+        /// / scheduler can stream it to clients before the action completes.
+        async fn action_output(
+            &self,
+            request: tonic::Request<super::ActionOutputRequest>,
+        ) -> std::result::Result<tonic::Response<()>, tonic::Status>;
     }
     /// / This API describes how schedulers communicate with Worker nodes.
     /// /
@@ -716,6 +789,50 @@ pub mod worker_api_server {
                     };
                     Box::pin(fut)
                 }
+                "/com.github.trace_machina.nativelink.remote_execution.WorkerApi/ActionOutput" => {
+                    #[allow(non_camel_case_types)]
+                    struct ActionOutputSvc<T: WorkerApi>(pub Arc<T>);
+                    impl<T: WorkerApi> tonic::server::UnaryService<super::ActionOutputRequest>
+                    for ActionOutputSvc<T> {
+                        type Response = ();
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ActionOutputRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as WorkerApi>::action_output(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ActionOutputSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => {
                     Box::pin(async move {
                         Ok(
@@ -55,3 +55,7 @@ pub mod google {
         include!("google.rpc.pb.rs");
     }
 }
+
+/// Encoded `FileDescriptorSet` of all the protos above, used by gRPC
+/// server reflection.
+pub const FILE_DESCRIPTOR_SET: &[u8] = include_bytes!("descriptor_set.bin");
@@ -64,6 +64,9 @@ def update(proto_packages):
     with open(_REPO_DIR + "/lib.rs", "wb") as outfile:
         with open(_BAZEL_DIR + "/lib.rs", "rb") as infile:
             outfile.write(infile.read())
+    shutil.copyfile(
+        _BAZEL_DIR + "/descriptor_set.bin", _REPO_DIR + "/descriptor_set.bin"
+    )
 
 
 def check(proto_packages):
@@ -102,6 +105,22 @@ def check(proto_packages):
         print("%s out of date" % dst)
         failed = True
 
+    # Now check the descriptor set used by server reflection.
+    dst = _REPO_DIR + "/descriptor_set.bin"
+    try:
+        with open(_BAZEL_DIR + "/descriptor_set.bin", "rb") as infile:
+            expected = infile.read()
+        with open(dst, "rb") as infile:
+            actual = infile.read()
+    except OSError as e:
+        failed = True
+        print("Could not read descriptor_set.bin: %s" % e)
+    if expected == actual:
+        print("%s OK" % dst)
+    else:
+        print("%s out of date" % dst)
+        failed = True
+
     if failed:
         print("To update, run: 'bazel run proto:update_protos'")
         raise SystemExit(1)
@@ -0,0 +1,155 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use nativelink_util::action_messages::ActionOutputStream;
+use tokio::sync::watch;
+
+#[derive(Default)]
+struct ActionOutputState {
+    /// Chunks in the order they were written.
+    chunks: Vec<Bytes>,
+    /// Total number of bytes in `chunks`.
+    len: u64,
+    /// Number of bytes that were dropped because the buffer was full.
+    dropped_bytes: u64,
+    /// Set once no more output will be written.
+    finished: bool,
+}
+
+/// Output written by a running action. Readers can tail the output while
+/// it is being written.
+pub struct ActionOutput {
+    max_bytes: u64,
+    state: watch::Sender<ActionOutputState>,
+}
+
+impl ActionOutput {
+    /// Creates an empty output that keeps at most `max_bytes`, anything
+    /// written after that is dropped.
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            max_bytes,
+            state: watch::Sender::new(ActionOutputState::default()),
+        }
+    }
+
+    /// Appends `data` to the output. Does nothing once finished.
+    pub fn append(&self, mut data: Bytes) {
+        if data.is_empty() {
+            return;
+        }
+        self.state.send_if_modified(|state| {
+            if state.finished {
+                return false;
+            }
+            let remaining = self.max_bytes.saturating_sub(state.len);
+            if (data.len() as u64) > remaining {
+                state.dropped_bytes += data.len() as u64 - remaining;
+                data.truncate(remaining as usize);
+            }
+            if data.is_empty() {
+                return false;
+            }
+            state.len += data.len() as u64;
+            state.chunks.push(data);
+            true
+        });
+    }
+
+    /// Marks the output as complete and wakes up all readers.
+    pub fn finish(&self) {
+        self.state.send_if_modified(|state| {
+            let was_finished = state.finished;
+            state.finished = true;
+            !was_finished
+        });
+    }
+
+    /// Number of bytes that were written.
+    pub fn len(&self) -> u64 {
+        self.state.borrow().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true once no more output can be kept.
+    pub fn is_full(&self) -> bool {
+        self.len() >= self.max_bytes
+    }
+
+    /// Number of bytes that were dropped because the output was too large.
+    pub fn dropped_bytes(&self) -> u64 {
+        self.state.borrow().dropped_bytes
+    }
+
+    /// Returns up to `max_len` bytes starting at `offset`, waiting for them
+    /// to be written if needed. An empty result means the output finished
+    /// and there is nothing more to read after `offset`.
+    pub async fn read(&self, offset: u64, max_len: usize) -> Bytes {
+        let mut rx = self.state.subscribe();
+        // The sender is owned by `self`, so it can not be dropped while we wait.
+        let Ok(state) = rx
+            .wait_for(|state| state.len > offset || state.finished)
+            .await
+        else {
+            return Bytes::new();
+        };
+        let mut chunk_start = 0;
+        for chunk in &state.chunks {
+            let chunk_end = chunk_start + chunk.len() as u64;
+            if offset < chunk_end {
+                let start = (offset - chunk_start) as usize;
+                let end = chunk.len().min(start.saturating_add(max_len));
+                return chunk.slice(start..end);
+            }
+            chunk_start = chunk_end;
+        }
+        Bytes::new()
+    }
+}
+
+/// The stdout and stderr of an action while it runs on a worker. Both are
+/// finished when this is dropped, so readers know no more output is coming.
+pub struct ActionOutputs {
+    stdout: Arc<ActionOutput>,
+    stderr: Arc<ActionOutput>,
+}
+
+impl ActionOutputs {
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            stdout: Arc::new(ActionOutput::new(max_bytes)),
+            stderr: Arc::new(ActionOutput::new(max_bytes)),
+        }
+    }
+
+    pub fn get(&self, stream: ActionOutputStream) -> &Arc<ActionOutput> {
+        match stream {
+            ActionOutputStream::Stdout => &self.stdout,
+            ActionOutputStream::Stderr => &self.stderr,
+        }
+    }
+}
+
+impl Drop for ActionOutputs {
+    fn drop(&mut self) {
+        self.stdout.finish();
+        self.stderr.finish();
+    }
+}
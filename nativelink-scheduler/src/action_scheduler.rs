@@ -12,16 +12,64 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use nativelink_error::Error;
-use nativelink_util::action_messages::{ActionInfo, ActionInfoHashKey, ActionState};
+use nativelink_error::{make_err, Code, Error};
+use nativelink_util::action_messages::{
+    ActionInfo, ActionInfoHashKey, ActionOutputStream, ActionState,
+};
 use nativelink_util::metrics_utils::Registry;
+use serde::Serialize;
 use tokio::sync::watch;
 
+use crate::action_output::ActionOutput;
 use crate::platform_property_manager::PlatformPropertyManager;
 
+/// Whether a scheduled action is waiting for a worker or running on one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ScheduledActionStage {
+    Queued,
+    Executing,
+}
+
+/// Snapshot of an action that is queued or executing, used to inspect the
+/// scheduler.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScheduledActionInfo {
+    /// Name of the action, as returned by `ActionInfoHashKey::action_name()`.
+    pub action_name: String,
+    pub stage: ScheduledActionStage,
+    /// The priority of the action. Higher value means it should execute faster.
+    pub priority: i32,
+    /// Platform properties a worker needs to execute the action.
+    pub platform_properties: BTreeMap<String, String>,
+    /// Seconds since the action was added to the scheduler.
+    pub age_s: u64,
+    /// Number of times the action was sent to a worker.
+    pub attempts: usize,
+    /// Worker executing the action, if any.
+    pub worker_id: Option<String>,
+}
+
+/// Snapshot of an action that completed recently, used to inspect the
+/// scheduler.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CompletedActionInfo {
+    /// Name of the action, as returned by `ActionInfoHashKey::action_name()`.
+    pub action_name: String,
+    /// Seconds since the unix epoch when the action completed.
+    pub completed_timestamp: u64,
+    pub exit_code: i32,
+    /// Worker that executed the action, empty if unknown.
+    pub worker: String,
+    /// Milliseconds the worker spent on the action, zero if unknown.
+    pub worker_duration_ms: u64,
+    /// Set if the action failed to execute (not if the command failed).
+    pub error: Option<String>,
+}
+
 /// ActionScheduler interface is responsible for interactions between the scheduler
 /// and action related operations.
 #[async_trait]
@@ -44,6 +92,70 @@ pub trait ActionScheduler: Sync + Send + Unpin {
         unique_qualifier: &ActionInfoHashKey,
     ) -> Option<watch::Receiver<Arc<ActionState>>>;
 
+    /// Returns the current state of every action the scheduler knows about,
+    /// including actions that completed recently.
+    async fn list_actions(&self) -> Result<Vec<Arc<ActionState>>, Error> {
+        Err(make_err!(
+            Code::Unimplemented,
+            "list_actions not implemented for this scheduler"
+        ))
+    }
+
+    /// Withdraws one client from a queued or running action. The action is
+    /// only cancelled, and its listeners notified, once no other client that
+    /// added it is waiting on it. Listeners from `find_existing_action` do
+    /// not keep the action alive. Requests do not identify the client, so
+    /// calling this twice withdraws two clients. Returns false if the action
+    /// was not found or already finished.
+    async fn cancel_action(&self, _unique_qualifier: &ActionInfoHashKey) -> Result<bool, Error> {
+        Err(make_err!(
+            Code::Unimplemented,
+            "cancel_action not implemented for this scheduler"
+        ))
+    }
+
+    /// Returns a snapshot of every queued and executing action.
+    async fn list_scheduled_actions(&self) -> Result<Vec<ScheduledActionInfo>, Error> {
+        Err(make_err!(
+            Code::Unimplemented,
+            "list_scheduled_actions not implemented for this scheduler"
+        ))
+    }
+
+    /// Returns a snapshot of the actions that completed recently.
+    async fn list_completed_actions(&self) -> Result<Vec<CompletedActionInfo>, Error> {
+        Err(make_err!(
+            Code::Unimplemented,
+            "list_completed_actions not implemented for this scheduler"
+        ))
+    }
+
+    /// Changes the priority of a queued action. Returns false if the action
+    /// is not queued.
+    async fn set_action_priority(
+        &self,
+        _unique_qualifier: &ActionInfoHashKey,
+        _priority: i32,
+    ) -> Result<bool, Error> {
+        Err(make_err!(
+            Code::Unimplemented,
+            "set_action_priority not implemented for this scheduler"
+        ))
+    }
+
+    /// Returns the output of an action that is currently executing, so it can
+    /// be read while the action runs.
+    async fn action_output(
+        &self,
+        _unique_qualifier: &ActionInfoHashKey,
+        _stream: ActionOutputStream,
+    ) -> Result<Arc<ActionOutput>, Error> {
+        Err(make_err!(
+            Code::Unimplemented,
+            "action_output not implemented for this scheduler"
+        ))
+    }
+
     /// Cleans up the cache of recently completed actions.
     async fn clean_recently_completed_actions(&self);
 
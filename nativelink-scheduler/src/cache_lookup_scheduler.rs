@@ -18,16 +18,17 @@ use std::sync::Arc;
 
 use async_trait::async_trait;
 use futures::stream::StreamExt;
-use nativelink_error::Error;
+use nativelink_error::{make_err, Code, Error};
 use nativelink_proto::build::bazel::remote::execution::v2::{
     digest_function, ActionResult as ProtoActionResult, GetActionResultRequest,
 };
 use nativelink_store::ac_utils::get_and_decode_digest;
 use nativelink_store::grpc_store::GrpcStore;
 use nativelink_util::action_messages::{
-    ActionInfo, ActionInfoHashKey, ActionResult, ActionStage, ActionState,
+    ActionInfo, ActionInfoHashKey, ActionOutputStream, ActionResult, ActionStage, ActionState,
 };
 use nativelink_util::common::DigestInfo;
+use nativelink_util::request_context::{RequestContext, StoreAccess};
 use nativelink_util::store_trait::Store;
 use parking_lot::{Mutex, MutexGuard};
 use scopeguard::guard;
@@ -36,13 +37,26 @@ use tokio::sync::watch;
 use tokio_stream::wrappers::WatchStream;
 use tonic::Request;
 
-use crate::action_scheduler::ActionScheduler;
+use crate::action_output::ActionOutput;
+use crate::action_scheduler::{ActionScheduler, CompletedActionInfo, ScheduledActionInfo};
 use crate::platform_property_manager::PlatformPropertyManager;
 
 /// Actions that are having their cache checked or failed cache lookup and are
 /// being forwarded upstream.  Missing the skip_cache_check actions which are
 /// forwarded directly.
-type CheckActions = HashMap<ActionInfoHashKey, Arc<watch::Sender<Arc<ActionState>>>>;
+type CheckActions = HashMap<ActionInfoHashKey, CheckAction>;
+
+struct CheckAction {
+    tx: Arc<watch::Sender<Arc<ActionState>>>,
+    /// Number of `add_action` calls waiting on the action. Listeners from
+    /// `find_existing_action` only watch the action and are not counted.
+    clients: usize,
+    /// Number of times the action was asked to be cancelled. The upstream
+    /// scheduler only sees a single client, so the action is only forwarded
+    /// as cancelled once every one of our clients was withdrawn. Like in
+    /// `SimpleScheduler`, every request withdraws one client.
+    cancel_requests: usize,
+}
 
 pub struct CacheLookupScheduler {
     /// A reference to the CAS which is used to validate all the outputs of a
@@ -123,15 +137,18 @@ fn subscribe_to_existing_action(
     cache_check_actions: &MutexGuard<CheckActions>,
     unique_qualifier: &ActionInfoHashKey,
 ) -> Option<watch::Receiver<Arc<ActionState>>> {
-    cache_check_actions.get(unique_qualifier).map(|tx| {
-        let current_value = tx.borrow();
-        // Subscribe marks the current value as seen, so we have to
-        // re-send it to all receivers.
-        // TODO: Fix this when fixed upstream tokio-rs/tokio#5871
-        let rx = tx.subscribe();
-        let _ = tx.send(current_value.clone());
-        rx
-    })
+    cache_check_actions
+        .get(unique_qualifier)
+        .map(|check_action| {
+            let tx = &check_action.tx;
+            let current_value = tx.borrow();
+            // Subscribe marks the current value as seen, so we have to
+            // re-send it to all receivers.
+            // TODO: Fix this when fixed upstream tokio-rs/tokio#5871
+            let rx = tx.subscribe();
+            let _ = tx.send(current_value.clone());
+            rx
+        })
 }
 
 impl CacheLookupScheduler {
@@ -180,34 +197,73 @@ impl ActionScheduler for CacheLookupScheduler {
             if let Some(rx) =
                 subscribe_to_existing_action(&cache_check_actions, &action_info.unique_qualifier)
             {
+                if let Some(check_action) =
+                    cache_check_actions.get_mut(&action_info.unique_qualifier)
+                {
+                    check_action.clients += 1;
+                }
                 return Ok(rx);
             }
-            cache_check_actions.insert(action_info.unique_qualifier.clone(), tx.clone());
+            cache_check_actions.insert(
+                action_info.unique_qualifier.clone(),
+                CheckAction {
+                    tx: tx.clone(),
+                    clients: 1,
+                    cancel_requests: 0,
+                },
+            );
             // In the event we loose the reference to our `scope_guard`, it will remove
             // the action from the cache_check_actions map.
             let cache_check_actions = self.cache_check_actions.clone();
             let unique_qualifier = action_info.unique_qualifier.clone();
+            let tx = tx.clone();
             guard((), move |_| {
-                cache_check_actions.lock().remove(&unique_qualifier);
+                let mut cache_check_actions = cache_check_actions.lock();
+                // The action may have been cancelled and re-added in the meantime.
+                if cache_check_actions
+                    .get(&unique_qualifier)
+                    .is_some_and(|check_action| Arc::ptr_eq(&check_action.tx, &tx))
+                {
+                    cache_check_actions.remove(&unique_qualifier);
+                }
             })
         };
 
         let ac_store = self.ac_store.clone();
         let cas_store = self.cas_store.clone();
         let action_scheduler = self.action_scheduler.clone();
+        let cache_check_actions = self.cache_check_actions.clone();
         // We need this spawn because we are returning a stream and this spawn will populate the stream's data.
         tokio::spawn(async move {
             // If our spawn ever dies, we will remove the action from the cache_check_actions map.
             let _scope_guard = scope_guard;
 
-            // Perform cache check.
+            // Perform cache check. This runs in its own task, so the stores
+            // need to be told which request they are serving again.
             let action_digest = current_state.action_digest();
             let instance_name = action_info.instance_name().clone();
-            if let Some(action_result) =
-                get_action_from_store(Pin::new(ac_store.as_ref()), *action_digest, instance_name)
-                    .await
+            let request_context = RequestContext {
+                instance_name: instance_name.clone(),
+                digest_function: action_info.digest_function,
+                access: StoreAccess::Ac,
+            };
+            if let Some(action_result) = request_context
+                .clone()
+                .scope(get_action_from_store(
+                    Pin::new(ac_store.as_ref()),
+                    *action_digest,
+                    instance_name,
+                ))
+                .await
             {
-                if validate_outputs_exist(&cas_store, &action_result).await {
+                let request_context = RequestContext {
+                    access: StoreAccess::Cas,
+                    ..request_context
+                };
+                if request_context
+                    .scope(validate_outputs_exist(&cas_store, &action_result))
+                    .await
+                {
                     // Found in the cache, return the result immediately.
                     Arc::make_mut(&mut current_state).stage =
                         ActionStage::CompletedFromCache(action_result);
@@ -215,6 +271,14 @@ impl ActionScheduler for CacheLookupScheduler {
                     return;
                 }
             }
+            // Don't forward actions that were cancelled during the cache check.
+            if !cache_check_actions
+                .lock()
+                .get(&action_info.unique_qualifier)
+                .is_some_and(|check_action| Arc::ptr_eq(&check_action.tx, &tx))
+            {
+                return;
+            }
             // Not in cache, forward to upstream and proxy state.
             match action_scheduler.add_action(action_info).await {
                 Ok(rx) => {
@@ -261,5 +325,87 @@ impl ActionScheduler for CacheLookupScheduler {
             .await
     }
 
+    async fn list_actions(&self) -> Result<Vec<Arc<ActionState>>, Error> {
+        let mut actions = self.action_scheduler.list_actions().await?;
+        let cache_check_actions = self.cache_check_actions.lock();
+        // Actions which were forwarded upstream are already in the list.
+        let checking_actions: Vec<_> = cache_check_actions
+            .iter()
+            .filter(|(unique_qualifier, _)| {
+                !actions
+                    .iter()
+                    .any(|action| &action.unique_qualifier == *unique_qualifier)
+            })
+            .map(|(_, check_action)| check_action.tx.borrow().clone())
+            .collect();
+        actions.extend(checking_actions);
+        Ok(actions)
+    }
+
+    async fn cancel_action(&self, unique_qualifier: &ActionInfoHashKey) -> Result<bool, Error> {
+        if let Some(check_action) = self.cache_check_actions.lock().get_mut(unique_qualifier) {
+            check_action.cancel_requests += 1;
+            // Other clients are still waiting on the action.
+            let waiting_clients = check_action.tx.receiver_count().min(check_action.clients);
+            if waiting_clients > check_action.cancel_requests {
+                return Ok(true);
+            }
+        }
+        if self
+            .action_scheduler
+            .cancel_action(unique_qualifier)
+            .await?
+        {
+            // The cancelled state is proxied to our listeners.
+            return Ok(true);
+        }
+        let Some(CheckAction { tx, .. }) = self.cache_check_actions.lock().remove(unique_qualifier)
+        else {
+            return Ok(false);
+        };
+        let mut current_state = tx.borrow().clone();
+        if current_state.stage.is_finished() {
+            return Ok(false);
+        }
+        Arc::make_mut(&mut current_state).stage = ActionStage::Completed(ActionResult {
+            error: Some(make_err!(
+                Code::Cancelled,
+                "Action {} was cancelled",
+                unique_qualifier.digest.hash_str()
+            )),
+            ..Default::default()
+        });
+        let _ = tx.send(current_state);
+        Ok(true)
+    }
+
+    async fn list_scheduled_actions(&self) -> Result<Vec<ScheduledActionInfo>, Error> {
+        self.action_scheduler.list_scheduled_actions().await
+    }
+
+    async fn list_completed_actions(&self) -> Result<Vec<CompletedActionInfo>, Error> {
+        self.action_scheduler.list_completed_actions().await
+    }
+
+    async fn set_action_priority(
+        &self,
+        unique_qualifier: &ActionInfoHashKey,
+        priority: i32,
+    ) -> Result<bool, Error> {
+        self.action_scheduler
+            .set_action_priority(unique_qualifier, priority)
+            .await
+    }
+
+    async fn action_output(
+        &self,
+        unique_qualifier: &ActionInfoHashKey,
+        stream: ActionOutputStream,
+    ) -> Result<Arc<ActionOutput>, Error> {
+        self.action_scheduler
+            .action_output(unique_qualifier, stream)
+            .await
+    }
+
     async fn clean_recently_completed_actions(&self) {}
 }
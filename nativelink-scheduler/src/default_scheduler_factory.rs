@@ -19,6 +19,7 @@ use std::time::Duration;
 use nativelink_config::schedulers::SchedulerConfig;
 use nativelink_error::{Error, ResultExt};
 use nativelink_store::store_manager::StoreManager;
+use nativelink_util::health_utils::HealthRegistryBuilder;
 use nativelink_util::metrics_utils::Registry;
 use tokio::time::interval;
 
@@ -38,12 +39,14 @@ pub fn scheduler_factory(
     scheduler_type_cfg: &SchedulerConfig,
     store_manager: &StoreManager,
     scheduler_metrics: &mut Registry,
+    maybe_health_registry_builder: Option<&mut HealthRegistryBuilder>,
 ) -> Result<SchedulerFactoryResults, Error> {
     let mut visited_schedulers = HashSet::new();
     inner_scheduler_factory(
         scheduler_type_cfg,
         store_manager,
         Some(scheduler_metrics),
+        maybe_health_registry_builder,
         &mut visited_schedulers,
     )
 }
@@ -52,11 +55,15 @@ fn inner_scheduler_factory(
     scheduler_type_cfg: &SchedulerConfig,
     store_manager: &StoreManager,
     maybe_scheduler_metrics: Option<&mut Registry>,
+    maybe_health_registry_builder: Option<&mut HealthRegistryBuilder>,
     visited_schedulers: &mut HashSet<usize>,
 ) -> Result<SchedulerFactoryResults, Error> {
     let scheduler: SchedulerFactoryResults = match scheduler_type_cfg {
         SchedulerConfig::simple(config) => {
             let scheduler = Arc::new(SimpleScheduler::new(config));
+            if let Some(health_registry_builder) = maybe_health_registry_builder {
+                health_registry_builder.register_indicator(scheduler.clone());
+            }
             (Some(scheduler.clone()), Some(scheduler))
         }
         SchedulerConfig::grpc(config) => (Some(Arc::new(GrpcScheduler::new(config)?)), None),
@@ -67,9 +74,14 @@ fn inner_scheduler_factory(
             let ac_store = store_manager
                 .get_store(&config.ac_store)
                 .err_tip(|| format!("'ac_store': '{}' does not exist", config.ac_store))?;
-            let (action_scheduler, worker_scheduler) =
-                inner_scheduler_factory(&config.scheduler, store_manager, None, visited_schedulers)
-                    .err_tip(|| "In nested CacheLookupScheduler construction")?;
+            let (action_scheduler, worker_scheduler) = inner_scheduler_factory(
+                &config.scheduler,
+                store_manager,
+                None,
+                maybe_health_registry_builder,
+                visited_schedulers,
+            )
+            .err_tip(|| "In nested CacheLookupScheduler construction")?;
             let cache_lookup_scheduler = Arc::new(CacheLookupScheduler::new(
                 cas_store,
                 ac_store,
@@ -78,9 +90,14 @@ fn inner_scheduler_factory(
             (Some(cache_lookup_scheduler), worker_scheduler)
         }
         SchedulerConfig::property_modifier(config) => {
-            let (action_scheduler, worker_scheduler) =
-                inner_scheduler_factory(&config.scheduler, store_manager, None, visited_schedulers)
-                    .err_tip(|| "In nested PropertyModifierScheduler construction")?;
+            let (action_scheduler, worker_scheduler) = inner_scheduler_factory(
+                &config.scheduler,
+                store_manager,
+                None,
+                maybe_health_registry_builder,
+                visited_schedulers,
+            )
+            .err_tip(|| "In nested PropertyModifierScheduler construction")?;
             let property_modifier_scheduler = Arc::new(PropertyModifierScheduler::new(
                 config,
                 action_scheduler.err_tip(|| "Nested scheduler is not an action scheduler")?,
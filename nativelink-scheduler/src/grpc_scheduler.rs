@@ -26,7 +26,10 @@ use nativelink_proto::build::bazel::remote::execution::v2::execution_client::Exe
 use nativelink_proto::build::bazel::remote::execution::v2::{
     digest_function, ExecuteRequest, ExecutionPolicy, GetCapabilitiesRequest, WaitExecutionRequest,
 };
-use nativelink_proto::google::longrunning::Operation;
+use nativelink_proto::google::longrunning::operations_client::OperationsClient;
+use nativelink_proto::google::longrunning::{
+    CancelOperationRequest, ListOperationsRequest, Operation,
+};
 use nativelink_util::action_messages::{
     ActionInfo, ActionInfoHashKey, ActionState, DEFAULT_EXECUTION_PRIORITY,
 };
@@ -268,5 +271,62 @@ impl ActionScheduler for GrpcScheduler {
         }
     }
 
+    async fn list_actions(&self) -> Result<Vec<Arc<ActionState>>, Error> {
+        let mut actions = Vec::new();
+        let mut page_token = String::new();
+        loop {
+            let request = ListOperationsRequest {
+                page_token,
+                ..Default::default()
+            };
+            let response = self
+                .perform_request(request, |request| async move {
+                    let (connection, channel) = self.connection_manager.get_connection().await;
+                    let result = OperationsClient::new(channel)
+                        .list_operations(Request::new(request))
+                        .await
+                        .err_tip(|| "Listing operations with upstream scheduler");
+                    if let Err(err) = &result {
+                        connection.on_error(err);
+                    }
+                    result
+                })
+                .await?
+                .into_inner();
+            for operation in response.operations {
+                actions.push(Arc::new(operation.try_into()?));
+            }
+            if response.next_page_token.is_empty() {
+                return Ok(actions);
+            }
+            page_token = response.next_page_token;
+        }
+    }
+
+    async fn cancel_action(&self, unique_qualifier: &ActionInfoHashKey) -> Result<bool, Error> {
+        let request = CancelOperationRequest {
+            name: unique_qualifier.action_name(),
+        };
+        let result = self
+            .perform_request(request, |request| async move {
+                let (connection, channel) = self.connection_manager.get_connection().await;
+                let result = OperationsClient::new(channel)
+                    .cancel_operation(Request::new(request))
+                    .await
+                    .err_tip(|| "Cancelling operation with upstream scheduler");
+                match result {
+                    // Not found is an answer, not a connection failure.
+                    Err(err) if err.code == Code::NotFound => Ok(false),
+                    Err(err) => {
+                        connection.on_error(&err);
+                        Err(err)
+                    }
+                    Ok(_) => Ok(true),
+                }
+            })
+            .await?;
+        Ok(result)
+    }
+
     async fn clean_recently_completed_actions(&self) {}
 }
@@ -19,11 +19,14 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use nativelink_config::schedulers::{PropertyModification, PropertyType};
 use nativelink_error::{Error, ResultExt};
-use nativelink_util::action_messages::{ActionInfo, ActionInfoHashKey, ActionState};
+use nativelink_util::action_messages::{
+    ActionInfo, ActionInfoHashKey, ActionOutputStream, ActionState,
+};
 use parking_lot::Mutex;
 use tokio::sync::watch;
 
-use crate::action_scheduler::ActionScheduler;
+use crate::action_output::ActionOutput;
+use crate::action_scheduler::{ActionScheduler, CompletedActionInfo, ScheduledActionInfo};
 use crate::platform_property_manager::PlatformPropertyManager;
 
 pub struct PropertyModifierScheduler {
@@ -120,6 +123,40 @@ impl ActionScheduler for PropertyModifierScheduler {
         self.scheduler.find_existing_action(unique_qualifier).await
     }
 
+    async fn list_actions(&self) -> Result<Vec<Arc<ActionState>>, Error> {
+        self.scheduler.list_actions().await
+    }
+
+    async fn cancel_action(&self, unique_qualifier: &ActionInfoHashKey) -> Result<bool, Error> {
+        self.scheduler.cancel_action(unique_qualifier).await
+    }
+
+    async fn list_scheduled_actions(&self) -> Result<Vec<ScheduledActionInfo>, Error> {
+        self.scheduler.list_scheduled_actions().await
+    }
+
+    async fn list_completed_actions(&self) -> Result<Vec<CompletedActionInfo>, Error> {
+        self.scheduler.list_completed_actions().await
+    }
+
+    async fn set_action_priority(
+        &self,
+        unique_qualifier: &ActionInfoHashKey,
+        priority: i32,
+    ) -> Result<bool, Error> {
+        self.scheduler
+            .set_action_priority(unique_qualifier, priority)
+            .await
+    }
+
+    async fn action_output(
+        &self,
+        unique_qualifier: &ActionInfoHashKey,
+        stream: ActionOutputStream,
+    ) -> Result<Arc<ActionOutput>, Error> {
+        self.scheduler.action_output(unique_qualifier, stream).await
+    }
+
     async fn clean_recently_completed_actions(&self) {
         self.scheduler.clean_recently_completed_actions().await
     }
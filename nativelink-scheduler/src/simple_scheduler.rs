@@ -12,23 +12,26 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::borrow::Borrow;
+use std::borrow::{Borrow, Cow};
 use std::cmp;
 use std::collections::BTreeMap;
 use std::hash::{Hash, Hasher};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{Instant, SystemTime};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 use async_trait::async_trait;
+use bytes::Bytes;
 use futures::Future;
 use hashbrown::{HashMap, HashSet};
 use lru::LruCache;
-use nativelink_config::schedulers::WorkerAllocationStrategy;
+use nativelink_config::schedulers::{PropertyType, WorkerAllocationStrategy};
 use nativelink_error::{error_if, make_err, make_input_err, Code, Error, ResultExt};
 use nativelink_util::action_messages::{
-    ActionInfo, ActionInfoHashKey, ActionResult, ActionStage, ActionState, ExecutionMetadata,
+    ActionInfo, ActionInfoHashKey, ActionOutputStream, ActionResult, ActionStage, ActionState,
+    ExecutionMetadata, INTERNAL_ERROR_EXIT_CODE,
 };
+use nativelink_util::health_utils::{HealthStatus, HealthStatusIndicator};
 use nativelink_util::metrics_utils::{
     AsyncCounterWrapper, Collector, CollectorState, CounterWithTime, FuncCounterWrapper,
     MetricsComponent, Registry,
@@ -40,9 +43,12 @@ use tokio::task::JoinHandle;
 use tokio::time::Duration;
 use tracing::{error, warn};
 
-use crate::action_scheduler::ActionScheduler;
+use crate::action_output::{ActionOutput, ActionOutputs};
+use crate::action_scheduler::{
+    ActionScheduler, CompletedActionInfo, ScheduledActionInfo, ScheduledActionStage,
+};
 use crate::platform_property_manager::PlatformPropertyManager;
-use crate::worker::{Worker, WorkerId, WorkerTimestamp, WorkerUpdate};
+use crate::worker::{Worker, WorkerId, WorkerInfo, WorkerTimestamp, WorkerUpdate};
 use crate::worker_scheduler::WorkerScheduler;
 
 /// Default timeout for workers in seconds.
@@ -57,6 +63,15 @@ const DEFAULT_RETAIN_COMPLETED_FOR_S: u64 = 60;
 /// If this changes, remember to change the documentation in the config.
 const DEFAULT_MAX_JOB_RETRIES: usize = 3;
 
+/// Default time in seconds, beyond an action's timeout, before the scheduler
+/// kills an action that has not reported back.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_ACTION_TIMEOUT_GRACE_S: u64 = 60;
+
+/// Default number of bytes of stdout and of stderr kept for each running action.
+/// If this changes, remember to change the documentation in the config.
+const DEFAULT_MAX_ACTION_OUTPUT_BYTES: u64 = 16 * 1024 * 1024;
+
 /// An action that is being awaited on and last known state.
 struct AwaitedAction {
     action_info: Arc<ActionInfo>,
@@ -68,12 +83,25 @@ struct AwaitedAction {
     /// Possible last error set by the worker. If empty and attempts is set, it may be due to
     /// something like a worker timeout.
     last_error: Option<Error>,
+    /// Number of `add_action` calls waiting on the action. Unlike the
+    /// receivers of `notify_channel`, this does not count listeners that
+    /// only watch the action, e.g. through `WaitOperation`.
+    clients: usize,
+    /// Number of times the action was asked to be cancelled. Cancel requests
+    /// do not identify the client, so every request withdraws one client,
+    /// including repeated requests from the same client.
+    cancel_requests: usize,
 }
 
 /// Holds the relationship of a worker that is executing a specific action.
 struct RunningAction {
     worker_id: WorkerId,
+    /// Timestamp of when the action was sent to the worker.
+    started_timestamp: WorkerTimestamp,
     action: AwaitedAction,
+    /// Output the worker streamed while running the action. Readers are told
+    /// the output finished once the action stops running on this worker.
+    output: ActionOutputs,
 }
 
 struct Workers {
@@ -153,17 +181,23 @@ impl Workers {
             awaited_action.current_state.stage,
             ActionStage::Queued
         ));
-        let action_properties = &awaited_action.action_info.platform_properties;
+        let action_info = &awaited_action.action_info;
+        let action_properties = &action_info.platform_properties;
+        // Workers still running a cancelled copy of the action can't tell the
+        // two apart, so they are skipped.
+        let can_run = |w: &Worker| {
+            w.can_accept_work()
+                && action_properties.is_satisfied_by(&w.platform_properties)
+                && !w.running_action_infos.contains(action_info)
+        };
         let mut workers_iter = self.workers.iter_mut();
         let workers_iter = match self.allocation_strategy {
             // Use rfind to get the least recently used that satisfies the properties.
-            WorkerAllocationStrategy::least_recently_used => workers_iter.rfind(|(_, w)| {
-                w.can_accept_work() && action_properties.is_satisfied_by(&w.platform_properties)
-            }),
+            WorkerAllocationStrategy::least_recently_used => {
+                workers_iter.rfind(|(_, w)| can_run(w))
+            }
             // Use find to get the most recently used that satisfies the properties.
-            WorkerAllocationStrategy::most_recently_used => workers_iter.find(|(_, w)| {
-                w.can_accept_work() && action_properties.is_satisfied_by(&w.platform_properties)
-            }),
+            WorkerAllocationStrategy::most_recently_used => workers_iter.find(|(_, w)| can_run(w)),
         };
         let worker_id = workers_iter.map(|(_, w)| &w.id);
         // We need to "touch" the worker to ensure it gets re-ordered in the LRUCache, since it was selected.
@@ -216,6 +250,12 @@ struct SimpleSchedulerImpl {
     queued_actions: BTreeMap<Arc<ActionInfo>, AwaitedAction>,
     workers: Workers,
     active_actions: HashMap<Arc<ActionInfo>, RunningAction>,
+    /// Actions that were cancelled while running, keyed by the worker that
+    /// is still executing them. The same action may be re-added, run and
+    /// cancelled on another worker before the first one reports back. The
+    /// worker's slot stays occupied until it reports back, but its result is
+    /// discarded.
+    cancelled_actions: HashMap<(ActionInfoHashKey, WorkerId), Arc<ActionInfo>>,
     // These actions completed recently but had no listener, they might have
     // completed while the caller was thinking about calling wait_execution, so
     // keep their completion state around for a while to send back.
@@ -227,6 +267,12 @@ struct SimpleSchedulerImpl {
     worker_timeout_s: u64,
     /// Default times a job can retry before failing.
     max_job_retries: usize,
+    /// Time in seconds, beyond an action's timeout, before it is killed.
+    action_timeout_grace_s: u64,
+    /// Whether draining a worker kills and requeues its running actions.
+    kill_actions_on_drain: bool,
+    /// Number of bytes of stdout and of stderr kept for each running action.
+    max_action_output_bytes: u64,
     /// Notify task<->worker matching engine that work needs to be done.
     tasks_or_workers_change_notify: Arc<Notify>,
     metrics: Arc<Metrics>,
@@ -256,16 +302,18 @@ impl SimpleSchedulerImpl {
         // Check to see if the action is running, if it is and cacheable, merge the actions.
         if let Some(running_action) = self.active_actions.get_mut(&action_info) {
             self.metrics.add_action_joined_running_action.inc();
+            running_action.action.clients += 1;
             return Ok(Self::subscribe_to_channel(&running_action.action));
         }
 
         // Check to see if the action is queued, if it is and cacheable, merge the actions.
         if let Some(mut arc_action_info) = self.queued_actions_set.take(&action_info) {
-            let (original_action_info, queued_action) = self
+            let (original_action_info, mut queued_action) = self
                 .queued_actions
                 .remove_entry(&arc_action_info)
                 .err_tip(|| "Internal error queued_actions and queued_actions_set should match")?;
             self.metrics.add_action_joined_queued_action.inc();
+            queued_action.clients += 1;
 
             let new_priority = cmp::max(original_action_info.priority, action_info.priority);
             drop(original_action_info); // This increases the chance Arc::make_mut won't copy.
@@ -307,6 +355,8 @@ impl SimpleSchedulerImpl {
                 notify_channel: tx,
                 attempts: 0,
                 last_error: None,
+                clients: 1,
+                cancel_requests: 0,
             },
         );
 
@@ -346,6 +396,377 @@ impl SimpleSchedulerImpl {
             .map(Self::subscribe_to_channel)
     }
 
+    fn list_actions(&self) -> Vec<Arc<ActionState>> {
+        self.queued_actions
+            .values()
+            .map(|awaited_action| awaited_action.current_state.clone())
+            .chain(
+                self.active_actions
+                    .values()
+                    .map(|running_action| running_action.action.current_state.clone()),
+            )
+            .chain(
+                self.recently_completed_actions
+                    .iter()
+                    .map(|completed_action| completed_action.state.clone()),
+            )
+            .collect()
+    }
+
+    fn list_scheduled_actions(&self) -> Vec<ScheduledActionInfo> {
+        let now = SystemTime::now();
+        let age_s = |action_info: &ActionInfo| {
+            now.duration_since(action_info.insert_timestamp)
+                .unwrap_or_default()
+                .as_secs()
+        };
+        let platform_properties = |action_info: &ActionInfo| {
+            action_info
+                .platform_properties
+                .properties
+                .iter()
+                .map(|(name, value)| (name.clone(), value.as_str().into_owned()))
+                .collect()
+        };
+        // Queued actions are listed in the order they will be scheduled.
+        self.queued_actions
+            .values()
+            .rev()
+            .map(|awaited_action| ScheduledActionInfo {
+                action_name: awaited_action.action_info.unique_qualifier.action_name(),
+                stage: ScheduledActionStage::Queued,
+                priority: awaited_action.action_info.priority,
+                platform_properties: platform_properties(&awaited_action.action_info),
+                age_s: age_s(&awaited_action.action_info),
+                attempts: awaited_action.attempts,
+                worker_id: None,
+            })
+            .chain(self.active_actions.values().map(|running_action| {
+                ScheduledActionInfo {
+                    action_name: running_action
+                        .action
+                        .action_info
+                        .unique_qualifier
+                        .action_name(),
+                    stage: ScheduledActionStage::Executing,
+                    priority: running_action.action.action_info.priority,
+                    platform_properties: platform_properties(&running_action.action.action_info),
+                    age_s: age_s(&running_action.action.action_info),
+                    attempts: running_action.action.attempts,
+                    worker_id: Some(running_action.worker_id.to_string()),
+                }
+            }))
+            .collect()
+    }
+
+    fn list_completed_actions(&self) -> Vec<CompletedActionInfo> {
+        let mut completed_actions: Vec<&CompletedAction> =
+            self.recently_completed_actions.iter().collect();
+        // Most recently completed first.
+        completed_actions.sort_unstable_by(|a, b| b.completed_time.cmp(&a.completed_time));
+        completed_actions
+            .into_iter()
+            .map(|completed_action| {
+                let (exit_code, worker, worker_duration_ms, error) =
+                    match &completed_action.state.stage {
+                        ActionStage::Completed(action_result) => {
+                            let metadata = &action_result.execution_metadata;
+                            (
+                                action_result.exit_code,
+                                metadata.worker.clone(),
+                                metadata
+                                    .worker_completed_timestamp
+                                    .duration_since(metadata.worker_start_timestamp)
+                                    .unwrap_or_default()
+                                    .as_millis() as u64,
+                                action_result.error.as_ref().map(|err| format!("{err}")),
+                            )
+                        }
+                        // Cached results were not executed by a worker of ours.
+                        ActionStage::CompletedFromCache(action_result) => (
+                            action_result.exit_code,
+                            action_result
+                                .execution_metadata
+                                .as_ref()
+                                .map(|metadata| metadata.worker.clone())
+                                .unwrap_or_default(),
+                            0,
+                            None,
+                        ),
+                        stage => (
+                            INTERNAL_ERROR_EXIT_CODE,
+                            String::new(),
+                            0,
+                            Some(format!("Action completed with unexpected stage {stage:?}")),
+                        ),
+                    };
+                CompletedActionInfo {
+                    action_name: completed_action.state.unique_qualifier.action_name(),
+                    completed_timestamp: completed_action
+                        .completed_time
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs(),
+                    exit_code,
+                    worker,
+                    worker_duration_ms,
+                    error,
+                }
+            })
+            .collect()
+    }
+
+    /// Changes the priority of a queued action. Like when an action with a
+    /// higher priority joins a queued action, the action is reinserted so it
+    /// is sorted by its new priority.
+    fn set_action_priority(
+        &mut self,
+        unique_qualifier: &ActionInfoHashKey,
+        priority: i32,
+    ) -> Result<bool, Error> {
+        let Some(mut arc_action_info) = self.queued_actions_set.take(unique_qualifier) else {
+            return Ok(false);
+        };
+        let (original_action_info, mut queued_action) = self
+            .queued_actions
+            .remove_entry(&arc_action_info)
+            .err_tip(|| "Internal error queued_actions and queued_actions_set should match")?;
+        drop(original_action_info); // This increases the chance Arc::make_mut won't copy.
+
+        Arc::make_mut(&mut arc_action_info).priority = priority;
+        queued_action.action_info = arc_action_info.clone();
+
+        self.queued_actions
+            .insert(arc_action_info.clone(), queued_action);
+        self.queued_actions_set.insert(arc_action_info);
+        self.tasks_or_workers_change_notify.notify_one();
+        Ok(true)
+    }
+
+    fn action_output(
+        &self,
+        unique_qualifier: &ActionInfoHashKey,
+        stream: ActionOutputStream,
+    ) -> Result<Arc<ActionOutput>, Error> {
+        self.active_actions
+            .get(unique_qualifier)
+            .map(|running_action| running_action.output.get(stream).clone())
+            .ok_or_else(|| {
+                make_err!(
+                    Code::NotFound,
+                    "Action {} is not executing",
+                    unique_qualifier.action_name()
+                )
+            })
+    }
+
+    fn append_action_output(
+        &mut self,
+        worker_id: &WorkerId,
+        action_info_hash_key: &ActionInfoHashKey,
+        stdout: Bytes,
+        stderr: Bytes,
+    ) -> Result<(), Error> {
+        // Nobody is interested in the output of actions that were cancelled.
+        if self
+            .cancelled_actions
+            .contains_key(&(action_info_hash_key.clone(), *worker_id))
+        {
+            return Ok(());
+        }
+        let running_action = self
+            .active_actions
+            .get(action_info_hash_key)
+            .ok_or_else(|| {
+                make_err!(
+                    Code::NotFound,
+                    "Got output for action {} that is not executing",
+                    action_info_hash_key.action_name()
+                )
+            })?;
+        if running_action.worker_id != *worker_id {
+            self.metrics.append_action_output_from_wrong_worker.inc();
+            return Err(make_input_err!(
+                "Got output for action {} from worker {worker_id}, but it is running on worker {}",
+                action_info_hash_key.action_name(),
+                running_action.worker_id
+            ));
+        }
+        let stdout_output = running_action.output.get(ActionOutputStream::Stdout);
+        let stderr_output = running_action.output.get(ActionOutputStream::Stderr);
+        stdout_output.append(stdout);
+        stderr_output.append(stderr);
+        // Tell the worker to stop sending output nobody could read.
+        if stdout_output.is_full() || stderr_output.is_full() {
+            return Err(make_err!(
+                Code::ResourceExhausted,
+                "Output of action {} reached max_action_output_bytes, no more output is streamed",
+                action_info_hash_key.action_name()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Withdraws one client from a queued or running action. Once no other
+    /// client is waiting on the action it is removed from the scheduler and
+    /// all listeners are notified that it was cancelled. Running actions are
+    /// remembered in `cancelled_actions` until their worker reports back.
+    /// Clients that went away no longer count as waiting.
+    fn cancel_action(&mut self, unique_qualifier: &ActionInfoHashKey) -> bool {
+        let awaited_action =
+            if let Some(action_info) = self.queued_actions_set.get(unique_qualifier) {
+                self.queued_actions.get_mut(action_info)
+            } else {
+                self.active_actions
+                    .get_mut(unique_qualifier)
+                    .map(|running_action| &mut running_action.action)
+            };
+        if let Some(awaited_action) = awaited_action {
+            awaited_action.cancel_requests += 1;
+            // Clients that dropped their receiver are gone, while receivers
+            // beyond `clients` only watch the action.
+            let waiting_clients = awaited_action
+                .notify_channel
+                .receiver_count()
+                .min(awaited_action.clients);
+            if waiting_clients > awaited_action.cancel_requests {
+                self.metrics.cancel_action_has_waiters.inc();
+                return true;
+            }
+        }
+        let (action_info, awaited_action) =
+            if let Some(action_info) = self.queued_actions_set.take(unique_qualifier) {
+                let Some(awaited_action) = self.queued_actions.remove(&action_info) else {
+                    error!(
+                        "queued_actions out of sync with itself for action {}",
+                        action_info.digest().hash_str()
+                    );
+                    return false;
+                };
+                self.metrics.cancel_action_queued.inc();
+                (action_info, awaited_action)
+            } else if let Some((action_info, running_action)) =
+                self.active_actions.remove_entry(unique_qualifier)
+            {
+                self.metrics.cancel_action_running.inc();
+                self.kill_action_on_worker(&action_info, running_action.worker_id);
+                (action_info, running_action.action)
+            } else {
+                self.metrics.cancel_action_not_found.inc();
+                return false;
+            };
+
+        let err = make_err!(
+            Code::Cancelled,
+            "Action {} was cancelled",
+            action_info.digest().hash_str()
+        );
+        self.fail_awaited_action(awaited_action, err);
+        true
+    }
+
+    /// Completes an action that is no longer queued or running with `err`
+    /// and notifies its listeners.
+    fn fail_awaited_action(&mut self, mut awaited_action: AwaitedAction, err: Error) {
+        Arc::make_mut(&mut awaited_action.current_state).stage =
+            ActionStage::Completed(ActionResult {
+                error: Some(err),
+                ..ActionResult::default()
+            });
+        // Listeners may have already gone away, that is fine.
+        let _ = awaited_action
+            .notify_channel
+            .send(awaited_action.current_state.clone());
+        self.recently_completed_actions.insert(CompletedAction {
+            completed_time: SystemTime::now(),
+            state: awaited_action.current_state,
+        });
+    }
+
+    /// Asks the worker to kill an action that was removed from
+    /// `active_actions`. The worker's result for it will be discarded.
+    fn kill_action_on_worker(&mut self, action_info: &Arc<ActionInfo>, worker_id: WorkerId) {
+        self.cancelled_actions.insert(
+            (action_info.unique_qualifier.clone(), worker_id),
+            action_info.clone(),
+        );
+        let Some(worker) = self.workers.workers.get_mut(&worker_id) else {
+            return;
+        };
+        if worker
+            .notify_update(WorkerUpdate::KillAction(action_info.clone()))
+            .is_err()
+        {
+            let err = make_err!(
+                Code::Internal,
+                "Worker command failed, removing worker {}",
+                worker_id
+            );
+            warn!("{:?}", err);
+            self.immediate_evict_worker(&worker_id, err);
+        }
+    }
+
+    /// Kills actions that have been running for longer than their timeout
+    /// plus `action_timeout_grace_s`.
+    fn kill_timedout_actions(&mut self, now_timestamp: WorkerTimestamp) {
+        let timedout_actions: Vec<Arc<ActionInfo>> = self
+            .active_actions
+            .iter()
+            .filter(|(action_info, running_action)| {
+                action_info.timeout != Duration::ZERO
+                    && running_action
+                        .started_timestamp
+                        .saturating_add(action_info.timeout.as_secs())
+                        .saturating_add(self.action_timeout_grace_s)
+                        < now_timestamp
+            })
+            .map(|(action_info, _)| action_info.clone())
+            .collect();
+        for action_info in timedout_actions {
+            let Some(running_action) = self.active_actions.remove(&action_info) else {
+                continue;
+            };
+            self.metrics.timedout_actions.inc();
+            let err = make_err!(
+                Code::DeadlineExceeded,
+                "Action {} did not complete within its timeout of {} seconds on worker {}",
+                action_info.digest().hash_str(),
+                action_info.timeout.as_secs(),
+                running_action.worker_id
+            );
+            warn!("{:?}", err);
+            self.kill_action_on_worker(&action_info, running_action.worker_id);
+            self.fail_awaited_action(running_action.action, err);
+        }
+    }
+
+    /// If the action was cancelled while running on `worker_id`, releases it
+    /// from the worker once `is_finished` and returns true, so the update is
+    /// ignored.
+    fn handle_cancelled_action_update(
+        &mut self,
+        worker_id: &WorkerId,
+        action_info_hash_key: &ActionInfoHashKey,
+        is_finished: bool,
+    ) -> bool {
+        let key = (action_info_hash_key.clone(), *worker_id);
+        if !self.cancelled_actions.contains_key(&key) {
+            return false;
+        }
+        if !is_finished {
+            return true;
+        }
+        let Some(action_info) = self.cancelled_actions.remove(&key) else {
+            return false;
+        };
+        if let Some(worker) = self.workers.workers.get_mut(worker_id) {
+            worker.complete_action(&action_info);
+        }
+        self.tasks_or_workers_change_notify.notify_one();
+        true
+    }
+
     fn retry_action(&mut self, action_info: &Arc<ActionInfo>, worker_id: &WorkerId, err: Error) {
         match self.active_actions.remove(action_info) {
             Some(running_action) => {
@@ -407,6 +828,14 @@ impl SimpleSchedulerImpl {
             // We create a temporary Vec to avoid doubt about a possible code
             // path touching the worker.running_action_infos elsewhere.
             for action_info in worker.running_action_infos.drain() {
+                if self
+                    .cancelled_actions
+                    .remove(&(action_info.unique_qualifier.clone(), *worker_id))
+                    .is_some()
+                {
+                    // Nobody is waiting on a cancelled action, so don't retry it.
+                    continue;
+                }
                 self.metrics.workers_evicted_with_running_action.inc();
                 self.retry_action(&action_info, worker_id, err.clone());
             }
@@ -424,6 +853,26 @@ impl SimpleSchedulerImpl {
             .err_tip(|| format!("Worker {worker_id} doesn't exist in the pool"))?;
         self.metrics.workers_drained.inc();
         worker.is_draining = is_draining;
+        if is_draining && self.kill_actions_on_drain {
+            let running_action_infos: Vec<Arc<ActionInfo>> =
+                worker.running_action_infos.iter().cloned().collect();
+            for action_info in running_action_infos {
+                let Some(running_action) = self.active_actions.remove(&action_info) else {
+                    continue;
+                };
+                self.metrics.drained_actions_requeued.inc();
+                self.kill_action_on_worker(&action_info, worker_id);
+                // Being drained is not the action's fault, so don't count the attempt.
+                let mut awaited_action = running_action.action;
+                awaited_action.attempts = awaited_action.attempts.saturating_sub(1);
+                Arc::make_mut(&mut awaited_action.current_state).stage = ActionStage::Queued;
+                let _ = awaited_action
+                    .notify_channel
+                    .send(awaited_action.current_state.clone());
+                self.queued_actions_set.insert(action_info.clone());
+                self.queued_actions.insert(action_info, awaited_action);
+            }
+        }
         self.tasks_or_workers_change_notify.notify_one();
         Ok(())
     }
@@ -497,7 +946,12 @@ impl SimpleSchedulerImpl {
                 action_info.clone(),
                 RunningAction {
                     worker_id,
+                    started_timestamp: SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs(),
                     action: awaited_action,
+                    output: ActionOutputs::new(self.max_action_output_bytes),
                 },
             );
         }
@@ -510,6 +964,9 @@ impl SimpleSchedulerImpl {
         err: Error,
     ) {
         self.metrics.update_action_with_internal_error.inc();
+        if self.handle_cancelled_action_update(worker_id, action_info_hash_key, true) {
+            return;
+        }
         let Some((action_info, mut running_action)) =
             self.active_actions.remove_entry(action_info_hash_key)
         else {
@@ -581,6 +1038,14 @@ impl SimpleSchedulerImpl {
             return Err(err);
         }
 
+        if self.handle_cancelled_action_update(
+            worker_id,
+            action_info_hash_key,
+            action_stage.is_finished(),
+        ) {
+            return Ok(());
+        }
+
         let (action_info, mut running_action) = self
             .active_actions
             .remove_entry(action_info_hash_key)
@@ -644,7 +1109,8 @@ impl SimpleSchedulerImpl {
 /// should be held in this struct.
 pub struct SimpleScheduler {
     inner: Arc<Mutex<SimpleSchedulerImpl>>,
-    platform_property_manager: Arc<PlatformPropertyManager>,
+    /// Replaced when the supported platform properties change.
+    platform_property_manager: Mutex<Arc<PlatformPropertyManager>>,
     task_worker_matching_future: JoinHandle<()>,
     metrics: Arc<Metrics>,
 }
@@ -694,6 +1160,16 @@ impl SimpleScheduler {
             max_job_retries = DEFAULT_MAX_JOB_RETRIES;
         }
 
+        let mut action_timeout_grace_s = scheduler_cfg.action_timeout_grace_s;
+        if action_timeout_grace_s == 0 {
+            action_timeout_grace_s = DEFAULT_ACTION_TIMEOUT_GRACE_S;
+        }
+
+        let mut max_action_output_bytes = scheduler_cfg.max_action_output_bytes;
+        if max_action_output_bytes == 0 {
+            max_action_output_bytes = DEFAULT_MAX_ACTION_OUTPUT_BYTES;
+        }
+
         let tasks_or_workers_change_notify = Arc::new(Notify::new());
 
         let metrics = Arc::new(Metrics::default());
@@ -703,17 +1179,21 @@ impl SimpleScheduler {
             queued_actions: BTreeMap::new(),
             workers: Workers::new(scheduler_cfg.allocation_strategy),
             active_actions: HashMap::new(),
+            cancelled_actions: HashMap::new(),
             recently_completed_actions: HashSet::new(),
             retain_completed_for: Duration::new(retain_completed_for_s, 0),
             worker_timeout_s,
             max_job_retries,
+            action_timeout_grace_s,
+            kill_actions_on_drain: scheduler_cfg.kill_actions_on_drain,
+            max_action_output_bytes,
             tasks_or_workers_change_notify: tasks_or_workers_change_notify.clone(),
             metrics: metrics.clone(),
         }));
         let weak_inner = Arc::downgrade(&inner);
         Self {
             inner,
-            platform_property_manager,
+            platform_property_manager: Mutex::new(platform_property_manager),
             task_worker_matching_future: tokio::spawn(async move {
                 // Break out of the loop only when the inner is dropped.
                 loop {
@@ -787,7 +1267,7 @@ impl ActionScheduler for SimpleScheduler {
         &self,
         _instance_name: &str,
     ) -> Result<Arc<PlatformPropertyManager>, Error> {
-        Ok(self.platform_property_manager.clone())
+        Ok(self.platform_property_manager.lock().clone())
     }
 
     async fn add_action(
@@ -816,6 +1296,40 @@ impl ActionScheduler for SimpleScheduler {
         result
     }
 
+    async fn list_actions(&self) -> Result<Vec<Arc<ActionState>>, Error> {
+        Ok(self.get_inner_lock().list_actions())
+    }
+
+    async fn cancel_action(&self, unique_qualifier: &ActionInfoHashKey) -> Result<bool, Error> {
+        Ok(self.get_inner_lock().cancel_action(unique_qualifier))
+    }
+
+    async fn list_scheduled_actions(&self) -> Result<Vec<ScheduledActionInfo>, Error> {
+        Ok(self.get_inner_lock().list_scheduled_actions())
+    }
+
+    async fn list_completed_actions(&self) -> Result<Vec<CompletedActionInfo>, Error> {
+        Ok(self.get_inner_lock().list_completed_actions())
+    }
+
+    async fn set_action_priority(
+        &self,
+        unique_qualifier: &ActionInfoHashKey,
+        priority: i32,
+    ) -> Result<bool, Error> {
+        self.get_inner_lock()
+            .set_action_priority(unique_qualifier, priority)
+    }
+
+    async fn action_output(
+        &self,
+        unique_qualifier: &ActionInfoHashKey,
+        stream: ActionOutputStream,
+    ) -> Result<Arc<ActionOutput>, Error> {
+        self.get_inner_lock()
+            .action_output(unique_qualifier, stream)
+    }
+
     async fn clean_recently_completed_actions(&self) {
         self.get_inner_lock().clean_recently_completed_actions();
         self.metrics.clean_recently_completed_actions.inc()
@@ -828,8 +1342,17 @@ impl ActionScheduler for SimpleScheduler {
 
 #[async_trait]
 impl WorkerScheduler for SimpleScheduler {
-    fn get_platform_property_manager(&self) -> &PlatformPropertyManager {
-        self.platform_property_manager.as_ref()
+    fn get_platform_property_manager(&self) -> Arc<PlatformPropertyManager> {
+        self.platform_property_manager.lock().clone()
+    }
+
+    fn set_supported_platform_properties(
+        &self,
+        supported_platform_properties: std::collections::HashMap<String, PropertyType>,
+    ) -> Result<(), Error> {
+        *self.platform_property_manager.lock() =
+            Arc::new(PlatformPropertyManager::new(supported_platform_properties));
+        Ok(())
     }
 
     async fn add_worker(&self, worker: Worker) -> Result<(), Error> {
@@ -870,6 +1393,19 @@ impl WorkerScheduler for SimpleScheduler {
             .wrap(move || inner.update_action(worker_id, action_info_hash_key, action_stage))
     }
 
+    async fn append_action_output(
+        &self,
+        worker_id: &WorkerId,
+        action_info_hash_key: &ActionInfoHashKey,
+        stdout: Bytes,
+        stderr: Bytes,
+    ) -> Result<(), Error> {
+        let mut inner = self.get_inner_lock();
+        self.metrics.append_action_output.wrap(move || {
+            inner.append_action_output(worker_id, action_info_hash_key, stdout, stderr)
+        })
+    }
+
     async fn worker_keep_alive_received(
         &self,
         worker_id: &WorkerId,
@@ -916,6 +1452,7 @@ impl WorkerScheduler for SimpleScheduler {
                 warn!("{:?}", err);
                 inner.immediate_evict_worker(worker_id, err);
             }
+            inner.kill_timedout_actions(now_timestamp);
 
             Ok(())
         })
@@ -926,12 +1463,46 @@ impl WorkerScheduler for SimpleScheduler {
         inner.set_drain_worker(worker_id, is_draining)
     }
 
+    async fn list_workers(&self) -> Result<Vec<WorkerInfo>, Error> {
+        let inner = self.get_inner_lock();
+        Ok(inner
+            .workers
+            .workers
+            .iter()
+            .map(|(_, worker)| worker.info())
+            .collect())
+    }
+
     fn register_metrics(self: Arc<Self>, _registry: &mut Registry) {
         // We do not register anything here because we only want to register metrics
         // once and we rely on the `ActionScheduler::register_metrics()` to do that.
     }
 }
 
+#[async_trait]
+impl HealthStatusIndicator for SimpleScheduler {
+    fn get_name(&self) -> &'static str {
+        "SimpleScheduler"
+    }
+
+    async fn check_health(&self, _namespace: Cow<'static, str>) -> HealthStatus {
+        let inner = self.get_inner_lock();
+        let workers = inner.workers.workers.len();
+        let queued_actions = inner.queued_actions.len();
+        // Workers may connect at any time, so having none is not a failure.
+        if workers == 0 {
+            return HealthStatus::new_warning(
+                self,
+                format!("No workers connected, {queued_actions} actions queued").into(),
+            );
+        }
+        HealthStatus::new_ok(
+            self,
+            format!("{workers} workers connected, {queued_actions} actions queued").into(),
+        )
+    }
+}
+
 impl Drop for SimpleScheduler {
     fn drop(&mut self) {
         self.task_worker_matching_future.abort();
@@ -959,6 +1530,11 @@ impl MetricsComponent for SimpleScheduler {
                 &inner.active_actions.len(),
                 "The number of running actions.",
             );
+            c.publish(
+                "cancelled_running_actions_total",
+                &inner.cancelled_actions.len(),
+                "The number of cancelled actions still occupying a worker.",
+            );
             c.publish(
                 "recently_completed_actions_total",
                 &inner.recently_completed_actions.len(),
@@ -1045,6 +1621,11 @@ impl MetricsComponent for CompletedAction {
 impl MetricsComponent for RunningAction {
     fn gather_metrics(&self, c: &mut CollectorState) {
         c.publish("action", &self.action, "");
+        c.publish(
+            "started_timestamp",
+            &self.started_timestamp,
+            "When the action was sent to the worker.",
+        );
     }
 }
 
@@ -1098,6 +1679,14 @@ struct Metrics {
     add_action_joined_running_action: CounterWithTime,
     add_action_joined_queued_action: CounterWithTime,
     add_action_new_action_created: CounterWithTime,
+    cancel_action_queued: CounterWithTime,
+    cancel_action_running: CounterWithTime,
+    cancel_action_not_found: CounterWithTime,
+    cancel_action_has_waiters: CounterWithTime,
+    timedout_actions: CounterWithTime,
+    drained_actions_requeued: CounterWithTime,
+    append_action_output: FuncCounterWrapper,
+    append_action_output_from_wrong_worker: CounterWithTime,
     add_worker: FuncCounterWrapper,
     timedout_workers: CounterWithTime,
     lock_stall_time: AtomicU64,
@@ -1246,6 +1835,52 @@ impl Metrics {
                 vec![("result".into(), "new_action_created".into())],
             );
         }
+        {
+            c.publish_with_labels(
+                "cancel_action",
+                &self.cancel_action_queued,
+                "Stats about cancel_action().",
+                vec![("result".into(), "queued".into())],
+            );
+            c.publish_with_labels(
+                "cancel_action",
+                &self.cancel_action_running,
+                "Stats about cancel_action().",
+                vec![("result".into(), "running".into())],
+            );
+            c.publish_with_labels(
+                "cancel_action",
+                &self.cancel_action_not_found,
+                "Stats about cancel_action().",
+                vec![("result".into(), "not_found".into())],
+            );
+            c.publish_with_labels(
+                "cancel_action",
+                &self.cancel_action_has_waiters,
+                "Stats about cancel_action().",
+                vec![("result".into(), "has_waiters".into())],
+            );
+        }
+        c.publish(
+            "timedout_actions_total",
+            &self.timedout_actions,
+            "The number of actions killed by the scheduler for exceeding their timeout.",
+        );
+        c.publish(
+            "drained_actions_requeued_total",
+            &self.drained_actions_requeued,
+            "The number of running actions killed and requeued because their worker was drained.",
+        );
+        c.publish(
+            "append_action_output",
+            &self.append_action_output,
+            "Stats about workers sending the output of running actions to the scheduler.",
+        );
+        c.publish(
+            "append_action_output_from_wrong_worker",
+            &self.append_action_output_from_wrong_worker,
+            "The number of times a worker sent output for an action running on another worker.",
+        );
         c.publish(
             "add_worker",
             &self.add_worker,
@@ -12,20 +12,21 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use nativelink_error::{make_err, make_input_err, Code, Error, ResultExt};
 use nativelink_proto::com::github::trace_machina::nativelink::remote_execution::{
-    update_for_worker, ConnectionResult, StartExecute, UpdateForWorker,
+    update_for_worker, ConnectionResult, KillAction, StartExecute, UpdateForWorker,
 };
 use nativelink_util::action_messages::ActionInfo;
 use nativelink_util::metrics_utils::{
     CollectorState, CounterWithTime, FuncCounterWrapper, MetricsComponent,
 };
 use nativelink_util::platform_properties::{PlatformProperties, PlatformPropertyValue};
+use serde::Serialize;
 use tokio::sync::mpsc::UnboundedSender;
 use uuid::Uuid;
 
@@ -72,6 +73,9 @@ pub enum WorkerUpdate {
 
     /// Request that the worker is no longer in the pool and may discard any jobs.
     Disconnect,
+
+    /// Requests that the worker stops executing this action and kills its processes.
+    KillAction(Arc<ActionInfo>),
 }
 
 /// Represents a connection to a worker and used as the medium to
@@ -156,6 +160,7 @@ impl Worker {
                 run_action: FuncCounterWrapper::default(),
                 keep_alive: FuncCounterWrapper::default(),
                 notify_disconnect: CounterWithTime::default(),
+                notify_kill_action: CounterWithTime::default(),
             }),
         }
     }
@@ -180,6 +185,15 @@ impl Worker {
                 self.metrics.notify_disconnect.inc();
                 send_msg_to_worker(&mut self.tx, update_for_worker::Update::Disconnect(()))
             }
+            WorkerUpdate::KillAction(action_info) => {
+                self.metrics.notify_kill_action.inc();
+                send_msg_to_worker(
+                    &mut self.tx,
+                    update_for_worker::Update::KillAction(KillAction {
+                        action_name: action_info.unique_qualifier.action_name(),
+                    }),
+                )
+            }
         }
     }
 
@@ -241,6 +255,45 @@ impl Worker {
     pub fn can_accept_work(&self) -> bool {
         !self.is_paused && !self.is_draining
     }
+
+    /// Returns a snapshot of the worker's current state.
+    pub fn info(&self) -> WorkerInfo {
+        let mut running_actions: Vec<String> = self
+            .running_action_infos
+            .iter()
+            .map(|action_info| action_info.unique_qualifier.action_name())
+            .collect();
+        running_actions.sort_unstable();
+        WorkerInfo {
+            worker_id: self.id.to_string(),
+            platform_properties: self
+                .platform_properties
+                .properties
+                .iter()
+                .map(|(name, value)| (name.clone(), value.as_str().into_owned()))
+                .collect(),
+            running_actions,
+            last_update_timestamp: self.last_update_timestamp,
+            is_paused: self.is_paused,
+            is_draining: self.is_draining,
+        }
+    }
+}
+
+/// Snapshot of a worker, used to inspect the worker pool.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WorkerInfo {
+    pub worker_id: String,
+    /// Platform properties of the worker. `Minimum` properties only count
+    /// what is not used by the running actions.
+    pub platform_properties: BTreeMap<String, String>,
+    /// Names of the actions running on the worker.
+    pub running_actions: Vec<String>,
+    /// Timestamp of last time this worker had been communicated with.
+    pub last_update_timestamp: WorkerTimestamp,
+    /// Whether the worker rejected the last action due to back pressure.
+    pub is_paused: bool,
+    pub is_draining: bool,
 }
 
 impl PartialEq for Worker {
@@ -264,6 +317,7 @@ struct Metrics {
     run_action: FuncCounterWrapper,
     keep_alive: FuncCounterWrapper,
     notify_disconnect: CounterWithTime,
+    notify_kill_action: CounterWithTime,
 }
 
 impl MetricsComponent for Worker {
@@ -298,6 +352,12 @@ impl MetricsComponent for Worker {
             "The number of notify_disconnect sent to this worker.",
             vec![("worker_id".into(), format!("{}", self.id).into())],
         );
+        c.publish_with_labels(
+            "notify_kill_action",
+            &self.metrics.notify_kill_action,
+            "The number of kill_action sent to this worker.",
+            vec![("worker_id".into(), format!("{}", self.id).into())],
+        );
 
         // Publish info about current state of worker.
         c.publish_with_labels(
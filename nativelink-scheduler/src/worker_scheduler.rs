@@ -12,22 +12,38 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use nativelink_error::Error;
+use bytes::Bytes;
+use nativelink_config::schedulers::PropertyType;
+use nativelink_error::{make_err, Code, Error};
 use nativelink_util::action_messages::{ActionInfoHashKey, ActionStage};
 use nativelink_util::metrics_utils::Registry;
 
 use crate::platform_property_manager::PlatformPropertyManager;
-use crate::worker::{Worker, WorkerId, WorkerTimestamp};
+use crate::worker::{Worker, WorkerId, WorkerInfo, WorkerTimestamp};
 
 /// WorkerScheduler interface is responsible for interactions between the scheduler
 /// and worker related operations.
 #[async_trait]
 pub trait WorkerScheduler: Sync + Send + Unpin {
     /// Returns the platform property manager.
-    fn get_platform_property_manager(&self) -> &PlatformPropertyManager;
+    fn get_platform_property_manager(&self) -> Arc<PlatformPropertyManager>;
+
+    /// Replaces the platform properties the scheduler supports. Workers that
+    /// are already connected and actions that are already queued keep the
+    /// properties they were given.
+    fn set_supported_platform_properties(
+        &self,
+        _supported_platform_properties: HashMap<String, PropertyType>,
+    ) -> Result<(), Error> {
+        Err(make_err!(
+            Code::Unimplemented,
+            "Changing the supported platform properties is not supported by this scheduler"
+        ))
+    }
 
     /// Adds a worker to the scheduler and begin using it to execute actions (when able).
     async fn add_worker(&self, worker: Worker) -> Result<(), Error>;
@@ -49,6 +65,17 @@ pub trait WorkerScheduler: Sync + Send + Unpin {
         action_stage: ActionStage,
     ) -> Result<(), Error>;
 
+    /// Appends output the action wrote while running on the worker. Returns
+    /// `ResourceExhausted` once no more output is kept for the action, the
+    /// worker should stop sending output then.
+    async fn append_action_output(
+        &self,
+        worker_id: &WorkerId,
+        action_info_hash_key: &ActionInfoHashKey,
+        stdout: Bytes,
+        stderr: Bytes,
+    ) -> Result<(), Error>;
+
     /// Event for when the keep alive message was received from the worker.
     async fn worker_keep_alive_received(
         &self,
@@ -66,6 +93,14 @@ pub trait WorkerScheduler: Sync + Send + Unpin {
     /// Sets if the worker is draining or not.
     async fn set_drain_worker(&self, worker_id: WorkerId, is_draining: bool) -> Result<(), Error>;
 
+    /// Returns a snapshot of every worker in the pool.
+    async fn list_workers(&self) -> Result<Vec<WorkerInfo>, Error> {
+        Err(make_err!(
+            Code::Unimplemented,
+            "list_workers not implemented for this scheduler"
+        ))
+    }
+
     /// Register the metrics for the worker scheduler.
     fn register_metrics(self: Arc<Self>, _registry: &mut Registry) {}
 }
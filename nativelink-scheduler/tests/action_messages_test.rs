@@ -16,16 +16,20 @@ use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
-use nativelink_error::Error;
-use nativelink_proto::build::bazel::remote::execution::v2::ExecuteResponse;
+use nativelink_error::{Code, Error};
+use nativelink_proto::build::bazel::remote::execution::v2::{
+    ExecuteOperationMetadata, ExecuteResponse,
+};
 use nativelink_proto::google::longrunning::{operation, Operation};
 use nativelink_proto::google::rpc::Status;
 use nativelink_util::action_messages::{
-    ActionInfo, ActionInfoHashKey, ActionResult, ActionStage, ActionState, ExecutionMetadata,
+    ActionInfo, ActionInfoHashKey, ActionOutputStream, ActionResult, ActionStage, ActionState,
+    ExecutionMetadata,
 };
 use nativelink_util::common::DigestInfo;
 use nativelink_util::digest_hasher::DigestHasherFunc;
 use nativelink_util::platform_properties::PlatformProperties;
+use prost::Message;
 
 const NOW_TIME: u64 = 10000;
 
@@ -213,4 +217,70 @@ mod action_messages_tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn action_output_stream_name_round_trip_test() -> Result<(), Error> {
+        let unique_qualifier = ActionInfoHashKey {
+            instance_name: "foo/instance".to_string(),
+            digest: DigestInfo::new([1u8; 32], 5),
+            salt: 0x1a,
+        };
+        let name = unique_qualifier.output_stream_name(ActionOutputStream::Stderr);
+        assert_eq!(
+            name,
+            format!(
+                "foo/instance/action-logs/{}-5/1A/stderr",
+                DigestInfo::new([1u8; 32], 5).hash_str()
+            )
+        );
+        assert_eq!(
+            ActionInfoHashKey::try_from_output_stream_name(&name)?,
+            (unique_qualifier, ActionOutputStream::Stderr)
+        );
+        assert_eq!(
+            ActionInfoHashKey::try_from_output_stream_name("foo/blobs/abc/5")
+                .err()
+                .map(|e| e.code),
+            Some(Code::InvalidArgument)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn executing_operation_advertises_served_output_streams_test() -> Result<(), Error> {
+        let unique_qualifier = ActionInfoHashKey {
+            instance_name: "foo_instance".to_string(),
+            digest: DigestInfo::new([1u8; 32], 5),
+            salt: 0,
+        };
+        let action_state = ActionState {
+            unique_qualifier: unique_qualifier.clone(),
+            stage: ActionStage::Executing,
+        };
+        let decode_metadata = |operation: Operation| {
+            ExecuteOperationMetadata::decode(
+                operation
+                    .metadata
+                    .expect("Expected metadata")
+                    .value
+                    .as_slice(),
+            )
+        };
+
+        // Nothing is advertised unless the output is served.
+        let metadata = decode_metadata(action_state.clone().into())?;
+        assert_eq!(metadata.stdout_stream_name, "");
+        assert_eq!(metadata.stderr_stream_name, "");
+
+        let metadata = decode_metadata(action_state.into_operation(true))?;
+        assert_eq!(
+            metadata.stdout_stream_name,
+            unique_qualifier.output_stream_name(ActionOutputStream::Stdout)
+        );
+        assert_eq!(
+            metadata.stderr_stream_name,
+            unique_qualifier.output_stream_name(ActionOutputStream::Stderr)
+        );
+        Ok(())
+    }
 }
@@ -17,22 +17,27 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use bytes::Bytes;
+use nativelink_config::schedulers::PropertyType;
 use nativelink_error::{make_err, Code, Error, ResultExt};
-use nativelink_scheduler::action_scheduler::ActionScheduler;
+use nativelink_scheduler::action_scheduler::{
+    ActionScheduler, ScheduledActionInfo, ScheduledActionStage,
+};
 use nativelink_util::action_messages::{
-    ActionInfoHashKey, ActionResult, ActionStage, ActionState, DirectoryInfo, ExecutionMetadata,
-    FileInfo, NameOrPath, SymlinkInfo, INTERNAL_ERROR_EXIT_CODE,
+    ActionInfoHashKey, ActionOutputStream, ActionResult, ActionStage, ActionState, DirectoryInfo,
+    ExecutionMetadata, FileInfo, NameOrPath, SymlinkInfo, INTERNAL_ERROR_EXIT_CODE,
 };
+use nativelink_util::health_utils::{HealthStatus, HealthStatusIndicator};
 use nativelink_util::platform_properties::{PlatformProperties, PlatformPropertyValue};
 mod utils {
     pub(crate) mod scheduler_utils;
 }
 use nativelink_proto::build::bazel::remote::execution::v2::{digest_function, ExecuteRequest};
 use nativelink_proto::com::github::trace_machina::nativelink::remote_execution::{
-    update_for_worker, ConnectionResult, StartExecute, UpdateForWorker,
+    update_for_worker, ConnectionResult, KillAction, StartExecute, UpdateForWorker,
 };
 use nativelink_scheduler::simple_scheduler::SimpleScheduler;
-use nativelink_scheduler::worker::{Worker, WorkerId};
+use nativelink_scheduler::worker::{Worker, WorkerId, WorkerInfo};
 use nativelink_scheduler::worker_scheduler::WorkerScheduler;
 use nativelink_util::common::DigestInfo;
 use tokio::sync::{mpsc, watch};
@@ -1602,4 +1607,815 @@ mod scheduler_tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn cancel_queued_action_notifies_client_test() -> Result<(), Error> {
+        const WORKER_ID: WorkerId = WorkerId(0x1234_5678_9111);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &nativelink_config::schedulers::SimpleScheduler::default(),
+            || async move {},
+        );
+        let action_digest = DigestInfo::new([99u8; 32], 512);
+        let action_info_hash_key = ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: action_digest,
+            salt: 0,
+        };
+
+        let mut client_rx = setup_action(
+            &scheduler,
+            action_digest,
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+        assert_eq!(client_rx.borrow_and_update().stage, ActionStage::Queued);
+
+        assert!(scheduler.cancel_action(&action_info_hash_key).await?);
+        {
+            let action_state = client_rx.borrow_and_update();
+            let ActionStage::Completed(action_result) = &action_state.stage else {
+                panic!("Expected Completed, got : {:?}", action_state.stage);
+            };
+            assert_eq!(
+                action_result.error.as_ref().map(|err| err.code),
+                Some(Code::Cancelled)
+            );
+        }
+        // Cancelling a second time is a no-op.
+        assert!(!scheduler.cancel_action(&action_info_hash_key).await?);
+
+        let actions = scheduler.list_actions().await?;
+        assert_eq!(actions.len(), 1);
+        assert!(actions[0].stage.is_finished());
+
+        // A new worker must not be given the cancelled action.
+        let mut rx_from_worker =
+            setup_new_worker(&scheduler, WORKER_ID, PlatformProperties::default()).await?;
+        assert!(rx_from_worker.try_recv().is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cancel_deduplicated_action_waits_for_all_clients_test() -> Result<(), Error> {
+        const WORKER_ID: WorkerId = WorkerId(0x1234_5678_9111);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &nativelink_config::schedulers::SimpleScheduler::default(),
+            || async move {},
+        );
+        let action_digest = DigestInfo::new([99u8; 32], 512);
+        let action_info_hash_key = ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: action_digest,
+            salt: 0,
+        };
+
+        let mut rx_from_worker =
+            setup_new_worker(&scheduler, WORKER_ID, PlatformProperties::default()).await?;
+        let mut client1_rx = setup_action(
+            &scheduler,
+            action_digest,
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+        let mut client2_rx = setup_action(
+            &scheduler,
+            action_digest,
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+        match rx_from_worker.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        assert_eq!(client1_rx.borrow_and_update().stage, ActionStage::Executing);
+        assert_eq!(client2_rx.borrow_and_update().stage, ActionStage::Executing);
+
+        // The first client withdrawing must not affect the second client.
+        assert!(scheduler.cancel_action(&action_info_hash_key).await?);
+        drop(client1_rx);
+        assert!(rx_from_worker.try_recv().is_err());
+        assert_eq!(client2_rx.borrow_and_update().stage, ActionStage::Executing);
+
+        // Once the last client cancels the action is killed.
+        assert!(scheduler.cancel_action(&action_info_hash_key).await?);
+        assert_eq!(
+            rx_from_worker.recv().await.unwrap(),
+            UpdateForWorker {
+                update: Some(update_for_worker::Update::KillAction(KillAction {
+                    action_name: action_info_hash_key.action_name(),
+                })),
+            }
+        );
+        let action_state = client2_rx.borrow_and_update();
+        let ActionStage::Completed(action_result) = &action_state.stage else {
+            panic!("Expected Completed, got : {:?}", action_state.stage);
+        };
+        assert_eq!(
+            action_result.error.as_ref().map(|err| err.code),
+            Some(Code::Cancelled)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cancel_action_counts_requests_not_watchers_test() -> Result<(), Error> {
+        let scheduler = SimpleScheduler::new_with_callback(
+            &nativelink_config::schedulers::SimpleScheduler::default(),
+            || async move {},
+        );
+        let action_digest = DigestInfo::new([99u8; 32], 512);
+        let action_info_hash_key = ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: action_digest,
+            salt: 0,
+        };
+
+        let mut client1_rx = setup_action(
+            &scheduler,
+            action_digest,
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+        let mut client2_rx = setup_action(
+            &scheduler,
+            action_digest,
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+        // Listeners such as `WaitOperation` only watch the action.
+        let mut watcher_rx = scheduler
+            .find_existing_action(&action_info_hash_key)
+            .await
+            .err_tip(|| "Action should exist")?;
+
+        assert!(scheduler.cancel_action(&action_info_hash_key).await?);
+        assert_eq!(client1_rx.borrow_and_update().stage, ActionStage::Queued);
+
+        // Every request withdraws one client, even if the same client sends
+        // it again.
+        assert!(scheduler.cancel_action(&action_info_hash_key).await?);
+        for rx in [&mut client1_rx, &mut client2_rx, &mut watcher_rx] {
+            let action_state = rx.borrow_and_update();
+            let ActionStage::Completed(action_result) = &action_state.stage else {
+                panic!("Expected Completed, got : {:?}", action_state.stage);
+            };
+            assert_eq!(
+                action_result.error.as_ref().map(|err| err.code),
+                Some(Code::Cancelled)
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cancel_action_on_two_workers_releases_both_test() -> Result<(), Error> {
+        const WORKER_ID1: WorkerId = WorkerId(0x1111_1111_1111);
+        const WORKER_ID2: WorkerId = WorkerId(0x2222_2222_2222);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &nativelink_config::schedulers::SimpleScheduler::default(),
+            || async move {},
+        );
+        let action_digest = DigestInfo::new([99u8; 32], 512);
+        let action_info_hash_key = ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: action_digest,
+            salt: 0,
+        };
+
+        let mut rx_from_worker1 =
+            setup_new_worker(&scheduler, WORKER_ID1, PlatformProperties::default()).await?;
+        let mut rx_from_worker2 =
+            setup_new_worker(&scheduler, WORKER_ID2, PlatformProperties::default()).await?;
+        let mut started_on = Vec::new();
+        for _ in 0..2 {
+            let _client_rx = setup_action(
+                &scheduler,
+                action_digest,
+                PlatformProperties::default(),
+                make_system_time(1),
+            )
+            .await?;
+            // The action must not be started again on a worker that is still
+            // running the cancelled copy.
+            let (worker_id, rx_from_worker) = tokio::select! {
+                update = rx_from_worker1.recv() => (WORKER_ID1, update),
+                update = rx_from_worker2.recv() => (WORKER_ID2, update),
+            };
+            match rx_from_worker.unwrap().update {
+                Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+                v => panic!("Expected StartAction, got : {v:?}"),
+            }
+            assert!(!started_on.contains(&worker_id));
+            started_on.push(worker_id);
+            assert!(scheduler.cancel_action(&action_info_hash_key).await?);
+            let rx_from_worker = if worker_id == WORKER_ID1 {
+                &mut rx_from_worker1
+            } else {
+                &mut rx_from_worker2
+            };
+            match rx_from_worker.recv().await.unwrap().update {
+                Some(update_for_worker::Update::KillAction(_)) => { /* Success */ }
+                v => panic!("Expected KillAction, got : {v:?}"),
+            }
+        }
+
+        // Both workers reporting back on their cancelled copies is expected
+        // and keeps them in the pool.
+        for worker_id in [WORKER_ID1, WORKER_ID2] {
+            scheduler
+                .update_action(
+                    &worker_id,
+                    &action_info_hash_key,
+                    ActionStage::Completed(ActionResult::default()),
+                )
+                .await?;
+            assert!(scheduler.contains_worker_for_test(&worker_id));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cancel_running_action_ignores_worker_result_test() -> Result<(), Error> {
+        const WORKER_ID: WorkerId = WorkerId(0x1234_5678_9111);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &nativelink_config::schedulers::SimpleScheduler::default(),
+            || async move {},
+        );
+        let action_digest = DigestInfo::new([99u8; 32], 512);
+        let action_info_hash_key = ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: action_digest,
+            salt: 0,
+        };
+
+        let mut rx_from_worker =
+            setup_new_worker(&scheduler, WORKER_ID, PlatformProperties::default()).await?;
+        let mut client_rx = setup_action(
+            &scheduler,
+            action_digest,
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+        {
+            // Other tests check full data. We only care if we got StartAction.
+            match rx_from_worker.recv().await.unwrap().update {
+                Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+                v => panic!("Expected StartAction, got : {v:?}"),
+            }
+            assert_eq!(client_rx.borrow_and_update().stage, ActionStage::Executing);
+        }
+
+        assert!(scheduler.cancel_action(&action_info_hash_key).await?);
+        {
+            let action_state = client_rx.borrow_and_update();
+            let ActionStage::Completed(action_result) = &action_state.stage else {
+                panic!("Expected Completed, got : {:?}", action_state.stage);
+            };
+            assert_eq!(
+                action_result.error.as_ref().map(|err| err.code),
+                Some(Code::Cancelled)
+            );
+        }
+        // The worker should have been told to kill the action.
+        assert_eq!(
+            rx_from_worker.recv().await.unwrap(),
+            UpdateForWorker {
+                update: Some(update_for_worker::Update::KillAction(KillAction {
+                    action_name: action_info_hash_key.action_name(),
+                })),
+            }
+        );
+
+        // The worker finishing the cancelled action is not an error and its
+        // result is not sent to the client.
+        scheduler
+            .update_action(
+                &WORKER_ID,
+                &action_info_hash_key,
+                ActionStage::Completed(ActionResult::default()),
+            )
+            .await?;
+        assert!(!client_rx.has_changed().unwrap_or(false));
+        assert!(scheduler.contains_worker_for_test(&WORKER_ID));
+
+        let actions = scheduler.list_actions().await?;
+        assert_eq!(actions.len(), 1);
+        let ActionStage::Completed(action_result) = &actions[0].stage else {
+            panic!("Expected Completed, got : {:?}", actions[0].stage);
+        };
+        assert_eq!(
+            action_result.error.as_ref().map(|err| err.code),
+            Some(Code::Cancelled)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn action_timeout_kills_action_on_worker_test() -> Result<(), Error> {
+        const WORKER_ID: WorkerId = WorkerId(0x1234_5678_9111);
+        const ACTION_TIMEOUT_S: u64 = 5;
+        const ACTION_TIMEOUT_GRACE_S: u64 = 2;
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &nativelink_config::schedulers::SimpleScheduler {
+                worker_timeout_s: WORKER_TIMEOUT_S,
+                action_timeout_grace_s: ACTION_TIMEOUT_GRACE_S,
+                ..Default::default()
+            },
+            || async move {},
+        );
+        let mut rx_from_worker =
+            setup_new_worker(&scheduler, WORKER_ID, PlatformProperties::default()).await?;
+        let mut action_info = make_base_action_info(make_system_time(1));
+        action_info.timeout = Duration::from_secs(ACTION_TIMEOUT_S);
+        let action_info_hash_key = action_info.unique_qualifier.clone();
+        let mut client_rx = scheduler.add_action(action_info).await?;
+        tokio::task::yield_now().await; // Allow task<->worker matcher to run.
+        {
+            // Other tests check full data. We only care if we got StartAction.
+            match rx_from_worker.recv().await.unwrap().update {
+                Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+                v => panic!("Expected StartAction, got : {v:?}"),
+            }
+            assert_eq!(client_rx.borrow_and_update().stage, ActionStage::Executing);
+        }
+
+        // Actions are timed from when they were started on the worker.
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        scheduler
+            .worker_keep_alive_received(&WORKER_ID, now)
+            .await?;
+
+        // Still within the timeout and grace period.
+        scheduler
+            .remove_timedout_workers(now + ACTION_TIMEOUT_S)
+            .await?;
+        assert!(!client_rx.has_changed().unwrap_or(false));
+
+        scheduler
+            .remove_timedout_workers(now + ACTION_TIMEOUT_S + ACTION_TIMEOUT_GRACE_S + 1)
+            .await?;
+        assert_eq!(
+            rx_from_worker.recv().await.unwrap(),
+            UpdateForWorker {
+                update: Some(update_for_worker::Update::KillAction(KillAction {
+                    action_name: action_info_hash_key.action_name(),
+                })),
+            }
+        );
+        {
+            let action_state = client_rx.borrow_and_update();
+            let ActionStage::Completed(action_result) = &action_state.stage else {
+                panic!("Expected Completed, got : {:?}", action_state.stage);
+            };
+            assert_eq!(
+                action_result.error.as_ref().map(|err| err.code),
+                Some(Code::DeadlineExceeded)
+            );
+        }
+        // The worker is kept, only the action is killed.
+        assert!(scheduler.contains_worker_for_test(&WORKER_ID));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn drain_worker_kills_and_requeues_running_action_test() -> Result<(), Error> {
+        const WORKER_ID1: WorkerId = WorkerId(0x1111_1111);
+        const WORKER_ID2: WorkerId = WorkerId(0x2222_2222);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &nativelink_config::schedulers::SimpleScheduler {
+                kill_actions_on_drain: true,
+                ..Default::default()
+            },
+            || async move {},
+        );
+        let action_digest = DigestInfo::new([99u8; 32], 512);
+
+        let mut rx_from_worker1 =
+            setup_new_worker(&scheduler, WORKER_ID1, PlatformProperties::default()).await?;
+        let mut client_rx = setup_action(
+            &scheduler,
+            action_digest,
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+        let action_name = {
+            let action_state = client_rx.borrow_and_update();
+            assert_eq!(action_state.stage, ActionStage::Executing);
+            action_state.unique_qualifier.action_name()
+        };
+        match rx_from_worker1.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+
+        scheduler.set_drain_worker(WORKER_ID1, true).await?;
+        tokio::task::yield_now().await;
+        assert_eq!(
+            rx_from_worker1.recv().await.unwrap(),
+            UpdateForWorker {
+                update: Some(update_for_worker::Update::KillAction(KillAction {
+                    action_name
+                })),
+            }
+        );
+        assert_eq!(client_rx.borrow_and_update().stage, ActionStage::Queued);
+
+        // The action is picked up by the next available worker.
+        let mut rx_from_worker2 =
+            setup_new_worker(&scheduler, WORKER_ID2, PlatformProperties::default()).await?;
+        match rx_from_worker2.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+        assert_eq!(client_rx.borrow_and_update().stage, ActionStage::Executing);
+
+        // The drained worker finishing the killed action is ignored.
+        scheduler
+            .update_action(
+                &WORKER_ID1,
+                &ActionInfoHashKey {
+                    instance_name: INSTANCE_NAME.to_string(),
+                    digest: action_digest,
+                    salt: 0,
+                },
+                ActionStage::Completed(ActionResult::default()),
+            )
+            .await?;
+        assert!(!client_rx.has_changed().unwrap_or(false));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn action_output_is_readable_while_action_runs_test() -> Result<(), Error> {
+        const WORKER_ID1: WorkerId = WorkerId(0x1111_1111);
+        const WORKER_ID2: WorkerId = WorkerId(0x2222_2222);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &nativelink_config::schedulers::SimpleScheduler {
+                max_action_output_bytes: 8,
+                ..Default::default()
+            },
+            || async move {},
+        );
+        let action_digest = DigestInfo::new([99u8; 32], 512);
+        let unique_qualifier = ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: action_digest,
+            salt: 0,
+        };
+
+        // Nothing is running yet, so there is no output to read.
+        assert_eq!(
+            scheduler
+                .action_output(&unique_qualifier, ActionOutputStream::Stdout)
+                .await
+                .err()
+                .map(|e| e.code),
+            Some(Code::NotFound)
+        );
+
+        let mut rx_from_worker =
+            setup_new_worker(&scheduler, WORKER_ID1, PlatformProperties::default()).await?;
+        let _client_rx = setup_action(
+            &scheduler,
+            action_digest,
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+        match rx_from_worker.recv().await.unwrap().update {
+            Some(update_for_worker::Update::StartAction(_)) => { /* Success */ }
+            v => panic!("Expected StartAction, got : {v:?}"),
+        }
+
+        let stdout = scheduler
+            .action_output(&unique_qualifier, ActionOutputStream::Stdout)
+            .await?;
+        let stderr = scheduler
+            .action_output(&unique_qualifier, ActionOutputStream::Stderr)
+            .await?;
+        scheduler
+            .append_action_output(
+                &WORKER_ID1,
+                &unique_qualifier,
+                Bytes::from_static(b"hello "),
+                Bytes::from_static(b"oops"),
+            )
+            .await?;
+        // Anything past `max_action_output_bytes` is dropped and the worker
+        // is told to stop sending output.
+        assert_eq!(
+            scheduler
+                .append_action_output(
+                    &WORKER_ID1,
+                    &unique_qualifier,
+                    Bytes::from_static(b"world"),
+                    Bytes::new(),
+                )
+                .await
+                .err()
+                .map(|e| e.code),
+            Some(Code::ResourceExhausted)
+        );
+        assert_eq!(stdout.read(0, 100).await, Bytes::from_static(b"hello "));
+        assert_eq!(stdout.read(6, 100).await, Bytes::from_static(b"wo"));
+        assert_eq!(stdout.dropped_bytes(), 3);
+        assert_eq!(stderr.read(1, 2).await, Bytes::from_static(b"op"));
+
+        // Output from a worker that is not running the action is rejected.
+        assert_eq!(
+            scheduler
+                .append_action_output(
+                    &WORKER_ID2,
+                    &unique_qualifier,
+                    Bytes::from_static(b"x"),
+                    Bytes::new(),
+                )
+                .await
+                .err()
+                .map(|e| e.code),
+            Some(Code::InvalidArgument)
+        );
+
+        scheduler
+            .update_action(
+                &WORKER_ID1,
+                &unique_qualifier,
+                ActionStage::Completed(ActionResult::default()),
+            )
+            .await?;
+        // Readers at the end of the output see EOF once the action completed.
+        assert_eq!(stdout.read(8, 100).await, Bytes::new());
+        assert_eq!(stderr.read(4, 100).await, Bytes::new());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn health_reports_connected_workers_test() -> Result<(), Error> {
+        const WORKER_ID: WorkerId = WorkerId(0x1234_5678);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &nativelink_config::schedulers::SimpleScheduler::default(),
+            || async move {},
+        );
+        assert!(matches!(
+            scheduler.check_health("".into()).await,
+            HealthStatus::Warning { .. }
+        ));
+
+        let _rx_from_worker =
+            setup_new_worker(&scheduler, WORKER_ID, PlatformProperties::default()).await?;
+        assert_eq!(
+            scheduler.check_health("".into()).await,
+            HealthStatus::new_ok(&scheduler, "1 workers connected, 0 actions queued".into())
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_workers_and_actions_test() -> Result<(), Error> {
+        const WORKER_ID: WorkerId = WorkerId(0x1234_5678);
+
+        let scheduler = SimpleScheduler::new_with_callback(
+            &nativelink_config::schedulers::SimpleScheduler::default(),
+            || async move {},
+        );
+        let mut worker_properties = PlatformProperties::default();
+        worker_properties.properties.insert(
+            "prop".to_string(),
+            PlatformPropertyValue::Exact("foo".to_string()),
+        );
+        let mut other_properties = PlatformProperties::default();
+        other_properties.properties.insert(
+            "prop".to_string(),
+            PlatformPropertyValue::Exact("bar".to_string()),
+        );
+        let running_digest = DigestInfo::new([99u8; 32], 512);
+        let queued_digest = DigestInfo::new([88u8; 32], 512);
+
+        let mut rx_from_worker =
+            setup_new_worker(&scheduler, WORKER_ID, worker_properties.clone()).await?;
+        let _running_rx = setup_action(
+            &scheduler,
+            running_digest,
+            worker_properties,
+            make_system_time(1),
+        )
+        .await?;
+        let _queued_rx = setup_action(
+            &scheduler,
+            queued_digest,
+            other_properties,
+            make_system_time(2),
+        )
+        .await?;
+        assert!(matches!(
+            rx_from_worker.recv().await.unwrap().update,
+            Some(update_for_worker::Update::StartAction(_))
+        ));
+
+        let running_action_name = format!("{INSTANCE_NAME}/{}-512/0", running_digest.hash_str());
+        let queued_action_name = format!("{INSTANCE_NAME}/{}-512/0", queued_digest.hash_str());
+
+        assert_eq!(
+            scheduler.list_workers().await?,
+            vec![WorkerInfo {
+                worker_id: WORKER_ID.to_string(),
+                platform_properties: [("prop".to_string(), "foo".to_string())].into(),
+                running_actions: vec![running_action_name.clone()],
+                last_update_timestamp: NOW_TIME,
+                is_paused: false,
+                is_draining: false,
+            }]
+        );
+
+        let scheduled_actions = scheduler.list_scheduled_actions().await?;
+        let age_s: Vec<u64> = scheduled_actions
+            .iter()
+            .map(|action| action.age_s)
+            .collect();
+        assert!(
+            age_s[0] < age_s[1],
+            "Queued action was added after the running action"
+        );
+        assert_eq!(
+            scheduled_actions,
+            vec![
+                ScheduledActionInfo {
+                    action_name: queued_action_name,
+                    stage: ScheduledActionStage::Queued,
+                    priority: 0,
+                    platform_properties: [("prop".to_string(), "bar".to_string())].into(),
+                    age_s: age_s[0],
+                    attempts: 0,
+                    worker_id: None,
+                },
+                ScheduledActionInfo {
+                    action_name: running_action_name.clone(),
+                    stage: ScheduledActionStage::Executing,
+                    priority: 0,
+                    platform_properties: [("prop".to_string(), "foo".to_string())].into(),
+                    age_s: age_s[1],
+                    attempts: 1,
+                    worker_id: Some(WORKER_ID.to_string()),
+                },
+            ]
+        );
+
+        let action_info_hash_key = ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: running_digest,
+            salt: 0,
+        };
+        scheduler
+            .update_action(
+                &WORKER_ID,
+                &action_info_hash_key,
+                ActionStage::Completed(ActionResult {
+                    exit_code: 1,
+                    execution_metadata: ExecutionMetadata {
+                        worker: WORKER_ID.to_string(),
+                        worker_start_timestamp: make_system_time(3),
+                        worker_completed_timestamp: make_system_time(5),
+                        ..ExecutionMetadata::default()
+                    },
+                    error: None,
+                    ..ActionResult::default()
+                }),
+            )
+            .await?;
+
+        let completed_actions = scheduler.list_completed_actions().await?;
+        assert_eq!(completed_actions.len(), 1);
+        assert_eq!(completed_actions[0].action_name, running_action_name);
+        assert_eq!(completed_actions[0].exit_code, 1);
+        assert_eq!(completed_actions[0].worker, WORKER_ID.to_string());
+        assert_eq!(completed_actions[0].worker_duration_ms, 2000);
+        assert_eq!(completed_actions[0].error, None);
+        assert_eq!(scheduler.list_workers().await?[0].running_actions.len(), 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn set_action_priority_reorders_queue_test() -> Result<(), Error> {
+        let scheduler = SimpleScheduler::new_with_callback(
+            &nativelink_config::schedulers::SimpleScheduler::default(),
+            || async move {},
+        );
+        let first_digest = DigestInfo::new([99u8; 32], 512);
+        let second_digest = DigestInfo::new([88u8; 32], 512);
+        let _first_rx = setup_action(
+            &scheduler,
+            first_digest,
+            PlatformProperties::default(),
+            make_system_time(1),
+        )
+        .await?;
+        let _second_rx = setup_action(
+            &scheduler,
+            second_digest,
+            PlatformProperties::default(),
+            make_system_time(2),
+        )
+        .await?;
+
+        let queued_action_names = |actions: Vec<ScheduledActionInfo>| -> Vec<(String, i32)> {
+            actions
+                .into_iter()
+                .map(|action| (action.action_name, action.priority))
+                .collect()
+        };
+        let first_action_name = format!("{INSTANCE_NAME}/{}-512/0", first_digest.hash_str());
+        let second_action_name = format!("{INSTANCE_NAME}/{}-512/0", second_digest.hash_str());
+        assert_eq!(
+            queued_action_names(scheduler.list_scheduled_actions().await?),
+            vec![
+                (first_action_name.clone(), 0),
+                (second_action_name.clone(), 0)
+            ]
+        );
+
+        let second_unique_qualifier = ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: second_digest,
+            salt: 0,
+        };
+        assert!(
+            scheduler
+                .set_action_priority(&second_unique_qualifier, 5)
+                .await?
+        );
+        assert_eq!(
+            queued_action_names(scheduler.list_scheduled_actions().await?),
+            vec![(second_action_name, 5), (first_action_name, 0)]
+        );
+
+        let unknown_unique_qualifier = ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: DigestInfo::new([77u8; 32], 512),
+            salt: 0,
+        };
+        assert!(
+            !scheduler
+                .set_action_priority(&unknown_unique_qualifier, 5)
+                .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn set_supported_platform_properties_test() -> Result<(), Error> {
+        let scheduler = SimpleScheduler::new_with_callback(
+            &nativelink_config::schedulers::SimpleScheduler::default(),
+            || async move {},
+        );
+        assert!(WorkerScheduler::get_platform_property_manager(&scheduler)
+            .make_prop_value("cpu_count", "1")
+            .is_err());
+
+        scheduler.set_supported_platform_properties(HashMap::from([(
+            "cpu_count".to_string(),
+            PropertyType::minimum,
+        )]))?;
+        assert_eq!(
+            WorkerScheduler::get_platform_property_manager(&scheduler)
+                .make_prop_value("cpu_count", "1")?,
+            PlatformPropertyValue::Minimum(1)
+        );
+        assert_eq!(
+            ActionScheduler::get_platform_property_manager(&scheduler, INSTANCE_NAME)
+                .await?
+                .make_prop_value("cpu_count", "1")?,
+            PlatformPropertyValue::Minimum(1)
+        );
+
+        Ok(())
+    }
 }
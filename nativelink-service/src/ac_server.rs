@@ -12,13 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Instant;
 
 use bytes::BytesMut;
-use nativelink_config::cas_server::{AcStoreConfig, InstanceName};
+use nativelink_config::cas_server::{AcStoreConfig, AuthPermission, InstanceName};
 use nativelink_error::{make_err, make_input_err, Code, Error, ResultExt};
 use nativelink_proto::build::bazel::remote::execution::v2::action_cache_server::{
     ActionCache, ActionCacheServer as Server,
@@ -30,25 +30,77 @@ use nativelink_store::ac_utils::{get_and_decode_digest, ESTIMATED_DIGEST_SIZE};
 use nativelink_store::grpc_store::GrpcStore;
 use nativelink_store::store_manager::StoreManager;
 use nativelink_util::common::DigestInfo;
+use nativelink_util::request_context::{RequestContext, StoreAccess};
 use nativelink_util::store_trait::Store;
+use parking_lot::Mutex;
 use prost::Message;
+use serde::Serialize;
 use tonic::{Request, Response, Status};
 use tracing::{error, info};
 
+use crate::auth::authorize;
+
 #[derive(Clone)]
 pub struct AcStoreInfo {
     store: Arc<dyn Store>,
     read_only: bool,
 }
 
+/// Number of `GetActionResult` requests that found a cached result or not.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct AcCacheCounts {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Cache hits and misses of `GetActionResult` requests per instance name.
+/// Can be shared by multiple `AcServer`s to count all requests of a process.
+#[derive(Default)]
+pub struct AcCacheStats {
+    counts: Mutex<HashMap<String, AcCacheCounts>>,
+}
+
+impl AcCacheStats {
+    fn record(&self, instance_name: &str, is_hit: bool) {
+        let mut counts = self.counts.lock();
+        let instance_counts = match counts.get_mut(instance_name) {
+            Some(instance_counts) => instance_counts,
+            None => counts.entry(instance_name.to_string()).or_default(),
+        };
+        if is_hit {
+            instance_counts.hits += 1;
+        } else {
+            instance_counts.misses += 1;
+        }
+    }
+
+    /// Returns the counts of every instance that received a request.
+    pub fn counts(&self) -> BTreeMap<String, AcCacheCounts> {
+        self.counts
+            .lock()
+            .iter()
+            .map(|(instance_name, counts)| (instance_name.clone(), *counts))
+            .collect()
+    }
+}
+
 pub struct AcServer {
     stores: HashMap<String, AcStoreInfo>,
+    cache_stats: Arc<AcCacheStats>,
 }
 
 impl AcServer {
     pub fn new(
         config: &HashMap<InstanceName, AcStoreConfig>,
         store_manager: &StoreManager,
+    ) -> Result<Self, Error> {
+        Self::new_with_cache_stats(config, store_manager, Arc::default())
+    }
+
+    pub fn new_with_cache_stats(
+        config: &HashMap<InstanceName, AcStoreConfig>,
+        store_manager: &StoreManager,
+        cache_stats: Arc<AcCacheStats>,
     ) -> Result<Self, Error> {
         let mut stores = HashMap::with_capacity(config.len());
         for (instance_name, ac_cfg) in config {
@@ -65,6 +117,7 @@ impl AcServer {
         }
         Ok(AcServer {
             stores: stores.clone(),
+            cache_stats,
         })
     }
 
@@ -76,6 +129,11 @@ impl AcServer {
         &self,
         grpc_request: Request<GetActionResultRequest>,
     ) -> Result<Response<ActionResult>, Error> {
+        authorize(
+            grpc_request.extensions(),
+            &grpc_request.get_ref().instance_name,
+            AuthPermission::read_only,
+        )?;
         let get_action_request = grpc_request.into_inner();
 
         let instance_name = &get_action_request.instance_name;
@@ -93,22 +151,32 @@ impl AcServer {
 
         // If we are a GrpcStore we shortcut here, as this is a special store.
         let any_store = store_info.store.inner_store(Some(digest)).as_any();
-        if let Some(grpc_store) = any_store.downcast_ref::<GrpcStore>() {
-            return grpc_store
-                .get_action_result(Request::new(get_action_request))
-                .await;
-        }
-
-        Ok(Response::new(
+        let result = if let Some(grpc_store) = any_store.downcast_ref::<GrpcStore>() {
+            grpc_store
+                .get_action_result(Request::new(get_action_request.clone()))
+                .await
+        } else {
             get_and_decode_digest::<ActionResult>(Pin::new(store_info.store.as_ref()), &digest)
-                .await?,
-        ))
+                .await
+                .map(Response::new)
+        };
+        match &result {
+            Ok(_) => self.cache_stats.record(instance_name, true),
+            Err(err) if err.code == Code::NotFound => self.cache_stats.record(instance_name, false),
+            Err(_) => {}
+        }
+        result
     }
 
     async fn inner_update_action_result(
         &self,
         grpc_request: Request<UpdateActionResultRequest>,
     ) -> Result<Response<ActionResult>, Error> {
+        authorize(
+            grpc_request.extensions(),
+            &grpc_request.get_ref().instance_name,
+            AuthPermission::read_write,
+        )?;
         let update_action_request = grpc_request.into_inner();
 
         let instance_name = &update_action_request.instance_name;
@@ -171,7 +239,14 @@ impl ActionCache for AcServer {
             .action_digest
             .as_ref()
             .map(|v| v.hash.to_string());
-        let resp = self.inner_get_action_result(grpc_request).await;
+        let request_context = RequestContext::new(
+            &grpc_request.get_ref().instance_name,
+            grpc_request.get_ref().digest_function,
+            StoreAccess::Ac,
+        );
+        let resp = request_context
+            .scope(self.inner_get_action_result(grpc_request))
+            .await;
         let d = now.elapsed().as_secs_f32();
         if resp.is_err() && resp.as_ref().err().unwrap().code != Code::NotFound {
             error!(
@@ -196,7 +271,14 @@ impl ActionCache for AcServer {
             "\x1b[0;31mupdate_action_result Req\x1b[0m: {:?}",
             grpc_request.get_ref()
         );
-        let resp = self.inner_update_action_result(grpc_request).await;
+        let request_context = RequestContext::new(
+            &grpc_request.get_ref().instance_name,
+            grpc_request.get_ref().digest_function,
+            StoreAccess::Ac,
+        );
+        let resp = request_context
+            .scope(self.inner_update_action_result(grpc_request))
+            .await;
         let d = now.elapsed().as_secs_f32();
         if resp.is_err() {
             log::error!(
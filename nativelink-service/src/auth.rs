@@ -0,0 +1,328 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use axum::http::{Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use nativelink_config::cas_server::{AuthConfig, AuthPermission, JwtAuthConfig};
+use nativelink_error::{error_if, make_err, make_input_err, Code, Error, ResultExt};
+use serde_json::Value;
+use tonic::codegen::http::HeaderMap;
+use tonic::Extensions;
+use x509_parser::certificate::X509Certificate;
+use x509_parser::prelude::FromDer;
+
+/// Default claim holding the name of the principal of a JWT.
+/// Note: This must be kept in sync with the documentation in
+/// `JwtAuthConfig::principal_claim`.
+const DEFAULT_PRINCIPAL_CLAIM: &str = "sub";
+
+/// Principal name that matches every authenticated principal.
+const ANY_PRINCIPAL: &str = "*";
+
+const BEARER_PREFIX: &str = "Bearer ";
+const API_KEY_HEADER: &str = "x-api-key";
+
+/// A key from the JWKS file along with the only algorithm it may verify.
+struct JwtKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    decoding_key: DecodingKey,
+}
+
+impl JwtKey {
+    fn from_jwk(jwk: &Jwk) -> Result<Option<Self>, Error> {
+        let algorithm = match &jwk.algorithm {
+            AlgorithmParameters::RSA(_) => Algorithm::RS256,
+            AlgorithmParameters::EllipticCurve(params) if params.curve == EllipticCurve::P256 => {
+                Algorithm::ES256
+            }
+            _ => return Ok(None),
+        };
+        let decoding_key =
+            DecodingKey::from_jwk(jwk).map_err(|e| make_input_err!("Could not load JWK : {e}"))?;
+        Ok(Some(Self {
+            kid: jwk.common.key_id.clone(),
+            algorithm,
+            decoding_key,
+        }))
+    }
+}
+
+struct JwtValidator {
+    keys: Vec<JwtKey>,
+    issuer: Option<String>,
+    audience: Option<String>,
+    principal_claim: String,
+}
+
+impl JwtValidator {
+    fn new(config: &JwtAuthConfig) -> Result<Self, Error> {
+        let jwks_data = std::fs::read(&config.jwks_file)
+            .err_tip(|| format!("Could not read JWKS file {}", config.jwks_file))?;
+        let jwks: JwkSet = serde_json::from_slice(&jwks_data)
+            .map_err(|e| make_input_err!("Could not parse JWKS file {} : {e}", config.jwks_file))?;
+        let keys = jwks
+            .keys
+            .iter()
+            .filter_map(|jwk| JwtKey::from_jwk(jwk).transpose())
+            .collect::<Result<Vec<_>, _>>()
+            .err_tip(|| format!("In JWKS file {}", config.jwks_file))?;
+        error_if!(
+            keys.is_empty(),
+            "JWKS file {} has no RSA or P-256 keys",
+            config.jwks_file
+        );
+        Ok(Self {
+            keys,
+            issuer: config.issuer.clone(),
+            audience: config.audience.clone(),
+            principal_claim: if config.principal_claim.is_empty() {
+                DEFAULT_PRINCIPAL_CLAIM.to_string()
+            } else {
+                config.principal_claim.clone()
+            },
+        })
+    }
+
+    fn validation(&self, algorithm: Algorithm) -> Validation {
+        let mut validation = Validation::new(algorithm);
+        validation.leeway = 0;
+        validation.validate_nbf = true;
+        validation.set_required_spec_claims(&["exp"]);
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        validation
+    }
+
+    /// Validates `token` and returns the name of its principal.
+    fn validate(&self, token: &str) -> Result<String, Error> {
+        let header =
+            decode_header(token).map_err(|e| make_input_err!("Token is not a JWT : {e}"))?;
+        let mut result = Err(make_input_err!("No key in the JWKS matches the JWT"));
+        for key in self
+            .keys
+            .iter()
+            .filter(|key| header.kid.is_none() || key.kid == header.kid)
+        {
+            result = decode::<Value>(token, &key.decoding_key, &self.validation(key.algorithm))
+                .map_err(|e| make_input_err!("JWT is not valid : {e}"));
+            if result.is_ok() {
+                break;
+            }
+        }
+        let claims = result?.claims;
+        claims[&self.principal_claim]
+            .as_str()
+            .map(str::to_string)
+            .err_tip(|| format!("JWT has no '{}' claim", self.principal_claim))
+    }
+}
+
+/// Returns the common name of the subject of a DER encoded X.509
+/// certificate.
+pub fn certificate_common_name(certificate: &[u8]) -> Option<String> {
+    let (_, certificate) = X509Certificate::from_der(certificate).ok()?;
+    let common_name = certificate.subject().iter_common_name().next()?;
+    common_name.as_str().ok().map(str::to_string)
+}
+
+/// Authenticates requests and holds the rights of each principal, as
+/// configured by `AuthConfig`.
+pub struct Authenticator {
+    tokens: HashMap<String, String>,
+    jwt_validator: Option<JwtValidator>,
+    mtls_subjects: HashMap<String, String>,
+    instances: HashMap<String, HashMap<String, AuthPermission>>,
+    worker_principals: HashSet<String>,
+    admin_principals: HashSet<String>,
+}
+
+impl Authenticator {
+    pub fn new(config: &AuthConfig) -> Result<Self, Error> {
+        let tokens = match &config.tokens_file {
+            Some(tokens_file) => {
+                let tokens_data = std::fs::read(tokens_file)
+                    .err_tip(|| format!("Could not read tokens file {tokens_file}"))?;
+                serde_json::from_slice(&tokens_data).map_err(|e| {
+                    make_input_err!("Could not parse tokens file {tokens_file} : {e}")
+                })?
+            }
+            None => HashMap::new(),
+        };
+        Ok(Self {
+            tokens,
+            jwt_validator: config.jwt.as_ref().map(JwtValidator::new).transpose()?,
+            mtls_subjects: config.mtls_subjects.clone(),
+            instances: config.instances.clone(),
+            worker_principals: config.worker_principals.iter().cloned().collect(),
+            admin_principals: config.admin_principals.iter().cloned().collect(),
+        })
+    }
+
+    /// Authenticates a request from its headers, or the common name of the
+    /// client certificate of the connection it was received on.
+    pub fn authenticate(
+        self: &Arc<Self>,
+        headers: &HeaderMap,
+        peer_common_name: Option<&str>,
+    ) -> AuthContext {
+        AuthContext {
+            authenticator: self.clone(),
+            principal: self.find_principal(headers, peer_common_name),
+        }
+    }
+
+    fn find_principal(
+        &self,
+        headers: &HeaderMap,
+        peer_common_name: Option<&str>,
+    ) -> Result<String, Error> {
+        let bearer_token = headers
+            .get(tonic::codegen::http::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix(BEARER_PREFIX));
+        let api_key = headers
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok());
+        if let Some(token) = bearer_token.or(api_key) {
+            if let Some(principal) = self.tokens.get(token) {
+                return Ok(principal.clone());
+            }
+            let Some(jwt_validator) = &self.jwt_validator else {
+                return Err(make_err!(Code::Unauthenticated, "Unknown token"));
+            };
+            return jwt_validator
+                .validate(token)
+                .map_err(|e| make_err!(Code::Unauthenticated, "{}", e.message_string()));
+        }
+        if let Some(principal) = peer_common_name.and_then(|name| self.mtls_subjects.get(name)) {
+            return Ok(principal.clone());
+        }
+        Err(make_err!(
+            Code::Unauthenticated,
+            "Request has no credentials"
+        ))
+    }
+}
+
+/// Result of authenticating a request. Inserted in the request extensions
+/// when auth is configured for the server the request was received on.
+#[derive(Clone)]
+pub struct AuthContext {
+    authenticator: Arc<Authenticator>,
+    principal: Result<String, Error>,
+}
+
+impl AuthContext {
+    /// Returns the name of the authenticated principal, or why the request
+    /// could not be authenticated.
+    pub fn principal(&self) -> Result<&str, Error> {
+        self.principal.as_deref().map_err(Clone::clone)
+    }
+
+    /// Checks that the principal has `permission` on `instance_name`.
+    pub fn authorize(&self, instance_name: &str, permission: AuthPermission) -> Result<(), Error> {
+        let principal = self.principal()?;
+        let granted = self
+            .authenticator
+            .instances
+            .get(instance_name)
+            .and_then(|principals| {
+                principals
+                    .get(principal)
+                    .or_else(|| principals.get(ANY_PRINCIPAL))
+            });
+        if granted.is_some_and(|granted| *granted >= permission) {
+            return Ok(());
+        }
+        Err(make_err!(
+            Code::PermissionDenied,
+            "'{principal}' does not have {permission:?} permission on instance '{instance_name}'"
+        ))
+    }
+
+    /// Checks that the principal may use the admin API and dashboard.
+    pub fn authorize_admin(&self) -> Result<(), Error> {
+        let principal = self.principal()?;
+        if self.authenticator.admin_principals.contains(principal) {
+            return Ok(());
+        }
+        Err(make_err!(
+            Code::PermissionDenied,
+            "'{principal}' may not use the admin API"
+        ))
+    }
+}
+
+/// Checks that the principal of the request with `extensions` has `permission` on
+/// `instance_name`. Requests received on servers without auth are always
+/// allowed.
+pub fn authorize(
+    extensions: &Extensions,
+    instance_name: &str,
+    permission: AuthPermission,
+) -> Result<(), Error> {
+    extensions.get::<AuthContext>().map_or(Ok(()), |context| {
+        context.authorize(instance_name, permission)
+    })
+}
+
+/// Checks that the principal of the request with `extensions` may use the worker API. Requests
+/// received on servers without auth are always allowed.
+pub fn authorize_worker(extensions: &Extensions) -> Result<(), Error> {
+    let Some(context) = extensions.get::<AuthContext>() else {
+        return Ok(());
+    };
+    let principal = context.principal()?;
+    if context.authenticator.worker_principals.contains(principal) {
+        return Ok(());
+    }
+    Err(make_err!(
+        Code::PermissionDenied,
+        "'{principal}' may not use the worker API"
+    ))
+}
+
+/// Axum middleware that rejects requests from principals that may not use
+/// the admin API and dashboard, with 401 if they are not authenticated and
+/// 403 otherwise. Requests received on servers without auth are always
+/// allowed.
+pub async fn require_admin<B>(request: Request<B>, next: Next<B>) -> Response {
+    let result = request
+        .extensions()
+        .get::<AuthContext>()
+        .map_or(Ok(()), AuthContext::authorize_admin);
+    match result {
+        Ok(()) => next.run(request).await,
+        Err(e) => {
+            let status = if e.code == Code::Unauthenticated {
+                StatusCode::UNAUTHORIZED
+            } else {
+                StatusCode::FORBIDDEN
+            };
+            (status, e.message_string()).into_response()
+        }
+    }
+}
@@ -0,0 +1,768 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{Display, Write};
+use std::pin::Pin;
+use std::sync::Arc;
+
+use bytes::{Bytes, BytesMut};
+use nativelink_config::cas_server::{AuthPermission, BrowserConfig};
+use nativelink_error::{make_err, make_input_err, Code, Error, ResultExt};
+use nativelink_proto::build::bazel::remote::execution::v2::{
+    digest_function, Action, ActionResult, Command, Digest, Directory, DirectoryNode, Platform,
+    Tree,
+};
+use nativelink_proto::com::github::trace_machina::nativelink::remote_execution::HistoricalExecuteResponse;
+use nativelink_store::ac_utils::{get_and_decode_digest, message_to_digest};
+use nativelink_store::store_manager::StoreManager;
+use nativelink_util::buf_channel::{make_buf_channel_pair, DropCloserReadHalf};
+use nativelink_util::common::DigestInfo;
+use nativelink_util::digest_hasher::DigestHasherFunc;
+use nativelink_util::store_trait::Store;
+
+use crate::auth::AuthContext;
+
+/// Number of bytes of stdout and stderr shown on the action page. The
+/// complete output can be downloaded.
+const MAX_INLINE_OUTPUT_BYTES: usize = 64 * 1024;
+
+/// Kind of page requested, as named in `bb_browser` URLs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PageKind {
+    Action,
+    Command,
+    Directory,
+    Tree,
+    HistoricalExecuteResponse,
+    File,
+}
+
+impl PageKind {
+    fn try_from_str(kind: &str) -> Result<Self, Error> {
+        match kind {
+            "action" => Ok(Self::Action),
+            "command" => Ok(Self::Command),
+            "directory" => Ok(Self::Directory),
+            "tree" => Ok(Self::Tree),
+            "historical_execute_response" => Ok(Self::HistoricalExecuteResponse),
+            "file" => Ok(Self::File),
+            _ => Err(make_input_err!("Unknown blob type '{kind}'")),
+        }
+    }
+
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Action => "action",
+            Self::Command => "command",
+            Self::Directory => "directory",
+            Self::Tree => "tree",
+            Self::HistoricalExecuteResponse => "historical_execute_response",
+            Self::File => "file",
+        }
+    }
+}
+
+/// A parsed `{instance_name}/blobs/{digest_function}/{kind}/{hash}-{size}/{name}` path.
+struct BlobPath<'a> {
+    instance_name: &'a str,
+    digest_function: &'a str,
+    hasher: DigestHasherFunc,
+    kind: PageKind,
+    digest: DigestInfo,
+    /// Name of the file, only used for downloads.
+    name: &'a str,
+}
+
+impl<'a> BlobPath<'a> {
+    fn try_parse(path: &'a str) -> Result<Self, Error> {
+        let path = path.trim_start_matches('/');
+        // The instance name may contain slashes.
+        let (instance_name, blob_path) = if let Some(blob_path) = path.strip_prefix("blobs/") {
+            ("", blob_path)
+        } else {
+            path.split_once("/blobs/").ok_or_else(|| {
+                make_input_err!("Expected path to contain '/blobs/', got '{path}'")
+            })?
+        };
+        let mut parts = blob_path.splitn(4, '/');
+        let (Some(digest_function), Some(kind), Some(digest)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(make_input_err!(
+                "Expected '{{digest_function}}/{{type}}/{{hash}}-{{size}}' after 'blobs/', got '{blob_path}'"
+            ));
+        };
+        let hasher = digest_function::Value::from_str_name(&digest_function.to_uppercase())
+            .ok_or_else(|| make_input_err!("Unknown digest function '{digest_function}'"))?
+            .try_into()?;
+        let (hash, size) = digest
+            .split_once('-')
+            .ok_or_else(|| make_input_err!("Expected '{{hash}}-{{size}}', got '{digest}'"))?;
+        let size = size
+            .parse::<u64>()
+            .map_err(|e| make_input_err!("Invalid size in '{digest}' : {e:?}"))?;
+        Ok(Self {
+            instance_name,
+            digest_function,
+            hasher,
+            kind: PageKind::try_from_str(kind)?,
+            digest: DigestInfo::try_new(hash, size)?,
+            name: parts.next().unwrap_or_default().trim_end_matches('/'),
+        })
+    }
+}
+
+/// Response to a request for a blob.
+pub enum BlobBrowserResponse {
+    /// A rendered HTML page.
+    Page(String),
+    /// Contents of a file, streamed from the CAS.
+    File {
+        name: String,
+        size: u64,
+        reader: DropCloserReadHalf,
+    },
+}
+
+/// Escapes text so it can be put in HTML elements and attribute values.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Percent-encodes a file name so it can be used as a single path segment.
+fn encode_path_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    encoded
+}
+
+fn optional<T: Display>(value: Option<T>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| escape_html(&value.to_string()))
+}
+
+fn render_platform(html: &mut String, platform: Option<&Platform>) {
+    let properties = platform.map_or(&[][..], |platform| &platform.properties[..]);
+    if properties.is_empty() {
+        return;
+    }
+    html.push_str("<h2>Platform</h2><table><tr><th>Name</th><th>Value</th></tr>");
+    for property in properties {
+        let _ = write!(
+            html,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            escape_html(&property.name),
+            escape_html(&property.value)
+        );
+    }
+    html.push_str("</table>");
+}
+
+/// State of the request a page is rendered for. Links on the page point to
+/// pages of the same instance with the same digest function.
+struct PageContext<'a> {
+    base_path: &'a str,
+    blob_path: &'a BlobPath<'a>,
+    cas_store: Pin<&'a dyn Store>,
+}
+
+impl PageContext<'_> {
+    fn url(&self, kind: PageKind, digest: &DigestInfo) -> String {
+        let instance_prefix = if self.blob_path.instance_name.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", self.blob_path.instance_name)
+        };
+        format!(
+            "{}/{instance_prefix}blobs/{}/{}/{}-{}/",
+            self.base_path,
+            self.blob_path.digest_function,
+            kind.as_str(),
+            digest.hash_str(),
+            digest.size_bytes
+        )
+    }
+
+    /// Link to a page, the digest is shown if `text` is empty.
+    fn link(&self, kind: PageKind, digest: Option<&Digest>, text: &str) -> String {
+        let Some(digest) = digest.and_then(|digest| DigestInfo::try_from(digest.clone()).ok())
+        else {
+            return "-".to_string();
+        };
+        let text = if text.is_empty() {
+            format!("{}-{}", digest.hash_str(), digest.size_bytes)
+        } else {
+            text.to_string()
+        };
+        let mut url = self.url(kind, &digest);
+        if kind == PageKind::File {
+            url.push_str(&encode_path_segment(&text));
+        }
+        format!(
+            "<a href=\"{}\">{}</a>",
+            escape_html(&url),
+            escape_html(&text)
+        )
+    }
+}
+
+/// Serves `bb_browser` compatible pages for the Actions, Commands, Directories,
+/// ActionResults and files in the CAS and action cache.
+pub struct BlobBrowser {
+    /// Path the browser is served on, links on the pages start with it.
+    base_path: String,
+    cas_stores: HashMap<String, Arc<dyn Store>>,
+    ac_stores: HashMap<String, Arc<dyn Store>>,
+}
+
+impl BlobBrowser {
+    pub fn new(
+        config: &BrowserConfig,
+        base_path: &str,
+        store_manager: &StoreManager,
+    ) -> Result<Self, Error> {
+        let get_stores = |stores: &HashMap<String, String>| {
+            stores
+                .iter()
+                .map(|(instance_name, store_name)| {
+                    let store = store_manager
+                        .get_store(store_name)
+                        .ok_or_else(|| make_input_err!("Store '{store_name}' does not exist"))?;
+                    Ok((instance_name.clone(), store))
+                })
+                .collect::<Result<HashMap<_, _>, Error>>()
+        };
+        Ok(Self {
+            base_path: base_path.trim_end_matches('/').to_string(),
+            cas_stores: get_stores(&config.cas_stores).err_tip(|| "In browser cas_stores")?,
+            ac_stores: get_stores(&config.ac_stores).err_tip(|| "In browser ac_stores")?,
+        })
+    }
+
+    /// Handles a request for `path`, relative to the path the browser is
+    /// served on. Requests with an `auth_context` need `read_only`
+    /// permission on the instance.
+    pub async fn handle(
+        &self,
+        path: &str,
+        auth_context: Option<&AuthContext>,
+    ) -> Result<BlobBrowserResponse, Error> {
+        let blob_path = BlobPath::try_parse(path)?;
+        if let Some(auth_context) = auth_context {
+            auth_context.authorize(blob_path.instance_name, AuthPermission::read_only)?;
+        }
+        let cas_store = self
+            .cas_stores
+            .get(blob_path.instance_name)
+            .ok_or_else(|| {
+                make_err!(
+                    Code::NotFound,
+                    "'instance_name' not configured for '{}'",
+                    blob_path.instance_name
+                )
+            })?;
+        let cas_store = Pin::new(cas_store.as_ref());
+        let page = PageContext {
+            base_path: &self.base_path,
+            blob_path: &blob_path,
+            cas_store,
+        };
+        let digest = &blob_path.digest;
+        let (title, body) = match blob_path.kind {
+            PageKind::Action => (
+                "Action",
+                self.render_action(
+                    &page,
+                    get_and_decode_digest::<Action>(cas_store, digest).await?,
+                )
+                .await,
+            ),
+            PageKind::Command => (
+                "Command",
+                render_command(get_and_decode_digest::<Command>(cas_store, digest).await?),
+            ),
+            PageKind::Directory => (
+                "Directory",
+                render_directory(
+                    &page,
+                    &get_and_decode_digest::<Directory>(cas_store, digest).await?,
+                ),
+            ),
+            PageKind::Tree => (
+                "Tree",
+                render_tree(
+                    &page,
+                    get_and_decode_digest::<Tree>(cas_store, digest).await?,
+                    blob_path.hasher,
+                )?,
+            ),
+            PageKind::HistoricalExecuteResponse => (
+                "Historical execute response",
+                render_historical_execute_response(
+                    &page,
+                    get_and_decode_digest::<HistoricalExecuteResponse>(cas_store, digest).await?,
+                )
+                .await,
+            ),
+            PageKind::File => {
+                return self.stream_file(&blob_path).await;
+            }
+        };
+        Ok(BlobBrowserResponse::Page(format!(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{title}</title>\
+             <style>body{{font-family:sans-serif;margin:1em 2em}}\
+             table{{border-collapse:collapse}}th,td{{border:1px solid #ddd;padding:.2em .6em;text-align:left}}\
+             pre{{background:#f6f8fa;padding:.5em;overflow:auto}}</style></head>\
+             <body><h1>{title} {}-{}</h1>{body}</body></html>",
+            digest.hash_str(),
+            digest.size_bytes
+        )))
+    }
+
+    /// Fails with `NotFound` if the file is not in the CAS, so no response
+    /// is started for a file that can't be sent.
+    async fn stream_file(&self, blob_path: &BlobPath<'_>) -> Result<BlobBrowserResponse, Error> {
+        let cas_store = self.cas_stores[blob_path.instance_name].clone();
+        let digest = blob_path.digest;
+        let size = Pin::new(cas_store.as_ref())
+            .has(digest)
+            .await
+            .err_tip(|| "In BlobBrowser::stream_file")?
+            .ok_or_else(|| {
+                make_err!(
+                    Code::NotFound,
+                    "File {}-{} not found",
+                    digest.hash_str(),
+                    digest.size_bytes
+                )
+            })?;
+        let (tx, reader) = make_buf_channel_pair();
+        // Errors are seen by the reader, since the writer is dropped without
+        // sending an EOF.
+        tokio::spawn(async move { Pin::new(cas_store.as_ref()).get(digest, tx).await });
+        let name = if blob_path.name.is_empty() {
+            format!("{}-{}", digest.hash_str(), digest.size_bytes)
+        } else {
+            blob_path.name.to_string()
+        };
+        Ok(BlobBrowserResponse::File {
+            name,
+            size: size as u64,
+            reader,
+        })
+    }
+
+    async fn render_action(&self, page: &PageContext<'_>, action: Action) -> String {
+        let mut html = format!(
+            "<table><tr><th>Command</th><td>{}</td></tr>\
+             <tr><th>Input root</th><td>{}</td></tr>\
+             <tr><th>Timeout</th><td>{}</td></tr>\
+             <tr><th>Do not cache</th><td>{}</td></tr></table>",
+            page.link(PageKind::Command, action.command_digest.as_ref(), ""),
+            page.link(PageKind::Directory, action.input_root_digest.as_ref(), ""),
+            optional(action.timeout.as_ref()),
+            action.do_not_cache,
+        );
+        render_platform(&mut html, action.platform.as_ref());
+
+        html.push_str("<h1>Cached result</h1>");
+        let Some(ac_store) = self.ac_stores.get(page.blob_path.instance_name) else {
+            html.push_str("<p>No action cache configured for this instance.</p>");
+            return html;
+        };
+        match get_and_decode_digest::<ActionResult>(
+            Pin::new(ac_store.as_ref()),
+            &page.blob_path.digest,
+        )
+        .await
+        {
+            Ok(action_result) => render_action_result(page, &mut html, &action_result).await,
+            Err(err) if err.code == Code::NotFound => {
+                html.push_str("<p>The action has no cached result.</p>");
+            }
+            Err(err) => {
+                let _ = write!(
+                    html,
+                    "<p>Could not read the cached result: {}</p>",
+                    escape_html(&err.to_string())
+                );
+            }
+        }
+        html
+    }
+}
+
+/// Renders stdout or stderr of an action, truncated to
+/// `MAX_INLINE_OUTPUT_BYTES`.
+async fn render_output(
+    page: &PageContext<'_>,
+    html: &mut String,
+    name: &str,
+    raw: &[u8],
+    digest: Option<&Digest>,
+) {
+    let _ = write!(html, "<h2>{name}</h2>");
+    let output = if raw.is_empty() {
+        match digest.and_then(|digest| DigestInfo::try_from(digest.clone()).ok()) {
+            Some(digest_info) if digest_info.size_bytes > 0 => {
+                let _ = write!(html, "<p>{}</p>", page.link(PageKind::File, digest, name));
+                page.cas_store
+                    .get_part_unchunked(digest_info, 0, Some(MAX_INLINE_OUTPUT_BYTES), None)
+                    .await
+            }
+            _ => {
+                html.push_str("<p>Empty</p>");
+                return;
+            }
+        }
+    } else {
+        Ok(Bytes::copy_from_slice(
+            &raw[..raw.len().min(MAX_INLINE_OUTPUT_BYTES)],
+        ))
+    };
+    match output {
+        Ok(output) => {
+            let _ = write!(
+                html,
+                "<pre>{}</pre>",
+                escape_html(&String::from_utf8_lossy(&output))
+            );
+            if output.len() >= MAX_INLINE_OUTPUT_BYTES {
+                html.push_str("<p>Output truncated, download the file to see all of it.</p>");
+            }
+        }
+        Err(err) => {
+            let _ = write!(
+                html,
+                "<p>Could not read {name}: {}</p>",
+                escape_html(&err.to_string())
+            );
+        }
+    }
+}
+
+async fn render_action_result(
+    page: &PageContext<'_>,
+    html: &mut String,
+    action_result: &ActionResult,
+) {
+    let _ = write!(html, "<p>Exit code: {}</p>", action_result.exit_code);
+    if let Some(metadata) = &action_result.execution_metadata {
+        let _ = write!(
+            html,
+            "<table><tr><th>Worker</th><td>{}</td></tr>\
+             <tr><th>Queued</th><td>{}</td></tr>\
+             <tr><th>Worker start</th><td>{}</td></tr>\
+             <tr><th>Execution start</th><td>{}</td></tr>\
+             <tr><th>Execution completed</th><td>{}</td></tr>\
+             <tr><th>Worker completed</th><td>{}</td></tr></table>",
+            escape_html(&metadata.worker),
+            optional(metadata.queued_timestamp.as_ref()),
+            optional(metadata.worker_start_timestamp.as_ref()),
+            optional(metadata.execution_start_timestamp.as_ref()),
+            optional(metadata.execution_completed_timestamp.as_ref()),
+            optional(metadata.worker_completed_timestamp.as_ref()),
+        );
+    }
+    render_output(
+        page,
+        html,
+        "stdout",
+        &action_result.stdout_raw,
+        action_result.stdout_digest.as_ref(),
+    )
+    .await;
+    render_output(
+        page,
+        html,
+        "stderr",
+        &action_result.stderr_raw,
+        action_result.stderr_digest.as_ref(),
+    )
+    .await;
+
+    html.push_str("<h2>Output files</h2>");
+    if action_result.output_files.is_empty() {
+        html.push_str("<p>None</p>");
+    } else {
+        html.push_str("<table><tr><th>Path</th><th>Size</th><th>Executable</th></tr>");
+        for output_file in &action_result.output_files {
+            let file_name = output_file.path.rsplit('/').next().unwrap_or_default();
+            let _ = write!(
+                html,
+                "<tr><td>{} ({})</td><td>{}</td><td>{}</td></tr>",
+                escape_html(&output_file.path),
+                page.link(PageKind::File, output_file.digest.as_ref(), file_name),
+                output_file
+                    .digest
+                    .as_ref()
+                    .map_or(0, |digest| digest.size_bytes),
+                output_file.is_executable
+            );
+        }
+        html.push_str("</table>");
+    }
+
+    html.push_str("<h2>Output directories</h2>");
+    if action_result.output_directories.is_empty() {
+        html.push_str("<p>None</p>");
+    } else {
+        html.push_str("<ul>");
+        for output_directory in &action_result.output_directories {
+            let _ = write!(
+                html,
+                "<li>{}</li>",
+                page.link(
+                    PageKind::Tree,
+                    output_directory.tree_digest.as_ref(),
+                    &output_directory.path
+                )
+            );
+        }
+        html.push_str("</ul>");
+    }
+
+    let symlinks: Vec<_> = action_result
+        .output_symlinks
+        .iter()
+        .chain(&action_result.output_file_symlinks)
+        .chain(&action_result.output_directory_symlinks)
+        .collect();
+    if !symlinks.is_empty() {
+        html.push_str("<h2>Output symlinks</h2><ul>");
+        for symlink in symlinks {
+            let _ = write!(
+                html,
+                "<li>{} &rarr; {}</li>",
+                escape_html(&symlink.path),
+                escape_html(&symlink.target)
+            );
+        }
+        html.push_str("</ul>");
+    }
+}
+
+async fn render_historical_execute_response(
+    page: &PageContext<'_>,
+    historical_execute_response: HistoricalExecuteResponse,
+) -> String {
+    let mut html = format!(
+        "<p>Action: {}</p>",
+        page.link(
+            PageKind::Action,
+            historical_execute_response.action_digest.as_ref(),
+            ""
+        )
+    );
+    let Some(execute_response) = historical_execute_response.execute_response else {
+        html.push_str("<p>No execute response.</p>");
+        return html;
+    };
+    if let Some(status) = &execute_response.status {
+        let _ = write!(
+            html,
+            "<p>Status: {:?} {}</p>",
+            Code::from(status.code),
+            escape_html(&status.message)
+        );
+    }
+    if !execute_response.message.is_empty() {
+        let _ = write!(
+            html,
+            "<p>Message: {}</p>",
+            escape_html(&execute_response.message)
+        );
+    }
+    if let Some(action_result) = &execute_response.result {
+        html.push_str("<h1>Result</h1>");
+        render_action_result(page, &mut html, action_result).await;
+    }
+    html
+}
+
+fn render_command(command: Command) -> String {
+    let mut html = format!(
+        "<h2>Arguments</h2><pre>{}</pre>",
+        escape_html(&command.arguments.join(" \\\n    "))
+    );
+    html.push_str("<h2>Environment variables</h2>");
+    if command.environment_variables.is_empty() {
+        html.push_str("<p>None</p>");
+    } else {
+        html.push_str("<table><tr><th>Name</th><th>Value</th></tr>");
+        for variable in &command.environment_variables {
+            let _ = write!(
+                html,
+                "<tr><td>{}</td><td>{}</td></tr>",
+                escape_html(&variable.name),
+                escape_html(&variable.value)
+            );
+        }
+        html.push_str("</table>");
+    }
+    let _ = write!(
+        html,
+        "<h2>Working directory</h2><p>{}</p>",
+        escape_html(&command.working_directory)
+    );
+    let output_paths: Vec<&String> = command
+        .output_paths
+        .iter()
+        .chain(&command.output_files)
+        .chain(&command.output_directories)
+        .collect();
+    html.push_str("<h2>Output paths</h2><ul>");
+    for output_path in output_paths {
+        let _ = write!(html, "<li>{}</li>", escape_html(output_path));
+    }
+    html.push_str("</ul>");
+    render_platform(&mut html, command.platform.as_ref());
+    html
+}
+
+fn render_directory(page: &PageContext<'_>, directory: &Directory) -> String {
+    render_directory_contents(
+        directory,
+        |html, directory_node| {
+            let _ = write!(
+                html,
+                "<li>{}/</li>",
+                page.link(
+                    PageKind::Directory,
+                    directory_node.digest.as_ref(),
+                    &directory_node.name
+                )
+            );
+        },
+        page,
+    )
+}
+
+/// Renders the files and symlinks of `directory` with links to download
+/// the files. Subdirectories are rendered by `render_subdirectory`.
+fn render_directory_contents(
+    directory: &Directory,
+    mut render_subdirectory: impl FnMut(&mut String, &DirectoryNode),
+    page: &PageContext<'_>,
+) -> String {
+    let mut html = String::from("<ul>");
+    for directory_node in &directory.directories {
+        render_subdirectory(&mut html, directory_node);
+    }
+    for file in &directory.files {
+        let _ = write!(
+            html,
+            "<li>{} ({} bytes{})</li>",
+            page.link(PageKind::File, file.digest.as_ref(), &file.name),
+            file.digest.as_ref().map_or(0, |digest| digest.size_bytes),
+            if file.is_executable {
+                ", executable"
+            } else {
+                ""
+            }
+        );
+    }
+    for symlink in &directory.symlinks {
+        let _ = write!(
+            html,
+            "<li>{} &rarr; {}</li>",
+            escape_html(&symlink.name),
+            escape_html(&symlink.target)
+        );
+    }
+    html.push_str("</ul>");
+    html
+}
+
+/// Renders the root of the tree followed by each of its children. A child
+/// is rendered once, even if several directories contain it, and is linked
+/// to from its parents.
+fn render_tree(
+    page: &PageContext<'_>,
+    tree: Tree,
+    hasher: DigestHasherFunc,
+) -> Result<String, Error> {
+    // Children are referenced by the digest of their encoded message.
+    let mut children = HashMap::with_capacity(tree.children.len());
+    for child in tree.children {
+        let digest = message_to_digest(&child, &mut BytesMut::new(), &mut hasher.hasher())
+            .err_tip(|| "Computing digest of child in tree")?;
+        children.insert(digest, child);
+    }
+
+    let anchor = |digest: &DigestInfo| format!("{}-{}", digest.hash_str(), digest.size_bytes);
+    let root = tree.root.unwrap_or_default();
+    let mut html = String::new();
+    let mut queued = HashSet::new();
+    let mut pending = VecDeque::from([(String::new(), &root, None)]);
+    while let Some((name, directory, maybe_digest)) = pending.pop_front() {
+        match maybe_digest {
+            Some(digest) => {
+                let _ = write!(
+                    html,
+                    "<h2 id=\"{0}\">{1}/ ({0})</h2>",
+                    anchor(&digest),
+                    escape_html(&name)
+                );
+            }
+            None => html.push_str("<h2>/</h2>"),
+        }
+        html.push_str(&render_directory_contents(
+            directory,
+            |html, directory_node| {
+                let maybe_child = directory_node
+                    .digest
+                    .clone()
+                    .and_then(|digest| DigestInfo::try_from(digest).ok())
+                    .and_then(|digest| children.get_key_value(&digest));
+                let Some((digest, child)) = maybe_child else {
+                    let _ = write!(
+                        html,
+                        "<li>{}/ (missing from tree)</li>",
+                        escape_html(&directory_node.name)
+                    );
+                    return;
+                };
+                if queued.insert(*digest) {
+                    pending.push_back((directory_node.name.clone(), child, Some(*digest)));
+                }
+                let _ = write!(
+                    html,
+                    "<li><a href=\"#{}\">{}/</a></li>",
+                    anchor(digest),
+                    escape_html(&directory_node.name)
+                );
+            },
+            page,
+        ));
+    }
+    Ok(html)
+}
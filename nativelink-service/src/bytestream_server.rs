@@ -23,8 +23,9 @@ use std::time::{Duration, Instant};
 use futures::future::{pending, BoxFuture};
 use futures::stream::unfold;
 use futures::{try_join, Future, Stream, TryFutureExt};
-use nativelink_config::cas_server::ByteStreamConfig;
+use nativelink_config::cas_server::{AuthPermission, ByteStreamConfig};
 use nativelink_error::{make_err, make_input_err, Code, Error, ResultExt};
+use nativelink_proto::build::bazel::remote::execution::v2::digest_function::Value as ProtoDigestFunction;
 use nativelink_proto::google::bytestream::byte_stream_server::{
     ByteStream, ByteStreamServer as Server,
 };
@@ -32,13 +33,17 @@ use nativelink_proto::google::bytestream::{
     QueryWriteStatusRequest, QueryWriteStatusResponse, ReadRequest, ReadResponse, WriteRequest,
     WriteResponse,
 };
+use nativelink_scheduler::action_output::ActionOutput;
+use nativelink_scheduler::action_scheduler::ActionScheduler;
 use nativelink_store::grpc_store::GrpcStore;
 use nativelink_store::store_manager::StoreManager;
+use nativelink_util::action_messages::{ActionInfoHashKey, ActionOutputStream};
 use nativelink_util::buf_channel::{
     make_buf_channel_pair, DropCloserReadHalf, DropCloserWriteHalf,
 };
 use nativelink_util::common::DigestInfo;
 use nativelink_util::proto_stream_utils::WriteRequestStreamWrapper;
+use nativelink_util::request_context::{RequestContext, StoreAccess};
 use nativelink_util::resource_info::ResourceInfo;
 use nativelink_util::store_trait::{Store, UploadSizeInfo};
 use parking_lot::Mutex;
@@ -47,6 +52,8 @@ use tokio::time::sleep;
 use tonic::{Request, Response, Status, Streaming};
 use tracing::{enabled, error, info, Level};
 
+use crate::auth::authorize;
+
 /// If this value changes update the documentation in the config definition.
 const DEFAULT_PERSIST_STREAM_ON_DISCONNECT_TIMEOUT: Duration = Duration::from_secs(60);
 
@@ -149,8 +156,18 @@ impl IdleStream {
 type BytesWrittenAndIdleStream = (Arc<AtomicU64>, Option<IdleStream>);
 type SleepFn = Arc<dyn Fn() -> BoxFuture<'static, ()> + Send + Sync>;
 
+/// ByteStream resource names hold the digest function by name instead of by
+/// value like the other REAPI requests.
+fn make_request_context(instance_name: &str, digest_function: Option<&str>) -> RequestContext {
+    let digest_function = digest_function
+        .and_then(|name| ProtoDigestFunction::from_str_name(&name.to_ascii_uppercase()))
+        .map_or(0, |digest_function| digest_function as i32);
+    RequestContext::new(instance_name, digest_function, StoreAccess::Cas)
+}
+
 pub struct ByteStreamServer {
     stores: HashMap<String, Arc<dyn Store>>,
+    action_output_schedulers: HashMap<String, Arc<dyn ActionScheduler>>,
     // Max number of bytes to send on each grpc stream chunk.
     max_bytes_per_stream: usize,
     active_uploads: Arc<Mutex<HashMap<String, BytesWrittenAndIdleStream>>>,
@@ -158,7 +175,11 @@ pub struct ByteStreamServer {
 }
 
 impl ByteStreamServer {
-    pub fn new(config: &ByteStreamConfig, store_manager: &StoreManager) -> Result<Self, Error> {
+    pub fn new(
+        config: &ByteStreamConfig,
+        store_manager: &StoreManager,
+        scheduler_map: &HashMap<String, Arc<dyn ActionScheduler>>,
+    ) -> Result<Self, Error> {
         let mut persist_stream_on_disconnect_timeout =
             Duration::from_secs(config.persist_stream_on_disconnect_timeout as u64);
         if config.persist_stream_on_disconnect_timeout == 0 {
@@ -167,6 +188,7 @@ impl ByteStreamServer {
         Self::new_with_sleep_fn(
             config,
             store_manager,
+            scheduler_map,
             Arc::new(move || Box::pin(sleep(persist_stream_on_disconnect_timeout))),
         )
     }
@@ -174,6 +196,7 @@ impl ByteStreamServer {
     pub fn new_with_sleep_fn(
         config: &ByteStreamConfig,
         store_manager: &StoreManager,
+        scheduler_map: &HashMap<String, Arc<dyn ActionScheduler>>,
         sleep_fn: SleepFn,
     ) -> Result<Self, Error> {
         let mut stores = HashMap::with_capacity(config.cas_stores.len());
@@ -183,6 +206,17 @@ impl ByteStreamServer {
                 .ok_or_else(|| make_input_err!("'cas_store': '{}' does not exist", store_name))?;
             stores.insert(instance_name.to_string(), store);
         }
+        let mut action_output_schedulers =
+            HashMap::with_capacity(config.action_output_schedulers.len());
+        for (instance_name, scheduler_name) in &config.action_output_schedulers {
+            let scheduler = scheduler_map.get(scheduler_name).ok_or_else(|| {
+                make_input_err!(
+                    "'action_output_schedulers': '{}' does not exist",
+                    scheduler_name
+                )
+            })?;
+            action_output_schedulers.insert(instance_name.to_string(), scheduler.clone());
+        }
         let max_bytes_per_stream = if config.max_bytes_per_stream == 0 {
             DEFAULT_MAX_BYTES_PER_STREAM
         } else {
@@ -190,6 +224,7 @@ impl ByteStreamServer {
         };
         Ok(ByteStreamServer {
             stores,
+            action_output_schedulers,
             max_bytes_per_stream,
             active_uploads: Arc::new(Mutex::new(HashMap::new())),
             sleep_fn,
@@ -259,6 +294,18 @@ impl ByteStreamServer {
         &self,
         grpc_request: Request<ReadRequest>,
     ) -> Result<Response<ReadStream>, Error> {
+        if let Ok((unique_qualifier, stream)) =
+            ActionInfoHashKey::try_from_output_stream_name(&grpc_request.get_ref().resource_name)
+        {
+            return self
+                .inner_read_action_output(grpc_request, unique_qualifier, stream)
+                .await;
+        }
+        authorize(
+            grpc_request.extensions(),
+            ResourceInfo::new(&grpc_request.get_ref().resource_name, false)?.instance_name,
+            AuthPermission::read_only,
+        )?;
         let read_request = grpc_request.into_inner();
 
         let read_limit = usize::try_from(read_request.read_limit)
@@ -272,9 +319,13 @@ impl ByteStreamServer {
             .clone();
 
         let digest = DigestInfo::try_new(resource_info.hash, resource_info.expected_size)?;
+        let request_context = make_request_context(instance_name, resource_info.digest_function);
 
         // If we are a GrpcStore we shortcut here, as this is a special store.
-        let any_store = store.inner_store(Some(digest)).as_any();
+        let any_store = request_context
+            .clone()
+            .sync_scope(|| store.inner_store(Some(digest)))
+            .as_any();
         if let Some(grpc_store) = any_store.downcast_ref::<GrpcStore>() {
             let stream = grpc_store.read(Request::new(read_request)).await?;
             return Ok(Response::new(Box::pin(stream)));
@@ -300,11 +351,11 @@ impl ByteStreamServer {
             rx,
             max_bytes_per_stream: self.max_bytes_per_stream,
             maybe_get_part_result: None,
-            get_part_fut: Box::pin(async move {
+            get_part_fut: Box::pin(request_context.scope(async move {
                 store
                     .get_part_arc(digest, tx, read_request.read_offset as usize, read_limit)
                     .await
-            }),
+            })),
         });
 
         Ok(Response::new(Box::pin(unfold(state, move |state| async {
@@ -371,6 +422,73 @@ impl ByteStreamServer {
         }))))
     }
 
+    /// Streams the output of a running action until it finishes.
+    async fn inner_read_action_output(
+        &self,
+        grpc_request: Request<ReadRequest>,
+        unique_qualifier: ActionInfoHashKey,
+        stream: ActionOutputStream,
+    ) -> Result<Response<ReadStream>, Error> {
+        authorize(
+            grpc_request.extensions(),
+            &unique_qualifier.instance_name,
+            AuthPermission::read_only,
+        )?;
+        let read_request = grpc_request.into_inner();
+        let read_offset = u64::try_from(read_request.read_offset)
+            .err_tip(|| "read_offset must not be negative")?;
+        let read_limit =
+            u64::try_from(read_request.read_limit).err_tip(|| "read_limit must not be negative")?;
+        let scheduler = self
+            .action_output_schedulers
+            .get(&unique_qualifier.instance_name)
+            .ok_or_else(|| {
+                make_err!(
+                    Code::NotFound,
+                    "Action output is not served for instance '{}'",
+                    unique_qualifier.instance_name
+                )
+            })?;
+        let output = scheduler
+            .action_output(&unique_qualifier, stream)
+            .await
+            .err_tip(|| "In ByteStreamServer::inner_read_action_output")?;
+
+        struct ReaderState {
+            output: Arc<ActionOutput>,
+            offset: u64,
+            // Number of bytes left to send, `None` means no limit.
+            remaining: Option<u64>,
+            max_bytes_per_stream: usize,
+        }
+
+        let state = ReaderState {
+            output,
+            offset: read_offset,
+            remaining: (read_limit != 0).then_some(read_limit),
+            max_bytes_per_stream: self.max_bytes_per_stream,
+        };
+        Ok(Response::new(Box::pin(unfold(state, |mut state| async {
+            let max_len = match state.remaining {
+                Some(0) => return None,
+                Some(remaining) => state
+                    .max_bytes_per_stream
+                    .min(usize::try_from(remaining).unwrap_or(usize::MAX)),
+                None => state.max_bytes_per_stream,
+            };
+            // Waits for the action to write more output. Empty means it finished.
+            let data = state.output.read(state.offset, max_len).await;
+            if data.is_empty() {
+                return None;
+            }
+            state.offset += data.len() as u64;
+            if let Some(remaining) = state.remaining.as_mut() {
+                *remaining -= data.len() as u64;
+            }
+            Some((Ok(ReadResponse { data }), state))
+        }))))
+    }
+
     async fn inner_write(
         &self,
         mut stream: WriteRequestStreamWrapper<Streaming<WriteRequest>, Status>,
@@ -521,9 +639,14 @@ impl ByteStreamServer {
             .clone();
 
         let digest = DigestInfo::try_new(resource_info.hash, resource_info.expected_size)?;
+        let request_context =
+            make_request_context(resource_info.instance_name, resource_info.digest_function);
 
         // If we are a GrpcStore we shortcut here, as this is a special store.
-        let any_store = store_clone.inner_store(Some(digest)).as_any();
+        let any_store = request_context
+            .clone()
+            .sync_scope(|| store_clone.inner_store(Some(digest)))
+            .as_any();
         if let Some(grpc_store) = any_store.downcast_ref::<GrpcStore>() {
             return grpc_store
                 .query_write_status(Request::new(query_request.clone()))
@@ -547,7 +670,7 @@ impl ByteStreamServer {
             }
         }
 
-        let has_fut = Pin::new(store_clone.as_ref()).has(digest);
+        let has_fut = request_context.scope(Pin::new(store_clone.as_ref()).has(digest));
         let Some(item_size) = has_fut.await.err_tip(|| "Failed to call .has() on store")? else {
             return Err(make_err!(Code::NotFound, "{}", "not found"));
         };
@@ -586,10 +709,16 @@ impl ByteStream for ByteStreamServer {
         grpc_request: Request<Streaming<WriteRequest>>,
     ) -> Result<Response<WriteResponse>, Status> {
         let now = Instant::now();
-        let stream = WriteRequestStreamWrapper::from(grpc_request.into_inner())
+        let (_, extensions, stream) = grpc_request.into_parts();
+        let stream = WriteRequestStreamWrapper::from(stream)
             .await
             .err_tip(|| "Could not unwrap first stream message")
             .map_err(Into::<Status>::into)?;
+        authorize(
+            &extensions,
+            &stream.instance_name,
+            AuthPermission::read_write,
+        )?;
         let hash = if enabled!(Level::DEBUG) {
             Some(stream.hash.clone())
         } else {
@@ -598,8 +727,10 @@ impl ByteStream for ByteStreamServer {
 
         info!("\x1b[0;31mWrite Req\x1b[0m: {:?}", hash);
 
-        let resp = self
-            .inner_write(stream)
+        let request_context =
+            make_request_context(&stream.instance_name, stream.digest_function.as_deref());
+        let resp = request_context
+            .scope(self.inner_write(stream))
             .await
             .err_tip(|| "In ByteStreamServer::write()")
             .map_err(|e| e.into());
@@ -618,6 +749,11 @@ impl ByteStream for ByteStreamServer {
         grpc_request: Request<QueryWriteStatusRequest>,
     ) -> Result<Response<QueryWriteStatusResponse>, Status> {
         let now = Instant::now();
+        authorize(
+            grpc_request.extensions(),
+            ResourceInfo::new(&grpc_request.get_ref().resource_name, true)?.instance_name,
+            AuthPermission::read_only,
+        )?;
         let query_request = grpc_request.into_inner();
 
         let resp = self
@@ -12,10 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
-use nativelink_config::cas_server::{CapabilitiesConfig, InstanceName};
+use nativelink_config::cas_server::{AuthPermission, CapabilitiesConfig, InstanceName};
 use nativelink_error::{Error, ResultExt};
 use nativelink_proto::build::bazel::remote::execution::v2::capabilities_server::{
     Capabilities, CapabilitiesServer as Server,
@@ -32,17 +32,22 @@ use nativelink_scheduler::action_scheduler::ActionScheduler;
 use nativelink_util::digest_hasher::default_digest_hasher_func;
 use tonic::{Request, Response, Status};
 
+use crate::auth::authorize;
+
 const MAX_BATCH_TOTAL_SIZE: i64 = 64 * 1024;
 
 #[derive(Debug, Default)]
 pub struct CapabilitiesServer {
     supported_node_properties_for_instance: HashMap<InstanceName, Vec<String>>,
+    /// Instances whose CAS store can serve `SplitBlob` and `SpliceBlob`.
+    split_blob_instances: HashSet<InstanceName>,
 }
 
 impl CapabilitiesServer {
     pub async fn new(
         config: &HashMap<InstanceName, CapabilitiesConfig>,
         scheduler_map: &HashMap<String, Arc<dyn ActionScheduler>>,
+        split_blob_instances: HashSet<InstanceName>,
     ) -> Result<Self, Error> {
         let mut supported_node_properties_for_instance = HashMap::new();
         for (instance_name, cfg) in config {
@@ -72,6 +77,7 @@ impl CapabilitiesServer {
         }
         Ok(CapabilitiesServer {
             supported_node_properties_for_instance,
+            split_blob_instances,
         })
     }
 
@@ -86,7 +92,13 @@ impl Capabilities for CapabilitiesServer {
         &self,
         request: Request<GetCapabilitiesRequest>,
     ) -> Result<Response<ServerCapabilities>, Status> {
+        authorize(
+            request.extensions(),
+            &request.get_ref().instance_name,
+            AuthPermission::read_only,
+        )?;
         let instance_name = request.into_inner().instance_name;
+        let split_blob_support = self.split_blob_instances.contains(&instance_name);
         let maybe_supported_node_properties = self
             .supported_node_properties_for_instance
             .get(&instance_name);
@@ -121,6 +133,8 @@ impl Capabilities for CapabilitiesServer {
                 symlink_absolute_path_strategy: SymlinkAbsolutePathStrategy::Disallowed.into(),
                 supported_compressors: vec![],
                 supported_batch_update_compressors: vec![],
+                split_blob_support,
+                splice_blob_support: split_blob_support,
             }),
             execution_capabilities,
             deprecated_api_version: None,
@@ -15,12 +15,12 @@
 use std::collections::HashMap;
 use std::pin::Pin;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use bytes::Bytes;
 use futures::stream::{FuturesUnordered, Stream};
 use futures::TryStreamExt;
-use nativelink_config::cas_server::{CasStoreConfig, InstanceName};
+use nativelink_config::cas_server::{AuthPermission, CasStoreConfig, InstanceName};
 use nativelink_error::{error_if, make_err, make_input_err, Code, Error, ResultExt};
 use nativelink_proto::build::bazel::remote::execution::v2::content_addressable_storage_server::{
     ContentAddressableStorage, ContentAddressableStorageServer as Server,
@@ -29,17 +29,34 @@ use nativelink_proto::build::bazel::remote::execution::v2::{
     batch_read_blobs_response, batch_update_blobs_response, compressor, BatchReadBlobsRequest,
     BatchReadBlobsResponse, BatchUpdateBlobsRequest, BatchUpdateBlobsResponse,
     FindMissingBlobsRequest, FindMissingBlobsResponse, GetTreeRequest, GetTreeResponse,
+    SpliceBlobRequest, SpliceBlobResponse, SplitBlobRequest, SplitBlobResponse,
 };
 use nativelink_proto::google::rpc::Status as GrpcStatus;
+use nativelink_store::dedup_store::DedupStore;
 use nativelink_store::grpc_store::GrpcStore;
 use nativelink_store::store_manager::StoreManager;
+use nativelink_util::buf_channel::make_buf_channel_pair;
 use nativelink_util::common::DigestInfo;
-use nativelink_util::store_trait::Store;
+use nativelink_util::digest_hasher::{DigestHasher, DigestHasherFunc};
+use nativelink_util::request_context::{RequestContext, StoreAccess};
+use nativelink_util::store_trait::{Store, UploadSizeInfo};
 use tonic::{Request, Response, Status};
 use tracing::{error, info};
 
+use crate::auth::authorize;
+
 pub struct CasServer {
     stores: HashMap<String, Arc<dyn Store>>,
+    /// How long blobs found by `FindMissingBlobs` are pinned, per instance.
+    pin_found_blobs_durations: HashMap<String, Duration>,
+}
+
+/// Whether `split_blob` and `splice_blob` can be served by `store`, which is
+/// the case for dedup stores and for grpc stores that forward the request.
+pub fn supports_split_blob(store: &dyn Store) -> bool {
+    let any_store = store.inner_store(None).as_any();
+    any_store.downcast_ref::<DedupStore>().is_some()
+        || any_store.downcast_ref::<GrpcStore>().is_some()
 }
 
 type GetTreeStream = Pin<Box<dyn Stream<Item = Result<GetTreeResponse, Status>> + Send + 'static>>;
@@ -50,13 +67,23 @@ impl CasServer {
         store_manager: &StoreManager,
     ) -> Result<Self, Error> {
         let mut stores = HashMap::with_capacity(config.len());
+        let mut pin_found_blobs_durations = HashMap::new();
         for (instance_name, cas_cfg) in config {
             let store = store_manager.get_store(&cas_cfg.cas_store).ok_or_else(|| {
                 make_input_err!("'cas_store': '{}' does not exist", cas_cfg.cas_store)
             })?;
             stores.insert(instance_name.to_string(), store);
+            if cas_cfg.pin_found_blobs_seconds != 0 {
+                pin_found_blobs_durations.insert(
+                    instance_name.to_string(),
+                    Duration::from_secs(cas_cfg.pin_found_blobs_seconds),
+                );
+            }
         }
-        Ok(CasServer { stores })
+        Ok(CasServer {
+            stores,
+            pin_found_blobs_durations,
+        })
     }
 
     pub fn into_service(self) -> Server<CasServer> {
@@ -67,6 +94,11 @@ impl CasServer {
         &self,
         grpc_request: Request<FindMissingBlobsRequest>,
     ) -> Result<Response<FindMissingBlobsResponse>, Error> {
+        authorize(
+            grpc_request.extensions(),
+            &grpc_request.get_ref().instance_name,
+            AuthPermission::read_only,
+        )?;
         let inner_request = grpc_request.into_inner();
 
         let instance_name = &inner_request.instance_name;
@@ -84,6 +116,17 @@ impl CasServer {
             .has_many(&requested_blobs)
             .await
             .err_tip(|| "In find_missing_blobs")?;
+        if let Some(pin_duration) = self.pin_found_blobs_durations.get(instance_name) {
+            let found_blobs: Vec<DigestInfo> = requested_blobs
+                .iter()
+                .zip(sizes.iter())
+                .filter_map(|(digest, maybe_size)| maybe_size.map(|_| *digest))
+                .collect();
+            Pin::new(store.as_ref())
+                .pin(&found_blobs, *pin_duration)
+                .await
+                .err_tip(|| "Pinning found blobs in find_missing_blobs")?;
+        }
         let missing_blob_digests = sizes
             .into_iter()
             .zip(inner_request.blob_digests)
@@ -99,6 +142,11 @@ impl CasServer {
         &self,
         grpc_request: Request<BatchUpdateBlobsRequest>,
     ) -> Result<Response<BatchUpdateBlobsResponse>, Error> {
+        authorize(
+            grpc_request.extensions(),
+            &grpc_request.get_ref().instance_name,
+            AuthPermission::read_write,
+        )?;
         let inner_request = grpc_request.into_inner();
         let instance_name = &inner_request.instance_name;
 
@@ -158,6 +206,11 @@ impl CasServer {
         &self,
         grpc_request: Request<BatchReadBlobsRequest>,
     ) -> Result<Response<BatchReadBlobsResponse>, Error> {
+        authorize(
+            grpc_request.extensions(),
+            &grpc_request.get_ref().instance_name,
+            AuthPermission::read_only,
+        )?;
         let inner_request = grpc_request.into_inner();
         let instance_name = &inner_request.instance_name;
 
@@ -221,6 +274,11 @@ impl CasServer {
         &self,
         grpc_request: Request<GetTreeRequest>,
     ) -> Result<Response<GetTreeStream>, Error> {
+        authorize(
+            grpc_request.extensions(),
+            &grpc_request.get_ref().instance_name,
+            AuthPermission::read_only,
+        )?;
         let inner_request = grpc_request.into_inner();
         let instance_name = &inner_request.instance_name;
 
@@ -246,6 +304,185 @@ impl CasServer {
             "get_tree is not implemented"
         ))
     }
+
+    async fn inner_split_blob(
+        &self,
+        grpc_request: Request<SplitBlobRequest>,
+    ) -> Result<Response<SplitBlobResponse>, Error> {
+        authorize(
+            grpc_request.extensions(),
+            &grpc_request.get_ref().instance_name,
+            AuthPermission::read_only,
+        )?;
+        let inner_request = grpc_request.into_inner();
+        let instance_name = &inner_request.instance_name;
+
+        let store = self
+            .stores
+            .get(instance_name)
+            .err_tip(|| format!("'instance_name' not configured for '{}'", instance_name))?
+            .clone();
+
+        // If we are a GrpcStore we shortcut here, as this is a special store.
+        // Note: We don't know the digests here, so we try perform a very shallow
+        // check to see if it's a grpc store.
+        let any_store = store.inner_store(None).as_any();
+        if let Some(grpc_store) = any_store.downcast_ref::<GrpcStore>() {
+            return grpc_store.split_blob(Request::new(inner_request)).await;
+        }
+
+        let digest = DigestInfo::try_from(
+            inner_request
+                .blob_digest
+                .err_tip(|| "Expected blob_digest to exist in SplitBlobRequest")?,
+        )?;
+        let Some(dedup_store) = store
+            .inner_store(Some(digest))
+            .as_any()
+            .downcast_ref::<DedupStore>()
+        else {
+            return Err(make_err!(
+                Code::Unimplemented,
+                "split_blob requires a dedup store as the CAS store for instance '{}'",
+                instance_name
+            ));
+        };
+        // DedupStore always hashes chunks with blake3, so the chunk list is only
+        // valid for clients using the same digest function.
+        let digest_function = DigestHasherFunc::try_from(inner_request.digest_function)
+            .err_tip(|| "In SplitBlobRequest")?;
+        if digest_function != DigestHasherFunc::Blake3 {
+            return Err(make_input_err!(
+                "split_blob only supports the blake3 digest function, got {:?}",
+                digest_function
+            ));
+        }
+        let chunk_digests = Pin::new(dedup_store)
+            .get_chunk_digests(digest)
+            .await
+            .err_tip(|| "In split_blob")?;
+
+        // Clients will fetch the chunks individually, so only hand out the
+        // chunk list if every chunk can actually be read.
+        let sizes = Pin::new(dedup_store)
+            .has_chunks(&chunk_digests)
+            .await
+            .err_tip(|| "In split_blob")?;
+        if let Some(missing_digest) = chunk_digests
+            .iter()
+            .zip(sizes)
+            .find_map(|(chunk_digest, size)| size.map_or(Some(chunk_digest), |_| None))
+        {
+            return Err(make_err!(
+                Code::NotFound,
+                "Chunk {} of {} is missing in split_blob",
+                missing_digest.hash_str(),
+                digest.hash_str()
+            ));
+        }
+
+        Ok(Response::new(SplitBlobResponse {
+            chunk_digests: chunk_digests.into_iter().map(Into::into).collect(),
+            digest_function: inner_request.digest_function,
+        }))
+    }
+
+    async fn inner_splice_blob(
+        &self,
+        grpc_request: Request<SpliceBlobRequest>,
+    ) -> Result<Response<SpliceBlobResponse>, Error> {
+        authorize(
+            grpc_request.extensions(),
+            &grpc_request.get_ref().instance_name,
+            AuthPermission::read_write,
+        )?;
+        let inner_request = grpc_request.into_inner();
+        let instance_name = &inner_request.instance_name;
+
+        let store = self
+            .stores
+            .get(instance_name)
+            .err_tip(|| format!("'instance_name' not configured for '{}'", instance_name))?
+            .clone();
+
+        // If we are a GrpcStore we shortcut here, as this is a special store.
+        // Note: We don't know the digests here, so we try perform a very shallow
+        // check to see if it's a grpc store.
+        let any_store = store.inner_store(None).as_any();
+        if let Some(grpc_store) = any_store.downcast_ref::<GrpcStore>() {
+            return grpc_store.splice_blob(Request::new(inner_request)).await;
+        }
+
+        let blob_digest = inner_request
+            .blob_digest
+            .err_tip(|| "Expected blob_digest to exist in SpliceBlobRequest")?;
+        let digest = DigestInfo::try_from(blob_digest.clone())?;
+        let digest_function = DigestHasherFunc::try_from(inner_request.digest_function)
+            .err_tip(|| "In SpliceBlobRequest")?;
+        let mut chunk_digests = Vec::with_capacity(inner_request.chunk_digests.len());
+        for chunk_digest in inner_request.chunk_digests {
+            chunk_digests.push(DigestInfo::try_from(chunk_digest)?);
+        }
+        let chunks_size: i64 = chunk_digests.iter().map(|d| d.size_bytes).sum();
+        error_if!(
+            chunks_size != digest.size_bytes,
+            "Chunks in splice_blob add up to {} bytes, but blob digest said {}",
+            chunks_size,
+            digest.size_bytes
+        );
+        let size_bytes = usize::try_from(digest.size_bytes)
+            .err_tip(|| "Digest size_bytes was not convertible to usize")?;
+
+        // Stream the chunks into the store and only send EOF once the data is
+        // known to match the requested digest, otherwise the upload is aborted.
+        let store_pin = Pin::new(store.as_ref());
+        let (mut tx, rx) = make_buf_channel_pair();
+        let send_fut = async move {
+            let mut hasher = digest_function.hasher();
+            for chunk_digest in chunk_digests {
+                let data = store_pin
+                    .get_part_unchunked(chunk_digest, 0, None, None)
+                    .await
+                    .err_tip(|| {
+                        format!(
+                            "Failed to read chunk {} in splice_blob",
+                            chunk_digest.hash_str()
+                        )
+                    })?;
+                if data.is_empty() {
+                    continue;
+                }
+                hasher.update(&data);
+                tx.send(data)
+                    .await
+                    .err_tip(|| "Failed to send chunk in splice_blob")?;
+            }
+            let spliced_digest = hasher.finalize_digest();
+            if spliced_digest != digest {
+                return Err(make_input_err!(
+                    "Spliced blob has digest {}-{}, but {}-{} was requested",
+                    spliced_digest.hash_str(),
+                    spliced_digest.size_bytes,
+                    digest.hash_str(),
+                    digest.size_bytes
+                ));
+            }
+            tx.send_eof()
+                .await
+                .err_tip(|| "Failed to send EOF in splice_blob")
+        };
+        let (send_result, update_result) = futures::join!(
+            send_fut,
+            store_pin.update(digest, rx, UploadSizeInfo::ExactSize(size_bytes))
+        );
+        send_result
+            .merge(update_result)
+            .err_tip(|| "In splice_blob")?;
+
+        Ok(Response::new(SpliceBlobResponse {
+            blob_digest: Some(blob_digest),
+        }))
+    }
 }
 
 #[tonic::async_trait]
@@ -259,8 +496,13 @@ impl ContentAddressableStorage for CasServer {
             grpc_request.get_ref()
         );
         let now = Instant::now();
-        let resp = self
-            .inner_find_missing_blobs(grpc_request)
+        let request_context = RequestContext::new(
+            &grpc_request.get_ref().instance_name,
+            grpc_request.get_ref().digest_function,
+            StoreAccess::Cas,
+        );
+        let resp = request_context
+            .scope(self.inner_find_missing_blobs(grpc_request))
             .await
             .err_tip(|| "Failed on find_missing_blobs() command")
             .map_err(|e| e.into());
@@ -282,8 +524,13 @@ impl ContentAddressableStorage for CasServer {
             grpc_request.get_ref()
         );
         let now = Instant::now();
-        let resp = self
-            .inner_batch_update_blobs(grpc_request)
+        let request_context = RequestContext::new(
+            &grpc_request.get_ref().instance_name,
+            grpc_request.get_ref().digest_function,
+            StoreAccess::Cas,
+        );
+        let resp = request_context
+            .scope(self.inner_batch_update_blobs(grpc_request))
             .await
             .err_tip(|| "Failed on batch_update_blobs() command")
             .map_err(|e| e.into());
@@ -305,8 +552,13 @@ impl ContentAddressableStorage for CasServer {
             grpc_request.get_ref()
         );
         let now = Instant::now();
-        let resp = self
-            .inner_batch_read_blobs(grpc_request)
+        let request_context = RequestContext::new(
+            &grpc_request.get_ref().instance_name,
+            grpc_request.get_ref().digest_function,
+            StoreAccess::Cas,
+        );
+        let resp = request_context
+            .scope(self.inner_batch_read_blobs(grpc_request))
             .await
             .err_tip(|| "Failed on batch_read_blobs() command")
             .map_err(|e| e.into());
@@ -329,8 +581,13 @@ impl ContentAddressableStorage for CasServer {
             grpc_request.get_ref()
         );
         let now = Instant::now();
-        let resp: Result<Response<Self::GetTreeStream>, Status> = self
-            .inner_get_tree(grpc_request)
+        let request_context = RequestContext::new(
+            &grpc_request.get_ref().instance_name,
+            grpc_request.get_ref().digest_function,
+            StoreAccess::Cas,
+        );
+        let resp: Result<Response<Self::GetTreeStream>, Status> = request_context
+            .scope(self.inner_get_tree(grpc_request))
             .await
             .err_tip(|| "Failed on get_tree() command")
             .map_err(|e| e.into());
@@ -341,4 +598,60 @@ impl ContentAddressableStorage for CasServer {
         }
         resp
     }
+
+    async fn split_blob(
+        &self,
+        grpc_request: Request<SplitBlobRequest>,
+    ) -> Result<Response<SplitBlobResponse>, Status> {
+        info!(
+            "\x1b[0;31msplit_blob Req\x1b[0m: {:?}",
+            grpc_request.get_ref()
+        );
+        let now = Instant::now();
+        let request_context = RequestContext::new(
+            &grpc_request.get_ref().instance_name,
+            grpc_request.get_ref().digest_function,
+            StoreAccess::Cas,
+        );
+        let resp = request_context
+            .scope(self.inner_split_blob(grpc_request))
+            .await
+            .err_tip(|| "Failed on split_blob() command")
+            .map_err(|e| e.into());
+        let d = now.elapsed().as_secs_f32();
+        if resp.is_err() {
+            error!("\x1b[0;31msplit_blob Resp\x1b[0m: {} {:?}", d, resp);
+        } else {
+            info!("\x1b[0;31msplit_blob Resp\x1b[0m: {} {:?}", d, resp);
+        }
+        resp
+    }
+
+    async fn splice_blob(
+        &self,
+        grpc_request: Request<SpliceBlobRequest>,
+    ) -> Result<Response<SpliceBlobResponse>, Status> {
+        info!(
+            "\x1b[0;31msplice_blob Req\x1b[0m: {:?}",
+            grpc_request.get_ref()
+        );
+        let now = Instant::now();
+        let request_context = RequestContext::new(
+            &grpc_request.get_ref().instance_name,
+            grpc_request.get_ref().digest_function,
+            StoreAccess::Cas,
+        );
+        let resp = request_context
+            .scope(self.inner_splice_blob(grpc_request))
+            .await
+            .err_tip(|| "Failed on splice_blob() command")
+            .map_err(|e| e.into());
+        let d = now.elapsed().as_secs_f32();
+        if resp.is_err() {
+            error!("\x1b[0;31msplice_blob Resp\x1b[0m: {} {:?}", d, resp);
+        } else {
+            info!("\x1b[0;31msplice_blob Resp\x1b[0m: {} {:?}", d, resp);
+        }
+        resp
+    }
 }
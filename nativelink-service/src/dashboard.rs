@@ -0,0 +1,221 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use nativelink_error::Error;
+use nativelink_scheduler::action_scheduler::{
+    ActionScheduler, CompletedActionInfo, ScheduledActionInfo, ScheduledActionStage,
+};
+use nativelink_scheduler::worker::WorkerInfo;
+use nativelink_scheduler::worker_scheduler::WorkerScheduler;
+use nativelink_util::health_utils::{CachedHealthRegistry, HealthStatusDescription};
+use serde::Serialize;
+
+use crate::ac_server::AcCacheStats;
+
+/// Self-contained page that renders the state served by `Dashboard::state()`.
+/// It expects the state to be served at `state.json` below the page's path.
+pub const DASHBOARD_HTML: &str = include_str!("dashboard.html");
+
+/// Number of recently completed actions shown for each scheduler.
+const MAX_RECENT_COMPLETIONS: usize = 100;
+
+/// Number of queued and executing actions that need workers with the same
+/// platform properties.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueueDepth {
+    pub platform_properties: BTreeMap<String, String>,
+    pub queued: usize,
+    pub executing: usize,
+    /// Age in seconds of the action that has been queued the longest.
+    pub oldest_queued_age_s: u64,
+}
+
+/// Summary of the workers connected to a scheduler.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct WorkerFleet {
+    pub total: usize,
+    /// Workers executing at least one action.
+    pub busy: usize,
+    pub paused: usize,
+    pub draining: usize,
+    pub running_actions: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SchedulerState {
+    pub name: String,
+    pub queue: Vec<QueueDepth>,
+    pub fleet: WorkerFleet,
+    pub workers: Vec<WorkerInfo>,
+    /// Most recently completed actions first.
+    pub recent_completions: Vec<CompletedActionInfo>,
+    /// Errors of the parts of the state the scheduler could not provide,
+    /// for example because it forwards to a remote scheduler.
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CacheHitRate {
+    pub instance_name: String,
+    pub hits: u64,
+    pub misses: u64,
+    /// Fraction of requests that were hits, zero if there were none.
+    pub hit_rate: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DashboardState {
+    pub schedulers: Vec<SchedulerState>,
+    pub cache_hit_rates: Vec<CacheHitRate>,
+    pub health: Vec<HealthStatusDescription>,
+}
+
+/// Collects the state of the schedulers, workers, action cache and stores
+/// shown on the web dashboard.
+pub struct Dashboard {
+    action_schedulers: HashMap<String, Arc<dyn ActionScheduler>>,
+    worker_schedulers: HashMap<String, Arc<dyn WorkerScheduler>>,
+    ac_cache_stats: Arc<AcCacheStats>,
+    /// Polling the dashboard only reads the last health check, it never
+    /// checks the stores itself.
+    health_registry: CachedHealthRegistry,
+}
+
+/// Schedulers may not support every query, the rest of the state is still
+/// shown when one fails.
+fn or_record_error<T>(result: Result<Vec<T>, Error>, errors: &mut Vec<String>) -> Vec<T> {
+    result.unwrap_or_else(|err| {
+        errors.push(format!("{err}"));
+        Vec::new()
+    })
+}
+
+fn queue_depths(actions: &[ScheduledActionInfo]) -> Vec<QueueDepth> {
+    let mut queue: BTreeMap<&BTreeMap<String, String>, QueueDepth> = BTreeMap::new();
+    for action in actions {
+        let depth = queue
+            .entry(&action.platform_properties)
+            .or_insert_with(|| QueueDepth {
+                platform_properties: action.platform_properties.clone(),
+                queued: 0,
+                executing: 0,
+                oldest_queued_age_s: 0,
+            });
+        match action.stage {
+            ScheduledActionStage::Queued => {
+                depth.queued += 1;
+                depth.oldest_queued_age_s = depth.oldest_queued_age_s.max(action.age_s);
+            }
+            ScheduledActionStage::Executing => depth.executing += 1,
+        }
+    }
+    queue.into_values().collect()
+}
+
+fn worker_fleet(workers: &[WorkerInfo]) -> WorkerFleet {
+    workers
+        .iter()
+        .fold(WorkerFleet::default(), |mut fleet, worker| {
+            fleet.total += 1;
+            fleet.busy += usize::from(!worker.running_actions.is_empty());
+            fleet.paused += usize::from(worker.is_paused);
+            fleet.draining += usize::from(worker.is_draining);
+            fleet.running_actions += worker.running_actions.len();
+            fleet
+        })
+}
+
+impl Dashboard {
+    pub fn new(
+        action_schedulers: HashMap<String, Arc<dyn ActionScheduler>>,
+        worker_schedulers: HashMap<String, Arc<dyn WorkerScheduler>>,
+        ac_cache_stats: Arc<AcCacheStats>,
+        health_registry: CachedHealthRegistry,
+    ) -> Self {
+        Self {
+            action_schedulers,
+            worker_schedulers,
+            ac_cache_stats,
+            health_registry,
+        }
+    }
+
+    async fn scheduler_state(
+        &self,
+        name: &str,
+        action_scheduler: &Arc<dyn ActionScheduler>,
+    ) -> SchedulerState {
+        let mut errors = Vec::new();
+        let scheduled_actions =
+            or_record_error(action_scheduler.list_scheduled_actions().await, &mut errors);
+        let mut recent_completions =
+            or_record_error(action_scheduler.list_completed_actions().await, &mut errors);
+        recent_completions.truncate(MAX_RECENT_COMPLETIONS);
+        let workers = match self.worker_schedulers.get(name) {
+            Some(worker_scheduler) => {
+                or_record_error(worker_scheduler.list_workers().await, &mut errors)
+            }
+            None => Vec::new(),
+        };
+        SchedulerState {
+            name: name.to_string(),
+            queue: queue_depths(&scheduled_actions),
+            fleet: worker_fleet(&workers),
+            workers,
+            recent_completions,
+            errors,
+        }
+    }
+
+    /// Returns a snapshot of the state shown on the dashboard.
+    pub async fn state(&self) -> DashboardState {
+        let mut scheduler_names: Vec<&String> = self.action_schedulers.keys().collect();
+        scheduler_names.sort_unstable();
+        let mut schedulers = Vec::with_capacity(scheduler_names.len());
+        for name in scheduler_names {
+            schedulers.push(
+                self.scheduler_state(name, &self.action_schedulers[name])
+                    .await,
+            );
+        }
+
+        let cache_hit_rates = self
+            .ac_cache_stats
+            .counts()
+            .into_iter()
+            .map(|(instance_name, counts)| {
+                let total = counts.hits + counts.misses;
+                CacheHitRate {
+                    instance_name,
+                    hits: counts.hits,
+                    misses: counts.misses,
+                    hit_rate: if total == 0 {
+                        0.0
+                    } else {
+                        counts.hits as f64 / total as f64
+                    },
+                }
+            })
+            .collect();
+
+        DashboardState {
+            schedulers,
+            cache_hit_rates,
+            health: self.health_registry.report().await,
+        }
+    }
+}
@@ -12,13 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use futures::{Stream, StreamExt};
-use nativelink_config::cas_server::{ExecutionConfig, InstanceName};
+use nativelink_config::cas_server::{AuthPermission, ExecutionConfig, InstanceName};
 use nativelink_error::{make_input_err, Error, ResultExt};
 use nativelink_proto::build::bazel::remote::execution::v2::execution_server::{
     Execution, ExecutionServer as Server,
@@ -36,6 +36,7 @@ use nativelink_util::action_messages::{
 use nativelink_util::common::DigestInfo;
 use nativelink_util::digest_hasher::DigestHasherFunc;
 use nativelink_util::platform_properties::PlatformProperties;
+use nativelink_util::request_context::{RequestContext, StoreAccess};
 use nativelink_util::store_trait::Store;
 use rand::{thread_rng, Rng};
 use tokio::sync::watch;
@@ -43,9 +44,13 @@ use tokio_stream::wrappers::WatchStream;
 use tonic::{Request, Response, Status};
 use tracing::{error, info};
 
+use crate::auth::authorize;
+
 struct InstanceInfo {
     scheduler: Arc<dyn ActionScheduler>,
     cas_store: Arc<dyn Store>,
+    /// Set if the running actions' output is served by ByteStream.
+    advertise_output_streams: bool,
 }
 
 impl InstanceInfo {
@@ -145,10 +150,14 @@ pub struct ExecutionServer {
 type ExecuteStream = Pin<Box<dyn Stream<Item = Result<Operation, Status>> + Send + Sync + 'static>>;
 
 impl ExecutionServer {
+    /// `output_stream_instances` are the instances whose running actions'
+    /// output can be read through ByteStream, only their operations
+    /// advertise the output streams.
     pub fn new(
         config: &HashMap<InstanceName, ExecutionConfig>,
         scheduler_map: &HashMap<String, Arc<dyn ActionScheduler>>,
         store_manager: &StoreManager,
+        output_stream_instances: &HashSet<InstanceName>,
     ) -> Result<Self, Error> {
         let mut instance_infos = HashMap::with_capacity(config.len());
         for (instance_name, exec_cfg) in config {
@@ -172,6 +181,7 @@ impl ExecutionServer {
                 InstanceInfo {
                     scheduler,
                     cas_store,
+                    advertise_output_streams: output_stream_instances.contains(instance_name),
                 },
             );
         }
@@ -182,10 +192,16 @@ impl ExecutionServer {
         Server::new(self)
     }
 
-    fn to_execute_stream(receiver: watch::Receiver<Arc<ActionState>>) -> Response<ExecuteStream> {
-        let receiver_stream = Box::pin(WatchStream::new(receiver).map(|action_update| {
+    fn to_execute_stream(
+        receiver: watch::Receiver<Arc<ActionState>>,
+        advertise_output_streams: bool,
+    ) -> Response<ExecuteStream> {
+        let receiver_stream = Box::pin(WatchStream::new(receiver).map(move |action_update| {
             info!("\x1b[0;31mexecute Resp Stream\x1b[0m: {:?}", action_update);
-            Ok(Into::<Operation>::into(action_update.as_ref().clone()))
+            Ok(action_update
+                .as_ref()
+                .clone()
+                .into_operation(advertise_output_streams))
         }));
         tonic::Response::new(receiver_stream)
     }
@@ -194,6 +210,11 @@ impl ExecutionServer {
         &self,
         request: Request<ExecuteRequest>,
     ) -> Result<Response<ExecuteStream>, Error> {
+        authorize(
+            request.extensions(),
+            &request.get_ref().instance_name,
+            AuthPermission::execute,
+        )?;
         let execute_req = request.into_inner();
         let instance_name = execute_req.instance_name;
 
@@ -234,15 +255,23 @@ impl ExecutionServer {
             .await
             .err_tip(|| "Failed to schedule task")?;
 
-        Ok(Self::to_execute_stream(rx))
+        Ok(Self::to_execute_stream(
+            rx,
+            instance_info.advertise_output_streams,
+        ))
     }
 
     async fn inner_wait_execution(
         &self,
         request: Request<WaitExecutionRequest>,
     ) -> Result<Response<ExecuteStream>, Status> {
-        let unique_qualifier = ActionInfoHashKey::try_from(request.into_inner().name.as_str())
+        let unique_qualifier = ActionInfoHashKey::try_from(request.get_ref().name.as_str())
             .err_tip(|| "Decoding operation name into ActionInfoHashKey")?;
+        authorize(
+            request.extensions(),
+            &unique_qualifier.instance_name,
+            AuthPermission::read_only,
+        )?;
         let Some(instance_info) = self.instance_infos.get(&unique_qualifier.instance_name) else {
             return Err(Status::not_found(format!(
                 "No scheduler with the instance name {}",
@@ -256,7 +285,10 @@ impl ExecutionServer {
         else {
             return Err(Status::not_found("Failed to find existing task"));
         };
-        Ok(Self::to_execute_stream(rx))
+        Ok(Self::to_execute_stream(
+            rx,
+            instance_info.advertise_output_streams,
+        ))
     }
 }
 
@@ -271,8 +303,14 @@ impl Execution for ExecutionServer {
         // TODO(blaise.bruer) This is a work in progress, remote execution likely won't work yet.
         info!("\x1b[0;31mexecute Req\x1b[0m: {:?}", grpc_request.get_ref());
         let now = Instant::now();
-        let resp = self
-            .inner_execute(grpc_request)
+        // Stores reading the `Action` and `Command` may route by instance.
+        let request_context = RequestContext::new(
+            &grpc_request.get_ref().instance_name,
+            grpc_request.get_ref().digest_function,
+            StoreAccess::Cas,
+        );
+        let resp = request_context
+            .scope(self.inner_execute(grpc_request))
             .await
             .err_tip(|| "Failed on execute() command")
             .map_err(|e| e.into());
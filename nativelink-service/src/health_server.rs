@@ -0,0 +1,249 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::pin::Pin;
+
+use futures::stream::unfold;
+use futures::{Stream, StreamExt};
+use nativelink_config::cas_server::ServicesConfig;
+use nativelink_error::{make_err, Code, Error, ResultExt};
+use nativelink_proto::build::bazel::remote::execution::v2::action_cache_server::ActionCacheServer;
+use nativelink_proto::build::bazel::remote::execution::v2::capabilities_server::CapabilitiesServer;
+use nativelink_proto::build::bazel::remote::execution::v2::content_addressable_storage_server::ContentAddressableStorageServer;
+use nativelink_proto::build::bazel::remote::execution::v2::execution_server::ExecutionServer;
+use nativelink_proto::com::github::trace_machina::nativelink::remote_execution::worker_api_server::WorkerApiServer;
+use nativelink_proto::google::bytestream::byte_stream_server::ByteStreamServer;
+use nativelink_proto::google::longrunning::operations_server::OperationsServer;
+use nativelink_util::health_utils::{CachedHealthRegistry, HealthStatus, HealthStatusReporter};
+use tonic::server::NamedService;
+use tonic::{Request, Response, Status};
+use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_server::{Health, HealthServer as Server};
+use tonic_health::pb::{HealthCheckRequest, HealthCheckResponse};
+
+/// Name of the service that reports the health of the server as a whole.
+const SERVER_SERVICE_NAME: &str = "";
+
+type WatchStream = Pin<Box<dyn Stream<Item = Result<HealthCheckResponse, Status>> + Send>>;
+
+/// Implements the `grpc.health.v1.Health` service. The status of each gRPC
+/// service is derived from the health of the stores and schedulers it uses,
+/// as of their last periodic check.
+pub struct HealthServer {
+    services: HashMap<&'static str, CachedHealthRegistry>,
+}
+
+fn store_namespace(store_name: &str) -> String {
+    format!("stores/{store_name}")
+}
+
+fn scheduler_namespace(scheduler_name: &str) -> String {
+    format!("schedulers/{scheduler_name}")
+}
+
+/// A service is only `SERVING` if none of the components it uses failed or
+/// are still starting up.
+async fn serving_status(health_registry: &CachedHealthRegistry) -> ServingStatus {
+    let health_status_descriptions: Vec<_> = health_registry.health_status_report().collect().await;
+    let is_serving = health_status_descriptions.iter().all(|description| {
+        !matches!(
+            description.status,
+            HealthStatus::Failed { .. } | HealthStatus::Initializing { .. }
+        )
+    });
+    if is_serving {
+        ServingStatus::Serving
+    } else {
+        ServingStatus::NotServing
+    }
+}
+
+impl HealthServer {
+    pub fn new(config: &ServicesConfig, health_registry: &CachedHealthRegistry) -> Self {
+        // Namespaces in the health registry that each service depends on.
+        let mut dependencies: HashMap<&'static str, Vec<String>> = HashMap::new();
+        if let Some(cas_cfg) = &config.cas {
+            dependencies.insert(
+                <ContentAddressableStorageServer<crate::cas_server::CasServer>>::NAME,
+                cas_cfg
+                    .values()
+                    .map(|cfg| store_namespace(&cfg.cas_store))
+                    .collect(),
+            );
+        }
+        if let Some(ac_cfg) = &config.ac {
+            dependencies.insert(
+                <ActionCacheServer<crate::ac_server::AcServer>>::NAME,
+                ac_cfg
+                    .values()
+                    .map(|cfg| store_namespace(&cfg.ac_store))
+                    .collect(),
+            );
+        }
+        if let Some(capabilities_cfg) = &config.capabilities {
+            dependencies.insert(
+                <CapabilitiesServer<crate::capabilities_server::CapabilitiesServer>>::NAME,
+                capabilities_cfg
+                    .values()
+                    .filter_map(|cfg| cfg.remote_execution.as_ref())
+                    .map(|cfg| scheduler_namespace(&cfg.scheduler))
+                    .collect(),
+            );
+        }
+        if let Some(execution_cfg) = &config.execution {
+            dependencies.insert(
+                <ExecutionServer<crate::execution_server::ExecutionServer>>::NAME,
+                execution_cfg
+                    .values()
+                    .flat_map(|cfg| {
+                        [
+                            store_namespace(&cfg.cas_store),
+                            scheduler_namespace(&cfg.scheduler),
+                        ]
+                    })
+                    .collect(),
+            );
+        }
+        if let Some(operations_cfg) = &config.operations {
+            dependencies.insert(
+                <OperationsServer<crate::operations_server::OperationsServer>>::NAME,
+                operations_cfg
+                    .values()
+                    .map(|cfg| scheduler_namespace(&cfg.scheduler))
+                    .collect(),
+            );
+        }
+        if let Some(bytestream_cfg) = &config.bytestream {
+            dependencies.insert(
+                <ByteStreamServer<crate::bytestream_server::ByteStreamServer>>::NAME,
+                bytestream_cfg
+                    .cas_stores
+                    .values()
+                    .map(|store_name| store_namespace(store_name))
+                    .chain(
+                        bytestream_cfg
+                            .action_output_schedulers
+                            .values()
+                            .map(|scheduler_name| scheduler_namespace(scheduler_name)),
+                    )
+                    .collect(),
+            );
+        }
+        if let Some(worker_api_cfg) = &config.worker_api {
+            dependencies.insert(
+                <WorkerApiServer<crate::worker_api_server::WorkerApiServer>>::NAME,
+                vec![scheduler_namespace(&worker_api_cfg.scheduler)],
+            );
+        }
+        let all_dependencies = dependencies.values().flatten().cloned().collect();
+        dependencies.insert(SERVER_SERVICE_NAME, all_dependencies);
+
+        let services = dependencies
+            .into_iter()
+            .map(|(service_name, namespaces)| {
+                (
+                    service_name,
+                    health_registry.sub_registry(namespaces.iter().map(String::as_str)),
+                )
+            })
+            .collect();
+        Self { services }
+    }
+
+    /// Names of the gRPC services whose health is reported, including the
+    /// health service itself.
+    pub fn service_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.services
+            .keys()
+            .copied()
+            .filter(|service_name| *service_name != SERVER_SERVICE_NAME)
+            .chain([<Server<HealthServer>>::NAME])
+    }
+
+    pub fn into_service(self) -> Server<HealthServer> {
+        Server::new(self)
+    }
+
+    async fn inner_check(
+        &self,
+        request: Request<HealthCheckRequest>,
+    ) -> Result<Response<HealthCheckResponse>, Error> {
+        let service = request.into_inner().service;
+        let health_registry = self
+            .services
+            .get(service.as_str())
+            .ok_or_else(|| make_err!(Code::NotFound, "Unknown service '{service}'"))?;
+        Ok(Response::new(HealthCheckResponse {
+            status: serving_status(health_registry).await.into(),
+        }))
+    }
+
+    fn inner_watch(
+        &self,
+        request: Request<HealthCheckRequest>,
+    ) -> Result<Response<WatchStream>, Error> {
+        let service = request.into_inner().service;
+        // Unknown services are reported as such, the stream is kept open as
+        // the protocol requires.
+        let maybe_health_registry = self.services.get(service.as_str()).cloned();
+        let stream = unfold(
+            (maybe_health_registry, None),
+            move |(mut maybe_health_registry, last_status)| async move {
+                loop {
+                    let status = match &maybe_health_registry {
+                        Some(health_registry) => serving_status(health_registry).await,
+                        None => ServingStatus::ServiceUnknown,
+                    };
+                    if last_status != Some(status) {
+                        let response = HealthCheckResponse {
+                            status: status.into(),
+                        };
+                        return Some((Ok(response), (maybe_health_registry, Some(status))));
+                    }
+                    match &mut maybe_health_registry {
+                        Some(health_registry) => health_registry.changed().await,
+                        // The status of an unknown service never changes.
+                        None => futures::future::pending().await,
+                    }
+                }
+            },
+        );
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+#[tonic::async_trait]
+impl Health for HealthServer {
+    type WatchStream = WatchStream;
+
+    async fn check(
+        &self,
+        request: Request<HealthCheckRequest>,
+    ) -> Result<Response<HealthCheckResponse>, Status> {
+        self.inner_check(request)
+            .await
+            .err_tip(|| "Failed on check() command")
+            .map_err(|e| e.into())
+    }
+
+    async fn watch(
+        &self,
+        request: Request<HealthCheckRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        self.inner_watch(request)
+            .err_tip(|| "Failed on watch() command")
+            .map_err(|e| e.into())
+    }
+}
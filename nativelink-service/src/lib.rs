@@ -13,8 +13,13 @@
 // limitations under the License.
 
 pub mod ac_server;
+pub mod auth;
+pub mod blob_browser;
 pub mod bytestream_server;
 pub mod capabilities_server;
 pub mod cas_server;
+pub mod dashboard;
 pub mod execution_server;
+pub mod health_server;
+pub mod operations_server;
 pub mod worker_api_server;
@@ -0,0 +1,338 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use nativelink_config::cas_server::{AuthPermission, InstanceName, OperationsConfig};
+use nativelink_error::{error_if, make_err, Code, Error, ResultExt};
+use nativelink_proto::google::longrunning::operations_server::{
+    Operations, OperationsServer as Server,
+};
+use nativelink_proto::google::longrunning::{
+    CancelOperationRequest, DeleteOperationRequest, GetOperationRequest, ListOperationsRequest,
+    ListOperationsResponse, Operation, WaitOperationRequest,
+};
+use nativelink_scheduler::action_scheduler::ActionScheduler;
+use nativelink_util::action_messages::{ActionInfoHashKey, ActionState};
+use tokio::sync::watch;
+use tonic::{Request, Response, Status};
+use tracing::error;
+
+use crate::auth::authorize;
+
+/// Default number of operations returned by `list_operations` when the
+/// client does not set `page_size`.
+const DEFAULT_LIST_PAGE_SIZE: usize = 100;
+
+/// Maximum number of operations returned by a single `list_operations` call.
+const MAX_LIST_PAGE_SIZE: usize = 1000;
+
+pub struct OperationsServer {
+    schedulers: HashMap<InstanceName, Arc<dyn ActionScheduler>>,
+    /// Instances whose running actions' output is served by ByteStream.
+    output_stream_instances: HashSet<InstanceName>,
+}
+
+impl OperationsServer {
+    /// `output_stream_instances` are the instances whose running actions'
+    /// output can be read through ByteStream, only their operations
+    /// advertise the output streams.
+    pub fn new(
+        config: &HashMap<InstanceName, OperationsConfig>,
+        scheduler_map: &HashMap<String, Arc<dyn ActionScheduler>>,
+        output_stream_instances: &HashSet<InstanceName>,
+    ) -> Result<Self, Error> {
+        let mut schedulers = HashMap::with_capacity(config.len());
+        for (instance_name, operations_cfg) in config {
+            let scheduler = scheduler_map
+                .get(&operations_cfg.scheduler)
+                .err_tip(|| {
+                    format!(
+                        "Scheduler needs config for '{}' because it exists in operations",
+                        operations_cfg.scheduler
+                    )
+                })?
+                .clone();
+            schedulers.insert(instance_name.to_string(), scheduler);
+        }
+        Ok(Self {
+            schedulers,
+            output_stream_instances: output_stream_instances.clone(),
+        })
+    }
+
+    pub fn into_service(self) -> Server<OperationsServer> {
+        Server::new(self)
+    }
+
+    fn get_scheduler(&self, instance_name: &str) -> Result<&Arc<dyn ActionScheduler>, Error> {
+        self.schedulers.get(instance_name).ok_or_else(|| {
+            make_err!(
+                Code::NotFound,
+                "No scheduler with the instance name {instance_name}"
+            )
+        })
+    }
+
+    fn to_operation(&self, action_state: ActionState) -> Operation {
+        let advertise_output_streams = self
+            .output_stream_instances
+            .contains(&action_state.unique_qualifier.instance_name);
+        action_state.into_operation(advertise_output_streams)
+    }
+
+    async fn find_action(
+        &self,
+        unique_qualifier: &ActionInfoHashKey,
+    ) -> Result<watch::Receiver<Arc<ActionState>>, Error> {
+        self.get_scheduler(&unique_qualifier.instance_name)?
+            .find_existing_action(unique_qualifier)
+            .await
+            .ok_or_else(|| {
+                make_err!(
+                    Code::NotFound,
+                    "Operation {} not found",
+                    unique_qualifier.action_name()
+                )
+            })
+    }
+
+    async fn inner_list_operations(
+        &self,
+        request: Request<ListOperationsRequest>,
+    ) -> Result<Response<ListOperationsResponse>, Error> {
+        // An empty name lists the operations of every instance the caller
+        // can read, otherwise it is the instance to list.
+        let instance_names: Vec<&InstanceName> = if request.get_ref().name.is_empty() {
+            self.schedulers
+                .keys()
+                .filter(|instance_name| {
+                    authorize(
+                        request.extensions(),
+                        instance_name,
+                        AuthPermission::read_only,
+                    )
+                    .is_ok()
+                })
+                .collect()
+        } else {
+            let instance_name = &request.get_ref().name;
+            authorize(
+                request.extensions(),
+                instance_name,
+                AuthPermission::read_only,
+            )?;
+            vec![
+                self.schedulers
+                    .get_key_value(instance_name)
+                    .ok_or_else(|| {
+                        make_err!(
+                            Code::NotFound,
+                            "No scheduler with the instance name {instance_name}"
+                        )
+                    })?
+                    .0,
+            ]
+        };
+        let request = request.into_inner();
+        error_if!(
+            !request.filter.is_empty(),
+            "Filters are not supported in list_operations, got '{}'",
+            request.filter
+        );
+        error_if!(
+            request.page_size < 0,
+            "page_size must not be negative, got {}",
+            request.page_size
+        );
+        let page_size = match request.page_size as usize {
+            0 => DEFAULT_LIST_PAGE_SIZE,
+            page_size => page_size.min(MAX_LIST_PAGE_SIZE),
+        };
+
+        // Instances may share a scheduler, so only ask each scheduler once.
+        let mut listed_schedulers: Vec<&Arc<dyn ActionScheduler>> = Vec::new();
+        // Sorted by name so the page token can be the last name returned.
+        let mut operations = BTreeMap::new();
+        for instance_name in &instance_names {
+            let scheduler = &self.schedulers[*instance_name];
+            if listed_schedulers
+                .iter()
+                .any(|other| Arc::ptr_eq(scheduler, other))
+            {
+                continue;
+            }
+            listed_schedulers.push(scheduler);
+            let actions = scheduler
+                .list_actions()
+                .await
+                .err_tip(|| format!("Listing actions for instance {instance_name}"))?;
+            for action in actions {
+                if !instance_names.contains(&&action.unique_qualifier.instance_name) {
+                    continue;
+                }
+                let name = action.unique_qualifier.action_name();
+                if name > request.page_token {
+                    operations.insert(name, action);
+                }
+            }
+        }
+
+        let next_page_token = if operations.len() > page_size {
+            operations
+                .keys()
+                .nth(page_size - 1)
+                .cloned()
+                .unwrap_or_default()
+        } else {
+            String::new()
+        };
+        Ok(Response::new(ListOperationsResponse {
+            operations: operations
+                .into_values()
+                .take(page_size)
+                .map(|action| self.to_operation(action.as_ref().clone()))
+                .collect(),
+            next_page_token,
+        }))
+    }
+
+    async fn inner_get_operation(
+        &self,
+        request: Request<GetOperationRequest>,
+    ) -> Result<Response<Operation>, Error> {
+        let unique_qualifier = ActionInfoHashKey::try_from(request.get_ref().name.as_str())
+            .err_tip(|| "Decoding operation name into ActionInfoHashKey")?;
+        authorize(
+            request.extensions(),
+            &unique_qualifier.instance_name,
+            AuthPermission::read_only,
+        )?;
+        let rx = self.find_action(&unique_qualifier).await?;
+        let action_state = rx.borrow().as_ref().clone();
+        Ok(Response::new(self.to_operation(action_state)))
+    }
+
+    async fn inner_cancel_operation(
+        &self,
+        request: Request<CancelOperationRequest>,
+    ) -> Result<Response<()>, Error> {
+        let unique_qualifier = ActionInfoHashKey::try_from(request.get_ref().name.as_str())
+            .err_tip(|| "Decoding operation name into ActionInfoHashKey")?;
+        authorize(
+            request.extensions(),
+            &unique_qualifier.instance_name,
+            AuthPermission::execute,
+        )?;
+        let cancelled = self
+            .get_scheduler(&unique_qualifier.instance_name)?
+            .cancel_action(&unique_qualifier)
+            .await
+            .err_tip(|| "Cancelling action in scheduler")?;
+        if !cancelled {
+            // Cancelling an operation that already finished is not an error.
+            self.find_action(&unique_qualifier).await?;
+        }
+        Ok(Response::new(()))
+    }
+
+    async fn inner_wait_operation(
+        &self,
+        request: Request<WaitOperationRequest>,
+    ) -> Result<Response<Operation>, Error> {
+        let unique_qualifier = ActionInfoHashKey::try_from(request.get_ref().name.as_str())
+            .err_tip(|| "Decoding operation name into ActionInfoHashKey")?;
+        authorize(
+            request.extensions(),
+            &unique_qualifier.instance_name,
+            AuthPermission::read_only,
+        )?;
+        let timeout = match request.into_inner().timeout {
+            Some(timeout) => {
+                error_if!(
+                    timeout.seconds < 0 || timeout.nanos < 0,
+                    "timeout must not be negative"
+                );
+                Duration::new(timeout.seconds as u64, timeout.nanos as u32)
+            }
+            None => Duration::MAX,
+        };
+        let mut rx = self.find_action(&unique_qualifier).await?;
+        let wait_result = tokio::time::timeout(
+            timeout,
+            rx.wait_for(|action_state| action_state.stage.is_finished()),
+        )
+        .await;
+        if let Ok(Err(err)) = wait_result.map(|result| result.map(|_| ())) {
+            // The scheduler dropped the action, reply with the last known state.
+            error!("Scheduler dropped action while waiting on operation: {err}");
+        }
+        let action_state = rx.borrow().as_ref().clone();
+        Ok(Response::new(self.to_operation(action_state)))
+    }
+}
+
+#[tonic::async_trait]
+impl Operations for OperationsServer {
+    async fn list_operations(
+        &self,
+        request: Request<ListOperationsRequest>,
+    ) -> Result<Response<ListOperationsResponse>, Status> {
+        self.inner_list_operations(request)
+            .await
+            .err_tip(|| "Failed on list_operations() command")
+            .map_err(|e| e.into())
+    }
+
+    async fn get_operation(
+        &self,
+        request: Request<GetOperationRequest>,
+    ) -> Result<Response<Operation>, Status> {
+        self.inner_get_operation(request)
+            .await
+            .err_tip(|| "Failed on get_operation() command")
+            .map_err(|e| e.into())
+    }
+
+    async fn delete_operation(
+        &self,
+        _request: Request<DeleteOperationRequest>,
+    ) -> Result<Response<()>, Status> {
+        // Operations are removed by the scheduler once they have completed
+        // and nobody is waiting on them.
+        Err(make_err!(Code::Unimplemented, "delete_operation is not supported").into())
+    }
+
+    async fn cancel_operation(
+        &self,
+        request: Request<CancelOperationRequest>,
+    ) -> Result<Response<()>, Status> {
+        self.inner_cancel_operation(request)
+            .await
+            .err_tip(|| "Failed on cancel_operation() command")
+            .map_err(|e| e.into())
+    }
+
+    async fn wait_operation(
+        &self,
+        request: Request<WaitOperationRequest>,
+    ) -> Result<Response<Operation>, Status> {
+        self.inner_wait_operation(request)
+            .await
+            .err_tip(|| "Failed on wait_operation() command")
+            .map_err(|e| e.into())
+    }
+}
@@ -25,7 +25,7 @@ use nativelink_proto::com::github::trace_machina::nativelink::remote_execution::
     WorkerApi, WorkerApiServer as Server,
 };
 use nativelink_proto::com::github::trace_machina::nativelink::remote_execution::{
-    execute_result, ExecuteResult, GoingAwayRequest, KeepAliveRequest, SupportedProperties, UpdateForWorker,
+    execute_result, ActionOutputRequest, ExecuteResult, GoingAwayRequest, KeepAliveRequest, SupportedProperties, UpdateForWorker,
 };
 use nativelink_scheduler::worker::{Worker, WorkerId};
 use nativelink_scheduler::worker_scheduler::WorkerScheduler;
@@ -36,6 +36,8 @@ use tokio::sync::mpsc;
 use tokio::time::interval;
 use tonic::{Request, Response, Status};
 use tracing::{error, info, warn};
+
+use crate::auth::authorize_worker;
 use uuid::Uuid;
 
 pub type ConnectWorkerStream =
@@ -123,10 +125,9 @@ impl WorkerApiServer {
         // First convert our proto platform properties into one our scheduler understands.
         let platform_properties = {
             let mut platform_properties = PlatformProperties::default();
+            let platform_property_manager = self.scheduler.get_platform_property_manager();
             for property in supported_properties.properties {
-                let platform_property_value = self
-                    .scheduler
-                    .get_platform_property_manager()
+                let platform_property_value = platform_property_manager
                     .make_prop_value(&property.name, &property.value)
                     .err_tip(|| "Bad Property during connect_worker()")?;
                 platform_properties
@@ -226,6 +227,32 @@ impl WorkerApiServer {
         }
         Ok(Response::new(()))
     }
+
+    async fn inner_action_output(
+        &self,
+        action_output: ActionOutputRequest,
+    ) -> Result<Response<()>, Error> {
+        let worker_id: WorkerId = action_output.worker_id.try_into()?;
+        let action_digest: DigestInfo = action_output
+            .action_digest
+            .err_tip(|| "Expected action_digest to exist")?
+            .try_into()?;
+        let action_info_hash_key = ActionInfoHashKey {
+            instance_name: action_output.instance_name,
+            digest: action_digest,
+            salt: action_output.salt,
+        };
+        self.scheduler
+            .append_action_output(
+                &worker_id,
+                &action_info_hash_key,
+                action_output.stdout,
+                action_output.stderr,
+            )
+            .await
+            .err_tip(|| format!("Failed to append_action_output {:?}", action_digest))?;
+        Ok(Response::new(()))
+    }
 }
 
 #[tonic::async_trait]
@@ -240,6 +267,7 @@ impl WorkerApi for WorkerApiServer {
             "\x1b[0;31mconnect_worker Req\x1b[0m: {:?}",
             grpc_request.get_ref()
         );
+        authorize_worker(grpc_request.extensions())?;
         let supported_properties = grpc_request.into_inner();
         let resp = self.inner_connect_worker(supported_properties).await;
         let d = now.elapsed().as_secs_f32();
@@ -260,6 +288,7 @@ impl WorkerApi for WorkerApiServer {
             "\x1b[0;31mkeep_alive Req\x1b[0m: {:?}",
             grpc_request.get_ref()
         );
+        authorize_worker(grpc_request.extensions())?;
         let keep_alive_request = grpc_request.into_inner();
         let resp = self.inner_keep_alive(keep_alive_request).await;
         let d = now.elapsed().as_secs_f32();
@@ -280,6 +309,7 @@ impl WorkerApi for WorkerApiServer {
             "\x1b[0;31mgoing_away Req\x1b[0m: {:?}",
             grpc_request.get_ref()
         );
+        authorize_worker(grpc_request.extensions())?;
         let going_away_request = grpc_request.into_inner();
         let resp = self.inner_going_away(going_away_request).await;
         let d = now.elapsed().as_secs_f32();
@@ -300,6 +330,7 @@ impl WorkerApi for WorkerApiServer {
             "\x1b[0;31mexecution_response Req\x1b[0m: {:?}",
             grpc_request.get_ref()
         );
+        authorize_worker(grpc_request.extensions())?;
         let execute_result = grpc_request.into_inner();
         let resp = self.inner_execution_response(execute_result).await;
         let d = now.elapsed().as_secs_f32();
@@ -310,4 +341,33 @@ impl WorkerApi for WorkerApiServer {
         }
         return resp.map_err(|e| e.into());
     }
+
+    async fn action_output(
+        &self,
+        grpc_request: Request<ActionOutputRequest>,
+    ) -> Result<Response<()>, Status> {
+        let now = Instant::now();
+        // The output itself can be large, so only its size is logged.
+        info!(
+            "\x1b[0;31maction_output Req\x1b[0m: worker_id: {} stdout: {} bytes stderr: {} bytes",
+            grpc_request.get_ref().worker_id,
+            grpc_request.get_ref().stdout.len(),
+            grpc_request.get_ref().stderr.len()
+        );
+        authorize_worker(grpc_request.extensions())?;
+        let action_output = grpc_request.into_inner();
+        let resp = self.inner_action_output(action_output).await;
+        let d = now.elapsed().as_secs_f32();
+        if let Err(err) = resp.as_ref() {
+            // Workers are expected to hit the output limit.
+            if err.code == Code::ResourceExhausted {
+                info!("\x1b[0;31maction_output Resp\x1b[0m: {} {:?}", d, err);
+            } else {
+                error!("\x1b[0;31maction_output Resp\x1b[0m: {} {:?}", d, err);
+            }
+        } else {
+            info!("\x1b[0;31maction_output Resp\x1b[0m: {}", d);
+        }
+        return resp.map_err(|e| e.into());
+    }
 }
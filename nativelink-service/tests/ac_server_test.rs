@@ -22,7 +22,7 @@ use nativelink_proto::build::bazel::remote::execution::v2::action_cache_server::
 use nativelink_proto::build::bazel::remote::execution::v2::{
     digest_function, ActionResult, Digest,
 };
-use nativelink_service::ac_server::AcServer;
+use nativelink_service::ac_server::{AcCacheCounts, AcCacheStats, AcServer};
 use nativelink_store::default_store_factory::store_factory;
 use nativelink_store::store_manager::StoreManager;
 use nativelink_util::common::DigestInfo;
@@ -180,12 +180,48 @@ mod get_action_result {
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn counts_cache_hits_and_misses() -> Result<(), Box<dyn std::error::Error>> {
+        let store_manager = make_store_manager().await?;
+        let cache_stats = Arc::new(AcCacheStats::default());
+        let ac_server = AcServer::new_with_cache_stats(
+            &hashmap! {
+                INSTANCE_NAME.to_string() => nativelink_config::cas_server::AcStoreConfig{
+                    ac_store: "main_ac".to_string(),
+                    read_only: false,
+                }
+            },
+            &store_manager,
+            cache_stats.clone(),
+        )?;
+        let ac_store_owned = store_manager.get_store("main_ac").unwrap();
+        let ac_store = Pin::new(ac_store_owned.as_ref());
+        insert_into_store(ac_store, HASH1, HASH1_SIZE, &ActionResult::default()).await?;
+
+        get_action_result(&ac_server, HASH1, HASH1_SIZE).await?;
+        get_action_result(&ac_server, HASH1, HASH1_SIZE).await?;
+        let _ = get_action_result(&ac_server, HASH1, HASH1_SIZE - 1).await;
+
+        assert_eq!(
+            cache_stats.counts(),
+            [(
+                INSTANCE_NAME.to_string(),
+                AcCacheCounts { hits: 2, misses: 1 }
+            )]
+            .into()
+        );
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod update_action_result {
+    use nativelink_config::cas_server::{AuthConfig, AuthPermission};
     use nativelink_proto::build::bazel::remote::execution::v2::UpdateActionResultRequest;
+    use nativelink_service::auth::Authenticator;
     use pretty_assertions::assert_eq; // Must be declared in every module.
+    use tonic::codegen::http::HeaderMap;
 
     use super::*;
 
@@ -249,4 +285,38 @@ mod update_action_result {
         assert_eq!(decoded_action_result, action_result);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn update_denied_to_read_only_principal() -> Result<(), Box<dyn std::error::Error>> {
+        let store_manager = make_store_manager().await?;
+        let ac_server = make_ac_server(&store_manager)?;
+        let authenticator = Arc::new(Authenticator::new(&AuthConfig {
+            mtls_subjects: hashmap! {
+                "ci-reader".to_string() => "reader".to_string(),
+            },
+            instances: hashmap! {
+                INSTANCE_NAME.to_string() => hashmap! {
+                    "reader".to_string() => AuthPermission::read_only,
+                },
+            },
+            ..Default::default()
+        })?);
+
+        let mut request = Request::new(UpdateActionResultRequest {
+            instance_name: INSTANCE_NAME.to_string(),
+            action_digest: Some(Digest {
+                hash: HASH1.to_string(),
+                size_bytes: 0,
+            }),
+            action_result: Some(ActionResult::default()),
+            results_cache_policy: None,
+            digest_function: digest_function::Value::Sha256.into(),
+        });
+        request
+            .extensions_mut()
+            .insert(authenticator.authenticate(&HeaderMap::new(), Some("ci-reader")));
+        let err = ac_server.update_action_result(request).await.unwrap_err();
+        assert_eq!(err.code(), Code::PermissionDenied);
+        Ok(())
+    }
 }
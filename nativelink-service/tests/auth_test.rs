@@ -0,0 +1,342 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::env;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use maplit::hashmap;
+use nativelink_config::cas_server::{AuthConfig, AuthPermission, JwtAuthConfig};
+use nativelink_error::{Code, Error};
+use nativelink_service::auth::{
+    authorize, authorize_worker, certificate_common_name, require_admin, AuthContext, Authenticator,
+};
+use rand::{thread_rng, Rng};
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+use tonic::codegen::http::HeaderMap;
+use tonic::Request;
+use tower::ServiceExt;
+
+const INSTANCE_NAME: &str = "foo_instance_name";
+
+/// Self signed certificate with subject "O=NativeLink, CN=worker-1".
+const CERTIFICATE: &str = "MIIBpjCCAUugAwIBAgIUFK+C1hU4H9RBd0zjTm0KyBE0ZSAwCgYIKoZIzj0EAwIwKDETMBEGA1UECgwKTmF0aXZlTGluazERMA8GA1UEAwwId29ya2VyLTEwHhcNMjYxMDE5MTExNDMzWhcNMzYxMDE2MTExNDMzWjAoMRMwEQYDVQQKDApOYXRpdmVMaW5rMREwDwYDVQQDDAh3b3JrZXItMTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABB5PFJOJLJ0yp71518EtnDJs/1aqrJU6WNv6tJaNekGLwGnzjA8eX5Gk2rNNHWgZzCXhIOdleDviDSx7g9kufB6jUzBRMB0GA1UdDgQWBBQbB94Dm1IWdBhQj1Pn4Wy4h8MJqTAfBgNVHSMEGDAWgBQbB94Dm1IWdBhQj1Pn4Wy4h8MJqTAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0kAMEYCIQD2Mnm9dHTB9hvzwPRGQ4L1H/C32Bn0bifjNeR+50FcSgIhALDpBbf1bGo48FVv0mUiowwyq/azP6KfrT0z9T0HQPD0";
+
+/// Writes `data` to a new file in either `TEST_TMPDIR` or best effort temp
+/// directory if not set.
+fn write_temp_file(data: &str) -> String {
+    let path = format!(
+        "{}/{}",
+        env::var("TEST_TMPDIR").unwrap_or(env::temp_dir().to_str().unwrap().to_string()),
+        thread_rng().gen::<u64>(),
+    );
+    std::fs::write(&path, data).unwrap();
+    path
+}
+
+fn make_config() -> AuthConfig {
+    AuthConfig {
+        tokens_file: Some(write_temp_file(
+            r#"{"reader-token": "reader", "writer-token": "writer"}"#,
+        )),
+        mtls_subjects: hashmap! {
+            "worker-1".to_string() => "worker".to_string(),
+        },
+        instances: hashmap! {
+            INSTANCE_NAME.to_string() => hashmap! {
+                "reader".to_string() => AuthPermission::read_only,
+                "writer".to_string() => AuthPermission::read_write,
+            },
+            "public".to_string() => hashmap! {
+                "*".to_string() => AuthPermission::read_only,
+            },
+        },
+        worker_principals: vec!["worker".to_string()],
+        admin_principals: vec!["writer".to_string()],
+        ..Default::default()
+    }
+}
+
+fn make_request(
+    authenticator: &Arc<Authenticator>,
+    headers: &[(&'static str, &str)],
+    peer_common_name: Option<&str>,
+) -> Request<()> {
+    let mut header_map = HeaderMap::new();
+    for (name, value) in headers {
+        header_map.insert(*name, value.parse().unwrap());
+    }
+    let mut request = Request::new(());
+    let auth_context: AuthContext = authenticator.authenticate(&header_map, peer_common_name);
+    request.extensions_mut().insert(auth_context);
+    request
+}
+
+fn error_code(result: Result<(), Error>) -> Option<Code> {
+    result.err().map(|e| e.code)
+}
+
+#[cfg(test)]
+mod auth_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*; // Must be declared in every module.
+
+    #[tokio::test]
+    async fn static_tokens_are_authorized_per_instance() -> Result<(), Error> {
+        let authenticator = Arc::new(Authenticator::new(&make_config())?);
+
+        let reader = make_request(&authenticator, &[("x-api-key", "reader-token")], None);
+        assert_eq!(
+            error_code(authorize(
+                reader.extensions(),
+                INSTANCE_NAME,
+                AuthPermission::read_only
+            )),
+            None
+        );
+        assert_eq!(
+            error_code(authorize(
+                reader.extensions(),
+                INSTANCE_NAME,
+                AuthPermission::read_write
+            )),
+            Some(Code::PermissionDenied)
+        );
+        assert_eq!(
+            error_code(authorize(
+                reader.extensions(),
+                "public",
+                AuthPermission::read_only
+            )),
+            None
+        );
+        assert_eq!(
+            error_code(authorize(
+                reader.extensions(),
+                "other_instance",
+                AuthPermission::read_only
+            )),
+            Some(Code::PermissionDenied)
+        );
+
+        let writer = make_request(
+            &authenticator,
+            &[("authorization", "Bearer writer-token")],
+            None,
+        );
+        assert_eq!(
+            error_code(authorize(
+                writer.extensions(),
+                INSTANCE_NAME,
+                AuthPermission::read_write
+            )),
+            None
+        );
+        assert_eq!(
+            error_code(authorize(
+                writer.extensions(),
+                INSTANCE_NAME,
+                AuthPermission::execute
+            )),
+            Some(Code::PermissionDenied)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn missing_or_unknown_credentials_are_unauthenticated() -> Result<(), Error> {
+        let authenticator = Arc::new(Authenticator::new(&make_config())?);
+
+        let unknown = make_request(
+            &authenticator,
+            &[("authorization", "Bearer bad-token")],
+            None,
+        );
+        assert_eq!(
+            error_code(authorize(
+                unknown.extensions(),
+                "public",
+                AuthPermission::read_only
+            )),
+            Some(Code::Unauthenticated)
+        );
+        let anonymous = make_request(&authenticator, &[], Some("unknown-host"));
+        assert_eq!(
+            error_code(authorize(
+                anonymous.extensions(),
+                "public",
+                AuthPermission::read_only
+            )),
+            Some(Code::Unauthenticated)
+        );
+
+        // Requests received on servers without auth are always allowed.
+        assert_eq!(
+            error_code(authorize(
+                Request::new(()).extensions(),
+                INSTANCE_NAME,
+                AuthPermission::execute
+            )),
+            None
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn client_certificates_map_to_principals() -> Result<(), Error> {
+        let certificate = STANDARD.decode(CERTIFICATE).unwrap();
+        let common_name = certificate_common_name(&certificate);
+        assert_eq!(common_name.as_deref(), Some("worker-1"));
+        assert_eq!(certificate_common_name(&certificate[..100]), None);
+
+        let authenticator = Arc::new(Authenticator::new(&make_config())?);
+        let worker = make_request(&authenticator, &[], common_name.as_deref());
+        assert_eq!(error_code(authorize_worker(worker.extensions())), None);
+        let reader = make_request(&authenticator, &[("x-api-key", "reader-token")], None);
+        assert_eq!(
+            error_code(authorize_worker(reader.extensions())),
+            Some(Code::PermissionDenied)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn admin_routes_require_admin_principal() -> Result<(), Error> {
+        let authenticator = Arc::new(Authenticator::new(&make_config())?);
+        let router = axum::Router::new()
+            .route("/", axum::routing::get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn(require_admin));
+        let status = |headers: &[(&'static str, &str)]| {
+            let mut header_map = HeaderMap::new();
+            for (name, value) in headers {
+                header_map.insert(*name, value.parse().unwrap());
+            }
+            let mut request = axum::http::Request::new(hyper::Body::empty());
+            request
+                .extensions_mut()
+                .insert(authenticator.authenticate(&header_map, None));
+            let router = router.clone();
+            async move { router.oneshot(request).await.unwrap().status() }
+        };
+
+        assert_eq!(status(&[]).await, axum::http::StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            status(&[("x-api-key", "reader-token")]).await,
+            axum::http::StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(&[("x-api-key", "writer-token")]).await,
+            axum::http::StatusCode::OK
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn jwt_is_validated_against_jwks() -> Result<(), Error> {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let key_pair =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+                .unwrap();
+        // Uncompressed point: 0x04 followed by x and y.
+        let public_key = key_pair.public_key().as_ref();
+        let jwks_file = write_temp_file(&format!(
+            r#"{{"keys": [{{"kty": "EC", "crv": "P-256", "kid": "key1", "x": "{}", "y": "{}"}}]}}"#,
+            URL_SAFE_NO_PAD.encode(&public_key[1..33]),
+            URL_SAFE_NO_PAD.encode(&public_key[33..]),
+        ));
+        let authenticator = Arc::new(Authenticator::new(&AuthConfig {
+            jwt: Some(JwtAuthConfig {
+                jwks_file,
+                issuer: Some("https://issuer.example.com".to_string()),
+                audience: Some("nativelink".to_string()),
+                principal_claim: String::new(),
+            }),
+            ..make_config()
+        })?);
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let make_token = |claims: String| {
+            let signed_data = format!(
+                "{}.{}",
+                URL_SAFE_NO_PAD.encode(r#"{"alg": "ES256", "kid": "key1"}"#),
+                URL_SAFE_NO_PAD.encode(claims)
+            );
+            let signature = key_pair.sign(&rng, signed_data.as_bytes()).unwrap();
+            format!(
+                "Bearer {signed_data}.{}",
+                URL_SAFE_NO_PAD.encode(signature.as_ref())
+            )
+        };
+        let check = |token: String| {
+            let request = make_request(&authenticator, &[("authorization", &token)], None);
+            error_code(authorize(
+                request.extensions(),
+                INSTANCE_NAME,
+                AuthPermission::read_write,
+            ))
+        };
+
+        assert_eq!(
+            check(make_token(format!(
+                r#"{{"sub": "writer", "iss": "https://issuer.example.com", "aud": ["nativelink"], "exp": {}}}"#,
+                now + 60
+            ))),
+            None
+        );
+        assert_eq!(
+            check(make_token(format!(
+                r#"{{"sub": "reader", "iss": "https://issuer.example.com", "aud": "nativelink", "exp": {}}}"#,
+                now + 60
+            ))),
+            Some(Code::PermissionDenied)
+        );
+        // Expired.
+        assert_eq!(
+            check(make_token(format!(
+                r#"{{"sub": "writer", "iss": "https://issuer.example.com", "aud": "nativelink", "exp": {}}}"#,
+                now - 60
+            ))),
+            Some(Code::Unauthenticated)
+        );
+        // Wrong issuer.
+        assert_eq!(
+            check(make_token(format!(
+                r#"{{"sub": "writer", "iss": "https://other.example.com", "aud": "nativelink", "exp": {}}}"#,
+                now + 60
+            ))),
+            Some(Code::Unauthenticated)
+        );
+        // Tampered claims.
+        let token = make_token(format!(
+            r#"{{"sub": "reader", "iss": "https://issuer.example.com", "aud": "nativelink", "exp": {}}}"#,
+            now + 60
+        ));
+        let mut parts: Vec<&str> = token.split('.').collect();
+        let tampered_claims = URL_SAFE_NO_PAD.encode(format!(
+            r#"{{"sub": "writer", "iss": "https://issuer.example.com", "aud": "nativelink", "exp": {}}}"#,
+            now + 60
+        ));
+        parts[1] = &tampered_claims;
+        assert_eq!(check(parts.join(".")), Some(Code::Unauthenticated));
+        Ok(())
+    }
+}
@@ -0,0 +1,464 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::pin::Pin;
+use std::sync::Arc;
+
+use bytes::{Bytes, BytesMut};
+use maplit::hashmap;
+use nativelink_config::cas_server::{AuthConfig, AuthPermission, BrowserConfig};
+use nativelink_error::{Code, Error};
+use nativelink_proto::build::bazel::remote::execution::v2::{
+    Action, ActionResult, Command, Directory, DirectoryNode, ExecuteResponse, FileNode, OutputFile,
+    Tree,
+};
+use nativelink_proto::com::github::trace_machina::nativelink::remote_execution::HistoricalExecuteResponse;
+use nativelink_proto::google::rpc::Status;
+use nativelink_service::auth::Authenticator;
+use nativelink_service::blob_browser::{BlobBrowser, BlobBrowserResponse};
+use nativelink_store::ac_utils::{message_to_digest, serialize_and_upload_message};
+use nativelink_store::default_store_factory::store_factory;
+use nativelink_store::store_manager::StoreManager;
+use nativelink_util::common::DigestInfo;
+use nativelink_util::digest_hasher::DigestHasherFunc;
+use prometheus_client::registry::Registry;
+use prost::Message;
+use tonic::codegen::http::HeaderMap;
+
+const INSTANCE_NAME: &str = "main";
+const BASE_PATH: &str = "/browser";
+const FILE_CONTENT: &str = "Hello <world>";
+
+async fn make_store_manager() -> Result<Arc<StoreManager>, Error> {
+    let store_manager = Arc::new(StoreManager::new());
+    for store_name in ["main_cas", "main_ac"] {
+        store_manager.add_store(
+            store_name,
+            store_factory(
+                &nativelink_config::stores::StoreConfig::memory(
+                    nativelink_config::stores::MemoryStore::default(),
+                ),
+                &store_manager,
+                Some(&mut <Registry>::default()),
+                None,
+            )
+            .await?,
+        );
+    }
+    Ok(store_manager)
+}
+
+fn make_browser(store_manager: &StoreManager) -> Result<BlobBrowser, Error> {
+    BlobBrowser::new(
+        &BrowserConfig {
+            path: String::new(),
+            cas_stores: hashmap! {
+                INSTANCE_NAME.to_string() => "main_cas".to_string(),
+            },
+            ac_stores: hashmap! {
+                INSTANCE_NAME.to_string() => "main_ac".to_string(),
+            },
+        },
+        BASE_PATH,
+        store_manager,
+    )
+}
+
+fn blob_path(kind: &str, digest: &DigestInfo) -> String {
+    format!(
+        "/{INSTANCE_NAME}/blobs/sha256/{kind}/{}-{}/",
+        digest.hash_str(),
+        digest.size_bytes
+    )
+}
+
+async fn get_page(browser: &BlobBrowser, path: &str) -> Result<String, Error> {
+    match browser.handle(path, None).await? {
+        BlobBrowserResponse::Page(html) => Ok(html),
+        BlobBrowserResponse::File { .. } => panic!("Expected a page for {path}"),
+    }
+}
+
+/// Uploads an Action with a cached result that has a single output file.
+/// Returns the digests of the action, its command, its input root and the
+/// output file.
+async fn upload_action(
+    store_manager: &StoreManager,
+) -> Result<(DigestInfo, DigestInfo, DigestInfo, DigestInfo), Error> {
+    let cas_store = store_manager.get_store("main_cas").unwrap();
+    let cas_store = Pin::new(cas_store.as_ref());
+    let ac_store = store_manager.get_store("main_ac").unwrap();
+
+    let file_digest = DigestInfo::try_new(
+        "a2a1c1e9ae1a1a2ac3ea8e05c7eb0b4a9a9c5d1e93d87c8d82ff6dd5c8dbf6a5",
+        FILE_CONTENT.len(),
+    )?;
+    cas_store
+        .update_oneshot(file_digest, Bytes::from_static(FILE_CONTENT.as_bytes()))
+        .await?;
+    let command_digest = serialize_and_upload_message(
+        &Command {
+            arguments: vec!["echo".to_string(), "<hello>".to_string()],
+            ..Default::default()
+        },
+        cas_store,
+        &mut DigestHasherFunc::Sha256.hasher(),
+    )
+    .await?;
+    let input_root_digest = serialize_and_upload_message(
+        &Directory {
+            files: vec![FileNode {
+                name: "input file.txt".to_string(),
+                digest: Some(file_digest.into()),
+                is_executable: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+        cas_store,
+        &mut DigestHasherFunc::Sha256.hasher(),
+    )
+    .await?;
+    let action_digest = serialize_and_upload_message(
+        &Action {
+            command_digest: Some(command_digest.into()),
+            input_root_digest: Some(input_root_digest.into()),
+            ..Default::default()
+        },
+        cas_store,
+        &mut DigestHasherFunc::Sha256.hasher(),
+    )
+    .await?;
+    let action_result = ActionResult {
+        exit_code: 3,
+        stdout_raw: "stdout <output>".into(),
+        output_files: vec![OutputFile {
+            path: "out/result.txt".to_string(),
+            digest: Some(file_digest.into()),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    Pin::new(ac_store.as_ref())
+        .update_oneshot(action_digest, action_result.encode_to_vec().into())
+        .await?;
+    Ok((
+        action_digest,
+        command_digest,
+        input_root_digest,
+        file_digest,
+    ))
+}
+
+#[cfg(test)]
+mod blob_browser_tests {
+    use pretty_assertions::assert_eq; // Must be declared in every module.
+
+    use super::*;
+
+    #[tokio::test]
+    async fn action_page_links_to_command_input_root_and_result(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let store_manager = make_store_manager().await?;
+        let browser = make_browser(&store_manager)?;
+        let (action_digest, command_digest, input_root_digest, file_digest) =
+            upload_action(&store_manager).await?;
+
+        let html = get_page(&browser, &blob_path("action", &action_digest)).await?;
+        assert!(html.contains(&format!(
+            "href=\"{BASE_PATH}{}\"",
+            blob_path("command", &command_digest)
+        )));
+        assert!(html.contains(&format!(
+            "href=\"{BASE_PATH}{}\"",
+            blob_path("directory", &input_root_digest)
+        )));
+        assert!(html.contains("Exit code: 3"));
+        assert!(html.contains("<pre>stdout &lt;output&gt;</pre>"));
+        assert!(html.contains(&format!(
+            "href=\"{BASE_PATH}{}result.txt\"",
+            blob_path("file", &file_digest)
+        )));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn command_and_directory_pages() -> Result<(), Box<dyn std::error::Error>> {
+        let store_manager = make_store_manager().await?;
+        let browser = make_browser(&store_manager)?;
+        let (_, command_digest, input_root_digest, file_digest) =
+            upload_action(&store_manager).await?;
+
+        let html = get_page(&browser, &blob_path("command", &command_digest)).await?;
+        assert!(html.contains("<pre>echo \\\n    &lt;hello&gt;</pre>"));
+
+        let html = get_page(&browser, &blob_path("directory", &input_root_digest)).await?;
+        assert!(html.contains(&format!(
+            "<a href=\"{BASE_PATH}{}input%20file.txt\">input file.txt</a> ({} bytes, executable)",
+            blob_path("file", &file_digest),
+            FILE_CONTENT.len()
+        )));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn tree_page_renders_each_directory_once() -> Result<(), Box<dyn std::error::Error>> {
+        let store_manager = make_store_manager().await?;
+        let browser = make_browser(&store_manager)?;
+        let (_, _, _, file_digest) = upload_action(&store_manager).await?;
+        let cas_store = store_manager.get_store("main_cas").unwrap();
+        let digest_of = |directory: &Directory| {
+            message_to_digest(
+                directory,
+                &mut BytesMut::new(),
+                &mut DigestHasherFunc::Sha256.hasher(),
+            )
+        };
+        let directory_node = |name: &str, digest: DigestInfo| DirectoryNode {
+            name: name.to_string(),
+            digest: Some(digest.into()),
+        };
+
+        // Both `a` and `b` contain the same directory.
+        let shared = Directory {
+            files: vec![FileNode {
+                name: "shared.txt".to_string(),
+                digest: Some(file_digest.into()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let shared_digest = digest_of(&shared)?;
+        let tree_digest = serialize_and_upload_message(
+            &Tree {
+                root: Some(Directory {
+                    directories: vec![
+                        directory_node("a", shared_digest),
+                        directory_node("b", shared_digest),
+                        directory_node("c", DigestInfo::new([1u8; 32], 10)),
+                    ],
+                    ..Default::default()
+                }),
+                children: vec![shared],
+            },
+            Pin::new(cas_store.as_ref()),
+            &mut DigestHasherFunc::Sha256.hasher(),
+        )
+        .await?;
+
+        let html = get_page(&browser, &blob_path("tree", &tree_digest)).await?;
+        let anchor = format!("{}-{}", shared_digest.hash_str(), shared_digest.size_bytes);
+        assert_eq!(html.matches(&format!("id=\"{anchor}\"")).count(), 1);
+        assert_eq!(html.matches(&format!("href=\"#{anchor}\"")).count(), 2);
+        assert_eq!(html.matches("shared.txt</a>").count(), 1);
+        assert!(html.contains("<li>c/ (missing from tree)</li>"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn deep_tree_page_is_rendered() -> Result<(), Box<dyn std::error::Error>> {
+        const DEPTH: usize = 10_000;
+        let store_manager = make_store_manager().await?;
+        let browser = make_browser(&store_manager)?;
+        let cas_store = store_manager.get_store("main_cas").unwrap();
+
+        // Each directory only contains the next one.
+        let mut children = Vec::with_capacity(DEPTH);
+        let mut directory = Directory::default();
+        for _ in 0..DEPTH {
+            let digest = message_to_digest(
+                &directory,
+                &mut BytesMut::new(),
+                &mut DigestHasherFunc::Sha256.hasher(),
+            )?;
+            children.push(directory);
+            directory = Directory {
+                directories: vec![DirectoryNode {
+                    name: "d".to_string(),
+                    digest: Some(digest.into()),
+                }],
+                ..Default::default()
+            };
+        }
+        let tree_digest = serialize_and_upload_message(
+            &Tree {
+                root: Some(directory),
+                children,
+            },
+            Pin::new(cas_store.as_ref()),
+            &mut DigestHasherFunc::Sha256.hasher(),
+        )
+        .await?;
+
+        let html = get_page(&browser, &blob_path("tree", &tree_digest)).await?;
+        assert_eq!(html.matches("<h2").count(), DEPTH + 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn historical_execute_response_page_shows_result(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let store_manager = make_store_manager().await?;
+        let browser = make_browser(&store_manager)?;
+        let (action_digest, _, _, _) = upload_action(&store_manager).await?;
+        let cas_store = store_manager.get_store("main_cas").unwrap();
+
+        let stdout = "x".repeat(100 * 1024);
+        let historical_execute_response_digest = serialize_and_upload_message(
+            &HistoricalExecuteResponse {
+                action_digest: Some(action_digest.into()),
+                execute_response: Some(ExecuteResponse {
+                    result: Some(ActionResult {
+                        exit_code: 1,
+                        stdout_raw: stdout.clone().into(),
+                        ..Default::default()
+                    }),
+                    status: Some(Status {
+                        code: Code::Internal as i32,
+                        message: "<failed>".to_string(),
+                        ..Default::default()
+                    }),
+                    message: "see logs".to_string(),
+                    ..Default::default()
+                }),
+            },
+            Pin::new(cas_store.as_ref()),
+            &mut DigestHasherFunc::Sha256.hasher(),
+        )
+        .await?;
+
+        let html = get_page(
+            &browser,
+            &blob_path(
+                "historical_execute_response",
+                &historical_execute_response_digest,
+            ),
+        )
+        .await?;
+        assert!(html.contains(&format!(
+            "href=\"{BASE_PATH}{}\"",
+            blob_path("action", &action_digest)
+        )));
+        assert!(html.contains("Status: Internal &lt;failed&gt;"));
+        assert!(html.contains("Message: see logs"));
+        assert!(html.contains("Exit code: 1"));
+        // Only the start of the output is shown.
+        assert!(html.contains(&format!("<pre>{}</pre>", &stdout[..64 * 1024])));
+        assert!(html.contains("Output truncated"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn file_is_downloaded() -> Result<(), Box<dyn std::error::Error>> {
+        let store_manager = make_store_manager().await?;
+        let browser = make_browser(&store_manager)?;
+        let (_, _, _, file_digest) = upload_action(&store_manager).await?;
+
+        let path = format!("{}result.txt", blob_path("file", &file_digest));
+        let BlobBrowserResponse::File { name, size, reader } = browser.handle(&path, None).await?
+        else {
+            panic!("Expected a file");
+        };
+        assert_eq!(name, "result.txt");
+        assert_eq!(size, FILE_CONTENT.len() as u64);
+        assert_eq!(
+            reader.collect_all_with_size_hint(0).await?,
+            Bytes::from_static(FILE_CONTENT.as_bytes())
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invalid_requests_fail() -> Result<(), Box<dyn std::error::Error>> {
+        let store_manager = make_store_manager().await?;
+        let browser = make_browser(&store_manager)?;
+        let (action_digest, _, _, _) = upload_action(&store_manager).await?;
+
+        let err = |path: String| {
+            let browser = &browser;
+            async move {
+                match browser.handle(&path, None).await {
+                    Ok(_) => panic!("Expected {path} to fail"),
+                    Err(err) => err.code,
+                }
+            }
+        };
+        assert_eq!(
+            err(format!(
+                "/{INSTANCE_NAME}/action/{}",
+                action_digest.hash_str()
+            ))
+            .await,
+            Code::InvalidArgument
+        );
+        assert_eq!(
+            err(blob_path("unknown", &action_digest)).await,
+            Code::InvalidArgument
+        );
+        assert_eq!(
+            err(blob_path("action", &action_digest).replace("sha256", "md5")).await,
+            Code::InvalidArgument
+        );
+        assert_eq!(
+            err(blob_path("action", &action_digest).replace(INSTANCE_NAME, "other")).await,
+            Code::NotFound
+        );
+        assert_eq!(
+            err(blob_path("action", &DigestInfo::new([1u8; 32], 10))).await,
+            Code::NotFound
+        );
+        assert_eq!(
+            err(blob_path("file", &DigestInfo::new([1u8; 32], 10))).await,
+            Code::NotFound
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pages_need_read_permission_on_instance() -> Result<(), Box<dyn std::error::Error>> {
+        let store_manager = make_store_manager().await?;
+        let browser = make_browser(&store_manager)?;
+        let (action_digest, _, _, _) = upload_action(&store_manager).await?;
+        let path = blob_path("action", &action_digest);
+        let authenticator = Arc::new(Authenticator::new(&AuthConfig {
+            instances: hashmap! {
+                INSTANCE_NAME.to_string() => hashmap! {
+                    "reader".to_string() => AuthPermission::read_only,
+                },
+            },
+            mtls_subjects: hashmap! {
+                "reader-1".to_string() => "reader".to_string(),
+                "other-1".to_string() => "other".to_string(),
+            },
+            ..Default::default()
+        })?);
+        let handle = |peer_common_name: Option<&'static str>| {
+            let auth_context = authenticator.authenticate(&HeaderMap::new(), peer_common_name);
+            let browser = &browser;
+            let path = &path;
+            async move {
+                browser
+                    .handle(path, Some(&auth_context))
+                    .await
+                    .err()
+                    .map(|err| err.code)
+            }
+        };
+
+        assert_eq!(handle(None).await, Some(Code::Unauthenticated));
+        assert_eq!(handle(Some("other-1")).await, Some(Code::PermissionDenied));
+        assert_eq!(handle(Some("reader-1")).await, None);
+        Ok(())
+    }
+}
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::pin::Pin;
 use std::sync::Arc;
 
@@ -57,8 +58,10 @@ fn make_bytestream_server(store_manager: &StoreManager) -> Result<ByteStreamServ
             },
             persist_stream_on_disconnect_timeout: 0,
             max_bytes_per_stream: 1024,
+            action_output_schedulers: HashMap::new(),
         },
         store_manager,
+        &HashMap::new(),
     )
 }
 
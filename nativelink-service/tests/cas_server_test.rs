@@ -20,7 +20,7 @@ use nativelink_error::Error;
 use nativelink_proto::build::bazel::remote::execution::v2::content_addressable_storage_server::ContentAddressableStorage;
 use nativelink_proto::build::bazel::remote::execution::v2::{compressor, digest_function, Digest};
 use nativelink_proto::google::rpc::Status as GrpcStatus;
-use nativelink_service::cas_server::CasServer;
+use nativelink_service::cas_server::{supports_split_blob, CasServer};
 use nativelink_store::default_store_factory::store_factory;
 use nativelink_store::store_manager::StoreManager;
 use nativelink_util::common::DigestInfo;
@@ -55,6 +55,7 @@ fn make_cas_server(store_manager: &StoreManager) -> Result<CasServer, Error> {
         &hashmap! {
             "foo_instance_name".to_string() => nativelink_config::cas_server::CasStoreConfig{
                 cas_store: "main_cas".to_string(),
+                pin_found_blobs_seconds: 0,
             }
         },
         store_manager,
@@ -157,6 +158,60 @@ mod find_missing_blobs {
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn found_blobs_are_pinned() -> Result<(), Box<dyn std::error::Error>> {
+        let store_manager = Arc::new(StoreManager::new());
+        store_manager.add_store(
+            "main_cas",
+            store_factory(
+                &nativelink_config::stores::StoreConfig::memory(
+                    nativelink_config::stores::MemoryStore {
+                        eviction_policy: Some(nativelink_config::stores::EvictionPolicy {
+                            max_count: 1,
+                            max_pinned_bytes: 100,
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    },
+                ),
+                &store_manager,
+                None,
+                None,
+            )
+            .await?,
+        );
+        let cas_server = CasServer::new(
+            &hashmap! {
+                INSTANCE_NAME.to_string() => nativelink_config::cas_server::CasStoreConfig{
+                    cas_store: "main_cas".to_string(),
+                    pin_found_blobs_seconds: 60,
+                }
+            },
+            &store_manager,
+        )?;
+        let store_owned = store_manager.get_store("main_cas").unwrap();
+        let store = Pin::new(store_owned.as_ref());
+
+        const VALUE: &str = "1";
+        let digest1 = DigestInfo::try_new(HASH1, VALUE.len())?;
+        let digest2 = DigestInfo::try_new(HASH2, VALUE.len())?;
+        store.update_oneshot(digest1, VALUE.into()).await?;
+        let response = cas_server
+            .find_missing_blobs(Request::new(FindMissingBlobsRequest {
+                instance_name: INSTANCE_NAME.to_string(),
+                blob_digests: vec![digest1.into()],
+                digest_function: digest_function::Value::Sha256.into(),
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(response.missing_blob_digests.len(), 0);
+
+        // The store only holds one item, but the pinned blob must not be evicted.
+        store.update_oneshot(digest2, VALUE.into()).await?;
+        assert_eq!(store.has(digest1).await?, Some(VALUE.len()));
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -428,3 +483,242 @@ mod end_to_end {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod split_blob {
+    use nativelink_proto::build::bazel::remote::execution::v2::SplitBlobRequest;
+    use pretty_assertions::assert_eq; // Must be declared in every module.
+    use tonic::Code;
+
+    use super::*;
+
+    async fn make_dedup_store_manager() -> Result<Arc<StoreManager>, Error> {
+        let store_manager = Arc::new(StoreManager::new());
+        store_manager.add_store(
+            "main_cas",
+            store_factory(
+                &nativelink_config::stores::StoreConfig::dedup(Box::new(
+                    nativelink_config::stores::DedupStore {
+                        index_store: nativelink_config::stores::StoreConfig::memory(
+                            nativelink_config::stores::MemoryStore::default(),
+                        ),
+                        content_store: nativelink_config::stores::StoreConfig::memory(
+                            nativelink_config::stores::MemoryStore::default(),
+                        ),
+                        min_size: 8,
+                        normal_size: 32,
+                        max_size: 64,
+                        max_concurrent_fetch_per_get: 10,
+                        track_chunk_references: false,
+                    },
+                )),
+                &store_manager,
+                Some(&mut <Registry>::default()),
+                None,
+            )
+            .await?,
+        );
+        Ok(store_manager)
+    }
+
+    #[tokio::test]
+    async fn split_blob_returns_readable_chunks() -> Result<(), Box<dyn std::error::Error>> {
+        let store_manager = make_dedup_store_manager().await?;
+        let cas_server = make_cas_server(&store_manager)?;
+        let store = store_manager.get_store("main_cas").unwrap();
+
+        let data: Vec<u8> = (0..1024u32).map(|i| (i * 7919 % 251) as u8).collect();
+        let digest = DigestInfo::try_new(HASH1, data.len())?;
+        Pin::new(store.as_ref())
+            .update_oneshot(digest, data.clone().into())
+            .await?;
+
+        let response = cas_server
+            .split_blob(Request::new(SplitBlobRequest {
+                instance_name: INSTANCE_NAME.to_string(),
+                blob_digest: Some(digest.into()),
+                digest_function: digest_function::Value::Blake3.into(),
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(
+            response.digest_function,
+            i32::from(digest_function::Value::Blake3)
+        );
+        assert!(
+            response.chunk_digests.len() > 1,
+            "Expected blob to be split into multiple chunks"
+        );
+
+        let mut spliced_data = Vec::with_capacity(data.len());
+        for chunk_digest in response.chunk_digests {
+            let chunk = Pin::new(store.as_ref())
+                .get_part_unchunked(DigestInfo::try_from(chunk_digest)?, 0, None, None)
+                .await?;
+            spliced_data.extend_from_slice(&chunk);
+        }
+        assert_eq!(spliced_data, data);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn split_blob_missing_blob() -> Result<(), Box<dyn std::error::Error>> {
+        let store_manager = make_dedup_store_manager().await?;
+        let cas_server = make_cas_server(&store_manager)?;
+
+        let result = cas_server
+            .split_blob(Request::new(SplitBlobRequest {
+                instance_name: INSTANCE_NAME.to_string(),
+                blob_digest: Some(DigestInfo::try_new(HASH1, 10)?.into()),
+                digest_function: digest_function::Value::Blake3.into(),
+            }))
+            .await;
+        assert_eq!(result.unwrap_err().code(), Code::NotFound);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn split_blob_requires_dedup_store() -> Result<(), Box<dyn std::error::Error>> {
+        let store_manager = make_store_manager().await?;
+        let cas_server = make_cas_server(&store_manager)?;
+
+        let result = cas_server
+            .split_blob(Request::new(SplitBlobRequest {
+                instance_name: INSTANCE_NAME.to_string(),
+                blob_digest: Some(DigestInfo::try_new(HASH1, 10)?.into()),
+                digest_function: digest_function::Value::Blake3.into(),
+            }))
+            .await;
+        assert_eq!(result.unwrap_err().code(), Code::Unimplemented);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn split_blob_rejects_other_digest_functions() -> Result<(), Box<dyn std::error::Error>> {
+        let store_manager = make_dedup_store_manager().await?;
+        let cas_server = make_cas_server(&store_manager)?;
+
+        let result = cas_server
+            .split_blob(Request::new(SplitBlobRequest {
+                instance_name: INSTANCE_NAME.to_string(),
+                blob_digest: Some(DigestInfo::try_new(HASH1, 10)?.into()),
+                digest_function: digest_function::Value::Sha256.into(),
+            }))
+            .await;
+        assert_eq!(result.unwrap_err().code(), Code::InvalidArgument);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn supports_split_blob_only_for_dedup_stores() -> Result<(), Box<dyn std::error::Error>> {
+        let dedup_store_manager = make_dedup_store_manager().await?;
+        let dedup_store = dedup_store_manager.get_store("main_cas").unwrap();
+        assert!(supports_split_blob(dedup_store.as_ref()));
+
+        let store_manager = make_store_manager().await?;
+        let store = store_manager.get_store("main_cas").unwrap();
+        assert!(!supports_split_blob(store.as_ref()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod splice_blob {
+    use bytes::Bytes;
+    use nativelink_proto::build::bazel::remote::execution::v2::SpliceBlobRequest;
+    use nativelink_util::digest_hasher::{DigestHasher, DigestHasherFunc};
+    use pretty_assertions::assert_eq; // Must be declared in every module.
+    use tonic::Code;
+
+    use super::*;
+
+    fn sha256_digest(data: &[u8]) -> DigestInfo {
+        let mut hasher = DigestHasherFunc::Sha256.hasher();
+        hasher.update(data);
+        hasher.finalize_digest()
+    }
+
+    async fn upload_chunks(
+        store_manager: &StoreManager,
+        chunks: &[&'static str],
+    ) -> Result<Vec<Digest>, Error> {
+        let store = store_manager.get_store("main_cas").unwrap();
+        let mut digests = Vec::with_capacity(chunks.len());
+        for chunk in chunks {
+            let digest = sha256_digest(chunk.as_bytes());
+            Pin::new(store.as_ref())
+                .update_oneshot(digest, Bytes::from_static(chunk.as_bytes()))
+                .await?;
+            digests.push(digest.into());
+        }
+        Ok(digests)
+    }
+
+    #[tokio::test]
+    async fn splice_blob_assembles_chunks() -> Result<(), Box<dyn std::error::Error>> {
+        let store_manager = make_store_manager().await?;
+        let cas_server = make_cas_server(&store_manager)?;
+        let chunk_digests = upload_chunks(&store_manager, &["hello ", "spliced ", "world"]).await?;
+        let blob_digest = sha256_digest(b"hello spliced world");
+
+        let response = cas_server
+            .splice_blob(Request::new(SpliceBlobRequest {
+                instance_name: INSTANCE_NAME.to_string(),
+                blob_digest: Some(blob_digest.into()),
+                chunk_digests,
+                digest_function: digest_function::Value::Sha256.into(),
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(response.blob_digest, Some(blob_digest.into()));
+
+        let store = store_manager.get_store("main_cas").unwrap();
+        let data = Pin::new(store.as_ref())
+            .get_part_unchunked(blob_digest, 0, None, None)
+            .await?;
+        assert_eq!(data, "hello spliced world");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn splice_blob_rejects_mismatched_digest() -> Result<(), Box<dyn std::error::Error>> {
+        let store_manager = make_store_manager().await?;
+        let cas_server = make_cas_server(&store_manager)?;
+        let chunk_digests = upload_chunks(&store_manager, &["hello ", "spliced ", "world"]).await?;
+        let blob_digest = sha256_digest(b"hello spliced word!");
+
+        let result = cas_server
+            .splice_blob(Request::new(SpliceBlobRequest {
+                instance_name: INSTANCE_NAME.to_string(),
+                blob_digest: Some(blob_digest.into()),
+                chunk_digests,
+                digest_function: digest_function::Value::Sha256.into(),
+            }))
+            .await;
+        assert_eq!(result.unwrap_err().code(), Code::InvalidArgument);
+
+        let store = store_manager.get_store("main_cas").unwrap();
+        assert_eq!(Pin::new(store.as_ref()).has(blob_digest).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn splice_blob_missing_chunk() -> Result<(), Box<dyn std::error::Error>> {
+        let store_manager = make_store_manager().await?;
+        let cas_server = make_cas_server(&store_manager)?;
+        let mut chunk_digests = upload_chunks(&store_manager, &["hello ", "world"]).await?;
+        chunk_digests.push(DigestInfo::try_new(HASH2, 3)?.into());
+        let blob_digest = sha256_digest(b"hello worldfoo");
+
+        let result = cas_server
+            .splice_blob(Request::new(SpliceBlobRequest {
+                instance_name: INSTANCE_NAME.to_string(),
+                blob_digest: Some(blob_digest.into()),
+                chunk_digests,
+                digest_function: digest_function::Value::Sha256.into(),
+            }))
+            .await;
+        assert_eq!(result.unwrap_err().code(), Code::NotFound);
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use nativelink_error::{make_err, Code, Error};
+use nativelink_scheduler::action_scheduler::ActionScheduler;
+use nativelink_scheduler::platform_property_manager::PlatformPropertyManager;
+use nativelink_scheduler::simple_scheduler::SimpleScheduler;
+use nativelink_scheduler::worker::{Worker, WorkerId};
+use nativelink_scheduler::worker_scheduler::WorkerScheduler;
+use nativelink_service::ac_server::AcCacheStats;
+use nativelink_service::dashboard::{Dashboard, QueueDepth, WorkerFleet};
+use nativelink_util::action_messages::{
+    ActionInfo, ActionInfoHashKey, ActionResult, ActionStage, ActionState, ExecutionMetadata,
+};
+use nativelink_util::common::DigestInfo;
+use nativelink_util::digest_hasher::DigestHasherFunc;
+use nativelink_util::health_utils::{
+    CachedHealthRegistry, HealthRegistryBuilder, HealthStatus, HealthStatusIndicator,
+};
+use nativelink_util::platform_properties::{PlatformProperties, PlatformPropertyValue};
+use tokio::sync::{mpsc, watch};
+
+const INSTANCE_NAME: &str = "instance_name";
+const WORKER_ID: WorkerId = WorkerId(0x1234_5678);
+
+struct MockIndicator;
+
+#[tonic::async_trait]
+impl HealthStatusIndicator for MockIndicator {
+    fn get_name(&self) -> &'static str {
+        "MockIndicator"
+    }
+
+    async fn check_health(&self, _namespace: Cow<'static, str>) -> HealthStatus {
+        HealthStatus::new_ok(self, "ok".into())
+    }
+}
+
+/// Scheduler that supports none of the queries of the dashboard, like a
+/// scheduler that forwards to a remote scheduler.
+struct OpaqueScheduler;
+
+#[tonic::async_trait]
+impl ActionScheduler for OpaqueScheduler {
+    async fn get_platform_property_manager(
+        &self,
+        _instance_name: &str,
+    ) -> Result<Arc<PlatformPropertyManager>, Error> {
+        Err(make_err!(Code::Unimplemented, "Not implemented"))
+    }
+
+    async fn add_action(
+        &self,
+        _action_info: ActionInfo,
+    ) -> Result<watch::Receiver<Arc<ActionState>>, Error> {
+        Err(make_err!(Code::Unimplemented, "Not implemented"))
+    }
+
+    async fn find_existing_action(
+        &self,
+        _unique_qualifier: &ActionInfoHashKey,
+    ) -> Option<watch::Receiver<Arc<ActionState>>> {
+        None
+    }
+
+    async fn clean_recently_completed_actions(&self) {}
+}
+
+fn make_action_info(digest: DigestInfo, platform_properties: PlatformProperties) -> ActionInfo {
+    ActionInfo {
+        command_digest: DigestInfo::new([0u8; 32], 0),
+        input_root_digest: DigestInfo::new([0u8; 32], 0),
+        timeout: Duration::MAX,
+        platform_properties,
+        priority: 0,
+        load_timestamp: UNIX_EPOCH,
+        insert_timestamp: UNIX_EPOCH,
+        unique_qualifier: ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest,
+            salt: 0,
+        },
+        skip_cache_lookup: true,
+        digest_function: DigestHasherFunc::Sha256,
+    }
+}
+
+#[cfg(test)]
+mod dashboard_tests {
+    use pretty_assertions::assert_eq; // Must be declared in every module.
+
+    use super::*;
+
+    #[tokio::test]
+    async fn state_shows_schedulers_workers_and_health() -> Result<(), Box<dyn std::error::Error>> {
+        let scheduler = Arc::new(SimpleScheduler::new_with_callback(
+            &nativelink_config::schedulers::SimpleScheduler::default(),
+            || async move {},
+        ));
+        let (tx, _rx_from_worker) = mpsc::unbounded_channel();
+        scheduler
+            .add_worker(Worker::new(WORKER_ID, PlatformProperties::default(), tx, 0))
+            .await?;
+
+        let completed_digest = DigestInfo::new([99u8; 32], 512);
+        let _completed_rx = scheduler
+            .add_action(make_action_info(
+                completed_digest,
+                PlatformProperties::default(),
+            ))
+            .await?;
+        let mut linux_properties = PlatformProperties::default();
+        linux_properties.properties.insert(
+            "OSFamily".to_string(),
+            PlatformPropertyValue::Exact("linux".to_string()),
+        );
+        let _queued_rx = scheduler
+            .add_action(make_action_info(
+                DigestInfo::new([88u8; 32], 512),
+                linux_properties,
+            ))
+            .await?;
+        tokio::task::yield_now().await; // Allow task<->worker matcher to run.
+        scheduler
+            .update_action(
+                &WORKER_ID,
+                &ActionInfoHashKey {
+                    instance_name: INSTANCE_NAME.to_string(),
+                    digest: completed_digest,
+                    salt: 0,
+                },
+                ActionStage::Completed(ActionResult {
+                    exit_code: 3,
+                    execution_metadata: ExecutionMetadata {
+                        worker: WORKER_ID.to_string(),
+                        ..ExecutionMetadata::default()
+                    },
+                    error: None,
+                    ..ActionResult::default()
+                }),
+            )
+            .await?;
+
+        let mut health_registry_builder = HealthRegistryBuilder::new("nativelink".into());
+        health_registry_builder
+            .sub_builder("stores/CAS_STORE".into())
+            .register_indicator(Arc::new(MockIndicator));
+
+        let dashboard = Dashboard::new(
+            HashMap::from([
+                (
+                    "main".to_string(),
+                    scheduler.clone() as Arc<dyn ActionScheduler>,
+                ),
+                (
+                    "remote".to_string(),
+                    Arc::new(OpaqueScheduler) as Arc<dyn ActionScheduler>,
+                ),
+            ]),
+            HashMap::from([(
+                "main".to_string(),
+                scheduler.clone() as Arc<dyn WorkerScheduler>,
+            )]),
+            Arc::new(AcCacheStats::default()),
+            CachedHealthRegistry::new(health_registry_builder.build(), Duration::from_secs(3600)),
+        );
+        let state = dashboard.state().await;
+
+        assert_eq!(state.schedulers.len(), 2);
+        let main = &state.schedulers[0];
+        assert_eq!(main.name, "main");
+        assert_eq!(
+            main.queue,
+            vec![QueueDepth {
+                platform_properties: [("OSFamily".to_string(), "linux".to_string())].into(),
+                queued: 1,
+                executing: 0,
+                oldest_queued_age_s: main.queue[0].oldest_queued_age_s,
+            }]
+        );
+        assert_eq!(
+            main.fleet,
+            WorkerFleet {
+                total: 1,
+                busy: 0,
+                paused: 0,
+                draining: 0,
+                running_actions: 0,
+            }
+        );
+        assert_eq!(main.recent_completions.len(), 1);
+        assert_eq!(main.recent_completions[0].exit_code, 3);
+        assert_eq!(main.errors, Vec::<String>::new());
+
+        // The state of other schedulers is still shown if one can't be queried.
+        let remote = &state.schedulers[1];
+        assert_eq!(remote.name, "remote");
+        assert_eq!(remote.queue, vec![]);
+        assert_eq!(remote.errors.len(), 2);
+
+        assert_eq!(state.cache_hit_rates, vec![]);
+        assert_eq!(state.health.len(), 1);
+        assert_eq!(
+            state.health[0].namespace,
+            "/nativelink/stores/CAS_STORE/MockIndicator"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet};
+use std::pin::Pin;
+use std::sync::Arc;
+
+use maplit::hashmap;
+use nativelink_config::cas_server::ExecutionConfig;
+use nativelink_config::stores::{
+    MemoryStore as MemoryStoreConfig, RoutingRule, RoutingStore as RoutingStoreConfig, StoreConfig,
+};
+use nativelink_error::Error;
+use nativelink_proto::build::bazel::remote::execution::v2::execution_server::Execution;
+use nativelink_proto::build::bazel::remote::execution::v2::{
+    digest_function, Action, Command, ExecuteRequest,
+};
+use nativelink_scheduler::action_scheduler::ActionScheduler;
+use nativelink_scheduler::simple_scheduler::SimpleScheduler;
+use nativelink_service::execution_server::ExecutionServer;
+use nativelink_store::ac_utils::serialize_and_upload_message;
+use nativelink_store::memory_store::MemoryStore;
+use nativelink_store::routing_store::RoutingStore;
+use nativelink_store::store_manager::StoreManager;
+use nativelink_util::digest_hasher::DigestHasherFunc;
+use nativelink_util::store_trait::Store;
+use tonic::Request;
+
+const TOOLCHAINS_INSTANCE_NAME: &str = "toolchains";
+const SCHEDULER_NAME: &str = "MAIN_SCHEDULER";
+
+/// Sets up a CAS that keeps the blobs of the toolchains instance in a
+/// separate store and returns it along with the toolchains store.
+fn make_routing_cas() -> Result<(Arc<RoutingStore>, Arc<MemoryStore>), Error> {
+    let toolchains_store = Arc::new(MemoryStore::new(&MemoryStoreConfig::default()));
+    let routing_store = RoutingStore::new(
+        &RoutingStoreConfig {
+            rules: vec![RoutingRule {
+                store: StoreConfig::memory(MemoryStoreConfig::default()),
+                min_size: 0,
+                max_size: 0,
+                digest_functions: vec![],
+                instance_names: vec![TOOLCHAINS_INSTANCE_NAME.to_string()],
+                access: None,
+                digest_prefixes: vec![],
+            }],
+            default_store: StoreConfig::memory(MemoryStoreConfig::default()),
+        },
+        vec![toolchains_store.clone()],
+        Arc::new(MemoryStore::new(&MemoryStoreConfig::default())),
+    )?;
+    Ok((Arc::new(routing_store), toolchains_store))
+}
+
+#[cfg(test)]
+mod execution_server_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*; // Must be declared in every module.
+
+    #[tokio::test]
+    async fn execute_reads_action_from_instance_store() -> Result<(), Error> {
+        let (routing_store, toolchains_store) = make_routing_cas()?;
+        let store_manager = StoreManager::new();
+        store_manager.add_store("main_cas", routing_store.clone());
+        let scheduler: Arc<dyn ActionScheduler> = Arc::new(SimpleScheduler::new(
+            &nativelink_config::schedulers::SimpleScheduler::default(),
+        ));
+        let execution_server = ExecutionServer::new(
+            &hashmap! {
+                TOOLCHAINS_INSTANCE_NAME.to_string() => ExecutionConfig {
+                    cas_store: "main_cas".to_string(),
+                    scheduler: SCHEDULER_NAME.to_string(),
+                },
+            },
+            &HashMap::from([(SCHEDULER_NAME.to_string(), scheduler)]),
+            &store_manager,
+            &HashSet::new(),
+        )?;
+
+        // Only the toolchains store has the `Action` and `Command`.
+        let toolchains_pin = Pin::new(toolchains_store.as_ref() as &dyn Store);
+        let command_digest = serialize_and_upload_message(
+            &Command::default(),
+            toolchains_pin,
+            &mut DigestHasherFunc::Sha256.hasher(),
+        )
+        .await?;
+        let action_digest = serialize_and_upload_message(
+            &Action {
+                command_digest: Some(command_digest.into()),
+                input_root_digest: Some(command_digest.into()),
+                ..Default::default()
+            },
+            toolchains_pin,
+            &mut DigestHasherFunc::Sha256.hasher(),
+        )
+        .await?;
+        assert_eq!(
+            Pin::new(routing_store.as_ref()).has(action_digest).await?,
+            None,
+            "Expected the action to only be visible to the toolchains instance"
+        );
+
+        // Fails with `NotFound` if the `Action` is read from the default store.
+        execution_server
+            .execute(Request::new(ExecuteRequest {
+                instance_name: TOOLCHAINS_INSTANCE_NAME.to_string(),
+                skip_cache_lookup: true,
+                action_digest: Some(action_digest.into()),
+                digest_function: digest_function::Value::Sha256.into(),
+                ..Default::default()
+            }))
+            .await
+            .map_err(Error::from)?;
+        Ok(())
+    }
+}
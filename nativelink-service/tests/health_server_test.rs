@@ -0,0 +1,271 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use nativelink_config::cas_server::ServicesConfig;
+use nativelink_service::health_server::HealthServer;
+use nativelink_util::health_utils::{
+    CachedHealthRegistry, HealthRegistryBuilder, HealthStatus, HealthStatusIndicator,
+};
+use tokio_stream::StreamExt;
+use tonic::{Code, Request};
+use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_server::Health;
+use tonic_health::pb::HealthCheckRequest;
+
+const CAS_SERVICE_NAME: &str = "build.bazel.remote.execution.v2.ContentAddressableStorage";
+const EXECUTION_SERVICE_NAME: &str = "build.bazel.remote.execution.v2.Execution";
+
+/// Indicator whose status can be flipped by the test.
+#[derive(Default)]
+struct MockIndicator {
+    failed: AtomicBool,
+    checks: AtomicUsize,
+}
+
+#[tonic::async_trait]
+impl HealthStatusIndicator for MockIndicator {
+    fn get_name(&self) -> &'static str {
+        "MockIndicator"
+    }
+
+    async fn check_health(&self, _namespace: Cow<'static, str>) -> HealthStatus {
+        self.checks.fetch_add(1, Ordering::AcqRel);
+        if self.failed.load(Ordering::Acquire) {
+            HealthStatus::new_failed(self, "failed".into())
+        } else {
+            HealthStatus::new_ok(self, "ok".into())
+        }
+    }
+}
+
+struct TestContext {
+    health_server: HealthServer,
+    cas_store: Arc<MockIndicator>,
+    scheduler: Arc<MockIndicator>,
+}
+
+fn setup_health_server(health_check_interval: Duration) -> TestContext {
+    let services: ServicesConfig = serde_json::from_str(
+        r#"{
+            "cas": { "main": { "cas_store": "CAS_STORE" } },
+            "execution": { "main": { "cas_store": "CAS_STORE", "scheduler": "SCHEDULER" } }
+        }"#,
+    )
+    .unwrap();
+
+    let cas_store = Arc::new(MockIndicator::default());
+    let scheduler = Arc::new(MockIndicator::default());
+    let mut health_registry_builder = HealthRegistryBuilder::new("nativelink".into());
+    health_registry_builder
+        .sub_builder("stores/CAS_STORE".into())
+        .register_indicator(cas_store.clone());
+    health_registry_builder
+        .sub_builder("schedulers/SCHEDULER".into())
+        .register_indicator(scheduler.clone());
+
+    TestContext {
+        health_server: HealthServer::new(
+            &services,
+            &CachedHealthRegistry::new(health_registry_builder.build(), health_check_interval),
+        ),
+        cas_store,
+        scheduler,
+    }
+}
+
+async fn check(health_server: &HealthServer, service: &str) -> Result<i32, tonic::Status> {
+    Ok(health_server
+        .check(Request::new(HealthCheckRequest {
+            service: service.to_string(),
+        }))
+        .await?
+        .into_inner()
+        .status)
+}
+
+/// Checks `service` until the cached status catches up with `expected`.
+async fn wait_for_status(
+    health_server: &HealthServer,
+    service: &str,
+    expected: ServingStatus,
+) -> Result<i32, tonic::Status> {
+    let mut status = check(health_server, service).await?;
+    for _ in 0..100 {
+        if status == expected as i32 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        status = check(health_server, service).await?;
+    }
+    Ok(status)
+}
+
+#[cfg(test)]
+mod health_server_tests {
+    use pretty_assertions::assert_eq; // Must be declared in every module.
+
+    use super::*;
+
+    #[tokio::test]
+    async fn check_reports_status_of_dependencies() -> Result<(), Box<dyn std::error::Error>> {
+        let test_context = setup_health_server(Duration::from_millis(1));
+        let health_server = &test_context.health_server;
+
+        assert_eq!(
+            check(health_server, CAS_SERVICE_NAME).await?,
+            ServingStatus::Serving as i32
+        );
+        assert_eq!(
+            check(health_server, EXECUTION_SERVICE_NAME).await?,
+            ServingStatus::Serving as i32
+        );
+        assert_eq!(
+            check(health_server, "").await?,
+            ServingStatus::Serving as i32
+        );
+
+        // Only the services that use the scheduler are affected.
+        test_context.scheduler.failed.store(true, Ordering::Release);
+        assert_eq!(
+            wait_for_status(
+                health_server,
+                EXECUTION_SERVICE_NAME,
+                ServingStatus::NotServing
+            )
+            .await?,
+            ServingStatus::NotServing as i32
+        );
+        assert_eq!(
+            check(health_server, CAS_SERVICE_NAME).await?,
+            ServingStatus::Serving as i32
+        );
+        assert_eq!(
+            check(health_server, "").await?,
+            ServingStatus::NotServing as i32
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_serves_cached_status() -> Result<(), Box<dyn std::error::Error>> {
+        let test_context = setup_health_server(Duration::from_secs(3600));
+        let health_server = &test_context.health_server;
+
+        for _ in 0..10 {
+            assert_eq!(
+                check(health_server, EXECUTION_SERVICE_NAME).await?,
+                ServingStatus::Serving as i32
+            );
+        }
+        // The indicators only ran for the first, shared check.
+        assert_eq!(test_context.cas_store.checks.load(Ordering::Acquire), 1);
+        assert_eq!(test_context.scheduler.checks.load(Ordering::Acquire), 1);
+
+        // Status changes are only picked up by the next check.
+        test_context.scheduler.failed.store(true, Ordering::Release);
+        assert_eq!(
+            check(health_server, EXECUTION_SERVICE_NAME).await?,
+            ServingStatus::Serving as i32
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_unknown_service_is_not_found() -> Result<(), Box<dyn std::error::Error>> {
+        let test_context = setup_health_server(Duration::from_millis(1));
+
+        let result = check(&test_context.health_server, "google.bytestream.ByteStream").await;
+        assert_eq!(result.map_err(|status| status.code()), Err(Code::NotFound));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn watch_sends_status_changes() -> Result<(), Box<dyn std::error::Error>> {
+        let test_context = setup_health_server(Duration::from_millis(1));
+
+        let mut watch_stream = test_context
+            .health_server
+            .watch(Request::new(HealthCheckRequest {
+                service: CAS_SERVICE_NAME.to_string(),
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(
+            watch_stream.next().await.unwrap()?.status,
+            ServingStatus::Serving as i32
+        );
+
+        test_context.cas_store.failed.store(true, Ordering::Release);
+        assert_eq!(
+            watch_stream.next().await.unwrap()?.status,
+            ServingStatus::NotServing as i32
+        );
+
+        test_context
+            .cas_store
+            .failed
+            .store(false, Ordering::Release);
+        assert_eq!(
+            watch_stream.next().await.unwrap()?.status,
+            ServingStatus::Serving as i32
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn watch_unknown_service_is_service_unknown() -> Result<(), Box<dyn std::error::Error>> {
+        let test_context = setup_health_server(Duration::from_millis(1));
+
+        let mut watch_stream = test_context
+            .health_server
+            .watch(Request::new(HealthCheckRequest {
+                service: "google.bytestream.ByteStream".to_string(),
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(
+            watch_stream.next().await.unwrap()?.status,
+            ServingStatus::ServiceUnknown as i32
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn service_names_lists_configured_services() -> Result<(), Box<dyn std::error::Error>> {
+        let test_context = setup_health_server(Duration::from_millis(1));
+
+        let mut service_names: Vec<&str> = test_context.health_server.service_names().collect();
+        service_names.sort_unstable();
+        assert_eq!(
+            service_names,
+            vec![
+                CAS_SERVICE_NAME,
+                EXECUTION_SERVICE_NAME,
+                "grpc.health.v1.Health"
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,185 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use maplit::hashmap;
+use nativelink_config::cas_server::OperationsConfig;
+use nativelink_error::Error;
+use nativelink_proto::google::longrunning::operations_server::Operations;
+use nativelink_proto::google::longrunning::{
+    CancelOperationRequest, GetOperationRequest, ListOperationsRequest,
+};
+use nativelink_scheduler::action_scheduler::ActionScheduler;
+use nativelink_scheduler::simple_scheduler::SimpleScheduler;
+use nativelink_service::operations_server::OperationsServer;
+use nativelink_util::action_messages::{ActionInfo, ActionInfoHashKey};
+use nativelink_util::common::DigestInfo;
+use nativelink_util::digest_hasher::DigestHasherFunc;
+use nativelink_util::platform_properties::PlatformProperties;
+use tonic::{Code, Request};
+
+const INSTANCE_NAME: &str = "foo_instance_name";
+const SCHEDULER_NAME: &str = "MAIN_SCHEDULER";
+
+fn make_action_info(hash_byte: u8) -> ActionInfo {
+    ActionInfo {
+        command_digest: DigestInfo::new([0u8; 32], 0),
+        input_root_digest: DigestInfo::new([0u8; 32], 0),
+        timeout: Duration::MAX,
+        platform_properties: PlatformProperties::default(),
+        priority: 0,
+        load_timestamp: UNIX_EPOCH,
+        insert_timestamp: SystemTime::now(),
+        unique_qualifier: ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: DigestInfo::new([hash_byte; 32], 10),
+            salt: 0,
+        },
+        skip_cache_lookup: true,
+        digest_function: DigestHasherFunc::Sha256,
+    }
+}
+
+fn make_operations_server(scheduler: Arc<SimpleScheduler>) -> Result<OperationsServer, Error> {
+    let scheduler: Arc<dyn ActionScheduler> = scheduler;
+    OperationsServer::new(
+        &hashmap! {
+            INSTANCE_NAME.to_string() => OperationsConfig {
+                scheduler: SCHEDULER_NAME.to_string(),
+            },
+        },
+        &HashMap::from([(SCHEDULER_NAME.to_string(), scheduler)]),
+        &HashSet::new(),
+    )
+}
+
+#[cfg(test)]
+mod operations_server_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*; // Must be declared in every module.
+
+    #[tokio::test]
+    async fn list_operations_pages_in_name_order() -> Result<(), Error> {
+        let scheduler = Arc::new(SimpleScheduler::new(
+            &nativelink_config::schedulers::SimpleScheduler::default(),
+        ));
+        let operations_server = make_operations_server(scheduler.clone())?;
+        let mut names = Vec::new();
+        let mut receivers = Vec::new();
+        for hash_byte in [3u8, 1, 2] {
+            let action_info = make_action_info(hash_byte);
+            names.push(action_info.unique_qualifier.action_name());
+            receivers.push(scheduler.add_action(action_info).await?);
+        }
+        names.sort();
+
+        let first_page = operations_server
+            .list_operations(Request::new(ListOperationsRequest {
+                page_size: 2,
+                ..Default::default()
+            }))
+            .await
+            .map_err(Error::from)?
+            .into_inner();
+        assert_eq!(
+            first_page
+                .operations
+                .iter()
+                .map(|operation| operation.name.clone())
+                .collect::<Vec<_>>(),
+            names[..2].to_vec()
+        );
+        assert_eq!(first_page.next_page_token, names[1]);
+
+        let second_page = operations_server
+            .list_operations(Request::new(ListOperationsRequest {
+                name: INSTANCE_NAME.to_string(),
+                page_size: 2,
+                page_token: first_page.next_page_token,
+                ..Default::default()
+            }))
+            .await
+            .map_err(Error::from)?
+            .into_inner();
+        assert_eq!(
+            second_page
+                .operations
+                .iter()
+                .map(|operation| operation.name.clone())
+                .collect::<Vec<_>>(),
+            names[2..].to_vec()
+        );
+        assert_eq!(second_page.next_page_token, "");
+
+        let unknown_instance = operations_server
+            .list_operations(Request::new(ListOperationsRequest {
+                name: "bad_instance_name".to_string(),
+                ..Default::default()
+            }))
+            .await;
+        assert_eq!(unknown_instance.unwrap_err().code(), Code::NotFound);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cancel_operation_cancels_queued_action() -> Result<(), Error> {
+        let scheduler = Arc::new(SimpleScheduler::new(
+            &nativelink_config::schedulers::SimpleScheduler::default(),
+        ));
+        let operations_server = make_operations_server(scheduler.clone())?;
+        let action_info = make_action_info(1);
+        let name = action_info.unique_qualifier.action_name();
+        let mut client_rx = scheduler.add_action(action_info).await?;
+
+        let operation = operations_server
+            .get_operation(Request::new(GetOperationRequest { name: name.clone() }))
+            .await
+            .map_err(Error::from)?
+            .into_inner();
+        assert_eq!(operation.name, name);
+        assert!(!operation.done);
+
+        operations_server
+            .cancel_operation(Request::new(CancelOperationRequest { name: name.clone() }))
+            .await
+            .map_err(Error::from)?;
+        client_rx.changed().await.unwrap();
+        assert!(client_rx.borrow().stage.is_finished());
+
+        let operation = operations_server
+            .get_operation(Request::new(GetOperationRequest { name: name.clone() }))
+            .await
+            .map_err(Error::from)?
+            .into_inner();
+        assert!(operation.done);
+
+        // Cancelling a finished operation is allowed.
+        operations_server
+            .cancel_operation(Request::new(CancelOperationRequest { name }))
+            .await
+            .map_err(Error::from)?;
+
+        let unknown = operations_server
+            .cancel_operation(Request::new(CancelOperationRequest {
+                name: make_action_info(9).unique_qualifier.action_name(),
+            }))
+            .await;
+        assert_eq!(unknown.unwrap_err().code(), Code::NotFound);
+        Ok(())
+    }
+}
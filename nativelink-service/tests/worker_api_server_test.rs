@@ -24,7 +24,8 @@ use nativelink_proto::build::bazel::remote::execution::v2::{
 };
 use nativelink_proto::com::github::trace_machina::nativelink::remote_execution::worker_api_server::WorkerApi;
 use nativelink_proto::com::github::trace_machina::nativelink::remote_execution::{
-    execute_result, update_for_worker, ExecuteResult, KeepAliveRequest, SupportedProperties,
+    execute_result, update_for_worker, ActionOutputRequest, ExecuteResult, KeepAliveRequest,
+    SupportedProperties,
 };
 use nativelink_proto::google::rpc::Status as ProtoStatus;
 use nativelink_scheduler::action_scheduler::ActionScheduler;
@@ -435,3 +436,105 @@ pub mod execution_response_tests {
         Ok(())
     }
 }
+
+#[cfg(test)]
+pub mod action_output_tests {
+    use bytes::Bytes;
+    use maplit::hashmap;
+    use nativelink_config::cas_server::ByteStreamConfig;
+    use nativelink_proto::google::bytestream::byte_stream_server::ByteStream;
+    use nativelink_proto::google::bytestream::ReadRequest;
+    use nativelink_service::bytestream_server::ByteStreamServer;
+    use nativelink_store::store_manager::StoreManager;
+    use nativelink_util::action_messages::{ActionOutputStream, ActionResult};
+    use pretty_assertions::assert_eq; // Must be declared in every module.
+
+    use super::*;
+
+    #[tokio::test]
+    pub async fn action_output_is_readable_from_bytestream_test(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        const SCHEDULER_NAME: &str = "main_scheduler";
+        let test_context = setup_api_server(BASE_WORKER_TIMEOUT_S, Box::new(static_now_fn)).await?;
+
+        let unique_qualifier = ActionInfoHashKey {
+            instance_name: "instance_name".to_string(),
+            digest: DigestInfo::new([7u8; 32], 123),
+            salt: 5,
+        };
+        let mut client_action_state_receiver = test_context
+            .scheduler
+            .add_action(ActionInfo {
+                command_digest: DigestInfo::new([0u8; 32], 0),
+                input_root_digest: DigestInfo::new([0u8; 32], 0),
+                timeout: Duration::MAX,
+                platform_properties: PlatformProperties {
+                    properties: HashMap::new(),
+                },
+                priority: 0,
+                load_timestamp: UNIX_EPOCH,
+                insert_timestamp: UNIX_EPOCH,
+                unique_qualifier: unique_qualifier.clone(),
+                skip_cache_lookup: true,
+                digest_function: DigestHasherFunc::Sha256,
+            })
+            .await?;
+        {
+            // Ensure our client thinks we are executing.
+            client_action_state_receiver.changed().await?;
+            let action_state = client_action_state_receiver.borrow();
+            assert_eq!(action_state.stage, ActionStage::Executing);
+        }
+
+        let scheduler: Arc<dyn ActionScheduler> = test_context.scheduler.clone();
+        let bytestream_server = ByteStreamServer::new(
+            &ByteStreamConfig {
+                cas_stores: HashMap::new(),
+                persist_stream_on_disconnect_timeout: 0,
+                max_bytes_per_stream: 0,
+                action_output_schedulers: hashmap! {
+                    unique_qualifier.instance_name.clone() => SCHEDULER_NAME.to_string(),
+                },
+            },
+            &StoreManager::new(),
+            &hashmap! { SCHEDULER_NAME.to_string() => scheduler },
+        )?;
+
+        test_context
+            .worker_api_server
+            .action_output(Request::new(ActionOutputRequest {
+                worker_id: test_context.worker_id.to_string(),
+                instance_name: unique_qualifier.instance_name.clone(),
+                action_digest: Some(unique_qualifier.digest.into()),
+                salt: unique_qualifier.salt,
+                stdout: Bytes::from_static(b"hello"),
+                stderr: Bytes::from_static(b"ignored"),
+            }))
+            .await?;
+        let mut read_stream = bytestream_server
+            .read(Request::new(ReadRequest {
+                resource_name: unique_qualifier.output_stream_name(ActionOutputStream::Stdout),
+                read_offset: 0,
+                read_limit: 0,
+            }))
+            .await?
+            .into_inner();
+        assert_eq!(
+            read_stream.next().await.unwrap()?.data,
+            Bytes::from_static(b"hello")
+        );
+
+        // The stream ends once the action completes.
+        test_context
+            .scheduler
+            .update_action(
+                &test_context.worker_id,
+                &unique_qualifier,
+                ActionStage::Completed(ActionResult::default()),
+            )
+            .await?;
+        assert!(read_stream.next().await.is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,470 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use nativelink_config::stores::CircuitBreakerStore as CircuitBreakerStoreConfig;
+use nativelink_error::{error_if, make_err, Code, Error};
+use nativelink_util::buf_channel::{DropCloserReadHalf, DropCloserWriteHalf};
+use nativelink_util::common::DigestInfo;
+use nativelink_util::health_utils::{HealthRegistryBuilder, HealthStatus, HealthStatusIndicator};
+use nativelink_util::metrics_utils::{
+    Collector, CollectorState, Counter, CounterWithTime, MetricsComponent, Registry,
+};
+use nativelink_util::store_trait::{Store, UploadSizeInfo};
+use parking_lot::Mutex;
+use tracing::warn;
+
+const DEFAULT_FAILURE_RATE_THRESHOLD: u32 = 50;
+const DEFAULT_WINDOW_SIZE: usize = 100;
+const DEFAULT_MINIMUM_CALLS: usize = 20;
+const DEFAULT_OPEN_DURATION_MS: u64 = 30_000;
+const DEFAULT_HALF_OPEN_CALLS: usize = 5;
+
+/// First message of the error of a request the circuit breaker rejected.
+const REJECTED_MESSAGE: &str = "Rejected by circuit breaker";
+
+/// Whether `err` is a request that a `CircuitBreakerStore` rejected without
+/// sending it to its backend.
+pub fn is_rejected_by_circuit_breaker(err: &Error) -> bool {
+    err.code == Code::Unavailable
+        && err
+            .messages
+            .first()
+            .is_some_and(|message| message.starts_with(REJECTED_MESSAGE))
+}
+
+/// Whether an error with `code` is caused by the backend, rather than by
+/// the request (ie: invalid or corrupt data, or a cancelled upload).
+const fn is_backend_failure(code: Code) -> bool {
+    matches!(
+        code,
+        Code::Unavailable
+            | Code::DeadlineExceeded
+            | Code::Internal
+            | Code::Unknown
+            | Code::ResourceExhausted
+    )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CircuitState {
+    /// Requests are sent to the backend.
+    Closed,
+    /// Requests fail right away until `until` is reached.
+    Open { until: Instant },
+    /// A limited number of trial requests are sent to the backend.
+    HalfOpen { started: usize, succeeded: usize },
+}
+
+impl CircuitState {
+    fn as_metric(&self) -> u8 {
+        match self {
+            CircuitState::Closed => 0,
+            CircuitState::Open { .. } => 1,
+            CircuitState::HalfOpen { .. } => 2,
+        }
+    }
+}
+
+struct CircuitBreakerState {
+    state: CircuitState,
+    /// Outcome of the most recent requests, `true` meaning the request failed.
+    outcomes: VecDeque<bool>,
+    failure_count: usize,
+    /// Incremented each time the circuit becomes half-open, so trial
+    /// requests of an earlier half-open period are not counted.
+    half_open_period: u64,
+}
+
+pub struct CircuitBreakerStore {
+    backend: Arc<dyn Store>,
+    failure_rate_threshold: u32,
+    slow_call_threshold: Option<Duration>,
+    window_size: usize,
+    minimum_calls: usize,
+    open_duration: Duration,
+    half_open_calls: usize,
+    state: Mutex<CircuitBreakerState>,
+
+    // Metrics.
+    times_opened: CounterWithTime,
+    rejected_calls: Counter,
+    failed_calls: Counter,
+    slow_calls: Counter,
+}
+
+/// Tracks a request that was let through by the circuit breaker. If the
+/// request is dropped before it finishes it is not counted.
+struct CallPermit<'a> {
+    store: &'a CircuitBreakerStore,
+    start_time: Instant,
+    /// The half-open period this request is a trial request of, if any.
+    trial_period: Option<u64>,
+    finished: bool,
+}
+
+impl<'a> CallPermit<'a> {
+    fn finish<T>(mut self, result: Result<T, Error>) -> Result<T, Error> {
+        self.finished = true;
+        let slow = self
+            .store
+            .slow_call_threshold
+            .is_some_and(|threshold| self.start_time.elapsed() > threshold);
+        let failed = match &result {
+            Ok(_) => false,
+            Err(err) => is_backend_failure(err.code),
+        };
+        if slow {
+            self.store.slow_calls.inc();
+        }
+        if failed {
+            self.store.failed_calls.inc();
+        }
+        self.store
+            .record_outcome(!failed && !slow, self.trial_period);
+        result
+    }
+}
+
+impl<'a> Drop for CallPermit<'a> {
+    fn drop(&mut self) {
+        if let (false, Some(trial_period)) = (self.finished, self.trial_period) {
+            self.store.release_trial_call(trial_period);
+        }
+    }
+}
+
+impl CircuitBreakerStore {
+    pub fn new(config: &CircuitBreakerStoreConfig, backend: Arc<dyn Store>) -> Result<Self, Error> {
+        let failure_rate_threshold = if config.failure_rate_threshold == 0 {
+            DEFAULT_FAILURE_RATE_THRESHOLD
+        } else {
+            config.failure_rate_threshold
+        };
+        error_if!(
+            failure_rate_threshold > 100,
+            "failure_rate_threshold must be between 0 and 100 in CircuitBreakerStore, got {failure_rate_threshold}"
+        );
+        let window_size = if config.window_size == 0 {
+            DEFAULT_WINDOW_SIZE
+        } else {
+            config.window_size
+        };
+        let minimum_calls = if config.minimum_calls == 0 {
+            DEFAULT_MINIMUM_CALLS
+        } else {
+            config.minimum_calls
+        };
+        error_if!(
+            minimum_calls > window_size,
+            "minimum_calls must not be larger than window_size in CircuitBreakerStore"
+        );
+        Ok(Self {
+            backend,
+            failure_rate_threshold,
+            slow_call_threshold: if config.slow_call_threshold_ms == 0 {
+                None
+            } else {
+                Some(Duration::from_millis(config.slow_call_threshold_ms))
+            },
+            window_size,
+            minimum_calls,
+            open_duration: Duration::from_millis(if config.open_duration_ms == 0 {
+                DEFAULT_OPEN_DURATION_MS
+            } else {
+                config.open_duration_ms
+            }),
+            half_open_calls: if config.half_open_calls == 0 {
+                DEFAULT_HALF_OPEN_CALLS
+            } else {
+                config.half_open_calls
+            },
+            state: Mutex::new(CircuitBreakerState {
+                state: CircuitState::Closed,
+                outcomes: VecDeque::with_capacity(window_size),
+                failure_count: 0,
+                half_open_period: 0,
+            }),
+            times_opened: CounterWithTime::default(),
+            rejected_calls: Counter::default(),
+            failed_calls: Counter::default(),
+            slow_calls: Counter::default(),
+        })
+    }
+
+    /// Returns true if the circuit is open and requests are being rejected.
+    pub fn is_open(&self) -> bool {
+        matches!(self.state.lock().state, CircuitState::Open { until } if Instant::now() < until)
+    }
+
+    fn try_acquire(&self) -> Result<CallPermit<'_>, Error> {
+        let mut state = self.state.lock();
+        let trial_period = match state.state {
+            CircuitState::Closed => None,
+            CircuitState::Open { until } => {
+                if Instant::now() < until {
+                    self.rejected_calls.inc();
+                    return Err(make_err!(
+                        Code::Unavailable,
+                        "{REJECTED_MESSAGE}: circuit is open, backend store is not being called"
+                    ));
+                }
+                state.state = CircuitState::HalfOpen {
+                    started: 1,
+                    succeeded: 0,
+                };
+                state.half_open_period += 1;
+                Some(state.half_open_period)
+            }
+            CircuitState::HalfOpen {
+                ref mut started, ..
+            } => {
+                if *started >= self.half_open_calls {
+                    self.rejected_calls.inc();
+                    return Err(make_err!(
+                        Code::Unavailable,
+                        "{REJECTED_MESSAGE}: circuit is half-open and all trial requests are in flight"
+                    ));
+                }
+                *started += 1;
+                Some(state.half_open_period)
+            }
+        };
+        Ok(CallPermit {
+            store: self,
+            start_time: Instant::now(),
+            trial_period,
+            finished: false,
+        })
+    }
+
+    fn release_trial_call(&self, trial_period: u64) {
+        let mut state = self.state.lock();
+        if state.half_open_period != trial_period {
+            return;
+        }
+        if let CircuitState::HalfOpen {
+            ref mut started, ..
+        } = state.state
+        {
+            *started = started.saturating_sub(1);
+        }
+    }
+
+    fn open(&self, state: &mut CircuitBreakerState) {
+        state.state = CircuitState::Open {
+            until: Instant::now() + self.open_duration,
+        };
+        state.outcomes.clear();
+        state.failure_count = 0;
+        self.times_opened.inc();
+    }
+
+    fn record_outcome(&self, success: bool, trial_period: Option<u64>) {
+        let mut state = self.state.lock();
+        let is_current_trial = trial_period == Some(state.half_open_period);
+        match state.state {
+            CircuitState::Closed => {
+                if state.outcomes.len() >= self.window_size
+                    && state.outcomes.pop_front() == Some(true)
+                {
+                    state.failure_count -= 1;
+                }
+                state.outcomes.push_back(!success);
+                if !success {
+                    state.failure_count += 1;
+                }
+                let calls = state.outcomes.len();
+                if calls >= self.minimum_calls
+                    && state.failure_count * 100 >= self.failure_rate_threshold as usize * calls
+                {
+                    warn!(
+                        "Opening circuit breaker, {} of the last {calls} requests failed",
+                        state.failure_count
+                    );
+                    self.open(&mut state);
+                }
+            }
+            // Only the trial requests of this half-open period decide whether
+            // the circuit closes again.
+            CircuitState::HalfOpen { .. } if !is_current_trial => {}
+            CircuitState::HalfOpen {
+                ref mut succeeded, ..
+            } => {
+                if !success {
+                    warn!("Trial request failed, re-opening circuit breaker");
+                    self.open(&mut state);
+                    return;
+                }
+                *succeeded += 1;
+                if *succeeded >= self.half_open_calls {
+                    state.state = CircuitState::Closed;
+                }
+            }
+            // Requests that started before the circuit opened are ignored.
+            CircuitState::Open { .. } => {}
+        }
+    }
+}
+
+#[async_trait]
+impl Store for CircuitBreakerStore {
+    async fn has_with_results(
+        self: Pin<&Self>,
+        digests: &[DigestInfo],
+        results: &mut [Option<usize>],
+    ) -> Result<(), Error> {
+        let permit = self.try_acquire()?;
+        let result = Pin::new(self.backend.as_ref())
+            .has_with_results(digests, results)
+            .await;
+        permit.finish(result)
+    }
+
+    async fn update(
+        self: Pin<&Self>,
+        digest: DigestInfo,
+        reader: DropCloserReadHalf,
+        size_info: UploadSizeInfo,
+    ) -> Result<(), Error> {
+        let permit = self.try_acquire()?;
+        let result = Pin::new(self.backend.as_ref())
+            .update(digest, reader, size_info)
+            .await;
+        permit.finish(result)
+    }
+
+    async fn get_part_ref(
+        self: Pin<&Self>,
+        digest: DigestInfo,
+        writer: &mut DropCloserWriteHalf,
+        offset: usize,
+        length: Option<usize>,
+    ) -> Result<(), Error> {
+        let permit = self.try_acquire()?;
+        let result = Pin::new(self.backend.as_ref())
+            .get_part_ref(digest, writer, offset, length)
+            .await;
+        permit.finish(result)
+    }
+
+    async fn list(
+        self: Pin<&Self>,
+        handler: &mut (dyn FnMut(DigestInfo, usize) + Send),
+    ) -> Result<(), Error> {
+        Pin::new(self.backend.as_ref()).list(handler).await
+    }
+
+    async fn touch(self: Pin<&Self>, digests: &[DigestInfo]) -> Result<(), Error> {
+        Pin::new(self.backend.as_ref()).touch(digests).await
+    }
+
+    async fn pin(self: Pin<&Self>, digests: &[DigestInfo], ttl: Duration) -> Result<usize, Error> {
+        Pin::new(self.backend.as_ref()).pin(digests, ttl).await
+    }
+
+    fn inner_store(&self, _digest: Option<DigestInfo>) -> &'_ dyn Store {
+        self
+    }
+
+    fn inner_store_arc(self: Arc<Self>, _digest: Option<DigestInfo>) -> Arc<dyn Store> {
+        self
+    }
+
+    fn as_any<'a>(&'a self) -> &'a (dyn std::any::Any + Sync + Send + 'static) {
+        self
+    }
+
+    fn as_any_arc(self: Arc<Self>) -> Arc<dyn std::any::Any + Sync + Send + 'static> {
+        self
+    }
+
+    fn register_metrics(self: Arc<Self>, registry: &mut Registry) {
+        let backend_registry = registry.sub_registry_with_prefix("backend");
+        self.backend.clone().register_metrics(backend_registry);
+        registry.register_collector(Box::new(Collector::new(&self)));
+    }
+
+    fn register_health(self: Arc<Self>, registry: &mut HealthRegistryBuilder) {
+        let mut backend_registry = registry.sub_builder("backend".into());
+        self.backend.clone().register_health(&mut backend_registry);
+        registry.register_indicator(self);
+    }
+}
+
+impl MetricsComponent for CircuitBreakerStore {
+    fn gather_metrics(&self, c: &mut CollectorState) {
+        c.publish(
+            "state",
+            &self.state.lock().state.as_metric(),
+            "State of the circuit breaker. 0 = closed, 1 = open, 2 = half-open",
+        );
+        c.publish(
+            "opened_total",
+            &self.times_opened,
+            "Number of times the circuit breaker opened",
+        );
+        c.publish(
+            "rejected_calls_total",
+            &self.rejected_calls,
+            "Number of requests rejected because the circuit breaker was open",
+        );
+        c.publish(
+            "failed_calls_total",
+            &self.failed_calls,
+            "Number of requests to the backend that failed",
+        );
+        c.publish(
+            "slow_calls_total",
+            &self.slow_calls,
+            "Number of requests to the backend that took longer than slow_call_threshold_ms",
+        );
+        c.publish(
+            "failure_rate_threshold",
+            &self.failure_rate_threshold,
+            "Percentage of failed requests at which the circuit breaker opens",
+        );
+    }
+}
+
+#[async_trait]
+impl HealthStatusIndicator for CircuitBreakerStore {
+    fn get_name(&self) -> &'static str {
+        "CircuitBreakerStore"
+    }
+
+    async fn check_health(&self, _namespace: Cow<'static, str>) -> HealthStatus {
+        match self.state.lock().state {
+            CircuitState::Closed => HealthStatus::new_ok(self, "Circuit breaker is closed".into()),
+            CircuitState::Open { until } => HealthStatus::new_failed(
+                self,
+                format!(
+                    "Circuit breaker is open for another {:?}",
+                    until.saturating_duration_since(Instant::now())
+                )
+                .into(),
+            ),
+            CircuitState::HalfOpen { .. } => HealthStatus::new_warning(
+                self,
+                "Circuit breaker is half-open and sending trial requests".into(),
+            ),
+        }
+    }
+}
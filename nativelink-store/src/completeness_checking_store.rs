@@ -14,6 +14,7 @@
 
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 use std::{iter, mem};
 
 use async_trait::async_trait;
@@ -25,7 +26,10 @@ use nativelink_proto::build::bazel::remote::execution::v2::{
 };
 use nativelink_util::buf_channel::{DropCloserReadHalf, DropCloserWriteHalf};
 use nativelink_util::common::DigestInfo;
-use nativelink_util::health_utils::{default_health_status_indicator, HealthStatusIndicator};
+use nativelink_util::health_utils::{
+    default_health_status_indicator, HealthRegistryBuilder, HealthStatusIndicator,
+};
+use nativelink_util::request_context::{RequestContext, StoreAccess};
 use nativelink_util::store_trait::{Store, UploadSizeInfo};
 use parking_lot::Mutex;
 use tokio::sync::Notify;
@@ -36,13 +40,25 @@ use crate::ac_utils::{get_and_decode_digest, get_size_and_decode_digest};
 pub struct CompletenessCheckingStore {
     cas_store: Arc<dyn Store>,
     ac_store: Arc<dyn Store>,
+    pin_outputs_duration: Option<Duration>,
+    refresh_outputs: bool,
 }
 
 impl CompletenessCheckingStore {
-    pub fn new(ac_store: Arc<dyn Store>, cas_store: Arc<dyn Store>) -> Self {
+    pub fn new(
+        config: &nativelink_config::stores::CompletenessCheckingStore,
+        ac_store: Arc<dyn Store>,
+        cas_store: Arc<dyn Store>,
+    ) -> Self {
         CompletenessCheckingStore {
             cas_store,
             ac_store,
+            pin_outputs_duration: if config.pin_outputs_seconds == 0 {
+                None
+            } else {
+                Some(Duration::from_secs(config.pin_outputs_seconds))
+            },
+            refresh_outputs: config.refresh_outputs,
         }
     }
 }
@@ -123,6 +139,8 @@ async fn check_output_directories(
 async fn inner_has_with_results(
     ac_store: Pin<&dyn Store>,
     cas_store: Pin<&dyn Store>,
+    pin_outputs_duration: Option<Duration>,
+    refresh_outputs: bool,
     action_result_digests: &[DigestInfo],
     results: &mut [Option<usize>],
 ) -> Result<(), Error> {
@@ -189,15 +207,18 @@ async fn inner_has_with_results(
                     return Ok(());
                 }
 
-                check_output_directories(cas_store, output_directories, &move |digest_infos| {
-                    let mut state = state_mux.lock();
-                    let rep_len = digest_infos.len();
-                    state.digests_to_check.extend(digest_infos);
-                    state
-                        .digests_to_check_idxs
-                        .extend(iter::repeat(i).take(rep_len));
-                    state.notify.notify_one();
-                })
+                RequestContext::scope_access(
+                    StoreAccess::Cas,
+                    check_output_directories(cas_store, output_directories, &move |digest_infos| {
+                        let mut state = state_mux.lock();
+                        let rep_len = digest_infos.len();
+                        state.digests_to_check.extend(digest_infos);
+                        state
+                            .digests_to_check_idxs
+                            .extend(iter::repeat(i).take(rep_len));
+                        state.notify.notify_one();
+                    }),
+                )
                 .await?;
 
                 Result::<(), Error>::Ok(())
@@ -222,7 +243,9 @@ async fn inner_has_with_results(
     // we want to give the ability for stores to batch requests together
     // whenever possible.
     // The most common case is only one notify will ever happen.
-    let check_existence_fut = async {
+    // The outputs are CAS digests, so stores routing on the kind of access
+    // must see a CAS access even when serving an AC request.
+    let check_existence_fut = RequestContext::scope_access(StoreAccess::Cas, async {
         let mut has_results = vec![];
         let notify = state_mux.lock().notify.clone();
         loop {
@@ -260,6 +283,24 @@ async fn inner_has_with_results(
                 .err_tip(|| {
                     "Error calling has_with_results() inside CompletenessCheckingStore::has"
                 })?;
+            if pin_outputs_duration.is_some() || refresh_outputs {
+                let found_digests: Vec<DigestInfo> = digests
+                    .iter()
+                    .zip(has_results.iter())
+                    .filter_map(|(digest, result)| result.map(|_| *digest))
+                    .collect();
+                if let Some(pin_outputs_duration) = pin_outputs_duration {
+                    cas_store
+                        .pin(&found_digests, pin_outputs_duration)
+                        .await
+                        .err_tip(|| "Error calling pin() inside CompletenessCheckingStore::has")?;
+                }
+                if refresh_outputs {
+                    cas_store.touch(&found_digests).await.err_tip(|| {
+                        "Error calling touch() inside CompletenessCheckingStore::has"
+                    })?;
+                }
+            }
             let missed_indexes = has_results
                 .iter()
                 .zip(indexes)
@@ -272,7 +313,7 @@ async fn inner_has_with_results(
             }
         }
         Result::<(), Error>::Ok(())
-    }
+    })
     .fuse();
     tokio::pin!(check_existence_fut);
 
@@ -326,6 +367,8 @@ impl Store for CompletenessCheckingStore {
         inner_has_with_results(
             Pin::new(self.ac_store.as_ref()),
             Pin::new(self.cas_store.as_ref()),
+            self.pin_outputs_duration,
+            self.refresh_outputs,
             action_result_digests,
             results,
         )
@@ -355,6 +398,8 @@ impl Store for CompletenessCheckingStore {
         inner_has_with_results(
             ac_store,
             Pin::new(self.cas_store.as_ref()),
+            self.pin_outputs_duration,
+            self.refresh_outputs,
             &[digest],
             results,
         )
@@ -384,6 +429,15 @@ impl Store for CompletenessCheckingStore {
     fn as_any_arc(self: Arc<Self>) -> Arc<dyn std::any::Any + Sync + Send + 'static> {
         self
     }
+
+    fn register_health(self: Arc<Self>, registry: &mut HealthRegistryBuilder) {
+        self.ac_store
+            .clone()
+            .register_health(&mut registry.sub_builder("ac_store".into()));
+        self.cas_store
+            .clone()
+            .register_health(&mut registry.sub_builder("cas_store".into()));
+    }
 }
 
 default_health_status_indicator!(CompletenessCheckingStore);
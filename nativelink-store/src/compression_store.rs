@@ -15,6 +15,7 @@
 use std::cmp;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use bincode::config::{FixintEncoding, WithOtherIntEncoding};
@@ -28,7 +29,9 @@ use nativelink_util::buf_channel::{
     make_buf_channel_pair, DropCloserReadHalf, DropCloserWriteHalf,
 };
 use nativelink_util::common::{DigestInfo, JoinHandleDropGuard};
-use nativelink_util::health_utils::{default_health_status_indicator, HealthStatusIndicator};
+use nativelink_util::health_utils::{
+    default_health_status_indicator, HealthRegistryBuilder, HealthStatusIndicator,
+};
 use nativelink_util::metrics_utils::Registry;
 use nativelink_util::store_trait::{Store, UploadSizeInfo};
 use serde::{Deserialize, Serialize};
@@ -615,6 +618,14 @@ impl Store for CompressionStore {
         Ok(())
     }
 
+    async fn touch(self: Pin<&Self>, digests: &[DigestInfo]) -> Result<(), Error> {
+        Pin::new(self.inner_store.as_ref()).touch(digests).await
+    }
+
+    async fn pin(self: Pin<&Self>, digests: &[DigestInfo], ttl: Duration) -> Result<usize, Error> {
+        Pin::new(self.inner_store.as_ref()).pin(digests, ttl).await
+    }
+
     fn inner_store(&self, _digest: Option<DigestInfo>) -> &'_ dyn Store {
         self
     }
@@ -637,6 +648,12 @@ impl Store for CompressionStore {
             .clone()
             .register_metrics(inner_store_registry);
     }
+
+    fn register_health(self: Arc<Self>, registry: &mut HealthRegistryBuilder) {
+        self.inner_store
+            .clone()
+            .register_health(&mut registry.sub_builder("inner_store".into()));
+    }
 }
 
 default_health_status_indicator!(CompressionStore);
@@ -13,7 +13,10 @@
 // limitations under the License.
 
 use std::cmp;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use async_trait::async_trait;
@@ -24,9 +27,16 @@ use nativelink_error::{make_err, Code, Error, ResultExt};
 use nativelink_util::buf_channel::{DropCloserReadHalf, DropCloserWriteHalf, StreamReader};
 use nativelink_util::common::DigestInfo;
 use nativelink_util::fastcdc::FastCDC;
-use nativelink_util::health_utils::{default_health_status_indicator, HealthStatusIndicator};
-use nativelink_util::store_trait::{Store, UploadSizeInfo};
+use nativelink_util::health_utils::{
+    default_health_status_indicator, HealthRegistryBuilder, HealthStatusIndicator,
+};
+use nativelink_util::metrics_utils::{
+    Collector, CollectorState, Counter, MetricsComponent, Registry,
+};
+use nativelink_util::store_trait::{Store, StoreEvictionCallback, UploadSizeInfo};
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
 use tokio_util::codec::FramedRead;
 use tracing::warn;
 
@@ -37,11 +47,482 @@ const DEFAULT_NORM_SIZE: usize = 256 * 1024;
 const DEFAULT_MAX_SIZE: usize = 512 * 1024;
 const DEFAULT_MAX_CONCURRENT_FETCH_PER_GET: usize = 10;
 
+/// Maximum number of evictions waiting to be handled by the chunk tracker.
+/// Evictions that happen while the queue is full are not tracked.
+const MAX_PENDING_EVICTIONS: usize = 1024;
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Default, Clone)]
 pub struct DedupIndex {
     pub entries: Vec<DigestInfo>,
 }
 
+/// Reference information about a single chunk in the content store.
+struct ChunkRefs {
+    size: u64,
+    /// Indexes, including ones that are still being uploaded, that
+    /// reference this chunk.
+    referenced_by: HashSet<DigestInfo>,
+    /// Whether the chunk was uploaded by us. Chunks that already existed
+    /// might be referenced by indexes we don't know about, so they are
+    /// never removed.
+    owned: bool,
+    /// Whether the chunk is being removed from the content store.
+    removing: bool,
+    /// Incremented every time we upload the chunk, so checks made without
+    /// holding the lock can tell if the chunk was uploaded again since.
+    generation: u64,
+}
+
+/// Chunks of an index that was uploaded.
+struct IndexRefs {
+    chunks: Vec<DigestInfo>,
+    /// Unique for every commit, so checks made without holding the lock can
+    /// tell if the index was uploaded again since.
+    version: u64,
+}
+
+/// Removals that have to be done in the backing stores, which is done
+/// without holding the lock on the state.
+#[derive(Default)]
+struct PendingRemovals {
+    chunks: Vec<DigestInfo>,
+    indexes: Vec<DigestInfo>,
+}
+
+impl PendingRemovals {
+    fn is_empty(&self) -> bool {
+        self.chunks.is_empty() && self.indexes.is_empty()
+    }
+}
+
+#[derive(Default)]
+struct ChunkTrackerState {
+    /// Chunks that are referenced by at least one index, plus owned chunks
+    /// that are no longer referenced but could not be removed.
+    chunks: HashMap<DigestInfo, ChunkRefs>,
+    /// Every index that was uploaded.
+    indexes: HashMap<DigestInfo, IndexRefs>,
+    /// Indexes that lost a chunk and must not be served. They stay here if
+    /// they could not be removed from the index store.
+    broken_indexes: HashSet<DigestInfo>,
+    /// The version of the next committed index.
+    next_version: u64,
+    /// Sum of the sizes of all tracked indexes.
+    logical_bytes: u64,
+    /// Sum of the sizes of all chunks referenced by an index.
+    referenced_bytes: u64,
+    /// Sum of the sizes of unreferenced chunks that could not be removed.
+    orphaned_bytes: u64,
+}
+
+impl ChunkTrackerState {
+    /// Removes the reference `index` holds on `chunk`, and schedules the
+    /// chunk for removal if it is now orphaned.
+    fn release(&mut self, index: DigestInfo, chunk: DigestInfo, pending: &mut PendingRemovals) {
+        let Some(chunk_refs) = self.chunks.get_mut(&chunk) else {
+            return;
+        };
+        if !chunk_refs.referenced_by.remove(&index) || !chunk_refs.referenced_by.is_empty() {
+            return;
+        }
+        self.referenced_bytes -= chunk_refs.size;
+        if chunk_refs.owned {
+            chunk_refs.removing = true;
+            pending.chunks.push(chunk);
+            return;
+        }
+        self.chunks.remove(&chunk);
+    }
+
+    /// Stops tracking `index` and releases all of its chunks.
+    fn release_index(&mut self, index: DigestInfo, pending: &mut PendingRemovals) {
+        let Some(index_refs) = self.indexes.remove(&index) else {
+            return;
+        };
+        self.logical_bytes -= total_size(&index_refs.chunks);
+        for chunk in index_refs.chunks {
+            self.release(index, chunk, pending);
+        }
+    }
+
+    /// Stops tracking `index` and schedules it for removal, because one of
+    /// its chunks is gone.
+    fn break_index(&mut self, index: DigestInfo, pending: &mut PendingRemovals) {
+        self.release_index(index, pending);
+        // Make sure we never serve it.
+        self.broken_indexes.insert(index);
+        pending.indexes.push(index);
+    }
+
+    /// Stops tracking `chunk`, which is no longer in the content store, and
+    /// breaks every committed index that references it.
+    fn drop_chunk(&mut self, chunk: DigestInfo, pending: &mut PendingRemovals) {
+        let Some(chunk_refs) = self.chunks.remove(&chunk) else {
+            return;
+        };
+        if chunk_refs.referenced_by.is_empty() {
+            if !chunk_refs.removing {
+                self.orphaned_bytes -= chunk_refs.size;
+            }
+            return;
+        }
+        self.referenced_bytes -= chunk_refs.size;
+        for index in chunk_refs.referenced_by {
+            // Indexes that are still being uploaded will notice the missing
+            // chunk when they are committed.
+            if self.indexes.contains_key(&index) {
+                self.break_index(index, pending);
+            }
+        }
+    }
+}
+
+/// An eviction from one of the backing stores of a `ChunkTracker`.
+enum Eviction {
+    Index(DigestInfo),
+    Chunk(DigestInfo),
+}
+
+fn total_size(chunks: &[DigestInfo]) -> u64 {
+    chunks.iter().map(|chunk| chunk.size_bytes as u64).sum()
+}
+
+/// Keeps track of which indexes reference which chunks, so chunks can be
+/// removed once nothing references them anymore and indexes can be removed
+/// once one of their chunks is gone.
+///
+/// The lock on the state is never held while talking to the backing stores,
+/// so the results of those calls are checked against the state again.
+struct ChunkTracker {
+    state: Mutex<ChunkTrackerState>,
+    index_store: Arc<dyn Store>,
+    content_store: Arc<dyn Store>,
+
+    // Metrics.
+    removed_chunks: Counter,
+    removed_chunk_bytes: Counter,
+    broken_indexes: Counter,
+    dropped_evictions: Counter,
+    // Copies of the sizes in the state, so metrics don't need the lock.
+    logical_bytes: AtomicU64,
+    referenced_bytes: AtomicU64,
+    orphaned_bytes: AtomicU64,
+    tracked_indexes: AtomicUsize,
+    tracked_chunks: AtomicUsize,
+}
+
+impl ChunkTracker {
+    fn new(index_store: Arc<dyn Store>, content_store: Arc<dyn Store>) -> Result<Arc<Self>, Error> {
+        let chunk_tracker = Arc::new(Self {
+            state: Mutex::new(ChunkTrackerState::default()),
+            index_store,
+            content_store,
+            removed_chunks: Counter::default(),
+            removed_chunk_bytes: Counter::default(),
+            broken_indexes: Counter::default(),
+            dropped_evictions: Counter::default(),
+            logical_bytes: AtomicU64::new(0),
+            referenced_bytes: AtomicU64::new(0),
+            orphaned_bytes: AtomicU64::new(0),
+            tracked_indexes: AtomicUsize::new(0),
+            tracked_chunks: AtomicUsize::new(0),
+        });
+
+        // Evictions are handled one at a time by a single task.
+        let (eviction_tx, mut eviction_rx) = mpsc::channel(MAX_PENDING_EVICTIONS);
+        chunk_tracker
+            .index_store
+            .clone()
+            .register_eviction_callback(
+                chunk_tracker.eviction_callback(eviction_tx.clone(), Eviction::Index),
+            )
+            .err_tip(|| "While enabling track_chunk_references on the index_store of DedupStore")?;
+        chunk_tracker
+            .content_store
+            .clone()
+            .register_eviction_callback(
+                chunk_tracker.eviction_callback(eviction_tx, Eviction::Chunk),
+            )
+            .err_tip(|| {
+                "While enabling track_chunk_references on the content_store of DedupStore"
+            })?;
+        let weak_tracker = Arc::downgrade(&chunk_tracker);
+        tokio::spawn(async move {
+            while let Some(eviction) = eviction_rx.recv().await {
+                let Some(chunk_tracker) = weak_tracker.upgrade() else {
+                    return;
+                };
+                match eviction {
+                    Eviction::Index(index) => chunk_tracker.index_evicted(index).await,
+                    Eviction::Chunk(chunk) => chunk_tracker.chunk_evicted(chunk).await,
+                }
+            }
+        });
+        Ok(chunk_tracker)
+    }
+
+    /// Returns a callback that queues evictions of a backing store.
+    fn eviction_callback(
+        self: &Arc<Self>,
+        eviction_tx: mpsc::Sender<Eviction>,
+        make_eviction: fn(DigestInfo) -> Eviction,
+    ) -> StoreEvictionCallback {
+        let weak_tracker = Arc::downgrade(self);
+        Box::new(move |digest, _data| {
+            if eviction_tx.try_send(make_eviction(digest)).is_ok() {
+                return;
+            }
+            if let Some(chunk_tracker) = weak_tracker.upgrade() {
+                chunk_tracker.dropped_evictions.inc();
+                warn!(
+                    "Eviction queue of DedupStore is full, not tracking eviction of {}",
+                    digest.hash_str()
+                );
+            }
+        })
+    }
+
+    /// Runs `f` with the state locked and updates the metrics afterwards.
+    fn with_state<R>(&self, f: impl FnOnce(&mut ChunkTrackerState) -> R) -> R {
+        let mut state = self.state.lock();
+        let result = f(&mut state);
+        self.logical_bytes
+            .store(state.logical_bytes, Ordering::Relaxed);
+        self.referenced_bytes
+            .store(state.referenced_bytes, Ordering::Relaxed);
+        self.orphaned_bytes
+            .store(state.orphaned_bytes, Ordering::Relaxed);
+        self.tracked_indexes
+            .store(state.indexes.len(), Ordering::Relaxed);
+        self.tracked_chunks
+            .store(state.chunks.len(), Ordering::Relaxed);
+        result
+    }
+
+    fn is_broken(&self, index: &DigestInfo) -> bool {
+        self.state.lock().broken_indexes.contains(index)
+    }
+
+    /// Adds a reference from `index` to `chunk`. This must happen before
+    /// checking if the chunk exists, so it can't be removed before the
+    /// index is written.
+    fn add_reference(&self, index: DigestInfo, chunk: DigestInfo) {
+        self.with_state(|state| match state.chunks.entry(chunk) {
+            Entry::Occupied(mut entry) => {
+                let chunk_refs = entry.get_mut();
+                if chunk_refs.referenced_by.is_empty() {
+                    // The chunk was orphaned. Chunks that are being removed
+                    // are not counted as orphaned, and if the removal wins
+                    // the index will be broken when it's committed.
+                    if !chunk_refs.removing {
+                        state.orphaned_bytes -= chunk_refs.size;
+                    }
+                    state.referenced_bytes += chunk_refs.size;
+                }
+                chunk_refs.referenced_by.insert(index);
+            }
+            Entry::Vacant(entry) => {
+                state.referenced_bytes += chunk.size_bytes as u64;
+                entry.insert(ChunkRefs {
+                    size: chunk.size_bytes as u64,
+                    referenced_by: HashSet::from([index]),
+                    owned: false,
+                    removing: false,
+                    generation: 0,
+                });
+            }
+        });
+    }
+
+    /// Marks `chunk` as uploaded by us, which allows it to be removed once
+    /// it is no longer referenced.
+    fn mark_owned(&self, chunk: DigestInfo) {
+        if let Some(chunk_refs) = self.state.lock().chunks.get_mut(&chunk) {
+            chunk_refs.owned = true;
+            // We only upload chunks that were missing, so a pending removal
+            // finished before this upload.
+            chunk_refs.removing = false;
+            chunk_refs.generation += 1;
+        }
+    }
+
+    /// Records that `index` was written and consists of `chunks`.
+    async fn commit_index(&self, index: DigestInfo, chunks: &[DigestInfo]) {
+        let pending = self.with_state(|state| {
+            let mut pending = PendingRemovals::default();
+            state.broken_indexes.remove(&index);
+            // Release chunks only a previous version of this index referenced.
+            if let Some(old_index_refs) = state.indexes.remove(&index) {
+                state.logical_bytes -= total_size(&old_index_refs.chunks);
+                let new_chunks: HashSet<&DigestInfo> = chunks.iter().collect();
+                for chunk in old_index_refs.chunks {
+                    if !new_chunks.contains(&chunk) {
+                        state.release(index, chunk, &mut pending);
+                    }
+                }
+            }
+            state.logical_bytes += total_size(chunks);
+            let version = state.next_version;
+            state.next_version += 1;
+            state.indexes.insert(
+                index,
+                IndexRefs {
+                    chunks: chunks.to_vec(),
+                    version,
+                },
+            );
+
+            let lost_chunk = chunks.iter().any(|chunk| {
+                !state
+                    .chunks
+                    .get(chunk)
+                    .is_some_and(|chunk_refs| chunk_refs.referenced_by.contains(&index))
+            });
+            if lost_chunk {
+                // A chunk was evicted while the index was being uploaded.
+                state.break_index(index, &mut pending);
+            }
+            pending
+        });
+        self.remove(pending).await;
+    }
+
+    /// Releases the references an upload of `index` took on `chunks` when
+    /// the upload failed.
+    async fn abort_index(&self, index: DigestInfo, chunks: &[DigestInfo]) {
+        let pending = self.with_state(|state| {
+            let committed_chunks: HashSet<DigestInfo> = state
+                .indexes
+                .get(&index)
+                .map(|index_refs| index_refs.chunks.iter().copied().collect())
+                .unwrap_or_default();
+            let mut pending = PendingRemovals::default();
+            for chunk in chunks {
+                if !committed_chunks.contains(chunk) {
+                    state.release(index, *chunk, &mut pending);
+                }
+            }
+            pending
+        });
+        self.remove(pending).await;
+    }
+
+    async fn index_evicted(&self, index: DigestInfo) {
+        let version = self
+            .state
+            .lock()
+            .indexes
+            .get(&index)
+            .map(|index_refs| index_refs.version);
+        // The index might have been uploaded again since it was evicted.
+        if matches!(
+            Pin::new(self.index_store.as_ref()).has(index).await,
+            Ok(Some(_))
+        ) {
+            return;
+        }
+        let pending = self.with_state(|state| {
+            let mut pending = PendingRemovals::default();
+            // Or it might have been committed again while we checked.
+            if state
+                .indexes
+                .get(&index)
+                .map(|index_refs| index_refs.version)
+                != version
+            {
+                return pending;
+            }
+            state.broken_indexes.remove(&index);
+            state.release_index(index, &mut pending);
+            pending
+        });
+        self.remove(pending).await;
+    }
+
+    async fn chunk_evicted(&self, chunk: DigestInfo) {
+        let Some(generation) = self
+            .state
+            .lock()
+            .chunks
+            .get(&chunk)
+            .map(|chunk_refs| chunk_refs.generation)
+        else {
+            return;
+        };
+        // The chunk might have been uploaded again since it was evicted.
+        if matches!(
+            Pin::new(self.content_store.as_ref()).has(chunk).await,
+            Ok(Some(_))
+        ) {
+            return;
+        }
+        let pending = self.with_state(|state| {
+            let mut pending = PendingRemovals::default();
+            // Or it might have been uploaded again while we checked.
+            if state
+                .chunks
+                .get(&chunk)
+                .map(|chunk_refs| chunk_refs.generation)
+                == Some(generation)
+            {
+                state.drop_chunk(chunk, &mut pending);
+            }
+            pending
+        });
+        self.remove(pending).await;
+    }
+
+    /// Removes broken indexes and orphaned chunks from the backing stores.
+    async fn remove(&self, mut pending: PendingRemovals) {
+        while !pending.is_empty() {
+            for index in std::mem::take(&mut pending.indexes) {
+                self.broken_indexes.inc();
+                if Pin::new(self.index_store.as_ref())
+                    .remove(index)
+                    .await
+                    .is_ok()
+                {
+                    let mut state = self.state.lock();
+                    // Unless it was committed again in the meantime, in which
+                    // case the commit cleared it.
+                    if !state.indexes.contains_key(&index) {
+                        state.broken_indexes.remove(&index);
+                    }
+                }
+            }
+            for chunk in std::mem::take(&mut pending.chunks) {
+                let removed = Pin::new(self.content_store.as_ref())
+                    .remove(chunk)
+                    .await
+                    .is_ok();
+                self.with_state(|state| {
+                    let Some(chunk_refs) = state.chunks.get_mut(&chunk) else {
+                        return;
+                    };
+                    if !chunk_refs.removing {
+                        // It was uploaded again in the meantime.
+                        return;
+                    }
+                    let size = chunk_refs.size;
+                    if !removed {
+                        // Keep tracking it, so it can be reused if referenced again.
+                        chunk_refs.removing = false;
+                        if chunk_refs.referenced_by.is_empty() {
+                            state.orphaned_bytes += size;
+                        }
+                        return;
+                    }
+                    self.removed_chunks.inc();
+                    self.removed_chunk_bytes.add(size);
+                    // If an index referenced it while it was being removed,
+                    // the index is broken now.
+                    state.drop_chunk(chunk, &mut pending);
+                });
+            }
+        }
+    }
+}
+
 pub struct DedupStore {
     index_store: Arc<dyn Store>,
     content_store: Arc<dyn Store>,
@@ -49,6 +530,7 @@ pub struct DedupStore {
     max_concurrent_fetch_per_get: usize,
     upload_normal_size: usize,
     bincode_options: WithOtherIntEncoding<DefaultOptions, FixintEncoding>,
+    chunk_tracker: Option<Arc<ChunkTracker>>,
 }
 
 impl DedupStore {
@@ -56,7 +538,7 @@ impl DedupStore {
         config: &nativelink_config::stores::DedupStore,
         index_store: Arc<dyn Store>,
         content_store: Arc<dyn Store>,
-    ) -> Self {
+    ) -> Result<Self, Error> {
         let min_size = if config.min_size == 0 {
             DEFAULT_MIN_SIZE
         } else {
@@ -77,7 +559,15 @@ impl DedupStore {
         } else {
             config.max_concurrent_fetch_per_get as usize
         };
-        Self {
+        let chunk_tracker = if config.track_chunk_references {
+            Some(ChunkTracker::new(
+                index_store.clone(),
+                content_store.clone(),
+            )?)
+        } else {
+            None
+        };
+        Ok(Self {
             index_store,
             content_store,
             fast_cdc_decoder: FastCDC::new(min_size, normal_size, max_size),
@@ -86,14 +576,126 @@ impl DedupStore {
             // over estimate than under estimate.
             upload_normal_size: (normal_size * 13) / 10,
             bincode_options: DefaultOptions::new().with_fixint_encoding(),
-        }
+            chunk_tracker,
+        })
     }
 
     fn pin_index_store(&self) -> Pin<&dyn Store> {
         Pin::new(self.index_store.as_ref())
     }
 
+    fn pin_content_store(&self) -> Pin<&dyn Store> {
+        Pin::new(self.content_store.as_ref())
+    }
+
+    /// Returns the digests of the chunks `digest` was split into, in the
+    /// order they need to be concatenated to reassemble the original blob.
+    /// Chunk digests are always BLAKE3 hashes and can be read back from
+    /// this store like any other blob.
+    pub async fn get_chunk_digests(
+        self: Pin<&Self>,
+        digest: DigestInfo,
+    ) -> Result<Vec<DigestInfo>, Error> {
+        let data = self
+            .pin_index_store()
+            .get_part_unchunked(digest, 0, None, Some(self.upload_normal_size))
+            .await
+            .err_tip(|| "Failed to read index store in dedup store")?;
+
+        let index_entries = self
+            .bincode_options
+            .deserialize::<DedupIndex>(&data)
+            .map_err(|e| {
+                make_err!(
+                    Code::Internal,
+                    "Failed to deserialize index in dedup_store::get_chunk_digests : {:?}",
+                    e
+                )
+            })?;
+        Ok(index_entries.entries)
+    }
+
+    async fn upload_chunks(
+        self: Pin<&Self>,
+        digest: DigestInfo,
+        reader: DropCloserReadHalf,
+        referenced_chunks: &Mutex<Vec<DigestInfo>>,
+    ) -> Result<Vec<DigestInfo>, Error> {
+        let mut bytes_reader = StreamReader::new(reader);
+        let frame_reader = FramedRead::new(&mut bytes_reader, self.fast_cdc_decoder.clone());
+        let content_store_pin = self.pin_content_store();
+        let chunk_tracker = self.chunk_tracker.as_deref();
+        let index_entries = frame_reader
+            .map(|r| r.err_tip(|| "Failed to decode frame from fast_cdc"))
+            .map_ok(|frame| async move {
+                let hash = blake3::hash(&frame[..]).into();
+                let index_entry = DigestInfo::new(hash, frame.len() as i64);
+                if let Some(chunk_tracker) = chunk_tracker {
+                    chunk_tracker.add_reference(digest, index_entry);
+                    referenced_chunks.lock().push(index_entry);
+                }
+                if content_store_pin
+                    .has(index_entry)
+                    .await
+                    .err_tip(|| "Failed to call .has() in DedupStore::update()")?
+                    .is_some()
+                {
+                    // If our store has this digest, we don't need to upload it.
+                    return Result::<_, Error>::Ok(index_entry);
+                }
+                content_store_pin
+                    .update_oneshot(index_entry, frame)
+                    .await
+                    .err_tip(|| "Failed to update content store in dedup_store")?;
+                if let Some(chunk_tracker) = chunk_tracker {
+                    chunk_tracker.mark_owned(index_entry);
+                }
+                Ok(index_entry)
+            })
+            .try_buffered(self.max_concurrent_fetch_per_get)
+            .try_collect()
+            .await?;
+
+        let index = DedupIndex {
+            entries: index_entries,
+        };
+        let serialized_index = self.bincode_options.serialize(&index).map_err(|e| {
+            make_err!(
+                Code::Internal,
+                "Failed to serialize index in dedup_store : {:?}",
+                e
+            )
+        })?;
+
+        self.pin_index_store()
+            .update_oneshot(digest, serialized_index.into())
+            .await
+            .err_tip(|| "Failed to insert our index entry to index_store in dedup_store")?;
+
+        Ok(index.entries)
+    }
+
+    /// Returns the sizes of the given chunks in the content store, or `None`
+    /// for each chunk that is missing. Unlike `has`, this does not look the
+    /// digests up in the index store.
+    pub async fn has_chunks(
+        self: Pin<&Self>,
+        chunk_digests: &[DigestInfo],
+    ) -> Result<Vec<Option<usize>>, Error> {
+        self.pin_content_store().has_many(chunk_digests).await
+    }
+
+    fn is_broken_index(&self, digest: &DigestInfo) -> bool {
+        match &self.chunk_tracker {
+            Some(chunk_tracker) => chunk_tracker.is_broken(digest),
+            None => false,
+        }
+    }
+
     async fn has(self: Pin<&Self>, digest: DigestInfo) -> Result<Option<usize>, Error> {
+        if self.is_broken_index(&digest) {
+            return Ok(None);
+        }
         // First we need to load the index that contains where the individual parts actually
         // can be fetched from.
         let index_entries = {
@@ -132,10 +734,7 @@ impl DedupStore {
             .map(|index_entry| DigestInfo::new(index_entry.packed_hash, index_entry.size_bytes))
             .collect();
         let mut sum = 0;
-        for size in Pin::new(self.content_store.as_ref())
-            .has_many(&digests)
-            .await?
-        {
+        for size in self.pin_content_store().has_many(&digests).await? {
             let Some(size) = size else {
                 // A part is missing so return None meaning not-found.
                 // This will abort all in-flight queries related to this request.
@@ -178,52 +777,19 @@ impl Store for DedupStore {
         reader: DropCloserReadHalf,
         _size_info: UploadSizeInfo,
     ) -> Result<(), Error> {
-        let mut bytes_reader = StreamReader::new(reader);
-        let frame_reader = FramedRead::new(&mut bytes_reader, self.fast_cdc_decoder.clone());
-        let content_store_pin = Pin::new(self.content_store.as_ref());
-        let index_entries = frame_reader
-            .map(|r| r.err_tip(|| "Failed to decode frame from fast_cdc"))
-            .map_ok(|frame| async move {
-                let hash = blake3::hash(&frame[..]).into();
-                let index_entry = DigestInfo::new(hash, frame.len() as i64);
-                if content_store_pin
-                    .has(index_entry)
-                    .await
-                    .err_tip(|| "Failed to call .has() in DedupStore::update()")?
-                    .is_some()
-                {
-                    // If our store has this digest, we don't need to upload it.
-                    return Result::<_, Error>::Ok(index_entry);
+        let referenced_chunks = Mutex::new(Vec::new());
+        let result = self.upload_chunks(digest, reader, &referenced_chunks).await;
+        if let Some(chunk_tracker) = &self.chunk_tracker {
+            match &result {
+                Ok(index_entries) => chunk_tracker.commit_index(digest, index_entries).await,
+                Err(_) => {
+                    chunk_tracker
+                        .abort_index(digest, &referenced_chunks.into_inner())
+                        .await;
                 }
-                content_store_pin
-                    .update_oneshot(index_entry, frame)
-                    .await
-                    .err_tip(|| "Failed to update content store in dedup_store")?;
-                Ok(index_entry)
-            })
-            .try_buffered(self.max_concurrent_fetch_per_get)
-            .try_collect()
-            .await?;
-
-        let serialized_index = self
-            .bincode_options
-            .serialize(&DedupIndex {
-                entries: index_entries,
-            })
-            .map_err(|e| {
-                make_err!(
-                    Code::Internal,
-                    "Failed to serialize index in dedup_store : {:?}",
-                    e
-                )
-            })?;
-
-        self.pin_index_store()
-            .update_oneshot(digest, serialized_index.into())
-            .await
-            .err_tip(|| "Failed to insert our index entry to index_store in dedup_store")?;
-
-        Ok(())
+            }
+        }
+        result.map(|_| ())
     }
 
     async fn get_part_ref(
@@ -241,34 +807,37 @@ impl Store for DedupStore {
                 .err_tip(|| "Failed to write EOF out from get_part dedup")?;
             return Ok(());
         }
+        if self.is_broken_index(&digest) {
+            return Err(make_err!(
+                Code::NotFound,
+                "Index for {} in dedup store references a chunk that no longer exists",
+                digest.hash_str()
+            ));
+        }
         // First we need to download the index that contains where the individual parts actually
         // can be fetched from.
-        let index_entries = {
-            let data = self
-                .pin_index_store()
-                .get_part_unchunked(digest, 0, None, Some(self.upload_normal_size))
-                .await
-                .err_tip(|| "Failed to read index store in dedup store")?;
-
-            self.bincode_options
-                .deserialize::<DedupIndex>(&data)
-                .map_err(|e| {
-                    make_err!(
-                        Code::Internal,
-                        "Failed to deserialize index in dedup_store::get_part : {:?}",
-                        e
-                    )
-                })?
+        let index_entries = match self.get_chunk_digests(digest).await {
+            Ok(index_entries) => index_entries,
+            Err(e) if e.code == Code::NotFound => {
+                // The digest may be one of our chunks, which clients can
+                // request directly after splitting a blob.
+                return self
+                    .pin_content_store()
+                    .get_part_ref(digest, writer, offset, length)
+                    .await
+                    .err_tip(|| "Failed to read index store in dedup store");
+            }
+            Err(e) => return Err(e),
         };
 
         let mut start_byte_in_stream: usize = 0;
         let entries = {
             if offset == 0 && length.is_none() {
-                index_entries.entries
+                index_entries
             } else {
                 let mut current_entries_sum = 0;
-                let mut entries = Vec::with_capacity(index_entries.entries.len());
-                for entry in index_entries.entries {
+                let mut entries = Vec::with_capacity(index_entries.len());
+                for entry in index_entries {
                     let first_byte = current_entries_sum;
                     let entry_size = usize::try_from(entry.size_bytes)
                         .err_tip(|| "Failed to convert to usize in DedupStore")?;
@@ -355,6 +924,25 @@ impl Store for DedupStore {
         Ok(())
     }
 
+    async fn touch(self: Pin<&Self>, digests: &[DigestInfo]) -> Result<(), Error> {
+        self.pin_index_store()
+            .touch(digests)
+            .await
+            .err_tip(|| "Failed to touch indexes in DedupStore::touch")?;
+        let mut chunk_digests = Vec::new();
+        for digest in digests {
+            match self.get_chunk_digests(*digest).await {
+                Ok(index_chunk_digests) => chunk_digests.extend(index_chunk_digests),
+                Err(err) if err.code == Code::NotFound => {}
+                Err(err) => return Err(err).err_tip(|| "In DedupStore::touch"),
+            }
+        }
+        self.pin_content_store()
+            .touch(&chunk_digests)
+            .await
+            .err_tip(|| "Failed to touch chunks in DedupStore::touch")
+    }
+
     fn inner_store(&self, _digest: Option<DigestInfo>) -> &'_ dyn Store {
         self
     }
@@ -370,6 +958,85 @@ impl Store for DedupStore {
     fn as_any_arc(self: Arc<Self>) -> Arc<dyn std::any::Any + Sync + Send + 'static> {
         self
     }
+
+    fn register_metrics(self: Arc<Self>, registry: &mut Registry) {
+        if self.chunk_tracker.is_some() {
+            registry.register_collector(Box::new(Collector::new(&self)));
+        }
+    }
+
+    fn register_health(self: Arc<Self>, registry: &mut HealthRegistryBuilder) {
+        self.index_store
+            .clone()
+            .register_health(&mut registry.sub_builder("index_store".into()));
+        self.content_store
+            .clone()
+            .register_health(&mut registry.sub_builder("content_store".into()));
+    }
+}
+
+impl MetricsComponent for DedupStore {
+    fn gather_metrics(&self, c: &mut CollectorState) {
+        let Some(chunk_tracker) = &self.chunk_tracker else {
+            return;
+        };
+        c.publish(
+            "removed_chunks_total",
+            &chunk_tracker.removed_chunks,
+            "Number of orphaned chunks removed from the content store",
+        );
+        c.publish(
+            "removed_chunk_bytes_total",
+            &chunk_tracker.removed_chunk_bytes,
+            "Number of bytes of orphaned chunks removed from the content store",
+        );
+        c.publish(
+            "broken_indexes_total",
+            &chunk_tracker.broken_indexes,
+            "Number of indexes removed because one of their chunks was evicted",
+        );
+        c.publish(
+            "dropped_evictions_total",
+            &chunk_tracker.dropped_evictions,
+            "Number of evictions that were not tracked because the eviction queue was full",
+        );
+        c.publish(
+            "logical_bytes",
+            &chunk_tracker.logical_bytes,
+            "Total size of all tracked blobs before deduplication",
+        );
+        c.publish(
+            "referenced_chunk_bytes",
+            &chunk_tracker.referenced_bytes,
+            "Total size of all chunks referenced by a tracked blob",
+        );
+        let logical_bytes = chunk_tracker.logical_bytes.load(Ordering::Relaxed);
+        let referenced_bytes = chunk_tracker.referenced_bytes.load(Ordering::Relaxed);
+        c.publish(
+            "dedup_ratio",
+            &if referenced_bytes == 0 {
+                0.
+            } else {
+                logical_bytes as f64 / referenced_bytes as f64
+            },
+            "Ratio between the size of tracked blobs and the size of the chunks they use",
+        );
+        c.publish(
+            "orphaned_bytes",
+            &chunk_tracker.orphaned_bytes,
+            "Size of unreferenced chunks that could not be removed from the content store",
+        );
+        c.publish(
+            "tracked_indexes",
+            &chunk_tracker.tracked_indexes,
+            "Number of indexes with tracked chunk references",
+        );
+        c.publish(
+            "tracked_chunks",
+            &chunk_tracker.tracked_chunks,
+            "Number of chunks with tracked references",
+        );
+    }
 }
 
 default_health_status_indicator!(DedupStore);
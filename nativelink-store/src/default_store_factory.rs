@@ -23,6 +23,7 @@ use nativelink_util::health_utils::HealthRegistryBuilder;
 use nativelink_util::metrics_utils::Registry;
 use nativelink_util::store_trait::Store;
 
+use crate::circuit_breaker_store::CircuitBreakerStore;
 use crate::completeness_checking_store::CompletenessCheckingStore;
 use crate::compression_store::CompressionStore;
 use crate::dedup_store::DedupStore;
@@ -30,16 +31,20 @@ use crate::existence_cache_store::ExistenceCacheStore;
 use crate::fast_slow_store::FastSlowStore;
 use crate::filesystem_store::FilesystemStore;
 use crate::grpc_store::GrpcStore;
+use crate::hedge_store::HedgeStore;
 use crate::memory_store::MemoryStore;
 use crate::noop_store::NoopStore;
+use crate::quota_store::{QuotaOwners, QuotaStore};
 use crate::ref_store::RefStore;
+use crate::routing_store::RoutingStore;
 use crate::s3_store::S3Store;
 use crate::shard_store::ShardStore;
 use crate::size_partitioning_store::SizePartitioningStore;
 use crate::store_manager::StoreManager;
+use crate::tiered_store::TieredStore;
 use crate::verify_store::VerifyStore;
 
-type FutureMaybeStore<'a> = Box<dyn Future<Output = Result<Arc<dyn Store>, Error>> + 'a>;
+type FutureMaybeStore<'a> = Box<dyn Future<Output = Result<Arc<dyn Store>, Error>> + Send + 'a>;
 
 pub fn store_factory<'a>(
     backend: &'a StoreConfig,
@@ -63,15 +68,48 @@ pub fn store_factory<'a>(
                 config,
                 store_factory(&config.index_store, store_manager, None, None).await?,
                 store_factory(&config.content_store, store_manager, None, None).await?,
-            )),
+            )?),
             StoreConfig::existence_cache(config) => Arc::new(ExistenceCacheStore::new(
                 config,
                 store_factory(&config.backend, store_manager, None, None).await?,
             )),
             StoreConfig::completeness_checking(config) => Arc::new(CompletenessCheckingStore::new(
+                config,
                 store_factory(&config.backend, store_manager, None, None).await?,
                 store_factory(&config.cas_store, store_manager, None, None).await?,
             )),
+            StoreConfig::circuit_breaker(config) => Arc::new(CircuitBreakerStore::new(
+                config,
+                store_factory(&config.backend, store_manager, None, None).await?,
+            )?),
+            StoreConfig::quota(config) => {
+                // Only quota stores referencing the same named store can
+                // share objects, a nested backend belongs to a single one.
+                let owners = match &config.backend {
+                    StoreConfig::ref_store(ref_config) => {
+                        store_manager.quota_owners(&ref_config.name)
+                    }
+                    _ => Arc::new(QuotaOwners::default()),
+                };
+                Arc::new(QuotaStore::new(
+                    config,
+                    store_factory(&config.backend, store_manager, None, None).await?,
+                    owners,
+                ))
+            }
+            StoreConfig::hedge(config) => {
+                let hedge_backend = match &config.hedge_backend {
+                    Some(hedge_backend) => {
+                        Some(store_factory(hedge_backend, store_manager, None, None).await?)
+                    }
+                    None => None,
+                };
+                Arc::new(HedgeStore::new(
+                    config,
+                    store_factory(&config.backend, store_manager, None, None).await?,
+                    hedge_backend,
+                )?)
+            }
             StoreConfig::fast_slow(config) => Arc::new(FastSlowStore::new(
                 config,
                 store_factory(&config.fast, store_manager, None, None).await?,
@@ -86,6 +124,20 @@ pub fn store_factory<'a>(
                 store_factory(&config.lower_store, store_manager, None, None).await?,
                 store_factory(&config.upper_store, store_manager, None, None).await?,
             )),
+            StoreConfig::routing(config) => {
+                let rule_stores = config
+                    .rules
+                    .iter()
+                    .map(|rule| store_factory(&rule.store, store_manager, None, None))
+                    .collect::<FuturesOrdered<_>>()
+                    .try_collect::<Vec<_>>()
+                    .await?;
+                Arc::new(RoutingStore::new(
+                    config,
+                    rule_stores,
+                    store_factory(&config.default_store, store_manager, None, None).await?,
+                )?)
+            }
             StoreConfig::grpc(config) => Arc::new(GrpcStore::new(config).await?),
             StoreConfig::noop => Arc::new(NoopStore::new()),
             StoreConfig::shard(config) => {
@@ -100,6 +152,16 @@ pub fn store_factory<'a>(
                     .await?;
                 Arc::new(ShardStore::new(config, stores)?)
             }
+            StoreConfig::tiered(config) => {
+                let stores = config
+                    .tiers
+                    .iter()
+                    .map(|tier_config| store_factory(&tier_config.store, store_manager, None, None))
+                    .collect::<FuturesOrdered<_>>()
+                    .try_collect::<Vec<_>>()
+                    .await?;
+                TieredStore::new(config, stores)?
+            }
         };
         if let Some(store_metrics) = maybe_store_metrics {
             store.clone().register_metrics(store_metrics);
@@ -12,19 +12,33 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::f64::consts::LN_2;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use async_trait::async_trait;
-use nativelink_config::stores::{EvictionPolicy, ExistenceCacheStore as ExistenceCacheStoreConfig};
-use nativelink_error::{error_if, Error, ResultExt};
+use nativelink_config::stores::{
+    BloomFilterConfig, EvictionPolicy, ExistenceCacheStore as ExistenceCacheStoreConfig,
+};
+use nativelink_error::{Error, ResultExt};
 use nativelink_util::buf_channel::{DropCloserReadHalf, DropCloserWriteHalf};
-use nativelink_util::common::DigestInfo;
+use nativelink_util::common::{DigestInfo, JoinHandleDropGuard};
 use nativelink_util::evicting_map::{EvictingMap, LenEntry};
-use nativelink_util::health_utils::{default_health_status_indicator, HealthStatusIndicator};
-use nativelink_util::metrics_utils::{CollectorState, MetricsComponent, Registry};
+use nativelink_util::health_utils::{
+    default_health_status_indicator, HealthRegistryBuilder, HealthStatusIndicator,
+};
+use nativelink_util::metrics_utils::{
+    Collector, CollectorState, Counter, MetricsComponent, Registry,
+};
 use nativelink_util::store_trait::{Store, UploadSizeInfo};
+use parking_lot::Mutex;
+use tracing::{info, warn};
+
+const DEFAULT_NEGATIVE_CACHE_MAX_ITEMS: u64 = 1_000_000;
+const DEFAULT_BLOOM_FILTER_EXPECTED_ITEMS: u64 = 10_000_000;
+const DEFAULT_BLOOM_FILTER_FALSE_POSITIVE_RATE: f64 = 0.01;
 
 #[derive(Clone, Debug)]
 struct ExistanceItem(usize);
@@ -41,18 +55,185 @@ impl LenEntry for ExistanceItem {
     }
 }
 
+/// Bloom filter of digests. Digests are already uniformly distributed
+/// hashes, so the bit positions are derived from the digest itself instead
+/// of hashing it again.
+struct BloomFilter {
+    bits: Vec<AtomicU64>,
+    num_bits: u64,
+    num_hashes: u64,
+    /// Set once the filter holds every digest in the backend.
+    ready: AtomicBool,
+}
+
+impl BloomFilter {
+    fn new(config: &BloomFilterConfig) -> Self {
+        let expected_items = if config.expected_items == 0 {
+            DEFAULT_BLOOM_FILTER_EXPECTED_ITEMS
+        } else {
+            config.expected_items
+        } as f64;
+        let false_positive_rate =
+            if config.false_positive_rate > 0. && config.false_positive_rate < 1. {
+                config.false_positive_rate
+            } else {
+                DEFAULT_BLOOM_FILTER_FALSE_POSITIVE_RATE
+            };
+        let num_bits =
+            ((-expected_items * false_positive_rate.ln() / (LN_2 * LN_2)).ceil() as u64).max(64);
+        let num_hashes = ((num_bits as f64 / expected_items * LN_2).round() as u64).max(1);
+        Self {
+            bits: (0..num_bits.div_ceil(64))
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+            num_bits,
+            num_hashes,
+            ready: AtomicBool::new(false),
+        }
+    }
+
+    fn bit_indexes(&self, digest: &DigestInfo) -> impl Iterator<Item = u64> {
+        let mut words = [0u64; 4];
+        for (word, bytes) in words.iter_mut().zip(digest.packed_hash.chunks_exact(8)) {
+            let mut buf = [0u8; 8];
+            buf.copy_from_slice(bytes);
+            *word = u64::from_le_bytes(buf);
+        }
+        let first = words[0] ^ words[2] ^ digest.size_bytes as u64;
+        let second = (words[1] ^ words[3]) | 1;
+        let num_bits = self.num_bits;
+        (0..self.num_hashes).map(move |i| first.wrapping_add(i.wrapping_mul(second)) % num_bits)
+    }
+
+    fn insert(&self, digest: &DigestInfo) {
+        for bit in self.bit_indexes(digest) {
+            self.bits[(bit / 64) as usize].fetch_or(1 << (bit % 64), Ordering::Relaxed);
+        }
+    }
+
+    fn may_contain(&self, digest: &DigestInfo) -> bool {
+        self.bit_indexes(digest).all(|bit| {
+            self.bits[(bit / 64) as usize].load(Ordering::Relaxed) & (1 << (bit % 64)) != 0
+        })
+    }
+
+    fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+}
+
+/// Fills `bloom_filter` with every digest listed by `inner_store` and marks
+/// it ready once the listing succeeds.
+fn spawn_populate_task(
+    bloom_filter: Arc<BloomFilter>,
+    inner_store: Arc<dyn Store>,
+) -> JoinHandleDropGuard<()> {
+    JoinHandleDropGuard::new(tokio::spawn(async move {
+        let mut item_count: u64 = 0;
+        let result = Pin::new(inner_store.as_ref())
+            .list(&mut |digest, _size| {
+                bloom_filter.insert(&digest);
+                item_count += 1;
+            })
+            .await;
+        match result {
+            Ok(()) => {
+                info!("Populated bloom filter of ExistenceCacheStore with {item_count} digests");
+                bloom_filter.ready.store(true, Ordering::Release);
+            }
+            Err(err) => warn!(
+                "Failed to populate bloom filter of ExistenceCacheStore, it will not be used : {err:?}"
+            ),
+        }
+    }))
+}
+
 pub struct ExistenceCacheStore {
     inner_store: Arc<dyn Store>,
     existence_cache: EvictingMap<ExistanceItem, SystemTime>,
+    /// Digests the backend recently reported as missing.
+    negative_cache: Option<EvictingMap<ExistanceItem, SystemTime>>,
+    bloom_filter: Option<Arc<BloomFilter>>,
+    /// Lists the backend into the bloom filter. Started when the store is
+    /// created if preloading, otherwise on the first lookup.
+    populate_task: Mutex<Option<JoinHandleDropGuard<()>>>,
+
+    // Metrics.
+    negative_cache_hits: Counter,
+    bloom_filter_hits: Counter,
 }
 
 impl ExistenceCacheStore {
     pub fn new(config: &ExistenceCacheStoreConfig, inner_store: Arc<dyn Store>) -> Self {
         let empty_policy = EvictionPolicy::default();
         let eviction_policy = config.eviction_policy.as_ref().unwrap_or(&empty_policy);
+        let negative_cache = (config.negative_cache_seconds != 0).then(|| {
+            EvictingMap::new(
+                &EvictionPolicy {
+                    max_seconds: u32::try_from(config.negative_cache_seconds).unwrap_or(u32::MAX),
+                    max_count: if config.negative_cache_max_items == 0 {
+                        DEFAULT_NEGATIVE_CACHE_MAX_ITEMS
+                    } else {
+                        config.negative_cache_max_items
+                    },
+                    ..Default::default()
+                },
+                SystemTime::now(),
+            )
+        });
+        let bloom_filter = config
+            .bloom_filter
+            .as_ref()
+            .map(|bloom_filter_config| Arc::new(BloomFilter::new(bloom_filter_config)));
+        let populate_task = bloom_filter
+            .as_ref()
+            .zip(config.bloom_filter.as_ref())
+            .filter(|(_, bloom_filter_config)| bloom_filter_config.preload)
+            .map(|(bloom_filter, _)| {
+                spawn_populate_task(bloom_filter.clone(), inner_store.clone())
+            });
         Self {
             inner_store,
             existence_cache: EvictingMap::new(eviction_policy, SystemTime::now()),
+            negative_cache,
+            bloom_filter,
+            populate_task: Mutex::new(populate_task),
+            negative_cache_hits: Counter::default(),
+            bloom_filter_hits: Counter::default(),
+        }
+    }
+
+    /// Returns true once the bloom filter is used to answer lookups.
+    pub fn bloom_filter_ready(&self) -> bool {
+        self.bloom_filter
+            .as_ref()
+            .is_some_and(|bloom_filter| bloom_filter.is_ready())
+    }
+
+    /// Starts populating the bloom filter if nothing has started it yet.
+    fn maybe_populate_bloom_filter(&self, bloom_filter: &Arc<BloomFilter>) {
+        let mut populate_task = self.populate_task.lock();
+        if populate_task.is_none() {
+            *populate_task = Some(spawn_populate_task(
+                bloom_filter.clone(),
+                self.inner_store.clone(),
+            ));
+        }
+    }
+
+    /// Records that `digest` exists in the backend.
+    async fn mark_exists(&self, digest: DigestInfo, size: Option<usize>) {
+        if let Some(negative_cache) = &self.negative_cache {
+            negative_cache.remove(&digest).await;
+        }
+        if let Some(bloom_filter) = &self.bloom_filter {
+            bloom_filter.insert(&digest);
+        }
+        if let Some(size) = size {
+            let _ = self
+                .existence_cache
+                .insert(digest, ExistanceItem(size))
+                .await;
         }
     }
 
@@ -71,16 +252,53 @@ impl ExistenceCacheStore {
     ) -> Result<(), Error> {
         self.existence_cache.sizes_for_keys(digests, results).await;
 
-        let not_cached_digests: Vec<DigestInfo> = digests
+        // Digests not known to exist, along with their index in `results`.
+        let mut not_cached: Vec<(usize, DigestInfo)> = digests
             .iter()
             .zip(results.iter())
-            .filter_map(|(digest, result)| result.map_or_else(|| Some(*digest), |_| None))
+            .enumerate()
+            .filter_map(|(i, (digest, result))| result.map_or_else(|| Some((i, *digest)), |_| None))
             .collect();
 
         // Hot path optimization when all digests are cached.
-        if not_cached_digests.is_empty() {
+        if not_cached.is_empty() {
+            return Ok(());
+        }
+
+        // Until the bloom filter holds every digest in the backend, lookups
+        // fall through to the backend.
+        let bloom_filter = self.bloom_filter.as_ref().filter(|bloom_filter| {
+            if !bloom_filter.is_ready() {
+                self.maybe_populate_bloom_filter(bloom_filter);
+            }
+            bloom_filter.is_ready()
+        });
+        if let Some(bloom_filter) = bloom_filter {
+            let not_cached_count = not_cached.len();
+            not_cached.retain(|(_, digest)| bloom_filter.may_contain(digest));
+            self.bloom_filter_hits
+                .add((not_cached_count - not_cached.len()) as u64);
+        }
+
+        if let Some(negative_cache) = &self.negative_cache {
+            let maybe_missing_digests: Vec<DigestInfo> =
+                not_cached.iter().map(|(_, digest)| *digest).collect();
+            let mut negative_results = vec![None; maybe_missing_digests.len()];
+            negative_cache
+                .sizes_for_keys(&maybe_missing_digests, &mut negative_results)
+                .await;
+            let mut negative_results = negative_results.into_iter();
+            let not_cached_count = not_cached.len();
+            not_cached.retain(|_| negative_results.next().flatten().is_none());
+            self.negative_cache_hits
+                .add((not_cached_count - not_cached.len()) as u64);
+        }
+
+        if not_cached.is_empty() {
             return Ok(());
         }
+        let not_cached_digests: Vec<DigestInfo> =
+            not_cached.iter().map(|(_, digest)| *digest).collect();
 
         // Now query only the items not found in the cache.
         let mut inner_results = vec![None; not_cached_digests.len()];
@@ -98,26 +316,26 @@ impl ExistenceCacheStore {
                 .zip(inner_results.iter())
                 .filter_map(|(digest, result)| result.map(|size| (*digest, ExistanceItem(size))))
                 .collect::<Vec<_>>();
+            if let Some(bloom_filter) = &self.bloom_filter {
+                for (digest, _) in &inserts {
+                    bloom_filter.insert(digest);
+                }
+            }
             let _ = self.existence_cache.insert_many(inserts).await;
         }
+        if let Some(negative_cache) = &self.negative_cache {
+            let inserts = not_cached_digests
+                .iter()
+                .zip(inner_results.iter())
+                .filter(|(_, result)| result.is_none())
+                .map(|(digest, _)| (*digest, ExistanceItem(0)))
+                .collect::<Vec<_>>();
+            let _ = negative_cache.insert_many(inserts).await;
+        }
 
         // Merge the results from the cache and the query.
-        {
-            let mut inner_results_iter = inner_results.into_iter();
-            // We know at this point that any None in results was queried and will have
-            // a result in inner_results_iter, so use this knowledge to fill in the results.
-            for result in results.iter_mut() {
-                if result.is_none() {
-                    *result = inner_results_iter
-                        .next()
-                        .expect("has_with_results returned less results than expected");
-                }
-            }
-            // Ensure that there was no logic error by ensuring our iterator is not empty.
-            error_if!(
-                inner_results_iter.next().is_some(),
-                "has_with_results returned more results than expected"
-            );
+        for ((index, _), inner_result) in not_cached.into_iter().zip(inner_results) {
+            results[index] = inner_result;
         }
 
         Ok(())
@@ -159,12 +377,11 @@ impl Store for ExistenceCacheStore {
         }
         let result = self.pin_inner().update(digest, reader, size_info).await;
         if result.is_ok() {
-            if let UploadSizeInfo::ExactSize(size) = size_info {
-                let _ = self
-                    .existence_cache
-                    .insert(digest, ExistanceItem(size))
-                    .await;
-            }
+            let size = match size_info {
+                UploadSizeInfo::ExactSize(size) => Some(size),
+                UploadSizeInfo::MaxSize(_) => None,
+            };
+            self.mark_exists(digest, size).await;
         }
         result
     }
@@ -183,14 +400,26 @@ impl Store for ExistenceCacheStore {
         if result.is_ok() {
             let size = usize::try_from(digest.size_bytes)
                 .err_tip(|| "Could not convert size_bytes in ExistenceCacheStore::get_part")?;
-            let _ = self
-                .existence_cache
-                .insert(digest, ExistanceItem(size))
-                .await;
+            self.mark_exists(digest, Some(size)).await;
         }
         result
     }
 
+    async fn list(
+        self: Pin<&Self>,
+        handler: &mut (dyn FnMut(DigestInfo, usize) + Send),
+    ) -> Result<(), Error> {
+        Pin::new(self.inner_store.as_ref()).list(handler).await
+    }
+
+    async fn touch(self: Pin<&Self>, digests: &[DigestInfo]) -> Result<(), Error> {
+        Pin::new(self.inner_store.as_ref()).touch(digests).await
+    }
+
+    async fn pin(self: Pin<&Self>, digests: &[DigestInfo], ttl: Duration) -> Result<usize, Error> {
+        Pin::new(self.inner_store.as_ref()).pin(digests, ttl).await
+    }
+
     fn inner_store(&self, _digest: Option<DigestInfo>) -> &'_ dyn Store {
         self
     }
@@ -212,12 +441,41 @@ impl Store for ExistenceCacheStore {
         self.inner_store
             .clone()
             .register_metrics(inner_store_registry);
+        registry.register_collector(Box::new(Collector::new(&self)));
+    }
+
+    fn register_health(self: Arc<Self>, registry: &mut HealthRegistryBuilder) {
+        self.inner_store
+            .clone()
+            .register_health(&mut registry.sub_builder("inner_store".into()));
     }
 }
 
 impl MetricsComponent for ExistenceCacheStore {
     fn gather_metrics(&self, c: &mut CollectorState) {
-        self.existence_cache.gather_metrics(c)
+        self.existence_cache.gather_metrics(c);
+        c.publish(
+            "negative_cache_hits_total",
+            &self.negative_cache_hits,
+            "Number of digests reported missing because the backend recently reported them missing",
+        );
+        if let Some(bloom_filter) = &self.bloom_filter {
+            c.publish(
+                "bloom_filter_hits_total",
+                &self.bloom_filter_hits,
+                "Number of digests reported missing because they were not in the bloom filter",
+            );
+            c.publish(
+                "bloom_filter_ready",
+                &bloom_filter.is_ready(),
+                "Whether the bloom filter is used to answer lookups",
+            );
+            c.publish(
+                "bloom_filter_size_bytes",
+                &(bloom_filter.bits.len() * 8),
+                "Memory used by the bloom filter",
+            );
+        }
     }
 }
 
@@ -16,6 +16,7 @@ use std::cmp::{max, min};
 use std::ops::Range;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use futures::{join, FutureExt};
@@ -25,12 +26,16 @@ use nativelink_util::buf_channel::{
 };
 use nativelink_util::common::DigestInfo;
 use nativelink_util::fs;
-use nativelink_util::health_utils::{default_health_status_indicator, HealthStatusIndicator};
+use nativelink_util::health_utils::{
+    default_health_status_indicator, HealthRegistryBuilder, HealthStatusIndicator,
+};
 use nativelink_util::metrics_utils::Registry;
 use nativelink_util::store_trait::{
     slow_update_store_with_file, Store, StoreOptimizations, UploadSizeInfo,
 };
 
+use crate::circuit_breaker_store::is_rejected_by_circuit_breaker;
+
 // TODO(blaise.bruer) This store needs to be evaluated for more efficient memory usage,
 // there are many copies happening internally.
 
@@ -140,9 +145,22 @@ impl Store for FastSlowStore {
         // down stream might be unable to get it.  This should not affect
         // workers as they only use get() and a CAS can use an
         // ExistenceCacheStore to avoid the bottleneck.
-        self.pin_slow_store()
+        match self
+            .pin_slow_store()
             .has_with_results(digests, results)
             .await
+        {
+            // The circuit breaker of the slow store is refusing requests, so
+            // answer from the fast store until it recovers. Other errors of
+            // the slow store are returned, as the fast store may be missing
+            // blobs.
+            Err(err) if is_rejected_by_circuit_breaker(&err) => self
+                .pin_fast_store()
+                .has_with_results(digests, results)
+                .await
+                .err_tip(|| "Slow store unavailable in FastSlowStore::has_with_results"),
+            result => result,
+        }
     }
 
     async fn update(
@@ -346,6 +364,33 @@ impl Store for FastSlowStore {
         }
     }
 
+    async fn list(
+        self: Pin<&Self>,
+        handler: &mut (dyn FnMut(DigestInfo, usize) + Send),
+    ) -> Result<(), Error> {
+        // Everything in the fast store is also in the slow store.
+        Pin::new(self.slow_store.as_ref()).list(handler).await
+    }
+
+    async fn touch(self: Pin<&Self>, digests: &[DigestInfo]) -> Result<(), Error> {
+        let (fast_result, slow_result) = join!(
+            Pin::new(self.fast_store.as_ref()).touch(digests),
+            Pin::new(self.slow_store.as_ref()).touch(digests),
+        );
+        fast_result.merge(slow_result)
+    }
+
+    async fn pin(self: Pin<&Self>, digests: &[DigestInfo], ttl: Duration) -> Result<usize, Error> {
+        let (fast_result, slow_result) = join!(
+            Pin::new(self.fast_store.as_ref()).pin(digests, ttl),
+            Pin::new(self.slow_store.as_ref()).pin(digests, ttl),
+        );
+        match (fast_result, slow_result) {
+            (Ok(fast_pinned), Ok(slow_pinned)) => Ok(fast_pinned.max(slow_pinned)),
+            (fast_result, slow_result) => fast_result.merge(slow_result),
+        }
+    }
+
     fn inner_store(&self, _digest: Option<DigestInfo>) -> &'_ dyn Store {
         self
     }
@@ -372,6 +417,15 @@ impl Store for FastSlowStore {
             .clone()
             .register_metrics(slow_store_registry);
     }
+
+    fn register_health(self: Arc<Self>, registry: &mut HealthRegistryBuilder) {
+        self.fast_store
+            .clone()
+            .register_health(&mut registry.sub_builder("fast".into()));
+        self.slow_store
+            .clone()
+            .register_health(&mut registry.sub_builder("slow".into()));
+    }
 }
 
 default_health_status_indicator!(FastSlowStore);
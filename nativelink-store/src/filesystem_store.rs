@@ -890,6 +890,30 @@ impl<Fe: FileEntry> Store for FilesystemStore<Fe> {
         Ok(())
     }
 
+    async fn remove(self: Pin<&Self>, digest: DigestInfo) -> Result<bool, Error> {
+        Ok(self.evicting_map.remove(&digest).await)
+    }
+
+    async fn list(
+        self: Pin<&Self>,
+        handler: &mut (dyn FnMut(DigestInfo, usize) + Send),
+    ) -> Result<(), Error> {
+        self.evicting_map
+            .for_each_item(|digest, size| handler(*digest, size))
+            .await;
+        Ok(())
+    }
+
+    async fn touch(self: Pin<&Self>, digests: &[DigestInfo]) -> Result<(), Error> {
+        self.evicting_map.touch(digests).await;
+        Ok(())
+    }
+
+    async fn pin(self: Pin<&Self>, digests: &[DigestInfo], ttl: Duration) -> Result<usize, Error> {
+        let pinned = self.evicting_map.pin(digests, ttl).await;
+        Ok(pinned.into_iter().filter(|pinned| *pinned).count())
+    }
+
     fn inner_store(&self, _digest: Option<DigestInfo>) -> &'_ dyn Store {
         self
     }
@@ -906,6 +930,14 @@ impl<Fe: FileEntry> Store for FilesystemStore<Fe> {
         self
     }
 
+    async fn set_eviction_policy(
+        &self,
+        eviction_policy: &nativelink_config::stores::EvictionPolicy,
+    ) -> Result<(), Error> {
+        self.evicting_map.set_eviction_policy(eviction_policy).await;
+        Ok(())
+    }
+
     fn register_metrics(self: Arc<Self>, registry: &mut Registry) {
         registry.register_collector(Box::new(Collector::new(&self)));
     }
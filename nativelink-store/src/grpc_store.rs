@@ -27,6 +27,7 @@ use nativelink_proto::build::bazel::remote::execution::v2::{
     digest_function, ActionResult, BatchReadBlobsRequest, BatchReadBlobsResponse,
     BatchUpdateBlobsRequest, BatchUpdateBlobsResponse, FindMissingBlobsRequest,
     FindMissingBlobsResponse, GetActionResultRequest, GetTreeRequest, GetTreeResponse,
+    SpliceBlobRequest, SpliceBlobResponse, SplitBlobRequest, SplitBlobResponse,
     UpdateActionResultRequest,
 };
 use nativelink_proto::google::bytestream::byte_stream_client::ByteStreamClient;
@@ -106,9 +107,10 @@ impl GrpcStore {
                 Arc::new(jitter_fn),
                 config.retry.to_owned(),
             ),
-            connection_manager: ConnectionManager::new(
+            connection_manager: ConnectionManager::new_with_load_balancing(
                 endpoints.into_iter(),
                 config.max_concurrent_requests,
+                config.load_balancing.as_ref(),
             ),
         })
     }
@@ -233,6 +235,56 @@ impl GrpcStore {
         .await
     }
 
+    pub async fn split_blob(
+        &self,
+        grpc_request: Request<SplitBlobRequest>,
+    ) -> Result<Response<SplitBlobResponse>, Error> {
+        error_if!(
+            matches!(self.store_type, nativelink_config::stores::StoreType::ac),
+            "CAS operation on AC store"
+        );
+
+        let mut request = grpc_request.into_inner();
+        request.instance_name = self.instance_name.clone();
+        self.perform_request(request, |request| async move {
+            let (connection, channel) = self.connection_manager.get_connection().await;
+            let result = ContentAddressableStorageClient::new(channel)
+                .split_blob(Request::new(request))
+                .await
+                .err_tip(|| "in GrpcStore::split_blob");
+            if let Err(err) = &result {
+                connection.on_error(err);
+            }
+            result
+        })
+        .await
+    }
+
+    pub async fn splice_blob(
+        &self,
+        grpc_request: Request<SpliceBlobRequest>,
+    ) -> Result<Response<SpliceBlobResponse>, Error> {
+        error_if!(
+            matches!(self.store_type, nativelink_config::stores::StoreType::ac),
+            "CAS operation on AC store"
+        );
+
+        let mut request = grpc_request.into_inner();
+        request.instance_name = self.instance_name.clone();
+        self.perform_request(request, |request| async move {
+            let (connection, channel) = self.connection_manager.get_connection().await;
+            let result = ContentAddressableStorageClient::new(channel)
+                .splice_blob(Request::new(request))
+                .await
+                .err_tip(|| "in GrpcStore::splice_blob");
+            if let Err(err) = &result {
+                connection.on_error(err);
+            }
+            result
+        })
+        .await
+    }
+
     fn get_read_request(&self, mut request: ReadRequest) -> Result<ReadRequest, Error> {
         const IS_UPLOAD_FALSE: bool = false;
         let mut resource_info = ResourceInfo::new(&request.resource_name, IS_UPLOAD_FALSE)?;
@@ -292,6 +344,10 @@ impl GrpcStore {
             "CAS operation on AC store"
         );
 
+        // Every attempt of an upload goes to the same endpoint so it can be
+        // resumed.
+        let upload_key = stream.uuid.clone().unwrap_or_else(|| stream.hash.clone());
+        let upload_key = upload_key.as_str();
         let local_state = Arc::new(Mutex::new(WriteState::new(
             self.instance_name.clone(),
             stream,
@@ -300,7 +356,10 @@ impl GrpcStore {
         let result = self
             .retrier
             .retry(unfold(local_state, move |local_state| async move {
-                let (connection, channel) = self.connection_manager.get_connection().await;
+                let (connection, channel) = self
+                    .connection_manager
+                    .get_connection_for_key(upload_key)
+                    .await;
                 // The client write may occur on a separate thread and
                 // therefore in order to share the state with it we have to
                 // wrap it in a Mutex and retrieve it after the write
@@ -353,13 +412,18 @@ impl GrpcStore {
 
         const IS_UPLOAD_TRUE: bool = true;
         let mut request_info = ResourceInfo::new(&request.resource_name, IS_UPLOAD_TRUE)?;
+        let upload_key = request_info.uuid.unwrap_or(request_info.hash).to_string();
         if request_info.instance_name != self.instance_name {
             request_info.instance_name = &self.instance_name;
             request.resource_name = request_info.to_string(IS_UPLOAD_TRUE);
         }
 
+        let upload_key = upload_key.as_str();
         self.perform_request(request, |request| async move {
-            let (connection, channel) = self.connection_manager.get_connection().await;
+            let (connection, channel) = self
+                .connection_manager
+                .get_connection_for_key(upload_key)
+                .await;
             let result = ByteStreamClient::new(channel)
                 .query_write_status(Request::new(request))
                 .await
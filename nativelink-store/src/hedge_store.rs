@@ -0,0 +1,404 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use futures::join;
+use nativelink_config::stores::HedgeStore as HedgeStoreConfig;
+use nativelink_error::{error_if, Error, ResultExt};
+use nativelink_util::buf_channel::{
+    make_buf_channel_pair, DropCloserReadHalf, DropCloserWriteHalf,
+};
+use nativelink_util::common::DigestInfo;
+use nativelink_util::health_utils::{
+    default_health_status_indicator, HealthRegistryBuilder, HealthStatusIndicator,
+};
+use nativelink_util::metrics_utils::{
+    Collector, CollectorState, CounterWithTime, MetricsComponent, Registry,
+};
+use nativelink_util::store_trait::{Store, UploadSizeInfo};
+use parking_lot::Mutex;
+use tokio::time::sleep;
+
+const DEFAULT_DELAY_PERCENTILE: f32 = 95.;
+const DEFAULT_SAMPLE_WINDOW: usize = 1000;
+const DEFAULT_MAX_DELAY_MS: u64 = 1000;
+
+/// Number of samples that must be collected before the percentile is used
+/// instead of `max_delay`.
+const MIN_SAMPLES: usize = 20;
+
+/// Number of new samples after which the percentile is computed again.
+/// Computing it copies the whole sample window, so it is not done on every
+/// read.
+const SAMPLES_PER_DELAY_UPDATE: usize = 100;
+
+/// A read request that may be raced against another identical request.
+struct ReadAttempt<'a> {
+    /// When the request was sent, to measure its time-to-first-byte.
+    start_time: Instant,
+    get_fut: BoxFuture<'a, Result<(), Error>>,
+    get_result: Option<Result<(), Error>>,
+    rx: DropCloserReadHalf,
+}
+
+impl<'a> ReadAttempt<'a> {
+    fn new(
+        store: &'a Arc<dyn Store>,
+        digest: DigestInfo,
+        offset: usize,
+        length: Option<usize>,
+    ) -> Self {
+        let (tx, rx) = make_buf_channel_pair();
+        Self {
+            start_time: Instant::now(),
+            get_fut: Pin::new(store.as_ref()).get_part(digest, tx, offset, length),
+            get_result: None,
+            rx,
+        }
+    }
+
+    /// Resolves once the request produced data, finished or failed.
+    async fn wait_for_response(&mut self) {
+        if self.get_result.is_some() {
+            return;
+        }
+        // A failed request drops its writer, which makes `peek()` ready too,
+        // so the result of the request must be checked first.
+        let peek_failed = tokio::select! {
+            biased;
+            result = &mut self.get_fut => {
+                self.get_result = Some(result);
+                false
+            }
+            peek_result = self.rx.peek() => peek_result.is_err(),
+        };
+        if peek_failed {
+            self.get_result = Some((&mut self.get_fut).await);
+        }
+    }
+
+    fn failed(&self) -> bool {
+        matches!(self.get_result, Some(Err(_)))
+    }
+
+    /// Sends all the data of this request to `writer`.
+    async fn forward(self, writer: &mut DropCloserWriteHalf) -> Result<(), Error> {
+        let ReadAttempt {
+            start_time: _,
+            get_fut,
+            get_result,
+            mut rx,
+        } = self;
+        let get_fut = async move {
+            match get_result {
+                Some(result) => result,
+                None => get_fut.await,
+            }
+        };
+        let writer_ref = &mut *writer;
+        let forward_fut = async move {
+            loop {
+                let chunk = rx
+                    .recv()
+                    .await
+                    .err_tip(|| "Failed to read data in HedgeStore")?;
+                if chunk.is_empty() {
+                    return Ok(());
+                }
+                writer_ref
+                    .send(chunk)
+                    .await
+                    .err_tip(|| "Failed to write data in HedgeStore")?;
+            }
+        };
+        let (get_res, forward_res) = join!(get_fut, forward_fut);
+        get_res.merge(forward_res)?;
+        // Sending the EOF may drop us right away, so it must be last.
+        writer
+            .send_eof()
+            .await
+            .err_tip(|| "Failed to write EOF in HedgeStore")
+    }
+}
+
+struct TimeToFirstByteSamples {
+    samples: VecDeque<Duration>,
+    /// Samples added since the hedge delay was last computed.
+    new_samples: usize,
+}
+
+pub struct HedgeStore {
+    backend: Arc<dyn Store>,
+    hedge_backend: Option<Arc<dyn Store>>,
+    delay_percentile: f32,
+    sample_window: usize,
+    min_delay: Duration,
+    max_delay: Duration,
+    time_to_first_byte_samples: Mutex<TimeToFirstByteSamples>,
+    hedge_delay_us: AtomicU64,
+
+    // Metrics.
+    hedges_fired: CounterWithTime,
+    hedges_won: CounterWithTime,
+}
+
+impl HedgeStore {
+    pub fn new(
+        config: &HedgeStoreConfig,
+        backend: Arc<dyn Store>,
+        hedge_backend: Option<Arc<dyn Store>>,
+    ) -> Result<Self, Error> {
+        let delay_percentile = if config.delay_percentile == 0. {
+            DEFAULT_DELAY_PERCENTILE
+        } else {
+            config.delay_percentile
+        };
+        error_if!(
+            !(0. ..=100.).contains(&delay_percentile),
+            "delay_percentile must be between 0 and 100 in HedgeStore, got {delay_percentile}"
+        );
+        let sample_window = if config.sample_window == 0 {
+            DEFAULT_SAMPLE_WINDOW
+        } else {
+            config.sample_window
+        };
+        let max_delay = Duration::from_millis(if config.max_delay_ms == 0 {
+            DEFAULT_MAX_DELAY_MS
+        } else {
+            config.max_delay_ms
+        });
+        let min_delay = Duration::from_millis(config.min_delay_ms);
+        error_if!(
+            min_delay > max_delay,
+            "min_delay_ms must not be larger than max_delay_ms in HedgeStore"
+        );
+        Ok(Self {
+            backend,
+            hedge_backend,
+            delay_percentile,
+            sample_window,
+            min_delay,
+            max_delay,
+            time_to_first_byte_samples: Mutex::new(TimeToFirstByteSamples {
+                samples: VecDeque::with_capacity(sample_window),
+                new_samples: 0,
+            }),
+            hedge_delay_us: AtomicU64::new(max_delay.as_micros() as u64),
+            hedges_fired: CounterWithTime::default(),
+            hedges_won: CounterWithTime::default(),
+        })
+    }
+
+    fn hedge_delay(&self) -> Duration {
+        Duration::from_micros(self.hedge_delay_us.load(Ordering::Relaxed))
+    }
+
+    fn record_time_to_first_byte(&self, time_to_first_byte: Duration) {
+        let mut recent_samples = {
+            let mut time_to_first_byte_samples = self.time_to_first_byte_samples.lock();
+            let TimeToFirstByteSamples {
+                samples,
+                new_samples,
+            } = &mut *time_to_first_byte_samples;
+            if samples.len() >= self.sample_window {
+                samples.pop_front();
+            }
+            samples.push_back(time_to_first_byte);
+            *new_samples += 1;
+            let first_update = samples.len() == MIN_SAMPLES;
+            if samples.len() < MIN_SAMPLES
+                || (!first_update && *new_samples < SAMPLES_PER_DELAY_UPDATE)
+            {
+                return;
+            }
+            *new_samples = 0;
+            samples.iter().copied().collect::<Vec<_>>()
+        };
+        let index = ((recent_samples.len() - 1) as f32 * self.delay_percentile / 100.).round();
+        let (_, percentile, _) = recent_samples.select_nth_unstable(index as usize);
+        let hedge_delay = (*percentile).clamp(self.min_delay, self.max_delay);
+        self.hedge_delay_us
+            .store(hedge_delay.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+#[async_trait]
+impl Store for HedgeStore {
+    async fn has_with_results(
+        self: Pin<&Self>,
+        digests: &[DigestInfo],
+        results: &mut [Option<usize>],
+    ) -> Result<(), Error> {
+        Pin::new(self.backend.as_ref())
+            .has_with_results(digests, results)
+            .await
+    }
+
+    async fn update(
+        self: Pin<&Self>,
+        digest: DigestInfo,
+        reader: DropCloserReadHalf,
+        size_info: UploadSizeInfo,
+    ) -> Result<(), Error> {
+        Pin::new(self.backend.as_ref())
+            .update(digest, reader, size_info)
+            .await
+    }
+
+    async fn get_part_ref(
+        self: Pin<&Self>,
+        digest: DigestInfo,
+        writer: &mut DropCloserWriteHalf,
+        offset: usize,
+        length: Option<usize>,
+    ) -> Result<(), Error> {
+        let mut primary = ReadAttempt::new(&self.backend, digest, offset, length);
+        let primary_responded = tokio::select! {
+            () = primary.wait_for_response() => true,
+            () = sleep(self.hedge_delay()) => false,
+        };
+        if primary_responded {
+            if !primary.failed() {
+                self.record_time_to_first_byte(primary.start_time.elapsed());
+            }
+            return primary.forward(writer).await;
+        }
+
+        self.hedges_fired.inc();
+        let hedge_backend = self.hedge_backend.as_ref().unwrap_or(&self.backend);
+        let mut hedge = ReadAttempt::new(hedge_backend, digest, offset, length);
+        let mut hedge_won = tokio::select! {
+            () = primary.wait_for_response() => false,
+            () = hedge.wait_for_response() => true,
+        };
+        let (mut winner, mut loser) = if hedge_won {
+            (hedge, primary)
+        } else {
+            (primary, hedge)
+        };
+        if winner.failed() {
+            // The other request may still succeed, so we only fail if it
+            // fails too.
+            loser.wait_for_response().await;
+            if !loser.failed() {
+                std::mem::swap(&mut winner, &mut loser);
+                hedge_won = !hedge_won;
+            }
+        }
+        // The losing request is cancelled by dropping it.
+        drop(loser);
+        if hedge_won {
+            self.hedges_won.inc();
+        }
+        if !winner.failed() {
+            self.record_time_to_first_byte(winner.start_time.elapsed());
+        }
+        winner.forward(writer).await
+    }
+
+    async fn list(
+        self: Pin<&Self>,
+        handler: &mut (dyn FnMut(DigestInfo, usize) + Send),
+    ) -> Result<(), Error> {
+        Pin::new(self.backend.as_ref()).list(handler).await
+    }
+
+    async fn touch(self: Pin<&Self>, digests: &[DigestInfo]) -> Result<(), Error> {
+        if let Some(hedge_backend) = &self.hedge_backend {
+            Pin::new(hedge_backend.as_ref()).touch(digests).await?;
+        }
+        Pin::new(self.backend.as_ref()).touch(digests).await
+    }
+
+    async fn pin(self: Pin<&Self>, digests: &[DigestInfo], ttl: Duration) -> Result<usize, Error> {
+        let mut pinned = 0;
+        if let Some(hedge_backend) = &self.hedge_backend {
+            pinned = Pin::new(hedge_backend.as_ref()).pin(digests, ttl).await?;
+        }
+        Ok(pinned.max(Pin::new(self.backend.as_ref()).pin(digests, ttl).await?))
+    }
+
+    fn inner_store(&self, _digest: Option<DigestInfo>) -> &'_ dyn Store {
+        self
+    }
+
+    fn inner_store_arc(self: Arc<Self>, _digest: Option<DigestInfo>) -> Arc<dyn Store> {
+        self
+    }
+
+    fn as_any<'a>(&'a self) -> &'a (dyn std::any::Any + Sync + Send + 'static) {
+        self
+    }
+
+    fn as_any_arc(self: Arc<Self>) -> Arc<dyn std::any::Any + Sync + Send + 'static> {
+        self
+    }
+
+    fn register_metrics(self: Arc<Self>, registry: &mut Registry) {
+        let backend_registry = registry.sub_registry_with_prefix("backend");
+        self.backend.clone().register_metrics(backend_registry);
+        if let Some(hedge_backend) = &self.hedge_backend {
+            let hedge_backend_registry = registry.sub_registry_with_prefix("hedge_backend");
+            hedge_backend
+                .clone()
+                .register_metrics(hedge_backend_registry);
+        }
+        registry.register_collector(Box::new(Collector::new(&self)));
+    }
+
+    fn register_health(self: Arc<Self>, registry: &mut HealthRegistryBuilder) {
+        self.backend
+            .clone()
+            .register_health(&mut registry.sub_builder("backend".into()));
+        if let Some(hedge_backend) = &self.hedge_backend {
+            hedge_backend
+                .clone()
+                .register_health(&mut registry.sub_builder("hedge_backend".into()));
+        }
+    }
+}
+
+impl MetricsComponent for HedgeStore {
+    fn gather_metrics(&self, c: &mut CollectorState) {
+        c.publish(
+            "hedges_fired_total",
+            &self.hedges_fired,
+            "Number of reads that did not respond in time and were sent a second time",
+        );
+        c.publish(
+            "hedges_won_total",
+            &self.hedges_won,
+            "Number of hedged reads that responded before the original read",
+        );
+        c.publish(
+            "hedge_delay",
+            &self.hedge_delay(),
+            "Current delay before a read is hedged",
+        );
+        c.publish(
+            "delay_percentile",
+            &self.delay_percentile,
+            "Percentile of time-to-first-byte used to compute the hedge delay",
+        );
+    }
+}
+
+default_health_status_indicator!(HedgeStore);
@@ -14,6 +14,7 @@
 
 pub mod ac_utils;
 pub mod cas_utils;
+pub mod circuit_breaker_store;
 pub mod completeness_checking_store;
 pub mod compression_store;
 pub mod dedup_store;
@@ -22,11 +23,15 @@ pub mod existence_cache_store;
 pub mod fast_slow_store;
 pub mod filesystem_store;
 pub mod grpc_store;
+pub mod hedge_store;
 pub mod memory_store;
 pub mod noop_store;
+pub mod quota_store;
 pub mod ref_store;
+pub mod routing_store;
 pub mod s3_store;
 pub mod shard_store;
 pub mod size_partitioning_store;
 pub mod store_manager;
+pub mod tiered_store;
 pub mod verify_store;
@@ -12,20 +12,30 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::borrow::Cow;
 use std::fmt::Debug;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
-use std::sync::Arc;
-use std::time::SystemTime;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use async_trait::async_trait;
 use bytes::{Bytes, BytesMut};
-use nativelink_error::{Code, Error, ResultExt};
+use futures::FutureExt;
+use nativelink_error::{error_if, make_err, Code, Error, ResultExt};
 use nativelink_util::buf_channel::{DropCloserReadHalf, DropCloserWriteHalf};
-use nativelink_util::common::DigestInfo;
-use nativelink_util::evicting_map::{EvictingMap, LenEntry};
-use nativelink_util::health_utils::{default_health_status_indicator, HealthStatusIndicator};
+use nativelink_util::common::{DigestInfo, JoinHandleDropGuard};
+use nativelink_util::evicting_map::{EvictingMap, LenEntry, SerializedLRU};
+use nativelink_util::health_utils::{HealthRegistryBuilder, HealthStatus, HealthStatusIndicator};
 use nativelink_util::metrics_utils::{Collector, CollectorState, MetricsComponent, Registry};
-use nativelink_util::store_trait::{Store, UploadSizeInfo};
+use nativelink_util::shutdown::register_shutdown_hook;
+use nativelink_util::store_trait::{Store, StoreEvictionCallback, UploadSizeInfo};
+use tokio::task::spawn_blocking;
+use tokio::time::interval;
+use tracing::{info, warn};
 
 use crate::cas_utils::is_zero_digest;
 
@@ -50,19 +60,322 @@ impl LenEntry for BytesWrapper {
     }
 }
 
+/// Identifies a `MemoryStore` snapshot file.
+const SNAPSHOT_MAGIC: &[u8; 8] = b"NLMEMSNP";
+
+/// Version of the snapshot file layout, bumped on incompatible changes.
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Default interval between background snapshots, in seconds.
+const DEFAULT_SNAPSHOT_INTERVAL_SECONDS: u32 = 300;
+
+/// Writes a snapshot file while hashing everything written to it.
+struct SnapshotWriter {
+    file: BufWriter<File>,
+    hasher: blake3::Hasher,
+}
+
+impl SnapshotWriter {
+    fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.hasher.update(data);
+        self.file.write_all(data)?;
+        Ok(())
+    }
+}
+
+/// Reads a snapshot file while hashing everything read from it.
+struct SnapshotReader {
+    file: BufReader<File>,
+    hasher: blake3::Hasher,
+    // Bytes of the file not read yet, so lengths read from a corrupt file
+    // are rejected before allocating them.
+    remaining: u64,
+}
+
+impl SnapshotReader {
+    fn read<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut data = [0u8; N];
+        self.file.read_exact(&mut data)?;
+        self.hasher.update(&data);
+        self.remaining = self.remaining.saturating_sub(N as u64);
+        Ok(data)
+    }
+
+    /// Reads `len` bytes of item data, which must leave room for the
+    /// checksum at the end of the file.
+    fn read_data(&mut self, len: u64) -> Result<Vec<u8>, Error> {
+        let available = self.remaining.saturating_sub(blake3::OUT_LEN as u64);
+        error_if!(
+            len > available,
+            "Snapshot item of {len} bytes is larger than the {available} bytes left in the file"
+        );
+        let mut data = vec![0u8; usize::try_from(len)?];
+        self.file.read_exact(&mut data)?;
+        self.hasher.update(&data);
+        self.remaining -= len;
+        Ok(data)
+    }
+
+    fn read_u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.read()?))
+    }
+}
+
+/// Writes the content of the map to `path`. The file layout is (integers are
+/// little endian):
+///  * `SNAPSHOT_MAGIC`, `SNAPSHOT_VERSION` as u32.
+///  * Creation time as u64 unix seconds, `SerializedLRU::anchor_time` as u64
+///    and the number of items as u64.
+///  * For each item, from the most to the least recently used: the packed
+///    hash, size_bytes as i64, seconds_since_anchor as i32, the length of the
+///    data as u64 and the data.
+///  * The blake3 hash of everything above.
+///
+/// The file is written next to `path` and renamed, so a crash while writing
+/// never leaves a partial snapshot behind.
+fn write_snapshot(
+    path: &Path,
+    serialized_lru: &SerializedLRU,
+    items: &[BytesWrapper],
+) -> Result<(), Error> {
+    let temp_path = path.with_extension("tmp");
+    let mut writer = SnapshotWriter {
+        file: BufWriter::new(File::create(&temp_path)?),
+        hasher: blake3::Hasher::new(),
+    };
+    let created_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    writer.write(SNAPSHOT_MAGIC)?;
+    writer.write(&SNAPSHOT_VERSION.to_le_bytes())?;
+    writer.write(&created_at.to_le_bytes())?;
+    writer.write(&serialized_lru.anchor_time.to_le_bytes())?;
+    writer.write(&(items.len() as u64).to_le_bytes())?;
+    for ((digest, seconds_since_anchor), data) in serialized_lru.data.iter().zip(items) {
+        writer.write(&digest.packed_hash)?;
+        writer.write(&digest.size_bytes.to_le_bytes())?;
+        writer.write(&seconds_since_anchor.to_le_bytes())?;
+        writer.write(&(data.0.len() as u64).to_le_bytes())?;
+        writer.write(&data.0)?;
+    }
+    let SnapshotWriter { mut file, hasher } = writer;
+    file.write_all(hasher.finalize().as_bytes())?;
+    let file = file.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// Reads a snapshot written by `write_snapshot()`. Returns `None` if there is
+/// no snapshot at `path`. Snapshots older than `max_age` are rejected, as
+/// well as snapshots that don't match their checksum.
+fn read_snapshot(
+    path: &Path,
+    max_age: Option<Duration>,
+) -> Result<Option<(SerializedLRU, Vec<BytesWrapper>)>, Error> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut reader = SnapshotReader {
+        remaining: file.metadata()?.len(),
+        file: BufReader::new(file),
+        hasher: blake3::Hasher::new(),
+    };
+    error_if!(
+        &reader.read::<8>()? != SNAPSHOT_MAGIC,
+        "File is not a MemoryStore snapshot"
+    );
+    let version = u32::from_le_bytes(reader.read()?);
+    error_if!(
+        version != SNAPSHOT_VERSION,
+        "Unsupported snapshot version {version}, expected {SNAPSHOT_VERSION}"
+    );
+    let created_at = SystemTime::UNIX_EPOCH + Duration::from_secs(reader.read_u64()?);
+    if let Some(max_age) = max_age {
+        let age = SystemTime::now()
+            .duration_since(created_at)
+            .unwrap_or_default();
+        error_if!(
+            age > max_age,
+            "Snapshot is {}s old, which is older than the max age of {}s",
+            age.as_secs(),
+            max_age.as_secs()
+        );
+    }
+    let anchor_time = reader.read_u64()?;
+    let item_count = reader.read_u64()?;
+    let mut serialized_lru = SerializedLRU {
+        data: Vec::new(),
+        anchor_time,
+    };
+    let mut items = Vec::new();
+    for _ in 0..item_count {
+        let packed_hash = reader.read()?;
+        let size_bytes = i64::from_le_bytes(reader.read()?);
+        let seconds_since_anchor = i32::from_le_bytes(reader.read()?);
+        let data_len = reader.read_u64()?;
+        let data = reader.read_data(data_len)?;
+        serialized_lru.data.push((
+            DigestInfo::new(packed_hash, size_bytes),
+            seconds_since_anchor,
+        ));
+        items.push(BytesWrapper(Bytes::from(data)));
+    }
+    let expected_checksum = reader.hasher.finalize();
+    let mut checksum = [0u8; blake3::OUT_LEN];
+    reader.file.read_exact(&mut checksum)?;
+    error_if!(
+        expected_checksum != checksum,
+        "Snapshot checksum does not match its content"
+    );
+    Ok(Some((serialized_lru, items)))
+}
+
+/// Saves the content of a `MemoryStore` to a snapshot file and loads it back.
+struct Snapshotter {
+    path: PathBuf,
+    max_age: Option<Duration>,
+    evicting_map: Arc<EvictingMap<BytesWrapper, SystemTime>>,
+    // Set once the snapshot was loaded (or failed to load). No snapshot is
+    // written before that, so a slow load never loses the previous one.
+    loaded: AtomicBool,
+    // Held while writing, so background and shutdown snapshots don't
+    // write the same file at the same time.
+    write_lock: tokio::sync::Mutex<()>,
+}
+
+impl Snapshotter {
+    async fn load(&self) {
+        let path = self.path.clone();
+        let max_age = self.max_age;
+        let result = spawn_blocking(move || read_snapshot(&path, max_age))
+            .await
+            .map_err(Error::from)
+            .and_then(|result| result);
+        match result {
+            Ok(Some((serialized_lru, items))) => {
+                let item_count = items.len();
+                self.evicting_map.restore_items(serialized_lru, items).await;
+                info!(
+                    "Loaded {item_count} items from MemoryStore snapshot {:?}",
+                    self.path
+                );
+            }
+            Ok(None) => info!("No MemoryStore snapshot found at {:?}", self.path),
+            Err(e) => warn!("Ignoring MemoryStore snapshot {:?} : {e:?}", self.path),
+        }
+        self.loaded.store(true, Ordering::Release);
+    }
+
+    async fn write(&self) -> Result<(), Error> {
+        if !self.loaded.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        let _write_guard = self.write_lock.lock().await;
+        let (serialized_lru, items) = self.evicting_map.build_lru_index_with_items().await;
+        let path = self.path.clone();
+        spawn_blocking(move || write_snapshot(&path, &serialized_lru, &items))
+            .await?
+            .err_tip(|| format!("Failed to write MemoryStore snapshot {:?}", self.path))
+    }
+}
+
 pub struct MemoryStore {
-    evicting_map: EvictingMap<BytesWrapper, SystemTime>,
+    evicting_map: Arc<EvictingMap<BytesWrapper, SystemTime>>,
+    snapshotter: Option<Arc<Snapshotter>>,
+    _snapshot_task: Option<JoinHandleDropGuard<()>>,
 }
 
 impl MemoryStore {
+    /// Creates the store. If a snapshot is configured this must be called
+    /// from within a tokio runtime, as the snapshot is loaded in the
+    /// background.
     pub fn new(config: &nativelink_config::stores::MemoryStore) -> Self {
         let empty_policy = nativelink_config::stores::EvictionPolicy::default();
         let eviction_policy = config.eviction_policy.as_ref().unwrap_or(&empty_policy);
+        let evicting_map = Arc::new(EvictingMap::new(eviction_policy, SystemTime::now()));
+        let Some(snapshot_config) = &config.snapshot else {
+            return MemoryStore {
+                evicting_map,
+                snapshotter: None,
+                _snapshot_task: None,
+            };
+        };
+
+        let snapshotter = Arc::new(Snapshotter {
+            path: PathBuf::from(&snapshot_config.path),
+            max_age: if snapshot_config.max_age_seconds == 0 {
+                None
+            } else {
+                Some(Duration::from_secs(u64::from(
+                    snapshot_config.max_age_seconds,
+                )))
+            },
+            evicting_map: evicting_map.clone(),
+            loaded: AtomicBool::new(false),
+            write_lock: tokio::sync::Mutex::new(()),
+        });
+        let mut interval_seconds = snapshot_config.interval_seconds;
+        if interval_seconds == 0 {
+            interval_seconds = DEFAULT_SNAPSHOT_INTERVAL_SECONDS;
+        }
+        let snapshot_task = JoinHandleDropGuard::new(tokio::spawn({
+            let snapshotter = snapshotter.clone();
+            async move {
+                snapshotter.load().await;
+                let mut interval = interval(Duration::from_secs(u64::from(interval_seconds)));
+                // The first tick completes immediately.
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = snapshotter.write().await {
+                        warn!("{e:?}");
+                    }
+                }
+            }
+        }));
+        let weak_snapshotter = Arc::downgrade(&snapshotter);
+        register_shutdown_hook(move || {
+            let weak_snapshotter: Weak<Snapshotter> = weak_snapshotter.clone();
+            async move {
+                let Some(snapshotter) = weak_snapshotter.upgrade() else {
+                    return;
+                };
+                match snapshotter.write().await {
+                    Ok(()) => info!("Wrote MemoryStore snapshot {:?}", snapshotter.path),
+                    Err(e) => warn!("{e:?}"),
+                }
+            }
+            .boxed()
+        });
         MemoryStore {
-            evicting_map: EvictingMap::new(eviction_policy, SystemTime::now()),
+            evicting_map,
+            snapshotter: Some(snapshotter),
+            _snapshot_task: Some(snapshot_task),
+        }
+    }
+
+    /// Writes a snapshot of the store right away. Does nothing if snapshots
+    /// are not configured or the previous snapshot is still being loaded.
+    pub async fn write_snapshot(&self) -> Result<(), Error> {
+        match &self.snapshotter {
+            Some(snapshotter) => snapshotter.write().await,
+            None => Ok(()),
         }
     }
 
+    /// Returns true once the snapshot configured for the store was loaded, or
+    /// if no snapshot is configured.
+    pub fn is_snapshot_loaded(&self) -> bool {
+        self.snapshotter.as_ref().map_or(true, |snapshotter| {
+            snapshotter.loaded.load(Ordering::Acquire)
+        })
+    }
+
     /// Returns the number of key-value pairs that are currently in the the cache.
     /// Function is not for production code paths.
     pub async fn len_for_test(&self) -> usize {
@@ -163,6 +476,30 @@ impl Store for MemoryStore {
         Ok(())
     }
 
+    async fn remove(self: Pin<&Self>, digest: DigestInfo) -> Result<bool, Error> {
+        Ok(self.evicting_map.remove(&digest).await)
+    }
+
+    async fn list(
+        self: Pin<&Self>,
+        handler: &mut (dyn FnMut(DigestInfo, usize) + Send),
+    ) -> Result<(), Error> {
+        self.evicting_map
+            .for_each_item(|digest, size| handler(*digest, size))
+            .await;
+        Ok(())
+    }
+
+    async fn touch(self: Pin<&Self>, digests: &[DigestInfo]) -> Result<(), Error> {
+        self.evicting_map.touch(digests).await;
+        Ok(())
+    }
+
+    async fn pin(self: Pin<&Self>, digests: &[DigestInfo], ttl: Duration) -> Result<usize, Error> {
+        let pinned = self.evicting_map.pin(digests, ttl).await;
+        Ok(pinned.into_iter().filter(|pinned| *pinned).count())
+    }
+
     fn inner_store(&self, _digest: Option<DigestInfo>) -> &'_ dyn Store {
         self
     }
@@ -179,15 +516,57 @@ impl Store for MemoryStore {
         self
     }
 
+    fn register_eviction_callback(
+        self: Arc<Self>,
+        callback: StoreEvictionCallback,
+    ) -> Result<(), Error> {
+        let registered = self.evicting_map.set_eviction_callback(Box::new(
+            move |digest, data: &BytesWrapper| callback(*digest, data.0.clone()),
+        ));
+        if !registered {
+            return Err(make_err!(
+                Code::AlreadyExists,
+                "MemoryStore already has an eviction callback registered"
+            ));
+        }
+        Ok(())
+    }
+
+    async fn set_eviction_policy(
+        &self,
+        eviction_policy: &nativelink_config::stores::EvictionPolicy,
+    ) -> Result<(), Error> {
+        self.evicting_map.set_eviction_policy(eviction_policy).await;
+        Ok(())
+    }
+
     fn register_metrics(self: Arc<Self>, registry: &mut Registry) {
         registry.register_collector(Box::new(Collector::new(&self)));
     }
+
+    fn register_health(self: Arc<Self>, registry: &mut HealthRegistryBuilder) {
+        if self.snapshotter.is_some() {
+            registry.register_indicator(self);
+        }
+    }
 }
 
 impl MetricsComponent for MemoryStore {
     fn gather_metrics(&self, c: &mut CollectorState) {
-        c.publish("evicting_map", &self.evicting_map, "");
+        c.publish("evicting_map", self.evicting_map.as_ref(), "");
     }
 }
 
-default_health_status_indicator!(MemoryStore);
+#[async_trait]
+impl HealthStatusIndicator for MemoryStore {
+    fn get_name(&self) -> &'static str {
+        "MemoryStore"
+    }
+
+    async fn check_health(&self, namespace: Cow<'static, str>) -> HealthStatus {
+        if !self.is_snapshot_loaded() {
+            return HealthStatus::new_initializing(self, "Loading snapshot".into());
+        }
+        Store::check_health(Pin::new(self), namespace).await
+    }
+}
@@ -0,0 +1,525 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashMap};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use nativelink_config::stores::QuotaStore as QuotaStoreConfig;
+use nativelink_error::{make_err, Code, Error, ResultExt};
+use nativelink_util::buf_channel::{DropCloserReadHalf, DropCloserWriteHalf};
+use nativelink_util::common::DigestInfo;
+use nativelink_util::health_utils::{
+    default_health_status_indicator, HealthRegistryBuilder, HealthStatusIndicator,
+};
+use nativelink_util::metrics_utils::{
+    Collector, CollectorState, Counter, MetricsComponent, Registry,
+};
+use nativelink_util::store_trait::{Store, UploadSizeInfo};
+use parking_lot::Mutex;
+use serde::Serialize;
+use tracing::warn;
+
+/// Maximum number of objects checked in the backend when an upload would
+/// exceed the hard quota, so a rejection doesn't query every tracked object.
+const FORGET_MISSING_BATCH_SIZE: usize = 1024;
+
+/// Counts how many quota stores account for each object in a backend, so
+/// objects shared between quota stores are only removed from the backend
+/// once no quota store accounts for them anymore. Every quota store writing
+/// to the same backend must be given the same `QuotaOwners`.
+#[derive(Default)]
+pub struct QuotaOwners {
+    counts: Mutex<HashMap<DigestInfo, usize>>,
+}
+
+impl QuotaOwners {
+    fn acquire(&self, digest: DigestInfo) {
+        *self.counts.lock().entry(digest).or_default() += 1;
+    }
+
+    /// Returns the number of quota stores still accounting for `digest`.
+    fn release(&self, digest: &DigestInfo) -> usize {
+        let mut counts = self.counts.lock();
+        let Some(count) = counts.get_mut(digest) else {
+            return 0;
+        };
+        *count -= 1;
+        let count = *count;
+        if count == 0 {
+            counts.remove(digest);
+        }
+        count
+    }
+}
+
+/// Usage of a quota store as reported by the admin API.
+#[derive(Serialize)]
+pub struct QuotaUsage {
+    pub used_bytes: u64,
+    pub reserved_bytes: u64,
+    pub tracked_objects: usize,
+    pub soft_quota_bytes: Option<u64>,
+    pub hard_quota_bytes: Option<u64>,
+}
+
+struct QuotaEntry {
+    size: u64,
+    sequence: u64,
+}
+
+struct QuotaState {
+    entries: HashMap<DigestInfo, QuotaEntry>,
+    /// Digests of `entries` ordered from least to most recently used.
+    lru: BTreeMap<u64, DigestInfo>,
+    next_sequence: u64,
+    /// Sum of the sizes of all `entries`.
+    used_bytes: u64,
+    /// Sum of the sizes of all uploads in progress.
+    reserved_bytes: u64,
+    /// Sequence of the next entry `forget_missing` checks.
+    forget_cursor: u64,
+    /// Shared with the other quota stores writing to the same backend.
+    owners: Arc<QuotaOwners>,
+}
+
+impl QuotaState {
+    fn insert_with_sequence(&mut self, digest: DigestInfo, size: u64, sequence: u64) {
+        self.remove(&digest);
+        self.entries.insert(digest, QuotaEntry { size, sequence });
+        self.lru.insert(sequence, digest);
+        self.used_bytes += size;
+        self.owners.acquire(digest);
+    }
+
+    fn insert(&mut self, digest: DigestInfo, size: u64) {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.insert_with_sequence(digest, size, sequence);
+    }
+
+    fn remove(&mut self, digest: &DigestInfo) -> Option<QuotaEntry> {
+        let entry = self.entries.remove(digest)?;
+        self.lru.remove(&entry.sequence);
+        self.used_bytes -= entry.size;
+        self.release_owner(digest);
+        Some(entry)
+    }
+
+    /// Returns whether other quota stores still account for `digest`.
+    fn release_owner(&self, digest: &DigestInfo) -> bool {
+        self.owners.release(digest) > 0
+    }
+
+    fn touch(&mut self, digest: &DigestInfo) {
+        if let Some(entry) = self.entries.get_mut(digest) {
+            self.lru.remove(&entry.sequence);
+            entry.sequence = self.next_sequence;
+            self.lru.insert(entry.sequence, *digest);
+            self.next_sequence += 1;
+        }
+    }
+
+    /// Removes and returns the least recently used entry if more than
+    /// `limit` bytes are in use, along with whether other quota stores
+    /// still account for it.
+    fn pop_over_limit(&mut self, limit: u64) -> Option<(DigestInfo, QuotaEntry, bool)> {
+        if self.used_bytes <= limit {
+            return None;
+        }
+        let (_, digest) = self.lru.pop_first()?;
+        let entry = self.entries.remove(&digest)?;
+        self.used_bytes -= entry.size;
+        let shared = self.release_owner(&digest);
+        Some((digest, entry, shared))
+    }
+
+    /// Returns the next entries `forget_missing` should check, going from
+    /// least to most recently used and starting over once all were checked.
+    fn next_forget_batch(&mut self) -> Vec<DigestInfo> {
+        let batch: Vec<(u64, DigestInfo)> = self
+            .lru
+            .range(self.forget_cursor..)
+            .take(FORGET_MISSING_BATCH_SIZE)
+            .map(|(sequence, digest)| (*sequence, *digest))
+            .collect();
+        self.forget_cursor = match batch.last() {
+            Some((sequence, _)) if batch.len() == FORGET_MISSING_BATCH_SIZE => sequence + 1,
+            _ => 0,
+        };
+        batch.into_iter().map(|(_, digest)| digest).collect()
+    }
+}
+
+pub struct QuotaStore {
+    backend: Arc<dyn Store>,
+    soft_quota_bytes: Option<u64>,
+    hard_quota_bytes: Option<u64>,
+    state: Mutex<QuotaState>,
+    /// Set once the backend reported it can't remove objects, after which
+    /// the soft quota is no longer enforced.
+    removal_unsupported: AtomicBool,
+
+    // Metrics.
+    evicted_items: Counter,
+    evicted_bytes: Counter,
+    unattributed_items: Counter,
+    rejected_updates: Counter,
+}
+
+/// Bytes reserved for an upload in progress. The reservation is released
+/// when dropped.
+struct Reservation<'a> {
+    store: &'a QuotaStore,
+    size: u64,
+}
+
+impl<'a> Reservation<'a> {
+    /// Releases the reservation while the state is already locked.
+    fn release(mut self, state: &mut QuotaState) {
+        state.reserved_bytes -= self.size;
+        self.size = 0;
+    }
+}
+
+impl<'a> Drop for Reservation<'a> {
+    fn drop(&mut self) {
+        if self.size != 0 {
+            self.store.state.lock().reserved_bytes -= self.size;
+        }
+    }
+}
+
+impl QuotaStore {
+    pub fn new(
+        config: &QuotaStoreConfig,
+        backend: Arc<dyn Store>,
+        owners: Arc<QuotaOwners>,
+    ) -> Self {
+        Self {
+            backend,
+            soft_quota_bytes: (config.soft_quota_bytes != 0).then_some(config.soft_quota_bytes),
+            hard_quota_bytes: (config.hard_quota_bytes != 0).then_some(config.hard_quota_bytes),
+            state: Mutex::new(QuotaState {
+                entries: HashMap::new(),
+                lru: BTreeMap::new(),
+                next_sequence: 0,
+                used_bytes: 0,
+                reserved_bytes: 0,
+                forget_cursor: 0,
+                owners,
+            }),
+            removal_unsupported: AtomicBool::new(false),
+            evicted_items: Counter::default(),
+            evicted_bytes: Counter::default(),
+            unattributed_items: Counter::default(),
+            rejected_updates: Counter::default(),
+        }
+    }
+
+    /// Number of bytes used by the objects written through this store.
+    pub fn used_bytes(&self) -> u64 {
+        self.state.lock().used_bytes
+    }
+
+    /// Number of bytes reserved by uploads in progress.
+    pub fn reserved_bytes(&self) -> u64 {
+        self.state.lock().reserved_bytes
+    }
+
+    pub fn usage(&self) -> QuotaUsage {
+        let state = self.state.lock();
+        QuotaUsage {
+            used_bytes: state.used_bytes,
+            reserved_bytes: state.reserved_bytes,
+            tracked_objects: state.entries.len(),
+            soft_quota_bytes: self.soft_quota_bytes,
+            hard_quota_bytes: self.hard_quota_bytes,
+        }
+    }
+
+    pub fn soft_quota_bytes(&self) -> Option<u64> {
+        self.soft_quota_bytes
+    }
+
+    pub fn hard_quota_bytes(&self) -> Option<u64> {
+        self.hard_quota_bytes
+    }
+
+    fn try_reserve(&self, digest: &DigestInfo, size: u64) -> Option<Reservation<'_>> {
+        let mut state = self.state.lock();
+        if let Some(hard_quota_bytes) = self.hard_quota_bytes {
+            let replaced_bytes = state.entries.get(digest).map_or(0, |entry| entry.size);
+            let needed_bytes =
+                (state.used_bytes + state.reserved_bytes + size).saturating_sub(replaced_bytes);
+            if needed_bytes > hard_quota_bytes {
+                return None;
+            }
+        }
+        state.reserved_bytes += size;
+        Some(Reservation { store: self, size })
+    }
+
+    /// Stops tracking objects the backend no longer has (ie: because it
+    /// evicted them on its own). Only checks a batch of objects per call.
+    async fn forget_missing(&self) -> Result<(), Error> {
+        let digests = self.state.lock().next_forget_batch();
+        let results = Pin::new(self.backend.as_ref())
+            .has_many(&digests)
+            .await
+            .err_tip(|| "In QuotaStore::forget_missing")?;
+        let mut state = self.state.lock();
+        for (digest, result) in digests.iter().zip(results) {
+            if result.is_none() {
+                state.remove(digest);
+            }
+        }
+        Ok(())
+    }
+
+    async fn reserve(&self, digest: &DigestInfo, size: u64) -> Result<Reservation<'_>, Error> {
+        if let Some(reservation) = self.try_reserve(digest, size) {
+            return Ok(reservation);
+        }
+        self.forget_missing().await?;
+        if let Some(reservation) = self.try_reserve(digest, size) {
+            return Ok(reservation);
+        }
+        self.rejected_updates.inc();
+        Err(make_err!(
+            Code::ResourceExhausted,
+            "Uploading {size} bytes would exceed the hard quota of {} bytes in QuotaStore",
+            self.hard_quota_bytes.unwrap_or_default()
+        ))
+    }
+
+    /// Removes the least recently used objects from the backend until the
+    /// soft quota is met again.
+    async fn enforce_soft_quota(&self) {
+        let Some(soft_quota_bytes) = self.soft_quota_bytes else {
+            return;
+        };
+        while !self.removal_unsupported.load(Ordering::Relaxed) {
+            let Some((digest, entry, shared)) = self.state.lock().pop_over_limit(soft_quota_bytes)
+            else {
+                return;
+            };
+            if shared {
+                // Other quota stores still account for it, so it only stops
+                // counting against this one.
+                self.unattributed_items.inc();
+                continue;
+            }
+            match Pin::new(self.backend.as_ref()).remove(digest).await {
+                Ok(_) => {
+                    self.evicted_items.inc();
+                    self.evicted_bytes.add(entry.size);
+                }
+                Err(err) => {
+                    if err.code == Code::Unimplemented
+                        && !self.removal_unsupported.swap(true, Ordering::Relaxed)
+                    {
+                        warn!("Backend of QuotaStore can't remove objects, soft quota is not enforced : {err:?}");
+                    } else if err.code != Code::Unimplemented {
+                        warn!("Failed to remove {digest:?} from backend of QuotaStore : {err:?}");
+                    }
+                    let mut state = self.state.lock();
+                    if !state.entries.contains_key(&digest) {
+                        state.insert_with_sequence(digest, entry.size, entry.sequence);
+                    }
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Store for QuotaStore {
+    async fn has_with_results(
+        self: Pin<&Self>,
+        digests: &[DigestInfo],
+        results: &mut [Option<usize>],
+    ) -> Result<(), Error> {
+        Pin::new(self.backend.as_ref())
+            .has_with_results(digests, results)
+            .await?;
+        let mut state = self.state.lock();
+        for (digest, result) in digests.iter().zip(results.iter()) {
+            if result.is_some() {
+                state.touch(digest);
+            } else {
+                state.remove(digest);
+            }
+        }
+        Ok(())
+    }
+
+    async fn update(
+        self: Pin<&Self>,
+        digest: DigestInfo,
+        reader: DropCloserReadHalf,
+        size_info: UploadSizeInfo,
+    ) -> Result<(), Error> {
+        let (UploadSizeInfo::ExactSize(size) | UploadSizeInfo::MaxSize(size)) = size_info;
+        let reservation = self.reserve(&digest, size as u64).await?;
+        Pin::new(self.backend.as_ref())
+            .update(digest, reader, size_info)
+            .await?;
+        let stored_size = match size_info {
+            UploadSizeInfo::ExactSize(size) => Some(size),
+            UploadSizeInfo::MaxSize(_) => Pin::new(self.backend.as_ref())
+                .has(digest)
+                .await
+                .err_tip(|| "Getting size of uploaded object in QuotaStore::update")?,
+        };
+        {
+            let mut state = self.state.lock();
+            reservation.release(&mut state);
+            if let Some(stored_size) = stored_size {
+                state.insert(digest, stored_size as u64);
+            }
+        }
+        self.enforce_soft_quota().await;
+        Ok(())
+    }
+
+    async fn get_part_ref(
+        self: Pin<&Self>,
+        digest: DigestInfo,
+        writer: &mut DropCloserWriteHalf,
+        offset: usize,
+        length: Option<usize>,
+    ) -> Result<(), Error> {
+        let result = Pin::new(self.backend.as_ref())
+            .get_part_ref(digest, writer, offset, length)
+            .await;
+        match &result {
+            Ok(()) => self.state.lock().touch(&digest),
+            Err(err) if err.code == Code::NotFound => {
+                self.state.lock().remove(&digest);
+            }
+            Err(_) => {}
+        }
+        result
+    }
+
+    async fn remove(self: Pin<&Self>, digest: DigestInfo) -> Result<bool, Error> {
+        let removed = Pin::new(self.backend.as_ref()).remove(digest).await?;
+        self.state.lock().remove(&digest);
+        Ok(removed)
+    }
+
+    async fn list(
+        self: Pin<&Self>,
+        handler: &mut (dyn FnMut(DigestInfo, usize) + Send),
+    ) -> Result<(), Error> {
+        Pin::new(self.backend.as_ref()).list(handler).await
+    }
+
+    async fn touch(self: Pin<&Self>, digests: &[DigestInfo]) -> Result<(), Error> {
+        Pin::new(self.backend.as_ref()).touch(digests).await
+    }
+
+    async fn pin(self: Pin<&Self>, digests: &[DigestInfo], ttl: Duration) -> Result<usize, Error> {
+        Pin::new(self.backend.as_ref()).pin(digests, ttl).await
+    }
+
+    fn inner_store(&self, _digest: Option<DigestInfo>) -> &'_ dyn Store {
+        self
+    }
+
+    fn inner_store_arc(self: Arc<Self>, _digest: Option<DigestInfo>) -> Arc<dyn Store> {
+        self
+    }
+
+    fn as_any<'a>(&'a self) -> &'a (dyn std::any::Any + Sync + Send + 'static) {
+        self
+    }
+
+    fn as_any_arc(self: Arc<Self>) -> Arc<dyn std::any::Any + Sync + Send + 'static> {
+        self
+    }
+
+    fn register_metrics(self: Arc<Self>, registry: &mut Registry) {
+        let backend_registry = registry.sub_registry_with_prefix("backend");
+        self.backend.clone().register_metrics(backend_registry);
+        registry.register_collector(Box::new(Collector::new(&self)));
+    }
+
+    fn register_health(self: Arc<Self>, registry: &mut HealthRegistryBuilder) {
+        let mut backend_registry = registry.sub_builder("backend".into());
+        self.backend.clone().register_health(&mut backend_registry);
+        registry.register_indicator(self);
+    }
+}
+
+impl MetricsComponent for QuotaStore {
+    fn gather_metrics(&self, c: &mut CollectorState) {
+        let (used_bytes, reserved_bytes, tracked_objects) = {
+            let state = self.state.lock();
+            (state.used_bytes, state.reserved_bytes, state.entries.len())
+        };
+        c.publish(
+            "used_bytes",
+            &used_bytes,
+            "Number of bytes used by the objects written through this store",
+        );
+        c.publish(
+            "reserved_bytes",
+            &reserved_bytes,
+            "Number of bytes reserved by uploads in progress",
+        );
+        c.publish(
+            "tracked_objects",
+            &tracked_objects,
+            "Number of objects written through this store that are tracked",
+        );
+        c.publish(
+            "soft_quota_bytes",
+            &self.soft_quota_bytes.unwrap_or_default(),
+            "Soft quota in bytes, zero meaning no soft quota",
+        );
+        c.publish(
+            "hard_quota_bytes",
+            &self.hard_quota_bytes.unwrap_or_default(),
+            "Hard quota in bytes, zero meaning no hard quota",
+        );
+        c.publish(
+            "evicted_items_total",
+            &self.evicted_items,
+            "Number of objects removed from the backend to meet the soft quota",
+        );
+        c.publish(
+            "evicted_bytes_total",
+            &self.evicted_bytes,
+            "Number of bytes removed from the backend to meet the soft quota",
+        );
+        c.publish(
+            "unattributed_items_total",
+            &self.unattributed_items,
+            "Number of objects that stopped counting against the soft quota without being removed, because other quota stores also use them",
+        );
+        c.publish(
+            "rejected_updates_total",
+            &self.rejected_updates,
+            "Number of uploads rejected because they would exceed the hard quota",
+        );
+    }
+}
+
+default_health_status_indicator!(QuotaStore);
@@ -15,6 +15,7 @@
 use std::cell::UnsafeCell;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
 
 use async_trait::async_trait;
 use nativelink_error::{make_err, make_input_err, Code, Error, ResultExt};
@@ -136,6 +137,21 @@ impl Store for RefStore {
             .await
     }
 
+    async fn list(
+        self: Pin<&Self>,
+        handler: &mut (dyn FnMut(DigestInfo, usize) + Send),
+    ) -> Result<(), Error> {
+        Pin::new(self.get_store()?.as_ref()).list(handler).await
+    }
+
+    async fn touch(self: Pin<&Self>, digests: &[DigestInfo]) -> Result<(), Error> {
+        Pin::new(self.get_store()?.as_ref()).touch(digests).await
+    }
+
+    async fn pin(self: Pin<&Self>, digests: &[DigestInfo], ttl: Duration) -> Result<usize, Error> {
+        Pin::new(self.get_store()?.as_ref()).pin(digests, ttl).await
+    }
+
     fn inner_store(&self, digest: Option<DigestInfo>) -> &'_ dyn Store {
         match self.get_store() {
             Ok(store) => store.inner_store(digest),
@@ -0,0 +1,390 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::future;
+use futures::stream::{FuturesUnordered, TryStreamExt};
+use nativelink_config::stores::{EvictionPolicy, RoutingAccess, RoutingRule};
+use nativelink_error::{error_if, make_input_err, Error, ResultExt};
+use nativelink_util::buf_channel::{DropCloserReadHalf, DropCloserWriteHalf};
+use nativelink_util::common::DigestInfo;
+use nativelink_util::digest_hasher::{default_digest_hasher_func, DigestHasherFunc};
+use nativelink_util::health_utils::{
+    default_health_status_indicator, HealthRegistryBuilder, HealthStatusIndicator,
+};
+use nativelink_util::metrics_utils::Registry;
+use nativelink_util::request_context::{RequestContext, StoreAccess};
+use nativelink_util::store_trait::{Store, StoreEvictionCallback, UploadSizeInfo};
+
+/// Conditions of a `RoutingRule` in a form that is quick to check.
+struct Conditions {
+    min_size: i64,
+    max_size: Option<i64>,
+    digest_functions: Vec<DigestHasherFunc>,
+    instance_names: Vec<String>,
+    access: Option<StoreAccess>,
+    digest_prefixes: Vec<String>,
+}
+
+impl Conditions {
+    fn new(rule: &RoutingRule) -> Result<Self, Error> {
+        let digest_prefixes: Vec<String> = rule
+            .digest_prefixes
+            .iter()
+            .map(|prefix| prefix.to_ascii_lowercase())
+            .collect();
+        for prefix in &digest_prefixes {
+            error_if!(
+                prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_hexdigit()),
+                "RoutingStore digest prefix '{prefix}' is not a hex string"
+            );
+        }
+        let to_i64 = |size: u64| {
+            i64::try_from(size).map_err(|_| make_input_err!("RoutingStore size {size} is too big"))
+        };
+        Ok(Self {
+            min_size: to_i64(rule.min_size)?,
+            max_size: if rule.max_size == 0 {
+                None
+            } else {
+                Some(to_i64(rule.max_size)?)
+            },
+            digest_functions: rule
+                .digest_functions
+                .iter()
+                .map(|digest_function| DigestHasherFunc::from(*digest_function))
+                .collect(),
+            instance_names: rule.instance_names.clone(),
+            access: rule.access.map(|access| match access {
+                RoutingAccess::ac => StoreAccess::Ac,
+                RoutingAccess::cas => StoreAccess::Cas,
+            }),
+            digest_prefixes,
+        })
+    }
+
+    fn matches(&self, digest: &DigestInfo, context: Option<&RequestContext>) -> bool {
+        if digest.size_bytes < self.min_size {
+            return false;
+        }
+        if self
+            .max_size
+            .is_some_and(|max_size| digest.size_bytes >= max_size)
+        {
+            return false;
+        }
+        if !self.digest_functions.is_empty() {
+            let digest_function = context.map_or_else(default_digest_hasher_func, |context| {
+                context.digest_function
+            });
+            if !self.digest_functions.contains(&digest_function) {
+                return false;
+            }
+        }
+        if !self.instance_names.is_empty()
+            && !context.is_some_and(|context| self.instance_names.contains(&context.instance_name))
+        {
+            return false;
+        }
+        if let Some(access) = self.access {
+            if !context.is_some_and(|context| context.access == access) {
+                return false;
+            }
+        }
+        if !self.digest_prefixes.is_empty() {
+            let hash = digest.hash_str();
+            if !self
+                .digest_prefixes
+                .iter()
+                .any(|prefix| hash.starts_with(prefix))
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+pub struct RoutingStore {
+    rules: Vec<Conditions>,
+    // Store of each rule followed by the default store.
+    stores: Vec<Arc<dyn Store>>,
+}
+
+impl RoutingStore {
+    pub fn new(
+        config: &nativelink_config::stores::RoutingStore,
+        rule_stores: Vec<Arc<dyn Store>>,
+        default_store: Arc<dyn Store>,
+    ) -> Result<Self, Error> {
+        error_if!(
+            config.rules.len() != rule_stores.len(),
+            "Config rules do not match stores length"
+        );
+        let rules = config
+            .rules
+            .iter()
+            .map(Conditions::new)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut stores = rule_stores;
+        stores.push(default_store);
+        Ok(Self { rules, stores })
+    }
+
+    fn get_store_index(&self, digest: &DigestInfo) -> usize {
+        RequestContext::with_current(|context| {
+            self.rules
+                .iter()
+                .position(|rule| rule.matches(digest, context))
+                .unwrap_or(self.rules.len())
+        })
+    }
+
+    fn get_store(&self, digest: &DigestInfo) -> Pin<&dyn Store> {
+        Pin::new(self.stores[self.get_store_index(digest)].as_ref())
+    }
+
+    /// Returns every store data can be routed to once. `ref_store`s are
+    /// resolved, so rules sending data to the same store share it.
+    fn unique_stores(&self) -> Vec<Arc<dyn Store>> {
+        let mut unique_stores: Vec<Arc<dyn Store>> = Vec::with_capacity(self.stores.len());
+        for store in &self.stores {
+            let store = store.clone().inner_store_arc(None);
+            if !unique_stores
+                .iter()
+                .any(|unique_store| Arc::ptr_eq(unique_store, &store))
+            {
+                unique_stores.push(store);
+            }
+        }
+        unique_stores
+    }
+
+    /// Groups `digests` by the index of the store they are routed to.
+    fn digests_for_stores(&self, digests: &[DigestInfo]) -> Vec<Vec<DigestInfo>> {
+        let mut digests_for_store = vec![Vec::new(); self.stores.len()];
+        for digest in digests {
+            digests_for_store[self.get_store_index(digest)].push(*digest);
+        }
+        digests_for_store
+    }
+}
+
+#[async_trait]
+impl Store for RoutingStore {
+    async fn has_with_results(
+        self: Pin<&Self>,
+        digests: &[DigestInfo],
+        results: &mut [Option<usize>],
+    ) -> Result<(), Error> {
+        if digests.len() == 1 {
+            // Hot path: It is very common to lookup only one digest.
+            return self
+                .get_store(&digests[0])
+                .has_with_results(digests, results)
+                .await
+                .err_tip(|| "In RoutingStore::has_with_results()");
+        }
+        let mut digests_for_store: Vec<(Vec<usize>, Vec<DigestInfo>)> =
+            vec![(Vec::new(), Vec::new()); self.stores.len()];
+        for (digest_idx, digest) in digests.iter().enumerate() {
+            let store_idx = self.get_store_index(digest);
+            digests_for_store[store_idx].0.push(digest_idx);
+            digests_for_store[store_idx].1.push(*digest);
+        }
+
+        let mut future_stream: FuturesUnordered<_> = digests_for_store
+            .into_iter()
+            .enumerate()
+            .filter(|(_, (digest_idxs, _))| !digest_idxs.is_empty())
+            .map(|(store_idx, (digest_idxs, digests))| async move {
+                let mut inner_results = vec![None; digests.len()];
+                Pin::new(self.stores[store_idx].as_ref())
+                    .has_with_results(&digests, &mut inner_results)
+                    .await
+                    .err_tip(|| {
+                        format!("In RoutingStore::has_with_results() for store {store_idx}")
+                    })?;
+                Result::<_, Error>::Ok((digest_idxs, inner_results))
+            })
+            .collect();
+
+        while let Some((digest_idxs, inner_results)) = future_stream.try_next().await? {
+            for (digest_idx, inner_result) in digest_idxs.into_iter().zip(inner_results) {
+                results[digest_idx] = inner_result;
+            }
+        }
+        Ok(())
+    }
+
+    async fn update(
+        self: Pin<&Self>,
+        digest: DigestInfo,
+        reader: DropCloserReadHalf,
+        size_info: UploadSizeInfo,
+    ) -> Result<(), Error> {
+        self.get_store(&digest)
+            .update(digest, reader, size_info)
+            .await
+            .err_tip(|| "In RoutingStore::update()")
+    }
+
+    async fn get_part_ref(
+        self: Pin<&Self>,
+        digest: DigestInfo,
+        writer: &mut DropCloserWriteHalf,
+        offset: usize,
+        length: Option<usize>,
+    ) -> Result<(), Error> {
+        self.get_store(&digest)
+            .get_part_ref(digest, writer, offset, length)
+            .await
+            .err_tip(|| "In RoutingStore::get_part_ref()")
+    }
+
+    async fn remove(self: Pin<&Self>, digest: DigestInfo) -> Result<bool, Error> {
+        self.get_store(&digest)
+            .remove(digest)
+            .await
+            .err_tip(|| "In RoutingStore::remove()")
+    }
+
+    async fn list(
+        self: Pin<&Self>,
+        handler: &mut (dyn FnMut(DigestInfo, usize) + Send),
+    ) -> Result<(), Error> {
+        for store in &self.stores {
+            Pin::new(store.as_ref()).list(handler).await?;
+        }
+        Ok(())
+    }
+
+    async fn touch(self: Pin<&Self>, digests: &[DigestInfo]) -> Result<(), Error> {
+        self.digests_for_stores(digests)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, digests)| !digests.is_empty())
+            .map(|(store_idx, digests)| async move {
+                Pin::new(self.stores[store_idx].as_ref())
+                    .touch(&digests)
+                    .await
+                    .err_tip(|| format!("In RoutingStore::touch() for store {store_idx}"))
+            })
+            .collect::<FuturesUnordered<_>>()
+            .try_collect()
+            .await
+    }
+
+    async fn pin(self: Pin<&Self>, digests: &[DigestInfo], ttl: Duration) -> Result<usize, Error> {
+        self.digests_for_stores(digests)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, digests)| !digests.is_empty())
+            .map(|(store_idx, digests)| async move {
+                Pin::new(self.stores[store_idx].as_ref())
+                    .pin(&digests, ttl)
+                    .await
+                    .err_tip(|| format!("In RoutingStore::pin() for store {store_idx}"))
+            })
+            .collect::<FuturesUnordered<_>>()
+            .try_fold(0, |total, pinned| future::ready(Ok(total + pinned)))
+            .await
+    }
+
+    fn inner_store(&self, digest: Option<DigestInfo>) -> &'_ dyn Store {
+        let Some(digest) = digest else {
+            return self;
+        };
+        self.stores[self.get_store_index(&digest)].inner_store(Some(digest))
+    }
+
+    fn inner_store_arc(self: Arc<Self>, digest: Option<DigestInfo>) -> Arc<dyn Store> {
+        let Some(digest) = digest else {
+            return self;
+        };
+        self.stores[self.get_store_index(&digest)]
+            .clone()
+            .inner_store_arc(Some(digest))
+    }
+
+    /// Registers `callback` on every store data can be routed to, so fails
+    /// if any of them can't report evictions.
+    fn register_eviction_callback(
+        self: Arc<Self>,
+        callback: StoreEvictionCallback,
+    ) -> Result<(), Error> {
+        let callback: Arc<dyn Fn(DigestInfo, Bytes) + Send + Sync> = Arc::from(callback);
+        for (store_idx, store) in self.unique_stores().into_iter().enumerate() {
+            let callback = callback.clone();
+            store
+                .register_eviction_callback(Box::new(move |digest, data| callback(digest, data)))
+                .err_tip(|| {
+                    format!("In RoutingStore::register_eviction_callback() for store {store_idx}")
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the eviction policy of every store data can be routed to.
+    async fn set_eviction_policy(&self, eviction_policy: &EvictionPolicy) -> Result<(), Error> {
+        for (store_idx, store) in self.unique_stores().into_iter().enumerate() {
+            store
+                .set_eviction_policy(eviction_policy)
+                .await
+                .err_tip(|| {
+                    format!("In RoutingStore::set_eviction_policy() for store {store_idx}")
+                })?;
+        }
+        Ok(())
+    }
+
+    fn as_any<'a>(&'a self) -> &'a (dyn std::any::Any + Sync + Send + 'static) {
+        self
+    }
+
+    fn as_any_arc(self: Arc<Self>) -> Arc<dyn std::any::Any + Sync + Send + 'static> {
+        self
+    }
+
+    fn register_metrics(self: Arc<Self>, registry: &mut Registry) {
+        let (default_store, rule_stores) = self.stores.split_last().unwrap();
+        for (i, store) in rule_stores.iter().enumerate() {
+            let store_registry = registry.sub_registry_with_prefix(format!("rule_{i}"));
+            store.clone().register_metrics(store_registry);
+        }
+        let default_store_registry = registry.sub_registry_with_prefix("default_store");
+        default_store
+            .clone()
+            .register_metrics(default_store_registry);
+    }
+
+    fn register_health(self: Arc<Self>, registry: &mut HealthRegistryBuilder) {
+        let (default_store, rule_stores) = self.stores.split_last().unwrap();
+        for (i, store) in rule_stores.iter().enumerate() {
+            store
+                .clone()
+                .register_health(&mut registry.sub_builder(format!("rule_{i}").into()));
+        }
+        default_store
+            .clone()
+            .register_health(&mut registry.sub_builder("default_store".into()));
+    }
+}
+
+default_health_status_indicator!(RoutingStore);
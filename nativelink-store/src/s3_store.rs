@@ -13,26 +13,30 @@
 // limitations under the License.
 
 use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{cmp, env};
 
 use async_trait::async_trait;
 use aws_config::default_provider::credentials;
 use aws_config::BehaviorVersion;
 use aws_sdk_s3::config::Region;
+use aws_sdk_s3::error::{ProvideErrorMetadata, SdkError};
+use aws_sdk_s3::operation::copy_object::{CopyObjectError, CopyObjectOutput};
 use aws_sdk_s3::operation::create_multipart_upload::CreateMultipartUploadOutput;
-use aws_sdk_s3::operation::get_object::GetObjectError;
-use aws_sdk_s3::operation::head_object::HeadObjectError;
+use aws_sdk_s3::operation::get_object::{GetObjectError, GetObjectOutput};
+use aws_sdk_s3::operation::head_object::{HeadObjectError, HeadObjectOutput};
 use aws_sdk_s3::primitives::{ByteStream, SdkBody};
 use aws_sdk_s3::types::builders::{CompletedMultipartUploadBuilder, CompletedPartBuilder};
+use aws_sdk_s3::types::{MetadataDirective, ServerSideEncryption, StorageClass};
 use aws_sdk_s3::Client;
 use aws_smithy_runtime::client::http::hyper_014::HyperClientBuilder;
-use futures::stream::{unfold, FuturesUnordered};
-use futures::{try_join, FutureExt, StreamExt, TryStreamExt};
+use futures::stream::{self, unfold, FuturesUnordered};
+use futures::{future, try_join, FutureExt, StreamExt, TryStreamExt};
 use hyper::client::connect::HttpConnector;
 use hyper::service::Service;
 use hyper::Uri;
@@ -43,13 +47,14 @@ use nativelink_util::common::DigestInfo;
 use nativelink_util::health_utils::{default_health_status_indicator, HealthStatusIndicator};
 use nativelink_util::retry::{Retrier, RetryResult};
 use nativelink_util::store_trait::{Store, UploadSizeInfo};
+use parking_lot::Mutex;
 use rand::rngs::OsRng;
 use rand::Rng;
 use tokio::net::TcpStream;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, Semaphore};
 use tokio::time::sleep;
 use tokio_stream::wrappers::ReceiverStream;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::cas_utils::is_zero_digest;
 
@@ -57,10 +62,36 @@ use crate::cas_utils::is_zero_digest;
 // https://docs.aws.amazon.com/AmazonS3/latest/userguide/qfacts.html
 const MIN_MULTIPART_SIZE: usize = 5 * 1024 * 1024; // 5mb.
 
+// CopyObject can only copy objects up to this size in a single request. See:
+// https://docs.aws.amazon.com/AmazonS3/latest/API/API_CopyObject.html
+const MAX_COPY_OBJECT_SIZE: usize = 5 * 1024 * 1024 * 1024; // 5gb.
+
 // Default limit for concurrent part uploads per multipart upload.
 // Note: If you change this, adjust the docs in the config.
 const DEFAULT_MULTIPART_MAX_CONCURRENT_UPLOADS: usize = 10;
 
+// Default number of hex characters of the hash object keys are grouped on
+// when listing the bucket.
+const DEFAULT_LIST_PREFIX_LENGTH: usize = 2;
+
+// Largest allowed `list_prefix_length`, which lists 65536 key groups.
+const MAX_LIST_PREFIX_LENGTH: usize = 4;
+
+// Number of ListObjectsV2 requests sent at the same time.
+const LIST_CONCURRENCY: usize = 16;
+
+// NOTE: If this changes update the comments in `stores.rs` to reflect
+// the new default.
+const DEFAULT_TOUCH_INTERVAL_SECONDS: u64 = 60 * 60;
+
+/// Maximum number of recently touched objects remembered, so repeated
+/// touches of the same object don't each send a HEAD request.
+const MAX_RECENTLY_TOUCHED: usize = 100_000;
+
+/// Maximum number of objects refreshed on read at the same time. Reads of
+/// old objects beyond this are not refreshed.
+const MAX_CONCURRENT_TOUCHES_ON_READ: usize = 16;
+
 #[derive(Clone)]
 pub struct TlsConnector {
     connector: HttpsConnector<HttpConnector>,
@@ -132,12 +163,104 @@ impl Service<Uri> for TlsConnector {
     }
 }
 
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+/// The attributes of an object that S3 resets when copying the object onto
+/// itself, unless they are given again.
+struct ObjectAttributes {
+    metadata: Option<HashMap<String, String>>,
+    content_type: Option<String>,
+    cache_control: Option<String>,
+    content_encoding: Option<String>,
+    content_disposition: Option<String>,
+    content_language: Option<String>,
+    storage_class: Option<StorageClass>,
+    server_side_encryption: Option<ServerSideEncryption>,
+    ssekms_key_id: Option<String>,
+    bucket_key_enabled: Option<bool>,
+}
+
+impl From<&HeadObjectOutput> for ObjectAttributes {
+    fn from(output: &HeadObjectOutput) -> Self {
+        Self {
+            metadata: output.metadata().cloned(),
+            content_type: output.content_type().map(str::to_string),
+            cache_control: output.cache_control().map(str::to_string),
+            content_encoding: output.content_encoding().map(str::to_string),
+            content_disposition: output.content_disposition().map(str::to_string),
+            content_language: output.content_language().map(str::to_string),
+            storage_class: output.storage_class().cloned(),
+            server_side_encryption: output.server_side_encryption().cloned(),
+            ssekms_key_id: output.ssekms_key_id().map(str::to_string),
+            bucket_key_enabled: output.bucket_key_enabled(),
+        }
+    }
+}
+
+impl From<&GetObjectOutput> for ObjectAttributes {
+    fn from(output: &GetObjectOutput) -> Self {
+        Self {
+            metadata: output.metadata().cloned(),
+            content_type: output.content_type().map(str::to_string),
+            cache_control: output.cache_control().map(str::to_string),
+            content_encoding: output.content_encoding().map(str::to_string),
+            content_disposition: output.content_disposition().map(str::to_string),
+            content_language: output.content_language().map(str::to_string),
+            storage_class: output.storage_class().cloned(),
+            server_side_encryption: output.server_side_encryption().cloned(),
+            ssekms_key_id: output.ssekms_key_id().map(str::to_string),
+            bucket_key_enabled: output.bucket_key_enabled(),
+        }
+    }
+}
+
+/// Copies an object onto itself, which resets its last modified time, so
+/// bucket lifecycle rules that expire old objects keep it around. S3 refuses
+/// to copy an object onto itself without changes, so the metadata is
+/// replaced with the object's own attributes.
+async fn copy_object_onto_itself(
+    s3_client: &Client,
+    bucket: &str,
+    key: &str,
+    attributes: ObjectAttributes,
+) -> Result<CopyObjectOutput, SdkError<CopyObjectError>> {
+    s3_client
+        .copy_object()
+        .bucket(bucket)
+        .key(key)
+        .copy_source(format!("{bucket}/{key}"))
+        .metadata_directive(MetadataDirective::Replace)
+        .set_metadata(attributes.metadata)
+        .set_content_type(attributes.content_type)
+        .set_cache_control(attributes.cache_control)
+        .set_content_encoding(attributes.content_encoding)
+        .set_content_disposition(attributes.content_disposition)
+        .set_content_language(attributes.content_language)
+        .set_storage_class(attributes.storage_class)
+        .set_server_side_encryption(attributes.server_side_encryption)
+        .set_ssekms_key_id(attributes.ssekms_key_id)
+        .set_bucket_key_enabled(attributes.bucket_key_enabled)
+        .send()
+        .await
+}
+
 pub struct S3Store {
     s3_client: Arc<Client>,
     bucket: String,
     key_prefix: String,
     retrier: Retrier,
     multipart_max_concurrent_uploads: usize,
+    list_prefix_length: usize,
+    list_batch_threshold: usize,
+    touch_on_read_seconds: u64,
+    touch_interval_seconds: u64,
+    /// When objects were last touched, in seconds since the epoch.
+    recently_touched: Mutex<HashMap<DigestInfo, i64>>,
+    touch_on_read_permits: Arc<Semaphore>,
 }
 
 impl S3Store {
@@ -175,6 +298,15 @@ impl S3Store {
         s3_client: Client,
         jitter_fn: Arc<dyn Fn(Duration) -> Duration + Send + Sync>,
     ) -> Result<Self, Error> {
+        let list_prefix_length = if config.list_prefix_length == 0 {
+            DEFAULT_LIST_PREFIX_LENGTH
+        } else {
+            config.list_prefix_length
+        };
+        error_if!(
+            list_prefix_length > MAX_LIST_PREFIX_LENGTH,
+            "list_prefix_length in S3Store must be at most {MAX_LIST_PREFIX_LENGTH}, got {list_prefix_length}"
+        );
         Ok(Self {
             s3_client: Arc::new(s3_client),
             bucket: config.bucket.to_string(),
@@ -187,6 +319,16 @@ impl S3Store {
             multipart_max_concurrent_uploads: config
                 .multipart_max_concurrent_uploads
                 .map_or(DEFAULT_MULTIPART_MAX_CONCURRENT_UPLOADS, |v| v),
+            list_prefix_length,
+            list_batch_threshold: config.list_batch_threshold,
+            touch_on_read_seconds: config.touch_on_read_seconds,
+            touch_interval_seconds: if config.touch_interval_seconds == 0 {
+                DEFAULT_TOUCH_INTERVAL_SECONDS
+            } else {
+                config.touch_interval_seconds
+            },
+            recently_touched: Mutex::new(HashMap::new()),
+            touch_on_read_permits: Arc::new(Semaphore::new(MAX_CONCURRENT_TOUCHES_ON_READ)),
         })
     }
 
@@ -199,6 +341,175 @@ impl S3Store {
         )
     }
 
+    /// Inverse of `make_s3_path()`. Returns None for keys that were not
+    /// written by this store.
+    fn parse_s3_path(&self, s3_path: &str) -> Option<DigestInfo> {
+        let (hash, size) = s3_path.strip_prefix(&self.key_prefix)?.rsplit_once('-')?;
+        DigestInfo::try_new(hash, size.parse::<i64>().ok()?).ok()
+    }
+
+    /// Key group `digest` is listed in.
+    fn make_list_prefix(&self, digest: &DigestInfo) -> String {
+        format!(
+            "{}{}",
+            self.key_prefix,
+            &digest.hash_str()[..self.list_prefix_length]
+        )
+    }
+
+    /// Lists every object whose key starts with `prefix`, along with its size.
+    /// Returns None if listing them takes more than `max_pages` requests.
+    async fn list_prefix(
+        &self,
+        prefix: String,
+        max_pages: usize,
+    ) -> Result<Option<Vec<(DigestInfo, usize)>>, Error> {
+        let mut items = Vec::new();
+        let mut continuation_token: Option<String> = None;
+        for _ in 0..max_pages {
+            let prefix = &prefix;
+            let token = &continuation_token;
+            let output = self
+                .retrier
+                .retry(unfold((), move |state| async move {
+                    let result = self
+                        .s3_client
+                        .list_objects_v2()
+                        .bucket(&self.bucket)
+                        .prefix(prefix)
+                        .set_continuation_token(token.clone())
+                        .send()
+                        .await;
+                    match result {
+                        Ok(output) => Some((RetryResult::Ok(output), state)),
+                        Err(sdk_error) => Some((
+                            RetryResult::Retry(make_err!(
+                                Code::Unavailable,
+                                "Unhandled ListObjectsV2Error in S3: {:?}",
+                                sdk_error.into_service_error()
+                            )),
+                            state,
+                        )),
+                    }
+                }))
+                .await
+                .err_tip(|| format!("Listing prefix {prefix} in S3Store"))?;
+            for object in output.contents() {
+                let Some(digest) = object.key().and_then(|key| self.parse_s3_path(key)) else {
+                    continue;
+                };
+                let Some(size) = object.size().and_then(|size| usize::try_from(size).ok()) else {
+                    continue;
+                };
+                items.push((digest, size));
+            }
+            match output.next_continuation_token() {
+                Some(token) if output.is_truncated() == Some(true) => {
+                    continuation_token = Some(token.to_string());
+                }
+                _ => return Ok(Some(items)),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Answers `has_with_results()` by listing the key groups of `digests`
+    /// instead of sending one HEAD request per digest. A key group is only
+    /// listed while that takes fewer requests than the digests checked in
+    /// it, the digests of larger groups are checked with HEAD requests.
+    async fn has_with_results_by_listing(
+        &self,
+        digests: &[DigestInfo],
+        results: &mut [Option<usize>],
+    ) -> Result<(), Error> {
+        let mut requested: HashSet<DigestInfo> = HashSet::with_capacity(digests.len());
+        let mut groups: HashMap<String, Vec<DigestInfo>> = HashMap::new();
+        for digest in digests {
+            if !is_zero_digest(digest) && requested.insert(*digest) {
+                groups
+                    .entry(self.make_list_prefix(digest))
+                    .or_default()
+                    .push(*digest);
+            }
+        }
+        // Only the requested digests are kept, as a key group may hold far
+        // more objects than were asked for.
+        let mut found: HashMap<DigestInfo, usize> = HashMap::new();
+        let mut pages = stream::iter(groups)
+            .map(|(prefix, group)| async move {
+                if let Some(items) = self.list_prefix(prefix, group.len()).await? {
+                    return Ok(items);
+                }
+                stream::iter(group)
+                    .map(|digest| async move {
+                        let size = Pin::new(self).has(&digest).await?;
+                        Ok::<_, Error>(size.map(|size| (digest, size)))
+                    })
+                    .buffered(LIST_CONCURRENCY)
+                    .try_filter_map(|item| future::ready(Ok(item)))
+                    .try_collect()
+                    .await
+            })
+            .buffer_unordered(LIST_CONCURRENCY);
+        while let Some(items) = pages.try_next().await? {
+            found.extend(
+                items
+                    .into_iter()
+                    .filter(|(digest, _)| requested.contains(digest)),
+            );
+        }
+        for (digest, result) in digests.iter().zip(results.iter_mut()) {
+            // We need to do a special pass to ensure our zero digest exist.
+            *result = if is_zero_digest(digest) {
+                Some(0)
+            } else {
+                found.get(digest).copied()
+            };
+        }
+        Ok(())
+    }
+
+    /// Refreshes the object that `get_object_output` was read from in the
+    /// background, if it was last modified more than `touch_on_read_seconds`
+    /// ago. Each object is refreshed at most once per
+    /// `touch_interval_seconds`, and at most `MAX_CONCURRENT_TOUCHES_ON_READ`
+    /// objects at a time.
+    fn maybe_touch_on_read(&self, digest: &DigestInfo, get_object_output: &GetObjectOutput) {
+        // CopyObject only supports objects up to 5GB.
+        if self.touch_on_read_seconds == 0 || digest.size_bytes > MAX_COPY_OBJECT_SIZE as i64 {
+            return;
+        }
+        let Some(last_modified) = get_object_output.last_modified() else {
+            return;
+        };
+        let now = now_secs();
+        if now.saturating_sub(last_modified.secs()) < self.touch_on_read_seconds as i64
+            || self.recently_touched(digest, now)
+        {
+            return;
+        }
+        let Ok(permit) = self.touch_on_read_permits.clone().try_acquire_owned() else {
+            return;
+        };
+        self.record_touched(*digest, now);
+        let s3_client = self.s3_client.clone();
+        let bucket = self.bucket.clone();
+        let s3_path = self.make_s3_path(digest);
+        let attributes = ObjectAttributes::from(get_object_output);
+        // Refreshing is best effort, and must not hold up the read, which is
+        // often dropped as soon as the reader received all the data.
+        tokio::spawn(async move {
+            let _permit = permit;
+            let result = copy_object_onto_itself(&s3_client, &bucket, &s3_path, attributes).await;
+            if let Err(sdk_error) = result {
+                warn!(
+                    "Failed to refresh {s3_path} in S3 on read : {:?}",
+                    sdk_error.into_service_error()
+                );
+            }
+        });
+    }
+
     async fn has(self: Pin<&Self>, digest: &DigestInfo) -> Result<Option<usize>, Error> {
         self.retrier
             .retry(unfold((), move |state| async move {
@@ -240,6 +551,96 @@ impl S3Store {
             }))
             .await
     }
+
+    /// Whether `digest` was touched less than `touch_interval_seconds` ago.
+    fn recently_touched(&self, digest: &DigestInfo, now: i64) -> bool {
+        self.recently_touched
+            .lock()
+            .get(digest)
+            .is_some_and(|touched| {
+                now.saturating_sub(*touched) < self.touch_interval_seconds as i64
+            })
+    }
+
+    fn record_touched(&self, digest: DigestInfo, now: i64) {
+        let mut recently_touched = self.recently_touched.lock();
+        if recently_touched.len() >= MAX_RECENTLY_TOUCHED {
+            recently_touched.retain(|_, touched| {
+                now.saturating_sub(*touched) < self.touch_interval_seconds as i64
+            });
+            if recently_touched.len() >= MAX_RECENTLY_TOUCHED {
+                recently_touched.clear();
+            }
+        }
+        recently_touched.insert(digest, now);
+    }
+
+    /// Copies the object onto itself, keeping its attributes, unless it was
+    /// modified less than `touch_interval_seconds` ago. See
+    /// `copy_object_onto_itself()`.
+    async fn touch_object(self: Pin<&Self>, digest: &DigestInfo) -> Result<(), Error> {
+        let s3_path = &self.make_s3_path(digest);
+        self.retrier
+            .retry(unfold((), move |state| async move {
+                let head_result = self
+                    .s3_client
+                    .head_object()
+                    .bucket(&self.bucket)
+                    .key(s3_path)
+                    .send()
+                    .await;
+                let head_object_output = match head_result {
+                    Ok(head_object_output) => head_object_output,
+                    Err(sdk_error) => match sdk_error.into_service_error() {
+                        HeadObjectError::NotFound(_) => return Some((RetryResult::Ok(()), state)),
+                        other => {
+                            return Some((
+                                RetryResult::Retry(make_err!(
+                                    Code::Unavailable,
+                                    "Unhandled HeadObjectError in S3: {other:?}"
+                                )),
+                                state,
+                            ))
+                        }
+                    },
+                };
+
+                if head_object_output
+                    .last_modified()
+                    .is_some_and(|last_modified| {
+                        now_secs().saturating_sub(last_modified.secs())
+                            < self.touch_interval_seconds as i64
+                    })
+                {
+                    return Some((RetryResult::Ok(()), state));
+                }
+                let result = copy_object_onto_itself(
+                    &self.s3_client,
+                    &self.bucket,
+                    s3_path,
+                    ObjectAttributes::from(&head_object_output),
+                )
+                .await;
+
+                match result {
+                    Ok(_) => Some((RetryResult::Ok(()), state)),
+                    Err(sdk_error) => {
+                        let service_error = sdk_error.into_service_error();
+                        if service_error.code() == Some("NoSuchKey") {
+                            return Some((RetryResult::Ok(()), state));
+                        }
+                        Some((
+                            RetryResult::Retry(make_err!(
+                                Code::Unavailable,
+                                "Unhandled CopyObjectError in S3: {service_error:?}"
+                            )),
+                            state,
+                        ))
+                    }
+                }
+            }))
+            .await
+    }
 }
 
 #[async_trait]
@@ -249,6 +650,9 @@ impl Store for S3Store {
         digests: &[DigestInfo],
         results: &mut [Option<usize>],
     ) -> Result<(), Error> {
+        if self.list_batch_threshold != 0 && digests.len() >= self.list_batch_threshold {
+            return self.has_with_results_by_listing(digests, results).await;
+        }
         digests
             .iter()
             .zip(results.iter_mut())
@@ -492,7 +896,10 @@ impl Store for S3Store {
                     .await;
 
                 let mut s3_in_stream = match result {
-                    Ok(head_object_output) => head_object_output.body,
+                    Ok(get_object_output) => {
+                        self.maybe_touch_on_read(&digest, &get_object_output);
+                        get_object_output.body
+                    }
                     Err(sdk_error) => match sdk_error.into_service_error() {
                         GetObjectError::NoSuchKey(e) => {
                             return Some((
@@ -556,6 +963,50 @@ impl Store for S3Store {
             .await
     }
 
+    async fn list(
+        self: Pin<&Self>,
+        handler: &mut (dyn FnMut(DigestInfo, usize) + Send),
+    ) -> Result<(), Error> {
+        let prefixes = (0..16usize.pow(self.list_prefix_length as u32)).map(|group| {
+            format!(
+                "{}{:0width$x}",
+                self.key_prefix,
+                group,
+                width = self.list_prefix_length
+            )
+        });
+        let mut pages = stream::iter(prefixes)
+            .map(|prefix| self.list_prefix(prefix, usize::MAX))
+            .buffered(LIST_CONCURRENCY);
+        while let Some(items) = pages.try_next().await? {
+            for (digest, size) in items.into_iter().flatten() {
+                handler(digest, size);
+            }
+        }
+        Ok(())
+    }
+
+    async fn touch(self: Pin<&Self>, digests: &[DigestInfo]) -> Result<(), Error> {
+        let now = now_secs();
+        digests
+            .iter()
+            // CopyObject only supports objects up to 5GB, larger objects are
+            // not refreshed.
+            .filter(|digest| {
+                !is_zero_digest(digest)
+                    && digest.size_bytes <= MAX_COPY_OBJECT_SIZE as i64
+                    && !self.recently_touched(digest, now)
+            })
+            .map(|digest| async move {
+                self.touch_object(digest).await?;
+                self.record_touched(*digest, now);
+                Result::<_, Error>::Ok(())
+            })
+            .collect::<FuturesUnordered<_>>()
+            .try_collect()
+            .await
+    }
+
     fn inner_store(&self, _digest: Option<DigestInfo>) -> &'_ dyn Store {
         self
     }
@@ -15,13 +15,17 @@
 use std::ops::BitXor;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
+use futures::future;
 use futures::stream::{FuturesUnordered, TryStreamExt};
 use nativelink_error::{error_if, Error, ResultExt};
 use nativelink_util::buf_channel::{DropCloserReadHalf, DropCloserWriteHalf};
 use nativelink_util::common::DigestInfo;
-use nativelink_util::health_utils::{default_health_status_indicator, HealthStatusIndicator};
+use nativelink_util::health_utils::{
+    default_health_status_indicator, HealthRegistryBuilder, HealthStatusIndicator,
+};
 use nativelink_util::metrics_utils::Registry;
 use nativelink_util::store_trait::{Store, UploadSizeInfo};
 
@@ -205,6 +209,58 @@ impl Store for ShardStore {
         self
     }
 
+    async fn list(
+        self: Pin<&Self>,
+        handler: &mut (dyn FnMut(DigestInfo, usize) + Send),
+    ) -> Result<(), Error> {
+        for (_, store) in &self.weights_and_stores {
+            Pin::new(store.as_ref()).list(handler).await?;
+        }
+        Ok(())
+    }
+
+    async fn touch(self: Pin<&Self>, digests: &[DigestInfo]) -> Result<(), Error> {
+        let mut digests_for_store: Vec<Vec<DigestInfo>> =
+            vec![Vec::new(); self.weights_and_stores.len()];
+        for digest in digests {
+            digests_for_store[self.get_store_index(digest)].push(*digest);
+        }
+        digests_for_store
+            .into_iter()
+            .enumerate()
+            .filter(|(_, digests)| !digests.is_empty())
+            .map(|(store_idx, digests)| async move {
+                Pin::new(self.weights_and_stores[store_idx].1.as_ref())
+                    .touch(&digests)
+                    .await
+                    .err_tip(|| format!("In ShardStore::touch() for store {store_idx}"))
+            })
+            .collect::<FuturesUnordered<_>>()
+            .try_collect()
+            .await
+    }
+
+    async fn pin(self: Pin<&Self>, digests: &[DigestInfo], ttl: Duration) -> Result<usize, Error> {
+        let mut digests_for_store: Vec<Vec<DigestInfo>> =
+            vec![Vec::new(); self.weights_and_stores.len()];
+        for digest in digests {
+            digests_for_store[self.get_store_index(digest)].push(*digest);
+        }
+        digests_for_store
+            .into_iter()
+            .enumerate()
+            .filter(|(_, digests)| !digests.is_empty())
+            .map(|(store_idx, digests)| async move {
+                Pin::new(self.weights_and_stores[store_idx].1.as_ref())
+                    .pin(&digests, ttl)
+                    .await
+                    .err_tip(|| format!("In ShardStore::pin() for store {store_idx}"))
+            })
+            .collect::<FuturesUnordered<_>>()
+            .try_fold(0, |total, pinned| future::ready(Ok(total + pinned)))
+            .await
+    }
+
     fn inner_store(&self, digest: Option<DigestInfo>) -> &'_ dyn Store {
         let Some(digest) = digest else {
             return self;
@@ -230,6 +286,14 @@ impl Store for ShardStore {
             store.clone().register_metrics(store_registry);
         }
     }
+
+    fn register_health(self: Arc<Self>, registry: &mut HealthRegistryBuilder) {
+        for (i, (_, store)) in self.weights_and_stores.iter().enumerate() {
+            store
+                .clone()
+                .register_health(&mut registry.sub_builder(format!("store_{i}").into()));
+        }
+    }
 }
 
 default_health_status_indicator!(ShardStore);
@@ -14,12 +14,15 @@
 
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use nativelink_error::{Error, ResultExt};
 use nativelink_util::buf_channel::{DropCloserReadHalf, DropCloserWriteHalf};
 use nativelink_util::common::DigestInfo;
-use nativelink_util::health_utils::{default_health_status_indicator, HealthStatusIndicator};
+use nativelink_util::health_utils::{
+    default_health_status_indicator, HealthRegistryBuilder, HealthStatusIndicator,
+};
 use nativelink_util::store_trait::{Store, UploadSizeInfo};
 use tokio::join;
 
@@ -115,6 +118,41 @@ impl Store for SizePartitioningStore {
             .await
     }
 
+    async fn list(
+        self: Pin<&Self>,
+        handler: &mut (dyn FnMut(DigestInfo, usize) + Send),
+    ) -> Result<(), Error> {
+        Pin::new(self.lower_store.as_ref()).list(handler).await?;
+        Pin::new(self.upper_store.as_ref()).list(handler).await
+    }
+
+    async fn touch(self: Pin<&Self>, digests: &[DigestInfo]) -> Result<(), Error> {
+        let (lower_digests, upper_digests): (Vec<_>, Vec<_>) = digests
+            .iter()
+            .cloned()
+            .partition(|digest| digest.size_bytes < self.size);
+        let (lower_result, upper_result) = join!(
+            Pin::new(self.lower_store.as_ref()).touch(&lower_digests),
+            Pin::new(self.upper_store.as_ref()).touch(&upper_digests),
+        );
+        lower_result.merge(upper_result)
+    }
+
+    async fn pin(self: Pin<&Self>, digests: &[DigestInfo], ttl: Duration) -> Result<usize, Error> {
+        let (lower_digests, upper_digests): (Vec<_>, Vec<_>) = digests
+            .iter()
+            .cloned()
+            .partition(|digest| digest.size_bytes < self.size);
+        let (lower_result, upper_result) = join!(
+            Pin::new(self.lower_store.as_ref()).pin(&lower_digests, ttl),
+            Pin::new(self.upper_store.as_ref()).pin(&upper_digests, ttl),
+        );
+        match (lower_result, upper_result) {
+            (Ok(lower_pinned), Ok(upper_pinned)) => Ok(lower_pinned + upper_pinned),
+            (lower_result, upper_result) => lower_result.merge(upper_result),
+        }
+    }
+
     fn inner_store(&self, digest: Option<DigestInfo>) -> &'_ dyn Store {
         let Some(digest) = digest else {
             return self;
@@ -142,6 +180,15 @@ impl Store for SizePartitioningStore {
     fn as_any_arc(self: Arc<Self>) -> Arc<dyn std::any::Any + Sync + Send + 'static> {
         self
     }
+
+    fn register_health(self: Arc<Self>, registry: &mut HealthRegistryBuilder) {
+        self.lower_store
+            .clone()
+            .register_health(&mut registry.sub_builder("lower_store".into()));
+        self.upper_store
+            .clone()
+            .register_health(&mut registry.sub_builder("upper_store".into()));
+    }
 }
 
 default_health_status_indicator!(SizePartitioningStore);
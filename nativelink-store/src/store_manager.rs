@@ -13,18 +13,23 @@
 // limitations under the License.
 
 use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 
 use nativelink_util::store_trait::Store;
 
+use crate::quota_store::QuotaOwners;
+
 pub struct StoreManager {
     stores: RwLock<HashMap<String, Arc<dyn Store>>>,
+    /// Owners shared by the quota stores writing to each named store.
+    quota_owners: Mutex<HashMap<String, Arc<QuotaOwners>>>,
 }
 
 impl StoreManager {
     pub fn new() -> StoreManager {
         StoreManager {
             stores: RwLock::new(HashMap::new()),
+            quota_owners: Mutex::new(HashMap::new()),
         }
     }
 
@@ -46,6 +51,19 @@ impl StoreManager {
         }
         None
     }
+
+    /// Returns the owners shared by all quota stores writing to the store
+    /// named `store_name`.
+    pub fn quota_owners(&self, store_name: &str) -> Arc<QuotaOwners> {
+        let mut quota_owners = self
+            .quota_owners
+            .lock()
+            .expect("Failed to lock mutex in quota_owners()");
+        quota_owners
+            .entry(store_name.to_string())
+            .or_default()
+            .clone()
+    }
 }
 
 impl Default for StoreManager {
@@ -0,0 +1,550 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::future::join_all;
+use futures::join;
+use nativelink_config::stores::{EvictionPolicy, TieredStore as TieredStoreConfig};
+use nativelink_error::{error_if, make_err, Code, Error, ResultExt};
+use nativelink_util::buf_channel::{
+    make_buf_channel_pair, DropCloserReadHalf, DropCloserWriteHalf,
+};
+use nativelink_util::common::DigestInfo;
+use nativelink_util::evicting_map::{EvictingMap, LenEntry};
+use nativelink_util::health_utils::{
+    default_health_status_indicator, HealthRegistryBuilder, HealthStatusIndicator,
+};
+use nativelink_util::metrics_utils::{
+    Collector, CollectorState, CounterWithTime, MetricsComponent, Registry,
+};
+use nativelink_util::store_trait::{Store, StoreOptimizations, UploadSizeInfo};
+use tokio::sync::{mpsc, Mutex as AsyncMutex};
+use tracing::warn;
+
+use crate::fast_slow_store::FastSlowStore;
+
+/// Maximum number of evicted objects waiting to be demoted. Objects evicted
+/// while the queue is full are not demoted.
+const MAX_PENDING_DEMOTIONS: usize = 1024;
+
+/// Number of reads of an object that was found in a slower tier.
+#[derive(Clone, Debug)]
+struct HitCounter(Arc<AtomicU32>);
+
+impl LenEntry for HitCounter {
+    #[inline]
+    fn len(&self) -> usize {
+        // Approximate memory used by each counter, so `max_bytes` is useful.
+        std::mem::size_of::<DigestInfo>() + std::mem::size_of::<AtomicU32>()
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+struct Tier {
+    store: Arc<dyn Store>,
+    promotion_hit_count: u32,
+    max_promotion_size: u64,
+    skip_on_update: bool,
+}
+
+impl Tier {
+    fn pin_store(&self) -> Pin<&dyn Store> {
+        Pin::new(self.store.as_ref())
+    }
+
+    fn accepts_updates(&self, digest: DigestInfo) -> bool {
+        !self
+            .store
+            .inner_store(Some(digest))
+            .optimized_for(StoreOptimizations::NoopUpdates)
+    }
+
+    fn serves_downloads(&self) -> bool {
+        !self
+            .store
+            .inner_store(None)
+            .optimized_for(StoreOptimizations::NoopDownloads)
+    }
+
+    fn should_promote(&self, digest: DigestInfo, hit_count: u32, size: usize) -> bool {
+        hit_count >= self.promotion_hit_count
+            && (self.max_promotion_size == 0 || size as u64 <= self.max_promotion_size)
+            && self.accepts_updates(digest)
+    }
+}
+
+pub struct TieredStore {
+    tiers: Vec<Tier>,
+    hit_counters: EvictingMap<HitCounter, SystemTime>,
+    /// Held while inserting a counter, so concurrent first reads of the
+    /// same object are all counted.
+    hit_counter_insert_lock: AsyncMutex<()>,
+
+    // Metrics.
+    promotions: CounterWithTime,
+    promotion_failures: CounterWithTime,
+    demotions: CounterWithTime,
+    demotion_failures: CounterWithTime,
+}
+
+impl TieredStore {
+    pub fn new(
+        config: &TieredStoreConfig,
+        stores: Vec<Arc<dyn Store>>,
+    ) -> Result<Arc<Self>, Error> {
+        error_if!(
+            config.tiers.len() < 2,
+            "TieredStore requires at least 2 tiers, got {}",
+            config.tiers.len()
+        );
+        error_if!(
+            config.tiers.len() != stores.len(),
+            "Number of stores does not match number of tiers in TieredStore"
+        );
+        let empty_policy = EvictionPolicy::default();
+        let eviction_policy = config
+            .hit_counter_eviction_policy
+            .as_ref()
+            .unwrap_or(&empty_policy);
+        let tiered_store = Arc::new(TieredStore {
+            tiers: config
+                .tiers
+                .iter()
+                .zip(stores)
+                .map(|(tier_config, store)| Tier {
+                    store,
+                    promotion_hit_count: tier_config.promotion_hit_count,
+                    max_promotion_size: tier_config.max_promotion_size,
+                    skip_on_update: tier_config.skip_on_update,
+                })
+                .collect(),
+            hit_counters: EvictingMap::new(eviction_policy, SystemTime::now()),
+            hit_counter_insert_lock: AsyncMutex::new(()),
+            promotions: CounterWithTime::default(),
+            promotion_failures: CounterWithTime::default(),
+            demotions: CounterWithTime::default(),
+            demotion_failures: CounterWithTime::default(),
+        });
+
+        if !config.tiers.iter().any(|tier| tier.demote_on_eviction) {
+            return Ok(tiered_store);
+        }
+        // Evicted objects are demoted one at a time by a single task.
+        let (demotion_tx, mut demotion_rx) = mpsc::channel(MAX_PENDING_DEMOTIONS);
+        for (tier_index, tier_config) in config.tiers.iter().enumerate() {
+            if !tier_config.demote_on_eviction {
+                continue;
+            }
+            error_if!(
+                tier_index + 1 == config.tiers.len(),
+                "demote_on_eviction can not be set on the last tier of TieredStore"
+            );
+            let weak_store = Arc::downgrade(&tiered_store);
+            let demotion_tx = demotion_tx.clone();
+            tiered_store.tiers[tier_index]
+                .store
+                .clone()
+                .register_eviction_callback(Box::new(move |digest: DigestInfo, data| {
+                    if demotion_tx.try_send((tier_index + 1, digest, data)).is_ok() {
+                        return;
+                    }
+                    if let Some(tiered_store) = weak_store.upgrade() {
+                        tiered_store.demotion_failures.inc();
+                        warn!(
+                            "Demotion queue of TieredStore is full, not demoting {}",
+                            digest.hash_str()
+                        );
+                    }
+                }))
+                .err_tip(|| {
+                    format!("While enabling demote_on_eviction on tier {tier_index} of TieredStore")
+                })?;
+        }
+        let weak_store = Arc::downgrade(&tiered_store);
+        tokio::spawn(async move {
+            while let Some((tier_index, digest, data)) = demotion_rx.recv().await {
+                let Some(tiered_store) = weak_store.upgrade() else {
+                    return;
+                };
+                tiered_store.demote(tier_index, digest, data).await;
+            }
+        });
+        Ok(tiered_store)
+    }
+
+    /// Writes an object that was evicted from a faster tier into `tier_index`
+    /// if it does not already exist there.
+    async fn demote(&self, tier_index: usize, digest: DigestInfo, data: Bytes) {
+        let tier_store = self.tiers[tier_index].pin_store();
+        let result = async {
+            if tier_store.has(digest).await?.is_some() {
+                return Ok(false);
+            }
+            tier_store.update_oneshot(digest, data).await?;
+            Ok::<_, Error>(true)
+        }
+        .await;
+        match result {
+            Ok(true) => self.demotions.inc(),
+            Ok(false) => {}
+            Err(e) => {
+                self.demotion_failures.inc();
+                warn!(
+                    "Failed to demote {} to tier {tier_index} in TieredStore : {e:?}",
+                    digest.hash_str()
+                );
+            }
+        }
+    }
+
+    /// Records that `digest` was read from a tier other than the fastest one
+    /// and returns the number of times this has happened.
+    async fn record_hit(&self, digest: &DigestInfo) -> u32 {
+        if let Some(hit_counter) = self.hit_counters.get(digest).await {
+            return hit_counter.0.fetch_add(1, Ordering::Relaxed) + 1;
+        }
+        let _insert_guard = self.hit_counter_insert_lock.lock().await;
+        // Another read may have inserted the counter while we waited.
+        if let Some(hit_counter) = self.hit_counters.get(digest).await {
+            return hit_counter.0.fetch_add(1, Ordering::Relaxed) + 1;
+        }
+        self.hit_counters
+            .insert(*digest, HitCounter(Arc::new(AtomicU32::new(1))))
+            .await;
+        1
+    }
+
+    /// Returns the index of the fastest tier holding `digest` and the size
+    /// of the object in that tier.
+    async fn find_tier(&self, digest: DigestInfo) -> Result<Option<(usize, usize)>, Error> {
+        for (tier_index, tier) in self.tiers.iter().enumerate() {
+            if !tier.serves_downloads() {
+                continue;
+            }
+            let maybe_size =
+                tier.pin_store().has(digest).await.err_tip(|| {
+                    format!("Failed to run has() on tier {tier_index} of TieredStore")
+                })?;
+            if let Some(size) = maybe_size {
+                return Ok(Some((tier_index, size)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[async_trait]
+impl Store for TieredStore {
+    async fn has_with_results(
+        self: Pin<&Self>,
+        digests: &[DigestInfo],
+        results: &mut [Option<usize>],
+    ) -> Result<(), Error> {
+        results.iter_mut().for_each(|result| *result = None);
+        let mut remaining: Vec<usize> = (0..digests.len()).collect();
+        for (tier_index, tier) in self.tiers.iter().enumerate() {
+            if remaining.is_empty() {
+                break;
+            }
+            if !tier.serves_downloads() {
+                continue;
+            }
+            let tier_digests: Vec<DigestInfo> = remaining.iter().map(|i| digests[*i]).collect();
+            let tier_results = tier.pin_store().has_many(&tier_digests).await.err_tip(|| {
+                format!("Failed to run has_many() on tier {tier_index} of TieredStore")
+            })?;
+            remaining = remaining
+                .into_iter()
+                .zip(tier_results)
+                .filter_map(|(i, tier_result)| {
+                    if tier_result.is_some() {
+                        results[i] = tier_result;
+                        return None;
+                    }
+                    Some(i)
+                })
+                .collect();
+        }
+        Ok(())
+    }
+
+    async fn update(
+        self: Pin<&Self>,
+        digest: DigestInfo,
+        mut reader: DropCloserReadHalf,
+        size_info: UploadSizeInfo,
+    ) -> Result<(), Error> {
+        let mut update_tiers: Vec<&Tier> = self
+            .tiers
+            .iter()
+            .filter(|tier| !tier.skip_on_update && tier.accepts_updates(digest))
+            .collect();
+        error_if!(
+            update_tiers.is_empty(),
+            "No tier of TieredStore accepts updates"
+        );
+        if update_tiers.len() == 1 {
+            return update_tiers
+                .pop()
+                .unwrap()
+                .pin_store()
+                .update(digest, reader, size_info)
+                .await;
+        }
+
+        let (mut txs, rxs): (Vec<_>, Vec<_>) =
+            update_tiers.iter().map(|_| make_buf_channel_pair()).unzip();
+        let data_stream_fut = async move {
+            loop {
+                let buffer = reader
+                    .recv()
+                    .await
+                    .err_tip(|| "Failed to read buffer in TieredStore::update")?;
+                if buffer.is_empty() {
+                    for tx in txs.iter_mut() {
+                        tx.send_eof()
+                            .await
+                            .err_tip(|| "Failed to write eof to tier in TieredStore::update")?;
+                    }
+                    return Result::<(), Error>::Ok(());
+                }
+                join_all(txs.iter_mut().map(|tx| tx.send(buffer.clone())))
+                    .await
+                    .into_iter()
+                    .try_for_each(|result| {
+                        result.err_tip(|| "Failed to send data to tier in TieredStore::update")
+                    })?;
+            }
+        };
+        let update_futs = join_all(
+            update_tiers
+                .iter()
+                .zip(rxs)
+                .map(|(tier, rx)| tier.pin_store().update(digest, rx, size_info)),
+        );
+
+        let (data_stream_res, update_results) = join!(data_stream_fut, update_futs);
+        update_results
+            .into_iter()
+            .fold(data_stream_res, |acc, result| acc.merge(result))
+    }
+
+    async fn get_part_ref(
+        self: Pin<&Self>,
+        digest: DigestInfo,
+        writer: &mut DropCloserWriteHalf,
+        offset: usize,
+        length: Option<usize>,
+    ) -> Result<(), Error> {
+        let (tier_index, size) = self.find_tier(digest).await?.ok_or_else(|| {
+            make_err!(
+                Code::NotFound,
+                "Object {} not found in any tier of TieredStore",
+                digest.hash_str()
+            )
+        })?;
+        let source_store = self.tiers[tier_index].pin_store();
+        if tier_index == 0 {
+            return source_store
+                .get_part_ref(digest, writer, offset, length)
+                .await;
+        }
+
+        let hit_count = self.record_hit(&digest).await;
+        let promote_tiers: Vec<&Tier> = self.tiers[..tier_index]
+            .iter()
+            .filter(|tier| tier.should_promote(digest, hit_count, size))
+            .collect();
+        if promote_tiers.is_empty() {
+            return source_store
+                .get_part_ref(digest, writer, offset, length)
+                .await;
+        }
+
+        // Promotions need the whole object, so we fetch everything from the
+        // source tier and only forward the requested range to the writer.
+        let send_range = offset..length.map_or(usize::MAX, |length| length + offset);
+        let (mut promote_txs, promote_rxs): (Vec<_>, Vec<_>) = promote_tiers
+            .iter()
+            .map(|_| {
+                let (tx, rx) = make_buf_channel_pair();
+                (Some(tx), rx)
+            })
+            .unzip();
+        let (source_tx, mut source_rx) = make_buf_channel_pair();
+        let data_stream_fut = async move {
+            let mut bytes_received: usize = 0;
+            loop {
+                let chunk = source_rx
+                    .recv()
+                    .await
+                    .err_tip(|| "Failed to read data from source tier in TieredStore")?;
+                if chunk.is_empty() {
+                    // A failure here will be reported by the promotion's update.
+                    for tx in promote_txs.iter_mut().flatten() {
+                        let _ = tx.send_eof().await;
+                    }
+                    // Our writer's EOF is sent after everything else is done.
+                    return Ok::<_, Error>(writer);
+                }
+                if let Some(range) = FastSlowStore::calculate_range(
+                    &(bytes_received..bytes_received + chunk.len()),
+                    &send_range,
+                ) {
+                    writer
+                        .send(chunk.slice(range))
+                        .await
+                        .err_tip(|| "Failed to write result to writer in TieredStore")?;
+                }
+                bytes_received += chunk.len();
+                // A failed promotion must never fail the read, so we stop
+                // feeding it instead.
+                join_all(promote_txs.iter_mut().map(|maybe_tx| {
+                    let chunk = chunk.clone();
+                    async move {
+                        let Some(tx) = maybe_tx else {
+                            return;
+                        };
+                        if tx.send(chunk).await.is_err() {
+                            *maybe_tx = None;
+                        }
+                    }
+                }))
+                .await;
+            }
+        };
+        let promote_futs = join_all(promote_tiers.iter().zip(promote_rxs).map(|(tier, rx)| {
+            tier.pin_store()
+                .update(digest, rx, UploadSizeInfo::ExactSize(size))
+        }));
+
+        let (data_stream_res, source_res, promote_results) = join!(
+            data_stream_fut,
+            source_store.get(digest, source_tx),
+            promote_futs
+        );
+        for result in promote_results {
+            match result {
+                Ok(()) => self.promotions.inc(),
+                Err(e) => {
+                    self.promotion_failures.inc();
+                    warn!(
+                        "Failed to promote {} in TieredStore : {e:?}",
+                        digest.hash_str()
+                    );
+                }
+            }
+        }
+        match data_stream_res {
+            // Sending the EOF may drop us right away, so it must be last.
+            Ok(writer) => source_res.merge(writer.send_eof().await),
+            Err(err) => source_res.merge(Err(err)),
+        }
+    }
+
+    async fn list(
+        self: Pin<&Self>,
+        handler: &mut (dyn FnMut(DigestInfo, usize) + Send),
+    ) -> Result<(), Error> {
+        for tier in &self.tiers {
+            Pin::new(tier.store.as_ref()).list(handler).await?;
+        }
+        Ok(())
+    }
+
+    async fn touch(self: Pin<&Self>, digests: &[DigestInfo]) -> Result<(), Error> {
+        for tier in &self.tiers {
+            Pin::new(tier.store.as_ref()).touch(digests).await?;
+        }
+        Ok(())
+    }
+
+    async fn pin(self: Pin<&Self>, digests: &[DigestInfo], ttl: Duration) -> Result<usize, Error> {
+        let mut pinned = 0;
+        for tier in &self.tiers {
+            pinned = pinned.max(Pin::new(tier.store.as_ref()).pin(digests, ttl).await?);
+        }
+        Ok(pinned)
+    }
+
+    fn inner_store(&self, _digest: Option<DigestInfo>) -> &'_ dyn Store {
+        self
+    }
+
+    fn inner_store_arc(self: Arc<Self>, _digest: Option<DigestInfo>) -> Arc<dyn Store> {
+        self
+    }
+
+    fn as_any<'a>(&'a self) -> &'a (dyn std::any::Any + Sync + Send + 'static) {
+        self
+    }
+
+    fn as_any_arc(self: Arc<Self>) -> Arc<dyn std::any::Any + Sync + Send + 'static> {
+        self
+    }
+
+    fn register_metrics(self: Arc<Self>, registry: &mut Registry) {
+        for (tier_index, tier) in self.tiers.iter().enumerate() {
+            let tier_registry = registry.sub_registry_with_prefix(format!("tier_{tier_index}"));
+            tier.store.clone().register_metrics(tier_registry);
+        }
+        registry.register_collector(Box::new(Collector::new(&self)));
+    }
+
+    fn register_health(self: Arc<Self>, registry: &mut HealthRegistryBuilder) {
+        for (tier_index, tier) in self.tiers.iter().enumerate() {
+            tier.store
+                .clone()
+                .register_health(&mut registry.sub_builder(format!("tier_{tier_index}").into()));
+        }
+    }
+}
+
+impl MetricsComponent for TieredStore {
+    fn gather_metrics(&self, c: &mut CollectorState) {
+        c.publish(
+            "promotions_total",
+            &self.promotions,
+            "Number of times an object was copied into a faster tier",
+        );
+        c.publish(
+            "promotion_failures_total",
+            &self.promotion_failures,
+            "Number of times copying an object into a faster tier failed",
+        );
+        c.publish(
+            "demotions_total",
+            &self.demotions,
+            "Number of times an evicted object was copied into a slower tier",
+        );
+        c.publish(
+            "demotion_failures_total",
+            &self.demotion_failures,
+            "Number of times copying an evicted object into a slower tier failed",
+        );
+        c.publish("hit_counters", &self.hit_counters, "");
+    }
+}
+
+default_health_status_indicator!(TieredStore);
@@ -14,29 +14,37 @@
 
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use nativelink_config::stores::ConfigDigestHashFunction;
-use nativelink_error::{make_input_err, Error, ResultExt};
+use nativelink_error::{make_err, make_input_err, Code, Error, ResultExt};
 use nativelink_util::buf_channel::{
     make_buf_channel_pair, DropCloserReadHalf, DropCloserWriteHalf,
 };
 use nativelink_util::common::DigestInfo;
-use nativelink_util::digest_hasher::{DigestHasher, DigestHasherFunc};
-use nativelink_util::health_utils::{default_health_status_indicator, HealthStatusIndicator};
+use nativelink_util::digest_hasher::{DigestHasher, DigestHasherFunc, DigestHasherImpl};
+use nativelink_util::health_utils::{
+    default_health_status_indicator, HealthRegistryBuilder, HealthStatusIndicator,
+};
 use nativelink_util::metrics_utils::{
     Collector, CollectorState, CounterWithTime, MetricsComponent, Registry,
 };
+use nativelink_util::request_context::RequestContext;
 use nativelink_util::store_trait::{Store, UploadSizeInfo};
+use tracing::warn;
 
 pub struct VerifyStore {
     inner_store: Arc<dyn Store>,
     verify_size: bool,
     hash_verification_function: Option<ConfigDigestHashFunction>,
+    verify_on_read: bool,
+    remove_corrupt_on_read: bool,
 
     // Metrics.
     size_verification_failures: CounterWithTime,
     hash_verification_failures: CounterWithTime,
+    read_verification_failures: CounterWithTime,
 }
 
 impl VerifyStore {
@@ -48,8 +56,11 @@ impl VerifyStore {
             inner_store,
             verify_size: config.verify_size,
             hash_verification_function: config.hash_verification_function,
+            verify_on_read: config.verify_on_read,
+            remove_corrupt_on_read: config.remove_corrupt_on_read,
             size_verification_failures: CounterWithTime::default(),
             hash_verification_failures: CounterWithTime::default(),
+            read_verification_failures: CounterWithTime::default(),
         }
     }
 
@@ -57,6 +68,21 @@ impl VerifyStore {
         Pin::new(self.inner_store.as_ref())
     }
 
+    /// Returns the hasher to verify data with if hash verification is
+    /// enabled, and whether it is the digest function of the request being
+    /// served. Outside of a request the configured function is used, which
+    /// may not be the one the digest was made with.
+    fn make_hasher(&self) -> Option<(DigestHasherImpl, bool)> {
+        let hash_verification_function = self.hash_verification_function?;
+        Some(RequestContext::with_current(|context| match context {
+            Some(context) => (context.digest_function.hasher(), true),
+            None => (
+                DigestHasherFunc::from(hash_verification_function).hasher(),
+                false,
+            ),
+        }))
+    }
+
     async fn inner_check_update<D: DigestHasher>(
         &self,
         mut tx: DropCloserWriteHalf,
@@ -115,6 +141,74 @@ impl VerifyStore {
         }
         Ok(())
     }
+
+    /// Streams `rx` into `tx`, verifying the data on the way. Errors come
+    /// with whether they prove the entry is corrupt. A hash mismatch only
+    /// does if `hasher_is_trusted`, ie: the hasher is the digest function
+    /// of the request.
+    async fn inner_check_read<D: DigestHasher>(
+        &self,
+        digest: DigestInfo,
+        mut rx: DropCloserReadHalf,
+        tx: &mut DropCloserWriteHalf,
+        mut maybe_hasher: Option<&mut D>,
+        hasher_is_trusted: bool,
+    ) -> Result<(), (Error, bool)> {
+        let mut sum_size: u64 = 0;
+        loop {
+            let chunk = rx
+                .recv()
+                .await
+                .err_tip(|| "Failed to read chunk in check_read in verify store")
+                .map_err(|err| (err, false))?;
+            sum_size += chunk.len() as u64;
+
+            if chunk.is_empty() {
+                // Is EOF. Only send it once the data is known to be good, so
+                // the reader never sees a successful read of corrupt data.
+                if self.verify_size && sum_size != digest.size_bytes as u64 {
+                    self.read_verification_failures.inc();
+                    return Err((
+                        make_err!(
+                            Code::DataLoss,
+                            "Expected size {} but got size {} on read",
+                            digest.size_bytes,
+                            sum_size
+                        ),
+                        true,
+                    ));
+                }
+                if let Some(hasher) = maybe_hasher {
+                    let hash_result: [u8; 32] = hasher.finalize_digest().packed_hash;
+                    if digest.packed_hash != hash_result {
+                        self.read_verification_failures.inc();
+                        return Err((
+                            make_err!(
+                                Code::DataLoss,
+                                "Hashes do not match on read, got: {} but digest hash was {}",
+                                hex::encode(hash_result),
+                                hex::encode(digest.packed_hash),
+                            ),
+                            hasher_is_trusted,
+                        ));
+                    }
+                }
+                return tx
+                    .send_eof()
+                    .await
+                    .err_tip(|| "In verify_store::check_read")
+                    .map_err(|err| (err, false));
+            }
+
+            if let Some(hasher) = maybe_hasher.as_mut() {
+                hasher.update(chunk.as_ref());
+            }
+            tx.send(chunk)
+                .await
+                .err_tip(|| "Failed to write chunk to reader in verify store")
+                .map_err(|err| (err, false))?;
+        }
+    }
 }
 
 #[async_trait]
@@ -146,9 +240,7 @@ impl Store for VerifyStore {
             }
         }
 
-        let mut hasher = self
-            .hash_verification_function
-            .map(|v| DigestHasherFunc::from(v).hasher());
+        let mut hasher = self.make_hasher().map(|(hasher, _)| hasher);
 
         let (tx, rx) = make_buf_channel_pair();
 
@@ -168,9 +260,59 @@ impl Store for VerifyStore {
         offset: usize,
         length: Option<usize>,
     ) -> Result<(), Error> {
-        self.pin_inner()
-            .get_part_ref(digest, writer, offset, length)
-            .await
+        let is_full_read =
+            offset == 0 && length.map_or(true, |length| length as u64 >= digest.size_bytes as u64);
+        if !self.verify_on_read || !is_full_read {
+            return self
+                .pin_inner()
+                .get_part_ref(digest, writer, offset, length)
+                .await;
+        }
+
+        let (mut hasher, hasher_is_trusted) = self
+            .make_hasher()
+            .map_or((None, false), |(hasher, is_trusted)| {
+                (Some(hasher), is_trusted)
+            });
+
+        let (tx, rx) = make_buf_channel_pair();
+
+        let get_fut = self.pin_inner().get_part(digest, tx, offset, length);
+        let check_fut =
+            self.inner_check_read(digest, rx, writer, hasher.as_mut(), hasher_is_trusted);
+
+        let (get_res, check_res) = tokio::join!(get_fut, check_fut);
+
+        let check_res = match check_res {
+            Ok(()) => Ok(()),
+            Err((err, is_corrupt)) => {
+                if self.remove_corrupt_on_read && is_corrupt {
+                    match self.pin_inner().remove(digest).await {
+                        Ok(_) => warn!("Removed corrupt entry {digest:?} from VerifyStore backend : {err:?}"),
+                        Err(remove_err) => warn!(
+                            "Could not remove corrupt entry {digest:?} from VerifyStore backend : {err:?} : {remove_err:?}"
+                        ),
+                    }
+                }
+                Err(err)
+            }
+        };
+        get_res.merge(check_res)
+    }
+
+    async fn list(
+        self: Pin<&Self>,
+        handler: &mut (dyn FnMut(DigestInfo, usize) + Send),
+    ) -> Result<(), Error> {
+        Pin::new(self.inner_store.as_ref()).list(handler).await
+    }
+
+    async fn touch(self: Pin<&Self>, digests: &[DigestInfo]) -> Result<(), Error> {
+        Pin::new(self.inner_store.as_ref()).touch(digests).await
+    }
+
+    async fn pin(self: Pin<&Self>, digests: &[DigestInfo], ttl: Duration) -> Result<usize, Error> {
+        Pin::new(self.inner_store.as_ref()).pin(digests, ttl).await
     }
 
     fn inner_store(&self, _digest: Option<DigestInfo>) -> &'_ dyn Store {
@@ -192,6 +334,12 @@ impl Store for VerifyStore {
     fn register_metrics(self: Arc<Self>, registry: &mut Registry) {
         registry.register_collector(Box::new(Collector::new(&self)));
     }
+
+    fn register_health(self: Arc<Self>, registry: &mut HealthRegistryBuilder) {
+        self.inner_store
+            .clone()
+            .register_health(&mut registry.sub_builder("inner_store".into()));
+    }
 }
 
 impl MetricsComponent for VerifyStore {
@@ -216,6 +364,16 @@ impl MetricsComponent for VerifyStore {
             &self.hash_verification_failures,
             "Number of failures the verification store had due to hash mismatches",
         );
+        c.publish(
+            "verify_on_read_enabled",
+            &self.verify_on_read,
+            "If the verification store is verifying data returned by reads",
+        );
+        c.publish(
+            "read_verification_failures_total",
+            &self.read_verification_failures,
+            "Number of reads the verification store failed due to size or hash mismatches",
+        );
     }
 }
 
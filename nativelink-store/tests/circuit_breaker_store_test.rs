@@ -0,0 +1,400 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use nativelink_config::stores::{CircuitBreakerStore as CircuitBreakerStoreConfig, StoreConfig};
+use nativelink_error::{make_err, Code, Error};
+use nativelink_store::circuit_breaker_store::CircuitBreakerStore;
+use nativelink_store::default_store_factory::store_factory;
+use nativelink_store::fast_slow_store::FastSlowStore;
+use nativelink_store::memory_store::MemoryStore;
+use nativelink_store::store_manager::StoreManager;
+use nativelink_util::buf_channel::{DropCloserReadHalf, DropCloserWriteHalf};
+use nativelink_util::common::DigestInfo;
+use nativelink_util::health_utils::{
+    default_health_status_indicator, HealthRegistryBuilder, HealthStatus, HealthStatusIndicator,
+    HealthStatusReporter,
+};
+use nativelink_util::metrics_utils::Registry;
+use nativelink_util::store_trait::{Store, UploadSizeInfo};
+
+/// Memory store that can be told to fail or delay every request.
+struct FlakyStore {
+    inner: MemoryStore,
+    fail: AtomicBool,
+    fail_code: Mutex<Code>,
+    delay_ms: AtomicU64,
+    calls: AtomicUsize,
+}
+
+impl FlakyStore {
+    fn new() -> Self {
+        Self {
+            inner: MemoryStore::new(&nativelink_config::stores::MemoryStore::default()),
+            fail: AtomicBool::new(false),
+            fail_code: Mutex::new(Code::Internal),
+            delay_ms: AtomicU64::new(0),
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    async fn check(&self) -> Result<(), Error> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        let delay_ms = self.delay_ms.load(Ordering::Relaxed);
+        if delay_ms > 0 {
+            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+        }
+        if self.fail.load(Ordering::Relaxed) {
+            let code = *self.fail_code.lock().unwrap();
+            return Err(make_err!(code, "FlakyStore is failing"));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Store for FlakyStore {
+    async fn has_with_results(
+        self: Pin<&Self>,
+        digests: &[DigestInfo],
+        results: &mut [Option<usize>],
+    ) -> Result<(), Error> {
+        self.check().await?;
+        Pin::new(&self.inner)
+            .has_with_results(digests, results)
+            .await
+    }
+
+    async fn update(
+        self: Pin<&Self>,
+        digest: DigestInfo,
+        reader: DropCloserReadHalf,
+        size_info: UploadSizeInfo,
+    ) -> Result<(), Error> {
+        self.check().await?;
+        Pin::new(&self.inner)
+            .update(digest, reader, size_info)
+            .await
+    }
+
+    async fn get_part_ref(
+        self: Pin<&Self>,
+        digest: DigestInfo,
+        writer: &mut DropCloserWriteHalf,
+        offset: usize,
+        length: Option<usize>,
+    ) -> Result<(), Error> {
+        self.check().await?;
+        Pin::new(&self.inner)
+            .get_part_ref(digest, writer, offset, length)
+            .await
+    }
+
+    fn inner_store(&self, _digest: Option<DigestInfo>) -> &'_ dyn Store {
+        self
+    }
+
+    fn inner_store_arc(self: Arc<Self>, _digest: Option<DigestInfo>) -> Arc<dyn Store> {
+        self
+    }
+
+    fn as_any(&self) -> &(dyn std::any::Any + Sync + Send + 'static) {
+        self
+    }
+
+    fn as_any_arc(self: Arc<Self>) -> Arc<dyn std::any::Any + Sync + Send + 'static> {
+        self
+    }
+
+    fn register_metrics(self: Arc<Self>, _registry: &mut Registry) {}
+}
+
+default_health_status_indicator!(FlakyStore);
+
+fn make_config() -> CircuitBreakerStoreConfig {
+    CircuitBreakerStoreConfig {
+        backend: StoreConfig::noop,
+        failure_rate_threshold: 50,
+        slow_call_threshold_ms: 0,
+        window_size: 4,
+        minimum_calls: 4,
+        open_duration_ms: 60_000,
+        half_open_calls: 2,
+    }
+}
+
+#[cfg(test)]
+mod circuit_breaker_store_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*; // Must be declared in every module.
+
+    const VALID_HASH1: &str = "0123456789abcdef000000000000000000010000000000000123456789abcdef";
+
+    async fn call_has(store: &CircuitBreakerStore) -> Result<Option<usize>, Error> {
+        Pin::new(store)
+            .has(DigestInfo::try_new(VALID_HASH1, 4)?)
+            .await
+    }
+
+    #[tokio::test]
+    async fn opens_after_failure_rate_is_reached() -> Result<(), Error> {
+        let backend = Arc::new(FlakyStore::new());
+        let store = CircuitBreakerStore::new(&make_config(), backend.clone())?;
+
+        call_has(&store).await?;
+        call_has(&store).await?;
+        backend.fail.store(true, Ordering::Relaxed);
+        assert!(call_has(&store).await.is_err());
+        assert!(!store.is_open(), "Expected circuit to still be closed");
+        assert!(call_has(&store).await.is_err());
+        assert!(store.is_open(), "Expected circuit to be open");
+        assert_eq!(backend.calls.load(Ordering::Relaxed), 4);
+
+        // Requests now fail without reaching the backend.
+        let err = call_has(&store).await.unwrap_err();
+        assert_eq!(err.code, Code::Unavailable);
+        assert_eq!(backend.calls.load(Ordering::Relaxed), 4);
+        assert!(matches!(
+            store.check_health("".into()).await,
+            HealthStatus::Failed { .. }
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn not_found_is_not_a_failure() -> Result<(), Error> {
+        let store = CircuitBreakerStore::new(&make_config(), Arc::new(FlakyStore::new()))?;
+        let digest = DigestInfo::try_new(VALID_HASH1, 4)?;
+        for _ in 0..10 {
+            let err = Pin::new(&store)
+                .get_part_unchunked(digest, 0, None, None)
+                .await
+                .unwrap_err();
+            assert_eq!(err.code, Code::NotFound);
+        }
+        assert!(!store.is_open(), "Expected circuit to still be closed");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_failures() -> Result<(), Error> {
+        let backend = Arc::new(FlakyStore::new());
+        let store = CircuitBreakerStore::new(&make_config(), backend.clone())?;
+        backend.fail.store(true, Ordering::Relaxed);
+        for code in [Code::InvalidArgument, Code::DataLoss, Code::Aborted] {
+            *backend.fail_code.lock().unwrap() = code;
+            for _ in 0..4 {
+                assert_eq!(call_has(&store).await.unwrap_err().code, code);
+            }
+        }
+        assert!(!store.is_open(), "Expected circuit to still be closed");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn half_open_closes_after_successful_trials() -> Result<(), Error> {
+        let backend = Arc::new(FlakyStore::new());
+        let store = CircuitBreakerStore::new(
+            &CircuitBreakerStoreConfig {
+                open_duration_ms: 10,
+                ..make_config()
+            },
+            backend.clone(),
+        )?;
+        backend.fail.store(true, Ordering::Relaxed);
+        for _ in 0..4 {
+            assert!(call_has(&store).await.is_err());
+        }
+        assert!(store.is_open(), "Expected circuit to be open");
+
+        backend.fail.store(false, Ordering::Relaxed);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        call_has(&store).await?;
+        assert!(matches!(
+            store.check_health("".into()).await,
+            HealthStatus::Warning { .. }
+        ));
+        call_has(&store).await?;
+        assert!(matches!(
+            store.check_health("".into()).await,
+            HealthStatus::Ok { .. }
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn half_open_reopens_on_failed_trial() -> Result<(), Error> {
+        let backend = Arc::new(FlakyStore::new());
+        let store = CircuitBreakerStore::new(
+            &CircuitBreakerStoreConfig {
+                open_duration_ms: 10,
+                ..make_config()
+            },
+            backend.clone(),
+        )?;
+        backend.fail.store(true, Ordering::Relaxed);
+        for _ in 0..4 {
+            assert!(call_has(&store).await.is_err());
+        }
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!store.is_open(), "Expected circuit to allow trial requests");
+        assert_eq!(call_has(&store).await.unwrap_err().code, Code::Internal);
+        assert!(store.is_open(), "Expected circuit to be open again");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn requests_from_before_half_open_are_not_trials() -> Result<(), Error> {
+        let backend = Arc::new(FlakyStore::new());
+        let store = CircuitBreakerStore::new(
+            &CircuitBreakerStoreConfig {
+                open_duration_ms: 10,
+                ..make_config()
+            },
+            backend.clone(),
+        )?;
+
+        // Started while the circuit is closed, finishes after the first of
+        // the two trial requests.
+        backend.delay_ms.store(500, Ordering::Relaxed);
+        let slow_request = call_has(&store);
+        let open_and_trial = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            backend.delay_ms.store(0, Ordering::Relaxed);
+            backend.fail.store(true, Ordering::Relaxed);
+            for _ in 0..4 {
+                assert!(call_has(&store).await.is_err());
+            }
+            assert!(store.is_open(), "Expected circuit to be open");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            backend.fail.store(false, Ordering::Relaxed);
+            call_has(&store).await
+        };
+        let (slow_result, trial_result) = tokio::join!(slow_request, open_and_trial);
+        slow_result?;
+        trial_result?;
+
+        assert!(matches!(
+            store.check_health("".into()).await,
+            HealthStatus::Warning { .. }
+        ));
+        call_has(&store).await?;
+        assert!(matches!(
+            store.check_health("".into()).await,
+            HealthStatus::Ok { .. }
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fast_slow_store_returns_other_slow_store_errors() -> Result<(), Error> {
+        let fast_store = Arc::new(MemoryStore::new(
+            &nativelink_config::stores::MemoryStore::default(),
+        ));
+        let slow_store = Arc::new(FlakyStore::new());
+        let fast_slow_store = FastSlowStore::new(
+            &nativelink_config::stores::FastSlowStore {
+                fast: StoreConfig::memory(nativelink_config::stores::MemoryStore::default()),
+                slow: StoreConfig::noop,
+            },
+            fast_store.clone(),
+            slow_store.clone(),
+        );
+        let digest = DigestInfo::try_new(VALID_HASH1, 4)?;
+        Pin::new(fast_store.as_ref())
+            .update_oneshot(digest, "data".into())
+            .await?;
+
+        slow_store.fail.store(true, Ordering::Relaxed);
+        *slow_store.fail_code.lock().unwrap() = Code::Unavailable;
+        let err = Pin::new(&fast_slow_store).has(digest).await.unwrap_err();
+        assert_eq!(err.code, Code::Unavailable);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn nested_circuit_breaker_reports_health() -> Result<(), Error> {
+        let store_config =
+            StoreConfig::fast_slow(Box::new(nativelink_config::stores::FastSlowStore {
+                fast: StoreConfig::memory(nativelink_config::stores::MemoryStore::default()),
+                slow: StoreConfig::circuit_breaker(Box::new(CircuitBreakerStoreConfig {
+                    backend: StoreConfig::memory(nativelink_config::stores::MemoryStore::default()),
+                    ..make_config()
+                })),
+            }));
+        let mut health_registry_builder = HealthRegistryBuilder::new("nativelink".into());
+        store_factory(
+            &store_config,
+            &Arc::new(StoreManager::new()),
+            None,
+            Some(&mut health_registry_builder.sub_builder("stores/CAS".into())),
+        )
+        .await?;
+
+        let health_registry = health_registry_builder.build();
+        let report: Vec<_> = health_registry.health_status_report().collect().await;
+        assert_eq!(report.len(), 1);
+        assert_eq!(
+            report[0].namespace,
+            "/nativelink/stores/CAS/slow/CircuitBreakerStore"
+        );
+        assert!(matches!(report[0].status, HealthStatus::Ok { .. }));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fast_slow_store_uses_fast_store_while_open() -> Result<(), Error> {
+        let fast_store = Arc::new(MemoryStore::new(
+            &nativelink_config::stores::MemoryStore::default(),
+        ));
+        let backend = Arc::new(FlakyStore::new());
+        let slow_store = Arc::new(CircuitBreakerStore::new(&make_config(), backend.clone())?);
+        let fast_slow_store = FastSlowStore::new(
+            &nativelink_config::stores::FastSlowStore {
+                fast: StoreConfig::memory(nativelink_config::stores::MemoryStore::default()),
+                slow: StoreConfig::noop,
+            },
+            fast_store.clone(),
+            slow_store.clone(),
+        );
+        let digest = DigestInfo::try_new(VALID_HASH1, 4)?;
+        Pin::new(fast_store.as_ref())
+            .update_oneshot(digest, "data".into())
+            .await?;
+
+        backend.fail.store(true, Ordering::Relaxed);
+        for _ in 0..4 {
+            assert!(call_has(&slow_store).await.is_err());
+        }
+        assert!(slow_store.is_open(), "Expected circuit to be open");
+
+        let fast_slow_store = Pin::new(&fast_slow_store);
+        assert_eq!(fast_slow_store.has(digest).await?, Some(4));
+        assert_eq!(
+            fast_slow_store
+                .get_part_unchunked(digest, 0, None, None)
+                .await?,
+            "data"
+        );
+        Ok(())
+    }
+}
@@ -15,7 +15,10 @@
 use std::pin::Pin;
 use std::sync::Arc;
 
-use nativelink_config::stores::MemoryStore as MemoryStoreConfig;
+use nativelink_config::stores::{
+    CompletenessCheckingStore as CompletenessCheckingStoreConfig, MemoryStore as MemoryStoreConfig,
+    StoreConfig,
+};
 use nativelink_error::Error;
 use nativelink_proto::build::bazel::remote::execution::v2::{
     ActionResult as ProtoActionResult, Directory, DirectoryNode, FileNode, OutputDirectory,
@@ -44,6 +47,12 @@ mod completeness_checking_store_tests {
         let backend_store = Arc::new(MemoryStore::new(&MemoryStoreConfig::default()));
         let cas_store = Arc::new(MemoryStore::new(&MemoryStoreConfig::default()));
         let ac_owned = Arc::new(CompletenessCheckingStore::new(
+            &CompletenessCheckingStoreConfig {
+                backend: StoreConfig::noop,
+                cas_store: StoreConfig::noop,
+                pin_outputs_seconds: 0,
+                refresh_outputs: false,
+            },
             backend_store.clone(),
             cas_store.clone(),
         ));
@@ -15,11 +15,14 @@
 use std::pin::Pin;
 use std::sync::Arc;
 
-use nativelink_error::{Code, Error, ResultExt};
+use nativelink_error::{make_err, Code, Error, ResultExt};
 use nativelink_store::dedup_store::DedupStore;
 use nativelink_store::memory_store::MemoryStore;
+use nativelink_store::noop_store::NoopStore;
 use nativelink_util::common::DigestInfo;
+use nativelink_util::metrics_utils::Registry;
 use nativelink_util::store_trait::Store;
+use prometheus_client::encoding::text::encode;
 use rand::rngs::SmallRng;
 use rand::{Rng, SeedableRng};
 
@@ -35,16 +38,47 @@ fn make_default_config() -> nativelink_config::stores::DedupStore {
         normal_size: 32 * 1024,
         max_size: 128 * 1024,
         max_concurrent_fetch_per_get: 10,
+        track_chunk_references: false,
     }
 }
 
 fn make_random_data(sz: usize) -> Vec<u8> {
+    make_seeded_random_data(sz, 1)
+}
+
+fn make_seeded_random_data(sz: usize, seed: u64) -> Vec<u8> {
     let mut value = vec![0u8; sz];
-    let mut rng = SmallRng::seed_from_u64(1);
+    let mut rng = SmallRng::seed_from_u64(seed);
     rng.fill(&mut value[..]);
     value
 }
 
+fn make_memory_store(max_count: u64) -> Arc<MemoryStore> {
+    Arc::new(MemoryStore::new(&nativelink_config::stores::MemoryStore {
+        eviction_policy: Some(nativelink_config::stores::EvictionPolicy {
+            max_count,
+            ..Default::default()
+        }),
+        ..Default::default()
+    }))
+}
+
+/// Waits for the task that handles evictions in the chunk tracker to remove
+/// `digest` from `store`.
+async fn wait_for_removal(store: &MemoryStore, digest: DigestInfo) -> Result<(), Error> {
+    for _ in 0..100 {
+        if Pin::new(store).has(digest).await?.is_none() {
+            return Ok(());
+        }
+        tokio::task::yield_now().await;
+    }
+    Err(make_err!(
+        Code::DeadlineExceeded,
+        "{} was never removed from the store",
+        digest.hash_str()
+    ))
+}
+
 #[cfg(test)]
 mod dedup_store_tests {
     use pretty_assertions::assert_eq;
@@ -65,7 +99,7 @@ mod dedup_store_tests {
             Arc::new(MemoryStore::new(
                 &nativelink_config::stores::MemoryStore::default(),
             )), // Content store.
-        );
+        )?;
         let store = Pin::new(&store_owned);
 
         let original_data = make_random_data(MEGABYTE_SZ);
@@ -96,7 +130,7 @@ mod dedup_store_tests {
                 &nativelink_config::stores::MemoryStore::default(),
             )), // Index store.
             content_store.clone(),
-        );
+        )?;
         let store = Pin::new(&store_owned);
 
         let original_data = make_random_data(MEGABYTE_SZ);
@@ -141,7 +175,7 @@ mod dedup_store_tests {
             Arc::new(MemoryStore::new(
                 &nativelink_config::stores::MemoryStore::default(),
             )), // Content store.
-        );
+        )?;
         let store = Pin::new(&store_owned);
 
         const DATA_SIZE: usize = MEGABYTE_SZ / 4;
@@ -187,6 +221,7 @@ mod dedup_store_tests {
                 normal_size: 6,
                 max_size: 7,
                 max_concurrent_fetch_per_get: 10,
+                track_chunk_references: false,
             },
             Arc::new(MemoryStore::new(
                 &nativelink_config::stores::MemoryStore::default(),
@@ -194,7 +229,7 @@ mod dedup_store_tests {
             Arc::new(MemoryStore::new(
                 &nativelink_config::stores::MemoryStore::default(),
             )), // Content store.
-        );
+        )?;
         let store = Pin::new(&store_owned);
 
         const DATA_SIZE: usize = 30;
@@ -240,6 +275,7 @@ mod dedup_store_tests {
                 normal_size: 6,
                 max_size: 7,
                 max_concurrent_fetch_per_get: 10,
+                track_chunk_references: false,
             },
             Arc::new(MemoryStore::new(
                 &nativelink_config::stores::MemoryStore::default(),
@@ -247,7 +283,7 @@ mod dedup_store_tests {
             Arc::new(MemoryStore::new(
                 &nativelink_config::stores::MemoryStore::default(),
             )), // Content store.
-        );
+        )?;
         let store = Pin::new(&store_owned);
 
         const DATA_SIZE: usize = 30;
@@ -315,13 +351,14 @@ mod dedup_store_tests {
                 max_count: 10,
                 ..Default::default()
             }),
+            ..Default::default()
         }));
 
         let store = DedupStore::new(
             &make_default_config(),
             index_store.clone(),
             content_store.clone(),
-        );
+        )?;
         let store_pin = Pin::new(&store);
 
         const DATA_SIZE: usize = MEGABYTE_SZ / 4;
@@ -388,13 +425,14 @@ mod dedup_store_tests {
                 max_count: 10,
                 ..Default::default()
             }),
+            ..Default::default()
         }));
 
         let store = DedupStore::new(
             &make_default_config(),
             index_store.clone(),
             content_store.clone(),
-        );
+        )?;
         let store_pin = Pin::new(&store);
 
         const DATA_SIZE: usize = 10;
@@ -413,4 +451,212 @@ mod dedup_store_tests {
         }
         Ok(())
     }
+
+    /// Chunks can be read directly after a blob is split, but `.has()` only
+    /// reports blobs that have an index.
+    #[tokio::test]
+    async fn chunks_are_readable_but_not_reported_by_has() -> Result<(), Error> {
+        let store = DedupStore::new(
+            &make_default_config(),
+            make_memory_store(0),
+            make_memory_store(0),
+        )?;
+        let store = Pin::new(&store);
+
+        let data = make_seeded_random_data(256 * 1024, 1);
+        let digest = DigestInfo::try_new(VALID_HASH1, data.len())?;
+        store.update_oneshot(digest, data.clone().into()).await?;
+
+        let chunks = store.get_chunk_digests(digest).await?;
+        let sizes = store.has_chunks(&chunks).await?;
+        assert!(
+            sizes.iter().all(Option::is_some),
+            "Expected every chunk to exist"
+        );
+        let chunk = chunks[0];
+        assert_eq!(store.has(chunk).await?, None);
+        let chunk_data = store.get_part_unchunked(chunk, 0, None, None).await?;
+        assert_eq!(chunk_data, &data[..chunk_data.len()]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn tracking_requires_eviction_callbacks() -> Result<(), Error> {
+        let result = DedupStore::new(
+            &nativelink_config::stores::DedupStore {
+                track_chunk_references: true,
+                ..make_default_config()
+            },
+            make_memory_store(0),
+            Arc::new(NoopStore::new()),
+        );
+        assert_eq!(result.err().map(|err| err.code), Some(Code::Unimplemented));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn evicted_index_removes_orphaned_chunks() -> Result<(), Error> {
+        let index_store = make_memory_store(1);
+        let content_store = make_memory_store(0);
+        let store = DedupStore::new(
+            &nativelink_config::stores::DedupStore {
+                track_chunk_references: true,
+                ..make_default_config()
+            },
+            index_store.clone(),
+            content_store.clone(),
+        )?;
+        let store = Pin::new(&store);
+        let digest1 = DigestInfo::try_new(VALID_HASH1, 256 * 1024)?;
+        let digest2 = DigestInfo::try_new(VALID_HASH2, 256 * 1024)?;
+        let data2 = make_seeded_random_data(256 * 1024, 2);
+
+        store
+            .update_oneshot(digest1, make_seeded_random_data(256 * 1024, 1).into())
+            .await?;
+        let chunks1 = store.get_chunk_digests(digest1).await?;
+        // Evicts the index of `digest1` from the index store.
+        store.update_oneshot(digest2, data2.clone().into()).await?;
+
+        for chunk in chunks1 {
+            wait_for_removal(&content_store, chunk).await?;
+        }
+        assert_eq!(store.has(digest1).await?, None);
+        assert_eq!(
+            store.get_part_unchunked(digest2, 0, None, None).await?,
+            data2
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn shared_chunks_are_not_removed() -> Result<(), Error> {
+        let index_store = make_memory_store(1);
+        let content_store = make_memory_store(0);
+        let store = DedupStore::new(
+            &nativelink_config::stores::DedupStore {
+                track_chunk_references: true,
+                ..make_default_config()
+            },
+            index_store.clone(),
+            content_store.clone(),
+        )?;
+        let store = Pin::new(&store);
+        let data1 = make_seeded_random_data(256 * 1024, 1);
+        let data2 = [data1.clone(), make_seeded_random_data(64 * 1024, 2)].concat();
+        let digest1 = DigestInfo::try_new(VALID_HASH1, data1.len())?;
+        let digest2 = DigestInfo::try_new(VALID_HASH2, data2.len())?;
+
+        store.update_oneshot(digest1, data1.into()).await?;
+        let chunks1 = store.get_chunk_digests(digest1).await?;
+        // Evicts the index of `digest1` from the index store.
+        store.update_oneshot(digest2, data2.clone().into()).await?;
+        let chunks2 = store.get_chunk_digests(digest2).await?;
+
+        let (shared_chunks, orphaned_chunks): (Vec<_>, Vec<_>) = chunks1
+            .into_iter()
+            .partition(|chunk| chunks2.contains(chunk));
+        assert!(
+            !shared_chunks.is_empty(),
+            "Expected some chunks to be shared"
+        );
+        assert!(
+            !orphaned_chunks.is_empty(),
+            "Expected some chunks to differ"
+        );
+        for chunk in orphaned_chunks {
+            wait_for_removal(&content_store, chunk).await?;
+        }
+        for chunk in shared_chunks {
+            assert!(
+                Pin::new(content_store.as_ref()).has(chunk).await?.is_some(),
+                "Expected shared chunk to still exist"
+            );
+        }
+        assert_eq!(
+            store.get_part_unchunked(digest2, 0, None, None).await?,
+            data2
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn evicted_chunk_removes_broken_index() -> Result<(), Error> {
+        let data1 = make_seeded_random_data(256 * 1024, 1);
+        let digest1 = DigestInfo::try_new(VALID_HASH1, data1.len())?;
+        let chunk_count = {
+            let store = DedupStore::new(
+                &make_default_config(),
+                make_memory_store(0),
+                make_memory_store(0),
+            )?;
+            let store = Pin::new(&store);
+            store.update_oneshot(digest1, data1.clone().into()).await?;
+            store.get_chunk_digests(digest1).await?.len()
+        };
+
+        let index_store = make_memory_store(0);
+        let content_store = make_memory_store(chunk_count as u64);
+        let store = DedupStore::new(
+            &nativelink_config::stores::DedupStore {
+                track_chunk_references: true,
+                ..make_default_config()
+            },
+            index_store.clone(),
+            content_store.clone(),
+        )?;
+        let store = Pin::new(&store);
+        store.update_oneshot(digest1, data1.into()).await?;
+        assert_eq!(store.has(digest1).await?, Some(256 * 1024));
+
+        // A single chunk that evicts the oldest chunk of `digest1`.
+        let data2 = make_seeded_random_data(100, 2);
+        let digest2 = DigestInfo::try_new(VALID_HASH2, data2.len())?;
+        store.update_oneshot(digest2, data2.clone().into()).await?;
+
+        wait_for_removal(&index_store, digest1).await?;
+        assert_eq!(store.has(digest1).await?, None);
+        assert_eq!(
+            store.get_part_unchunked(digest2, 0, None, None).await?,
+            data2
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn metrics_report_tracked_sizes() -> Result<(), Error> {
+        let store = Arc::new(DedupStore::new(
+            &nativelink_config::stores::DedupStore {
+                track_chunk_references: true,
+                ..make_default_config()
+            },
+            make_memory_store(0),
+            make_memory_store(0),
+        )?);
+        let mut registry = <Registry>::default();
+        store.clone().register_metrics(&mut registry);
+
+        // The same data under two digests shares all of its chunks.
+        let data = make_seeded_random_data(256 * 1024, 1);
+        for hash in [VALID_HASH1, VALID_HASH2] {
+            Pin::new(store.as_ref())
+                .update_oneshot(DigestInfo::try_new(hash, data.len())?, data.clone().into())
+                .await?;
+        }
+
+        let mut metrics = String::new();
+        encode(&mut metrics, &registry).unwrap();
+        for expected in [
+            "\nlogical_bytes 524288\n",
+            "\nreferenced_chunk_bytes 262144\n",
+            "\ndedup_ratio 2.0\n",
+            "\ntracked_indexes 2\n",
+        ] {
+            assert!(
+                metrics.contains(expected),
+                "Expected {expected:?} in metrics:\n{metrics}"
+            );
+        }
+        Ok(())
+    }
 }
@@ -15,7 +15,9 @@
 use std::pin::Pin;
 use std::sync::Arc;
 
-use nativelink_config::stores::{ExistenceCacheStore as ExistenceCacheStoreConfig, StoreConfig};
+use nativelink_config::stores::{
+    BloomFilterConfig, ExistenceCacheStore as ExistenceCacheStoreConfig, StoreConfig,
+};
 use nativelink_error::{Error, ResultExt};
 use nativelink_store::existence_cache_store::ExistenceCacheStore;
 use nativelink_store::memory_store::MemoryStore;
@@ -27,6 +29,7 @@ mod verify_store_tests {
     use super::*;
 
     const VALID_HASH1: &str = "0123456789abcdef000000000000000000010000000000000123456789abcdef";
+    const VALID_HASH2: &str = "0123456789abcdef000000000000000000020000000000000123456789abcdef";
 
     #[tokio::test]
     async fn simple_exist_cache_test() -> Result<(), Error> {
@@ -34,6 +37,9 @@ mod verify_store_tests {
         let config = ExistenceCacheStoreConfig {
             backend: StoreConfig::noop, // Note: Not used.
             eviction_policy: Default::default(),
+            negative_cache_seconds: 0,
+            negative_cache_max_items: 0,
+            bloom_filter: None,
         };
         let inner_store = Arc::new(MemoryStore::new(
             &nativelink_config::stores::MemoryStore::default(),
@@ -75,6 +81,9 @@ mod verify_store_tests {
         let config = ExistenceCacheStoreConfig {
             backend: StoreConfig::noop,
             eviction_policy: Default::default(),
+            negative_cache_seconds: 0,
+            negative_cache_max_items: 0,
+            bloom_filter: None,
         };
         let inner_store = Arc::new(MemoryStore::new(
             &nativelink_config::stores::MemoryStore::default(),
@@ -100,6 +109,9 @@ mod verify_store_tests {
         let config = ExistenceCacheStoreConfig {
             backend: StoreConfig::noop,
             eviction_policy: Default::default(),
+            negative_cache_seconds: 0,
+            negative_cache_max_items: 0,
+            bloom_filter: None,
         };
         let inner_store = Arc::new(MemoryStore::new(
             &nativelink_config::stores::MemoryStore::default(),
@@ -122,4 +134,129 @@ mod verify_store_tests {
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn negative_cache_skips_backend_until_update() -> Result<(), Error> {
+        const VALUE: &str = "123";
+        let config = ExistenceCacheStoreConfig {
+            backend: StoreConfig::noop,
+            eviction_policy: Default::default(),
+            negative_cache_seconds: 60,
+            negative_cache_max_items: 0,
+            bloom_filter: None,
+        };
+        let inner_store = Arc::new(MemoryStore::new(
+            &nativelink_config::stores::MemoryStore::default(),
+        ));
+        let store_owned = ExistenceCacheStore::new(&config, inner_store.clone());
+        let store = Pin::new(&store_owned);
+
+        let digest = DigestInfo::try_new(VALID_HASH1, 3).unwrap();
+        assert_eq!(store.has(digest).await?, None);
+
+        // Added behind the back of the existence cache, so the negative
+        // cache still answers.
+        Pin::new(inner_store.as_ref())
+            .update_oneshot(digest, VALUE.into())
+            .await
+            .err_tip(|| "Failed to update inner store")?;
+        assert_eq!(store.has(digest).await?, None);
+
+        store
+            .update_oneshot(digest, VALUE.into())
+            .await
+            .err_tip(|| "Failed to update store")?;
+        assert_eq!(store.has(digest).await?, Some(VALUE.len()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn preloaded_bloom_filter_reports_missing() -> Result<(), Error> {
+        const VALUE: &str = "123";
+        let config = ExistenceCacheStoreConfig {
+            backend: StoreConfig::noop,
+            eviction_policy: Default::default(),
+            negative_cache_seconds: 0,
+            negative_cache_max_items: 0,
+            bloom_filter: Some(BloomFilterConfig {
+                expected_items: 1000,
+                false_positive_rate: 0.,
+                preload: true,
+            }),
+        };
+        let inner_store = Arc::new(MemoryStore::new(
+            &nativelink_config::stores::MemoryStore::default(),
+        ));
+        let digest1 = DigestInfo::try_new(VALID_HASH1, 3).unwrap();
+        let digest2 = DigestInfo::try_new(VALID_HASH2, 3).unwrap();
+        Pin::new(inner_store.as_ref())
+            .update_oneshot(digest1, VALUE.into())
+            .await
+            .err_tip(|| "Failed to update inner store")?;
+        let store_owned = ExistenceCacheStore::new(&config, inner_store.clone());
+        let store = Pin::new(&store_owned);
+        while !store.bloom_filter_ready() {
+            tokio::task::yield_now().await;
+        }
+
+        // Added after the preload, so the bloom filter does not know it.
+        Pin::new(inner_store.as_ref())
+            .update_oneshot(digest2, VALUE.into())
+            .await
+            .err_tip(|| "Failed to update inner store")?;
+        assert_eq!(
+            store.has_many(&[digest1, digest2]).await?,
+            vec![Some(VALUE.len()), None]
+        );
+
+        // Uploads through the store are added to the bloom filter.
+        store
+            .update_oneshot(digest2, VALUE.into())
+            .await
+            .err_tip(|| "Failed to update store")?;
+        assert_eq!(store.has(digest2).await?, Some(VALUE.len()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn bloom_filter_without_preload_falls_through_until_populated() -> Result<(), Error> {
+        const VALUE: &str = "123";
+        let config = ExistenceCacheStoreConfig {
+            backend: StoreConfig::noop,
+            eviction_policy: Default::default(),
+            negative_cache_seconds: 0,
+            negative_cache_max_items: 0,
+            bloom_filter: Some(BloomFilterConfig {
+                expected_items: 1000,
+                false_positive_rate: 0.,
+                preload: false,
+            }),
+        };
+        let inner_store = Arc::new(MemoryStore::new(
+            &nativelink_config::stores::MemoryStore::default(),
+        ));
+        let digest1 = DigestInfo::try_new(VALID_HASH1, 3).unwrap();
+        let digest2 = DigestInfo::try_new(VALID_HASH2, 3).unwrap();
+        Pin::new(inner_store.as_ref())
+            .update_oneshot(digest1, VALUE.into())
+            .await
+            .err_tip(|| "Failed to update inner store")?;
+        let store_owned = ExistenceCacheStore::new(&config, inner_store.clone());
+        let store = Pin::new(&store_owned);
+
+        // Written to the backend directly, but found because the empty bloom
+        // filter is not trusted.
+        assert!(!store.bloom_filter_ready());
+        assert_eq!(store.has(digest1).await?, Some(VALUE.len()));
+
+        // The first lookup started populating the bloom filter.
+        while !store.bloom_filter_ready() {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            store.has_many(&[digest1, digest2]).await?,
+            vec![Some(VALUE.len()), None]
+        );
+        Ok(())
+    }
 }
@@ -700,6 +700,7 @@ mod filesystem_store_tests {
                     max_seconds: 0,
                     max_count: 1,
                     evict_bytes: 0,
+                    max_pinned_bytes: 0,
                 }),
                 ..Default::default()
             })
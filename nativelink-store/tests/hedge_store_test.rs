@@ -0,0 +1,218 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use nativelink_config::stores::{HedgeStore as HedgeStoreConfig, StoreConfig};
+use nativelink_error::Error;
+use nativelink_store::hedge_store::HedgeStore;
+use nativelink_store::memory_store::MemoryStore;
+use nativelink_util::buf_channel::{DropCloserReadHalf, DropCloserWriteHalf};
+use nativelink_util::common::DigestInfo;
+use nativelink_util::health_utils::{default_health_status_indicator, HealthStatusIndicator};
+use nativelink_util::metrics_utils::Registry;
+use nativelink_util::store_trait::{Store, UploadSizeInfo};
+
+/// Store that waits before serving reads from a memory store.
+struct DelayedStore {
+    inner: MemoryStore,
+    delay: Duration,
+}
+
+impl DelayedStore {
+    fn new(delay: Duration) -> Self {
+        Self {
+            inner: MemoryStore::new(&nativelink_config::stores::MemoryStore::default()),
+            delay,
+        }
+    }
+}
+
+#[async_trait]
+impl Store for DelayedStore {
+    async fn has_with_results(
+        self: Pin<&Self>,
+        digests: &[DigestInfo],
+        results: &mut [Option<usize>],
+    ) -> Result<(), Error> {
+        Pin::new(&self.inner)
+            .has_with_results(digests, results)
+            .await
+    }
+
+    async fn update(
+        self: Pin<&Self>,
+        digest: DigestInfo,
+        reader: DropCloserReadHalf,
+        size_info: UploadSizeInfo,
+    ) -> Result<(), Error> {
+        Pin::new(&self.inner)
+            .update(digest, reader, size_info)
+            .await
+    }
+
+    async fn get_part_ref(
+        self: Pin<&Self>,
+        digest: DigestInfo,
+        writer: &mut DropCloserWriteHalf,
+        offset: usize,
+        length: Option<usize>,
+    ) -> Result<(), Error> {
+        tokio::time::sleep(self.delay).await;
+        Pin::new(&self.inner)
+            .get_part_ref(digest, writer, offset, length)
+            .await
+    }
+
+    fn inner_store(&self, _digest: Option<DigestInfo>) -> &'_ dyn Store {
+        self
+    }
+
+    fn inner_store_arc(self: Arc<Self>, _digest: Option<DigestInfo>) -> Arc<dyn Store> {
+        self
+    }
+
+    fn as_any(&self) -> &(dyn std::any::Any + Sync + Send + 'static) {
+        self
+    }
+
+    fn as_any_arc(self: Arc<Self>) -> Arc<dyn std::any::Any + Sync + Send + 'static> {
+        self
+    }
+
+    fn register_metrics(self: Arc<Self>, _registry: &mut Registry) {}
+}
+
+default_health_status_indicator!(DelayedStore);
+
+fn make_config(max_delay_ms: u64) -> HedgeStoreConfig {
+    HedgeStoreConfig {
+        backend: StoreConfig::memory(nativelink_config::stores::MemoryStore::default()),
+        hedge_backend: Some(StoreConfig::memory(
+            nativelink_config::stores::MemoryStore::default(),
+        )),
+        delay_percentile: 0.,
+        sample_window: 0,
+        min_delay_ms: 0,
+        max_delay_ms,
+    }
+}
+
+#[cfg(test)]
+mod hedge_store_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*; // Must be declared in every module.
+
+    const VALID_HASH1: &str = "0123456789abcdef000000000000000000010000000000000123456789abcdef";
+
+    /// Creates a hedge store where `backend` and `hedge_backend` hold
+    /// different data for the same digest, so tests can tell which store
+    /// served a read.
+    async fn make_stores(
+        backend_delay: Duration,
+        max_delay_ms: u64,
+    ) -> Result<(HedgeStore, DigestInfo), Error> {
+        let backend = Arc::new(DelayedStore::new(backend_delay));
+        let hedge_backend = Arc::new(DelayedStore::new(Duration::ZERO));
+        let digest = DigestInfo::try_new(VALID_HASH1, 6)?;
+        Pin::new(backend.as_ref())
+            .update_oneshot(digest, "origin".into())
+            .await?;
+        Pin::new(hedge_backend.as_ref())
+            .update_oneshot(digest, "hedged".into())
+            .await?;
+        let store = HedgeStore::new(&make_config(max_delay_ms), backend, Some(hedge_backend))?;
+        Ok((store, digest))
+    }
+
+    #[tokio::test]
+    async fn fast_read_is_not_hedged() -> Result<(), Error> {
+        let (store, digest) = make_stores(Duration::ZERO, 60_000).await?;
+        let data = Pin::new(&store)
+            .get_part_unchunked(digest, 0, None, None)
+            .await?;
+        assert_eq!(data, "origin");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn slow_read_is_hedged() -> Result<(), Error> {
+        let (store, digest) = make_stores(Duration::from_secs(60), 10).await?;
+        let data = tokio::time::timeout(
+            Duration::from_secs(10),
+            Pin::new(&store).get_part_unchunked(digest, 2, Some(3), None),
+        )
+        .await
+        .expect("Hedged read should not wait for the slow backend")?;
+        assert_eq!(data, "dge");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_read_waits_for_hedge() -> Result<(), Error> {
+        // The backend does not have the object, so it fails after the hedge
+        // was sent but before the hedge responds.
+        let backend = Arc::new(DelayedStore::new(Duration::from_millis(50)));
+        let hedge_backend = Arc::new(DelayedStore::new(Duration::from_millis(200)));
+        let digest = DigestInfo::try_new(VALID_HASH1, 6)?;
+        Pin::new(hedge_backend.as_ref())
+            .update_oneshot(digest, "hedged".into())
+            .await?;
+        let store = HedgeStore::new(&make_config(10), backend, Some(hedge_backend))?;
+
+        let data = Pin::new(&store)
+            .get_part_unchunked(digest, 0, None, None)
+            .await?;
+        assert_eq!(data, "hedged");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn has_and_update_only_use_backend() -> Result<(), Error> {
+        let backend = Arc::new(DelayedStore::new(Duration::ZERO));
+        let hedge_backend = Arc::new(DelayedStore::new(Duration::ZERO));
+        let store = HedgeStore::new(
+            &make_config(10),
+            backend.clone(),
+            Some(hedge_backend.clone()),
+        )?;
+        let digest = DigestInfo::try_new(VALID_HASH1, 4)?;
+        Pin::new(&store)
+            .update_oneshot(digest, "data".into())
+            .await?;
+
+        assert_eq!(Pin::new(&store).has(digest).await?, Some(4));
+        assert_eq!(Pin::new(backend.as_ref()).has(digest).await?, Some(4));
+        assert_eq!(Pin::new(hedge_backend.as_ref()).has(digest).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invalid_delay_percentile_is_rejected() -> Result<(), Error> {
+        let result = HedgeStore::new(
+            &HedgeStoreConfig {
+                delay_percentile: 150.,
+                ..make_config(10)
+            },
+            Arc::new(DelayedStore::new(Duration::ZERO)),
+            None,
+        );
+        assert!(result.is_err(), "Expected HedgeStore::new to fail");
+        Ok(())
+    }
+}
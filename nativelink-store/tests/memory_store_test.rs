@@ -12,16 +12,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::env;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 use bytes::{BufMut, Bytes, BytesMut};
 use memory_stats::memory_stats;
+use nativelink_config::stores::{EvictionPolicy, MemoryStoreSnapshot};
 use nativelink_error::{Error, ResultExt};
 use nativelink_store::memory_store::MemoryStore;
 use nativelink_util::buf_channel::{make_buf_channel_pair, DropCloserReadHalf};
 use nativelink_util::common::{DigestInfo, JoinHandleDropGuard};
 use nativelink_util::store_trait::Store;
+use rand::{thread_rng, Rng};
 use sha2::{Digest, Sha256};
 
 const VALID_HASH1: &str = "0123456789abcdef000000000000000000010000000000000123456789abcdef";
@@ -32,6 +36,38 @@ const TOO_LONG_HASH: &str = "0123456789abcdef00000000000000000001000000000000012
 const TOO_SHORT_HASH: &str = "100000000000000000000000000000000000000000000000000000000000001";
 const INVALID_HASH: &str = "g111111111111111111111111111111111111111111111111111111111111111";
 
+/// Get a unique snapshot path in either `TEST_TMPDIR` or best effort temp
+/// directory if not set.
+fn make_snapshot_path() -> String {
+    let dir = format!(
+        "{}/{}",
+        env::var("TEST_TMPDIR").unwrap_or(env::temp_dir().to_str().unwrap().to_string()),
+        thread_rng().gen::<u64>(),
+    );
+    std::fs::create_dir_all(&dir).unwrap();
+    format!("{dir}/memory_store.snapshot")
+}
+
+fn make_snapshot_store(path: &str, max_age_seconds: u32) -> MemoryStore {
+    MemoryStore::new(&nativelink_config::stores::MemoryStore {
+        eviction_policy: Some(EvictionPolicy {
+            max_count: 3,
+            ..Default::default()
+        }),
+        snapshot: Some(MemoryStoreSnapshot {
+            path: path.to_string(),
+            interval_seconds: 0,
+            max_age_seconds,
+        }),
+    })
+}
+
+async fn wait_for_snapshot_loaded(store: &MemoryStore) {
+    while !store.is_snapshot_loaded() {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+}
+
 #[cfg(test)]
 mod memory_store_tests {
 
@@ -303,4 +339,172 @@ mod memory_store_tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn snapshot_reload_keeps_lru_order() -> Result<(), Error> {
+        const VALUE: &str = "123";
+        let snapshot_path = make_snapshot_path();
+        let digest1 = DigestInfo::try_new(VALID_HASH1, VALUE.len())?;
+        let digest2 = DigestInfo::try_new(VALID_HASH2, VALUE.len())?;
+        let digest3 = DigestInfo::try_new(VALID_HASH3, VALUE.len())?;
+        let digest4 = DigestInfo::try_new(VALID_HASH4, VALUE.len())?;
+        {
+            let store_owned = make_snapshot_store(&snapshot_path, 0);
+            wait_for_snapshot_loaded(&store_owned).await;
+            let store = Pin::new(&store_owned);
+            store.update_oneshot(digest1, VALUE.into()).await?;
+            store.update_oneshot(digest2, VALUE.into()).await?;
+            store.update_oneshot(digest3, VALUE.into()).await?;
+            // Make digest2 the least recently used item.
+            store.get_part_unchunked(digest1, 0, None, None).await?;
+            store_owned.write_snapshot().await?;
+        }
+
+        let store_owned = make_snapshot_store(&snapshot_path, 0);
+        wait_for_snapshot_loaded(&store_owned).await;
+        let store = Pin::new(&store_owned);
+        assert_eq!(
+            store.get_part_unchunked(digest3, 0, None, None).await?,
+            VALUE.as_bytes()
+        );
+        // Reading digest3 made digest2 the least recently used item, so
+        // it is the one evicted.
+        store.update_oneshot(digest4, VALUE.into()).await?;
+        assert_eq!(
+            store
+                .has_many(&[digest1, digest2, digest3, digest4])
+                .await?,
+            vec![
+                Some(VALUE.len()),
+                None,
+                Some(VALUE.len()),
+                Some(VALUE.len())
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn snapshot_with_bad_checksum_is_ignored() -> Result<(), Error> {
+        const VALUE: &str = "123";
+        let snapshot_path = make_snapshot_path();
+        let digest = DigestInfo::try_new(VALID_HASH1, VALUE.len())?;
+        {
+            let store_owned = make_snapshot_store(&snapshot_path, 0);
+            wait_for_snapshot_loaded(&store_owned).await;
+            Pin::new(&store_owned)
+                .update_oneshot(digest, VALUE.into())
+                .await?;
+            store_owned.write_snapshot().await?;
+        }
+
+        // Corrupt the last byte of the stored value.
+        let mut snapshot = std::fs::read(&snapshot_path)?;
+        let value_end = snapshot.len() - blake3::OUT_LEN - 1;
+        snapshot[value_end] ^= 0xff;
+        std::fs::write(&snapshot_path, snapshot)?;
+
+        let store_owned = make_snapshot_store(&snapshot_path, 0);
+        wait_for_snapshot_loaded(&store_owned).await;
+        assert_eq!(Pin::new(&store_owned).has(digest).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn snapshot_with_corrupt_length_is_ignored() -> Result<(), Error> {
+        const VALUE: &str = "123";
+        // Offset of the data length of the first item, after the header
+        // (magic, version, creation time, anchor time and item count) and
+        // the packed hash, size and seconds since anchor of the item.
+        const DATA_LEN_OFFSET: usize = 36 + 44;
+        let snapshot_path = make_snapshot_path();
+        let digest = DigestInfo::try_new(VALID_HASH1, VALUE.len())?;
+        {
+            let store_owned = make_snapshot_store(&snapshot_path, 0);
+            wait_for_snapshot_loaded(&store_owned).await;
+            Pin::new(&store_owned)
+                .update_oneshot(digest, VALUE.into())
+                .await?;
+            store_owned.write_snapshot().await?;
+        }
+
+        let mut snapshot = std::fs::read(&snapshot_path)?;
+        assert_eq!(
+            &snapshot[DATA_LEN_OFFSET..DATA_LEN_OFFSET + 8],
+            &(VALUE.len() as u64).to_le_bytes()
+        );
+        snapshot[DATA_LEN_OFFSET..DATA_LEN_OFFSET + 8]
+            .copy_from_slice(&(u64::MAX >> 1).to_le_bytes());
+        std::fs::write(&snapshot_path, snapshot)?;
+
+        let store_owned = make_snapshot_store(&snapshot_path, 0);
+        wait_for_snapshot_loaded(&store_owned).await;
+        assert_eq!(Pin::new(&store_owned).has(digest).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn snapshot_older_than_max_age_is_ignored() -> Result<(), Error> {
+        const VALUE: &str = "123";
+        // Offset of the creation time, after the magic and version.
+        const CREATED_AT_OFFSET: usize = 12;
+        let snapshot_path = make_snapshot_path();
+        let digest = DigestInfo::try_new(VALID_HASH1, VALUE.len())?;
+        {
+            let store_owned = make_snapshot_store(&snapshot_path, 0);
+            wait_for_snapshot_loaded(&store_owned).await;
+            Pin::new(&store_owned)
+                .update_oneshot(digest, VALUE.into())
+                .await?;
+            store_owned.write_snapshot().await?;
+        }
+
+        // Pretend the snapshot was written a day ago.
+        let mut snapshot = std::fs::read(&snapshot_path)?;
+        let content_len = snapshot.len() - blake3::OUT_LEN;
+        let created_at = u64::from_le_bytes(
+            snapshot[CREATED_AT_OFFSET..CREATED_AT_OFFSET + 8]
+                .try_into()
+                .unwrap(),
+        );
+        snapshot[CREATED_AT_OFFSET..CREATED_AT_OFFSET + 8]
+            .copy_from_slice(&(created_at - 24 * 60 * 60).to_le_bytes());
+        let checksum = blake3::hash(&snapshot[..content_len]);
+        snapshot[content_len..].copy_from_slice(checksum.as_bytes());
+        std::fs::write(&snapshot_path, snapshot)?;
+
+        let store_owned = make_snapshot_store(&snapshot_path, 60 * 60);
+        wait_for_snapshot_loaded(&store_owned).await;
+        assert_eq!(Pin::new(&store_owned).has(digest).await?, None);
+
+        let store_owned = make_snapshot_store(&snapshot_path, 0);
+        wait_for_snapshot_loaded(&store_owned).await;
+        assert_eq!(Pin::new(&store_owned).has(digest).await?, Some(VALUE.len()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pin_returns_number_of_pinned_digests() -> Result<(), Error> {
+        const VALUE: &str = "123";
+        let store_owned = MemoryStore::new(&nativelink_config::stores::MemoryStore {
+            eviction_policy: Some(EvictionPolicy {
+                max_pinned_bytes: 1024,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        let store = Pin::new(&store_owned);
+        let digest1 = DigestInfo::try_new(VALID_HASH1, VALUE.len())?;
+        let digest2 = DigestInfo::try_new(VALID_HASH2, VALUE.len())?;
+        store.update_oneshot(digest1, VALUE.into()).await?;
+
+        // Missing digests are not pinned.
+        assert_eq!(
+            store
+                .pin(&[digest1, digest2], Duration::from_secs(60))
+                .await?,
+            1
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,195 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::pin::Pin;
+use std::sync::Arc;
+
+use nativelink_config::stores::{QuotaStore as QuotaStoreConfig, StoreConfig};
+use nativelink_error::{Code, Error};
+use nativelink_store::default_store_factory::store_factory;
+use nativelink_store::memory_store::MemoryStore;
+use nativelink_store::quota_store::{QuotaOwners, QuotaStore};
+use nativelink_store::store_manager::StoreManager;
+use nativelink_util::common::DigestInfo;
+use nativelink_util::store_trait::Store;
+
+fn make_config(soft_quota_bytes: u64, hard_quota_bytes: u64) -> QuotaStoreConfig {
+    QuotaStoreConfig {
+        backend: StoreConfig::noop,
+        soft_quota_bytes,
+        hard_quota_bytes,
+    }
+}
+
+#[cfg(test)]
+mod quota_store_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*; // Must be declared in every module.
+
+    const VALID_HASH1: &str = "0123456789abcdef000000000000000000010000000000000123456789abcdef";
+    const VALID_HASH2: &str = "0123456789abcdef000000000000000000020000000000000123456789abcdef";
+    const VALID_HASH3: &str = "0123456789abcdef000000000000000000030000000000000123456789abcdef";
+
+    async fn upload(store: &QuotaStore, hash: &str, size: usize) -> Result<DigestInfo, Error> {
+        let digest = DigestInfo::try_new(hash, size)?;
+        Pin::new(store)
+            .update_oneshot(digest, vec![0u8; size].into())
+            .await?;
+        Ok(digest)
+    }
+
+    #[tokio::test]
+    async fn hard_quota_rejects_uploads() -> Result<(), Error> {
+        let backend = Arc::new(MemoryStore::new(&Default::default()));
+        let store = QuotaStore::new(&make_config(0, 100), backend.clone(), Arc::default());
+
+        upload(&store, VALID_HASH1, 60).await?;
+        let err = upload(&store, VALID_HASH2, 50).await.unwrap_err();
+        assert_eq!(err.code, Code::ResourceExhausted);
+        assert_eq!(store.used_bytes(), 60);
+        assert_eq!(store.reserved_bytes(), 0);
+        assert_eq!(
+            Pin::new(backend.as_ref())
+                .has(DigestInfo::try_new(VALID_HASH2, 50)?)
+                .await?,
+            None
+        );
+
+        // Uploading the same object again does not count twice.
+        upload(&store, VALID_HASH1, 60).await?;
+        assert_eq!(store.used_bytes(), 60);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn hard_quota_forgets_objects_removed_from_backend() -> Result<(), Error> {
+        let backend = Arc::new(MemoryStore::new(&Default::default()));
+        let store = QuotaStore::new(&make_config(0, 100), backend.clone(), Arc::default());
+
+        let digest1 = upload(&store, VALID_HASH1, 60).await?;
+        // Removed from the backend without going through the quota store.
+        assert!(backend.remove_entry(&digest1).await);
+
+        upload(&store, VALID_HASH2, 50).await?;
+        assert_eq!(store.used_bytes(), 50);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn soft_quota_evicts_least_recently_used() -> Result<(), Error> {
+        let backend = Arc::new(MemoryStore::new(&Default::default()));
+        let store = QuotaStore::new(&make_config(100, 0), backend.clone(), Arc::default());
+
+        let digest1 = upload(&store, VALID_HASH1, 40).await?;
+        let digest2 = upload(&store, VALID_HASH2, 40).await?;
+        // Reading the first object makes the second the least recently used.
+        Pin::new(&store)
+            .get_part_unchunked(digest1, 0, None, None)
+            .await?;
+        let digest3 = upload(&store, VALID_HASH3, 40).await?;
+
+        let results = Pin::new(backend.as_ref())
+            .has_many(&[digest1, digest2, digest3])
+            .await?;
+        assert_eq!(results, vec![Some(40), None, Some(40)]);
+        assert_eq!(store.used_bytes(), 80);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn soft_quota_only_evicts_own_objects() -> Result<(), Error> {
+        let backend = Arc::new(MemoryStore::new(&Default::default()));
+        let owners = Arc::new(QuotaOwners::default());
+        let store1 = QuotaStore::new(&make_config(50, 0), backend.clone(), owners.clone());
+        let store2 = QuotaStore::new(&make_config(50, 0), backend.clone(), owners);
+
+        let digest1 = upload(&store1, VALID_HASH1, 40).await?;
+        let digest2 = upload(&store2, VALID_HASH2, 40).await?;
+        let digest3 = upload(&store2, VALID_HASH3, 40).await?;
+
+        let results = Pin::new(backend.as_ref())
+            .has_many(&[digest1, digest2, digest3])
+            .await?;
+        assert_eq!(results, vec![Some(40), None, Some(40)]);
+        assert_eq!(store1.used_bytes(), 40);
+        assert_eq!(store2.used_bytes(), 40);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn soft_quota_keeps_objects_shared_with_other_stores() -> Result<(), Error> {
+        let backend = Arc::new(MemoryStore::new(&Default::default()));
+        let owners = Arc::new(QuotaOwners::default());
+        let store1 = QuotaStore::new(&make_config(50, 0), backend.clone(), owners.clone());
+        let store2 = QuotaStore::new(&make_config(50, 0), backend.clone(), owners);
+
+        let digest1 = upload(&store1, VALID_HASH1, 40).await?;
+        upload(&store2, VALID_HASH1, 40).await?;
+        // Only stops counting against `store2`, as `store1` still uses it.
+        let digest2 = upload(&store2, VALID_HASH2, 40).await?;
+        assert_eq!(
+            Pin::new(backend.as_ref())
+                .has_many(&[digest1, digest2])
+                .await?,
+            vec![Some(40), Some(40)]
+        );
+        assert_eq!(store1.used_bytes(), 40);
+        assert_eq!(store2.used_bytes(), 40);
+
+        // Removed once its last user evicts it.
+        let digest3 = upload(&store1, VALID_HASH3, 40).await?;
+        assert_eq!(
+            Pin::new(backend.as_ref())
+                .has_many(&[digest1, digest2, digest3])
+                .await?,
+            vec![None, Some(40), Some(40)]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn quota_stores_referencing_same_store_share_objects() -> Result<(), Error> {
+        let store_manager = Arc::new(StoreManager::new());
+        let backend = Arc::new(MemoryStore::new(&Default::default()));
+        store_manager.add_store("shared", backend.clone());
+        let quota_config = StoreConfig::quota(Box::new(QuotaStoreConfig {
+            backend: StoreConfig::ref_store(nativelink_config::stores::RefStore {
+                name: "shared".to_string(),
+            }),
+            ..make_config(50, 0)
+        }));
+        let store1 = store_factory(&quota_config, &store_manager, None, None).await?;
+        let store2 = store_factory(&quota_config, &store_manager, None, None).await?;
+
+        let digest1 = DigestInfo::try_new(VALID_HASH1, 40)?;
+        let digest2 = DigestInfo::try_new(VALID_HASH2, 40)?;
+        for store in [&store1, &store2] {
+            Pin::new(store.as_ref())
+                .update_oneshot(digest1, vec![0u8; 40].into())
+                .await?;
+        }
+        // `store2` evicts its copy, but `store1` still uses it.
+        Pin::new(store2.as_ref())
+            .update_oneshot(digest2, vec![0u8; 40].into())
+            .await?;
+        assert_eq!(
+            Pin::new(backend.as_ref())
+                .has_many(&[digest1, digest2])
+                .await?,
+            vec![Some(40), Some(40)]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,306 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use nativelink_config::stores::{
+    CompletenessCheckingStore as CompletenessCheckingStoreConfig, EvictionPolicy,
+    MemoryStore as MemoryStoreConfig, RoutingAccess, RoutingRule,
+    RoutingStore as RoutingStoreConfig, StoreConfig,
+};
+use nativelink_error::Error;
+use nativelink_proto::build::bazel::remote::execution::v2::{ActionResult, OutputFile};
+use nativelink_store::ac_utils::serialize_and_upload_message;
+use nativelink_store::completeness_checking_store::CompletenessCheckingStore;
+use nativelink_store::memory_store::MemoryStore;
+use nativelink_store::routing_store::RoutingStore;
+use nativelink_util::common::DigestInfo;
+use nativelink_util::digest_hasher::DigestHasherFunc;
+use nativelink_util::request_context::{RequestContext, StoreAccess};
+use nativelink_util::store_trait::Store;
+
+fn make_rule() -> RoutingRule {
+    RoutingRule {
+        store: StoreConfig::memory(MemoryStoreConfig::default()),
+        min_size: 0,
+        max_size: 0,
+        digest_functions: vec![],
+        instance_names: vec![],
+        access: None,
+        digest_prefixes: vec![],
+    }
+}
+
+fn make_memory_store() -> Arc<MemoryStore> {
+    Arc::new(MemoryStore::new(&MemoryStoreConfig::default()))
+}
+
+fn setup_store(rules: Vec<RoutingRule>) -> Result<(RoutingStore, Vec<Arc<MemoryStore>>), Error> {
+    let memory_stores: Vec<Arc<MemoryStore>> =
+        (0..=rules.len()).map(|_| make_memory_store()).collect();
+    let rule_stores = memory_stores[..rules.len()]
+        .iter()
+        .map(|store| store.clone() as Arc<dyn Store>)
+        .collect();
+    let store = RoutingStore::new(
+        &RoutingStoreConfig {
+            rules,
+            default_store: StoreConfig::memory(MemoryStoreConfig::default()),
+        },
+        rule_stores,
+        memory_stores.last().unwrap().clone(),
+    )?;
+    Ok((store, memory_stores))
+}
+
+#[cfg(test)]
+mod routing_store_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*; // Must be declared in every module.
+
+    const HASH1: &str = "0123456789abcdef000000000000000000010000000000000123456789abcdef";
+    const HASH2: &str = "fedcba9876543210000000000000000000020000000000000123456789abcdef";
+    const VALUE: &str = "123";
+
+    async fn stores_holding(
+        memory_stores: &[Arc<MemoryStore>],
+        digest: DigestInfo,
+    ) -> Result<Vec<bool>, Error> {
+        let mut holding = Vec::with_capacity(memory_stores.len());
+        for store in memory_stores {
+            holding.push(Pin::new(store.as_ref()).has(digest).await?.is_some());
+        }
+        Ok(holding)
+    }
+
+    #[tokio::test]
+    async fn routes_by_size_and_prefix() -> Result<(), Error> {
+        let (store, memory_stores) = setup_store(vec![
+            RoutingRule {
+                max_size: 2,
+                ..make_rule()
+            },
+            RoutingRule {
+                digest_prefixes: vec!["FEDC".to_string()],
+                ..make_rule()
+            },
+        ])?;
+        let store = Pin::new(&store);
+
+        let small_digest = DigestInfo::try_new(HASH1, 1)?;
+        store.update_oneshot(small_digest, "1".into()).await?;
+        let prefix_digest = DigestInfo::try_new(HASH2, VALUE.len())?;
+        store.update_oneshot(prefix_digest, VALUE.into()).await?;
+        let default_digest = DigestInfo::try_new(HASH1, VALUE.len())?;
+        store.update_oneshot(default_digest, VALUE.into()).await?;
+
+        assert_eq!(
+            stores_holding(&memory_stores, small_digest).await?,
+            vec![true, false, false]
+        );
+        assert_eq!(
+            stores_holding(&memory_stores, prefix_digest).await?,
+            vec![false, true, false]
+        );
+        assert_eq!(
+            stores_holding(&memory_stores, default_digest).await?,
+            vec![false, false, true]
+        );
+        assert_eq!(
+            store
+                .has_many(&[small_digest, prefix_digest, default_digest])
+                .await?,
+            vec![Some(1), Some(VALUE.len()), Some(VALUE.len())]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn routes_by_request_context() -> Result<(), Error> {
+        let (store, memory_stores) = setup_store(vec![
+            RoutingRule {
+                access: Some(RoutingAccess::ac),
+                ..make_rule()
+            },
+            RoutingRule {
+                instance_names: vec!["toolchains".to_string()],
+                ..make_rule()
+            },
+        ])?;
+        let store = Pin::new(&store);
+        let digest = DigestInfo::try_new(HASH1, VALUE.len())?;
+
+        RequestContext::new("main", 0, StoreAccess::Ac)
+            .scope(store.update_oneshot(digest, VALUE.into()))
+            .await?;
+        assert_eq!(
+            stores_holding(&memory_stores, digest).await?,
+            vec![true, false, false]
+        );
+
+        RequestContext::new("toolchains", 0, StoreAccess::Cas)
+            .scope(store.update_oneshot(digest, VALUE.into()))
+            .await?;
+        assert_eq!(
+            stores_holding(&memory_stores, digest).await?,
+            vec![true, true, false]
+        );
+
+        // Without a request context only rules that don't need one match.
+        store.update_oneshot(digest, VALUE.into()).await?;
+        assert_eq!(
+            stores_holding(&memory_stores, digest).await?,
+            vec![true, true, true]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn remove_is_forwarded_to_routed_store() -> Result<(), Error> {
+        let (store, memory_stores) = setup_store(vec![RoutingRule {
+            max_size: 2,
+            ..make_rule()
+        }])?;
+        let store = Pin::new(&store);
+        let small_digest = DigestInfo::try_new(HASH1, 1)?;
+        store.update_oneshot(small_digest, "1".into()).await?;
+        let default_digest = DigestInfo::try_new(HASH2, VALUE.len())?;
+        store.update_oneshot(default_digest, VALUE.into()).await?;
+
+        assert!(store.remove(small_digest).await?);
+        assert!(store.remove(default_digest).await?);
+        assert_eq!(
+            stores_holding(&memory_stores, small_digest).await?,
+            vec![false, false]
+        );
+        assert_eq!(
+            stores_holding(&memory_stores, default_digest).await?,
+            vec![false, false]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn completeness_checks_route_outputs_as_cas() -> Result<(), Error> {
+        let (cas_store, memory_stores) = setup_store(vec![RoutingRule {
+            access: Some(RoutingAccess::cas),
+            ..make_rule()
+        }])?;
+        let cas_store = Arc::new(cas_store);
+        let ac_backend = make_memory_store();
+        let ac_store = CompletenessCheckingStore::new(
+            &CompletenessCheckingStoreConfig {
+                backend: StoreConfig::noop,
+                cas_store: StoreConfig::noop,
+                pin_outputs_seconds: 0,
+                refresh_outputs: false,
+            },
+            ac_backend.clone(),
+            cas_store.clone(),
+        );
+
+        let output_digest = DigestInfo::try_new(HASH1, VALUE.len())?;
+        RequestContext::new("main", 0, StoreAccess::Cas)
+            .scope(Pin::new(cas_store.as_ref()).update_oneshot(output_digest, VALUE.into()))
+            .await?;
+        assert_eq!(
+            stores_holding(&memory_stores, output_digest).await?,
+            vec![true, false]
+        );
+        let action_result_digest = serialize_and_upload_message(
+            &ActionResult {
+                output_files: vec![OutputFile {
+                    digest: Some(output_digest.into()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            Pin::new(ac_backend.as_ref()),
+            &mut DigestHasherFunc::Sha256.hasher(),
+        )
+        .await?;
+
+        // The output is looked up in the CAS rule store, not the default
+        // store the AC access of the request would route to.
+        let result = RequestContext::new("main", 0, StoreAccess::Ac)
+            .scope(Pin::new(&ac_store).has(action_result_digest))
+            .await?;
+        assert!(result.is_some(), "Expected action result to be complete");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn eviction_hooks_are_forwarded_to_every_store() -> Result<(), Error> {
+        // Both rules send data to the same store, which only accepts one
+        // eviction callback.
+        let shared_store = make_memory_store();
+        let default_store = make_memory_store();
+        let store = Arc::new(RoutingStore::new(
+            &RoutingStoreConfig {
+                rules: vec![
+                    RoutingRule {
+                        max_size: 2,
+                        ..make_rule()
+                    },
+                    RoutingRule {
+                        digest_prefixes: vec!["FEDC".to_string()],
+                        ..make_rule()
+                    },
+                ],
+                default_store: StoreConfig::memory(MemoryStoreConfig::default()),
+            },
+            vec![shared_store.clone(), shared_store.clone()],
+            default_store.clone(),
+        )?);
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        store.clone().register_eviction_callback(Box::new({
+            let evicted = evicted.clone();
+            move |digest, _data| evicted.lock().unwrap().push(digest)
+        }))?;
+        store
+            .set_eviction_policy(&EvictionPolicy {
+                max_count: 1,
+                ..Default::default()
+            })
+            .await?;
+
+        let store = Pin::new(store.as_ref());
+        let small_digest = DigestInfo::try_new(HASH1, 1)?;
+        store.update_oneshot(small_digest, "1".into()).await?;
+        let prefix_digest = DigestInfo::try_new(HASH2, VALUE.len())?;
+        store.update_oneshot(prefix_digest, VALUE.into()).await?;
+        let default_digest1 = DigestInfo::try_new(HASH1, VALUE.len())?;
+        store.update_oneshot(default_digest1, VALUE.into()).await?;
+        let default_digest2 = DigestInfo::try_new(HASH1, VALUE.len() + 1)?;
+        store.update_oneshot(default_digest2, "1234".into()).await?;
+
+        assert_eq!(
+            *evicted.lock().unwrap(),
+            vec![small_digest, default_digest1]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_digest_prefix() -> Result<(), Error> {
+        let result = setup_store(vec![RoutingRule {
+            digest_prefixes: vec!["xyz".to_string()],
+            ..make_rule()
+        }]);
+        assert!(result.is_err(), "Expected invalid prefix to be rejected");
+        Ok(())
+    }
+}
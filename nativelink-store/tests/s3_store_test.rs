@@ -14,11 +14,13 @@
 
 use std::pin::Pin;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 use aws_sdk_s3::config::{BehaviorVersion, Builder, Region};
 use aws_smithy_runtime::client::http::test_util::{ReplayEvent, StaticReplayClient};
 use aws_smithy_types::body::SdkBody;
+use aws_smithy_types::date_time::Format;
+use aws_smithy_types::DateTime;
 use bytes::Bytes;
 use futures::join;
 use http::header;
@@ -634,4 +636,446 @@ mod s3_store_tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn touch_copies_object_onto_itself() -> Result<(), Error> {
+        const CONTENT_LENGTH: u64 = 50;
+        let mock_client = StaticReplayClient::new(vec![
+            ReplayEvent::new(
+                http::Request::builder()
+                    .uri(format!(
+                        "https://{BUCKET_NAME}.s3.{REGION}.amazonaws.com/{VALID_HASH1}-{CONTENT_LENGTH}",
+                    ))
+                    .method("HEAD")
+                    .body(SdkBody::empty())
+                    .unwrap(),
+                http::Response::builder()
+                    .header(header::CONTENT_LENGTH, CONTENT_LENGTH.to_string())
+                    .header(header::CONTENT_TYPE, "application/octet-stream")
+                    .header(header::CACHE_CONTROL, "max-age=60")
+                    .header(header::CONTENT_ENCODING, "zstd")
+                    .header(header::LAST_MODIFIED, "Wed, 21 Oct 2015 07:28:00 GMT")
+                    .header("x-amz-meta-owner", "nativelink")
+                    .header("x-amz-storage-class", "STANDARD_IA")
+                    .header("x-amz-server-side-encryption", "aws:kms")
+                    .header("x-amz-server-side-encryption-aws-kms-key-id", "key-id")
+                    .body(SdkBody::empty())
+                    .unwrap(),
+            ),
+            ReplayEvent::new(
+                http::Request::builder()
+                    .uri(format!(
+                        "https://{BUCKET_NAME}.s3.{REGION}.amazonaws.com/{VALID_HASH1}-{CONTENT_LENGTH}?x-id=CopyObject",
+                    ))
+                    .method("PUT")
+                    .header(
+                        "x-amz-copy-source",
+                        format!("{BUCKET_NAME}/{VALID_HASH1}-{CONTENT_LENGTH}"),
+                    )
+                    .header("x-amz-metadata-directive", "REPLACE")
+                    .header(header::CONTENT_TYPE, "application/octet-stream")
+                    .header(header::CACHE_CONTROL, "max-age=60")
+                    .header(header::CONTENT_ENCODING, "zstd")
+                    .header("x-amz-meta-owner", "nativelink")
+                    .header("x-amz-storage-class", "STANDARD_IA")
+                    .header("x-amz-server-side-encryption", "aws:kms")
+                    .header("x-amz-server-side-encryption-aws-kms-key-id", "key-id")
+                    .body(SdkBody::empty())
+                    .unwrap(),
+                http::Response::builder()
+                    .status(StatusCode::OK)
+                    .body(SdkBody::from("<CopyObjectResult></CopyObjectResult>"))
+                    .unwrap(),
+            ),
+        ]);
+        let test_config = Builder::new()
+            .behavior_version(BehaviorVersion::v2023_11_09())
+            .region(Region::from_static(REGION))
+            .http_client(mock_client.clone())
+            .build();
+        let s3_client = aws_sdk_s3::Client::from_conf(test_config);
+        let store = S3Store::new_with_client_and_jitter(
+            &nativelink_config::stores::S3Store {
+                bucket: BUCKET_NAME.to_string(),
+                ..Default::default()
+            },
+            s3_client,
+            Arc::new(move |_delay| Duration::from_secs(0)),
+        )?;
+        Pin::new(&store)
+            .touch(&[DigestInfo::try_new(VALID_HASH1, CONTENT_LENGTH)?])
+            .await?;
+        mock_client.assert_requests_match(&[]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn touch_skips_recently_modified_objects() -> Result<(), Error> {
+        const CONTENT_LENGTH: u64 = 50;
+        let mock_client = StaticReplayClient::new(vec![ReplayEvent::new(
+            http::Request::builder()
+                .uri(format!(
+                    "https://{BUCKET_NAME}.s3.{REGION}.amazonaws.com/{VALID_HASH1}-{CONTENT_LENGTH}",
+                ))
+                .method("HEAD")
+                .body(SdkBody::empty())
+                .unwrap(),
+            http::Response::builder()
+                .header(header::CONTENT_LENGTH, CONTENT_LENGTH.to_string())
+                .header(
+                    header::LAST_MODIFIED,
+                    DateTime::from(SystemTime::now())
+                        .fmt(Format::HttpDate)
+                        .unwrap(),
+                )
+                .body(SdkBody::empty())
+                .unwrap(),
+        )]);
+        let test_config = Builder::new()
+            .behavior_version(BehaviorVersion::v2023_11_09())
+            .region(Region::from_static(REGION))
+            .http_client(mock_client.clone())
+            .build();
+        let s3_client = aws_sdk_s3::Client::from_conf(test_config);
+        let store = S3Store::new_with_client_and_jitter(
+            &nativelink_config::stores::S3Store {
+                bucket: BUCKET_NAME.to_string(),
+                ..Default::default()
+            },
+            s3_client,
+            Arc::new(move |_delay| Duration::from_secs(0)),
+        )?;
+        let digest = DigestInfo::try_new(VALID_HASH1, CONTENT_LENGTH)?;
+        // The object was just modified, so it's not copied.
+        Pin::new(&store).touch(&[digest]).await?;
+        // And touching it again doesn't even check it.
+        Pin::new(&store).touch(&[digest]).await?;
+        mock_client.assert_requests_match(&[]);
+        Ok(())
+    }
+
+    fn list_response(keys: &[String], next_continuation_token: Option<&str>) -> String {
+        let contents: String = keys
+            .iter()
+            .map(|key| {
+                let size = key.rsplit_once('-').unwrap().1;
+                format!("<Contents><Key>{key}</Key><Size>{size}</Size></Contents>")
+            })
+            .collect();
+        let truncated = match next_continuation_token {
+            Some(token) => format!(
+                "<IsTruncated>true</IsTruncated><NextContinuationToken>{token}</NextContinuationToken>"
+            ),
+            None => "<IsTruncated>false</IsTruncated>".to_string(),
+        };
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><ListBucketResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\"><Name>{BUCKET_NAME}</Name>{truncated}{contents}</ListBucketResult>"
+        )
+    }
+
+    #[tokio::test]
+    async fn list_enumerates_objects() -> Result<(), Error> {
+        const KEY_PREFIX: &str = "cas/";
+        const VALID_HASH2: &str =
+            "f123456789abcdef000000000000000000010000000000000123456789abcdef";
+        // One request per key group, the objects are all returned for one
+        // of them.
+        let mut events = vec![ReplayEvent::new(
+            http::Request::builder().body(SdkBody::empty()).unwrap(),
+            http::Response::builder()
+                .status(StatusCode::OK)
+                .body(SdkBody::from(list_response(
+                    &[
+                        format!("{KEY_PREFIX}{VALID_HASH1}-10"),
+                        format!("{KEY_PREFIX}{VALID_HASH2}-20"),
+                        format!("{KEY_PREFIX}not-a-digest-5"),
+                    ],
+                    None,
+                )))
+                .unwrap(),
+        )];
+        for _ in 1..16 {
+            events.push(ReplayEvent::new(
+                http::Request::builder().body(SdkBody::empty()).unwrap(),
+                http::Response::builder()
+                    .status(StatusCode::OK)
+                    .body(SdkBody::from(list_response(&[], None)))
+                    .unwrap(),
+            ));
+        }
+        let mock_client = StaticReplayClient::new(events);
+        let test_config = Builder::new()
+            .behavior_version(BehaviorVersion::v2023_11_09())
+            .region(Region::from_static(REGION))
+            .http_client(mock_client.clone())
+            .build();
+        let s3_client = aws_sdk_s3::Client::from_conf(test_config);
+        let store = S3Store::new_with_client_and_jitter(
+            &nativelink_config::stores::S3Store {
+                bucket: BUCKET_NAME.to_string(),
+                key_prefix: Some(KEY_PREFIX.to_string()),
+                list_prefix_length: 1,
+                ..Default::default()
+            },
+            s3_client,
+            Arc::new(move |_delay| Duration::from_secs(0)),
+        )?;
+
+        let mut listed = Vec::new();
+        Pin::new(&store)
+            .list(&mut |digest, size| listed.push((digest, size)))
+            .await?;
+        listed.sort_by_key(|(_, size)| *size);
+        assert_eq!(
+            listed,
+            vec![
+                (DigestInfo::try_new(VALID_HASH1, 10)?, 10),
+                (DigestInfo::try_new(VALID_HASH2, 20)?, 20),
+            ]
+        );
+        assert_eq!(mock_client.actual_requests().count(), 16);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn has_with_results_lists_key_groups() -> Result<(), Error> {
+        const VALID_HASH2: &str =
+            "0223456789abcdef000000000000000000010000000000000123456789abcdef";
+        const VALID_HASH3: &str =
+            "0323456789abcdef000000000000000000010000000000000123456789abcdef";
+        let digest1 = DigestInfo::try_new(VALID_HASH1, 10)?;
+        let digest2 = DigestInfo::try_new(VALID_HASH2, 20)?;
+        let digest3 = DigestInfo::try_new(VALID_HASH3, 30)?;
+        let mock_client = StaticReplayClient::new(vec![
+            ReplayEvent::new(
+                http::Request::builder()
+                    .uri(format!(
+                        "https://{BUCKET_NAME}.s3.{REGION}.amazonaws.com/?list-type=2&prefix=0",
+                    ))
+                    .body(SdkBody::empty())
+                    .unwrap(),
+                http::Response::builder()
+                    .status(StatusCode::OK)
+                    .body(SdkBody::from(list_response(
+                        &[format!("{VALID_HASH1}-10")],
+                        Some("page2"),
+                    )))
+                    .unwrap(),
+            ),
+            ReplayEvent::new(
+                http::Request::builder()
+                    .uri(format!(
+                        "https://{BUCKET_NAME}.s3.{REGION}.amazonaws.com/?list-type=2&prefix=0&continuation-token=page2",
+                    ))
+                    .body(SdkBody::empty())
+                    .unwrap(),
+                http::Response::builder()
+                    .status(StatusCode::OK)
+                    .body(SdkBody::from(list_response(
+                        &[format!("{VALID_HASH2}-20")],
+                        None,
+                    )))
+                    .unwrap(),
+            ),
+        ]);
+        let test_config = Builder::new()
+            .behavior_version(BehaviorVersion::v2023_11_09())
+            .region(Region::from_static(REGION))
+            .http_client(mock_client.clone())
+            .build();
+        let s3_client = aws_sdk_s3::Client::from_conf(test_config);
+        let store = S3Store::new_with_client_and_jitter(
+            &nativelink_config::stores::S3Store {
+                bucket: BUCKET_NAME.to_string(),
+                list_prefix_length: 1,
+                list_batch_threshold: 2,
+                ..Default::default()
+            },
+            s3_client,
+            Arc::new(move |_delay| Duration::from_secs(0)),
+        )?;
+
+        let results = Pin::new(&store)
+            .has_many(&[digest1, digest2, digest3])
+            .await?;
+        assert_eq!(results, vec![Some(10), Some(20), None]);
+        mock_client.assert_requests_match(&[]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn has_with_results_checks_large_key_groups_with_head() -> Result<(), Error> {
+        const VALID_HASH2: &str =
+            "0223456789abcdef000000000000000000010000000000000123456789abcdef";
+        let digest1 = DigestInfo::try_new(VALID_HASH1, 10)?;
+        let digest2 = DigestInfo::try_new(VALID_HASH2, 20)?;
+        let list_page = |uri: String, next_continuation_token: &str| {
+            ReplayEvent::new(
+                http::Request::builder()
+                    .uri(uri)
+                    .body(SdkBody::empty())
+                    .unwrap(),
+                http::Response::builder()
+                    .status(StatusCode::OK)
+                    .body(SdkBody::from(list_response(
+                        &[format!("{VALID_HASH1}-10")],
+                        Some(next_continuation_token),
+                    )))
+                    .unwrap(),
+            )
+        };
+        // The key group needs more pages than digests are checked in it.
+        let mock_client = StaticReplayClient::new(vec![
+            list_page(
+                format!("https://{BUCKET_NAME}.s3.{REGION}.amazonaws.com/?list-type=2&prefix=0"),
+                "page2",
+            ),
+            list_page(
+                format!(
+                    "https://{BUCKET_NAME}.s3.{REGION}.amazonaws.com/?list-type=2&prefix=0&continuation-token=page2"
+                ),
+                "page3",
+            ),
+            ReplayEvent::new(
+                http::Request::builder()
+                    .uri(format!(
+                        "https://{BUCKET_NAME}.s3.{REGION}.amazonaws.com/{VALID_HASH1}-10"
+                    ))
+                    .body(SdkBody::empty())
+                    .unwrap(),
+                http::Response::builder()
+                    .status(StatusCode::OK)
+                    .header(header::CONTENT_LENGTH, "10")
+                    .body(SdkBody::empty())
+                    .unwrap(),
+            ),
+            ReplayEvent::new(
+                http::Request::builder()
+                    .uri(format!(
+                        "https://{BUCKET_NAME}.s3.{REGION}.amazonaws.com/{VALID_HASH2}-20"
+                    ))
+                    .body(SdkBody::empty())
+                    .unwrap(),
+                http::Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(SdkBody::empty())
+                    .unwrap(),
+            ),
+        ]);
+        let test_config = Builder::new()
+            .behavior_version(BehaviorVersion::v2023_11_09())
+            .region(Region::from_static(REGION))
+            .http_client(mock_client.clone())
+            .build();
+        let s3_client = aws_sdk_s3::Client::from_conf(test_config);
+        let store = S3Store::new_with_client_and_jitter(
+            &nativelink_config::stores::S3Store {
+                bucket: BUCKET_NAME.to_string(),
+                list_prefix_length: 1,
+                list_batch_threshold: 2,
+                ..Default::default()
+            },
+            s3_client,
+            Arc::new(move |_delay| Duration::from_secs(0)),
+        )?;
+
+        let results = Pin::new(&store).has_many(&[digest1, digest2]).await?;
+        assert_eq!(results, vec![Some(10), None]);
+        mock_client.assert_requests_match(&[]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_part_touches_old_objects() -> Result<(), Error> {
+        const CONTENT_LENGTH: u64 = 4;
+        let mock_client = StaticReplayClient::new(vec![
+            ReplayEvent::new(
+                http::Request::builder()
+                    .uri(format!(
+                        "https://{BUCKET_NAME}.s3.{REGION}.amazonaws.com/{VALID_HASH1}-{CONTENT_LENGTH}?x-id=GetObject",
+                    ))
+                    .body(SdkBody::empty())
+                    .unwrap(),
+                http::Response::builder()
+                    .status(StatusCode::OK)
+                    .header(header::LAST_MODIFIED, "Wed, 21 Oct 2015 07:28:00 GMT")
+                    .header("x-amz-meta-owner", "nativelink")
+                    .body(SdkBody::from("data"))
+                    .unwrap(),
+            ),
+            ReplayEvent::new(
+                http::Request::builder()
+                    .uri(format!(
+                        "https://{BUCKET_NAME}.s3.{REGION}.amazonaws.com/{VALID_HASH1}-{CONTENT_LENGTH}?x-id=CopyObject",
+                    ))
+                    .method("PUT")
+                    .header("x-amz-metadata-directive", "REPLACE")
+                    .header("x-amz-meta-owner", "nativelink")
+                    .body(SdkBody::empty())
+                    .unwrap(),
+                http::Response::builder()
+                    .status(StatusCode::OK)
+                    .body(SdkBody::from("<CopyObjectResult></CopyObjectResult>"))
+                    .unwrap(),
+            ),
+            ReplayEvent::new(
+                http::Request::builder()
+                    .uri(format!(
+                        "https://{BUCKET_NAME}.s3.{REGION}.amazonaws.com/{VALID_HASH1}-{CONTENT_LENGTH}?x-id=GetObject",
+                    ))
+                    .body(SdkBody::empty())
+                    .unwrap(),
+                http::Response::builder()
+                    .status(StatusCode::OK)
+                    .header(header::LAST_MODIFIED, "Wed, 21 Oct 2015 07:28:00 GMT")
+                    .body(SdkBody::from("data"))
+                    .unwrap(),
+            ),
+        ]);
+        let test_config = Builder::new()
+            .behavior_version(BehaviorVersion::v2023_11_09())
+            .region(Region::from_static(REGION))
+            .http_client(mock_client.clone())
+            .build();
+        let s3_client = aws_sdk_s3::Client::from_conf(test_config);
+        let store = S3Store::new_with_client_and_jitter(
+            &nativelink_config::stores::S3Store {
+                bucket: BUCKET_NAME.to_string(),
+                touch_on_read_seconds: 60,
+                ..Default::default()
+            },
+            s3_client,
+            Arc::new(move |_delay| Duration::from_secs(0)),
+        )?;
+
+        let data = Pin::new(&store)
+            .get_part_unchunked(
+                DigestInfo::try_new(VALID_HASH1, CONTENT_LENGTH)?,
+                0,
+                None,
+                None,
+            )
+            .await?;
+        assert_eq!(data, Bytes::from_static(b"data"));
+
+        // The object is refreshed in the background.
+        while mock_client.actual_requests().count() < 2 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        // Reading it again before the copy is visible does not refresh it
+        // again.
+        Pin::new(&store)
+            .get_part_unchunked(
+                DigestInfo::try_new(VALID_HASH1, CONTENT_LENGTH)?,
+                0,
+                None,
+                None,
+            )
+            .await?;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(mock_client.actual_requests().count(), 3);
+        mock_client.assert_requests_match(&[]);
+        Ok(())
+    }
 }
@@ -0,0 +1,283 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::pin::Pin;
+use std::sync::Arc;
+
+use nativelink_config::stores::{EvictionPolicy, StoreConfig, StoreTier};
+use nativelink_error::Error;
+use nativelink_store::memory_store::MemoryStore;
+use nativelink_store::tiered_store::TieredStore;
+use nativelink_util::common::DigestInfo;
+use nativelink_util::store_trait::Store;
+
+fn make_tier(memory_config: nativelink_config::stores::MemoryStore) -> StoreTier {
+    StoreTier {
+        store: StoreConfig::memory(memory_config),
+        promotion_hit_count: 0,
+        max_promotion_size: 0,
+        skip_on_update: false,
+        demote_on_eviction: false,
+    }
+}
+
+fn make_stores(tiers: Vec<StoreTier>) -> Result<(Arc<TieredStore>, Vec<Arc<MemoryStore>>), Error> {
+    let memory_stores: Vec<Arc<MemoryStore>> = tiers
+        .iter()
+        .map(|tier| {
+            let StoreConfig::memory(memory_config) = &tier.store else {
+                panic!("Expected memory store");
+            };
+            Arc::new(MemoryStore::new(memory_config))
+        })
+        .collect();
+    let tiered_store = TieredStore::new(
+        &nativelink_config::stores::TieredStore {
+            tiers,
+            hit_counter_eviction_policy: None,
+        },
+        memory_stores
+            .iter()
+            .map(|store| store.clone() as Arc<dyn Store>)
+            .collect(),
+    )?;
+    Ok((tiered_store, memory_stores))
+}
+
+#[cfg(test)]
+mod tiered_store_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*; // Must be declared in every module.
+
+    const VALID_HASH1: &str = "0123456789abcdef000000000000000000010000000000000123456789abcdef";
+    const VALID_HASH2: &str = "0123456789abcdef000000000000000000020000000000000123456789abcdef";
+
+    #[tokio::test]
+    async fn update_skips_tiers_with_skip_on_update() -> Result<(), Error> {
+        let (tiered_store, memory_stores) = make_stores(vec![
+            StoreTier {
+                skip_on_update: true,
+                ..make_tier(Default::default())
+            },
+            make_tier(Default::default()),
+            make_tier(Default::default()),
+        ])?;
+        let digest = DigestInfo::try_new(VALID_HASH1, 4)?;
+        Pin::new(tiered_store.as_ref())
+            .update_oneshot(digest, "data".into())
+            .await?;
+
+        assert_eq!(Pin::new(memory_stores[0].as_ref()).has(digest).await?, None);
+        assert_eq!(
+            Pin::new(memory_stores[1].as_ref()).has(digest).await?,
+            Some(4)
+        );
+        assert_eq!(
+            Pin::new(memory_stores[2].as_ref()).has(digest).await?,
+            Some(4)
+        );
+        assert_eq!(Pin::new(tiered_store.as_ref()).has(digest).await?, Some(4));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn has_checks_every_tier() -> Result<(), Error> {
+        let (tiered_store, memory_stores) = make_stores(vec![
+            make_tier(Default::default()),
+            make_tier(Default::default()),
+            make_tier(Default::default()),
+        ])?;
+        let digest1 = DigestInfo::try_new(VALID_HASH1, 4)?;
+        let digest2 = DigestInfo::try_new(VALID_HASH2, 5)?;
+        Pin::new(memory_stores[2].as_ref())
+            .update_oneshot(digest1, "data".into())
+            .await?;
+
+        let results = Pin::new(tiered_store.as_ref())
+            .has_many(&[digest2, digest1])
+            .await?;
+        assert_eq!(results, vec![None, Some(4)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn promotes_after_hit_count_is_reached() -> Result<(), Error> {
+        let (tiered_store, memory_stores) = make_stores(vec![
+            StoreTier {
+                promotion_hit_count: 2,
+                ..make_tier(Default::default())
+            },
+            make_tier(Default::default()),
+            make_tier(Default::default()),
+        ])?;
+        let digest = DigestInfo::try_new(VALID_HASH1, 11)?;
+        Pin::new(memory_stores[2].as_ref())
+            .update_oneshot(digest, "hello world".into())
+            .await?;
+        let tiered_store = Pin::new(tiered_store.as_ref());
+
+        // First read only promotes into the tier without a hit count.
+        let data = tiered_store
+            .get_part_unchunked(digest, 6, Some(5), None)
+            .await?;
+        assert_eq!(data, "world");
+        assert_eq!(Pin::new(memory_stores[0].as_ref()).has(digest).await?, None);
+        assert_eq!(
+            Pin::new(memory_stores[1].as_ref()).has(digest).await?,
+            Some(11)
+        );
+
+        // Second read is served from the middle tier and reaches the hit count.
+        let data = tiered_store
+            .get_part_unchunked(digest, 0, None, None)
+            .await?;
+        assert_eq!(data, "hello world");
+        assert_eq!(
+            Pin::new(memory_stores[0].as_ref())
+                .get_part_unchunked(digest, 0, None, None)
+                .await?,
+            "hello world"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn concurrent_reads_are_all_counted() -> Result<(), Error> {
+        let (tiered_store, memory_stores) = make_stores(vec![
+            StoreTier {
+                promotion_hit_count: 3,
+                ..make_tier(Default::default())
+            },
+            make_tier(Default::default()),
+        ])?;
+        let digest = DigestInfo::try_new(VALID_HASH1, 11)?;
+        Pin::new(memory_stores[1].as_ref())
+            .update_oneshot(digest, "hello world".into())
+            .await?;
+        let tiered_store = Pin::new(tiered_store.as_ref());
+
+        let (data1, data2, data3) = futures::join!(
+            tiered_store.get_part_unchunked(digest, 0, None, None),
+            tiered_store.get_part_unchunked(digest, 0, None, None),
+            tiered_store.get_part_unchunked(digest, 0, None, None),
+        );
+        assert_eq!(
+            (data1?, data2?, data3?),
+            (
+                "hello world".into(),
+                "hello world".into(),
+                "hello world".into()
+            )
+        );
+        assert_eq!(
+            Pin::new(memory_stores[0].as_ref()).has(digest).await?,
+            Some(11)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn max_promotion_size_prevents_promotion() -> Result<(), Error> {
+        let (tiered_store, memory_stores) = make_stores(vec![
+            StoreTier {
+                max_promotion_size: 10,
+                ..make_tier(Default::default())
+            },
+            make_tier(Default::default()),
+        ])?;
+        let digest = DigestInfo::try_new(VALID_HASH1, 11)?;
+        Pin::new(memory_stores[1].as_ref())
+            .update_oneshot(digest, "hello world".into())
+            .await?;
+
+        let data = Pin::new(tiered_store.as_ref())
+            .get_part_unchunked(digest, 0, None, None)
+            .await?;
+        assert_eq!(data, "hello world");
+        assert_eq!(Pin::new(memory_stores[0].as_ref()).has(digest).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn demotes_evicted_objects() -> Result<(), Error> {
+        let (tiered_store, memory_stores) = make_stores(vec![
+            StoreTier {
+                demote_on_eviction: true,
+                ..make_tier(nativelink_config::stores::MemoryStore {
+                    eviction_policy: Some(EvictionPolicy {
+                        max_count: 1,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+            },
+            StoreTier {
+                skip_on_update: true,
+                ..make_tier(Default::default())
+            },
+        ])?;
+        let digest1 = DigestInfo::try_new(VALID_HASH1, 5)?;
+        let digest2 = DigestInfo::try_new(VALID_HASH2, 5)?;
+        let tiered_store = Pin::new(tiered_store.as_ref());
+        tiered_store.update_oneshot(digest1, "data1".into()).await?;
+        assert_eq!(
+            Pin::new(memory_stores[1].as_ref()).has(digest1).await?,
+            None
+        );
+
+        tiered_store.update_oneshot(digest2, "data2".into()).await?;
+        // Demotion happens in a spawned task.
+        for _ in 0..100 {
+            if Pin::new(memory_stores[1].as_ref())
+                .has(digest1)
+                .await?
+                .is_some()
+            {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(
+            Pin::new(memory_stores[0].as_ref()).has(digest1).await?,
+            None
+        );
+        assert_eq!(
+            Pin::new(memory_stores[1].as_ref())
+                .get_part_unchunked(digest1, 0, None, None)
+                .await?,
+            "data1"
+        );
+        assert_eq!(
+            tiered_store
+                .get_part_unchunked(digest1, 0, None, None)
+                .await?,
+            "data1"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn demote_on_eviction_rejected_on_last_tier() -> Result<(), Error> {
+        let result = make_stores(vec![
+            make_tier(Default::default()),
+            StoreTier {
+                demote_on_eviction: true,
+                ..make_tier(Default::default())
+            },
+        ]);
+        assert!(result.is_err(), "Expected TieredStore::new to fail");
+        Ok(())
+    }
+}
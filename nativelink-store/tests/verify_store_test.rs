@@ -19,11 +19,13 @@ use futures::try_join;
 
 #[cfg(test)]
 mod verify_store_tests {
-    use nativelink_error::{Error, ResultExt};
+    use nativelink_error::{Code, Error, ResultExt};
+    use nativelink_proto::build::bazel::remote::execution::v2::digest_function;
     use nativelink_store::memory_store::MemoryStore;
     use nativelink_store::verify_store::VerifyStore;
     use nativelink_util::buf_channel::make_buf_channel_pair;
     use nativelink_util::common::DigestInfo;
+    use nativelink_util::request_context::{RequestContext, StoreAccess};
     use nativelink_util::store_trait::{Store, UploadSizeInfo};
     use pretty_assertions::assert_eq; // Must be declared in every module.
 
@@ -43,6 +45,8 @@ mod verify_store_tests {
                 ),
                 verify_size: false,
                 hash_verification_function: None,
+                verify_on_read: false,
+                remove_corrupt_on_read: false,
             },
             inner_store.clone(),
         );
@@ -77,6 +81,8 @@ mod verify_store_tests {
                 ),
                 verify_size: true,
                 hash_verification_function: None,
+                verify_on_read: false,
+                remove_corrupt_on_read: false,
             },
             inner_store.clone(),
         );
@@ -122,6 +128,8 @@ mod verify_store_tests {
                 ),
                 verify_size: true,
                 hash_verification_function: None,
+                verify_on_read: false,
+                remove_corrupt_on_read: false,
             },
             inner_store.clone(),
         );
@@ -151,6 +159,8 @@ mod verify_store_tests {
                 ),
                 verify_size: true,
                 hash_verification_function: None,
+                verify_on_read: false,
+                remove_corrupt_on_read: false,
             },
             inner_store.clone(),
         );
@@ -191,6 +201,8 @@ mod verify_store_tests {
                 hash_verification_function: Some(
                     nativelink_config::stores::ConfigDigestHashFunction::sha256,
                 ),
+                verify_on_read: false,
+                remove_corrupt_on_read: false,
             },
             inner_store.clone(),
         );
@@ -224,6 +236,8 @@ mod verify_store_tests {
                 hash_verification_function: Some(
                     nativelink_config::stores::ConfigDigestHashFunction::sha256,
                 ),
+                verify_on_read: false,
+                remove_corrupt_on_read: false,
             },
             inner_store.clone(),
         );
@@ -269,6 +283,8 @@ mod verify_store_tests {
                 hash_verification_function: Some(
                     nativelink_config::stores::ConfigDigestHashFunction::blake3,
                 ),
+                verify_on_read: false,
+                remove_corrupt_on_read: false,
             },
             inner_store.clone(),
         );
@@ -302,6 +318,8 @@ mod verify_store_tests {
                 hash_verification_function: Some(
                     nativelink_config::stores::ConfigDigestHashFunction::blake3,
                 ),
+                verify_on_read: false,
+                remove_corrupt_on_read: false,
             },
             inner_store.clone(),
         );
@@ -332,4 +350,139 @@ mod verify_store_tests {
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn verify_on_read_passes_valid_data() -> Result<(), Error> {
+        let inner_store = Arc::new(MemoryStore::new(
+            &nativelink_config::stores::MemoryStore::default(),
+        ));
+        let store_owned = VerifyStore::new(
+            &nativelink_config::stores::VerifyStore {
+                backend: nativelink_config::stores::StoreConfig::memory(
+                    nativelink_config::stores::MemoryStore::default(),
+                ),
+                verify_size: true,
+                hash_verification_function: Some(
+                    nativelink_config::stores::ConfigDigestHashFunction::sha256,
+                ),
+                verify_on_read: true,
+                remove_corrupt_on_read: true,
+            },
+            inner_store.clone(),
+        );
+        let store = Pin::new(&store_owned);
+
+        /// This value is sha256("123").
+        const HASH: &str = "a665a45920422f9d417e4867efdc4fb8a04a1f3fff1fa07e998e86f7f7a27ae3";
+        const VALUE: &str = "123";
+        let digest = DigestInfo::try_new(HASH, 3).unwrap();
+        store.update_oneshot(digest, VALUE.into()).await?;
+        assert_eq!(
+            store.get_part_unchunked(digest, 0, None, None).await,
+            Ok(VALUE.into()),
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn verify_on_read_fails_and_removes_corrupt_data() -> Result<(), Error> {
+        let inner_store = Arc::new(MemoryStore::new(
+            &nativelink_config::stores::MemoryStore::default(),
+        ));
+        let store_owned = VerifyStore::new(
+            &nativelink_config::stores::VerifyStore {
+                backend: nativelink_config::stores::StoreConfig::memory(
+                    nativelink_config::stores::MemoryStore::default(),
+                ),
+                verify_size: true,
+                hash_verification_function: Some(
+                    nativelink_config::stores::ConfigDigestHashFunction::sha256,
+                ),
+                verify_on_read: true,
+                remove_corrupt_on_read: true,
+            },
+            inner_store.clone(),
+        );
+        let store = Pin::new(&store_owned);
+
+        /// This value is sha256("123").
+        const HASH: &str = "a665a45920422f9d417e4867efdc4fb8a04a1f3fff1fa07e998e86f7f7a27ae3";
+        const CORRUPT_VALUE: &str = "124";
+        let digest = DigestInfo::try_new(HASH, 3).unwrap();
+        // Corrupt the entry behind the back of the verify store.
+        Pin::new(inner_store.as_ref())
+            .update_oneshot(digest, CORRUPT_VALUE.into())
+            .await?;
+
+        // Partial reads are not verified.
+        assert_eq!(
+            store.get_part_unchunked(digest, 1, None, None).await,
+            Ok("24".into()),
+        );
+
+        let err = RequestContext::new("", digest_function::Value::Sha256 as i32, StoreAccess::Cas)
+            .scope(store.get_part_unchunked(digest, 0, None, None))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, Code::DataLoss, "Unexpected error: {err:?}");
+        assert_eq!(
+            Pin::new(inner_store.as_ref()).has(digest).await,
+            Ok(None),
+            "Expected corrupt data to be removed from store"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn verify_uses_request_digest_function() -> Result<(), Error> {
+        let inner_store = Arc::new(MemoryStore::new(
+            &nativelink_config::stores::MemoryStore::default(),
+        ));
+        let store_owned = VerifyStore::new(
+            &nativelink_config::stores::VerifyStore {
+                backend: nativelink_config::stores::StoreConfig::memory(
+                    nativelink_config::stores::MemoryStore::default(),
+                ),
+                verify_size: true,
+                hash_verification_function: Some(
+                    nativelink_config::stores::ConfigDigestHashFunction::sha256,
+                ),
+                verify_on_read: true,
+                remove_corrupt_on_read: true,
+            },
+            inner_store.clone(),
+        );
+        let store = Pin::new(&store_owned);
+
+        /// This value is blake3("123").
+        const HASH: &str = "b3d4f8803f7e24b8f389b072e75477cdbcfbe074080fb5e500e53e26e054158e";
+        const VALUE: &str = "123";
+        let digest = DigestInfo::try_new(HASH, 3).unwrap();
+        let context =
+            RequestContext::new("", digest_function::Value::Blake3 as i32, StoreAccess::Cas);
+        context
+            .clone()
+            .scope(store.update_oneshot(digest, VALUE.into()))
+            .await?;
+        assert_eq!(
+            context
+                .scope(store.get_part_unchunked(digest, 0, None, None))
+                .await,
+            Ok(VALUE.into()),
+        );
+
+        // Outside of a request the configured function is used, which does
+        // not prove the entry is corrupt.
+        let err = store
+            .get_part_unchunked(digest, 0, None, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, Code::DataLoss, "Unexpected error: {err:?}");
+        assert_eq!(
+            Pin::new(inner_store.as_ref()).has(digest).await,
+            Ok(Some(VALUE.len())),
+            "Expected entry to be kept on a hash mismatch outside of a request"
+        );
+        Ok(())
+    }
 }
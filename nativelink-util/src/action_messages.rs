@@ -42,6 +42,26 @@ use crate::platform_properties::PlatformProperties;
 /// Default priority remote execution jobs will get when not provided.
 pub const DEFAULT_EXECUTION_PRIORITY: i32 = 0;
 
+/// Path segment that separates the instance name from the action in the
+/// ByteStream resource names of live action output.
+const ACTION_OUTPUT_SEGMENT: &str = "action-logs";
+
+/// Output streams of a running action that can be read while it runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ActionOutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl ActionOutputStream {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        }
+    }
+}
+
 /// This is a utility struct used to make it easier to match `ActionInfos` in a
 /// `HashMap` without needing to construct an entire `ActionInfo`.
 /// Since the hashing only needs the digest and salt we can just alias them here
@@ -82,6 +102,46 @@ impl ActionInfoHashKey {
             self.salt
         )
     }
+
+    /// ByteStream resource name that the output of the action can be read
+    /// from while it is executing. The format is:
+    /// `{instance_name}/action-logs/{hash}-{size}/{salt}/{stdout|stderr}`.
+    pub fn output_stream_name(&self, stream: ActionOutputStream) -> String {
+        format!(
+            "{}/{ACTION_OUTPUT_SEGMENT}/{}-{}/{:X}/{}",
+            self.instance_name,
+            self.digest.hash_str(),
+            self.digest.size_bytes,
+            self.salt,
+            stream.as_str()
+        )
+    }
+
+    /// Parses a name made by `output_stream_name()`.
+    pub fn try_from_output_stream_name(
+        resource_name: &str,
+    ) -> Result<(Self, ActionOutputStream), Error> {
+        let (action_part, stream) = resource_name
+            .rsplit_once('/')
+            .err_tip(|| format!("Invalid action output stream name - {resource_name}"))?;
+        let stream = match stream {
+            "stdout" => ActionOutputStream::Stdout,
+            "stderr" => ActionOutputStream::Stderr,
+            _ => {
+                return Err(make_input_err!(
+                "Expected action output stream name to end in stdout or stderr - {resource_name}"
+            ))
+            }
+        };
+        let (instance_name, action_part) = action_part
+            .split_once(&format!("/{ACTION_OUTPUT_SEGMENT}/"))
+            .err_tip(|| format!("Invalid action output stream name - {resource_name}"))?;
+        // The instance name may contain slashes, so it is not parsed by `try_from()`.
+        let mut unique_qualifier = Self::try_from(format!("/{action_part}").as_str())
+            .err_tip(|| format!("Invalid action output stream name - {resource_name}"))?;
+        unique_qualifier.instance_name = instance_name.to_string();
+        Ok((unique_qualifier, stream))
+    }
 }
 
 impl TryFrom<&str> for ActionInfoHashKey {
@@ -1028,20 +1088,29 @@ impl ActionState {
     pub fn action_digest(&self) -> &DigestInfo {
         &self.unique_qualifier.digest
     }
-}
-
-impl MetricsComponent for ActionState {
-    fn gather_metrics(&self, c: &mut CollectorState) {
-        c.publish("stage", &self.stage, "");
-    }
-}
 
-impl From<ActionState> for Operation {
-    fn from(val: ActionState) -> Self {
-        let stage = Into::<execution_stage::Value>::into(&val.stage) as i32;
-
-        let result = if val.stage.has_action_result() {
-            let execute_response: ExecuteResponse = val.stage.into();
+    /// Converts the state into an `Operation`. The names of the streams the
+    /// output can be read from while the action executes are only set if
+    /// `advertise_output_streams` is, which should only be done when a
+    /// ByteStream service serves the output of the action's instance.
+    pub fn into_operation(self, advertise_output_streams: bool) -> Operation {
+        let stage = Into::<execution_stage::Value>::into(&self.stage) as i32;
+
+        // Output can only be streamed while the action is running, once it
+        // completes it is available from the `ActionResult`.
+        let (stdout_stream_name, stderr_stream_name) =
+            if advertise_output_streams && self.stage == ActionStage::Executing {
+                (
+                    self.unique_qualifier
+                        .output_stream_name(ActionOutputStream::Stdout),
+                    self.unique_qualifier
+                        .output_stream_name(ActionOutputStream::Stderr),
+                )
+            } else {
+                (String::default(), String::default())
+            };
+        let result = if self.stage.has_action_result() {
+            let execute_response: ExecuteResponse = self.stage.into();
             Some(LongRunningResult::Response(to_any(&execute_response)))
         } else {
             None
@@ -1049,18 +1118,29 @@ impl From<ActionState> for Operation {
 
         let metadata = ExecuteOperationMetadata {
             stage,
-            action_digest: Some((&val.unique_qualifier.digest).into()),
-            // TODO(blaise.bruer) We should support stderr/stdout streaming.
-            stdout_stream_name: String::default(),
-            stderr_stream_name: String::default(),
+            action_digest: Some((&self.unique_qualifier.digest).into()),
+            stdout_stream_name,
+            stderr_stream_name,
             partial_execution_metadata: None,
         };
 
-        Self {
-            name: val.unique_qualifier.action_name(),
+        Operation {
+            name: self.unique_qualifier.action_name(),
             metadata: Some(to_any(&metadata)),
             done: result.is_some(),
             result,
         }
     }
 }
+
+impl MetricsComponent for ActionState {
+    fn gather_metrics(&self, c: &mut CollectorState) {
+        c.publish("stage", &self.stage, "");
+    }
+}
+
+impl From<ActionState> for Operation {
+    fn from(val: ActionState) -> Self {
+        val.into_operation(false)
+    }
+}
@@ -12,9 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::ops::DerefMut;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicI32, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use async_lock::Mutex;
@@ -131,11 +133,49 @@ struct State<T: LenEntry + Debug> {
     removed_bytes: Counter,
     removed_items: CounterWithTime,
     lifetime_inserted_bytes: Counter,
+
+    /// Items protected from eviction, mapped to the time (in seconds since
+    /// the anchor time) their pin expires.
+    pins: HashMap<DigestInfo, i32>,
+    /// Sum of the sizes of all pinned items.
+    pinned_bytes: u64,
+    rejected_pins: Counter,
 }
 
 impl<T: LenEntry + Debug + Sync> State<T> {
-    async fn remove(&mut self, eviction_item: &EvictionItem<T>, replaced: bool) {
+    fn is_pinned(&self, digest: &DigestInfo, now: i32) -> bool {
+        self.pins
+            .get(digest)
+            .is_some_and(|expires_at| *expires_at > now)
+    }
+
+    fn remove_expired_pins(&mut self, now: i32) {
+        let lru = &self.lru;
+        let mut released_bytes = 0;
+        self.pins.retain(|digest, expires_at| {
+            if *expires_at > now {
+                return true;
+            }
+            released_bytes += lru.peek(digest).map_or(0, |item| item.data.len() as u64);
+            false
+        });
+        self.pinned_bytes -= released_bytes;
+    }
+
+    /// Note: A replaced item keeps its pin.
+    async fn remove(
+        &mut self,
+        digest: &DigestInfo,
+        eviction_item: &EvictionItem<T>,
+        replaced: bool,
+    ) {
         self.sum_store_size -= eviction_item.data.len() as u64;
+        if self.pins.contains_key(digest) {
+            self.pinned_bytes -= eviction_item.data.len() as u64;
+            if !replaced {
+                self.pins.remove(digest);
+            }
+        }
         if replaced {
             self.replaced_items.inc();
             self.replaced_bytes.add(eviction_item.data.len() as u64);
@@ -148,13 +188,22 @@ impl<T: LenEntry + Debug + Sync> State<T> {
     }
 }
 
+/// Callback that is invoked with every item the eviction policy removes
+/// from the map. It is called while the map is locked, so it must not
+/// block or access the map.
+pub type EvictionCallback<T> = Box<dyn Fn(&DigestInfo, &T) + Send + Sync>;
+
 pub struct EvictingMap<T: LenEntry + Debug, I: InstantWrapper> {
     state: Mutex<State<T>>,
+    eviction_callback: OnceLock<EvictionCallback<T>>,
     anchor_time: I,
-    max_bytes: u64,
-    evict_bytes: u64,
-    max_seconds: i32,
-    max_count: u64,
+    // The limits can be changed while the map is in use, see
+    // `set_eviction_policy()`.
+    max_bytes: AtomicU64,
+    evict_bytes: AtomicU64,
+    max_seconds: AtomicI32,
+    max_count: AtomicU64,
+    max_pinned_bytes: AtomicU64,
 }
 
 impl<T, I> EvictingMap<T, I>
@@ -176,21 +225,60 @@ where
                 removed_bytes: Counter::default(),
                 removed_items: CounterWithTime::default(),
                 lifetime_inserted_bytes: Counter::default(),
+                pins: HashMap::new(),
+                pinned_bytes: 0,
+                rejected_pins: Counter::default(),
             }),
+            eviction_callback: OnceLock::new(),
             anchor_time,
-            max_bytes: config.max_bytes as u64,
-            evict_bytes: config.evict_bytes as u64,
-            max_seconds: config.max_seconds as i32,
-            max_count: config.max_count,
+            max_bytes: AtomicU64::new(config.max_bytes as u64),
+            evict_bytes: AtomicU64::new(config.evict_bytes as u64),
+            max_seconds: AtomicI32::new(config.max_seconds as i32),
+            max_count: AtomicU64::new(config.max_count),
+            max_pinned_bytes: AtomicU64::new(config.max_pinned_bytes as u64),
         }
     }
 
+    /// Replaces the limits of the eviction policy. Items over the new limits
+    /// are evicted right away. Existing pins are kept, even if they exceed a
+    /// lower `max_pinned_bytes`.
+    pub async fn set_eviction_policy(&self, config: &EvictionPolicy) {
+        let mut state = self.state.lock().await;
+        self.max_bytes
+            .store(config.max_bytes as u64, Ordering::Relaxed);
+        self.evict_bytes
+            .store(config.evict_bytes as u64, Ordering::Relaxed);
+        self.max_seconds
+            .store(config.max_seconds as i32, Ordering::Relaxed);
+        self.max_count.store(config.max_count, Ordering::Relaxed);
+        self.max_pinned_bytes
+            .store(config.max_pinned_bytes as u64, Ordering::Relaxed);
+        self.evict_items(state.deref_mut()).await;
+    }
+
+    fn seconds_since_anchor(&self) -> i32 {
+        self.anchor_time.elapsed().as_secs() as i32
+    }
+
     /// Returns the number of key-value pairs that are currently in the the cache.
     /// Function is not for production code paths.
     pub async fn len_for_test(&self) -> usize {
         self.state.lock().await.lru.len()
     }
 
+    /// Sets the callback to be invoked when an item is evicted because of the
+    /// eviction policy. Explicit removals and replacements do not trigger it.
+    /// Only one callback may be set, returns `false` if one was already set.
+    pub fn set_eviction_callback(&self, callback: EvictionCallback<T>) -> bool {
+        self.eviction_callback.set(callback).is_ok()
+    }
+
+    fn notify_evicted(&self, digest: &DigestInfo, data: &T) {
+        if let Some(callback) = self.eviction_callback.get() {
+            callback(digest, data);
+        }
+    }
+
     pub async fn build_lru_index(&self) -> SerializedLRU {
         let mut state = self.state.lock().await;
         self.evict_items(state.deref_mut()).await;
@@ -215,6 +303,8 @@ where
         let mut state = self.state.lock().await;
         self.anchor_time = I::from_secs(seiralized_lru.anchor_time);
         state.lru.clear();
+        state.pins.clear();
+        state.pinned_bytes = 0;
         for (digest, seconds_since_anchor) in seiralized_lru.data {
             let entry = entry_builder(&digest);
             state.lru.put(
@@ -229,6 +319,60 @@ where
         self.evict_items(state.deref_mut()).await;
     }
 
+    /// Same as `build_lru_index()`, but also returns the data of every item
+    /// in the same order as `SerializedLRU::data`. Used to save the content
+    /// of the map without changing the order of the items like `get()` would.
+    pub async fn build_lru_index_with_items(&self) -> (SerializedLRU, Vec<T>) {
+        let mut state = self.state.lock().await;
+        self.evict_items(state.deref_mut()).await;
+
+        let mut serialized_lru = SerializedLRU {
+            data: Vec::with_capacity(state.lru.len()),
+            anchor_time: self.anchor_time.unix_timestamp(),
+        };
+        let mut items = Vec::with_capacity(state.lru.len());
+        for (digest, eviction_item) in state.lru.iter() {
+            serialized_lru
+                .data
+                .push((*digest, eviction_item.seconds_since_anchor));
+            items.push(eviction_item.data.clone());
+        }
+        (serialized_lru, items)
+    }
+
+    /// Adds the items returned by `build_lru_index_with_items()` back into the
+    /// map. Unlike `restore_lru()` the map keeps its current anchor time and
+    /// content. Restored items are placed after the current items in the same
+    /// LRU order they were saved with, and items already in the map are kept
+    /// as they are newer.
+    pub async fn restore_items(&self, serialized_lru: SerializedLRU, items: Vec<T>) {
+        let mut state = self.state.lock().await;
+        let anchor_offset =
+            serialized_lru.anchor_time as i64 - self.anchor_time.unix_timestamp() as i64;
+        for ((digest, seconds_since_anchor), data) in serialized_lru.data.into_iter().zip(items) {
+            if state.lru.contains(&digest) {
+                continue;
+            }
+            let item_size = data.len() as u64;
+            state.lru.put(
+                digest,
+                EvictionItem {
+                    seconds_since_anchor: (seconds_since_anchor as i64 + anchor_offset) as i32,
+                    data,
+                },
+            );
+            // Items are given from the most to the least recently used, so
+            // each new item is the least recently used one so far.
+            state.lru.demote(&digest);
+            state.sum_store_size += item_size;
+            if state.pins.contains_key(&digest) {
+                state.pinned_bytes += item_size;
+            }
+            state.lifetime_inserted_bytes.add(item_size);
+        }
+        self.evict_items(state.deref_mut()).await;
+    }
+
     fn should_evict(
         &self,
         lru_len: usize,
@@ -238,12 +382,13 @@ where
     ) -> bool {
         let is_over_size = max_bytes != 0 && sum_store_size >= max_bytes;
 
-        let evict_older_than_seconds =
-            (self.anchor_time.elapsed().as_secs() as i32) - self.max_seconds;
+        let max_seconds = self.max_seconds.load(Ordering::Relaxed);
+        let evict_older_than_seconds = (self.anchor_time.elapsed().as_secs() as i32) - max_seconds;
         let old_item_exists =
-            self.max_seconds != 0 && peek_entry.seconds_since_anchor < evict_older_than_seconds;
+            max_seconds != 0 && peek_entry.seconds_since_anchor < evict_older_than_seconds;
 
-        let is_over_count = self.max_count != 0 && (lru_len as u64) > self.max_count;
+        let max_count = self.max_count.load(Ordering::Relaxed);
+        let is_over_count = max_count != 0 && (lru_len as u64) > max_count;
 
         is_over_size || old_item_exists || is_over_count
     }
@@ -253,30 +398,38 @@ where
             return;
         };
 
-        let max_bytes = if self.max_bytes != 0
-            && self.evict_bytes != 0
-            && self.should_evict(
-                state.lru.len(),
-                peek_entry,
-                state.sum_store_size,
-                self.max_bytes,
-            ) {
-            if self.max_bytes > self.evict_bytes {
-                self.max_bytes - self.evict_bytes
-            } else {
-                0
-            }
+        let max_bytes = self.max_bytes.load(Ordering::Relaxed);
+        let evict_bytes = self.evict_bytes.load(Ordering::Relaxed);
+        let max_bytes = if max_bytes != 0
+            && evict_bytes != 0
+            && self.should_evict(state.lru.len(), peek_entry, state.sum_store_size, max_bytes)
+        {
+            max_bytes.saturating_sub(evict_bytes)
         } else {
-            self.max_bytes
+            max_bytes
         };
 
+        let now = self.seconds_since_anchor();
+        let mut skipped_pinned_items = 0;
         while self.should_evict(state.lru.len(), peek_entry, state.sum_store_size, max_bytes) {
             let (key, eviction_item) = state
                 .lru
                 .pop_lru()
                 .expect("Tried to peek() then pop() but failed");
-            info!("\x1b[0;31mEvicting Map\x1b[0m: Evicting {}", key.hash_str());
-            state.remove(&eviction_item, false).await;
+            if state.is_pinned(&key, now) {
+                // Pinned items are moved to the front so the next oldest
+                // item gets evicted instead. Once every item was skipped
+                // only pinned items are left and nothing can be evicted.
+                state.lru.put(key, eviction_item);
+                skipped_pinned_items += 1;
+                if skipped_pinned_items >= state.lru.len() {
+                    return;
+                }
+            } else {
+                info!("\x1b[0;31mEvicting Map\x1b[0m: Evicting {}", key.hash_str());
+                self.notify_evicted(&key, &eviction_item.data);
+                state.remove(&key, &eviction_item, false).await;
+            }
 
             peek_entry = if let Some((_, entry)) = state.lru.peek_lru() {
                 entry
@@ -304,7 +457,7 @@ where
             "\x1b[0;31mEvicting Map\x1b[0m: Touch failed, evicting {}",
             key.hash_str()
         );
-        state.remove(&eviction_item, false).await;
+        state.remove(&key, &eviction_item, false).await;
         None
     }
 
@@ -318,14 +471,17 @@ where
 
         let mut lru_len = state.lru.len();
         let mut sum_store_size = state.sum_store_size;
+        let now = self.seconds_since_anchor();
+        let max_bytes = self.max_bytes.load(Ordering::Relaxed);
         let to_touch_or_remove: Vec<Option<T>> = digests
             .iter()
             .map(|digest| {
                 // Determine if a digest should be evicted or data should be touched.
                 // Digests to be eviected are collected in separate vector and chained
                 // in a single future.
+                let pinned = state.is_pinned(digest, now);
                 if let Some(entry) = state.lru.get(digest) {
-                    if self.should_evict(lru_len, entry, sum_store_size, self.max_bytes) {
+                    if !pinned && self.should_evict(lru_len, entry, sum_store_size, max_bytes) {
                         // Important to track the eviction size, otherwise if we
                         // reach the maximum we end up eviciting everything!
                         sum_store_size -= entry.data.len() as u64;
@@ -364,7 +520,8 @@ where
                     // Do not use inner_remove as it calls evict_items, which
                     // is precisely what we're doing here.
                     if let Some(entry) = state.lru.pop(digest) {
-                        state.remove(&entry, false).await;
+                        self.notify_evicted(digest, &entry.data);
+                        state.remove(digest, &entry, false).await;
                     }
                 }
             }
@@ -429,10 +586,13 @@ where
             };
 
             if let Some(old_item) = state.lru.put(digest, eviction_item) {
-                state.remove(&old_item, true).await;
+                state.remove(&digest, &old_item, true).await;
                 replaced_items.push(old_item.data);
             }
             state.sum_store_size += new_item_size;
+            if state.pins.contains_key(&digest) {
+                state.pinned_bytes += new_item_size;
+            }
             state.lifetime_inserted_bytes.add(new_item_size);
             self.evict_items(state.deref_mut()).await;
         }
@@ -447,12 +607,101 @@ where
     async fn inner_remove(&self, mut state: &mut State<T>, digest: &DigestInfo) -> bool {
         self.evict_items(state.deref_mut()).await;
         if let Some(entry) = state.lru.pop(digest) {
-            state.remove(&entry, false).await;
+            state.remove(digest, &entry, false).await;
             return true;
         }
         false
     }
 
+    /// Calls `handler` with the digest and size of every item in the map,
+    /// from the most to the least recently used. Iterates a snapshot, so the
+    /// map is only locked while the snapshot is taken.
+    pub async fn for_each_item(&self, mut handler: impl FnMut(&DigestInfo, usize)) {
+        let items: Vec<(DigestInfo, usize)> = self
+            .state
+            .lock()
+            .await
+            .lru
+            .iter()
+            .map(|(digest, eviction_item)| (*digest, eviction_item.data.len()))
+            .collect();
+        for (digest, size) in &items {
+            handler(digest, *size);
+        }
+    }
+
+    /// Marks the given items as just used, like `get()` does, and also
+    /// resets their age used by `max_seconds`. Items that are not in the
+    /// map are ignored.
+    pub async fn touch(&self, digests: &[DigestInfo]) {
+        let mut state = self.state.lock().await;
+        let now = self.seconds_since_anchor();
+        let to_touch: Vec<(DigestInfo, T)> = digests
+            .iter()
+            .filter_map(|digest| {
+                let entry = state.lru.get_mut(digest)?;
+                entry.seconds_since_anchor = now;
+                Some((*digest, entry.data.clone()))
+            })
+            .collect();
+        drop(state);
+        to_touch
+            .into_iter()
+            .map(|(digest, data)| async move {
+                self.touch_or_remove(&digest, data).await;
+            })
+            .collect::<FuturesUnordered<_>>()
+            .for_each(|_| future::ready(()))
+            .await;
+    }
+
+    /// Protects the given items from being evicted for `ttl`. Pinning an
+    /// item that is already pinned extends its pin. Items that are not in
+    /// the map or that would make the pinned items use more than
+    /// `max_pinned_bytes` are not pinned. Explicit removals still remove
+    /// pinned items. Returns whether each digest is now pinned.
+    pub async fn pin(&self, digests: &[DigestInfo], ttl: Duration) -> Vec<bool> {
+        let max_pinned_bytes = self.max_pinned_bytes.load(Ordering::Relaxed);
+        if max_pinned_bytes == 0 {
+            return vec![false; digests.len()];
+        }
+        let mut state = self.state.lock().await;
+        let now = self.seconds_since_anchor();
+        state.remove_expired_pins(now);
+        let expires_at = now.saturating_add(i32::try_from(ttl.as_secs()).unwrap_or(i32::MAX));
+        digests
+            .iter()
+            .map(|digest| {
+                let Some(item_size) = state.lru.peek(digest).map(|item| item.data.len() as u64)
+                else {
+                    return false;
+                };
+                if let Some(pin_expires_at) = state.pins.get_mut(digest) {
+                    *pin_expires_at = (*pin_expires_at).max(expires_at);
+                    return true;
+                }
+                if state.pinned_bytes + item_size > max_pinned_bytes {
+                    state.rejected_pins.inc();
+                    return false;
+                }
+                state.pins.insert(*digest, expires_at);
+                state.pinned_bytes += item_size;
+                true
+            })
+            .collect()
+    }
+
+    /// Removes the pins of the given items, if any.
+    pub async fn unpin(&self, digests: &[DigestInfo]) {
+        let mut state = self.state.lock().await;
+        for digest in digests {
+            if state.pins.remove(digest).is_some() {
+                let item_size = state.lru.peek(digest).map_or(0, |item| item.data.len());
+                state.pinned_bytes -= item_size as u64;
+            }
+        }
+    }
+
     /// Same as remove(), but allows for a conditional to be applied to the entry before removal
     /// in an atomic fashion.
     pub async fn remove_if<F: FnOnce(&T) -> bool>(&self, digest: &DigestInfo, cond: F) -> bool {
@@ -471,12 +720,12 @@ impl<T: LenEntry + Debug, I: InstantWrapper> MetricsComponent for EvictingMap<T,
     fn gather_metrics(&self, c: &mut CollectorState) {
         c.publish(
             "max_bytes",
-            &self.max_bytes,
+            &self.max_bytes.load(Ordering::Relaxed),
             "Maximum size of the store in bytes",
         );
         c.publish(
             "evict_bytes",
-            &self.evict_bytes,
+            &self.evict_bytes.load(Ordering::Relaxed),
             "Number of bytes to evict when the store is full",
         );
         c.publish(
@@ -486,14 +735,19 @@ impl<T: LenEntry + Debug, I: InstantWrapper> MetricsComponent for EvictingMap<T,
         );
         c.publish(
             "max_seconds",
-            &self.max_seconds,
+            &self.max_seconds.load(Ordering::Relaxed),
             "Maximum number of seconds to keep an item in the store",
         );
         c.publish(
             "max_count",
-            &self.max_count,
+            &self.max_count.load(Ordering::Relaxed),
             "Maximum number of items to keep in the store",
         );
+        c.publish(
+            "max_pinned_bytes",
+            &self.max_pinned_bytes.load(Ordering::Relaxed),
+            "Maximum number of bytes of items that can be pinned",
+        );
         futures::executor::block_on(async move {
             let state = self.state.lock().await;
             c.publish(
@@ -564,6 +818,21 @@ impl<T: LenEntry + Debug, I: InstantWrapper> MetricsComponent for EvictingMap<T,
                 &state.removed_items,
                 "Number of items explicitly removed from the store",
             );
+            c.publish(
+                "pinned_bytes",
+                &state.pinned_bytes,
+                "Number of bytes of items pinned in the store, including expired pins",
+            );
+            c.publish(
+                "pinned_items",
+                &state.pins.len(),
+                "Number of items pinned in the store, including expired pins",
+            );
+            c.publish(
+                "rejected_pins_total",
+                &state.rejected_pins,
+                "Number of items that were not pinned because max_pinned_bytes was reached",
+            );
             c.publish_stats(
                 "item_size_bytes",
                 state.lru.iter().take(1_000_000).map(|(_, v)| v.data.len()),
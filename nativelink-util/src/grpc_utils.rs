@@ -12,20 +12,95 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
 use async_lock::{Semaphore, SemaphoreGuard};
+use futures::future::join_all;
+use nativelink_config::stores::{GrpcHealthCheck, GrpcLoadBalancing, LoadBalancingStrategy};
 use nativelink_error::{Code, Error};
 use parking_lot::Mutex;
-use tonic::transport::{Channel, Endpoint};
+use rand::Rng;
+use tokio::time::{sleep, timeout};
+use tonic::transport::{Channel, Endpoint, Uri};
+use tracing::warn;
+
+const DEFAULT_CONNECTIONS_PER_ENDPOINT: usize = 1;
+const DEFAULT_HEALTH_CHECK_INTERVAL_MS: u64 = 5000;
+const DEFAULT_HEALTH_CHECK_TIMEOUT_MS: u64 = 1000;
+const DEFAULT_UNHEALTHY_THRESHOLD: u32 = 3;
+
+/// A Channel along with a monotonic index to ensure we only re-create the
+/// channel on the first error received on it.
+type ChannelSlot = Mutex<(usize, Channel)>;
+
+/// A single upstream endpoint with its own pool of channels.
+struct EndpointState {
+    endpoint: Endpoint,
+    channels: Vec<ChannelSlot>,
+    next_channel: AtomicUsize,
+    outstanding_requests: AtomicUsize,
+    consecutive_failures: AtomicU32,
+    healthy: AtomicBool,
+}
+
+impl EndpointState {
+    fn new(endpoint: Endpoint, connections: usize) -> Self {
+        Self {
+            channels: (0..connections)
+                .map(|_| Mutex::new((0, endpoint.connect_lazy())))
+                .collect(),
+            endpoint,
+            next_channel: AtomicUsize::new(0),
+            outstanding_requests: AtomicUsize::new(0),
+            consecutive_failures: AtomicU32::new(0),
+            healthy: AtomicBool::new(true),
+        }
+    }
+
+    fn record_success(&self) {
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+        if !self.healthy.swap(true, Ordering::Relaxed) {
+            warn!("GRPC endpoint {} is healthy again", self.endpoint.uri());
+        }
+    }
+
+    fn record_failure(&self, unhealthy_threshold: u32) {
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= unhealthy_threshold && self.healthy.swap(false, Ordering::Relaxed) {
+            warn!(
+                "GRPC endpoint {} is unhealthy after {failures} failures",
+                self.endpoint.uri()
+            );
+        }
+    }
+}
+
+enum Channels {
+    /// A balance channel over all the endpoints, the balancing is left to
+    /// Tonic.
+    Balanced {
+        endpoints: Vec<Endpoint>,
+        channel: ChannelSlot,
+    },
+    /// Channels to each endpoint, the endpoint of each request is picked by
+    /// the `strategy`.
+    PerEndpoint {
+        endpoints: Arc<Vec<EndpointState>>,
+        strategy: LoadBalancingStrategy,
+        next_endpoint: AtomicUsize,
+        /// Set if health checks are enabled.
+        unhealthy_threshold: Option<u32>,
+    },
+}
 
 /// A helper utility that enables management of a suite of connections to an
 /// upstream gRPC endpoint using Tonic.
 pub struct ConnectionManager {
-    /// The endpoints to establish Channels for.
-    endpoints: Vec<Endpoint>,
-    /// A balance channel over the above endpoints which is kept with a
-    /// monotonic index to ensure we only re-create a channel on the first error
-    /// received on it.
-    channel: Mutex<(usize, Channel)>,
+    channels: Channels,
     /// If a maximum number of upstream requests are allowed at a time, this
     /// is a Semaphore to manage that.
     request_semaphore: Option<Semaphore>,
@@ -39,12 +114,58 @@ impl ConnectionManager {
     pub fn new(
         endpoints: impl IntoIterator<Item = Endpoint>,
         max_concurrent_requests: usize,
+    ) -> Self {
+        Self::new_with_load_balancing(endpoints, max_concurrent_requests, None)
+    }
+
+    /// Same as `new()`, but if `load_balancing` is set each endpoint gets
+    /// its own connections and the endpoint of each request is picked by
+    /// the configured strategy, skipping unhealthy endpoints.
+    pub fn new_with_load_balancing(
+        endpoints: impl IntoIterator<Item = Endpoint>,
+        max_concurrent_requests: usize,
+        load_balancing: Option<&GrpcLoadBalancing>,
     ) -> Self {
         let endpoints = Vec::from_iter(endpoints);
-        let channel = Channel::balance_list(endpoints.iter().cloned());
+        let channels = match load_balancing {
+            None => {
+                let channel = Channel::balance_list(endpoints.iter().cloned());
+                Channels::Balanced {
+                    endpoints,
+                    channel: Mutex::new((0, channel)),
+                }
+            }
+            Some(load_balancing) => {
+                let connections = if load_balancing.connections_per_endpoint == 0 {
+                    DEFAULT_CONNECTIONS_PER_ENDPOINT
+                } else {
+                    load_balancing.connections_per_endpoint
+                };
+                let endpoints = Arc::new(
+                    endpoints
+                        .into_iter()
+                        .map(|endpoint| EndpointState::new(endpoint, connections))
+                        .collect::<Vec<_>>(),
+                );
+                let unhealthy_threshold = load_balancing.health_check.as_ref().map(|config| {
+                    let unhealthy_threshold = if config.unhealthy_threshold == 0 {
+                        DEFAULT_UNHEALTHY_THRESHOLD
+                    } else {
+                        config.unhealthy_threshold
+                    };
+                    spawn_health_checks(Arc::downgrade(&endpoints), config, unhealthy_threshold);
+                    unhealthy_threshold
+                });
+                Channels::PerEndpoint {
+                    endpoints,
+                    strategy: load_balancing.strategy,
+                    next_endpoint: AtomicUsize::new(0),
+                    unhealthy_threshold,
+                }
+            }
+        };
         Self {
-            endpoints,
-            channel: Mutex::new((0, channel)),
+            channels,
             request_semaphore: (max_concurrent_requests > 0)
                 .then_some(Semaphore::new(max_concurrent_requests)),
         }
@@ -54,15 +175,68 @@ impl ConnectionManager {
     /// should be used once and any errors should be reported back to the
     /// on_error method to ensure that the Channel is re-connected on error.
     pub async fn get_connection(&self) -> (Connection<'_>, Channel) {
+        let endpoint_index = match &self.channels {
+            Channels::Balanced { .. } => None,
+            Channels::PerEndpoint {
+                endpoints,
+                strategy,
+                next_endpoint,
+                ..
+            } => Some(select_endpoint(endpoints, *strategy, next_endpoint)),
+        };
+        self.connection_to(endpoint_index).await
+    }
+
+    /// Same as `get_connection()`, but requests with the same `key` are
+    /// always sent to the same endpoint while it is healthy. This is used
+    /// for requests that depend on state held by the endpoint, such as
+    /// resumable uploads.
+    pub async fn get_connection_for_key(&self, key: &str) -> (Connection<'_>, Channel) {
+        let endpoint_index = match &self.channels {
+            Channels::Balanced { .. } => None,
+            Channels::PerEndpoint { endpoints, .. } => {
+                let mut hasher = DefaultHasher::new();
+                key.hash(&mut hasher);
+                let hash = hasher.finish() as usize;
+                let healthy = healthy_endpoints(endpoints);
+                let preferred = hash % endpoints.len();
+                if healthy.contains(&preferred) {
+                    Some(preferred)
+                } else {
+                    Some(healthy[hash % healthy.len()])
+                }
+            }
+        };
+        self.connection_to(endpoint_index).await
+    }
+
+    async fn connection_to(&self, endpoint_index: Option<usize>) -> (Connection<'_>, Channel) {
         let _permit = if let Some(semaphore) = &self.request_semaphore {
             Some(semaphore.acquire().await)
         } else {
             None
         };
-        let channel_lock = self.channel.lock();
+        let (slot, channel_index) = match (&self.channels, endpoint_index) {
+            (Channels::PerEndpoint { endpoints, .. }, Some(endpoint_index)) => {
+                let endpoint = &endpoints[endpoint_index];
+                endpoint
+                    .outstanding_requests
+                    .fetch_add(1, Ordering::Relaxed);
+                let channel_index =
+                    endpoint.next_channel.fetch_add(1, Ordering::Relaxed) % endpoint.channels.len();
+                (&endpoint.channels[channel_index], channel_index)
+            }
+            (Channels::Balanced { channel, .. }, _) => (channel, 0),
+            (Channels::PerEndpoint { .. }, None) => {
+                unreachable!("An endpoint is always selected when load balancing")
+            }
+        };
+        let channel_lock = slot.lock();
         (
             Connection {
                 channel_id: channel_lock.0,
+                endpoint_index,
+                channel_index,
                 parent: self,
                 _permit,
             },
@@ -71,18 +245,135 @@ impl ConnectionManager {
     }
 }
 
+/// Returns the indexes of the healthy endpoints, or all of them if none are
+/// healthy.
+fn healthy_endpoints(endpoints: &[EndpointState]) -> Vec<usize> {
+    let healthy: Vec<usize> = endpoints
+        .iter()
+        .enumerate()
+        .filter(|(_, endpoint)| endpoint.healthy.load(Ordering::Relaxed))
+        .map(|(index, _)| index)
+        .collect();
+    if healthy.is_empty() {
+        return (0..endpoints.len()).collect();
+    }
+    healthy
+}
+
+fn select_endpoint(
+    endpoints: &[EndpointState],
+    strategy: LoadBalancingStrategy,
+    next_endpoint: &AtomicUsize,
+) -> usize {
+    let candidates = healthy_endpoints(endpoints);
+    let outstanding = |index: &usize| {
+        endpoints[*index]
+            .outstanding_requests
+            .load(Ordering::Relaxed)
+    };
+    match strategy {
+        LoadBalancingStrategy::round_robin => {
+            candidates[next_endpoint.fetch_add(1, Ordering::Relaxed) % candidates.len()]
+        }
+        LoadBalancingStrategy::least_outstanding_requests => {
+            // Rotate the starting point so ties are spread across endpoints.
+            let start = next_endpoint.fetch_add(1, Ordering::Relaxed) % candidates.len();
+            candidates[start..]
+                .iter()
+                .chain(candidates[..start].iter())
+                .copied()
+                .min_by_key(outstanding)
+                .unwrap_or(candidates[start])
+        }
+        LoadBalancingStrategy::power_of_two_choices => {
+            if candidates.len() == 1 {
+                return candidates[0];
+            }
+            let mut rng = rand::thread_rng();
+            let first = rng.gen_range(0..candidates.len());
+            let second = (first + rng.gen_range(1..candidates.len())) % candidates.len();
+            let (first, second) = (candidates[first], candidates[second]);
+            if outstanding(&second) < outstanding(&first) {
+                second
+            } else {
+                first
+            }
+        }
+    }
+}
+
+/// Periodically opens a connection to each endpoint to find out whether it
+/// is healthy. Stops once the endpoints are dropped.
+fn spawn_health_checks(
+    endpoints: Weak<Vec<EndpointState>>,
+    config: &GrpcHealthCheck,
+    unhealthy_threshold: u32,
+) {
+    let interval = Duration::from_millis(if config.interval_ms == 0 {
+        DEFAULT_HEALTH_CHECK_INTERVAL_MS
+    } else {
+        config.interval_ms
+    });
+    let check_timeout = Duration::from_millis(if config.timeout_ms == 0 {
+        DEFAULT_HEALTH_CHECK_TIMEOUT_MS
+    } else {
+        config.timeout_ms
+    });
+    tokio::spawn(async move {
+        loop {
+            sleep(interval).await;
+            let Some(endpoints) = endpoints.upgrade() else {
+                return;
+            };
+            join_all(endpoints.iter().map(|state| async move {
+                match timeout(check_timeout, state.endpoint.connect()).await {
+                    Ok(Ok(_)) => state.record_success(),
+                    _ => state.record_failure(unhealthy_threshold),
+                }
+            }))
+            .await;
+        }
+    });
+}
+
 /// An instance of this is obtained for every communication with the gGRPC
 /// service.  This handles the permit for limiting concurrency, and also
 /// re-connecting the underlying channel on error.  It depends on users
 /// reporting all errors.
 pub struct Connection<'a> {
     channel_id: usize,
+    endpoint_index: Option<usize>,
+    channel_index: usize,
     parent: &'a ConnectionManager,
     _permit: Option<SemaphoreGuard<'a>>,
 }
 
 impl<'a> Connection<'a> {
+    /// The URI of the endpoint this connection goes to, if a specific
+    /// endpoint was picked.
+    pub fn endpoint_uri(&self) -> Option<&Uri> {
+        match (&self.parent.channels, self.endpoint_index) {
+            (Channels::PerEndpoint { endpoints, .. }, Some(endpoint_index)) => {
+                Some(endpoints[endpoint_index].endpoint.uri())
+            }
+            _ => None,
+        }
+    }
+
     pub fn on_error(self, err: &Error) {
+        if let (
+            Channels::PerEndpoint {
+                endpoints,
+                unhealthy_threshold: Some(unhealthy_threshold),
+                ..
+            },
+            Some(endpoint_index),
+        ) = (&self.parent.channels, self.endpoint_index)
+        {
+            if err.code == Code::Unavailable {
+                endpoints[endpoint_index].record_failure(*unhealthy_threshold);
+            }
+        }
         // Usually Tonic reconnects on upstream errors (like Unavailable) but
         // if there are protocol errors (such as GoAway) then it will not
         // attempt to re-connect, and therefore we are forced to manually do
@@ -90,11 +381,24 @@ impl<'a> Connection<'a> {
         if err.code != Code::Internal {
             return;
         }
+        let (slot, new_channel) = match (&self.parent.channels, self.endpoint_index) {
+            (Channels::PerEndpoint { endpoints, .. }, Some(endpoint_index)) => {
+                let endpoint = &endpoints[endpoint_index];
+                (
+                    &endpoint.channels[self.channel_index],
+                    endpoint.endpoint.connect_lazy(),
+                )
+            }
+            (Channels::Balanced { endpoints, channel }, _) => {
+                (channel, Channel::balance_list(endpoints.iter().cloned()))
+            }
+            (Channels::PerEndpoint { .. }, None) => return,
+        };
         // Create a new channel for future requests to use upon a new request
         // to ConnectionManager::get_connection().  In order to ensure we only
         // do this for the first error on a cloned Channel we check the ID
         // matches the current ID when we get the lock.
-        let mut channel_lock = self.parent.channel.lock();
+        let mut channel_lock = slot.lock();
         if channel_lock.0 != self.channel_id {
             // The connection was already re-established by another user getting
             // and error on a clone of this Channel, so don't make another one.
@@ -106,6 +410,18 @@ impl<'a> Connection<'a> {
         // with an error now and it's up to the user whether they retry by
         // getting a new connection.
         channel_lock.0 += 1;
-        channel_lock.1 = Channel::balance_list(self.parent.endpoints.iter().cloned());
+        channel_lock.1 = new_channel;
+    }
+}
+
+impl<'a> Drop for Connection<'a> {
+    fn drop(&mut self) {
+        if let (Channels::PerEndpoint { endpoints, .. }, Some(endpoint_index)) =
+            (&self.parent.channels, self.endpoint_index)
+        {
+            endpoints[endpoint_index]
+                .outstanding_requests
+                .fetch_sub(1, Ordering::Relaxed);
+        }
     }
 }
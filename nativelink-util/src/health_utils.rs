@@ -17,11 +17,13 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use futures::{Stream, StreamExt};
 use parking_lot::Mutex;
 use serde::Serialize;
+use tokio::sync::watch;
 
 /// Struct name health indicator component.
 type StructName = str;
@@ -151,6 +153,7 @@ impl HealthRegistryBuilder {
     /// Finalize the production of the health registry.
     pub fn build(&mut self) -> HealthRegistry {
         HealthRegistry {
+            namespace: self.namespace.clone(),
             indicators: self.state.lock().clone().into_iter().collect(),
         }
     }
@@ -158,17 +161,43 @@ impl HealthRegistryBuilder {
 
 #[derive(Default, Clone)]
 pub struct HealthRegistry {
+    namespace: Cow<'static, str>,
     indicators: Vec<(Cow<'static, str>, Arc<dyn HealthStatusIndicator>)>,
 }
 
+impl HealthRegistry {
+    /// Create a registry with only the indicators registered under one of
+    /// `namespaces`. The namespaces are relative to the namespace of the
+    /// builder this registry was built from, eg: "stores/foo".
+    pub fn sub_registry<'a>(&self, namespaces: impl IntoIterator<Item = &'a str>) -> Self {
+        let prefixes: Vec<String> = namespaces
+            .into_iter()
+            .map(|namespace| format!("{}/{}/", self.namespace, namespace))
+            .collect();
+        Self {
+            namespace: self.namespace.clone(),
+            indicators: self
+                .indicators
+                .iter()
+                .filter(|(name, _)| prefixes.iter().any(|prefix| name.starts_with(prefix)))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
 pub trait HealthStatusReporter {
-    fn health_status_report(&self) -> Pin<Box<dyn Stream<Item = HealthStatusDescription> + '_>>;
+    fn health_status_report(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = HealthStatusDescription> + Send + '_>>;
 }
 
 /// Health status reporter implementation for the health registry that provides a stream
 /// of health status descriptions.
 impl HealthStatusReporter for HealthRegistry {
-    fn health_status_report(&self) -> Pin<Box<dyn Stream<Item = HealthStatusDescription> + '_>> {
+    fn health_status_report(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = HealthStatusDescription> + Send + '_>> {
         Box::pin(futures::stream::iter(self.indicators.iter()).then(
             |(namespace, indicator)| async move {
                 HealthStatusDescription {
@@ -180,6 +209,108 @@ impl HealthStatusReporter for HealthRegistry {
     }
 }
 
+/// The health of the components in a `CachedHealthRegistry`, `None` until
+/// they were checked for the first time.
+type CachedReport = Option<Arc<Vec<HealthStatusDescription>>>;
+
+/// A health registry whose components are checked on a fixed interval by a
+/// background task. Reports are served from the last check, so any number of
+/// callers polling the health only cost one check of each component per
+/// interval. The task stops once every clone of the registry is dropped.
+#[derive(Clone)]
+pub struct CachedHealthRegistry {
+    namespace: Cow<'static, str>,
+    /// Only descriptions whose namespace starts with one of these are
+    /// reported. Everything is reported if `None`.
+    prefixes: Option<Arc<Vec<String>>>,
+    report_rx: watch::Receiver<CachedReport>,
+}
+
+impl CachedHealthRegistry {
+    /// Checks the health of every component in `health_registry` every
+    /// `interval`. Must be called from within a tokio runtime.
+    pub fn new(health_registry: HealthRegistry, interval: Duration) -> Self {
+        let (report_tx, report_rx) = watch::channel(None);
+        let namespace = health_registry.namespace.clone();
+        tokio::spawn(async move {
+            loop {
+                let report: Vec<_> = health_registry.health_status_report().collect().await;
+                if report_tx.send(Some(Arc::new(report))).is_err() {
+                    return;
+                }
+                tokio::select! {
+                    () = tokio::time::sleep(interval) => {},
+                    () = report_tx.closed() => return,
+                }
+            }
+        });
+        Self {
+            namespace,
+            prefixes: None,
+            report_rx,
+        }
+    }
+
+    /// Same as `HealthRegistry::sub_registry`, the returned registry shares
+    /// the checks of this one.
+    pub fn sub_registry<'a>(&self, namespaces: impl IntoIterator<Item = &'a str>) -> Self {
+        let prefixes = namespaces
+            .into_iter()
+            .map(|namespace| format!("{}/{}/", self.namespace, namespace))
+            .filter(|prefix| {
+                self.prefixes.as_ref().map_or(true, |parent_prefixes| {
+                    parent_prefixes
+                        .iter()
+                        .any(|parent_prefix| prefix.starts_with(parent_prefix))
+                })
+            })
+            .collect();
+        Self {
+            namespace: self.namespace.clone(),
+            prefixes: Some(Arc::new(prefixes)),
+            report_rx: self.report_rx.clone(),
+        }
+    }
+
+    /// Returns the result of the last check, waiting for the first check if
+    /// it did not finish yet.
+    pub async fn report(&self) -> Vec<HealthStatusDescription> {
+        let mut report_rx = self.report_rx.clone();
+        let report = match report_rx.wait_for(Option::is_some).await {
+            Ok(report) => report.clone().unwrap_or_default(),
+            // The task only stops once all registries are dropped.
+            Err(_) => Arc::default(),
+        };
+        report
+            .iter()
+            .filter(|description| {
+                self.prefixes.as_ref().map_or(true, |prefixes| {
+                    prefixes
+                        .iter()
+                        .any(|prefix| description.namespace.starts_with(prefix))
+                })
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Waits for a check this registry did not wait for yet.
+    pub async fn changed(&mut self) {
+        if self.report_rx.changed().await.is_err() {
+            futures::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Serves the result of the last check.
+impl HealthStatusReporter for CachedHealthRegistry {
+    fn health_status_report(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = HealthStatusDescription> + Send + '_>> {
+        Box::pin(futures::stream::once(self.report()).flat_map(futures::stream::iter))
+    }
+}
+
 /// Default health status indicator implementation for a component.
 /// Generally used for components that don't need custom implementations
 /// of the `check_health` function.
@@ -24,8 +24,10 @@ pub mod health_utils;
 pub mod metrics_utils;
 pub mod platform_properties;
 pub mod proto_stream_utils;
+pub mod request_context;
 pub mod resource_info;
 pub mod retry;
+pub mod shutdown;
 pub mod store_trait;
 pub mod tls_utils;
 pub mod write_counter;
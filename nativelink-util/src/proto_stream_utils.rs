@@ -31,6 +31,7 @@ where
     T: Stream<Item = Result<WriteRequest, E>> + Unpin,
 {
     pub instance_name: String,
+    pub digest_function: Option<String>,
     pub uuid: Option<String>,
     pub hash: String,
     pub expected_size: usize,
@@ -59,12 +60,14 @@ where
             )
         })?;
         let instance_name = resource_info.instance_name.to_string();
+        let digest_function = resource_info.digest_function.map(|v| v.to_string());
         let hash = resource_info.hash.to_string();
         let expected_size = resource_info.expected_size;
         let uuid = resource_info.uuid.map(|v| v.to_string());
 
         Ok(WriteRequestStreamWrapper {
             instance_name,
+            digest_function,
             uuid,
             hash,
             expected_size,
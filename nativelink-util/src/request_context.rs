@@ -0,0 +1,96 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use futures::Future;
+
+use crate::digest_hasher::{default_digest_hasher_func, DigestHasherFunc};
+
+/// Which kind of store a request accesses.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StoreAccess {
+    /// Action cache.
+    Ac,
+    /// Content addressable storage.
+    Cas,
+}
+
+/// Information about the client request a store is being used for. The
+/// `Store` trait only passes digests around, so services set this for the
+/// duration of a request and stores that care about it (ie: routing stores)
+/// read it back with `RequestContext::with_current()`.
+///
+/// Note: This is stored in a task local, so it is not visible to tasks
+/// spawned while serving the request.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RequestContext {
+    pub instance_name: String,
+    pub digest_function: DigestHasherFunc,
+    pub access: StoreAccess,
+}
+
+tokio::task_local! {
+    static REQUEST_CONTEXT: RequestContext;
+}
+
+impl RequestContext {
+    /// Creates a context for a request. `digest_function` is the raw value
+    /// from the request, unset or unknown values use the default function.
+    pub fn new(
+        instance_name: impl Into<String>,
+        digest_function: i32,
+        access: StoreAccess,
+    ) -> Self {
+        Self {
+            instance_name: instance_name.into(),
+            digest_function: DigestHasherFunc::try_from(digest_function)
+                .unwrap_or_else(|_| default_digest_hasher_func()),
+            access,
+        }
+    }
+
+    /// Runs `f` with this context set as the current context.
+    pub fn sync_scope<R>(self, f: impl FnOnce() -> R) -> R {
+        REQUEST_CONTEXT.sync_scope(self, f)
+    }
+
+    /// Runs `fut` with this context set as the current context.
+    pub async fn scope<F: Future>(self, fut: F) -> F::Output {
+        REQUEST_CONTEXT.scope(self, fut).await
+    }
+
+    /// Runs `fut` with the access of the current context replaced by
+    /// `access`. Used by stores that look up digests of another kind than
+    /// the request itself (ie: CAS outputs of an AC entry). Without a current
+    /// context `fut` is run as is.
+    pub async fn scope_access<F: Future>(access: StoreAccess, fut: F) -> F::Output {
+        match Self::current() {
+            Some(context) => Self { access, ..context }.scope(fut).await,
+            None => fut.await,
+        }
+    }
+
+    /// Calls `f` with the context of the request being served, or `None` if
+    /// the store is used outside of a request (ie: by a worker).
+    pub fn with_current<R>(f: impl FnOnce(Option<&RequestContext>) -> R) -> R {
+        let mut f = Some(f);
+        REQUEST_CONTEXT
+            .try_with(|context| (f.take().unwrap())(Some(context)))
+            .unwrap_or_else(|_| (f.take().unwrap())(None))
+    }
+
+    /// Returns a copy of the context of the request being served.
+    pub fn current() -> Option<RequestContext> {
+        Self::with_current(|context| context.cloned())
+    }
+}
@@ -0,0 +1,34 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use futures::future::{join_all, BoxFuture};
+use parking_lot::{const_mutex, Mutex};
+
+type ShutdownHook = Box<dyn Fn() -> BoxFuture<'static, ()> + Send + Sync>;
+
+static SHUTDOWN_HOOKS: Mutex<Vec<ShutdownHook>> = const_mutex(Vec::new());
+
+/// Registers a hook that is run when the process is asked to shut down
+/// gracefully (ie: SIGTERM). Hooks should hold weak references to the
+/// components they save, so they don't keep them alive.
+pub fn register_shutdown_hook(hook: impl Fn() -> BoxFuture<'static, ()> + Send + Sync + 'static) {
+    SHUTDOWN_HOOKS.lock().push(Box::new(hook));
+}
+
+/// Runs all the registered shutdown hooks concurrently and waits for them
+/// to finish.
+pub async fn run_shutdown_hooks() {
+    let futures: Vec<_> = SHUTDOWN_HOOKS.lock().iter().map(|hook| hook()).collect();
+    join_all(futures).await;
+}
@@ -18,10 +18,12 @@ use std::hash::{Hash, Hasher};
 use std::ops::Deref;
 use std::pin::Pin;
 use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 
 use async_trait::async_trait;
 use bytes::{Bytes, BytesMut};
 use futures::{future, join, try_join, FutureExt};
+use nativelink_config::stores::EvictionPolicy;
 use nativelink_error::{error_if, make_err, Code, Error, ResultExt};
 use rand::rngs::StdRng;
 use rand::{RngCore, SeedableRng};
@@ -111,6 +113,11 @@ fn addr_eq<T: ?Sized, U: ?Sized>(p: *const T, q: *const U) -> bool {
     std::ptr::eq(p as *const (), q as *const ())
 }
 
+/// Callback invoked by a store when its eviction policy removes an item. The
+/// data of the evicted item is handed over so it can be kept somewhere else.
+/// This is called while the store is locked, so it must never block.
+pub type StoreEvictionCallback = Box<dyn Fn(DigestInfo, Bytes) + Send + Sync>;
+
 /// Optimizations that stores may want to expose to the callers.
 /// This is useful for specific cases when the store can optimize the processing
 /// of the data being processed.
@@ -167,6 +174,54 @@ pub trait Store: Sync + Send + Unpin + HealthStatusIndicator + 'static {
         upload_size: UploadSizeInfo,
     ) -> Result<(), Error>;
 
+    /// Removes `digest` from the store, returning `true` if it existed.
+    /// Stores that can not remove individual items will return an
+    /// `Unimplemented` error.
+    async fn remove(self: Pin<&Self>, _digest: DigestInfo) -> Result<bool, Error> {
+        Err(make_err!(
+            Code::Unimplemented,
+            "{} does not support removing items",
+            self.get_name()
+        ))
+    }
+
+    /// Calls `handler` with the digest and size of every item in the store,
+    /// in no particular order. Stores made of several stores may report an
+    /// item more than once. Stores that can not enumerate their items will
+    /// return an `Unimplemented` error.
+    async fn list(
+        self: Pin<&Self>,
+        _handler: &mut (dyn FnMut(DigestInfo, usize) + Send),
+    ) -> Result<(), Error> {
+        Err(make_err!(
+            Code::Unimplemented,
+            "{} does not support listing items",
+            self.get_name()
+        ))
+    }
+
+    /// Refreshes the recency of the given digests, so the eviction policy
+    /// (or the lifecycle rules of the service behind the store) treats them
+    /// as if they were just written. Digests that don't exist are ignored,
+    /// as are all digests by stores that don't track recency.
+    async fn touch(self: Pin<&Self>, _digests: &[DigestInfo]) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Protects the given digests from being evicted for `ttl`, so clients
+    /// that were told the digests exist can rely on it for a while. Stores
+    /// that don't evict items or can't pin them ignore the request, so this
+    /// is best effort. Returns the number of digests that were pinned.
+    /// Stores writing the same digests to several stores report the
+    /// highest number pinned by any of them.
+    async fn pin(
+        self: Pin<&Self>,
+        _digests: &[DigestInfo],
+        _ttl: Duration,
+    ) -> Result<usize, Error> {
+        Ok(0)
+    }
+
     /// Any optimizations the store might want to expose to the callers.
     /// By default, no optimizations are exposed.
     fn optimized_for(&self, _optimization: StoreOptimizations) -> bool {
@@ -390,6 +445,30 @@ pub trait Store: Sync + Send + Unpin + HealthStatusIndicator + 'static {
     fn as_any(&self) -> &(dyn std::any::Any + Sync + Send + 'static);
     fn as_any_arc(self: Arc<Self>) -> Arc<dyn std::any::Any + Sync + Send + 'static>;
 
+    /// Registers a callback that is called every time this store evicts an item.
+    /// Stores that can not hand out the data of evicted items will return an
+    /// `Unimplemented` error.
+    fn register_eviction_callback(
+        self: Arc<Self>,
+        _callback: StoreEvictionCallback,
+    ) -> Result<(), Error> {
+        Err(make_err!(
+            Code::Unimplemented,
+            "{} does not support eviction callbacks",
+            self.get_name()
+        ))
+    }
+
+    /// Replaces the eviction policy of the store while it is in use. Stores
+    /// without an eviction policy will return an `Unimplemented` error.
+    async fn set_eviction_policy(&self, _eviction_policy: &EvictionPolicy) -> Result<(), Error> {
+        Err(make_err!(
+            Code::Unimplemented,
+            "{} does not support changing the eviction policy",
+            self.get_name()
+        ))
+    }
+
     /// Register any metrics that this store wants to expose to the Prometheus.
     fn register_metrics(self: Arc<Self>, _registry: &mut Registry) {}
 
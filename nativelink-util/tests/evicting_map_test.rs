@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use async_trait::async_trait;
@@ -80,6 +80,7 @@ mod evicting_map_tests {
                 max_seconds: 0,
                 max_bytes: 0,
                 evict_bytes: 0,
+                max_pinned_bytes: 0,
             },
             MockInstantWrapped(MockInstant::now()),
         );
@@ -136,6 +137,7 @@ mod evicting_map_tests {
                 max_seconds: 0,
                 max_bytes: 17,
                 evict_bytes: 0,
+                max_pinned_bytes: 0,
             },
             MockInstantWrapped(MockInstant::now()),
         );
@@ -193,6 +195,7 @@ mod evicting_map_tests {
                 max_seconds: 0,
                 max_bytes: 17,
                 evict_bytes: 9,
+                max_pinned_bytes: 0,
             },
             MockInstantWrapped(MockInstant::now()),
         );
@@ -250,6 +253,7 @@ mod evicting_map_tests {
                 max_seconds: 5,
                 max_bytes: 0,
                 evict_bytes: 0,
+                max_pinned_bytes: 0,
             },
             MockInstantWrapped(MockInstant::now()),
         );
@@ -311,6 +315,7 @@ mod evicting_map_tests {
                 max_seconds: 3,
                 max_bytes: 0,
                 evict_bytes: 0,
+                max_pinned_bytes: 0,
             },
             MockInstantWrapped(MockInstant::now()),
         );
@@ -355,6 +360,57 @@ mod evicting_map_tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn touch_refreshes_time() -> Result<(), Error> {
+        let evicting_map = EvictingMap::<BytesWrapper, MockInstantWrapped>::new(
+            &EvictionPolicy {
+                max_count: 0,
+                max_seconds: 3,
+                max_bytes: 0,
+                evict_bytes: 0,
+                max_pinned_bytes: 0,
+            },
+            MockInstantWrapped(MockInstant::now()),
+        );
+
+        const DATA: &str = "12345678";
+        let digest_info1: DigestInfo = DigestInfo::try_new(HASH1, 0)?;
+        let digest_info2: DigestInfo = DigestInfo::try_new(HASH2, 0)?;
+        let digest_info3: DigestInfo = DigestInfo::try_new(HASH3, 0)?;
+        evicting_map
+            .insert(digest_info1, Bytes::from(DATA).into())
+            .await;
+        MockClock::advance(Duration::from_secs(2));
+        evicting_map
+            .insert(digest_info2, Bytes::from(DATA).into())
+            .await;
+        MockClock::advance(Duration::from_secs(2));
+        // Unlike get(), touch() also resets the age of the item.
+        evicting_map.touch(&[digest_info1]).await;
+        MockClock::advance(Duration::from_secs(2));
+        evicting_map
+            .insert(digest_info3, Bytes::from(DATA).into())
+            .await; // This will trigger an eviction.
+
+        assert_eq!(
+            evicting_map.size_for_key(&digest_info1).await,
+            Some(DATA.len()),
+            "Expected map to have item 1"
+        );
+        assert_eq!(
+            evicting_map.size_for_key(&digest_info2).await,
+            None,
+            "Expected map to not have item 2"
+        );
+        assert_eq!(
+            evicting_map.size_for_key(&digest_info3).await,
+            Some(DATA.len()),
+            "Expected map to have item 3"
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn unref_called_on_replace() -> Result<(), Error> {
         #[derive(Debug)]
@@ -390,6 +446,7 @@ mod evicting_map_tests {
                 max_seconds: 0,
                 max_bytes: 0,
                 evict_bytes: 0,
+                max_pinned_bytes: 0,
             },
             MockInstantWrapped(MockInstant::now()),
         );
@@ -436,6 +493,7 @@ mod evicting_map_tests {
                 max_seconds: 3,
                 max_bytes: 0,
                 evict_bytes: 0,
+                max_pinned_bytes: 0,
             },
             MockInstantWrapped(MockInstant::now()),
         );
@@ -490,6 +548,7 @@ mod evicting_map_tests {
                 max_seconds: 0,
                 max_bytes: 0,
                 evict_bytes: 0,
+                max_pinned_bytes: 0,
             },
             MockInstantWrapped(MockInstant::now()),
         );
@@ -543,6 +602,7 @@ mod evicting_map_tests {
                 max_seconds: 0,
                 max_bytes: 0,
                 evict_bytes: 0,
+                max_pinned_bytes: 0,
             },
             MockInstantWrapped(MockInstant::now()),
         );
@@ -628,6 +688,7 @@ mod evicting_map_tests {
                 max_seconds: 5,
                 max_bytes: 0,
                 evict_bytes: 0,
+                max_pinned_bytes: 0,
             },
             MockInstantWrapped(MockInstant::now()),
         );
@@ -660,6 +721,7 @@ mod evicting_map_tests {
                 max_seconds: 5,
                 max_bytes: 0,
                 evict_bytes: 0,
+                max_pinned_bytes: 0,
             },
             MockInstantWrapped(MockInstant::now()),
         );
@@ -685,4 +747,173 @@ mod evicting_map_tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn eviction_callback_receives_evicted_items() -> Result<(), Error> {
+        let evicting_map = EvictingMap::<BytesWrapper, MockInstantWrapped>::new(
+            &EvictionPolicy {
+                max_count: 1,
+                max_seconds: 0,
+                max_bytes: 0,
+                evict_bytes: 0,
+                max_pinned_bytes: 0,
+            },
+            MockInstantWrapped(MockInstant::now()),
+        );
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let evicted_clone = evicted.clone();
+        assert!(
+            evicting_map.set_eviction_callback(Box::new(move |digest, data| {
+                evicted_clone.lock().unwrap().push((*digest, data.clone()));
+            }))
+        );
+        // Only one callback may be set.
+        assert!(!evicting_map.set_eviction_callback(Box::new(|_, _| {})));
+
+        let digest_info1: DigestInfo = DigestInfo::try_new(HASH1, 0)?;
+        let digest_info2: DigestInfo = DigestInfo::try_new(HASH2, 0)?;
+        evicting_map
+            .insert(digest_info1, Bytes::from_static(b"12345678").into())
+            .await;
+        assert!(evicted.lock().unwrap().is_empty());
+        evicting_map
+            .insert(digest_info2, Bytes::from_static(b"87654321").into())
+            .await;
+
+        assert_eq!(
+            *evicted.lock().unwrap(),
+            vec![(digest_info1, Bytes::from_static(b"12345678").into())]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pinned_items_are_not_evicted_until_pin_expires() -> Result<(), Error> {
+        let evicting_map = EvictingMap::<BytesWrapper, MockInstantWrapped>::new(
+            &EvictionPolicy {
+                max_count: 1,
+                max_seconds: 0,
+                max_bytes: 0,
+                evict_bytes: 0,
+                max_pinned_bytes: 100,
+            },
+            MockInstantWrapped(MockInstant::now()),
+        );
+        let digest_info1: DigestInfo = DigestInfo::try_new(HASH1, 0)?;
+        let digest_info2: DigestInfo = DigestInfo::try_new(HASH2, 0)?;
+        let digest_info3: DigestInfo = DigestInfo::try_new(HASH3, 0)?;
+        evicting_map
+            .insert(digest_info1, Bytes::from_static(b"12345678").into())
+            .await;
+        assert_eq!(
+            evicting_map
+                .pin(&[digest_info1, digest_info2], Duration::from_secs(10))
+                .await,
+            vec![true, false],
+            "Expected only items in the map to be pinned"
+        );
+
+        evicting_map
+            .insert(digest_info2, Bytes::from_static(b"87654321").into())
+            .await;
+        assert_eq!(evicting_map.size_for_key(&digest_info1).await, Some(8));
+        assert_eq!(evicting_map.size_for_key(&digest_info2).await, None);
+
+        MockClock::advance(Duration::from_secs(11));
+
+        evicting_map
+            .insert(digest_info3, Bytes::from_static(b"12345678").into())
+            .await;
+        assert_eq!(evicting_map.size_for_key(&digest_info1).await, None);
+        assert_eq!(evicting_map.size_for_key(&digest_info3).await, Some(8));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pins_are_limited_to_max_pinned_bytes() -> Result<(), Error> {
+        let evicting_map = EvictingMap::<BytesWrapper, MockInstantWrapped>::new(
+            &EvictionPolicy {
+                max_count: 0,
+                max_seconds: 0,
+                max_bytes: 0,
+                evict_bytes: 0,
+                max_pinned_bytes: 10,
+            },
+            MockInstantWrapped(MockInstant::now()),
+        );
+        let digest_info1: DigestInfo = DigestInfo::try_new(HASH1, 0)?;
+        let digest_info2: DigestInfo = DigestInfo::try_new(HASH2, 0)?;
+        evicting_map
+            .insert(digest_info1, Bytes::from_static(b"12345678").into())
+            .await;
+        evicting_map
+            .insert(digest_info2, Bytes::from_static(b"87654321").into())
+            .await;
+
+        let ttl = Duration::from_secs(10);
+        assert_eq!(
+            evicting_map.pin(&[digest_info1, digest_info2], ttl).await,
+            vec![true, false]
+        );
+        // Removing the pin frees up the budget.
+        evicting_map.unpin(&[digest_info1]).await;
+        assert_eq!(evicting_map.pin(&[digest_info2], ttl).await, vec![true]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn set_eviction_policy_evicts_to_new_limits() -> Result<(), Error> {
+        let evicting_map = EvictingMap::<BytesWrapper, MockInstantWrapped>::new(
+            &EvictionPolicy::default(),
+            MockInstantWrapped(MockInstant::now()),
+        );
+        const DATA: &str = "12345678";
+        for hash in [HASH1, HASH2, HASH3] {
+            evicting_map
+                .insert(DigestInfo::try_new(hash, 0)?, Bytes::from(DATA).into())
+                .await;
+        }
+
+        evicting_map
+            .set_eviction_policy(&EvictionPolicy {
+                max_bytes: 17,
+                ..Default::default()
+            })
+            .await;
+        assert_eq!(
+            evicting_map
+                .size_for_key(&DigestInfo::try_new(HASH1, 0)?)
+                .await,
+            None,
+            "Expected map to not have item 1"
+        );
+        assert_eq!(
+            evicting_map
+                .size_for_key(&DigestInfo::try_new(HASH2, 0)?)
+                .await,
+            Some(DATA.len()),
+            "Expected map to have item 2"
+        );
+
+        // The new limits also apply to items inserted later. Item 2 was
+        // refreshed by `size_for_key()`, so item 3 is the oldest.
+        evicting_map
+            .insert(DigestInfo::try_new(HASH4, 0)?, Bytes::from(DATA).into())
+            .await;
+        assert_eq!(
+            evicting_map
+                .size_for_key(&DigestInfo::try_new(HASH3, 0)?)
+                .await,
+            None,
+            "Expected map to not have item 3"
+        );
+        assert_eq!(
+            evicting_map
+                .size_for_key(&DigestInfo::try_new(HASH4, 0)?)
+                .await,
+            Some(DATA.len()),
+            "Expected map to have item 4"
+        );
+        Ok(())
+    }
 }
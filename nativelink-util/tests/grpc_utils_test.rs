@@ -0,0 +1,168 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use nativelink_config::stores::{GrpcHealthCheck, GrpcLoadBalancing, LoadBalancingStrategy};
+use nativelink_error::Error;
+use nativelink_util::grpc_utils::ConnectionManager;
+use tokio::net::TcpListener;
+use tonic::transport::Endpoint;
+
+fn make_endpoints(addresses: &[&str]) -> Vec<Endpoint> {
+    addresses
+        .iter()
+        .map(|address| Endpoint::from_shared(address.to_string()).unwrap())
+        .collect()
+}
+
+fn make_manager(
+    strategy: LoadBalancingStrategy,
+    health_check: Option<GrpcHealthCheck>,
+    addresses: &[&str],
+) -> ConnectionManager {
+    ConnectionManager::new_with_load_balancing(
+        make_endpoints(addresses),
+        0,
+        Some(&GrpcLoadBalancing {
+            strategy,
+            connections_per_endpoint: 2,
+            health_check,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod grpc_utils_tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*; // Must be declared in every module.
+
+    const ADDRESSES: [&str; 3] = [
+        "http://127.0.0.1:50001/",
+        "http://127.0.0.1:50002/",
+        "http://127.0.0.1:50003/",
+    ];
+
+    async fn next_uri(connection_manager: &ConnectionManager) -> String {
+        let (connection, _channel) = connection_manager.get_connection().await;
+        connection.endpoint_uri().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn round_robin_cycles_through_endpoints() -> Result<(), Error> {
+        let connection_manager = make_manager(LoadBalancingStrategy::round_robin, None, &ADDRESSES);
+        let mut uris = Vec::new();
+        for _ in 0..6 {
+            uris.push(next_uri(&connection_manager).await);
+        }
+        assert_eq!(
+            uris,
+            [ADDRESSES, ADDRESSES].concat(),
+            "Expected each endpoint to be used in turn"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn least_outstanding_requests_avoids_busy_endpoints() -> Result<(), Error> {
+        let connection_manager = make_manager(
+            LoadBalancingStrategy::least_outstanding_requests,
+            None,
+            &ADDRESSES[..2],
+        );
+        for _ in 0..4 {
+            let (busy_connection, _channel) = connection_manager.get_connection().await;
+            let busy_uri = busy_connection.endpoint_uri().unwrap().to_string();
+            let (connection, _channel) = connection_manager.get_connection().await;
+            assert_ne!(connection.endpoint_uri().unwrap().to_string(), busy_uri);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn power_of_two_choices_avoids_busy_endpoint() -> Result<(), Error> {
+        let connection_manager = make_manager(
+            LoadBalancingStrategy::power_of_two_choices,
+            None,
+            &ADDRESSES[..2],
+        );
+        let (busy_connection, _channel) = connection_manager.get_connection().await;
+        let busy_uri = busy_connection.endpoint_uri().unwrap().to_string();
+        for _ in 0..10 {
+            assert_ne!(next_uri(&connection_manager).await, busy_uri);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn same_key_uses_same_endpoint() -> Result<(), Error> {
+        let connection_manager = make_manager(LoadBalancingStrategy::round_robin, None, &ADDRESSES);
+        let (connection, _channel) = connection_manager.get_connection_for_key("upload").await;
+        let expected_uri = connection.endpoint_uri().unwrap().to_string();
+        drop(connection);
+        for _ in 0..10 {
+            // Interleave other requests to move the round robin along.
+            next_uri(&connection_manager).await;
+            let (connection, _channel) = connection_manager.get_connection_for_key("upload").await;
+            assert_eq!(connection.endpoint_uri().unwrap().to_string(), expected_uri);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unhealthy_endpoints_are_skipped() -> Result<(), Error> {
+        let healthy_listener = TcpListener::bind("127.0.0.1:0").await?;
+        let healthy_address = format!("http://{}/", healthy_listener.local_addr()?);
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = healthy_listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+        // Bind and drop a listener to find a port nothing listens on.
+        let unhealthy_address = {
+            let listener = TcpListener::bind("127.0.0.1:0").await?;
+            format!("http://{}/", listener.local_addr()?)
+        };
+        let connection_manager = make_manager(
+            LoadBalancingStrategy::round_robin,
+            Some(GrpcHealthCheck {
+                interval_ms: 10,
+                timeout_ms: 500,
+                unhealthy_threshold: 1,
+            }),
+            &[&unhealthy_address, &healthy_address],
+        );
+
+        // Wait for the health check to notice the endpoint is down.
+        let mut consecutive_healthy = 0;
+        for _ in 0..500 {
+            if next_uri(&connection_manager).await == healthy_address {
+                consecutive_healthy += 1;
+                if consecutive_healthy == 10 {
+                    break;
+                }
+            } else {
+                consecutive_healthy = 0;
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+        assert_eq!(
+            consecutive_healthy, 10,
+            "Expected requests to stop going to the unhealthy endpoint"
+        );
+        Ok(())
+    }
+}
@@ -202,6 +202,51 @@ mod health_utils_tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn sub_registry_only_has_selected_namespaces() -> Result<(), Error> {
+        generate_health_status_indicator!(MockComponentImpl1, Ok, "ok");
+        generate_health_status_indicator!(MockComponentImpl2, Failed, "failed");
+        generate_health_status_indicator!(MockComponentImpl3, Ok, "ok");
+
+        let mut health_registry_builder = HealthRegistryBuilder::new("nativelink".into());
+        health_registry_builder
+            .sub_builder("stores/foo".into())
+            .register_indicator(Arc::new(MockComponentImpl1 {}));
+        health_registry_builder
+            .sub_builder("stores/foobar".into())
+            .register_indicator(Arc::new(MockComponentImpl2 {}));
+        health_registry_builder
+            .sub_builder("schedulers/foo".into())
+            .register_indicator(Arc::new(MockComponentImpl3 {}));
+
+        let health_registry = health_registry_builder.build();
+        let health_status: Vec<HealthStatusDescription> = health_registry
+            .sub_registry(["stores/foo", "schedulers/foo"])
+            .health_status_report()
+            .collect()
+            .await;
+
+        let expected_health_status = vec_to_set(vec![
+            HealthStatusDescription {
+                namespace: "/nativelink/stores/foo/MockComponentImpl1".into(),
+                status: HealthStatus::Ok {
+                    struct_name: "MockComponentImpl1",
+                    message: "ok".into(),
+                },
+            },
+            HealthStatusDescription {
+                namespace: "/nativelink/schedulers/foo/MockComponentImpl3".into(),
+                status: HealthStatus::Ok {
+                    struct_name: "MockComponentImpl3",
+                    message: "ok".into(),
+                },
+            },
+        ]);
+        assert_eq!(vec_to_set(health_status), expected_health_status);
+
+        Ok(())
+    }
+
     #[macro_export]
     macro_rules! generate_health_status_indicator {
         ($struct_name:ident, $health_status:ident, $status_msg:expr) => {
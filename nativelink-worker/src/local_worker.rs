@@ -19,6 +19,7 @@ use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Weak};
 use std::time::Duration;
 
+use bytes::BytesMut;
 use futures::future::BoxFuture;
 use futures::stream::FuturesUnordered;
 use futures::{select, Future, FutureExt, StreamExt, TryFutureExt};
@@ -27,10 +28,12 @@ use nativelink_error::{make_err, make_input_err, Code, Error, ResultExt};
 use nativelink_proto::com::github::trace_machina::nativelink::remote_execution::update_for_worker::Update;
 use nativelink_proto::com::github::trace_machina::nativelink::remote_execution::worker_api_client::WorkerApiClient;
 use nativelink_proto::com::github::trace_machina::nativelink::remote_execution::{
-    execute_result, ExecuteResult, KeepAliveRequest, UpdateForWorker,
+    execute_result, ActionOutputRequest, ExecuteResult, KeepAliveRequest, UpdateForWorker,
 };
 use nativelink_store::fast_slow_store::FastSlowStore;
-use nativelink_util::action_messages::{ActionResult, ActionStage};
+use nativelink_util::action_messages::{
+    ActionInfoHashKey, ActionOutputStream, ActionResult, ActionStage,
+};
 use nativelink_util::common::fs;
 use nativelink_util::digest_hasher::DigestHasherFunc;
 use nativelink_util::metrics_utils::{
@@ -39,15 +42,15 @@ use nativelink_util::metrics_utils::{
 use nativelink_util::store_trait::Store;
 use nativelink_util::tls_utils;
 use tokio::process;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tokio::time::sleep;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use tonic::Streaming;
 use tracing::{error, warn};
 
 use crate::running_actions_manager::{
-    ExecutionConfiguration, Metrics as RunningActionManagerMetrics, RunningAction,
-    RunningActionsManager, RunningActionsManagerArgs, RunningActionsManagerImpl,
+    ActionOutputChunk, ExecutionConfiguration, Metrics as RunningActionManagerMetrics,
+    RunningAction, RunningActionsManager, RunningActionsManagerArgs, RunningActionsManagerImpl,
 };
 use crate::worker_api_client_wrapper::{WorkerApiClientTrait, WorkerApiClientWrapper};
 use crate::worker_utils::make_supported_properties;
@@ -68,6 +71,10 @@ const DEFAULT_ENDPOINT_TIMEOUT_S: f32 = 5.;
 /// If this value gets modified the documentation in `cas_server.rs` must also be updated.
 const DEFAULT_MAX_ACTION_TIMEOUT: Duration = Duration::from_secs(1200); // 20 mins.
 
+/// Default amount of time a killed action is given to exit before SIGKILL.
+/// If this value gets modified the documentation in `cas_server.rs` must also be updated.
+const DEFAULT_KILL_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
 struct LocalWorkerImpl<'a, T: WorkerApiClientTrait, U: RunningActionsManager> {
     config: &'a LocalWorkerConfig,
     // According to the tonic documentation it is a cheap operation to clone this.
@@ -119,6 +126,49 @@ async fn preconditions_met(precondition_script: Option<String>) -> Result<(), Er
     }
 }
 
+/// Sends the output of a running action to the scheduler as it is written.
+/// Chunks that arrive while a request is in flight are sent together. Once
+/// the scheduler stops accepting output, or sending fails, the rest of the
+/// output is not forwarded, the action keeps running.
+async fn forward_action_output<T: WorkerApiClientTrait>(
+    mut grpc_client: T,
+    mut output_rx: mpsc::Receiver<ActionOutputChunk>,
+    request: ActionOutputRequest,
+) {
+    while let Some(chunk) = output_rx.recv().await {
+        let mut stdout = BytesMut::new();
+        let mut stderr = BytesMut::new();
+        let mut maybe_chunk = Some(chunk);
+        while let Some(chunk) = maybe_chunk {
+            match chunk.stream {
+                ActionOutputStream::Stdout => stdout.extend_from_slice(&chunk.data),
+                ActionOutputStream::Stderr => stderr.extend_from_slice(&chunk.data),
+            }
+            maybe_chunk = output_rx.try_recv().ok();
+        }
+        let result = grpc_client
+            .action_output(ActionOutputRequest {
+                stdout: stdout.freeze(),
+                stderr: stderr.freeze(),
+                ..request.clone()
+            })
+            .await;
+        match result {
+            Ok(_) => {}
+            // The scheduler keeps no more output for this action.
+            Err(status) if status.code() == tonic::Code::ResourceExhausted => return,
+            Err(err) => {
+                warn!(
+                    "Could not send action output, dropping the rest : {:?}",
+                    err
+                );
+                // Dropping the receiver stops the action from sending more.
+                return;
+            }
+        }
+    }
+}
+
 impl<'a, T: WorkerApiClientTrait, U: RunningActionsManager> LocalWorkerImpl<'a, T, U> {
     fn new(
         config: &'a LocalWorkerConfig,
@@ -215,6 +265,24 @@ impl<'a, T: WorkerApiClientTrait, U: RunningActionsManager> LocalWorkerImpl<'a,
                         Update::KeepAlive(()) => {
                             self.metrics.keep_alives_received.inc();
                         }
+                        Update::KillAction(kill_action) => {
+                            self.metrics.kill_actions_received.inc();
+                            let action_id = match ActionInfoHashKey::try_from(kill_action.action_name.as_str()) {
+                                Ok(unique_qualifier) => unique_qualifier.get_hash(),
+                                Err(err) => {
+                                    error!("Could not decode action name in KillAction : {:?}", err);
+                                    continue;
+                                }
+                            };
+                            let running_actions_manager = self.running_actions_manager.clone();
+                            futures.push(async move {
+                                // The action may have finished before the kill arrived.
+                                if let Err(err) = running_actions_manager.kill_action(&action_id).await {
+                                    warn!("Could not kill action {} : {:?}", kill_action.action_name, err);
+                                }
+                                Ok(())
+                            }.boxed());
+                        }
                         Update::StartAction(start_execute) => {
                             self.metrics.start_actions_received.inc();
                             let add_future_channel = add_future_channel.clone();
@@ -231,6 +299,23 @@ impl<'a, T: WorkerApiClientTrait, U: RunningActionsManager> LocalWorkerImpl<'a,
                             let worker_id_clone = worker_id.clone();
                             let precondition_script_cfg = self.config.experimental_precondition_script.clone();
                             let actions_in_transit = self.actions_in_transit.clone();
+                            // The output receiver only exists once the action was created, so
+                            // it is handed over to a future that lives here and owns the client.
+                            let (output_rx_tx, output_rx_rx) = oneshot::channel();
+                            let output_request = ActionOutputRequest {
+                                worker_id: worker_id.clone(),
+                                instance_name: maybe_instance_name.clone().unwrap_or_default(),
+                                action_digest: action_digest.clone(),
+                                salt,
+                                ..Default::default()
+                            };
+                            let output_grpc_client = self.grpc_client.clone();
+                            futures.push(async move {
+                                if let Ok(output_rx) = output_rx_rx.await {
+                                    forward_action_output(output_grpc_client, output_rx, output_request).await;
+                                }
+                                Ok(())
+                            }.boxed());
                             let start_action_fut = self.metrics.clone().wrap(move |metrics| async move {
                                 metrics.preconditions.wrap(preconditions_met(precondition_script_cfg))
                                 .and_then(|_| running_actions_manager.create_and_add_action(worker_id_clone, start_execute))
@@ -240,6 +325,12 @@ impl<'a, T: WorkerApiClientTrait, U: RunningActionsManager> LocalWorkerImpl<'a,
                                     actions_in_transit.fetch_sub(1, Ordering::Release);
                                     r
                                 })
+                                .inspect_ok(|action| {
+                                    if let Some(output_rx) = action.take_output_receiver() {
+                                        // The receiver is only gone if we are shutting down.
+                                        let _ = output_rx_tx.send(output_rx);
+                                    }
+                                })
                                 .and_then(|action|
                                     action
                                         .clone()
@@ -365,12 +456,18 @@ pub async fn new_local_worker(
     } else {
         Duration::from_secs(config.max_action_timeout as u64)
     };
+    let kill_grace_period = if config.kill_grace_period_s == 0 {
+        DEFAULT_KILL_GRACE_PERIOD
+    } else {
+        Duration::from_secs(u64::from(config.kill_grace_period_s))
+    };
     let running_actions_manager =
         Arc::new(RunningActionsManagerImpl::new(RunningActionsManagerArgs {
             root_work_directory: config.work_directory.clone(),
             execution_configuration: ExecutionConfiguration {
                 entrypoint,
                 additional_environment: config.additional_environment.clone(),
+                kill_grace_period,
             },
             cas_store: fast_slow_store,
             ac_store,
@@ -553,6 +650,7 @@ struct Metrics {
     start_actions_received: CounterWithTime,
     disconnects_received: CounterWithTime,
     keep_alives_received: CounterWithTime,
+    kill_actions_received: CounterWithTime,
     preconditions: AsyncCounterWrapper,
     running_actions_manager_metrics: Weak<RunningActionManagerMetrics>,
 }
@@ -563,6 +661,7 @@ impl Metrics {
             start_actions_received: CounterWithTime::default(),
             disconnects_received: CounterWithTime::default(),
             keep_alives_received: CounterWithTime::default(),
+            kill_actions_received: CounterWithTime::default(),
             preconditions: AsyncCounterWrapper::default(),
             running_actions_manager_metrics,
         }
@@ -599,6 +698,11 @@ impl MetricsComponent for Metrics {
             &self.keep_alives_received,
             "Total number of keep-alives received from the scheduler.",
         );
+        c.publish(
+            "kill_actions_received",
+            &self.kill_actions_received,
+            "Total number of requests to kill an action received from the scheduler.",
+        );
         c.publish(
             "preconditions",
             &self.preconditions,
@@ -33,7 +33,8 @@ use bytes::{Bytes, BytesMut};
 use filetime::{set_file_mtime, FileTime};
 use formatx::Template;
 use futures::future::{
-    try_join, try_join3, try_join_all, BoxFuture, Future, FutureExt, TryFutureExt,
+    try_join, try_join3, try_join_all, BoxFuture, Fuse, FusedFuture, Future, FutureExt,
+    TryFutureExt,
 };
 use futures::stream::{FuturesUnordered, StreamExt, TryStreamExt};
 use nativelink_config::cas_server::{
@@ -55,8 +56,8 @@ use nativelink_store::fast_slow_store::FastSlowStore;
 use nativelink_store::filesystem_store::{FileEntry, FilesystemStore};
 use nativelink_store::grpc_store::GrpcStore;
 use nativelink_util::action_messages::{
-    to_execute_response, ActionInfo, ActionResult, DirectoryInfo, ExecutionMetadata, FileInfo,
-    NameOrPath, SymlinkInfo,
+    to_execute_response, ActionInfo, ActionOutputStream, ActionResult, DirectoryInfo,
+    ExecutionMetadata, FileInfo, NameOrPath, SymlinkInfo,
 };
 use nativelink_util::common::{fs, DigestInfo, JoinHandleDropGuard};
 use nativelink_util::digest_hasher::{DigestHasher, DigestHasherFunc};
@@ -71,12 +72,12 @@ use scopeguard::{guard, ScopeGuard};
 use serde::Deserialize;
 use tokio::io::{AsyncReadExt, AsyncSeekExt};
 use tokio::process;
-use tokio::sync::{oneshot, watch};
+use tokio::sync::{mpsc, oneshot, watch};
 use tokio::task::spawn_blocking;
 use tokio::time::timeout;
 use tokio_stream::wrappers::ReadDirStream;
 use tonic::Request;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
 pub type ActionId = [u8; 32];
@@ -85,6 +86,15 @@ pub type ActionId = [u8; 32];
 /// due to a signal.
 const EXIT_CODE_FOR_SIGNAL: i32 = 9;
 
+/// How often to check whether processes of a killed action are still alive
+/// after its leader exited.
+const PROCESS_GROUP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Number of output chunks that may wait to be forwarded to the scheduler.
+/// Once this many are waiting, the rest of that stream's output is not
+/// forwarded.
+const ACTION_OUTPUT_CHANNEL_SIZE: usize = 64;
+
 /// Default strategy for uploading historical results.
 /// Note: If this value changes the config documentation
 /// should reflect it.
@@ -562,6 +572,83 @@ pub trait RunningAction: Sync + Send + Sized + Unpin + 'static {
 
     /// Returns the work directory of the action.
     fn get_work_directory(&self) -> &String;
+
+    /// Returns a receiver for the output the action writes while it executes.
+    /// The receiver closes once the action stopped executing. Can only be
+    /// taken once.
+    fn take_output_receiver(&self) -> Option<mpsc::Receiver<ActionOutputChunk>>;
+}
+
+/// Asks every process of an action to exit. On unix actions run in their own
+/// process group, so the whole process tree is sent SIGTERM.
+fn start_kill_process_tree(
+    child_process: &mut process::Child,
+    process_group_id: Option<u32>,
+) -> Result<(), Error> {
+    #[cfg(target_family = "unix")]
+    if let Some(process_group_id) = process_group_id {
+        // SAFETY: kill() has no memory safety requirements.
+        if unsafe { libc::kill(-(process_group_id as libc::pid_t), libc::SIGTERM) } != 0 {
+            return Err(std::io::Error::last_os_error())
+                .err_tip(|| format!("Could not send SIGTERM to process group {process_group_id}"));
+        }
+        return Ok(());
+    }
+    let _ = process_group_id;
+    child_process
+        .start_kill()
+        .err_tip(|| "Could not kill child process")
+}
+
+/// Returns true if any process in the action's process group is still alive.
+fn process_group_is_alive(process_group_id: Option<u32>) -> bool {
+    #[cfg(target_family = "unix")]
+    if let Some(process_group_id) = process_group_id {
+        // SAFETY: kill() with signal 0 only checks that the processes exist.
+        return unsafe { libc::kill(-(process_group_id as libc::pid_t), 0) } == 0;
+    }
+    let _ = process_group_id;
+    false
+}
+
+/// Forcefully kills every process of an action that is still alive.
+fn force_kill_process_tree(child_process: &mut process::Child, process_group_id: Option<u32>) {
+    #[cfg(target_family = "unix")]
+    if let Some(process_group_id) = process_group_id {
+        // SAFETY: kill() has no memory safety requirements. The process group
+        // may already be gone, which is fine.
+        unsafe { libc::kill(-(process_group_id as libc::pid_t), libc::SIGKILL) };
+    }
+    let _ = process_group_id;
+    // The process may have already exited, which is fine.
+    let _ = child_process.start_kill();
+}
+
+/// Output an action wrote while it was executing.
+#[derive(Debug)]
+pub struct ActionOutputChunk {
+    pub stream: ActionOutputStream,
+    pub data: Bytes,
+}
+
+/// Forwards `data` unless the receiver went away or fell behind, in which
+/// case the rest of the stream is not forwarded. The output is still
+/// collected for the `ActionResult` either way.
+fn send_action_output(
+    output_tx: &mut Option<mpsc::Sender<ActionOutputChunk>>,
+    stream: ActionOutputStream,
+    data: &[u8],
+) {
+    let Some(tx) = output_tx else {
+        return;
+    };
+    let result = tx.try_send(ActionOutputChunk {
+        stream,
+        data: Bytes::copy_from_slice(data),
+    });
+    if result.is_err() {
+        *output_tx = None;
+    }
 }
 
 struct RunningActionImplExecutionResult {
@@ -572,10 +659,15 @@ struct RunningActionImplExecutionResult {
 
 struct RunningActionImplState {
     command_proto: Option<ProtoCommand>,
-    // TODO(allada) Kill is not implemented yet, but is instrumented.
-    // However, it is used if the worker disconnects to destroy current jobs.
-    kill_channel_tx: Option<oneshot::Sender<()>>,
-    kill_channel_rx: Option<oneshot::Receiver<()>>,
+    // Used when the scheduler asks to kill the action and when the worker
+    // disconnects to destroy current jobs. Carries the code the action's
+    // error is reported with.
+    kill_channel_tx: Option<oneshot::Sender<Code>>,
+    kill_channel_rx: Option<oneshot::Receiver<Code>>,
+    // Output is sent on this while the command runs. Dropped once the command
+    // finished so the receiver knows there is no more output.
+    output_tx: Option<mpsc::Sender<ActionOutputChunk>>,
+    output_rx: Option<mpsc::Receiver<ActionOutputChunk>>,
     execution_result: Option<RunningActionImplExecutionResult>,
     action_result: Option<ActionResult>,
     execution_metadata: ExecutionMetadata,
@@ -607,6 +699,7 @@ impl RunningActionImpl {
         running_actions_manager: Arc<RunningActionsManagerImpl>,
     ) -> Self {
         let (kill_channel_tx, kill_channel_rx) = oneshot::channel();
+        let (output_tx, output_rx) = mpsc::channel(ACTION_OUTPUT_CHANNEL_SIZE);
         Self {
             action_id,
             work_directory,
@@ -617,6 +710,8 @@ impl RunningActionImpl {
                 command_proto: None,
                 kill_channel_rx: Some(kill_channel_rx),
                 kill_channel_tx: Some(kill_channel_tx),
+                output_tx: Some(output_tx),
+                output_rx: Some(output_rx),
                 execution_result: None,
                 action_result: None,
                 execution_metadata,
@@ -715,7 +810,7 @@ impl RunningActionImpl {
     }
 
     async fn inner_execute(self: Arc<Self>) -> Result<Arc<Self>, Error> {
-        let (command_proto, mut kill_channel_rx) = {
+        let (command_proto, mut kill_channel_rx, output_tx) = {
             let mut state = self.state.lock();
             state.execution_metadata.execution_start_timestamp =
                 (self.running_actions_manager.callbacks.now_fn)();
@@ -730,6 +825,7 @@ impl RunningActionImpl {
                     .err_tip(|| "Expected state to have kill_channel_rx in execute()")?
                     // This is important as we may be killed at any point.
                     .fuse(),
+                state.output_tx.take(),
             )
         };
         if command_proto.arguments.is_empty() {
@@ -747,7 +843,12 @@ impl RunningActionImpl {
             command_proto.arguments.iter().map(AsRef::as_ref).collect()
         };
         info!("\x1b[0;31mWorker Executing\x1b[0m: {:?}", &args);
-        let mut command_builder = process::Command::new(args[0]);
+        let mut std_command_builder = std::process::Command::new(args[0]);
+        // Run the action in its own process group so it can be killed along
+        // with every process it started.
+        #[cfg(target_family = "unix")]
+        std::os::unix::process::CommandExt::process_group(&mut std_command_builder, 0);
+        let mut command_builder = process::Command::from(std_command_builder);
         command_builder
             .args(&args[1..])
             .kill_on_drop(true)
@@ -825,6 +926,13 @@ impl RunningActionImpl {
         let mut child_process = command_builder
             .spawn()
             .err_tip(|| format!("Could not execute command {:?}", args))?;
+        // The child's pid is also its process group id. It is saved now because
+        // the pid is no longer available once the child has been waited on.
+        let process_group_id = if cfg!(target_family = "unix") {
+            child_process.id()
+        } else {
+            None
+        };
         let mut stdout_reader = child_process
             .stdout
             .take()
@@ -841,6 +949,7 @@ impl RunningActionImpl {
             tokio::spawn(async move { child_process.kill().await });
         });
 
+        let mut stdout_output_tx = output_tx.clone();
         let all_stdout_fut = JoinHandleDropGuard::new(tokio::spawn(async move {
             let mut all_stdout = BytesMut::new();
             loop {
@@ -851,9 +960,15 @@ impl RunningActionImpl {
                 if sz == 0 {
                     break; // EOF.
                 }
+                send_action_output(
+                    &mut stdout_output_tx,
+                    ActionOutputStream::Stdout,
+                    &all_stdout[all_stdout.len() - sz..],
+                );
             }
             Result::<Bytes, Error>::Ok(all_stdout.freeze())
         }));
+        let mut stderr_output_tx = output_tx;
         let all_stderr_fut = JoinHandleDropGuard::new(tokio::spawn(async move {
             let mut all_stderr = BytesMut::new();
             loop {
@@ -864,10 +979,36 @@ impl RunningActionImpl {
                 if sz == 0 {
                     break; // EOF.
                 }
+                send_action_output(
+                    &mut stderr_output_tx,
+                    ActionOutputStream::Stderr,
+                    &all_stderr[all_stderr.len() - sz..],
+                );
             }
             Result::<Bytes, Error>::Ok(all_stderr.freeze())
         }));
         let mut killed_action = false;
+        let kill_grace_period = self
+            .running_actions_manager
+            .execution_configuration
+            .kill_grace_period;
+        // Once the action is asked to exit, this sends SIGKILL to whatever is
+        // left after the grace period.
+        let mut force_kill_fut: Fuse<BoxFuture<'static, ()>> = Fuse::terminated();
+        let start_kill = |child_process: &mut process::Child| {
+            if kill_grace_period.is_zero() {
+                force_kill_process_tree(child_process, process_group_id);
+                return Fuse::terminated();
+            }
+            if let Err(e) = start_kill_process_tree(child_process, process_group_id) {
+                error!(
+                    "Could not kill process in RunningActionsManager for action {} : {:?}",
+                    hex::encode(self.action_id),
+                    e
+                );
+            }
+            (self.running_actions_manager.callbacks.sleep_fn)(kill_grace_period).fuse()
+        };
 
         let timer = self.metrics().child_process.begin_timer();
         let mut sleep_fut = (self.running_actions_manager.callbacks.sleep_fn)(self.timeout).fuse();
@@ -876,9 +1017,7 @@ impl RunningActionImpl {
                 _ = &mut sleep_fut => {
                     self.running_actions_manager.metrics.task_timeouts.inc();
                     killed_action = true;
-                    if let Err(e) = child_process_guard.start_kill() {
-                        error!("Could not kill process in RunningActionsManager for timeout : {:?}", e);
-                    }
+                    force_kill_fut = start_kill(&mut child_process_guard);
                     {
                         let mut state = self.state.lock();
                         state.error = Error::merge_option(state.error.take(), Some(Error::new(
@@ -891,14 +1030,31 @@ impl RunningActionImpl {
                         )));
                     }
                 },
+                _ = &mut force_kill_fut => {
+                    warn!(
+                        "Action {} did not exit within {:?} of being killed, sending SIGKILL",
+                        hex::encode(self.action_id),
+                        kill_grace_period
+                    );
+                    force_kill_process_tree(&mut child_process_guard, process_group_id);
+                },
                 maybe_exit_status = child_process_guard.wait() => {
+                    if killed_action {
+                        // The rest of the process group gets what is left of
+                        // the grace period before anything the action started
+                        // is force-killed.
+                        while !force_kill_fut.is_terminated() && process_group_is_alive(process_group_id) {
+                            tokio::select! {
+                                () = &mut force_kill_fut => {},
+                                () = tokio::time::sleep(PROCESS_GROUP_POLL_INTERVAL) => {},
+                            }
+                        }
+                        force_kill_process_tree(&mut child_process_guard, process_group_id);
+                    }
                     // Defuse our guard so it does not try to cleanup and make nessless logs.
                     drop(ScopeGuard::<_, _>::into_inner(child_process_guard));
                     let exit_status = maybe_exit_status.err_tip(|| "Failed to collect exit code of process")?;
-                    // TODO(allada) We should implement stderr/stdout streaming to client here.
                     // If we get killed before the stream is started, then these will lock up.
-                    // TODO(allada) There is a significant bug here. If we kill the action and the action creates
-                    // child processes, it can create zombies. See: https://github.com/tracemachina/nativelink/issues/225
                     let (stdout, stderr) = if killed_action {
                         drop(timer);
                         (Bytes::new(), Bytes::new())
@@ -941,23 +1097,13 @@ impl RunningActionImpl {
                     }
                     return Ok(self);
                 },
-                _ = &mut kill_channel_rx => {
+                kill_code = &mut kill_channel_rx => {
                     killed_action = true;
-                    if let Err(e) = child_process_guard.start_kill() {
-                        error!(
-                            "Could not kill process in RunningActionsManager for action {} : {:?}",
-                            hex::encode(self.action_id),
-                            e);
-                    } else {
-                        error!(
-                            "Could not get child process id, maybe already dead? for action {}",
-                            hex::encode(self.action_id)
-                        );
-                    }
+                    force_kill_fut = start_kill(&mut child_process_guard);
                     {
                         let mut state = self.state.lock();
                         state.error = Error::merge_option(state.error.take(), Some(Error::new(
-                            Code::Aborted,
+                            kill_code.unwrap_or(Code::Aborted),
                             format!(
                                 "Command '{}' was killed by scheduler",
                                 args.join(OsStr::new(" ")).to_string_lossy()
@@ -1210,6 +1356,8 @@ impl RunningActionImpl {
 
     async fn inner_cleanup(self: Arc<Self>) -> Result<Arc<Self>, Error> {
         info!("\x1b[0;31mWorker Cleanup\x1b[0m");
+        // Let output listeners know no more output is coming if we never executed.
+        drop(self.state.lock().output_tx.take());
         // Note: We need to be careful to keep trying to cleanup even if one of the steps fails.
         let remove_dir_result = fs::remove_dir_all(&self.work_directory)
             .await
@@ -1292,6 +1440,10 @@ impl RunningAction for RunningActionImpl {
     fn get_work_directory(&self) -> &String {
         &self.work_directory
     }
+
+    fn take_output_receiver(&self) -> Option<mpsc::Receiver<ActionOutputChunk>> {
+        self.state.lock().output_rx.take()
+    }
 }
 
 #[async_trait]
@@ -1313,6 +1465,9 @@ pub trait RunningActionsManager: Sync + Send + Sized + Unpin + 'static {
 
     async fn kill_all(&self);
 
+    /// Kills a single running action. The action finishes as cancelled.
+    async fn kill_action(&self, action_id: &ActionId) -> Result<(), Error>;
+
     fn metrics(&self) -> &Arc<Metrics>;
 }
 
@@ -1345,6 +1500,9 @@ pub struct ExecutionConfiguration {
     /// executes other than those in the ActionInfo.  On Windows, SystemRoot
     /// and PATH are also assigned (see inner_execute).
     pub additional_environment: Option<HashMap<String, EnvironmentSource>>,
+    /// How long a killed action's processes are given to exit after SIGTERM
+    /// before they are sent SIGKILL. Zero sends SIGKILL immediately.
+    pub kill_grace_period: Duration,
 }
 
 struct UploadActionResults {
@@ -1713,15 +1871,15 @@ impl RunningActionsManagerImpl {
         result.map(|_| ())
     }
 
-    // Note: We do not capture metrics on this call, only `.kill_all()`.
+    // Note: We do not capture metrics on this call, only `.kill_all()` and `.kill_action()`.
     // Important: When the future returns the process may still be running.
-    async fn kill_action(action: Arc<RunningActionImpl>) {
+    async fn kill_operation(action: Arc<RunningActionImpl>, code: Code) {
         let kill_channel_tx = {
             let mut action_state = action.state.lock();
             action_state.kill_channel_tx.take()
         };
         if let Some(kill_channel_tx) = kill_channel_tx {
-            if kill_channel_tx.send(()).is_err() {
+            if kill_channel_tx.send(code).is_err() {
                 error!(
                     "Error sending kill to running action {}",
                     hex::encode(action.action_id)
@@ -1823,7 +1981,7 @@ impl RunningActionsManager for RunningActionsManagerImpl {
                         .collect()
                 };
                 for action in kill_actions {
-                    Self::kill_action(action).await;
+                    Self::kill_operation(action, Code::Aborted).await;
                 }
             })
             .await;
@@ -1837,6 +1995,29 @@ impl RunningActionsManager for RunningActionsManagerImpl {
             .await;
     }
 
+    // Important: When the future returns the process may still be running.
+    async fn kill_action(&self, action_id: &ActionId) -> Result<(), Error> {
+        self.metrics
+            .kill_action
+            .wrap(async move {
+                let action = self
+                    .running_actions
+                    .lock()
+                    .get(action_id)
+                    .and_then(Weak::upgrade)
+                    .ok_or_else(|| {
+                        make_err!(
+                            Code::NotFound,
+                            "Action {} is not running on this worker",
+                            hex::encode(action_id)
+                        )
+                    })?;
+                Self::kill_operation(action, Code::Cancelled).await;
+                Ok(())
+            })
+            .await
+    }
+
     #[inline]
     fn metrics(&self) -> &Arc<Metrics> {
         &self.metrics
@@ -1848,6 +2029,7 @@ pub struct Metrics {
     create_and_add_action: AsyncCounterWrapper,
     cache_action_result: AsyncCounterWrapper,
     kill_all: AsyncCounterWrapper,
+    kill_action: AsyncCounterWrapper,
     create_action_info: AsyncCounterWrapper,
     make_work_directory: AsyncCounterWrapper,
     prepare_action: AsyncCounterWrapper,
@@ -1884,6 +2066,11 @@ impl MetricsComponent for Metrics {
             &self.kill_all,
             "Stats about the kill_all command.",
         );
+        c.publish(
+            "kill_action",
+            &self.kill_action,
+            "Stats about the kill_action command.",
+        );
         c.publish(
             "create_action_info",
             &self.create_action_info,
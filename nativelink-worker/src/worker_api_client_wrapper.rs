@@ -15,7 +15,8 @@
 use async_trait::async_trait;
 use nativelink_proto::com::github::trace_machina::nativelink::remote_execution::worker_api_client::WorkerApiClient;
 use nativelink_proto::com::github::trace_machina::nativelink::remote_execution::{
-    ExecuteResult, GoingAwayRequest, KeepAliveRequest, SupportedProperties, UpdateForWorker,
+    ActionOutputRequest, ExecuteResult, GoingAwayRequest, KeepAliveRequest, SupportedProperties,
+    UpdateForWorker,
 };
 use tonic::codec::Streaming;
 use tonic::transport::Channel;
@@ -35,6 +36,9 @@ pub trait WorkerApiClientTrait: Clone + Sync + Send + Sized + Unpin {
     async fn going_away(&mut self, request: GoingAwayRequest) -> Result<Response<()>, Status>;
 
     async fn execution_response(&mut self, request: ExecuteResult) -> Result<Response<()>, Status>;
+
+    async fn action_output(&mut self, request: ActionOutputRequest)
+        -> Result<Response<()>, Status>;
 }
 
 #[derive(Clone)]
@@ -68,4 +72,11 @@ impl WorkerApiClientTrait for WorkerApiClientWrapper {
     async fn execution_response(&mut self, request: ExecuteResult) -> Result<Response<()>, Status> {
         self.inner.execution_response(request).await
     }
+
+    async fn action_output(
+        &mut self,
+        request: ActionOutputRequest,
+    ) -> Result<Response<()>, Status> {
+        self.inner.action_output(request).await
+    }
 }
@@ -34,7 +34,7 @@ use nativelink_error::{make_err, make_input_err, Code, Error};
 use nativelink_proto::build::bazel::remote::execution::v2::platform::Property;
 use nativelink_proto::com::github::trace_machina::nativelink::remote_execution::update_for_worker::Update;
 use nativelink_proto::com::github::trace_machina::nativelink::remote_execution::{
-    execute_result, ConnectionResult, ExecuteResult, StartExecute, SupportedProperties,
+    execute_result, ConnectionResult, ExecuteResult, KillAction, StartExecute, SupportedProperties,
     UpdateForWorker,
 };
 use nativelink_store::fast_slow_store::FastSlowStore;
@@ -195,6 +195,54 @@ mod local_worker_tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn kill_action_request_kills_action() -> Result<(), Box<dyn std::error::Error>> {
+        let mut test_context = setup_local_worker(HashMap::new()).await;
+        let streaming_response = test_context.maybe_streaming_response.take().unwrap();
+
+        {
+            // Ensure our worker connects and properties were sent.
+            let props = test_context
+                .client
+                .expect_connect_worker(Ok(streaming_response))
+                .await;
+            assert_eq!(props, SupportedProperties::default());
+        }
+
+        let mut tx_stream = test_context.maybe_tx_stream.take().unwrap();
+        {
+            tx_stream
+                .send_data(encode_stream_proto(&UpdateForWorker {
+                    update: Some(Update::ConnectionResult(ConnectionResult {
+                        worker_id: "foobar".to_string(),
+                    })),
+                })?)
+                .await
+                .map_err(|e| make_input_err!("Could not send : {:?}", e))?;
+        }
+
+        let unique_qualifier = ActionInfoHashKey {
+            instance_name: INSTANCE_NAME.to_string(),
+            digest: DigestInfo::new([2u8; 32], 10),
+            salt: 0,
+        };
+        tx_stream
+            .send_data(encode_stream_proto(&UpdateForWorker {
+                update: Some(Update::KillAction(KillAction {
+                    action_name: unique_qualifier.action_name(),
+                })),
+            })?)
+            .await
+            .map_err(|e| make_input_err!("Could not send : {:?}", e))?;
+
+        assert_eq!(
+            test_context.actions_manager.expect_kill_action().await,
+            unique_qualifier.get_hash()
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn blake3_digest_function_registerd_properly() -> Result<(), Box<dyn std::error::Error>> {
         const SALT: u64 = 1000;
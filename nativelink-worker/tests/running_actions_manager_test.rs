@@ -44,7 +44,8 @@ use nativelink_store::filesystem_store::FilesystemStore;
 use nativelink_store::memory_store::MemoryStore;
 #[cfg_attr(target_family = "windows", allow(unused_imports))]
 use nativelink_util::action_messages::{
-    ActionResult, DirectoryInfo, ExecutionMetadata, FileInfo, NameOrPath, SymlinkInfo,
+    ActionInfoHashKey, ActionOutputStream, ActionResult, DirectoryInfo, ExecutionMetadata,
+    FileInfo, NameOrPath, SymlinkInfo,
 };
 use nativelink_util::common::{fs, DigestInfo};
 use nativelink_util::digest_hasher::{DigestHasher, DigestHasherFunc};
@@ -1424,6 +1425,466 @@ mod running_actions_manager_tests {
         Ok(())
     }
 
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn output_is_sent_while_action_runs() -> Result<(), Box<dyn std::error::Error>> {
+        const WORKER_ID: &str = "foo_worker_id";
+        const SALT: u64 = 55;
+
+        let (_, _, cas_store, ac_store) = setup_stores().await?;
+        let root_work_directory = make_temp_path("root_work_directory");
+        fs::create_dir_all(&root_work_directory).await?;
+
+        let running_actions_manager =
+            Arc::new(RunningActionsManagerImpl::new(RunningActionsManagerArgs {
+                root_work_directory: root_work_directory.clone(),
+                execution_configuration: ExecutionConfiguration::default(),
+                cas_store: Pin::into_inner(cas_store.clone()),
+                ac_store: Some(Pin::into_inner(ac_store.clone())),
+                historical_store: Pin::into_inner(cas_store.clone()),
+                upload_action_result_config:
+                    &nativelink_config::cas_server::UploadActionResultConfig {
+                        upload_ac_results_strategy:
+                            nativelink_config::cas_server::UploadCacheResultsStrategy::never,
+                        ..Default::default()
+                    },
+                max_action_timeout: Duration::MAX,
+                timeout_handled_externally: false,
+            })?);
+
+        let command = Command {
+            arguments: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "printf foo; printf bar >&2; printf baz".to_string(),
+            ],
+            output_paths: vec![],
+            working_directory: ".".to_string(),
+            ..Default::default()
+        };
+        let command_digest = serialize_and_upload_message(
+            &command,
+            cas_store.as_ref(),
+            &mut DigestHasherFunc::Sha256.hasher(),
+        )
+        .await?;
+        let input_root_digest = serialize_and_upload_message(
+            &Directory::default(),
+            cas_store.as_ref(),
+            &mut DigestHasherFunc::Sha256.hasher(),
+        )
+        .await?;
+        let action = Action {
+            command_digest: Some(command_digest.into()),
+            input_root_digest: Some(input_root_digest.into()),
+            ..Default::default()
+        };
+        let action_digest = serialize_and_upload_message(
+            &action,
+            cas_store.as_ref(),
+            &mut DigestHasherFunc::Sha256.hasher(),
+        )
+        .await?;
+
+        let running_action_impl = running_actions_manager
+            .clone()
+            .create_and_add_action(
+                WORKER_ID.to_string(),
+                StartExecute {
+                    execute_request: Some(ExecuteRequest {
+                        action_digest: Some(action_digest.into()),
+                        ..Default::default()
+                    }),
+                    salt: SALT,
+                    queued_timestamp: Some(make_system_time(1000).into()),
+                },
+            )
+            .await?;
+        let mut output_rx = running_action_impl
+            .take_output_receiver()
+            .err_tip(|| "Expected output receiver to exist")?;
+        assert!(
+            running_action_impl.take_output_receiver().is_none(),
+            "Expected output receiver to only be given out once"
+        );
+
+        let result = run_action(running_action_impl).await?;
+        assert_eq!(result.exit_code, 0);
+
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        // The channel is closed once the action is cleaned up.
+        while let Some(chunk) = output_rx.recv().await {
+            match chunk.stream {
+                ActionOutputStream::Stdout => stdout.extend_from_slice(&chunk.data),
+                ActionOutputStream::Stderr => stderr.extend_from_slice(&chunk.data),
+            }
+        }
+        assert_eq!(from_utf8(&stdout)?, "foobaz");
+        assert_eq!(from_utf8(&stderr)?, "bar");
+
+        Ok(())
+    }
+
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    async fn output_is_dropped_when_nobody_reads_it() -> Result<(), Box<dyn std::error::Error>> {
+        const WORKER_ID: &str = "foo_worker_id";
+        const SALT: u64 = 55;
+
+        let (_, _, cas_store, ac_store) = setup_stores().await?;
+        let root_work_directory = make_temp_path("root_work_directory");
+        fs::create_dir_all(&root_work_directory).await?;
+
+        let running_actions_manager =
+            Arc::new(RunningActionsManagerImpl::new(RunningActionsManagerArgs {
+                root_work_directory: root_work_directory.clone(),
+                execution_configuration: ExecutionConfiguration::default(),
+                cas_store: Pin::into_inner(cas_store.clone()),
+                ac_store: Some(Pin::into_inner(ac_store.clone())),
+                historical_store: Pin::into_inner(cas_store.clone()),
+                upload_action_result_config:
+                    &nativelink_config::cas_server::UploadActionResultConfig {
+                        upload_ac_results_strategy:
+                            nativelink_config::cas_server::UploadCacheResultsStrategy::never,
+                        ..Default::default()
+                    },
+                max_action_timeout: Duration::MAX,
+                timeout_handled_externally: false,
+            })?);
+
+        let command = Command {
+            arguments: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "for i in $(seq 1 200); do echo $i; sleep 0.001; done".to_string(),
+            ],
+            output_paths: vec![],
+            working_directory: ".".to_string(),
+            ..Default::default()
+        };
+        let command_digest = serialize_and_upload_message(
+            &command,
+            cas_store.as_ref(),
+            &mut DigestHasherFunc::Sha256.hasher(),
+        )
+        .await?;
+        let input_root_digest = serialize_and_upload_message(
+            &Directory::default(),
+            cas_store.as_ref(),
+            &mut DigestHasherFunc::Sha256.hasher(),
+        )
+        .await?;
+        let action = Action {
+            command_digest: Some(command_digest.into()),
+            input_root_digest: Some(input_root_digest.into()),
+            ..Default::default()
+        };
+        let action_digest = serialize_and_upload_message(
+            &action,
+            cas_store.as_ref(),
+            &mut DigestHasherFunc::Sha256.hasher(),
+        )
+        .await?;
+
+        let running_action_impl = running_actions_manager
+            .clone()
+            .create_and_add_action(
+                WORKER_ID.to_string(),
+                StartExecute {
+                    execute_request: Some(ExecuteRequest {
+                        action_digest: Some(action_digest.into()),
+                        ..Default::default()
+                    }),
+                    salt: SALT,
+                    queued_timestamp: Some(make_system_time(1000).into()),
+                },
+            )
+            .await?;
+        let mut output_rx = running_action_impl
+            .take_output_receiver()
+            .err_tip(|| "Expected output receiver to exist")?;
+
+        // Nothing reads the output while the action runs, that must not hold
+        // up the action or buffer all of its output.
+        let result = run_action(running_action_impl).await?;
+        assert_eq!(result.exit_code, 0);
+
+        let mut stdout = Vec::new();
+        let mut chunk_count = 0;
+        while let Some(chunk) = output_rx.recv().await {
+            stdout.extend_from_slice(&chunk.data);
+            chunk_count += 1;
+        }
+        assert!(chunk_count <= 64, "Got {chunk_count} chunks");
+        // The output that was forwarded is the start of the output.
+        let expected: String = (1..=200).map(|i| format!("{i}\n")).collect();
+        assert!(expected.as_bytes().starts_with(&stdout));
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn kill_action_kills_process_tree() -> Result<(), Box<dyn std::error::Error>> {
+        const WORKER_ID: &str = "foo_worker_id";
+        const SALT: u64 = 55;
+        const PID_FILE: &str = "sleep.pid";
+
+        let (_, _, cas_store, ac_store) = setup_stores().await?;
+        let root_work_directory = make_temp_path("root_work_directory");
+        fs::create_dir_all(&root_work_directory).await?;
+
+        let running_actions_manager =
+            Arc::new(RunningActionsManagerImpl::new(RunningActionsManagerArgs {
+                root_work_directory: root_work_directory.clone(),
+                execution_configuration: ExecutionConfiguration {
+                    kill_grace_period: Duration::from_secs(10),
+                    ..Default::default()
+                },
+                cas_store: Pin::into_inner(cas_store.clone()),
+                ac_store: Some(Pin::into_inner(ac_store.clone())),
+                historical_store: Pin::into_inner(cas_store.clone()),
+                upload_action_result_config:
+                    &nativelink_config::cas_server::UploadActionResultConfig {
+                        upload_ac_results_strategy:
+                            nativelink_config::cas_server::UploadCacheResultsStrategy::never,
+                        ..Default::default()
+                    },
+                max_action_timeout: Duration::MAX,
+                timeout_handled_externally: false,
+            })?);
+
+        // The action starts a child process of its own that must be killed too.
+        let command = Command {
+            arguments: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                format!(
+                    "sleep 1000 & echo $! > {PID_FILE}.tmp && mv {PID_FILE}.tmp {PID_FILE}; wait"
+                ),
+            ],
+            output_paths: vec![],
+            working_directory: ".".to_string(),
+            ..Default::default()
+        };
+        let command_digest = serialize_and_upload_message(
+            &command,
+            cas_store.as_ref(),
+            &mut DigestHasherFunc::Sha256.hasher(),
+        )
+        .await?;
+        let input_root_digest = serialize_and_upload_message(
+            &Directory::default(),
+            cas_store.as_ref(),
+            &mut DigestHasherFunc::Sha256.hasher(),
+        )
+        .await?;
+        let action = Action {
+            command_digest: Some(command_digest.into()),
+            input_root_digest: Some(input_root_digest.into()),
+            ..Default::default()
+        };
+        let action_digest = serialize_and_upload_message(
+            &action,
+            cas_store.as_ref(),
+            &mut DigestHasherFunc::Sha256.hasher(),
+        )
+        .await?;
+        let action_id = ActionInfoHashKey {
+            instance_name: String::new(),
+            digest: action_digest,
+            salt: SALT,
+        }
+        .get_hash();
+
+        let running_action_impl = running_actions_manager
+            .clone()
+            .create_and_add_action(
+                WORKER_ID.to_string(),
+                StartExecute {
+                    execute_request: Some(ExecuteRequest {
+                        action_digest: Some(action_digest.into()),
+                        ..Default::default()
+                    }),
+                    salt: SALT,
+                    queued_timestamp: Some(make_system_time(1000).into()),
+                },
+            )
+            .await?;
+        let pid_file = format!("{}/{PID_FILE}", running_action_impl.get_work_directory());
+
+        let kill_fut = async {
+            // Wait for the action to start its child process.
+            let sleep_pid = loop {
+                if let Ok(sleep_pid) = std::fs::read_to_string(&pid_file) {
+                    break sleep_pid.trim().to_string();
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            };
+            running_actions_manager.kill_action(&action_id).await?;
+            Result::<String, Error>::Ok(sleep_pid)
+        };
+        let (result, sleep_pid) = futures::join!(run_action(running_action_impl), kill_fut);
+        let (result, sleep_pid) = (result?, sleep_pid?);
+
+        assert_eq!(
+            result.error.as_ref().map(|err| err.code),
+            Some(Code::Cancelled)
+        );
+        // The child process must not outlive the action. It may be left as a
+        // zombie if nothing reaps orphans in this environment.
+        let mut sleep_is_alive = true;
+        for _ in 0..100 {
+            sleep_is_alive = match std::fs::read_to_string(format!("/proc/{sleep_pid}/stat")) {
+                Ok(stat) => !stat
+                    .rsplit_once(") ")
+                    .is_some_and(|(_, rest)| rest.starts_with('Z')),
+                Err(_) => false,
+            };
+            if !sleep_is_alive {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(
+            !sleep_is_alive,
+            "Child process {sleep_pid} is still running"
+        );
+
+        // Killing an action that is no longer running is an error.
+        assert_eq!(
+            running_actions_manager
+                .kill_action(&action_id)
+                .await
+                .unwrap_err()
+                .code,
+            Code::NotFound
+        );
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn kill_action_gives_process_group_grace_period() -> Result<(), Box<dyn std::error::Error>>
+    {
+        const WORKER_ID: &str = "foo_worker_id";
+        const SALT: u64 = 55;
+        const STARTED_FILE: &str = "started";
+
+        let (_, _, cas_store, ac_store) = setup_stores().await?;
+        let root_work_directory = make_temp_path("root_work_directory");
+        fs::create_dir_all(&root_work_directory).await?;
+        let cleanup_file = format!("{root_work_directory}/cleanup_done");
+
+        let running_actions_manager =
+            Arc::new(RunningActionsManagerImpl::new(RunningActionsManagerArgs {
+                root_work_directory: root_work_directory.clone(),
+                execution_configuration: ExecutionConfiguration {
+                    kill_grace_period: Duration::from_secs(10),
+                    ..Default::default()
+                },
+                cas_store: Pin::into_inner(cas_store.clone()),
+                ac_store: Some(Pin::into_inner(ac_store.clone())),
+                historical_store: Pin::into_inner(cas_store.clone()),
+                upload_action_result_config:
+                    &nativelink_config::cas_server::UploadActionResultConfig {
+                        upload_ac_results_strategy:
+                            nativelink_config::cas_server::UploadCacheResultsStrategy::never,
+                        ..Default::default()
+                    },
+                max_action_timeout: Duration::MAX,
+                timeout_handled_externally: false,
+            })?);
+
+        // The leader exits on SIGTERM right away, but its child needs a moment
+        // to clean up after itself.
+        let command = Command {
+            arguments: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                format!(
+                    "(trap 'sleep 0.5; touch {cleanup_file}; exit 0' TERM; \
+                     touch {STARTED_FILE}; while true; do sleep 0.1; done) & wait"
+                ),
+            ],
+            output_paths: vec![],
+            working_directory: ".".to_string(),
+            ..Default::default()
+        };
+        let command_digest = serialize_and_upload_message(
+            &command,
+            cas_store.as_ref(),
+            &mut DigestHasherFunc::Sha256.hasher(),
+        )
+        .await?;
+        let input_root_digest = serialize_and_upload_message(
+            &Directory::default(),
+            cas_store.as_ref(),
+            &mut DigestHasherFunc::Sha256.hasher(),
+        )
+        .await?;
+        let action = Action {
+            command_digest: Some(command_digest.into()),
+            input_root_digest: Some(input_root_digest.into()),
+            ..Default::default()
+        };
+        let action_digest = serialize_and_upload_message(
+            &action,
+            cas_store.as_ref(),
+            &mut DigestHasherFunc::Sha256.hasher(),
+        )
+        .await?;
+        let action_id = ActionInfoHashKey {
+            instance_name: String::new(),
+            digest: action_digest,
+            salt: SALT,
+        }
+        .get_hash();
+
+        let running_action_impl = running_actions_manager
+            .clone()
+            .create_and_add_action(
+                WORKER_ID.to_string(),
+                StartExecute {
+                    execute_request: Some(ExecuteRequest {
+                        action_digest: Some(action_digest.into()),
+                        ..Default::default()
+                    }),
+                    salt: SALT,
+                    queued_timestamp: Some(make_system_time(1000).into()),
+                },
+            )
+            .await?;
+        let started_file = format!(
+            "{}/{STARTED_FILE}",
+            running_action_impl.get_work_directory()
+        );
+
+        let kill_fut = async {
+            // Wait for the child process to install its handler.
+            while fs::metadata(&started_file).await.is_err() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            running_actions_manager.kill_action(&action_id).await
+        };
+        let (result, kill_result) = futures::join!(run_action(running_action_impl), kill_fut);
+        let (result, ()) = (result?, kill_result?);
+
+        assert_eq!(
+            result.error.as_ref().map(|err| err.code),
+            Some(Code::Cancelled)
+        );
+        // The child was not force-killed when the leader exited.
+        assert!(
+            fs::metadata(&cleanup_file).await.is_ok(),
+            "Child process was killed before it could clean up"
+        );
+
+        Ok(())
+    }
+
     // This script runs a command under a wrapper script set in a config.
     // The wrapper script will print a constant string to stderr, and the test itself will
     // print to stdout. We then check the results of both to make sure the shell script was
@@ -1492,6 +1953,7 @@ exit 0
                 execution_configuration: ExecutionConfiguration {
                     entrypoint: Some(test_wrapper_script.into_string().unwrap()),
                     additional_environment: None,
+                    ..Default::default()
                 },
                 cas_store: Pin::into_inner(cas_store.clone()),
                 ac_store: Some(Pin::into_inner(ac_store.clone())),
@@ -1648,6 +2110,7 @@ exit 0
                             EnvironmentSource::timeout_millis,
                         ),
                     ])),
+                    ..Default::default()
                 },
                 cas_store: Pin::into_inner(cas_store.clone()),
                 ac_store: Some(Pin::into_inner(ac_store.clone())),
@@ -1803,6 +2266,7 @@ exit 1
                         "SIDE_CHANNEL_FILE".to_string(),
                         EnvironmentSource::side_channel_file,
                     )])),
+                    ..Default::default()
                 },
                 cas_store: Pin::into_inner(cas_store.clone()),
                 ac_store: Some(Pin::into_inner(ac_store.clone())),
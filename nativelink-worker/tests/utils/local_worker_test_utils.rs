@@ -21,7 +21,8 @@ use hyper::body::Sender as HyperSender;
 use nativelink_config::cas_server::{EndpointConfig, LocalWorkerConfig, WorkerProperty};
 use nativelink_error::Error;
 use nativelink_proto::com::github::trace_machina::nativelink::remote_execution::{
-    ExecuteResult, GoingAwayRequest, KeepAliveRequest, SupportedProperties, UpdateForWorker,
+    ActionOutputRequest, ExecuteResult, GoingAwayRequest, KeepAliveRequest, SupportedProperties,
+    UpdateForWorker,
 };
 use nativelink_util::common::JoinHandleDropGuard;
 use nativelink_worker::local_worker::LocalWorker;
@@ -152,6 +153,13 @@ impl WorkerApiClientTrait for MockWorkerApiClient {
         unreachable!();
     }
 
+    async fn action_output(
+        &mut self,
+        _request: ActionOutputRequest,
+    ) -> Result<Response<()>, Status> {
+        unreachable!();
+    }
+
     async fn execution_response(&mut self, request: ExecuteResult) -> Result<Response<()>, Status> {
         self.tx_call
             .send(WorkerClientApiCalls::ExecutionResponse(request))
@@ -21,13 +21,16 @@ use nativelink_proto::com::github::trace_machina::nativelink::remote_execution::
 use nativelink_util::action_messages::ActionResult;
 use nativelink_util::common::DigestInfo;
 use nativelink_util::digest_hasher::DigestHasherFunc;
-use nativelink_worker::running_actions_manager::{Metrics, RunningAction, RunningActionsManager};
+use nativelink_worker::running_actions_manager::{
+    ActionId, ActionOutputChunk, Metrics, RunningAction, RunningActionsManager,
+};
 use tokio::sync::mpsc;
 
 #[derive(Debug)]
 enum RunningActionManagerCalls {
     CreateAndAddAction((String, StartExecute)),
     CacheActionResult(Box<(DigestInfo, ActionResult, DigestHasherFunc)>),
+    KillAction(ActionId),
 }
 
 enum RunningActionManagerReturns {
@@ -101,6 +104,18 @@ impl MockRunningActionsManager {
         }
     }
 
+    pub async fn expect_kill_action(&self) -> ActionId {
+        let mut rx_call_lock = self.rx_call.lock().await;
+        match rx_call_lock
+            .recv()
+            .await
+            .expect("Could not recieve msg in mpsc")
+        {
+            RunningActionManagerCalls::KillAction(action_id) => action_id,
+            _ => panic!("Got incorrect call waiting for kill_action"),
+        }
+    }
+
     pub async fn expect_kill_all(&self) {
         let mut rx_kill_all_lock = self.rx_kill_all.lock().await;
         rx_kill_all_lock
@@ -151,6 +166,13 @@ impl RunningActionsManager for MockRunningActionsManager {
         Ok(())
     }
 
+    async fn kill_action(&self, action_id: &ActionId) -> Result<(), Error> {
+        self.tx_call
+            .send(RunningActionManagerCalls::KillAction(*action_id))
+            .expect("Could not send request to mpsc");
+        Ok(())
+    }
+
     async fn kill_all(&self) {
         self.tx_kill_all
             .send(())
@@ -404,4 +426,8 @@ impl RunningAction for MockRunningAction {
     fn get_work_directory(&self) -> &String {
         unreachable!();
     }
+
+    fn take_output_receiver(&self) -> Option<mpsc::Receiver<ActionOutputChunk>> {
+        None
+    }
 }
@@ -14,41 +14,61 @@
 
 use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
+use std::pin::Pin;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use async_lock::Mutex as AsyncMutex;
+use axum::response::IntoResponse;
 use axum::Router;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use futures::future::{select_all, BoxFuture, OptionFuture, TryFutureExt};
 use futures::{FutureExt, StreamExt};
 use hyper::server::conn::Http;
 use hyper::{Response, StatusCode};
 use mimalloc::MiMalloc;
 use nativelink_config::cas_server::{
-    CasConfig, CompressionAlgorithm, GlobalConfig, ListenerConfig, ServerConfig, WorkerConfig,
+    CasConfig, CompressionAlgorithm, GlobalConfig, InstanceName, ListenerConfig, ServerConfig,
+    WorkerConfig,
 };
-use nativelink_config::stores::ConfigDigestHashFunction;
-use nativelink_error::{make_err, Code, Error, ResultExt};
+use nativelink_config::reload::{record_field_change, record_new_server, ConfigChanges};
+use nativelink_config::schedulers::SchedulerConfig;
+use nativelink_config::stores::{ConfigDigestHashFunction, StoreConfig};
+use nativelink_config::validate::{validate, Severity};
+use nativelink_error::{make_err, make_input_err, Code, Error, ResultExt};
+use nativelink_scheduler::action_scheduler::ActionScheduler;
 use nativelink_scheduler::default_scheduler_factory::scheduler_factory;
 use nativelink_scheduler::worker::WorkerId;
-use nativelink_service::ac_server::AcServer;
+use nativelink_scheduler::worker_scheduler::WorkerScheduler;
+use nativelink_service::ac_server::{AcCacheStats, AcServer};
+use nativelink_service::auth::{
+    certificate_common_name, require_admin, AuthContext, Authenticator,
+};
+use nativelink_service::blob_browser::{BlobBrowser, BlobBrowserResponse};
 use nativelink_service::bytestream_server::ByteStreamServer;
 use nativelink_service::capabilities_server::CapabilitiesServer;
-use nativelink_service::cas_server::CasServer;
+use nativelink_service::cas_server::{supports_split_blob, CasServer};
+use nativelink_service::dashboard::{Dashboard, DASHBOARD_HTML};
 use nativelink_service::execution_server::ExecutionServer;
+use nativelink_service::health_server::HealthServer;
+use nativelink_service::operations_server::OperationsServer;
 use nativelink_service::worker_api_server::WorkerApiServer;
 use nativelink_store::default_store_factory::store_factory;
+use nativelink_store::quota_store::QuotaStore;
 use nativelink_store::store_manager::StoreManager;
+use nativelink_util::action_messages::ActionInfoHashKey;
 use nativelink_util::common::fs::{set_idle_file_descriptor_timeout, set_open_file_limit};
+use nativelink_util::common::DigestInfo;
 use nativelink_util::digest_hasher::{set_default_digest_hasher_func, DigestHasherFunc};
 use nativelink_util::health_utils::{
-    HealthRegistryBuilder, HealthStatus, HealthStatusDescription, HealthStatusReporter,
+    CachedHealthRegistry, HealthRegistryBuilder, HealthStatus, HealthStatusDescription,
+    HealthStatusReporter,
 };
 use nativelink_util::metrics_utils::{
     set_metrics_enabled_for_this_thread, Collector, CollectorState, Counter, MetricsComponent,
     Registry,
 };
+use nativelink_util::shutdown::run_shutdown_hooks;
 use nativelink_util::store_trait::{
     set_default_digest_size_health_check, DEFAULT_DIGEST_SIZE_HEALTH_CHECK_CFG,
 };
@@ -67,8 +87,9 @@ use tokio_rustls::TlsAcceptor;
 use tonic::codec::CompressionEncoding;
 use tonic::transport::Server as TonicServer;
 use tower::util::ServiceExt;
-use tracing::{error, warn};
+use tracing::{error, info, warn};
 use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+use tracing_subscriber::reload;
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
@@ -79,185 +100,496 @@ const DEFAULT_PROMETHEUS_METRICS_PATH: &str = "/metrics";
 /// Note: This must be kept in sync with the documentation in `AdminConfig::path`.
 const DEFAULT_ADMIN_API_PATH: &str = "/admin";
 
+/// Note: This must be kept in sync with the documentation in `DashboardConfig::path`.
+const DEFAULT_DASHBOARD_PATH: &str = "/dashboard";
+
+/// Note: This must be kept in sync with the documentation in `BrowserConfig::path`.
+const DEFAULT_BROWSER_PATH: &str = "/browser";
+
 /// Name of environment variable to disable metrics.
 const METRICS_DISABLE_ENV: &str = "NATIVELINK_DISABLE_METRICS";
 
 /// Content type header value for JSON.
 const JSON_CONTENT_TYPE: &str = "application/json; charset=utf-8";
 
+/// How often the health of the stores and schedulers is checked for the
+/// gRPC health service and the dashboard.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
 /// Backend for bazel remote execution / cache API.
 #[derive(Parser, Debug)]
 #[clap(
     author = "Trace Machina, Inc. <nativelink@tracemachina.com>",
     version,
     about,
-    long_about = None
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
 )]
 struct Args {
     /// Config file to use.
-    #[clap(value_parser)]
-    config_file: String,
+    #[clap(value_parser, required = true)]
+    config_file: Option<String>,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
 }
 
-async fn inner_main(
-    cfg: CasConfig,
-    server_start_timestamp: u64,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut root_metrics_registry = <Registry>::with_prefix("nativelink");
-    let health_registry_builder = Arc::new(AsyncMutex::new(HealthRegistryBuilder::new(
-        "nativelink".into(),
-    )));
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Checks the config file for missing stores and schedulers, ref_store
+    /// cycles and risky settings without opening any store or starting any
+    /// server. Fails if an error is found.
+    Validate {
+        /// Config file to check.
+        #[clap(value_parser)]
+        config_file: String,
+    },
+}
 
-    let store_manager = Arc::new(StoreManager::new());
-    {
-        let mut health_registry_lock = health_registry_builder.lock().await;
-        let root_store_metrics = root_metrics_registry.sub_registry_with_prefix("stores");
+/// Prints the findings of `validate()` for the config file with the line
+/// they were found on.
+fn validate_config(config_file: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let contents = std::fs::read_to_string(config_file)
+        .err_tip(|| format!("Could not open config file {config_file}"))?;
+    let cfg: CasConfig = serde_json5::from_str(&contents)
+        .map_err(|e| make_input_err!("Could not parse config file {config_file} : {e}"))?;
+    let findings = validate(&cfg);
+    for finding in &findings {
+        match finding.path.find_line(&contents) {
+            Some(line) => println!("{config_file}:{line}: {finding}"),
+            None => println!("{config_file}: {finding}"),
+        }
+    }
+    let errors = findings
+        .iter()
+        .filter(|finding| finding.severity == Severity::Error)
+        .count();
+    println!(
+        "{config_file}: {errors} errors, {} warnings",
+        findings.len() - errors
+    );
+    if errors > 0 {
+        return Err(make_input_err!("Config file {config_file} is not valid").into());
+    }
+    Ok(())
+}
 
-        for (name, store_cfg) in cfg.stores {
-            let health_component_name = format!("stores/{name}");
-            let mut health_register_store =
-                health_registry_lock.sub_builder(health_component_name.into());
-            let store_metrics = root_store_metrics.sub_registry_with_prefix(&name);
-            store_manager.add_store(
-                &name,
-                store_factory(
-                    &store_cfg,
-                    &store_manager,
-                    Some(store_metrics),
-                    Some(&mut health_register_store),
-                )
-                .await
-                .err_tip(|| format!("Failed to create store '{name}'"))?,
+/// Looks up the scheduler named `name` for the admin API.
+fn get_scheduler<T: ?Sized>(
+    schedulers: &HashMap<String, Arc<T>>,
+    name: &str,
+) -> Result<Arc<T>, Error> {
+    schedulers.get(name).cloned().ok_or_else(|| {
+        make_err!(
+            Code::NotFound,
+            "Can not get an instance with the name of '{name}'"
+        )
+    })
+}
+
+/// Parses an action name as returned by `ActionInfoHashKey::action_name()`.
+fn parse_action_name(action_name: &str) -> Result<ActionInfoHashKey, Error> {
+    ActionInfoHashKey::try_from(action_name.trim_start_matches('/'))
+        .map_err(|e| make_input_err!("Invalid action name '{action_name}' : {e:?}"))
+}
+
+fn admin_error_response(e: Error) -> (axum::http::StatusCode, String) {
+    let status = match e.code {
+        Code::NotFound => axum::http::StatusCode::NOT_FOUND,
+        Code::Unauthenticated => axum::http::StatusCode::UNAUTHORIZED,
+        Code::PermissionDenied => axum::http::StatusCode::FORBIDDEN,
+        Code::InvalidArgument => axum::http::StatusCode::BAD_REQUEST,
+        Code::FailedPrecondition => axum::http::StatusCode::CONFLICT,
+        _ => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, format!("Error: {e:?}"))
+}
+
+fn into_encoding(from: &CompressionAlgorithm) -> Option<CompressionEncoding> {
+    match from {
+        CompressionAlgorithm::gzip => Some(CompressionEncoding::Gzip),
+        CompressionAlgorithm::none => None,
+    }
+}
+
+/// Handle to replace the filter of the log messages.
+type LogFilterHandle = reload::Handle<EnvFilter, tracing_subscriber::Registry>;
+
+/// Creates the filter of the log messages, `log_level` overrides `RUST_LOG`
+/// if set.
+fn make_log_filter(log_level: &str) -> Result<EnvFilter, Error> {
+    let builder = EnvFilter::builder().with_default_directive(LevelFilter::WARN.into());
+    if log_level.is_empty() {
+        return Ok(builder.from_env_lossy());
+    }
+    builder
+        .parse(log_level)
+        .map_err(|e| make_input_err!("Invalid log_level '{log_level}' : {e:?}"))
+}
+
+fn set_log_level(log_filter: &LogFilterHandle, log_level: &str) -> Result<(), Error> {
+    log_filter
+        .reload(make_log_filter(log_level)?)
+        .map_err(|e| make_err!(Code::Internal, "Could not change the log level : {e:?}"))
+}
+
+/// Reads the config file. The config is also returned as JSON, which is
+/// compared to the reloaded config to find what changed.
+fn read_config(config_file: &str) -> Result<(CasConfig, serde_json::Value), Error> {
+    let json_contents = String::from_utf8(
+        std::fs::read(config_file)
+            .err_tip(|| format!("Could not open config file {config_file}"))?,
+    )
+    .map_err(|e| make_input_err!("Config file {config_file} is not valid UTF-8 : {e:?}"))?;
+    let cfg = serde_json5::from_str(&json_contents)
+        .map_err(|e| make_input_err!("Could not parse config file {config_file} : {e}"))?;
+    let cfg_json = serde_json5::from_str(&json_contents)
+        .map_err(|e| make_input_err!("Could not parse config file {config_file} : {e}"))?;
+    Ok((cfg, cfg_json))
+}
+
+/// Simple wrapper to enable us to register the Hashmap so it can
+/// report metrics about what clients are connected.
+struct ConnectedClientsMetrics {
+    inner: Mutex<HashSet<SocketAddr>>,
+    counter: Counter,
+    server_start_ts: u64,
+}
+impl MetricsComponent for ConnectedClientsMetrics {
+    fn gather_metrics(&self, c: &mut CollectorState) {
+        c.publish(
+            "server_start_time",
+            &self.server_start_ts,
+            "Timestamp when the server started",
+        );
+
+        let connected_clients = self.inner.lock();
+        for client in connected_clients.iter() {
+            c.publish_with_labels(
+                "connected_clients",
+                &1,
+                "The endpoint of the connected clients",
+                vec![("endpoint".into(), format!("{client}").into())],
             );
         }
+
+        c.publish(
+            "total_client_connections",
+            &self.counter,
+            "Total client connections since server started",
+        );
     }
+}
 
-    let mut action_schedulers = HashMap::new();
-    let mut worker_schedulers = HashMap::new();
-    if let Some(schedulers_cfg) = cfg.schedulers {
-        let root_scheduler_metrics = root_metrics_registry.sub_registry_with_prefix("schedulers");
-        for (name, scheduler_cfg) in schedulers_cfg {
-            let scheduler_metrics = root_scheduler_metrics.sub_registry_with_prefix(&name);
-            let (maybe_action_scheduler, maybe_worker_scheduler) =
-                scheduler_factory(&scheduler_cfg, &store_manager, scheduler_metrics)
-                    .err_tip(|| format!("Failed to create scheduler '{name}'"))?;
-            if let Some(action_scheduler) = maybe_action_scheduler {
-                action_schedulers.insert(name.clone(), action_scheduler);
+fn register_server_metrics(
+    registry: &mut Registry,
+    i: usize,
+    server_cfg: &ServerConfig,
+    server_start_timestamp: u64,
+) -> Arc<ConnectedClientsMetrics> {
+    let name = if server_cfg.name.is_empty() {
+        format!("{i}")
+    } else {
+        server_cfg.name.clone()
+    };
+    let connected_clients_mux = Arc::new(ConnectedClientsMetrics {
+        inner: Mutex::new(HashSet::new()),
+        counter: Counter::default(),
+        server_start_ts: server_start_timestamp,
+    });
+    let server_metrics = registry.sub_registry_with_prefix(format!("server_{name}"));
+    server_metrics.register_collector(Box::new(Collector::new(&connected_clients_mux)));
+    connected_clients_mux
+}
+
+/// Stores, schedulers and registries the services of the servers are
+/// created with. Stores and schedulers added by a config reload are only
+/// used by servers started after the reload.
+struct ServerContext {
+    store_manager: Arc<StoreManager>,
+    action_schedulers: HashMap<String, Arc<dyn ActionScheduler>>,
+    worker_schedulers: HashMap<String, Arc<dyn WorkerScheduler>>,
+    health_registry_builder: Arc<AsyncMutex<HealthRegistryBuilder>>,
+    root_metrics_registry: Arc<AsyncMutex<Registry>>,
+    /// Shared by all listeners, so the dashboard shows the hit rate of every
+    /// action cache service.
+    ac_cache_stats: Arc<AcCacheStats>,
+}
+
+/// Applies the changes of the config file to the running process, on
+/// `SIGHUP` or through the admin API.
+struct ConfigReloader {
+    config_file: String,
+    log_filter: LogFilterHandle,
+    server_start_timestamp: u64,
+    state: AsyncMutex<ReloadState>,
+}
+
+struct ReloadState {
+    /// Config the process runs with, as JSON. Each change is recorded as
+    /// soon as it was applied, so a reload after a failed reload only
+    /// applies the remaining changes.
+    config: serde_json::Value,
+    ctx: ServerContext,
+    /// Index used to name the metrics of the next server without a name.
+    next_server_index: usize,
+}
+
+impl ConfigReloader {
+    /// Re-reads the config file and applies the changes. Nothing is applied
+    /// if any of the changes needs a restart. Returns a description of each
+    /// applied change. If applying a change fails, the changes applied
+    /// before it stay in effect.
+    // Boxed, because the future of the admin route that calls this function
+    // is part of the future of `start_server()`.
+    fn reload(self: &Arc<Self>) -> BoxFuture<'_, Result<Vec<String>, Error>> {
+        Box::pin(async move {
+            let (mut cfg, cfg_json) = read_config(&self.config_file)?;
+            let mut state = self.state.lock().await;
+            let changes = ConfigChanges::new(&state.config, &cfg_json);
+            if !changes.restart_required.is_empty() {
+                return Err(make_err!(
+                    Code::FailedPrecondition,
+                    "Config was not reloaded, these changes need a restart: {}",
+                    changes.restart_required.join(", ")
+                ));
             }
-            if let Some(worker_scheduler) = maybe_worker_scheduler {
-                worker_schedulers.insert(name.clone(), worker_scheduler);
+
+            let ReloadState {
+                config,
+                ctx,
+                next_server_index,
+            } = &mut *state;
+            let mut applied = Vec::new();
+            if changes.log_level_changed {
+                let log_level = cfg.global.as_ref().map_or("", |global| &global.log_level);
+                set_log_level(&self.log_filter, log_level)?;
+                record_field_change(config, &cfg_json, "global", "log_level");
+                applied.push(format!("Set log level to '{log_level}'"));
             }
-        }
-    }
 
-    fn into_encoding(from: &CompressionAlgorithm) -> Option<CompressionEncoding> {
-        match from {
-            CompressionAlgorithm::gzip => Some(CompressionEncoding::Gzip),
-            CompressionAlgorithm::none => None,
-        }
-    }
+            for name in &changes.new_stores {
+                // The registries are only locked once the store was built, so
+                // the metrics and health of the running servers are not
+                // blocked while the store connects to its backends.
+                let store = store_factory(&cfg.stores[name], &ctx.store_manager, None, None)
+                    .await
+                    .err_tip(|| format!("Failed to create store '{name}'"))?;
+                store.clone().register_metrics(
+                    ctx.root_metrics_registry
+                        .lock()
+                        .await
+                        .sub_registry_with_prefix("stores")
+                        .sub_registry_with_prefix(name),
+                );
+                store.clone().register_health(
+                    &mut ctx
+                        .health_registry_builder
+                        .lock()
+                        .await
+                        .sub_builder(format!("stores/{name}").into()),
+                );
+                ctx.store_manager.add_store(name, store);
+                record_field_change(config, &cfg_json, "stores", name);
+                applied.push(format!("Added store '{name}'"));
+            }
 
-    /// Simple wrapper to enable us to register the Hashmap so it can
-    /// report metrics about what clients are connected.
-    struct ConnectedClientsMetrics {
-        inner: Mutex<HashSet<SocketAddr>>,
-        counter: Counter,
-        server_start_ts: u64,
-    }
-    impl MetricsComponent for ConnectedClientsMetrics {
-        fn gather_metrics(&self, c: &mut CollectorState) {
-            c.publish(
-                "server_start_time",
-                &self.server_start_ts,
-                "Timestamp when the server started",
-            );
+            for name in &changes.eviction_policy_changes {
+                let eviction_policy = match &cfg.stores[name] {
+                    StoreConfig::memory(config) => config.eviction_policy.clone(),
+                    StoreConfig::filesystem(config) => config.eviction_policy.clone(),
+                    _ => None,
+                };
+                ctx.store_manager
+                    .get_store(name)
+                    .err_tip(|| format!("Store '{name}' does not exist"))?
+                    .set_eviction_policy(&eviction_policy.unwrap_or_default())
+                    .await
+                    .err_tip(|| format!("Failed to change eviction policy of store '{name}'"))?;
+                record_field_change(config, &cfg_json, "stores", name);
+                applied.push(format!("Changed eviction policy of store '{name}'"));
+            }
+
+            let schedulers_cfg = cfg.schedulers.take().unwrap_or_default();
+            for name in &changes.new_schedulers {
+                let mut root_metrics_registry = ctx.root_metrics_registry.lock().await;
+                let scheduler_metrics = root_metrics_registry
+                    .sub_registry_with_prefix("schedulers")
+                    .sub_registry_with_prefix(name);
+                let mut health_registry_builder = ctx.health_registry_builder.lock().await;
+                let (maybe_action_scheduler, maybe_worker_scheduler) = scheduler_factory(
+                    &schedulers_cfg[name],
+                    &ctx.store_manager,
+                    scheduler_metrics,
+                    Some(
+                        &mut health_registry_builder
+                            .sub_builder(format!("schedulers/{name}").into()),
+                    ),
+                )
+                .err_tip(|| format!("Failed to create scheduler '{name}'"))?;
+                if let Some(action_scheduler) = maybe_action_scheduler {
+                    ctx.action_schedulers.insert(name.clone(), action_scheduler);
+                }
+                if let Some(worker_scheduler) = maybe_worker_scheduler {
+                    ctx.worker_schedulers.insert(name.clone(), worker_scheduler);
+                }
+                record_field_change(config, &cfg_json, "schedulers", name);
+                applied.push(format!("Added scheduler '{name}'"));
+            }
 
-            let connected_clients = self.inner.lock();
-            for client in connected_clients.iter() {
-                c.publish_with_labels(
-                    "connected_clients",
-                    &1,
-                    "The endpoint of the connected clients",
-                    vec![("endpoint".into(), format!("{client}").into())],
+            for name in &changes.platform_property_changes {
+                let SchedulerConfig::simple(simple_cfg) = &schedulers_cfg[name] else {
+                    return Err(make_input_err!(
+                        "Scheduler '{name}' is not a simple scheduler"
+                    ));
+                };
+                ctx.worker_schedulers
+                    .get(name)
+                    .err_tip(|| format!("Scheduler '{name}' does not exist"))?
+                    .set_supported_platform_properties(
+                        simple_cfg
+                            .supported_platform_properties
+                            .clone()
+                            .unwrap_or_default(),
+                    )
+                    .err_tip(|| format!("Failed to change platform properties of '{name}'"))?;
+                record_field_change(config, &cfg_json, "schedulers", name);
+                applied.push(format!("Changed platform properties of scheduler '{name}'"));
+            }
+
+            for (i, server_cfg) in cfg.servers.into_iter().enumerate() {
+                if !changes.new_servers.contains(&i) {
+                    continue;
+                }
+                // The indexes in the reloaded config may already be used by
+                // the running servers.
+                let connected_clients_mux = register_server_metrics(
+                    &mut *ctx.root_metrics_registry.lock().await,
+                    *next_server_index,
+                    &server_cfg,
+                    self.server_start_timestamp,
                 );
+                *next_server_index += 1;
+                let server_fut = start_server(server_cfg, connected_clients_mux, ctx, self)
+                    .await
+                    .map_err(|e| make_err!(Code::Internal, "Failed to start server {i} : {e}"))?;
+                tokio::spawn(async move {
+                    if let Err(e) = server_fut.await {
+                        error!("Server {i} failed : {e:?}");
+                    }
+                });
+                record_new_server(config, &cfg_json, i);
+                applied.push(format!("Started server {i}"));
             }
 
-            c.publish(
-                "total_client_connections",
-                &self.counter,
-                "Total client connections since server started",
-            );
-        }
+            *config = cfg_json;
+            Ok(applied)
+        })
     }
+}
 
-    // Registers all the ConnectedClientsMetrics to the registries
-    // and zips them in. It is done this way to get around the need
-    // for `root_metrics_registry` to become immutable in the loop.
-    let servers_and_clients: Vec<(ServerConfig, _)> = cfg
-        .servers
-        .into_iter()
-        .enumerate()
-        .map(|(i, server_cfg)| {
-            let name = if server_cfg.name.is_empty() {
-                format!("{i}")
-            } else {
-                server_cfg.name.clone()
-            };
-            let connected_clients_mux = Arc::new(ConnectedClientsMetrics {
-                inner: Mutex::new(HashSet::new()),
-                counter: Counter::default(),
-                server_start_ts: server_start_timestamp,
-            });
-            let server_metrics =
-                root_metrics_registry.sub_registry_with_prefix(format!("server_{name}"));
-            server_metrics.register_collector(Box::new(Collector::new(&connected_clients_mux)));
-
-            (server_cfg, connected_clients_mux)
+/// Starts serving the services of `server_cfg`. Returns the future that
+/// accepts the connections, it never resolves.
+async fn start_server(
+    server_cfg: ServerConfig,
+    connected_clients_mux: Arc<ConnectedClientsMetrics>,
+    ctx: &ServerContext,
+    config_reloader: &Arc<ConfigReloader>,
+) -> Result<BoxFuture<'static, Result<(), Error>>, Box<dyn std::error::Error>> {
+    let ServerContext {
+        store_manager,
+        action_schedulers,
+        worker_schedulers,
+        health_registry_builder,
+        root_metrics_registry,
+        ac_cache_stats,
+    } = ctx;
+    let services = server_cfg.services.ok_or("'services' must be configured")?;
+    // Must be computed before the cas config is consumed below.
+    let split_blob_instances: HashSet<InstanceName> = services
+        .cas
+        .iter()
+        .flatten()
+        .filter(|(_, cas_cfg)| {
+            store_manager
+                .get_store(&cas_cfg.cas_store)
+                .is_some_and(|store| supports_split_blob(store.as_ref()))
         })
+        .map(|(instance_name, _)| instance_name.clone())
+        .collect();
+    // Instances whose running actions' output this listener serves, only
+    // their operations advertise output streams.
+    let out_streams: HashSet<InstanceName> = services
+        .bytestream
+        .iter()
+        .flat_map(|bytestream_cfg| bytestream_cfg.action_output_schedulers.keys())
+        .cloned()
         .collect();
 
-    let mut root_futures: Vec<BoxFuture<Result<(), Error>>> = Vec::new();
+    // Currently we only support http as our socket type.
+    let ListenerConfig::http(http_config) = server_cfg.listener;
 
-    // Lock our registry as immutable and clonable.
-    let root_metrics_registry = Arc::new(AsyncMutex::new(root_metrics_registry));
-    for (server_cfg, connected_clients_mux) in servers_and_clients {
-        let services = server_cfg.services.ok_or("'services' must be configured")?;
-
-        // Currently we only support http as our socket type.
-        let ListenerConfig::http(http_config) = server_cfg.listener;
+    let health_registry_status = health_registry_builder.lock().await.build();
+    // Must be created before the configs of the other services are consumed.
+    // Health checks may reach remote backends, so they run on one interval
+    // no matter how often clients ask for the health.
+    let cached_health_registry =
+        CachedHealthRegistry::new(health_registry_status.clone(), HEALTH_CHECK_INTERVAL);
+    let health_server = HealthServer::new(&services, &cached_health_registry);
+    let maybe_dashboard = services.dashboard.as_ref().map(|dashboard_config| {
+        let path = if dashboard_config.path.is_empty() {
+            DEFAULT_DASHBOARD_PATH.to_string()
+        } else {
+            dashboard_config.path.clone()
+        };
+        let dashboard = Dashboard::new(
+            action_schedulers.clone(),
+            worker_schedulers.clone(),
+            ac_cache_stats.clone(),
+            cached_health_registry.clone(),
+        );
+        (path, Arc::new(dashboard))
+    });
+    let maybe_browser = services
+        .browser
+        .as_ref()
+        .map(|browser_config| {
+            let path = if browser_config.path.is_empty() {
+                DEFAULT_BROWSER_PATH
+            } else {
+                &browser_config.path
+            };
+            BlobBrowser::new(browser_config, path, store_manager)
+                .map(|browser| (path.to_string(), Arc::new(browser)))
+        })
+        .transpose()
+        .err_tip(|| "Could not create browser")?;
+    let maybe_reflection_service =
+        if services.reflection {
+            let mut reflection_builder = tonic_reflection::server::Builder::configure()
+                .register_encoded_file_descriptor_set(nativelink_proto::FILE_DESCRIPTOR_SET)
+                .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET);
+            // Only advertise the services this listener actually serves.
+            for service_name in health_server.service_names() {
+                reflection_builder = reflection_builder.with_service_name(service_name);
+            }
+            Some(reflection_builder.build().map_err(|e| {
+                make_err!(Code::Internal, "Could not create reflection service: {e:?}")
+            })?)
+        } else {
+            None
+        };
 
-        let tonic_services = TonicServer::builder()
-            .add_optional_service(
-                services
-                    .ac
-                    .map_or(Ok(None), |cfg| {
-                        AcServer::new(&cfg, &store_manager).map(|v| {
-                            let mut service = v.into_service();
-                            let send_algo = &http_config.compression.send_compression_algorithm;
-                            if let Some(encoding) =
-                                into_encoding(&send_algo.unwrap_or(CompressionAlgorithm::none))
-                            {
-                                service = service.send_compressed(encoding);
-                            }
-                            for encoding in http_config
-                                .compression
-                                .accepted_compression_algorithms
-                                .iter()
-                                // Filter None values.
-                                .filter_map(into_encoding)
-                            {
-                                service = service.accept_compressed(encoding);
-                            }
-                            Some(service)
-                        })
-                    })
-                    .err_tip(|| "Could not create AC service")?,
-            )
-            .add_optional_service(
-                services
-                    .cas
-                    .map_or(Ok(None), |cfg| {
-                        CasServer::new(&cfg, &store_manager).map(|v| {
+    let tonic_services = TonicServer::builder()
+        .add_service(health_server.into_service())
+        .add_optional_service(maybe_reflection_service)
+        .add_optional_service(
+            services
+                .ac
+                .map_or(Ok(None), |cfg| {
+                    AcServer::new_with_cache_stats(&cfg, store_manager, ac_cache_stats.clone()).map(
+                        |v| {
                             let mut service = v.into_service();
                             let send_algo = &http_config.compression.send_compression_algorithm;
                             if let Some(encoding) =
@@ -275,82 +607,44 @@ async fn inner_main(
                                 service = service.accept_compressed(encoding);
                             }
                             Some(service)
-                        })
-                    })
-                    .err_tip(|| "Could not create CAS service")?,
-            )
-            .add_optional_service(
-                services
-                    .execution
-                    .map_or(Ok(None), |cfg| {
-                        ExecutionServer::new(&cfg, &action_schedulers, &store_manager).map(|v| {
-                            let mut service = v.into_service();
-                            let send_algo = &http_config.compression.send_compression_algorithm;
-                            if let Some(encoding) =
-                                into_encoding(&send_algo.unwrap_or(CompressionAlgorithm::none))
-                            {
-                                service = service.send_compressed(encoding);
-                            }
-                            for encoding in http_config
-                                .compression
-                                .accepted_compression_algorithms
-                                .iter()
-                                // Filter None values.
-                                .filter_map(into_encoding)
-                            {
-                                service = service.accept_compressed(encoding);
-                            }
-                            Some(service)
-                        })
-                    })
-                    .err_tip(|| "Could not create Execution service")?,
-            )
-            .add_optional_service(
-                services
-                    .bytestream
-                    .map_or(Ok(None), |cfg| {
-                        ByteStreamServer::new(&cfg, &store_manager).map(|v| {
-                            let mut service = v.into_service();
-                            let send_algo = &http_config.compression.send_compression_algorithm;
-                            if let Some(encoding) =
-                                into_encoding(&send_algo.unwrap_or(CompressionAlgorithm::none))
-                            {
-                                service = service.send_compressed(encoding);
-                            }
-                            for encoding in http_config
-                                .compression
-                                .accepted_compression_algorithms
-                                .iter()
-                                // Filter None values.
-                                .filter_map(into_encoding)
-                            {
-                                service = service.accept_compressed(encoding);
-                            }
-                            Some(service)
-                        })
+                        },
+                    )
+                })
+                .err_tip(|| "Could not create AC service")?,
+        )
+        .add_optional_service(
+            services
+                .cas
+                .map_or(Ok(None), |cfg| {
+                    CasServer::new(&cfg, store_manager).map(|v| {
+                        let mut service = v.into_service();
+                        let send_algo = &http_config.compression.send_compression_algorithm;
+                        if let Some(encoding) =
+                            into_encoding(&send_algo.unwrap_or(CompressionAlgorithm::none))
+                        {
+                            service = service.send_compressed(encoding);
+                        }
+                        for encoding in http_config
+                            .compression
+                            .accepted_compression_algorithms
+                            .iter()
+                            // Filter None values.
+                            .filter_map(into_encoding)
+                        {
+                            service = service.accept_compressed(encoding);
+                        }
+                        Some(service)
                     })
-                    .err_tip(|| "Could not create ByteStream service")?,
-            )
-            .add_optional_service(
-                OptionFuture::from(
-                    services
-                        .capabilities
-                        .as_ref()
-                        // Borrow checker fighting here...
-                        .map(|_| {
-                            CapabilitiesServer::new(
-                                services.capabilities.as_ref().unwrap(),
-                                &action_schedulers,
-                            )
-                        }),
-                )
-                .await
-                .map_or(Ok::<Option<CapabilitiesServer>, Error>(None), |server| {
-                    Ok(Some(server?))
                 })
-                .err_tip(|| "Could not create Capabilities service")?
-                .map(|v| {
-                    let mut service = v.into_service();
+                .err_tip(|| "Could not create CAS service")?,
+        )
+        .add_optional_service(
+            services
+                .execution
+                .map_or(Ok::<_, Error>(None), |cfg| {
+                    let execution_server =
+                        ExecutionServer::new(&cfg, action_schedulers, store_manager, &out_streams)?;
+                    let mut service = execution_server.into_service();
                     let send_algo = &http_config.compression.send_compression_algorithm;
                     if let Some(encoding) =
                         into_encoding(&send_algo.unwrap_or(CompressionAlgorithm::none))
@@ -366,160 +660,384 @@ async fn inner_main(
                     {
                         service = service.accept_compressed(encoding);
                     }
-                    service
-                }),
-            )
-            .add_optional_service(
+                    Ok(Some(service))
+                })
+                .err_tip(|| "Could not create Execution service")?,
+        )
+        .add_optional_service(
+            services
+                .operations
+                .map_or(Ok(None), |cfg| {
+                    OperationsServer::new(&cfg, action_schedulers, &out_streams).map(|v| {
+                        let mut service = v.into_service();
+                        let send_algo = &http_config.compression.send_compression_algorithm;
+                        if let Some(encoding) =
+                            into_encoding(&send_algo.unwrap_or(CompressionAlgorithm::none))
+                        {
+                            service = service.send_compressed(encoding);
+                        }
+                        for encoding in http_config
+                            .compression
+                            .accepted_compression_algorithms
+                            .iter()
+                            // Filter None values.
+                            .filter_map(into_encoding)
+                        {
+                            service = service.accept_compressed(encoding);
+                        }
+                        Some(service)
+                    })
+                })
+                .err_tip(|| "Could not create Operations service")?,
+        )
+        .add_optional_service(
+            services
+                .bytestream
+                .map_or(Ok(None), |cfg| {
+                    ByteStreamServer::new(&cfg, store_manager, action_schedulers).map(|v| {
+                        let mut service = v.into_service();
+                        let send_algo = &http_config.compression.send_compression_algorithm;
+                        if let Some(encoding) =
+                            into_encoding(&send_algo.unwrap_or(CompressionAlgorithm::none))
+                        {
+                            service = service.send_compressed(encoding);
+                        }
+                        for encoding in http_config
+                            .compression
+                            .accepted_compression_algorithms
+                            .iter()
+                            // Filter None values.
+                            .filter_map(into_encoding)
+                        {
+                            service = service.accept_compressed(encoding);
+                        }
+                        Some(service)
+                    })
+                })
+                .err_tip(|| "Could not create ByteStream service")?,
+        )
+        .add_optional_service(
+            OptionFuture::from(
                 services
-                    .worker_api
-                    .map_or(Ok(None), |cfg| {
-                        WorkerApiServer::new(&cfg, &worker_schedulers).map(|v| {
-                            let mut service = v.into_service();
-                            let send_algo = &http_config.compression.send_compression_algorithm;
-                            if let Some(encoding) =
-                                into_encoding(&send_algo.unwrap_or(CompressionAlgorithm::none))
-                            {
-                                service = service.send_compressed(encoding);
-                            }
-                            for encoding in http_config
-                                .compression
-                                .accepted_compression_algorithms
-                                .iter()
-                                // Filter None values.
-                                .filter_map(into_encoding)
-                            {
-                                service = service.accept_compressed(encoding);
-                            }
-                            Some(service)
-                        })
+                    .capabilities
+                    .as_ref()
+                    // Borrow checker fighting here...
+                    .map(|_| {
+                        CapabilitiesServer::new(
+                            services.capabilities.as_ref().unwrap(),
+                            action_schedulers,
+                            split_blob_instances,
+                        )
+                    }),
+            )
+            .await
+            .map_or(Ok::<Option<CapabilitiesServer>, Error>(None), |server| {
+                Ok(Some(server?))
+            })
+            .err_tip(|| "Could not create Capabilities service")?
+            .map(|v| {
+                let mut service = v.into_service();
+                let send_algo = &http_config.compression.send_compression_algorithm;
+                if let Some(encoding) =
+                    into_encoding(&send_algo.unwrap_or(CompressionAlgorithm::none))
+                {
+                    service = service.send_compressed(encoding);
+                }
+                for encoding in http_config
+                    .compression
+                    .accepted_compression_algorithms
+                    .iter()
+                    // Filter None values.
+                    .filter_map(into_encoding)
+                {
+                    service = service.accept_compressed(encoding);
+                }
+                service
+            }),
+        )
+        .add_optional_service(
+            services
+                .worker_api
+                .map_or(Ok(None), |cfg| {
+                    WorkerApiServer::new(&cfg, worker_schedulers).map(|v| {
+                        let mut service = v.into_service();
+                        let send_algo = &http_config.compression.send_compression_algorithm;
+                        if let Some(encoding) =
+                            into_encoding(&send_algo.unwrap_or(CompressionAlgorithm::none))
+                        {
+                            service = service.send_compressed(encoding);
+                        }
+                        for encoding in http_config
+                            .compression
+                            .accepted_compression_algorithms
+                            .iter()
+                            // Filter None values.
+                            .filter_map(into_encoding)
+                        {
+                            service = service.accept_compressed(encoding);
+                        }
+                        Some(service)
                     })
-                    .err_tip(|| "Could not create WorkerApi service")?,
-            );
-
-        let root_metrics_registry = root_metrics_registry.clone();
-        let health_registry_status = health_registry_builder.lock().await.build();
-
-        let mut svc = Router::new()
-            // This is the default service that executes if no other endpoint matches.
-            .fallback_service(tonic_services.into_service().map_err(|e| panic!("{e}")))
-            .route_service(
-                "/status",
-                axum::routing::get(move || async move {
-                    fn error_to_response<E: std::error::Error>(e: E) -> Response<String> {
-                        let mut response = Response::new(format!("Error: {e:?}"));
-                        *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
-                        response
-                    }
+                })
+                .err_tip(|| "Could not create WorkerApi service")?,
+        );
 
-                    spawn_blocking(move || {
-                        futures::executor::block_on(async {
-                            let health_status_descriptions: Vec<HealthStatusDescription> =
-                                health_registry_status
-                                    .health_status_report()
-                                    .collect()
-                                    .await;
+    let root_metrics_registry = root_metrics_registry.clone();
 
-                            match serde_json5::to_string(&health_status_descriptions) {
-                                Ok(body) => {
-                                    let contains_failed_report =
-                                        health_status_descriptions.iter().any(|description| {
-                                            matches!(
-                                                description.status,
-                                                HealthStatus::Failed { .. }
-                                            )
-                                        });
-                                    let status_code = if contains_failed_report {
-                                        StatusCode::SERVICE_UNAVAILABLE
-                                    } else {
-                                        StatusCode::OK
-                                    };
-                                    Response::builder()
-                                        .status(status_code)
-                                        .header(
-                                            hyper::header::CONTENT_TYPE,
-                                            hyper::header::HeaderValue::from_static(
-                                                JSON_CONTENT_TYPE,
-                                            ),
-                                        )
-                                        .body(body)
-                                        .unwrap()
-                                }
-                                Err(e) => Response::builder()
-                                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+    let mut svc = Router::new()
+        // This is the default service that executes if no other endpoint matches.
+        .fallback_service(tonic_services.into_service().map_err(|e| panic!("{e}")))
+        .route_service(
+            "/status",
+            axum::routing::get(move || async move {
+                fn error_to_response<E: std::error::Error>(e: E) -> Response<String> {
+                    let mut response = Response::new(format!("Error: {e:?}"));
+                    *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                    response
+                }
+
+                spawn_blocking(move || {
+                    futures::executor::block_on(async {
+                        let health_status_descriptions: Vec<HealthStatusDescription> =
+                            health_registry_status
+                                .health_status_report()
+                                .collect()
+                                .await;
+
+                        match serde_json5::to_string(&health_status_descriptions) {
+                            Ok(body) => {
+                                let contains_failed_report =
+                                    health_status_descriptions.iter().any(|description| {
+                                        matches!(description.status, HealthStatus::Failed { .. })
+                                    });
+                                let status_code = if contains_failed_report {
+                                    StatusCode::SERVICE_UNAVAILABLE
+                                } else {
+                                    StatusCode::OK
+                                };
+                                Response::builder()
+                                    .status(status_code)
                                     .header(
                                         hyper::header::CONTENT_TYPE,
                                         hyper::header::HeaderValue::from_static(JSON_CONTENT_TYPE),
                                     )
-                                    .body(format!("Internal Failure: {e:?}"))
-                                    .unwrap(),
+                                    .body(body)
+                                    .unwrap()
                             }
-                        })
+                            Err(e) => Response::builder()
+                                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                                .header(
+                                    hyper::header::CONTENT_TYPE,
+                                    hyper::header::HeaderValue::from_static(JSON_CONTENT_TYPE),
+                                )
+                                .body(format!("Internal Failure: {e:?}"))
+                                .unwrap(),
+                        }
                     })
-                    .await
-                    .unwrap_or_else(error_to_response)
-                }),
-            );
+                })
+                .await
+                .unwrap_or_else(error_to_response)
+            }),
+        );
 
-        if let Some(prometheus_cfg) = services.experimental_prometheus {
-            fn error_to_response<E: std::error::Error>(e: E) -> Response<String> {
-                let mut response = Response::new(format!("Error: {e:?}"));
-                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
-                response
-            }
-            let path = if prometheus_cfg.path.is_empty() {
-                DEFAULT_PROMETHEUS_METRICS_PATH
-            } else {
-                &prometheus_cfg.path
-            };
-            svc = svc.route_service(
-                path,
-                axum::routing::get(move |_request: hyper::Request<hyper::Body>| async move {
-                    // We spawn on a thread that can block to give more freedom to our metrics
-                    // collection. This allows it to call functions like `tokio::block_in_place`
-                    // if it needs to wait on a future.
-                    spawn_blocking(move || {
-                        let mut buf = String::new();
-                        let root_metrics_registry_guard =
-                            futures::executor::block_on(root_metrics_registry.lock());
-                        prometheus_client::encoding::text::encode(
-                            &mut buf,
-                            &root_metrics_registry_guard,
-                        )
-                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
-                        .map(|_| {
-                            // This is a hack to get around this bug: https://github.com/prometheus/client_rust/issues/155
-                            buf = buf.replace("nativelink_nativelink_stores_", "");
-                            buf = buf.replace("nativelink_nativelink_workers_", "");
-                            let mut response = Response::new(buf);
-                            // Per spec we should probably use `application/openmetrics-text; version=1.0.0; charset=utf-8`
-                            // https://github.com/OpenObservability/OpenMetrics/blob/1386544931307dff279688f332890c31b6c5de36/specification/OpenMetrics.md#overall-structure
-                            // However, this makes debugging more difficult, so we use the old text/plain instead.
-                            response.headers_mut().insert(
-                                hyper::header::CONTENT_TYPE,
-                                hyper::header::HeaderValue::from_static(
-                                    "text/plain; version=0.0.4; charset=utf-8",
-                                ),
-                            );
-                            response
-                        })
-                        .unwrap_or_else(error_to_response)
+    if let Some(prometheus_cfg) = services.experimental_prometheus {
+        fn error_to_response<E: std::error::Error>(e: E) -> Response<String> {
+            let mut response = Response::new(format!("Error: {e:?}"));
+            *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+            response
+        }
+        let path = if prometheus_cfg.path.is_empty() {
+            DEFAULT_PROMETHEUS_METRICS_PATH
+        } else {
+            &prometheus_cfg.path
+        };
+        svc = svc.route_service(
+            path,
+            axum::routing::get(move |_request: hyper::Request<hyper::Body>| async move {
+                // We spawn on a thread that can block to give more freedom to our metrics
+                // collection. This allows it to call functions like `tokio::block_in_place`
+                // if it needs to wait on a future.
+                spawn_blocking(move || {
+                    let mut buf = String::new();
+                    let root_metrics_registry_guard =
+                        futures::executor::block_on(root_metrics_registry.lock());
+                    prometheus_client::encoding::text::encode(
+                        &mut buf,
+                        &root_metrics_registry_guard,
+                    )
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+                    .map(|_| {
+                        // This is a hack to get around this bug: https://github.com/prometheus/client_rust/issues/155
+                        buf = buf.replace("nativelink_nativelink_stores_", "");
+                        buf = buf.replace("nativelink_nativelink_workers_", "");
+                        let mut response = Response::new(buf);
+                        // Per spec we should probably use `application/openmetrics-text; version=1.0.0; charset=utf-8`
+                        // https://github.com/OpenObservability/OpenMetrics/blob/1386544931307dff279688f332890c31b6c5de36/specification/OpenMetrics.md#overall-structure
+                        // However, this makes debugging more difficult, so we use the old text/plain instead.
+                        response.headers_mut().insert(
+                            hyper::header::CONTENT_TYPE,
+                            hyper::header::HeaderValue::from_static(
+                                "text/plain; version=0.0.4; charset=utf-8",
+                            ),
+                        );
+                        response
                     })
-                    .await
                     .unwrap_or_else(error_to_response)
-                }),
-            )
-        }
+                })
+                .await
+                .unwrap_or_else(error_to_response)
+            }),
+        )
+    }
 
-        if let Some(admin_config) = services.admin {
-            let path = if admin_config.path.is_empty() {
-                DEFAULT_ADMIN_API_PATH
-            } else {
-                &admin_config.path
-            };
-            let worker_schedulers = Arc::new(worker_schedulers.clone());
-            svc = svc.nest_service(
-                path,
-                Router::new().route(
+    if let Some(admin_config) = services.admin {
+        let path = if admin_config.path.is_empty() {
+            DEFAULT_ADMIN_API_PATH
+        } else {
+            &admin_config.path
+        };
+        let worker_schedulers = Arc::new(worker_schedulers.clone());
+        let action_schedulers = Arc::new(action_schedulers.clone());
+        let store_manager = store_manager.clone();
+        let config_reloader = config_reloader.clone();
+        svc = svc.nest_service(
+            path,
+            Router::new()
+                .route(
+                    "/scheduler/:instance_name/workers",
+                    axum::routing::get({
+                        let worker_schedulers = worker_schedulers.clone();
+                        move |params: axum::extract::Path<String>| async move {
+                            let instance_name = params.0;
+                            (async move {
+                                get_scheduler(&worker_schedulers, &instance_name)?
+                                    .list_workers()
+                                    .await
+                            })
+                            .await
+                            .map(axum::Json)
+                            .map_err(admin_error_response)
+                        }
+                    }),
+                )
+                // Removes the worker from the pool and requeues its running actions.
+                .route(
+                    "/scheduler/:instance_name/evict_worker/:worker_id",
+                    axum::routing::post({
+                        let worker_schedulers = worker_schedulers.clone();
+                        move |params: axum::extract::Path<(String, String)>| async move {
+                            let (instance_name, worker_id) = params.0;
+                            (async move {
+                                let worker_scheduler =
+                                    get_scheduler(&worker_schedulers, &instance_name)?;
+                                let worker_exists = worker_scheduler
+                                    .list_workers()
+                                    .await?
+                                    .iter()
+                                    .any(|worker| worker.worker_id == worker_id);
+                                if !worker_exists {
+                                    return Err(make_err!(
+                                        Code::NotFound,
+                                        "Worker {worker_id} does not exist"
+                                    ));
+                                }
+                                worker_scheduler
+                                    .remove_worker(WorkerId::try_from(worker_id.clone())?)
+                                    .await;
+                                Ok(format!("Evicted worker {worker_id}"))
+                            })
+                            .await
+                            .map_err(admin_error_response)
+                        }
+                    }),
+                )
+                .route(
+                    "/scheduler/:instance_name/actions",
+                    axum::routing::get({
+                        let action_schedulers = action_schedulers.clone();
+                        move |params: axum::extract::Path<String>| async move {
+                            let instance_name = params.0;
+                            (async move {
+                                get_scheduler(&action_schedulers, &instance_name)?
+                                    .list_scheduled_actions()
+                                    .await
+                            })
+                            .await
+                            .map(axum::Json)
+                            .map_err(admin_error_response)
+                        }
+                    }),
+                )
+                .route(
+                    "/scheduler/:instance_name/completed_actions",
+                    axum::routing::get({
+                        let action_schedulers = action_schedulers.clone();
+                        move |params: axum::extract::Path<String>| async move {
+                            let instance_name = params.0;
+                            (async move {
+                                get_scheduler(&action_schedulers, &instance_name)?
+                                    .list_completed_actions()
+                                    .await
+                            })
+                            .await
+                            .map(axum::Json)
+                            .map_err(admin_error_response)
+                        }
+                    }),
+                )
+                // The action name is the one listed by the `actions` route.
+                .route(
+                    "/scheduler/:instance_name/cancel_action/*action_name",
+                    axum::routing::post({
+                        let action_schedulers = action_schedulers.clone();
+                        move |params: axum::extract::Path<(String, String)>| async move {
+                            let (instance_name, action_name) = params.0;
+                            (async move {
+                                let unique_qualifier = parse_action_name(&action_name)?;
+                                let cancelled = get_scheduler(&action_schedulers, &instance_name)?
+                                    .cancel_action(&unique_qualifier)
+                                    .await?;
+                                if !cancelled {
+                                    return Err(make_err!(
+                                        Code::NotFound,
+                                        "Action {action_name} is not queued or executing"
+                                    ));
+                                }
+                                Ok(format!("Cancelled action {action_name}"))
+                            })
+                            .await
+                            .map_err(admin_error_response)
+                        }
+                    }),
+                )
+                // Only actions that are still queued can be reprioritized.
+                .route(
+                    "/scheduler/:instance_name/set_action_priority/:priority/*action_name",
+                    axum::routing::post({
+                        let action_schedulers = action_schedulers.clone();
+                        move |params: axum::extract::Path<(String, i32, String)>| async move {
+                            let (instance_name, priority, action_name) = params.0;
+                            (async move {
+                                let unique_qualifier = parse_action_name(&action_name)?;
+                                let updated = get_scheduler(&action_schedulers, &instance_name)?
+                                    .set_action_priority(&unique_qualifier, priority)
+                                    .await?;
+                                if !updated {
+                                    return Err(make_err!(
+                                        Code::NotFound,
+                                        "Action {action_name} is not queued"
+                                    ));
+                                }
+                                Ok(format!(
+                                    "Set priority of action {action_name} to {priority}"
+                                ))
+                            })
+                            .await
+                            .map_err(admin_error_response)
+                        }
+                    }),
+                )
+                .route(
                     "/scheduler/:instance_name/set_drain_worker/:worker_id/:is_draining",
                     axum::routing::post(
                         move |params: axum::extract::Path<(String, String, String)>| async move {
@@ -561,184 +1079,488 @@ async fn inner_main(
                             })
                         },
                     ),
+                )
+                .route(
+                    "/stores/:store_name/quota",
+                    axum::routing::get({
+                        let store_manager = store_manager.clone();
+                        move |params: axum::extract::Path<String>| async move {
+                            let store_name = params.0;
+                            (|| {
+                                let store =
+                                    store_manager.get_store(&store_name).ok_or_else(|| {
+                                        make_err!(
+                                            Code::NotFound,
+                                            "Can not get a store with the name of '{store_name}'"
+                                        )
+                                    })?;
+                                let quota_store = store
+                                    .as_any()
+                                    .downcast_ref::<QuotaStore>()
+                                    .ok_or_else(|| {
+                                        make_input_err!("Store '{store_name}' is not a quota store")
+                                    })?;
+                                Ok::<_, Error>(quota_store.usage())
+                            })()
+                            .map(axum::Json)
+                            .map_err(admin_error_response)
+                        }
+                    }),
+                )
+                // Pins the digests in the body, one `{hash}/{size}` per line.
+                .route(
+                    "/stores/:store_name/pin/:ttl_seconds",
+                    axum::routing::post(
+                        move |params: axum::extract::Path<(String, u64)>, body: String| async move {
+                            let (store_name, ttl_seconds) = params.0;
+                            (async move {
+                                let store =
+                                    store_manager.get_store(&store_name).ok_or_else(|| {
+                                        make_err!(
+                                            Code::NotFound,
+                                            "Can not get a store with the name of '{store_name}'"
+                                        )
+                                    })?;
+                                let digests = body
+                                    .lines()
+                                    .map(str::trim)
+                                    .filter(|line| !line.is_empty())
+                                    .map(|line| {
+                                        let (hash, size) = line.split_once('/').ok_or_else(|| {
+                                            make_input_err!(
+                                                "Expected '{{hash}}/{{size}}', got '{line}'"
+                                            )
+                                        })?;
+                                        let size = size.parse::<usize>().map_err(|e| {
+                                            make_input_err!("Invalid size in '{line}' : {e:?}")
+                                        })?;
+                                        DigestInfo::try_new(hash, size)
+                                    })
+                                    .collect::<Result<Vec<_>, Error>>()?;
+                                let pinned = Pin::new(store.as_ref())
+                                    .pin(&digests, Duration::from_secs(ttl_seconds))
+                                    .await?;
+                                Ok::<_, Error>(format!(
+                                    "Pinned {pinned} of {} digests in '{store_name}' for {ttl_seconds}s",
+                                    digests.len()
+                                ))
+                            })
+                            .await
+                            .map_err(admin_error_response)
+                        },
+                    ),
+                )
+                .route(
+                    "/reload_config",
+                    axum::routing::post(move || async move {
+                        config_reloader
+                            .reload()
+                            .await
+                            .map(axum::Json)
+                            .map_err(admin_error_response)
+                    }),
+                )
+                .layer(axum::middleware::from_fn(require_admin)),
+        )
+    }
+
+    if let Some((path, dashboard)) = maybe_dashboard {
+        svc = svc.nest_service(
+            &path,
+            Router::new()
+                .route(
+                    "/",
+                    axum::routing::get(|| async { axum::response::Html(DASHBOARD_HTML) }),
+                )
+                .route(
+                    "/state.json",
+                    axum::routing::get(move || async move { axum::Json(dashboard.state().await) }),
+                )
+                .layer(axum::middleware::from_fn(require_admin)),
+        )
+    }
+
+    if let Some((path, browser)) = maybe_browser {
+        svc = svc.nest_service(
+            &path,
+            Router::new().route(
+                "/*path",
+                axum::routing::get(
+                    move |axum::extract::Path(path): axum::extract::Path<String>,
+                          auth_context: Option<axum::Extension<AuthContext>>| async move {
+                        let auth_context = auth_context.map(|axum::Extension(context)| context);
+                        match browser.handle(&path, auth_context.as_ref()).await {
+                            Ok(BlobBrowserResponse::Page(html)) => {
+                                axum::response::Html(html).into_response()
+                            }
+                            Ok(BlobBrowserResponse::File { name, size, reader }) => {
+                                // An empty chunk marks the end of the file.
+                                let chunks =
+                                    futures::stream::unfold(reader, |mut reader| async move {
+                                        match reader.recv().await {
+                                            Ok(chunk) if chunk.is_empty() => None,
+                                            result => Some((result, reader)),
+                                        }
+                                    });
+                                (
+                                    [
+                                        (
+                                            axum::http::header::CONTENT_TYPE,
+                                            "application/octet-stream".to_string(),
+                                        ),
+                                        (
+                                            axum::http::header::CONTENT_DISPOSITION,
+                                            format!(
+                                                "attachment; filename=\"{}\"",
+                                                name.replace(['"', '\\'], "_")
+                                            ),
+                                        ),
+                                        (axum::http::header::CONTENT_LENGTH, size.to_string()),
+                                    ],
+                                    axum::body::StreamBody::new(chunks),
+                                )
+                                    .into_response()
+                            }
+                            Err(e) => admin_error_response(e).into_response(),
+                        }
+                    },
                 ),
-            )
-        }
+            ),
+        )
+    }
 
-        // Configure our TLS acceptor if we have TLS configured.
-        let maybe_tls_acceptor = http_config.tls.map_or(Ok(None), |tls_config| {
-            fn read_cert(cert_file: &str) -> Result<Vec<CertificateDer<'static>>, Error> {
-                let mut cert_reader = std::io::BufReader::new(
-                    std::fs::File::open(cert_file)
-                        .err_tip(|| format!("Could not open cert file {cert_file}"))?,
-                );
-                let certs = extract_certs(&mut cert_reader)
-                    .map(|certificate| certificate.map(CertificateDer::from))
-                    .collect::<Result<Vec<CertificateDer<'_>>, _>>()
-                    .err_tip(|| format!("Could not extract certs from file {cert_file}"))?;
-                Ok(certs)
-            }
-            let certs = read_cert(&tls_config.cert_file)?;
-            let mut key_reader = std::io::BufReader::new(
-                std::fs::File::open(&tls_config.key_file)
-                    .err_tip(|| format!("Could not open key file {}", tls_config.key_file))?,
+    // Configure our TLS acceptor if we have TLS configured.
+    let maybe_tls_acceptor = http_config.tls.map_or(Ok(None), |tls_config| {
+        fn read_cert(cert_file: &str) -> Result<Vec<CertificateDer<'static>>, Error> {
+            let mut cert_reader = std::io::BufReader::new(
+                std::fs::File::open(cert_file)
+                    .err_tip(|| format!("Could not open cert file {cert_file}"))?,
             );
-            let key = match rustls_pemfile::read_one(&mut key_reader)
-                .err_tip(|| format!("Could not extract key(s) from file {}", tls_config.key_file))?
-            {
-                Some(rustls_pemfile::Item::Pkcs8Key(key)) => key.into(),
-                Some(rustls_pemfile::Item::Sec1Key(key)) => key.into(),
-                Some(rustls_pemfile::Item::Pkcs1Key(key)) => key.into(),
-                _ => {
-                    return Err(make_err!(
-                        Code::Internal,
-                        "No keys found in file {}",
-                        tls_config.key_file
-                    ))
-                }
-            };
-            if let Ok(Some(_)) = rustls_pemfile::read_one(&mut key_reader) {
+            let certs = extract_certs(&mut cert_reader)
+                .map(|certificate| certificate.map(CertificateDer::from))
+                .collect::<Result<Vec<CertificateDer<'_>>, _>>()
+                .err_tip(|| format!("Could not extract certs from file {cert_file}"))?;
+            Ok(certs)
+        }
+        let certs = read_cert(&tls_config.cert_file)?;
+        let mut key_reader = std::io::BufReader::new(
+            std::fs::File::open(&tls_config.key_file)
+                .err_tip(|| format!("Could not open key file {}", tls_config.key_file))?,
+        );
+        let key = match rustls_pemfile::read_one(&mut key_reader)
+            .err_tip(|| format!("Could not extract key(s) from file {}", tls_config.key_file))?
+        {
+            Some(rustls_pemfile::Item::Pkcs8Key(key)) => key.into(),
+            Some(rustls_pemfile::Item::Sec1Key(key)) => key.into(),
+            Some(rustls_pemfile::Item::Pkcs1Key(key)) => key.into(),
+            _ => {
                 return Err(make_err!(
-                    Code::InvalidArgument,
-                    "Expected 1 key in file {}",
+                    Code::Internal,
+                    "No keys found in file {}",
                     tls_config.key_file
-                ));
+                ))
             }
-            let verifier = if let Some(client_ca_file) = &tls_config.client_ca_file {
-                let mut client_auth_roots = RootCertStore::empty();
-                for cert in read_cert(client_ca_file)?.into_iter() {
-                    client_auth_roots.add(cert).map_err(|e| {
-                        make_err!(Code::Internal, "Could not read client CA: {e:?}")
-                    })?;
-                }
-                let crls = if let Some(client_crl_file) = &tls_config.client_crl_file {
-                    let mut crl_reader = std::io::BufReader::new(
-                        std::fs::File::open(client_crl_file)
-                            .err_tip(|| format!("Could not open CRL file {client_crl_file}"))?,
-                    );
-                    extract_crls(&mut crl_reader)
-                        .map(|crl| crl.map(CertificateRevocationListDer::from))
-                        .collect::<Result<_, _>>()
-                        .err_tip(|| format!("Could not extract CRLs from file {client_crl_file}"))?
-                } else {
-                    Vec::new()
-                };
-                WebPkiClientVerifier::builder(Arc::new(client_auth_roots))
-                    .with_crls(crls)
-                    .build()
-                    .map_err(|e| {
-                        make_err!(
-                            Code::Internal,
-                            "Could not create WebPkiClientVerifier: {e:?}"
-                        )
-                    })?
+        };
+        if let Ok(Some(_)) = rustls_pemfile::read_one(&mut key_reader) {
+            return Err(make_err!(
+                Code::InvalidArgument,
+                "Expected 1 key in file {}",
+                tls_config.key_file
+            ));
+        }
+        let verifier = if let Some(client_ca_file) = &tls_config.client_ca_file {
+            let mut client_auth_roots = RootCertStore::empty();
+            for cert in read_cert(client_ca_file)?.into_iter() {
+                client_auth_roots
+                    .add(cert)
+                    .map_err(|e| make_err!(Code::Internal, "Could not read client CA: {e:?}"))?;
+            }
+            let crls = if let Some(client_crl_file) = &tls_config.client_crl_file {
+                let mut crl_reader = std::io::BufReader::new(
+                    std::fs::File::open(client_crl_file)
+                        .err_tip(|| format!("Could not open CRL file {client_crl_file}"))?,
+                );
+                extract_crls(&mut crl_reader)
+                    .map(|crl| crl.map(CertificateRevocationListDer::from))
+                    .collect::<Result<_, _>>()
+                    .err_tip(|| format!("Could not extract CRLs from file {client_crl_file}"))?
             } else {
-                WebPkiClientVerifier::no_client_auth()
+                Vec::new()
             };
-            let mut config = TlsServerConfig::builder()
-                .with_client_cert_verifier(verifier)
-                .with_single_cert(certs, key)
+            WebPkiClientVerifier::builder(Arc::new(client_auth_roots))
+                .with_crls(crls)
+                .build()
                 .map_err(|e| {
-                    make_err!(Code::Internal, "Could not create TlsServerConfig : {e:?}")
-                })?;
-
-            config.alpn_protocols.push("h2".into());
-            Ok(Some(TlsAcceptor::from(Arc::new(config))))
-        })?;
-
-        let socket_addr = http_config.socket_address.parse::<SocketAddr>()?;
-        let tcp_listener = TcpListener::bind(&socket_addr).await?;
-        let mut http = Http::new();
-        let http_config = &http_config.advanced_http;
-        if let Some(value) = http_config.http2_keep_alive_interval {
-            http.http2_keep_alive_interval(Duration::from_secs(u64::from(value)));
-        }
+                    make_err!(
+                        Code::Internal,
+                        "Could not create WebPkiClientVerifier: {e:?}"
+                    )
+                })?
+        } else {
+            WebPkiClientVerifier::no_client_auth()
+        };
+        let mut config = TlsServerConfig::builder()
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(certs, key)
+            .map_err(|e| make_err!(Code::Internal, "Could not create TlsServerConfig : {e:?}"))?;
 
-        if let Some(value) = http_config.experimental_http2_max_pending_accept_reset_streams {
-            http.http2_max_pending_accept_reset_streams(usize::try_from(value).err_tip(|| {
+        config.alpn_protocols.push("h2".into());
+        Ok(Some(TlsAcceptor::from(Arc::new(config))))
+    })?;
+
+    let maybe_authenticator = server_cfg
+        .auth
+        .as_ref()
+        .map(|auth_config| Authenticator::new(auth_config).map(Arc::new))
+        .transpose()
+        .err_tip(|| "Could not create authenticator")?;
+    // Authenticates each request before it reaches the services, using
+    // the common name of the client certificate of the connection.
+    let make_authenticated_svc = move |peer_common_name: Option<String>| {
+        let maybe_authenticator = maybe_authenticator.clone();
+        svc.clone()
+            .map_request(move |mut request: hyper::Request<hyper::Body>| {
+                if let Some(authenticator) = &maybe_authenticator {
+                    let auth_context =
+                        authenticator.authenticate(request.headers(), peer_common_name.as_deref());
+                    request.extensions_mut().insert(auth_context);
+                }
+                request
+            })
+    };
+
+    let socket_addr = http_config.socket_address.parse::<SocketAddr>()?;
+    let tcp_listener = TcpListener::bind(&socket_addr).await?;
+    let mut http = Http::new();
+    let http_config = &http_config.advanced_http;
+    if let Some(value) = http_config.http2_keep_alive_interval {
+        http.http2_keep_alive_interval(Duration::from_secs(u64::from(value)));
+    }
+
+    if let Some(value) = http_config.experimental_http2_max_pending_accept_reset_streams {
+        http.http2_max_pending_accept_reset_streams(
+            usize::try_from(value).err_tip(|| {
                 "Could not convert experimental_http2_max_pending_accept_reset_streams"
-            })?);
-        }
-        if let Some(value) = http_config.experimental_http2_initial_stream_window_size {
-            http.http2_initial_stream_window_size(value);
-        }
-        if let Some(value) = http_config.experimental_http2_initial_connection_window_size {
-            http.http2_initial_connection_window_size(value);
-        }
-        if let Some(value) = http_config.experimental_http2_adaptive_window {
-            http.http2_adaptive_window(value);
-        }
-        if let Some(value) = http_config.experimental_http2_max_frame_size {
-            http.http2_max_frame_size(value);
-        }
-        if let Some(value) = http_config.experimental_http2_max_concurrent_streams {
-            http.http2_max_concurrent_streams(value);
-        }
-        if let Some(value) = http_config.experimental_http2_keep_alive_timeout {
-            http.http2_keep_alive_timeout(Duration::from_secs(u64::from(value)));
-        }
-        if let Some(value) = http_config.experimental_http2_max_send_buf_size {
-            http.http2_max_send_buf_size(
-                usize::try_from(value).err_tip(|| "Could not convert http2_max_send_buf_size")?,
-            );
-        }
-        if let Some(true) = http_config.experimental_http2_enable_connect_protocol {
-            http.http2_enable_connect_protocol();
-        }
-        if let Some(value) = http_config.experimental_http2_max_header_list_size {
-            http.http2_max_header_list_size(value);
-        }
+            })?,
+        );
+    }
+    if let Some(value) = http_config.experimental_http2_initial_stream_window_size {
+        http.http2_initial_stream_window_size(value);
+    }
+    if let Some(value) = http_config.experimental_http2_initial_connection_window_size {
+        http.http2_initial_connection_window_size(value);
+    }
+    if let Some(value) = http_config.experimental_http2_adaptive_window {
+        http.http2_adaptive_window(value);
+    }
+    if let Some(value) = http_config.experimental_http2_max_frame_size {
+        http.http2_max_frame_size(value);
+    }
+    if let Some(value) = http_config.experimental_http2_max_concurrent_streams {
+        http.http2_max_concurrent_streams(value);
+    }
+    if let Some(value) = http_config.experimental_http2_keep_alive_timeout {
+        http.http2_keep_alive_timeout(Duration::from_secs(u64::from(value)));
+    }
+    if let Some(value) = http_config.experimental_http2_max_send_buf_size {
+        http.http2_max_send_buf_size(
+            usize::try_from(value).err_tip(|| "Could not convert http2_max_send_buf_size")?,
+        );
+    }
+    if let Some(true) = http_config.experimental_http2_enable_connect_protocol {
+        http.http2_enable_connect_protocol();
+    }
+    if let Some(value) = http_config.experimental_http2_max_header_list_size {
+        http.http2_max_header_list_size(value);
+    }
+
+    warn!("Ready, listening on {}", socket_addr);
+    Ok(Box::pin(async move {
+        loop {
+            // Wait for client to connect.
+            let (tcp_stream, remote_addr) = match tcp_listener.accept().await {
+                Ok(result) => result,
+                Err(e) => {
+                    error!(
+                        "{:?}",
+                        Result::<(), _>::Err(e).err_tip(|| "Failed to accept tcp connection")
+                    );
+                    continue;
+                }
+            };
+            connected_clients_mux.inner.lock().insert(remote_addr);
+            connected_clients_mux.counter.inc();
 
-        warn!("Ready, listening on {}", socket_addr);
-        root_futures.push(Box::pin(async move {
-            loop {
-                // Wait for client to connect.
-                let (tcp_stream, remote_addr) = match tcp_listener.accept().await {
+            // This is the safest way to guarantee that if our future
+            // is ever dropped we will cleanup our data.
+            let scope_guard = guard(
+                connected_clients_mux.clone(),
+                move |connected_clients_mux| {
+                    connected_clients_mux.inner.lock().remove(&remote_addr);
+                },
+            );
+            let http = http.clone();
+            let fut = if let Some(tls_acceptor) = &maybe_tls_acceptor {
+                let tls_stream = match tls_acceptor.accept(tcp_stream).await {
                     Ok(result) => result,
                     Err(e) => {
                         error!(
                             "{:?}",
-                            Result::<(), _>::Err(e).err_tip(|| "Failed to accept tcp connection")
+                            Result::<(), _>::Err(e).err_tip(|| "Failed to accept tls stream")
                         );
                         continue;
                     }
                 };
-                connected_clients_mux.inner.lock().insert(remote_addr);
-                connected_clients_mux.counter.inc();
-
-                // This is the safest way to guarantee that if our future
-                // is ever dropped we will cleanup our data.
-                let scope_guard = guard(
-                    connected_clients_mux.clone(),
-                    move |connected_clients_mux| {
-                        connected_clients_mux.inner.lock().remove(&remote_addr);
-                    },
-                );
-                let (http, svc) = (http.clone(), svc.clone());
-                let fut = if let Some(tls_acceptor) = &maybe_tls_acceptor {
-                    let tls_stream = match tls_acceptor.accept(tcp_stream).await {
-                        Ok(result) => result,
-                        Err(e) => {
-                            error!(
-                                "{:?}",
-                                Result::<(), _>::Err(e).err_tip(|| "Failed to accept tls stream")
-                            );
-                            continue;
-                        }
-                    };
-                    http.serve_connection(tls_stream, svc).left_future()
-                } else {
-                    http.serve_connection(tcp_stream, svc).right_future()
-                };
-                tokio::spawn(async move {
-                    // Move it into our spawn, so if our spawn dies the cleanup happens.
-                    let _guard = scope_guard;
-                    if let Err(e) = fut.await {
-                        error!("Failed running service : {:?}", e);
-                    }
-                });
+                let peer_common_name = tls_stream
+                    .get_ref()
+                    .1
+                    .peer_certificates()
+                    .and_then(|certificates| certificates.first())
+                    .and_then(|certificate| certificate_common_name(certificate));
+                http.serve_connection(tls_stream, make_authenticated_svc(peer_common_name))
+                    .left_future()
+            } else {
+                http.serve_connection(tcp_stream, make_authenticated_svc(None))
+                    .right_future()
+            };
+            tokio::spawn(async move {
+                // Move it into our spawn, so if our spawn dies the cleanup happens.
+                let _guard = scope_guard;
+                if let Err(e) = fut.await {
+                    error!("Failed running service : {:?}", e);
+                }
+            });
+        }
+    }))
+}
+
+async fn inner_main(
+    cfg: CasConfig,
+    cfg_json: serde_json::Value,
+    config_file: String,
+    log_filter: LogFilterHandle,
+    server_start_timestamp: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut root_metrics_registry = <Registry>::with_prefix("nativelink");
+    let health_registry_builder = Arc::new(AsyncMutex::new(HealthRegistryBuilder::new(
+        "nativelink".into(),
+    )));
+
+    let store_manager = Arc::new(StoreManager::new());
+    {
+        let mut health_registry_lock = health_registry_builder.lock().await;
+        let root_store_metrics = root_metrics_registry.sub_registry_with_prefix("stores");
+
+        for (name, store_cfg) in cfg.stores {
+            let health_component_name = format!("stores/{name}");
+            let mut health_register_store =
+                health_registry_lock.sub_builder(health_component_name.into());
+            let store_metrics = root_store_metrics.sub_registry_with_prefix(&name);
+            store_manager.add_store(
+                &name,
+                store_factory(
+                    &store_cfg,
+                    &store_manager,
+                    Some(store_metrics),
+                    Some(&mut health_register_store),
+                )
+                .await
+                .err_tip(|| format!("Failed to create store '{name}'"))?,
+            );
+        }
+    }
+
+    let mut action_schedulers = HashMap::new();
+    let mut worker_schedulers = HashMap::new();
+    if let Some(schedulers_cfg) = cfg.schedulers {
+        let root_scheduler_metrics = root_metrics_registry.sub_registry_with_prefix("schedulers");
+        let mut health_registry_lock = health_registry_builder.lock().await;
+        for (name, scheduler_cfg) in schedulers_cfg {
+            let scheduler_metrics = root_scheduler_metrics.sub_registry_with_prefix(&name);
+            let mut health_register_scheduler =
+                health_registry_lock.sub_builder(format!("schedulers/{name}").into());
+            let (maybe_action_scheduler, maybe_worker_scheduler) = scheduler_factory(
+                &scheduler_cfg,
+                &store_manager,
+                scheduler_metrics,
+                Some(&mut health_register_scheduler),
+            )
+            .err_tip(|| format!("Failed to create scheduler '{name}'"))?;
+            if let Some(action_scheduler) = maybe_action_scheduler {
+                action_schedulers.insert(name.clone(), action_scheduler);
+            }
+            if let Some(worker_scheduler) = maybe_worker_scheduler {
+                worker_schedulers.insert(name.clone(), worker_scheduler);
+            }
+        }
+    }
+
+    // Registers all the ConnectedClientsMetrics to the registries
+    // and zips them in. It is done this way to get around the need
+    // for `root_metrics_registry` to become immutable in the loop.
+    let servers_and_clients: Vec<(ServerConfig, _)> = cfg
+        .servers
+        .into_iter()
+        .enumerate()
+        .map(|(i, server_cfg)| {
+            let connected_clients_mux = register_server_metrics(
+                &mut root_metrics_registry,
+                i,
+                &server_cfg,
+                server_start_timestamp,
+            );
+            (server_cfg, connected_clients_mux)
+        })
+        .collect();
+
+    let mut root_futures: Vec<BoxFuture<Result<(), Error>>> = Vec::new();
+
+    // Lock our registry as immutable and clonable.
+    let root_metrics_registry = Arc::new(AsyncMutex::new(root_metrics_registry));
+    let config_reloader = Arc::new(ConfigReloader {
+        config_file,
+        log_filter,
+        server_start_timestamp,
+        state: AsyncMutex::new(ReloadState {
+            config: cfg_json,
+            next_server_index: servers_and_clients.len(),
+            ctx: ServerContext {
+                store_manager: store_manager.clone(),
+                action_schedulers,
+                worker_schedulers,
+                health_registry_builder,
+                root_metrics_registry: root_metrics_registry.clone(),
+                ac_cache_stats: Arc::new(AcCacheStats::default()),
+            },
+        }),
+    });
+    {
+        let state = config_reloader.state.lock().await;
+        for (server_cfg, connected_clients_mux) in servers_and_clients {
+            root_futures.push(
+                start_server(
+                    server_cfg,
+                    connected_clients_mux,
+                    &state.ctx,
+                    &config_reloader,
+                )
+                .await?,
+            );
+        }
+    }
+
+    #[cfg(target_family = "unix")]
+    {
+        let mut sighup = signal(SignalKind::hangup()).err_tip(|| "Failed to listen to SIGHUP")?;
+        let config_reloader = config_reloader.clone();
+        tokio::spawn(async move {
+            while sighup.recv().await.is_some() {
+                match config_reloader.reload().await {
+                    Ok(applied) => info!("Reloaded config: {applied:?}"),
+                    Err(e) => error!("Could not reload config: {e:?}"),
+                }
             }
-        }));
+        });
     }
 
     {
@@ -819,43 +1641,35 @@ async fn inner_main(
     unreachable!("None of the futures should resolve in main()");
 }
 
-async fn get_config() -> Result<CasConfig, Box<dyn std::error::Error>> {
-    let args = Args::parse();
-    let json_contents = String::from_utf8(
-        std::fs::read(&args.config_file)
-            .err_tip(|| format!("Could not open config file {}", args.config_file))?,
-    )?;
-    Ok(serde_json5::from_str(&json_contents)?)
-}
-
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     use tracing_subscriber::prelude::*;
 
-    let env_filter = EnvFilter::builder()
-        .with_default_directive(LevelFilter::WARN.into())
-        .from_env_lossy();
-
+    // The filter is replaced once the config is read, see `set_log_level()`.
+    let (log_filter, log_filter_handle) = reload::Layer::new(make_log_filter("")?);
+    let fmt_layer = tracing_subscriber::fmt::layer()
+        .pretty()
+        .with_thread_ids(true)
+        .with_thread_names(true)
+        .with_filter(log_filter);
     if cfg!(feature = "enable_tokio_console") {
         tracing_subscriber::registry()
+            .with(fmt_layer)
             .with(console_subscriber::spawn())
-            .with(
-                tracing_subscriber::fmt::layer()
-                    .pretty()
-                    .with_thread_ids(true)
-                    .with_thread_names(true)
-                    .with_filter(env_filter),
-            )
             .init();
     } else {
-        tracing_subscriber::fmt()
-            .pretty()
-            .with_thread_ids(true)
-            .with_thread_names(true)
-            .with_env_filter(env_filter)
-            .init();
+        tracing_subscriber::registry().with(fmt_layer).init();
     }
 
-    let mut cfg = futures::executor::block_on(get_config())?;
+    let args = Args::parse();
+    if let Some(Command::Validate { config_file }) = args.command {
+        return validate_config(&config_file);
+    }
+    let config_file = args.config_file.err_tip(|| "Config file must be set")?;
+    let (mut cfg, cfg_json) = read_config(&config_file)?;
+    set_log_level(
+        &log_filter_handle,
+        cfg.global.as_ref().map_or("", |global| &global.log_level),
+    )?;
 
     let (mut metrics_enabled, max_blocking_threads) = {
         // Note: If the default changes make sure you update the documentation in
@@ -876,7 +1690,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 global_cfg.default_digest_size_health_check = DEFAULT_DIGEST_SIZE_HEALTH_CHECK_CFG;
             }
 
-            *global_cfg
+            global_cfg.clone()
         } else {
             GlobalConfig {
                 max_open_files: DEFAULT_MAX_OPEN_FILES,
@@ -889,6 +1703,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }),
                 default_digest_hash_function: None,
                 default_digest_size_health_check: DEFAULT_DIGEST_SIZE_HEALTH_CHECK_CFG,
+                log_level: String::new(),
             }
         };
         set_open_file_limit(global_cfg.max_open_files);
@@ -923,6 +1738,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             .await
             .expect("Failed to listen to SIGINT");
         eprintln!("User terminated process via SIGINT");
+        run_shutdown_hooks().await;
         std::process::exit(130);
     });
 
@@ -933,8 +1749,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             .recv()
             .await;
         eprintln!("Process terminated via SIGTERM");
+        run_shutdown_hooks().await;
         std::process::exit(143);
     });
 
-    runtime.block_on(inner_main(cfg, server_start_time))
+    runtime.block_on(inner_main(
+        cfg,
+        cfg_json,
+        config_file,
+        log_filter_handle,
+        server_start_time,
+    ))
 }
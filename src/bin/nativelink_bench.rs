@@ -0,0 +1,488 @@
+// Copyright 2024 The NativeLink Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use clap::{Parser, ValueEnum};
+use futures::future::try_join_all;
+use futures::join;
+use mimalloc::MiMalloc;
+use nativelink_config::cas_server::CasConfig;
+use nativelink_config::stores::ConfigDigestHashFunction;
+use nativelink_error::{make_input_err, Error, ResultExt};
+use nativelink_store::default_store_factory::store_factory;
+use nativelink_store::store_manager::StoreManager;
+use nativelink_util::buf_channel::make_buf_channel_pair;
+use nativelink_util::common::fs::set_open_file_limit;
+use nativelink_util::common::DigestInfo;
+use nativelink_util::digest_hasher::{
+    default_digest_hasher_func, set_default_digest_hasher_func, DigestHasher, DigestHasherFunc,
+};
+use nativelink_util::store_trait::{Store, UploadSizeInfo};
+use parking_lot::Mutex;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+
+#[global_allocator]
+static GLOBAL: MiMalloc = MiMalloc;
+
+/// Note: Keep in sync with `DEFAULT_MAX_OPEN_FILES` in `nativelink.rs`.
+const DEFAULT_MAX_OPEN_FILES: usize = 512;
+
+/// Number of blobs uploaded before the `find-missing` and `mixed` workloads
+/// start so that lookups and reads have something to hit.
+const DEFAULT_PRELOAD_COUNT: usize = 1000;
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum Workload {
+    /// Large `has_with_results()` batches against a mix of known and unknown
+    /// digests, similar to what a `FindMissingBlobs` storm looks like.
+    FindMissing,
+
+    /// A mix of `get()` of previously uploaded blobs and `update()` of new
+    /// blobs, with sizes drawn from `--size-distribution`.
+    Mixed,
+
+    /// Streams `--upload-size` bytes per operation in `--chunk-size` chunks.
+    Upload,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum SizeDistribution {
+    /// Every blob is `--max-blob-size` bytes.
+    Fixed,
+
+    /// Sizes are uniformly distributed between the min and max size.
+    Uniform,
+
+    /// Sizes are uniformly distributed on a log scale between the min and max
+    /// size. This closely approximates the size distribution of build
+    /// artifacts, where most blobs are small and a few are very large.
+    LogUniform,
+}
+
+/// Benchmark and load generation tool for stores defined in a NativeLink config.
+#[derive(Parser, Debug)]
+#[clap(
+    author = "Trace Machina, Inc. <nativelink@tracemachina.com>",
+    version,
+    about,
+    long_about = None
+)]
+struct Args {
+    /// Config file to load the stores from.
+    #[clap(value_parser)]
+    config_file: String,
+
+    /// Name of the store in the config's `stores` map to run against.
+    #[clap(long)]
+    store: String,
+
+    /// Workload to run against the store.
+    #[clap(long, value_enum)]
+    workload: Workload,
+
+    /// Total number of operations to run.
+    #[clap(long, default_value_t = 10_000)]
+    operations: usize,
+
+    /// Number of operations in flight at any given time.
+    #[clap(long, default_value_t = 64)]
+    concurrency: usize,
+
+    /// Distribution used to pick blob sizes.
+    #[clap(long, value_enum, default_value_t = SizeDistribution::LogUniform)]
+    size_distribution: SizeDistribution,
+
+    /// Smallest blob size in bytes.
+    #[clap(long, default_value_t = 128)]
+    min_blob_size: usize,
+
+    /// Largest blob size in bytes.
+    #[clap(long, default_value_t = 4 * 1024 * 1024)]
+    max_blob_size: usize,
+
+    /// Fraction of `mixed` operations that are reads.
+    #[clap(long, default_value_t = 0.8)]
+    read_ratio: f64,
+
+    /// Number of digests per `find-missing` batch.
+    #[clap(long, default_value_t = 1000)]
+    batch_size: usize,
+
+    /// Fraction of the digests in a `find-missing` batch that exist.
+    #[clap(long, default_value_t = 0.9)]
+    hit_ratio: f64,
+
+    /// Number of blobs to upload before the benchmark starts.
+    #[clap(long, default_value_t = DEFAULT_PRELOAD_COUNT)]
+    preload: usize,
+
+    /// Bytes uploaded per `upload` operation.
+    #[clap(long, default_value_t = 1024 * 1024 * 1024)]
+    upload_size: usize,
+
+    /// Chunk size used when streaming an `upload` operation.
+    #[clap(long, default_value_t = 64 * 1024)]
+    chunk_size: usize,
+
+    /// Seed for the random number generator, used to make runs repeatable.
+    #[clap(long, default_value_t = 0)]
+    seed: u64,
+}
+
+/// Latency and throughput numbers gathered by a single benchmark task.
+#[derive(Default)]
+struct Recorder {
+    latencies: Vec<Duration>,
+    errors: usize,
+}
+
+impl Recorder {
+    fn record<T>(&mut self, start: Instant, result: Result<T, Error>) {
+        self.latencies.push(start.elapsed());
+        if let Err(e) = result {
+            if self.errors == 0 {
+                eprintln!("First error: {e:?}");
+            }
+            self.errors += 1;
+        }
+    }
+
+    fn merge(&mut self, other: Recorder) {
+        self.latencies.extend(other.latencies);
+        self.errors += other.errors;
+    }
+}
+
+struct BenchState {
+    args: Args,
+    store: Arc<dyn Store>,
+    hasher_func: DigestHasherFunc,
+    next_operation: AtomicUsize,
+    bytes_transferred: AtomicU64,
+    known_digests: Mutex<Vec<DigestInfo>>,
+}
+
+impl BenchState {
+    fn pin_store(&self) -> Pin<&dyn Store> {
+        Pin::new(self.store.as_ref())
+    }
+
+    fn pick_size(&self, rng: &mut StdRng) -> usize {
+        let min = self.args.min_blob_size.max(1);
+        let max = self.args.max_blob_size.max(min);
+        match self.args.size_distribution {
+            SizeDistribution::Fixed => max,
+            SizeDistribution::Uniform => rng.gen_range(min..=max),
+            SizeDistribution::LogUniform => {
+                let exp = rng.gen_range((min as f64).ln()..=(max as f64).ln());
+                (exp.exp() as usize).clamp(min, max)
+            }
+        }
+    }
+
+    fn make_blob(&self, rng: &mut StdRng, size: usize) -> (DigestInfo, Bytes) {
+        let mut data = vec![0u8; size];
+        rng.fill_bytes(&mut data);
+        let mut hasher = self.hasher_func.hasher();
+        hasher.update(&data);
+        (hasher.finalize_digest(), Bytes::from(data))
+    }
+
+    fn random_known_digest(&self, rng: &mut StdRng) -> Option<DigestInfo> {
+        let known_digests = self.known_digests.lock();
+        if known_digests.is_empty() {
+            return None;
+        }
+        Some(known_digests[rng.gen_range(0..known_digests.len())])
+    }
+
+    async fn upload_blob(&self, rng: &mut StdRng) -> Result<(), Error> {
+        let size = self.pick_size(rng);
+        let (digest, data) = self.make_blob(rng, size);
+        self.pin_store()
+            .update_oneshot(digest, data)
+            .await
+            .err_tip(|| "In BenchState::upload_blob")?;
+        self.bytes_transferred
+            .fetch_add(size as u64, Ordering::Relaxed);
+        self.known_digests.lock().push(digest);
+        Ok(())
+    }
+
+    async fn read_blob(&self, digest: DigestInfo) -> Result<(), Error> {
+        let (tx, mut rx) = make_buf_channel_pair();
+        let (get_res, drain_res) = join!(self.pin_store().get(digest, tx), rx.drain());
+        get_res
+            .merge(drain_res)
+            .err_tip(|| "In BenchState::read_blob")?;
+        self.bytes_transferred
+            .fetch_add(digest.size_bytes as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn find_missing(&self, rng: &mut StdRng) -> Result<(), Error> {
+        let digests: Vec<DigestInfo> = (0..self.args.batch_size)
+            .map(|_| {
+                if rng.gen_bool(self.args.hit_ratio.clamp(0.0, 1.0)) {
+                    if let Some(digest) = self.random_known_digest(rng) {
+                        return digest;
+                    }
+                }
+                let mut hash = [0u8; 32];
+                rng.fill_bytes(&mut hash);
+                DigestInfo::new(hash, rng.gen_range(1..=self.args.max_blob_size as i64))
+            })
+            .collect();
+        let mut results = vec![None; digests.len()];
+        self.pin_store()
+            .has_with_results(&digests, &mut results)
+            .await
+            .err_tip(|| "In BenchState::find_missing")
+    }
+
+    /// Streams a large blob into the store. The data is generated twice from
+    /// the same seed, once to compute the digest and once while uploading, so
+    /// we never need to hold the whole blob in memory.
+    async fn streaming_upload(&self, seed: u64) -> Result<(), Error> {
+        let chunk_size = self.args.chunk_size.max(1);
+        let upload_size = self.args.upload_size;
+        let generate_chunks = move || {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut remaining = upload_size;
+            std::iter::from_fn(move || {
+                if remaining == 0 {
+                    return None;
+                }
+                let mut chunk = vec![0u8; remaining.min(chunk_size)];
+                rng.fill_bytes(&mut chunk);
+                remaining -= chunk.len();
+                Some(Bytes::from(chunk))
+            })
+        };
+        let digest = {
+            let mut hasher = self.hasher_func.hasher();
+            generate_chunks().for_each(|chunk| hasher.update(&chunk));
+            hasher.finalize_digest()
+        };
+
+        let (mut tx, rx) = make_buf_channel_pair();
+        let send_fut = async move {
+            for chunk in generate_chunks() {
+                tx.send(chunk)
+                    .await
+                    .err_tip(|| "Failed to send chunk in streaming_upload")?;
+            }
+            tx.send_eof()
+                .await
+                .err_tip(|| "Failed to send EOF in streaming_upload")
+        };
+        let (send_res, update_res) = join!(
+            send_fut,
+            self.pin_store()
+                .update(digest, rx, UploadSizeInfo::ExactSize(upload_size))
+        );
+        update_res
+            .merge(send_res)
+            .err_tip(|| "In BenchState::streaming_upload")?;
+        self.bytes_transferred
+            .fetch_add(upload_size as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn run_task(self: Arc<Self>, task_id: usize) -> Result<Recorder, Error> {
+        let mut rng = StdRng::seed_from_u64(self.args.seed.wrapping_add(task_id as u64));
+        let mut recorder = Recorder::default();
+        loop {
+            let operation = self.next_operation.fetch_add(1, Ordering::Relaxed);
+            if operation >= self.args.operations {
+                return Ok(recorder);
+            }
+            let start = Instant::now();
+            match self.args.workload {
+                Workload::FindMissing => {
+                    let result = self.find_missing(&mut rng).await;
+                    recorder.record(start, result);
+                }
+                Workload::Mixed => {
+                    let maybe_digest = if rng.gen_bool(self.args.read_ratio.clamp(0.0, 1.0)) {
+                        self.random_known_digest(&mut rng)
+                    } else {
+                        None
+                    };
+                    let result = if let Some(digest) = maybe_digest {
+                        self.read_blob(digest).await
+                    } else {
+                        self.upload_blob(&mut rng).await
+                    };
+                    recorder.record(start, result);
+                }
+                Workload::Upload => {
+                    let seed = self.args.seed.wrapping_add(operation as u64);
+                    let result = self.streaming_upload(seed).await;
+                    recorder.record(start, result);
+                }
+            }
+        }
+    }
+}
+
+fn print_report(state: &BenchState, mut recorder: Recorder, elapsed: Duration) {
+    recorder.latencies.sort_unstable();
+    let operations = recorder.latencies.len();
+    let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+    let bytes = state.bytes_transferred.load(Ordering::Relaxed);
+    println!("Workload:   {:?}", state.args.workload);
+    println!("Store:      {}", state.args.store);
+    println!("Operations: {operations} ({} errors)", recorder.errors);
+    println!("Elapsed:    {elapsed:.3?}");
+    println!("Throughput: {:.1} ops/s", operations as f64 / secs);
+    println!(
+        "Bandwidth:  {:.1} MiB/s ({bytes} bytes)",
+        bytes as f64 / secs / (1024.0 * 1024.0)
+    );
+    if operations == 0 {
+        return;
+    }
+
+    println!("Latency:");
+    for quantile in [0.50, 0.90, 0.99, 0.999] {
+        let index = ((quantile * operations as f64) as usize).min(operations - 1);
+        println!(
+            "  p{:<5} {:.3?}",
+            quantile * 100.0,
+            recorder.latencies[index]
+        );
+    }
+    println!("  max    {:.3?}", recorder.latencies[operations - 1]);
+
+    // Power of two buckets in microseconds, which keeps the histogram short
+    // while still showing the shape of the tail.
+    println!("Histogram:");
+    let mut bucket_upper_us: u128 = 1;
+    let mut latencies = recorder.latencies.iter().peekable();
+    while latencies.peek().is_some() {
+        let mut count = 0;
+        while latencies
+            .next_if(|latency| latency.as_micros() < bucket_upper_us)
+            .is_some()
+        {
+            count += 1;
+        }
+        if count > 0 {
+            let percent = count as f64 * 100.0 / operations as f64;
+            println!(
+                "  < {:>12?} {count:>10} {percent:>6.2}% {}",
+                Duration::from_micros(bucket_upper_us as u64),
+                "#".repeat((percent / 2.0).ceil() as usize),
+            );
+        }
+        bucket_upper_us *= 2;
+    }
+}
+
+async fn inner_main(cfg: CasConfig, args: Args) -> Result<(), Error> {
+    let store_manager = Arc::new(StoreManager::new());
+    for (name, store_cfg) in cfg.stores {
+        let store = store_factory(&store_cfg, &store_manager, None, None)
+            .await
+            .err_tip(|| format!("Failed to create store '{name}'"))?;
+        store_manager.add_store(&name, store);
+    }
+    let store = store_manager
+        .get_store(&args.store)
+        .ok_or_else(|| make_input_err!("Store '{}' not found in config", args.store))?;
+
+    let state = Arc::new(BenchState {
+        store,
+        hasher_func: default_digest_hasher_func(),
+        next_operation: AtomicUsize::new(0),
+        bytes_transferred: AtomicU64::new(0),
+        known_digests: Mutex::new(Vec::new()),
+        args,
+    });
+
+    if matches!(state.args.workload, Workload::FindMissing | Workload::Mixed) {
+        println!("Preloading {} blobs...", state.args.preload);
+        let mut rng = StdRng::seed_from_u64(state.args.seed.wrapping_sub(1));
+        for _ in 0..state.args.preload {
+            state
+                .upload_blob(&mut rng)
+                .await
+                .err_tip(|| "While preloading store")?;
+        }
+        state.bytes_transferred.store(0, Ordering::Relaxed);
+    }
+
+    let start = Instant::now();
+    let recorders = try_join_all((0..state.args.concurrency.max(1)).map(|task_id| {
+        let state = state.clone();
+        async move {
+            tokio::spawn(state.run_task(task_id))
+                .await
+                .map_err(|e| make_input_err!("Benchmark task panicked: {e:?}"))?
+        }
+    }))
+    .await?;
+    let elapsed = start.elapsed();
+
+    let mut recorder = Recorder::default();
+    for task_recorder in recorders {
+        recorder.merge(task_recorder);
+    }
+    print_report(&state, recorder, elapsed);
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::WARN.into())
+                .from_env_lossy(),
+        )
+        .init();
+
+    let args = Args::parse();
+    let json_contents = String::from_utf8(
+        std::fs::read(&args.config_file)
+            .err_tip(|| format!("Could not open config file {}", args.config_file))?,
+    )?;
+    let cfg: CasConfig = serde_json5::from_str(&json_contents)?;
+
+    let global_cfg = cfg.global.as_ref();
+    set_open_file_limit(
+        global_cfg
+            .map(|global_cfg| global_cfg.max_open_files)
+            .filter(|max_open_files| *max_open_files != 0)
+            .unwrap_or(DEFAULT_MAX_OPEN_FILES),
+    );
+    set_default_digest_hasher_func(DigestHasherFunc::from(
+        global_cfg
+            .and_then(|global_cfg| global_cfg.default_digest_hash_function)
+            .unwrap_or(ConfigDigestHashFunction::sha256),
+    ))?;
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(inner_main(cfg, args))?;
+    Ok(())
+}